@@ -0,0 +1,47 @@
+//! benches/cpu_pool.rs
+//!
+//! Measures Argon2 throughput during a login burst, with the hashes run on the `CpuPool` and
+//! directly on Tokio's blocking threads, to check the pool's default size and queue depth.
+//!
+//! Run with `cargo bench --bench cpu_pool`.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use futures::future::join_all;
+use gameshop::cpu_pool::CpuPool;
+use gameshop::hashing::hash_random_salt;
+use std::time::Duration;
+
+/// The number of concurrent logins in a burst, the pool's default queue depth.
+const BURST: usize = 64;
+
+fn hash() {
+    hash_random_salt("correct horse battery staple").unwrap();
+}
+
+fn argon2_burst(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+
+    let mut group = c.benchmark_group("argon2_burst");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(20))
+        .throughput(Throughput::Elements(BURST as u64));
+
+    group.bench_function("spawn_blocking", |b| {
+        b.to_async(&runtime).iter(|| async {
+            join_all((0..BURST).map(|_| tokio::task::spawn_blocking(hash))).await
+        })
+    });
+    for size in [cpus, 2 * cpus] {
+        let pool = CpuPool::new(size, BURST);
+        group.bench_with_input(BenchmarkId::new("pool", size), &pool, |b, pool| {
+            b.to_async(&runtime)
+                .iter(|| async { join_all((0..BURST).map(|_| pool.run(hash))).await })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, argon2_burst);
+criterion_main!(benches);
@@ -0,0 +1,230 @@
+//! src/config.rs
+//!
+//! This module provides the runtime configuration that can be reloaded without restarting the
+//! server, such as the log level, feature flags and maintenance mode.
+
+use crate::errors::custom_errors::CustomError;
+use dotenvy::var;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{Registry, reload};
+
+/// The longest reservation window that can be configured: one week.
+const MAX_RESERVATION_HOURS: u32 = 7 * 24;
+
+/// The rate limit applied to every client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RateLimit {
+    /// The number of seconds after which one more request is allowed.
+    pub seconds_per_request: u64,
+    /// The number of requests a client can make in a burst.
+    pub burst_size: u32,
+}
+
+/// The configuration that can change while the server is running.
+///
+/// It is read from the environment (and the `.env` file) at startup and on every reload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuntimeConfig {
+    /// The maximum level of logged events (`LOG_LEVEL`, e.g. `info`).
+    pub log_level: String,
+    /// Whether the shop is in maintenance mode, rejecting changes by non-admins (`MAINTENANCE_MODE`).
+    pub maintenance_mode: bool,
+    /// Whether failed logins and registrations hide whether the account exists (`AUTH_PRIVACY_MODE`).
+    pub auth_privacy_mode: bool,
+    /// Whether sellers are notified when their offers expire (`OFFER_EXPIRATION_NOTIFICATIONS`).
+    pub offer_expiration_notifications: bool,
+    /// How many hours a buyer can reserve an offer for while arranging the payment
+    /// (`RESERVATION_HOURS`).
+    pub reservation_hours: u32,
+    /// The enabled feature flags (`FEATURE_FLAGS`, comma-separated).
+    pub feature_flags: BTreeSet<String>,
+    /// The rate limit (`RATE_LIMIT_SECONDS_PER_REQUEST`, `RATE_LIMIT_BURST_SIZE`).
+    pub rate_limit: RateLimit,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            log_level: "info".to_string(),
+            maintenance_mode: false,
+            auth_privacy_mode: false,
+            offer_expiration_notifications: true,
+            reservation_hours: 24,
+            feature_flags: BTreeSet::new(),
+            rate_limit: RateLimit {
+                seconds_per_request: 1,
+                burst_size: 5,
+            },
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Reads the configuration from the environment. Unset variables keep their defaults.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the configuration or a `CustomError` naming the first invalid variable.
+    pub fn from_env() -> Result<Self, CustomError> {
+        let mut config = RuntimeConfig::default();
+        if let Ok(level) = var("LOG_LEVEL") {
+            let level = LevelFilter::from_str(level.trim())
+                .map_err(|_| invalid_variable("LOG_LEVEL", &level))?;
+            config.log_level = level.to_string().to_lowercase();
+        }
+        if let Ok(mode) = var("MAINTENANCE_MODE") {
+            config.maintenance_mode = bool::from_str(mode.trim())
+                .map_err(|_| invalid_variable("MAINTENANCE_MODE", &mode))?;
+        }
+        if let Ok(mode) = var("AUTH_PRIVACY_MODE") {
+            config.auth_privacy_mode = bool::from_str(mode.trim())
+                .map_err(|_| invalid_variable("AUTH_PRIVACY_MODE", &mode))?;
+        }
+        if let Ok(notify) = var("OFFER_EXPIRATION_NOTIFICATIONS") {
+            config.offer_expiration_notifications = bool::from_str(notify.trim())
+                .map_err(|_| invalid_variable("OFFER_EXPIRATION_NOTIFICATIONS", &notify))?;
+        }
+        if let Ok(hours) = var("RESERVATION_HOURS") {
+            config.reservation_hours = hours
+                .trim()
+                .parse()
+                .ok()
+                .filter(|hours| (1..=MAX_RESERVATION_HOURS).contains(hours))
+                .ok_or_else(|| invalid_variable("RESERVATION_HOURS", &hours))?;
+        }
+        if let Ok(flags) = var("FEATURE_FLAGS") {
+            config.feature_flags = flags
+                .split(',')
+                .map(|flag| flag.trim().to_string())
+                .filter(|flag| !flag.is_empty())
+                .collect();
+        }
+        if let Ok(seconds) = var("RATE_LIMIT_SECONDS_PER_REQUEST") {
+            config.rate_limit.seconds_per_request = seconds
+                .trim()
+                .parse()
+                .ok()
+                .filter(|seconds| *seconds > 0)
+                .ok_or_else(|| invalid_variable("RATE_LIMIT_SECONDS_PER_REQUEST", &seconds))?;
+        }
+        if let Ok(burst) = var("RATE_LIMIT_BURST_SIZE") {
+            config.rate_limit.burst_size = burst
+                .trim()
+                .parse()
+                .ok()
+                .filter(|burst| *burst > 0)
+                .ok_or_else(|| invalid_variable("RATE_LIMIT_BURST_SIZE", &burst))?;
+        }
+        Ok(config)
+    }
+
+    /// Returns the log level as a filter for the tracing subscriber.
+    pub fn level_filter(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.log_level).unwrap_or(LevelFilter::INFO)
+    }
+
+    /// Returns whether the given feature flag is enabled.
+    pub fn feature_enabled(&self, flag: &str) -> bool {
+        self.feature_flags.contains(flag)
+    }
+}
+
+fn invalid_variable(name: &str, value: &str) -> CustomError {
+    CustomError::EnvironmentVariableError(format!("Invalid value for {}: {}", name, value))
+}
+
+/// A shared handle to the current runtime configuration.
+///
+/// Cloned into `web::Data` so handlers and middleware always see the latest configuration.
+/// Background tasks can `subscribe` to be woken up on every reload.
+#[derive(Debug, Clone)]
+pub struct ConfigHandle {
+    sender: Arc<watch::Sender<RuntimeConfig>>,
+}
+
+impl ConfigHandle {
+    /// Creates a handle holding the given configuration.
+    pub fn new(config: RuntimeConfig) -> Self {
+        ConfigHandle {
+            sender: Arc::new(watch::Sender::new(config)),
+        }
+    }
+
+    /// Returns the current configuration. The returned guard must not be held across `.await`.
+    pub fn current(&self) -> watch::Ref<'_, RuntimeConfig> {
+        self.sender.borrow()
+    }
+
+    /// Returns a receiver that is notified whenever the configuration is reloaded.
+    pub fn subscribe(&self) -> watch::Receiver<RuntimeConfig> {
+        self.sender.subscribe()
+    }
+
+    /// Re-reads the `.env` file and the environment and publishes the new configuration.
+    ///
+    /// If the new configuration is invalid, the current one is kept.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new configuration or a `CustomError` if it is invalid.
+    pub fn reload(&self) -> Result<RuntimeConfig, CustomError> {
+        if let Err(e) = dotenvy::dotenv_override()
+            && !e.not_found()
+        {
+            return Err(e.into());
+        }
+        let config = RuntimeConfig::from_env()?;
+        tracing::info!("Reloaded runtime configuration: {:?}", config);
+        self.sender.send_replace(config.clone());
+        Ok(config)
+    }
+}
+
+/// Applies the log level of every reloaded configuration to the tracing subscriber.
+///
+/// # Arguments
+///
+/// * `config` - The configuration handle to watch.
+/// * `level` - The reload handle of the subscriber's level filter.
+pub fn watch_log_level(config: &ConfigHandle, level: reload::Handle<LevelFilter, Registry>) {
+    let mut changes = config.subscribe();
+    tokio::spawn(async move {
+        while changes.changed().await.is_ok() {
+            let filter = changes.borrow_and_update().level_filter();
+            if let Err(e) = level.modify(|current| *current = filter) {
+                tracing::error!("Failed to change log level: {}", e);
+            }
+        }
+    });
+}
+
+/// Reloads the configuration whenever the process receives `SIGHUP`.
+///
+/// # Arguments
+///
+/// * `config` - The configuration handle to reload.
+#[cfg(unix)]
+pub fn reload_on_sighup(config: ConfigHandle) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::error!("Failed to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            tracing::info!("Received SIGHUP, reloading configuration");
+            if let Err(e) = config.reload() {
+                tracing::error!("Failed to reload configuration: {}", e);
+            }
+        }
+    });
+}
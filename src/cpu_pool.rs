@@ -0,0 +1,183 @@
+//! src/cpu_pool.rs
+//!
+//! This module provides the bounded pool CPU-heavy work (Argon2 hashing, field encryption) runs
+//! on, so it never blocks the async executor. Jobs run on Tokio's blocking threads, but at most
+//! `CPU_POOL_SIZE` at a time; callers beyond that wait in a queue, and once the queue is full new
+//! jobs are rejected with `CustomError::Overloaded` instead of piling up behind a login burst.
+
+use crate::errors::custom_errors::CustomError;
+use crate::metrics::PendingJobs;
+use dotenvy::var;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tokio::sync::Semaphore;
+
+/// The default number of waiting jobs before new jobs are rejected.
+const DEFAULT_MAX_QUEUE_DEPTH: usize = 64;
+
+/// A snapshot of the pool's metrics.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct CpuPoolMetrics {
+    /// The number of jobs that may run at the same time.
+    pub size: usize,
+    /// The number of waiting jobs at which new jobs are rejected.
+    pub max_queue_depth: usize,
+    /// The number of jobs currently running.
+    pub running: usize,
+    /// The number of jobs currently waiting for a free slot.
+    pub queued: usize,
+    /// The number of jobs completed since the server started, including failed ones.
+    pub completed: u64,
+    /// The number of jobs that panicked since the server started.
+    pub failed: u64,
+    /// The number of jobs rejected because the queue was full since the server started.
+    pub rejected: u64,
+    /// The time completed jobs spent running, in microseconds.
+    pub total_run_time_micros: u64,
+    /// How long the oldest waiting job has been waiting, in microseconds.
+    pub oldest_queued_micros: u64,
+}
+
+/// A bounded pool for CPU-heavy work.
+#[derive(Debug)]
+pub struct CpuPool {
+    permits: Arc<Semaphore>,
+    size: usize,
+    max_queue_depth: usize,
+    running: AtomicUsize,
+    queued: AtomicUsize,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    rejected: AtomicU64,
+    run_time_micros: AtomicU64,
+    pending: PendingJobs,
+}
+
+impl CpuPool {
+    /// Creates a pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The number of jobs that may run at the same time (at least 1).
+    /// * `max_queue_depth` - The number of waiting jobs at which new jobs are rejected.
+    pub fn new(size: usize, max_queue_depth: usize) -> Self {
+        let size = size.max(1);
+        CpuPool {
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+            max_queue_depth,
+            running: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            run_time_micros: AtomicU64::new(0),
+            pending: PendingJobs::default(),
+        }
+    }
+
+    /// Returns the shared pool, sized by `CPU_POOL_SIZE` (defaults to the number of CPUs) and
+    /// `CPU_POOL_MAX_QUEUE_DEPTH` (defaults to 64).
+    pub fn global() -> &'static CpuPool {
+        static POOL: OnceLock<CpuPool> = OnceLock::new();
+        POOL.get_or_init(|| {
+            let size = var("CPU_POOL_SIZE")
+                .ok()
+                .and_then(|size| size.trim().parse().ok())
+                .unwrap_or_else(|| {
+                    std::thread::available_parallelism().map_or(1, |cpus| cpus.get())
+                });
+            let max_queue_depth = var("CPU_POOL_MAX_QUEUE_DEPTH")
+                .ok()
+                .and_then(|depth| depth.trim().parse().ok())
+                .unwrap_or(DEFAULT_MAX_QUEUE_DEPTH);
+            CpuPool::new(size, max_queue_depth)
+        })
+    }
+
+    /// Runs a job on the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - The CPU-heavy work to run.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the job's result, or `CustomError::Overloaded` if the queue is full.
+    /// A job that panics is reported as `CustomError::Unknown`.
+    pub async fn run<T, F>(&self, job: F) -> Result<T, CustomError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let waiting = self.queued.fetch_add(1, Ordering::SeqCst);
+        let queued = Gauge(&self.queued);
+        if waiting >= self.max_queue_depth {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!("CPU pool queue is full, rejecting job");
+            return Err(CustomError::Overloaded);
+        }
+        let pending = self.pending.enqueue();
+        // The semaphore is never closed
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| CustomError::Unknown)?;
+        drop(pending);
+        drop(queued);
+
+        self.running.fetch_add(1, Ordering::SeqCst);
+        let _running = Gauge(&self.running);
+        let started = Instant::now();
+        // The permit moves into the job, so a cancelled caller doesn't free its slot while the
+        // job is still running
+        let result = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            job()
+        })
+        .await;
+        self.run_time_micros.fetch_add(
+            started.elapsed().as_micros().try_into().unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        result.map_err(|e| {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+            tracing::error!("CPU pool job failed: {}", e);
+            CustomError::Unknown
+        })
+    }
+
+    /// Returns a snapshot of the pool's metrics.
+    pub fn metrics(&self) -> CpuPoolMetrics {
+        CpuPoolMetrics {
+            size: self.size,
+            max_queue_depth: self.max_queue_depth,
+            running: self.running.load(Ordering::SeqCst),
+            queued: self.queued.load(Ordering::SeqCst),
+            completed: self.completed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            total_run_time_micros: self.run_time_micros.load(Ordering::Relaxed),
+            oldest_queued_micros: self
+                .pending
+                .oldest_age()
+                .as_micros()
+                .try_into()
+                .unwrap_or(u64::MAX),
+        }
+    }
+}
+
+/// Decrements a gauge when dropped, so it stays correct if a waiting caller is cancelled.
+struct Gauge<'a>(&'a AtomicUsize);
+
+impl Drop for Gauge<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,216 @@
+//! src/database/account_deletion.rs
+//!
+//! This module handles the soft deletion of user accounts. Deleted accounts can no longer log in
+//! and their offers disappear from the listings, but an admin can restore them until the grace
+//! period has passed. After that, the anonymization job replaces their personal data, unless the
+//! account is under a legal hold.
+
+use super::blind_index::blind_index;
+use super::ids::UserId;
+use super::legal_holds::LegalHoldTarget;
+use super::{Database, User, encrypt_fields_blocking, hash_email, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+
+/// The number of days a deleted account can be restored before the retention jobs may purge it.
+pub const ACCOUNT_DELETION_GRACE_DAYS: i64 = 30;
+
+/// The first name anonymized accounts are given.
+const ANONYMIZED_FIRSTNAME: &str = "Deleted";
+
+/// The last name anonymized accounts are given.
+const ANONYMIZED_LASTNAME: &str = "User";
+
+/// The condition of a deleted user being due for anonymization: the grace period has passed and
+/// no active legal hold applies. The `$user_target` variable must be bound to
+/// `LegalHoldTarget::User`.
+const ANONYMIZATION_DUE: &str = "deleted_at != NONE AND anonymized_at = NONE AND purge_after <= time::now() AND array::len((SELECT VALUE id FROM legal_holds WHERE target_kind = $user_target AND target_id = record::id($parent.id) AND active = true)) = 0";
+
+impl Database {
+    /// Marks a user account as deleted and hides the user's offers.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to delete.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the deleted `User`, or `None` if the user does not exist or is
+    /// already deleted.
+    pub async fn soft_delete_user(&self, user_id: String) -> Result<Option<User>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Soft-deleting user {}", user_id);
+        let sql = format!(
+            "UPDATE type::thing('users', $user_id) SET deleted_at = time::now(), purge_after = time::now() + {}d WHERE deleted_at IS NONE RETURN AFTER;",
+            ACCOUNT_DELETION_GRACE_DAYS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Option<User> = response.take(0)?;
+        if deleted.is_some() {
+            self.blocked_users.invalidate();
+            self.set_seller_deleted(user_id, true).await?;
+        }
+        Ok(deleted)
+    }
+
+    /// Restores a deleted user account and its offers.
+    ///
+    /// Accounts can only be restored while their grace period is running.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to restore.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if a deleted account was restored, or `false` if no restorable
+    /// account with the given ID exists.
+    pub async fn restore_user(&self, user_id: String) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Restoring user {}", user_id);
+        let sql = "UPDATE type::thing('users', $user_id) SET deleted_at = NONE, purge_after = NONE WHERE deleted_at IS NOT NONE AND purge_after > time::now() RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let restored: Option<User> = response.take(0)?;
+        if restored.is_none() {
+            return Ok(false);
+        }
+        self.blocked_users.invalidate();
+        self.set_seller_deleted(user_id, false).await?;
+        Ok(true)
+    }
+
+    /// Anonymizes the deleted accounts whose grace period has passed, skipping accounts under a
+    /// legal hold until the hold is lifted.
+    ///
+    /// The names, email address and date of birth are replaced, the password is removed and the
+    /// saved addresses are deleted. The user record itself is kept, so the orders of the account
+    /// stay intact for accounting. Anonymized accounts can no longer be restored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the IDs of the anonymized users or a `CustomError` if the update
+    /// fails.
+    pub async fn anonymize_deleted_users(&self) -> Result<Vec<String>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = format!("SELECT * FROM users WHERE {};", ANONYMIZATION_DUE);
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "user_target".into(),
+            Value::from(LegalHoldTarget::User.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let users: Vec<User> = response.take(0)?;
+        let mut anonymized = Vec::with_capacity(users.len());
+        for user in users {
+            let user_id = record_key(&user.id);
+            if self.anonymize_user(&user_id).await? {
+                anonymized.push(user_id);
+            }
+        }
+        if !anonymized.is_empty() {
+            tracing::info!("Anonymized {} deleted accounts", anonymized.len());
+        }
+        Ok(anonymized)
+    }
+
+    /// Replaces the personal data of a deleted account that is due for anonymization.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the account was anonymized, or `false` if it was restored,
+    /// anonymized or put under a legal hold in the meantime.
+    async fn anonymize_user(&self, user_id: &str) -> Result<bool, CustomError> {
+        let email = format!("deleted-{}@invalid", user_id);
+        let email_hash = hash_email(&email);
+        let firstname_index = blind_index("firstname", ANONYMIZED_FIRSTNAME)?;
+        let lastname_index = blind_index("lastname", ANONYMIZED_LASTNAME)?;
+        let [encrypted_firstname, encrypted_lastname, encrypted_email] = encrypt_fields_blocking(
+            user_id.to_string(),
+            [
+                ("encrypted_firstname", ANONYMIZED_FIRSTNAME.to_string()),
+                ("encrypted_lastname", ANONYMIZED_LASTNAME.to_string()),
+                ("encrypted_email", email),
+            ],
+        )
+        .await?;
+
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Anonymizing deleted user {}", user_id);
+        let sql = format!(
+            "UPDATE type::thing('users', $user_id) SET encrypted_firstname = $encrypted_firstname, encrypted_lastname = $encrypted_lastname, firstname_index = $firstname_index, lastname_index = $lastname_index, encrypted_email = $encrypted_email, email_hash = $email_hash, encrypted_date_of_birth = NONE, username = $username, password_hash = '', anonymized_at = time::now(), purge_after = NONE WHERE {} RETURN AFTER;",
+            ANONYMIZATION_DUE
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert(
+            "user_target".into(),
+            Value::from(LegalHoldTarget::User.as_str()),
+        );
+        vars.insert(
+            "encrypted_firstname".into(),
+            Value::from(encrypted_firstname),
+        );
+        vars.insert("encrypted_lastname".into(), Value::from(encrypted_lastname));
+        vars.insert("firstname_index".into(), Value::from(firstname_index));
+        vars.insert("lastname_index".into(), Value::from(lastname_index));
+        vars.insert("encrypted_email".into(), Value::from(encrypted_email));
+        vars.insert("email_hash".into(), Value::from(email_hash));
+        vars.insert(
+            "username".into(),
+            Value::from(format!("deleted_{}", user_id)),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let anonymized: Option<User> = response.take(0)?;
+        if anonymized.is_none() {
+            return Ok(false);
+        }
+
+        let sql = "DELETE addresses WHERE user_id = $user_id;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        self.db.query(sql).bind(vars).await?;
+        Ok(true)
+    }
+
+    /// Hides or shows all offers of a seller because their account was deleted or restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `seller_id` - The ID of the seller.
+    /// * `deleted` - Whether the seller's account is deleted.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the update fails.
+    async fn set_seller_deleted(
+        &self,
+        seller_id: String,
+        deleted: bool,
+    ) -> Result<(), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE offers SET seller_deleted = $deleted WHERE seller_id = $seller_id_thing;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "seller_id_thing".into(),
+            Value::from(UserId::new(seller_id).to_reference()),
+        );
+        vars.insert("deleted".into(), Value::from(deleted));
+
+        self.db.query(sql).bind(vars).await?;
+        Ok(())
+    }
+}
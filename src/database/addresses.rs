@@ -0,0 +1,346 @@
+//! src/database/addresses.rs
+//!
+//! This module handles users' shipping addresses. Addresses are stored encrypted, bound to the
+//! record they are stored in (see `encrypt_bound`). A buyer can attach one of their addresses to
+//! an order; the order keeps its own encrypted copy, so deleting or editing the address later
+//! doesn't change where the order is shipped to.
+
+use super::orders::{Order, OrderState};
+use super::{Database, define, record_key};
+use crate::cpu_pool::CpuPool;
+use crate::encryption::{decrypt_bound_strict, encrypt_bound, field_aad, generate_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+use uuid::Uuid;
+use validator_derive::Validate;
+
+/// The maximum number of addresses a user can store.
+pub const MAX_ADDRESSES: usize = 10;
+
+/// The field an address is encrypted in on `addresses` records.
+const ADDRESS_FIELD: &str = "encrypted_address";
+/// The field the attached address is encrypted in on `orders` records.
+const ORDER_ADDRESS_FIELD: &str = "encrypted_shipping_address";
+
+/// A postal address items are shipped to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct ShippingAddress {
+    /// The name of the recipient.
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Recipient must be 1 to 100 characters long"
+    ))]
+    pub recipient: String,
+    /// The street and house number.
+    #[validate(length(
+        min = 1,
+        max = 200,
+        message = "Street must be 1 to 200 characters long"
+    ))]
+    pub street: String,
+    /// An optional second address line, e.g. an apartment number.
+    #[serde(default)]
+    #[validate(length(
+        max = 200,
+        message = "Address line must be at most 200 characters long"
+    ))]
+    pub additional_line: Option<String>,
+    /// The postal code.
+    #[validate(length(
+        min = 1,
+        max = 20,
+        message = "Postal code must be 1 to 20 characters long"
+    ))]
+    pub postal_code: String,
+    /// The city.
+    #[validate(length(min = 1, max = 100, message = "City must be 1 to 100 characters long"))]
+    pub city: String,
+    /// The ISO 3166-1 alpha-2 code of the country.
+    #[validate(length(equal = 2, message = "Country must be a two-letter country code"))]
+    pub country: String,
+}
+
+/// A stored address of a user, as returned to the user.
+#[derive(Debug, Serialize, Clone)]
+pub struct Address {
+    /// The address's ID.
+    pub id: String,
+    /// The decrypted address.
+    #[serde(flatten)]
+    pub address: ShippingAddress,
+    /// The timestamp when the address was added.
+    pub created_at: String,
+}
+
+/// An address as stored in the database.
+#[derive(Debug, Deserialize)]
+struct StoredAddress {
+    id: Thing,
+    encrypted_address: String,
+    created_at: String,
+}
+
+/// Defines the `addresses` table.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE addresses SCHEMALESS;", "addresses table").await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON addresses TYPE datetime;",
+        "created_at field on addresses",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX addresses_user ON addresses FIELDS user_id",
+        "addresses_user index on addresses",
+    )
+    .await;
+}
+
+/// Encrypts an address on the `CpuPool`, bound to the record and field it is stored in.
+///
+/// # Arguments
+///
+/// * `record_id` - The key of the record the address is stored in.
+/// * `field` - The field the address is stored in.
+/// * `address` - The address to encrypt.
+///
+/// # Returns
+///
+/// A `Result` containing the encrypted address or a `CustomError` if the encryption fails.
+async fn encrypt_address(
+    record_id: String,
+    field: &'static str,
+    address: &ShippingAddress,
+) -> Result<String, CustomError> {
+    let plaintext = serde_json::to_string(address).map_err(|_| CustomError::EncryptionError)?;
+    CpuPool::global()
+        .run(move || {
+            let key_bytes: [u8; 32] = generate_key()?.into();
+            encrypt_bound(&key_bytes, &plaintext, &field_aad(&record_id, field))
+        })
+        .await?
+}
+
+/// Decrypts an address encrypted with `encrypt_address` on the `CpuPool`.
+///
+/// # Arguments
+///
+/// * `record_id` - The key of the record the address is stored in.
+/// * `field` - The field the address is stored in.
+/// * `encrypted` - The stored value.
+///
+/// # Returns
+///
+/// A `Result` containing the address, or a `DecryptionError` or `UnboundValue` if the value is
+/// invalid.
+async fn decrypt_address(
+    record_id: String,
+    field: &'static str,
+    encrypted: String,
+) -> Result<ShippingAddress, CustomError> {
+    let plaintext = CpuPool::global()
+        .run(move || {
+            let key_bytes: [u8; 32] = generate_key()?.into();
+            decrypt_bound_strict(&key_bytes, &encrypted, &field_aad(&record_id, field))
+        })
+        .await??;
+    serde_json::from_str(&plaintext).map_err(|_| CustomError::DecryptionError)
+}
+
+impl StoredAddress {
+    /// Decrypts the stored address.
+    async fn decrypt(self) -> Result<Address, CustomError> {
+        let id = record_key(&self.id);
+        let address = decrypt_address(id.clone(), ADDRESS_FIELD, self.encrypted_address).await?;
+        Ok(Address {
+            id,
+            address,
+            created_at: self.created_at,
+        })
+    }
+}
+
+impl Database {
+    /// Stores a new address of a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `address` - The address to store.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the stored `Address` or a `CustomError` if it cannot be stored.
+    pub async fn create_address(
+        &self,
+        user_id: &str,
+        address: &ShippingAddress,
+    ) -> Result<Address, CustomError> {
+        let address_id = Uuid::new_v4().to_string();
+        let encrypted = encrypt_address(address_id.clone(), ADDRESS_FIELD, address).await?;
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Adding address {} of user {}", address_id, user_id);
+        let sql = "CREATE type::thing('addresses', $address_id) SET user_id = $user_id, encrypted_address = $encrypted_address, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("address_id".into(), Value::from(address_id.as_str()));
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert("encrypted_address".into(), Value::from(encrypted));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<StoredAddress> = response.take(0)?;
+        match created {
+            Some(created) => created.decrypt().await,
+            None => Err(CustomError::DatabaseError(
+                "Failed to create address".to_string(),
+            )),
+        }
+    }
+
+    /// Retrieves the addresses of a user, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decrypted addresses or a `CustomError` if retrieval or
+    /// decryption fails.
+    pub async fn get_addresses(&self, user_id: &str) -> Result<Vec<Address>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM addresses WHERE user_id = $user_id ORDER BY created_at ASC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let stored: Vec<StoredAddress> = response.take(0)?;
+        let mut addresses = Vec::with_capacity(stored.len());
+        for address in stored {
+            addresses.push(address.decrypt().await?);
+        }
+        Ok(addresses)
+    }
+
+    /// Retrieves an address of a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `address_id` - The ID of the address.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decrypted address, or `None` if the user has no such address.
+    pub async fn get_address(
+        &self,
+        user_id: &str,
+        address_id: &str,
+    ) -> Result<Option<Address>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM type::thing('addresses', $address_id) WHERE user_id = $user_id;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("address_id".into(), Value::from(address_id));
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let stored: Option<StoredAddress> = response.take(0)?;
+        match stored {
+            Some(stored) => Ok(Some(stored.decrypt().await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes an address of a user. Orders it was attached to keep their copy.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `address_id` - The ID of the address.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the user had the address.
+    pub async fn delete_address(
+        &self,
+        user_id: &str,
+        address_id: &str,
+    ) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Deleting address {} of user {}", address_id, user_id);
+        let sql =
+            "DELETE type::thing('addresses', $address_id) WHERE user_id = $user_id RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("address_id".into(), Value::from(address_id));
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Vec<StoredAddress> = response.take(0)?;
+        Ok(!deleted.is_empty())
+    }
+
+    /// Attaches a copy of an address to an order, replacing a previously attached one.
+    ///
+    /// The address can only be changed until the order is shipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order.
+    /// * `address` - The address to ship the order to.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated order, or `None` if it was shipped, completed or
+    /// cancelled in the meantime.
+    pub async fn attach_shipping_address(
+        &self,
+        order: &Order,
+        address: &ShippingAddress,
+    ) -> Result<Option<Order>, CustomError> {
+        let order_id = record_key(&order.id);
+        let encrypted = encrypt_address(order_id.clone(), ORDER_ADDRESS_FIELD, address).await?;
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Attaching a shipping address to order {}", order_id);
+        let sql = "UPDATE type::thing('orders', $order_id) SET encrypted_shipping_address = $encrypted_address, updated_at = time::now() WHERE state IN [$pending, $paid] RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("order_id".into(), Value::from(order_id.as_str()));
+        vars.insert("encrypted_address".into(), Value::from(encrypted));
+        vars.insert("pending".into(), Value::from(OrderState::Pending.as_str()));
+        vars.insert("paid".into(), Value::from(OrderState::Paid.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Order> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Decrypts the shipping address attached to an order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the address, or `None` if the buyer hasn't attached one.
+    pub async fn get_shipping_address(
+        &self,
+        order: &Order,
+    ) -> Result<Option<ShippingAddress>, CustomError> {
+        let Some(encrypted) = order.encrypted_shipping_address.clone() else {
+            return Ok(None);
+        };
+        let address =
+            decrypt_address(record_key(&order.id), ORDER_ADDRESS_FIELD, encrypted).await?;
+        Ok(Some(address))
+    }
+}
@@ -0,0 +1,184 @@
+//! src/database/appeals.rs
+//!
+//! This module handles appeals submitted by users against moderation actions.
+
+use super::moderation::AppealState;
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// Represents an appeal against a moderation action.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Appeal {
+    /// The appeal's ID.
+    pub id: Thing,
+    /// The ID of the appealed moderation action.
+    pub action_id: String,
+    /// The ID of the user who submitted the appeal.
+    pub user_id: String,
+    /// The user's explanation of why the action should be reverted.
+    pub message: String,
+    /// The state of the appeal. Never `AppealState::None`.
+    pub status: AppealState,
+    /// The ID of the admin who decided the appeal.
+    #[serde(default)]
+    pub decided_by: Option<String>,
+    /// The note the admin left with their decision.
+    #[serde(default)]
+    pub decision_note: Option<String>,
+    /// The timestamp when the appeal was submitted.
+    pub created_at: String,
+    /// The timestamp when the appeal was decided.
+    #[serde(default)]
+    pub decided_at: Option<String>,
+}
+
+/// Defines the `appeals` table.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE appeals SCHEMALESS;", "appeals table").await;
+    define(
+        db,
+        "DEFINE INDEX appeals_action_id ON appeals FIELDS action_id UNIQUE",
+        "appeals_action_id index on appeals",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX appeals_status ON appeals FIELDS status",
+        "appeals_status index on appeals",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON appeals TYPE datetime;",
+        "created_at field on appeals",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD decided_at ON appeals TYPE option<datetime>;",
+        "decided_at field on appeals",
+    )
+    .await;
+}
+
+impl Database {
+    /// Submits an appeal against a moderation action.
+    ///
+    /// The moderation action's appeal state is moved from `none` to `open` first, so every
+    /// action can only be appealed once.
+    ///
+    /// # Arguments
+    ///
+    /// * `action_id` - The ID of the appealed moderation action.
+    /// * `user_id` - The ID of the user submitting the appeal.
+    /// * `message` - The user's explanation.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Appeal`, or `None` if the action was already appealed.
+    pub async fn create_appeal(
+        &self,
+        action_id: String,
+        user_id: String,
+        message: String,
+    ) -> Result<Option<Appeal>, CustomError> {
+        if !self
+            .transition_appeal_state(action_id.clone(), AppealState::None, AppealState::Open)
+            .await?
+        {
+            return Ok(None);
+        }
+
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Creating appeal against moderation action {}", action_id);
+        let sql = "CREATE appeals SET action_id = $action_id, user_id = $user_id, message = $message, status = 'open', created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("action_id".into(), Value::from(action_id.as_str()));
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("message".into(), Value::from(message.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<Appeal> = response.take(0)?;
+
+        created.map(Some).ok_or_else(|| {
+            tracing::error!("Failed to retrieve created appeal after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created appeal".to_string())
+        })
+    }
+
+    /// Retrieves appeals, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - Only return appeals in this state (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Appeal` structs or a `CustomError` if retrieval fails.
+    pub async fn get_appeals(
+        &self,
+        status: Option<AppealState>,
+    ) -> Result<Vec<Appeal>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let sql = match status {
+            Some(status) => {
+                vars.insert("status".into(), Value::from(status.as_str()));
+                "SELECT * FROM appeals WHERE status = $status ORDER BY created_at ASC;"
+            }
+            None => "SELECT * FROM appeals ORDER BY created_at ASC;",
+        };
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let appeals: Vec<Appeal> = response.take(0)?;
+        Ok(appeals)
+    }
+
+    /// Records the decision on an open appeal.
+    ///
+    /// # Arguments
+    ///
+    /// * `appeal_id` - The ID of the appeal.
+    /// * `approved` - Whether the appeal was approved.
+    /// * `admin_id` - The ID of the admin deciding the appeal.
+    /// * `note` - An optional note explaining the decision.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decided `Appeal`, or `None` if no open appeal with the given ID exists.
+    pub async fn decide_appeal(
+        &self,
+        appeal_id: String,
+        approved: bool,
+        admin_id: String,
+        note: Option<String>,
+    ) -> Result<Option<Appeal>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Deciding appeal {} (approved: {})", appeal_id, approved);
+        let status = if approved {
+            AppealState::Approved
+        } else {
+            AppealState::Rejected
+        };
+        let sql = "UPDATE type::thing('appeals', $appeal_id) SET status = $status, decided_by = $admin_id, decision_note = $note, decided_at = time::now() WHERE status = 'open' RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("appeal_id".into(), Value::from(appeal_id.as_str()));
+        vars.insert("status".into(), Value::from(status.as_str()));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+        vars.insert("note".into(), Value::from(note));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let decided: Option<Appeal> = response.take(0)?;
+        Ok(decided)
+    }
+}
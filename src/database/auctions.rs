@@ -0,0 +1,302 @@
+//! src/database/auctions.rs
+//!
+//! This module handles timed auctions. A seller can sell an active offer by auction instead of at
+//! a fixed price: buyers bid at least the starting price and then at least one increment above
+//! the highest bid, and when the auction ends the highest bidder wins. A bid shortly before the
+//! end extends the auction, so nobody can win by bidding in the last second.
+
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The shortest an auction can run, in hours.
+pub const MIN_AUCTION_HOURS: i64 = 1;
+
+/// The longest an auction can run, in days.
+pub const MAX_AUCTION_DAYS: i64 = 14;
+
+/// A bid within this many minutes of the end extends the auction to this many minutes after the
+/// bid.
+pub const ANTI_SNIPING_MINUTES: i64 = 2;
+
+/// The maximum number of bids returned with an auction.
+pub const MAX_LISTED_AUCTION_BIDS: usize = 50;
+
+/// The state of an auction.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuctionState {
+    /// Taking bids until it ends.
+    Open,
+    /// Ended. Final.
+    Closed,
+}
+
+/// Represents the auction of an offer. Its key is the offer's key.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Auction {
+    /// The auction's ID.
+    pub id: Thing,
+    /// The ID of the offer.
+    pub offer_id: String,
+    /// The ID of the offer's seller.
+    pub seller_id: String,
+    /// The lowest first bid.
+    pub starting_price: f64,
+    /// How much every bid must exceed the highest bid.
+    pub bid_increment: f64,
+    /// The highest bid so far.
+    #[serde(default)]
+    pub highest_bid: Option<f64>,
+    /// The ID of the user who made the highest bid.
+    #[serde(default)]
+    pub highest_bidder_id: Option<String>,
+    /// The number of bids.
+    #[serde(default)]
+    pub bid_count: u64,
+    /// The state of the auction.
+    pub state: AuctionState,
+    /// The timestamp when the auction ends, including extensions.
+    pub ends_at: String,
+    /// The timestamp the auction was started with.
+    pub scheduled_end: String,
+    /// The timestamp when the auction was started.
+    pub created_at: String,
+    /// The ID of the winning bidder, once the auction closed with bids.
+    #[serde(default)]
+    pub winner_id: Option<String>,
+}
+
+impl Auction {
+    /// Returns the lowest amount the next bid may have.
+    pub fn minimum_bid(&self) -> f64 {
+        match self.highest_bid {
+            Some(highest_bid) => highest_bid + self.bid_increment,
+            None => self.starting_price,
+        }
+    }
+}
+
+/// Represents a single bid in an auction.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuctionBid {
+    /// The bid's ID.
+    pub id: Thing,
+    /// The ID of the offer.
+    pub offer_id: String,
+    /// The ID of the bidder.
+    pub bidder_id: String,
+    /// The amount of the bid.
+    pub amount: f64,
+    /// The timestamp when the bid was made.
+    pub created_at: String,
+}
+
+/// Defines the `auctions` and `auction_bids` tables.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE auctions SCHEMALESS;", "auctions table").await;
+    define(
+        db,
+        "DEFINE FIELD ends_at ON auctions TYPE datetime;",
+        "ends_at field on auctions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD scheduled_end ON auctions TYPE datetime;",
+        "scheduled_end field on auctions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON auctions TYPE datetime;",
+        "created_at field on auctions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX auctions_state_ends_at ON auctions FIELDS state, ends_at",
+        "auctions_state_ends_at index on auctions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE TABLE auction_bids SCHEMALESS;",
+        "auction_bids table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON auction_bids TYPE datetime;",
+        "created_at field on auction_bids",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX auction_bids_offer_id ON auction_bids FIELDS offer_id",
+        "auction_bids_offer_id index on auction_bids",
+    )
+    .await;
+}
+
+impl Database {
+    /// Starts the auction of an offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `seller_id` - The ID of the offer's seller.
+    /// * `starting_price` - The lowest first bid.
+    /// * `bid_increment` - How much every bid must exceed the highest bid.
+    /// * `ends_at` - The RFC 3339 timestamp when the auction ends.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Auction`, or `None` if the offer already had an
+    /// auction.
+    pub async fn create_auction(
+        &self,
+        offer_id: String,
+        seller_id: String,
+        starting_price: f64,
+        bid_increment: f64,
+        ends_at: String,
+    ) -> Result<Option<Auction>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Starting auction of offer {} until {}", offer_id, ends_at);
+        let sql = "IF (SELECT * FROM type::thing('auctions', $offer_id)) = [] THEN (CREATE type::thing('auctions', $offer_id) SET offer_id = $offer_id, seller_id = $seller_id, starting_price = $starting_price, bid_increment = $bid_increment, bid_count = 0, state = 'open', ends_at = <datetime> $ends_at, scheduled_end = <datetime> $ends_at, created_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("seller_id".into(), Value::from(seller_id.as_str()));
+        vars.insert("starting_price".into(), Value::from(starting_price));
+        vars.insert("bid_increment".into(), Value::from(bid_increment));
+        vars.insert("ends_at".into(), Value::from(ends_at.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<Auction> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Retrieves the auction of an offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Auction`, `None` if the offer isn't sold by auction, or a
+    /// `CustomError` if retrieval fails.
+    pub async fn get_auction(&self, offer_id: String) -> Result<Option<Auction>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM type::thing('auctions', $offer_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let auction: Option<Auction> = response.take(0)?;
+        Ok(auction)
+    }
+
+    /// Retrieves the most recent bids of an auction, highest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing at most `MAX_LISTED_AUCTION_BIDS` bids or a `CustomError` if
+    /// retrieval fails.
+    pub async fn get_auction_bids(&self, offer_id: String) -> Result<Vec<AuctionBid>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = format!(
+            "SELECT * FROM auction_bids WHERE offer_id = $offer_id ORDER BY amount DESC LIMIT {};",
+            MAX_LISTED_AUCTION_BIDS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let bids: Vec<AuctionBid> = response.take(0)?;
+        Ok(bids)
+    }
+
+    /// Places a bid in an open auction.
+    ///
+    /// The bid is checked against the minimum bid and the end of the auction in the same
+    /// statement that records it, so concurrent bids can't both win. A bid within
+    /// `ANTI_SNIPING_MINUTES` minutes of the end extends the auction.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `bidder_id` - The ID of the bidder.
+    /// * `amount` - The amount of the bid.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the auction before and after the bid, or `None` if the auction
+    /// has ended or the bid is too low.
+    pub async fn place_auction_bid(
+        &self,
+        offer_id: String,
+        bidder_id: String,
+        amount: f64,
+    ) -> Result<Option<(Auction, Auction)>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = format!(
+            "UPDATE type::thing('auctions', $offer_id) SET highest_bid = $amount, highest_bidder_id = $bidder_id, bid_count += 1, ends_at = IF ends_at < time::now() + {window}m THEN time::now() + {window}m ELSE ends_at END WHERE state = 'open' AND ends_at > time::now() AND $amount >= (IF highest_bid = NONE THEN starting_price ELSE highest_bid + bid_increment END) RETURN BEFORE;
+            SELECT * FROM type::thing('auctions', $offer_id);",
+            window = ANTI_SNIPING_MINUTES
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("bidder_id".into(), Value::from(bidder_id.as_str()));
+        vars.insert("amount".into(), Value::from(amount));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars.clone()).await?;
+        let before: Option<Auction> = response.take(0)?;
+        let Some(before) = before else {
+            return Ok(None);
+        };
+        let after: Option<Auction> = response.take(1)?;
+        let after = after.ok_or_else(|| {
+            tracing::error!(
+                "Failed to retrieve auction of offer {} after bid.",
+                offer_id
+            );
+            CustomError::DatabaseError("Failed to retrieve auction".to_string())
+        })?;
+
+        let sql = "CREATE auction_bids SET offer_id = $offer_id, bidder_id = $bidder_id, amount = $amount, created_at = time::now();";
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(Some((before, after)))
+    }
+
+    /// Closes every open auction that has ended, making its highest bidder the winner.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the closed auctions or a `CustomError` if the update fails.
+    pub async fn close_ended_auctions(&self) -> Result<Vec<Auction>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE auctions SET state = 'closed', winner_id = highest_bidder_id WHERE state = 'open' AND ends_at <= time::now() RETURN AFTER;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let closed: Vec<Auction> = response.take(0)?;
+        if !closed.is_empty() {
+            tracing::info!("Closed {} auctions", closed.len());
+        }
+        Ok(closed)
+    }
+}
@@ -0,0 +1,190 @@
+//! src/database/audit.rs
+//!
+//! This module handles the audit log, which records every administrative action taken on the platform.
+//! Failed anonymous attempts, such as logins, are aggregated per hour and kept for a limited time.
+
+use super::{Count, Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// Represents a single entry in the audit log.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    /// The entry's ID.
+    pub id: Thing,
+    /// The ID of the user who performed the action.
+    pub actor_id: String,
+    /// A short machine-readable name of the action (e.g. `bulk_ban_users`).
+    pub action: String,
+    /// The IDs of the records affected by the action.
+    pub targets: Vec<String>,
+    /// A human-readable summary of the action and its outcome.
+    pub details: String,
+    /// The timestamp when the action was performed.
+    pub created_at: String,
+    /// How often the action was attempted within the hour, for aggregated anonymous failures.
+    pub attempts: Option<u64>,
+}
+
+/// The actor recorded for attempts made without an authenticated user.
+pub const ANONYMOUS_ACTOR: &str = "anonymous";
+
+/// The number of anonymous failure entries created per hour. Further failures within the hour
+/// are counted in a single overflow entry per action.
+const MAX_ANONYMOUS_ENTRIES_PER_HOUR: u64 = 1000;
+
+/// The subject of the overflow entry counting the failures over the hourly limit.
+const OVERFLOW_SUBJECT: &str = "*";
+
+/// The number of days anonymous failure entries are kept.
+const ANONYMOUS_RETENTION_DAYS: u32 = 30;
+
+/// Defines the `audit_log` table.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE audit_log SCHEMALESS;", "audit_log table").await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON audit_log TYPE datetime;",
+        "created_at field on audit_log",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX audit_log_actor_id ON audit_log FIELDS actor_id",
+        "audit_log_actor_id index on audit_log",
+    )
+    .await;
+}
+
+impl Database {
+    /// Records an entry in the audit log.
+    ///
+    /// # Arguments
+    ///
+    /// * `actor_id` - The ID of the user who performed the action.
+    /// * `action` - A short machine-readable name of the action.
+    /// * `targets` - The IDs of the records affected by the action.
+    /// * `details` - A human-readable summary of the action and its outcome.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `AuditEntry` or a `CustomError` if creation fails.
+    pub async fn record_audit_entry(
+        &self,
+        actor_id: String,
+        action: &str,
+        targets: Vec<String>,
+        details: String,
+    ) -> Result<AuditEntry, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Recording audit entry '{}' by {}", action, actor_id);
+        let sql = "CREATE audit_log SET actor_id = $actor_id, action = $action, targets = $targets, details = $details, created_at = time::now();";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("actor_id".into(), Value::from(actor_id.as_str()));
+        vars.insert("action".into(), Value::from(action));
+        vars.insert("targets".into(), Value::from(targets));
+        vars.insert("details".into(), Value::from(details.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<AuditEntry> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created audit entry after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created audit entry".to_string())
+        })
+    }
+
+    /// Retrieves all audit entries of an actor, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `actor_id` - The ID of the user who performed the actions.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `AuditEntry` structs or a `CustomError` if retrieval fails.
+    pub async fn get_audit_entries_by_actor(
+        &self,
+        actor_id: String,
+    ) -> Result<Vec<AuditEntry>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM audit_log WHERE actor_id = $actor_id ORDER BY created_at DESC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("actor_id".into(), Value::from(actor_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let entries: Vec<AuditEntry> = response.take(0)?;
+        Ok(entries)
+    }
+
+    /// Records a failed anonymous attempt, such as a login with a wrong password.
+    ///
+    /// Attempts are aggregated into one entry per action, subject and hour, counting the attempts
+    /// and keeping the latest details. Once `MAX_ANONYMOUS_ENTRIES_PER_HOUR` entries were created
+    /// within the hour, attempts for new subjects are only counted in an overflow entry. Entries
+    /// older than `ANONYMOUS_RETENTION_DAYS` are deleted when a new entry is created.
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - A short machine-readable name of the action.
+    /// * `subject` - What the attempt was made for, e.g. the hash of an email address.
+    /// * `details` - A human-readable summary of the attempt and why it failed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated `AuditEntry` or a `CustomError` if recording fails.
+    pub async fn record_anonymous_failure(
+        &self,
+        action: &str,
+        subject: &str,
+        details: String,
+    ) -> Result<AuditEntry, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("actor_id".into(), Value::from(ANONYMOUS_ACTOR));
+        vars.insert("action".into(), Value::from(action));
+        vars.insert("subject".into(), Value::from(subject));
+        vars.insert("details".into(), Value::from(details.as_str()));
+
+        let sql = "UPDATE type::thing('audit_log', [$action, $subject, time::floor(time::now(), 1h)]) SET attempts = (attempts ?? 1) + 1, details = $details RETURN AFTER;";
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars.clone()).await?;
+        let updated: Option<AuditEntry> = response.take(0)?;
+        if let Some(entry) = updated {
+            return Ok(entry);
+        }
+
+        let sql = "SELECT count() FROM audit_log WHERE actor_id = $actor_id AND created_at >= time::floor(time::now(), 1h) GROUP ALL;";
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars.clone()).await?;
+        let count: Option<Count> = response.take(0)?;
+        if count.is_some_and(|count| count.count >= MAX_ANONYMOUS_ENTRIES_PER_HOUR) {
+            tracing::warn!("Anonymous '{}' failures exceed the hourly limit", action);
+            vars.insert("subject".into(), Value::from(OVERFLOW_SUBJECT));
+            vars.insert(
+                "details".into(),
+                Value::from("Failures over the hourly limit of distinct entries"),
+            );
+        }
+
+        let sql = format!(
+            "UPSERT type::thing('audit_log', [$action, $subject, time::floor(time::now(), 1h)]) SET actor_id = $actor_id, action = $action, targets = [], details = $details, attempts = (attempts ?? 0) + 1, created_at = created_at ?? time::now() RETURN AFTER; DELETE audit_log WHERE actor_id = $actor_id AND created_at < time::now() - {}d;",
+            ANONYMOUS_RETENTION_DAYS
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<AuditEntry> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve anonymous failure entry after insertion.");
+            CustomError::DatabaseError("Failed to retrieve anonymous failure entry".to_string())
+        })
+    }
+}
@@ -0,0 +1,286 @@
+//! src/database/authenticity.rs
+//!
+//! This module handles the optional authenticity verification of high-value listings. Sellers
+//! submit photos of the item, an admin works through the review checklist, and listings that pass
+//! every check earn the "authenticated" badge.
+
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The minimum price of a listing that can be submitted for authentication.
+pub const MIN_AUTHENTICATION_PRICE: f64 = 100.0;
+
+/// The state of an authentication request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthenticationState {
+    /// Waiting for an admin to review the photos.
+    Pending,
+    /// Every check passed; the listing carries the badge.
+    Authenticated,
+    /// At least one check failed.
+    Rejected,
+}
+
+impl AuthenticationState {
+    /// Returns the string stored in the database for this state.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthenticationState::Pending => "pending",
+            AuthenticationState::Authenticated => "authenticated",
+            AuthenticationState::Rejected => "rejected",
+        }
+    }
+}
+
+/// The checklist an admin works through when reviewing the photos of a listing.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct AuthenticityChecklist {
+    /// The label or print quality matches a genuine copy.
+    pub label_genuine: bool,
+    /// The cartridge board, disc ring codes or device internals match a genuine copy.
+    pub media_genuine: bool,
+    /// The box, manual and inserts match a genuine copy (or are absent as stated in the listing).
+    pub packaging_genuine: bool,
+    /// The photographed item matches the title, platform and condition of the listing.
+    pub matches_listing: bool,
+}
+
+impl AuthenticityChecklist {
+    /// Returns whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.label_genuine && self.media_genuine && self.packaging_genuine && self.matches_listing
+    }
+}
+
+/// Represents a seller's request to have a listing authenticated.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuthenticationRequest {
+    /// The request's ID.
+    pub id: Thing,
+    /// The ID of the listing to authenticate.
+    pub offer_id: String,
+    /// The ID of the seller who submitted the request.
+    pub seller_id: String,
+    /// Links to the photos of the item.
+    pub photo_urls: Vec<String>,
+    /// The state of the request.
+    pub state: AuthenticationState,
+    /// The checklist filled in by the reviewing admin.
+    #[serde(default)]
+    pub checklist: Option<AuthenticityChecklist>,
+    /// The ID of the admin who reviewed the request.
+    #[serde(default)]
+    pub reviewed_by: Option<String>,
+    /// The note the admin left with their review.
+    #[serde(default)]
+    pub review_note: Option<String>,
+    /// The timestamp when the request was submitted.
+    pub created_at: String,
+    /// The timestamp when the request was reviewed.
+    #[serde(default)]
+    pub reviewed_at: Option<String>,
+}
+
+/// Defines the `authentication_requests` table and the badge field on `offers`.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD authenticated ON offers TYPE bool DEFAULT false;",
+        "authenticated field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE TABLE authentication_requests SCHEMALESS;",
+        "authentication_requests table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX authentication_requests_offer ON authentication_requests FIELDS offer_id, state",
+        "authentication_requests_offer index on authentication_requests",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON authentication_requests TYPE datetime;",
+        "created_at field on authentication_requests",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD reviewed_at ON authentication_requests TYPE option<datetime>;",
+        "reviewed_at field on authentication_requests",
+    )
+    .await;
+}
+
+impl Database {
+    /// Submits a listing for authentication.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the listing.
+    /// * `seller_id` - The ID of the seller submitting the request.
+    /// * `photo_urls` - Links to the photos of the item.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `AuthenticationRequest`, or `None` if the listing is
+    /// already waiting for review.
+    pub async fn request_authentication(
+        &self,
+        offer_id: String,
+        seller_id: String,
+        photo_urls: Vec<String>,
+    ) -> Result<Option<AuthenticationRequest>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Requesting authentication of offer {}", offer_id);
+        let sql = "IF (SELECT * FROM authentication_requests WHERE offer_id = $offer_id AND state = 'pending') = [] THEN (CREATE authentication_requests SET offer_id = $offer_id, seller_id = $seller_id, photo_urls = $photo_urls, state = 'pending', created_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("seller_id".into(), Value::from(seller_id.as_str()));
+        vars.insert(
+            "photo_urls".into(),
+            Value::from(
+                photo_urls
+                    .into_iter()
+                    .map(Value::from)
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<AuthenticationRequest> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Retrieves authentication requests, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - Only return requests in this state (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `AuthenticationRequest` structs or a `CustomError` if retrieval fails.
+    pub async fn get_authentication_requests(
+        &self,
+        state: Option<AuthenticationState>,
+    ) -> Result<Vec<AuthenticationRequest>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let sql = match state {
+            Some(state) => {
+                vars.insert("state".into(), Value::from(state.as_str()));
+                "SELECT * FROM authentication_requests WHERE state = $state ORDER BY created_at ASC;"
+            }
+            None => "SELECT * FROM authentication_requests ORDER BY created_at ASC;",
+        };
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let requests: Vec<AuthenticationRequest> = response.take(0)?;
+        Ok(requests)
+    }
+
+    /// Records the review of a pending authentication request.
+    ///
+    /// The request is authenticated only if every check passed, in which case the listing is
+    /// given the badge.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_id` - The ID of the request.
+    /// * `checklist` - The checklist filled in by the admin.
+    /// * `admin_id` - The ID of the reviewing admin.
+    /// * `note` - An optional note explaining the outcome.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the reviewed `AuthenticationRequest`, or `None` if no pending request
+    /// with the given ID exists.
+    pub async fn review_authentication_request(
+        &self,
+        request_id: String,
+        checklist: AuthenticityChecklist,
+        admin_id: String,
+        note: Option<String>,
+    ) -> Result<Option<AuthenticationRequest>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let state = if checklist.passed() {
+            AuthenticationState::Authenticated
+        } else {
+            AuthenticationState::Rejected
+        };
+        tracing::info!(
+            "Reviewing authentication request {} as {}",
+            request_id,
+            state.as_str()
+        );
+        let sql = "UPDATE type::thing('authentication_requests', $request_id) SET state = $state, checklist = $checklist, reviewed_by = $admin_id, review_note = $note, reviewed_at = time::now() WHERE state = 'pending' RETURN AFTER;";
+        let mut checklist_object: BTreeMap<String, Value> = BTreeMap::new();
+        checklist_object.insert("label_genuine".into(), Value::from(checklist.label_genuine));
+        checklist_object.insert("media_genuine".into(), Value::from(checklist.media_genuine));
+        checklist_object.insert(
+            "packaging_genuine".into(),
+            Value::from(checklist.packaging_genuine),
+        );
+        checklist_object.insert(
+            "matches_listing".into(),
+            Value::from(checklist.matches_listing),
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("request_id".into(), Value::from(request_id.as_str()));
+        vars.insert("state".into(), Value::from(state.as_str()));
+        vars.insert("checklist".into(), Value::from(checklist_object));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+        vars.insert("note".into(), Value::from(note));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let reviewed: Option<AuthenticationRequest> = response.take(0)?;
+
+        if let Some(request) = &reviewed
+            && request.state == AuthenticationState::Authenticated
+        {
+            self.set_offer_authenticated(request.offer_id.clone(), true)
+                .await?;
+        }
+        Ok(reviewed)
+    }
+
+    /// Grants or removes the "authenticated" badge of a listing.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the listing.
+    /// * `authenticated` - Whether the listing carries the badge.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the update fails.
+    pub async fn set_offer_authenticated(
+        &self,
+        offer_id: String,
+        authenticated: bool,
+    ) -> Result<(), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('offers', $offer_id) SET authenticated = $authenticated;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("authenticated".into(), Value::from(authenticated));
+
+        self.db.query(sql).bind(vars).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! src/database/bids.rs
+//!
+//! This module handles bids: private price proposals buyers make on an offer. The seller accepts,
+//! declines or counters a bid, the buyer answers a counter the same way, and an accepted bid
+//! reserves the offer for its buyer. Bids are only visible to their buyer and the seller.
+
+use super::{Database, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The state of a bid.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BidStatus {
+    /// Waiting for the seller's answer.
+    Pending,
+    /// The seller proposed another price and waits for the buyer's answer.
+    Countered,
+    /// Accepted; the offer is reserved for the buyer. Final.
+    Accepted,
+    /// Declined by the seller or the buyer, or because another bid was accepted. Final.
+    Declined,
+}
+
+impl BidStatus {
+    /// Returns the string stored in the database for this status.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BidStatus::Pending => "pending",
+            BidStatus::Countered => "countered",
+            BidStatus::Accepted => "accepted",
+            BidStatus::Declined => "declined",
+        }
+    }
+
+    /// Returns whether the negotiation is still going on.
+    pub fn is_open(&self) -> bool {
+        matches!(self, BidStatus::Pending | BidStatus::Countered)
+    }
+}
+
+/// Represents a buyer's price proposal on an offer.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bid {
+    /// The bid's ID.
+    pub id: Thing,
+    /// The ID of the offer.
+    pub offer_id: String,
+    /// The ID of the buyer who made the bid.
+    pub buyer_id: String,
+    /// The ID of the offer's seller.
+    pub seller_id: String,
+    /// The price proposed by the buyer.
+    pub amount: f64,
+    /// The price proposed by the seller in return, if they countered.
+    #[serde(default)]
+    pub counter_amount: Option<f64>,
+    /// The state of the bid.
+    pub status: BidStatus,
+    /// The timestamp when the bid was made.
+    pub created_at: String,
+    /// The timestamp of the last answer to the bid.
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Bid {
+    /// Returns the ID of the user whose answer the bid is waiting for, if it is still open.
+    pub fn awaiting(&self) -> Option<&str> {
+        match self.status {
+            BidStatus::Pending => Some(&self.seller_id),
+            BidStatus::Countered => Some(&self.buyer_id),
+            BidStatus::Accepted | BidStatus::Declined => None,
+        }
+    }
+
+    /// Returns the price the offer is sold for if the bid is accepted in its current state.
+    pub fn agreed_amount(&self) -> f64 {
+        match self.status {
+            BidStatus::Countered | BidStatus::Accepted => {
+                self.counter_amount.unwrap_or(self.amount)
+            }
+            BidStatus::Pending | BidStatus::Declined => self.amount,
+        }
+    }
+}
+
+/// Defines the `bids` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE bids SCHEMALESS;", "bids table").await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON bids TYPE datetime;",
+        "created_at field on bids",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD updated_at ON bids TYPE option<datetime>;",
+        "updated_at field on bids",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX bids_offer_buyer ON bids FIELDS offer_id, buyer_id",
+        "bids_offer_buyer index on bids",
+    )
+    .await;
+}
+
+impl Database {
+    /// Creates a bid on an offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `buyer_id` - The ID of the buyer making the bid.
+    /// * `seller_id` - The ID of the offer's seller.
+    /// * `amount` - The proposed price.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Bid`, or `None` if the buyer already has an open bid on
+    /// the offer.
+    pub async fn create_bid(
+        &self,
+        offer_id: String,
+        buyer_id: String,
+        seller_id: String,
+        amount: f64,
+    ) -> Result<Option<Bid>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Creating bid of user {} on offer {}", buyer_id, offer_id);
+        let sql = "IF (SELECT * FROM bids WHERE offer_id = $offer_id AND buyer_id = $buyer_id AND status IN ['pending', 'countered']) = [] THEN (CREATE bids SET offer_id = $offer_id, buyer_id = $buyer_id, seller_id = $seller_id, amount = $amount, status = 'pending', created_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("buyer_id".into(), Value::from(buyer_id.as_str()));
+        vars.insert("seller_id".into(), Value::from(seller_id.as_str()));
+        vars.insert("amount".into(), Value::from(amount));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<Bid> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Retrieves a single bid by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `bid_id` - The ID of the bid to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `Bid` or a `CustomError` if retrieval fails.
+    pub async fn get_bid(&self, bid_id: String) -> Result<Option<Bid>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM type::thing('bids', $bid_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("bid_id".into(), Value::from(bid_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let bid: Option<Bid> = response.take(0)?;
+        Ok(bid)
+    }
+
+    /// Retrieves the bids on an offer, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `buyer_id` - Only return the bids of this buyer (optional). Sellers see every bid.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Bid` structs or a `CustomError` if retrieval fails.
+    pub async fn get_bids_for_offer(
+        &self,
+        offer_id: String,
+        buyer_id: Option<String>,
+    ) -> Result<Vec<Bid>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        let sql = match buyer_id {
+            Some(buyer_id) => {
+                vars.insert("buyer_id".into(), Value::from(buyer_id.as_str()));
+                "SELECT * FROM bids WHERE offer_id = $offer_id AND buyer_id = $buyer_id ORDER BY created_at DESC;"
+            }
+            None => "SELECT * FROM bids WHERE offer_id = $offer_id ORDER BY created_at DESC;",
+        };
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let bids: Vec<Bid> = response.take(0)?;
+        Ok(bids)
+    }
+
+    /// Answers an open bid.
+    ///
+    /// The answer is only applied if the bid's status hasn't changed since it was read, so two
+    /// answers to the same bid can't both succeed.
+    ///
+    /// # Arguments
+    ///
+    /// * `bid` - The bid, as read before the answer.
+    /// * `next` - The new status.
+    /// * `counter_amount` - The price proposed in return, when countering.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated `Bid`, or `None` if its status changed concurrently.
+    pub async fn answer_bid(
+        &self,
+        bid: &Bid,
+        next: BidStatus,
+        counter_amount: Option<f64>,
+    ) -> Result<Option<Bid>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Changing status of bid {} from {} to {}",
+            bid.id,
+            bid.status.as_str(),
+            next.as_str()
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("bid_id".into(), Value::from(record_key(&bid.id).as_str()));
+        vars.insert("current".into(), Value::from(bid.status.as_str()));
+        vars.insert("next".into(), Value::from(next.as_str()));
+        let sql = match counter_amount {
+            Some(counter_amount) => {
+                vars.insert("counter_amount".into(), Value::from(counter_amount));
+                "UPDATE type::thing('bids', $bid_id) SET status = $next, counter_amount = $counter_amount, updated_at = time::now() WHERE status = $current RETURN AFTER;"
+            }
+            None => {
+                "UPDATE type::thing('bids', $bid_id) SET status = $next, updated_at = time::now() WHERE status = $current RETURN AFTER;"
+            }
+        };
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Bid> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Declines every open bid on an offer, e.g. after another bid was accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the declined bids or a `CustomError` if the update fails.
+    pub async fn decline_open_bids(&self, offer_id: String) -> Result<Vec<Bid>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE bids SET status = 'declined', updated_at = time::now() WHERE offer_id = $offer_id AND status IN ['pending', 'countered'] RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let declined: Vec<Bid> = response.take(0)?;
+        Ok(declined)
+    }
+}
@@ -0,0 +1,116 @@
+//! src/database/blind_index.rs
+//!
+//! This module provides blind indexes of users' encrypted names: keyed hashes stored next to the
+//! encrypted fields, so admins can look up users by exact name for support requests without
+//! decrypting the whole table. Without the key, the hashes can't be matched against guessed names.
+
+use super::{Database, User, define};
+use crate::encryption::generate_key;
+use crate::errors::custom_errors::CustomError;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The maximum number of users returned by a name lookup.
+pub const MAX_NAME_LOOKUP_RESULTS: usize = 50;
+
+/// Defines the indexed blind index fields on the `users` table.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE INDEX users_firstname_index ON users FIELDS firstname_index",
+        "users_firstname_index index on users",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX users_lastname_index ON users FIELDS lastname_index",
+        "users_lastname_index index on users",
+    )
+    .await;
+}
+
+/// Normalizes a name so lookups ignore case and surrounding or repeated whitespace.
+pub fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Computes the blind index of a field value.
+///
+/// The index is an HMAC-SHA256 of the field name and the normalized value. Its key is derived
+/// from the encryption key, so no separate secret has to be configured, but a leaked index key
+/// doesn't reveal the encryption key.
+///
+/// # Arguments
+///
+/// * `field` - The name of the indexed field, e.g. `firstname`.
+/// * `value` - The plaintext value.
+///
+/// # Returns
+///
+/// A `Result` containing the hex-encoded index or a `CustomError` if the key cannot be loaded.
+pub fn blind_index(field: &str, value: &str) -> Result<String, CustomError> {
+    let encryption_key = generate_key()?;
+    let mut derivation = Hmac::<Sha256>::new_from_slice(encryption_key.as_slice())
+        .map_err(|_| CustomError::EncryptionError)?;
+    derivation.update(b"gameshop blind index v1");
+    let index_key = derivation.finalize().into_bytes();
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(&index_key).map_err(|_| CustomError::EncryptionError)?;
+    mac.update(field.as_bytes());
+    mac.update(b":");
+    mac.update(normalize_name(value).as_bytes());
+    Ok(format!("{:x}", mac.finalize().into_bytes()))
+}
+
+impl Database {
+    /// Retrieves the users with the given first and/or last name.
+    ///
+    /// Names are matched exactly, ignoring case and whitespace, via their blind indexes.
+    ///
+    /// # Arguments
+    ///
+    /// * `firstname` - The first name to match (optional).
+    /// * `lastname` - The last name to match (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing at most `MAX_NAME_LOOKUP_RESULTS` matching users or a `CustomError`
+    /// if the lookup fails. No users are returned if neither name is given.
+    pub async fn find_users_by_name(
+        &self,
+        firstname: Option<String>,
+        lastname: Option<String>,
+    ) -> Result<Vec<User>, CustomError> {
+        let mut conditions = Vec::new();
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        for (field, value) in [("firstname", firstname), ("lastname", lastname)] {
+            if let Some(value) = value {
+                conditions.push(format!("{}_index = ${}", field, field));
+                vars.insert(
+                    field.into(),
+                    Value::from(blind_index(field, &value)?.as_str()),
+                );
+            }
+        }
+        if conditions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = format!(
+            "SELECT * FROM users WHERE {} LIMIT {};",
+            conditions.join(" AND "),
+            MAX_NAME_LOOKUP_RESULTS
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let users: Vec<User> = response.take(0)?;
+        Ok(users)
+    }
+}
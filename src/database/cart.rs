@@ -0,0 +1,166 @@
+//! src/database/cart.rs
+//!
+//! This module handles users' shopping carts. A cart item remembers the price of the offer when
+//! it was added, so a price change can be shown to the buyer before checkout.
+
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The maximum number of offers in a cart.
+pub const MAX_CART_ITEMS: usize = 50;
+
+/// Represents an offer in a user's cart.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CartItem {
+    /// The cart item's ID.
+    pub id: Thing,
+    /// The ID of the user owning the cart.
+    pub user_id: String,
+    /// The ID of the offer.
+    pub offer_id: String,
+    /// The price of the offer the buyer last saw.
+    pub price: f64,
+    /// The timestamp when the offer was added to the cart.
+    pub added_at: String,
+}
+
+/// Defines the `cart_items` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE cart_items SCHEMALESS;",
+        "cart_items table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD added_at ON cart_items TYPE datetime;",
+        "added_at field on cart_items",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX cart_items_user_offer ON cart_items FIELDS user_id, offer_id UNIQUE",
+        "cart_items_user_offer index on cart_items",
+    )
+    .await;
+}
+
+impl Database {
+    /// Adds an offer to a user's cart at its current price.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `offer` - The offer to add.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `CartItem`, or `None` if the offer already is in the
+    /// cart.
+    pub async fn add_cart_item(
+        &self,
+        user_id: &str,
+        offer: &Offer,
+    ) -> Result<Option<CartItem>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let offer_id = record_key(&offer.id);
+        tracing::info!("Adding offer {} to the cart of user {}", offer_id, user_id);
+        let sql = "IF (SELECT * FROM cart_items WHERE user_id = $user_id AND offer_id = $offer_id) = [] THEN (CREATE cart_items SET user_id = $user_id, offer_id = $offer_id, price = $price, added_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("price".into(), Value::from(offer.price));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<CartItem> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Removes an offer from a user's cart.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the offer was in the cart.
+    pub async fn remove_cart_item(
+        &self,
+        user_id: &str,
+        offer_id: &str,
+    ) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Removing offer {} from the cart of user {}",
+            offer_id,
+            user_id
+        );
+        let sql =
+            "DELETE cart_items WHERE user_id = $user_id AND offer_id = $offer_id RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert("offer_id".into(), Value::from(offer_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Vec<CartItem> = response.take(0)?;
+        Ok(!deleted.is_empty())
+    }
+
+    /// Retrieves the items of a user's cart, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `CartItem` structs or a `CustomError` if retrieval fails.
+    pub async fn get_cart_items(&self, user_id: &str) -> Result<Vec<CartItem>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM cart_items WHERE user_id = $user_id ORDER BY added_at ASC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let items: Vec<CartItem> = response.take(0)?;
+        Ok(items)
+    }
+
+    /// Updates the price of a cart item to the price the buyer has now seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The cart item.
+    /// * `price` - The current price of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok` if the update succeeded.
+    pub async fn update_cart_item_price(
+        &self,
+        item: &CartItem,
+        price: f64,
+    ) -> Result<(), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('cart_items', $item_id) SET price = $price;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("item_id".into(), Value::from(record_key(&item.id).as_str()));
+        vars.insert("price".into(), Value::from(price));
+
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,969 @@
+//! src/database/catalog.rs
+//!
+//! This module defines the structured catalog metadata of offers, such as the listing category with
+//! its category-specific attributes, genres, region coding and the languages of the box and manual,
+//! and the filters used to search offers by them.
+
+use super::conditions::Condition;
+use super::define;
+use super::ids::UserId;
+use super::pickup::{
+    DEFAULT_RADIUS_KM, MAX_RADIUS_KM, PickupLocation, parse_near, push_distance_condition,
+    validate_near,
+};
+use super::platforms::Platform;
+use super::preferences::Currency;
+use super::serial_blacklist::normalize_serial;
+use crate::exchange_rates::ExchangeRates;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+use validator::ValidationError;
+use validator_derive::Validate;
+
+/// The category of a listing.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// A game.
+    #[default]
+    Game,
+    /// A console.
+    Console,
+    /// A controller.
+    Controller,
+    /// Any other accessory (e.g. memory cards, cables, chargers).
+    Accessory,
+}
+
+impl Category {
+    /// Every category, in the order they are presented to buyers.
+    pub const ALL: [Category; 4] = [
+        Category::Game,
+        Category::Console,
+        Category::Controller,
+        Category::Accessory,
+    ];
+
+    /// Returns the string stored in the database for this category.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Game => "game",
+            Category::Console => "console",
+            Category::Controller => "controller",
+            Category::Accessory => "accessory",
+        }
+    }
+
+    /// Returns the human-readable name of this category.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Category::Game => "Games",
+            Category::Console => "Consoles",
+            Category::Controller => "Controllers",
+            Category::Accessory => "Accessories",
+        }
+    }
+}
+
+/// The genre of a game, from a curated list so buyers can browse by genre.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Genre {
+    /// Action games.
+    Action,
+    /// Adventure games.
+    Adventure,
+    /// Role-playing games.
+    Rpg,
+    /// Strategy games.
+    Strategy,
+    /// Simulation games.
+    Simulation,
+    /// Sports games.
+    Sports,
+    /// Racing games.
+    Racing,
+    /// Shooters.
+    Shooter,
+    /// Fighting games.
+    Fighting,
+    /// Platformers.
+    Platformer,
+    /// Puzzle games.
+    Puzzle,
+    /// Horror games.
+    Horror,
+    /// Party and family games.
+    Party,
+}
+
+impl Genre {
+    /// Every genre, in the order they are presented to buyers.
+    pub const ALL: [Genre; 13] = [
+        Genre::Action,
+        Genre::Adventure,
+        Genre::Rpg,
+        Genre::Strategy,
+        Genre::Simulation,
+        Genre::Sports,
+        Genre::Racing,
+        Genre::Shooter,
+        Genre::Fighting,
+        Genre::Platformer,
+        Genre::Puzzle,
+        Genre::Horror,
+        Genre::Party,
+    ];
+
+    /// Returns the string stored in the database for this genre.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Genre::Action => "action",
+            Genre::Adventure => "adventure",
+            Genre::Rpg => "rpg",
+            Genre::Strategy => "strategy",
+            Genre::Simulation => "simulation",
+            Genre::Sports => "sports",
+            Genre::Racing => "racing",
+            Genre::Shooter => "shooter",
+            Genre::Fighting => "fighting",
+            Genre::Platformer => "platformer",
+            Genre::Puzzle => "puzzle",
+            Genre::Horror => "horror",
+            Genre::Party => "party",
+        }
+    }
+
+    /// Returns the human-readable name of this genre.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Genre::Action => "Action",
+            Genre::Adventure => "Adventure",
+            Genre::Rpg => "Role-Playing",
+            Genre::Strategy => "Strategy",
+            Genre::Simulation => "Simulation",
+            Genre::Sports => "Sports",
+            Genre::Racing => "Racing",
+            Genre::Shooter => "Shooter",
+            Genre::Fighting => "Fighting",
+            Genre::Platformer => "Platformer",
+            Genre::Puzzle => "Puzzle",
+            Genre::Horror => "Horror",
+            Genre::Party => "Party & Family",
+        }
+    }
+}
+
+/// The maximum number of genres per listing.
+pub const MAX_GENRES: usize = 3;
+
+/// Ensures a listing has at most `MAX_GENRES` genres, each listed once.
+pub fn validate_genres(genres: &[Genre]) -> Result<(), ValidationError> {
+    let distinct = genres
+        .iter()
+        .enumerate()
+        .all(|(i, genre)| !genres[..i].contains(genre));
+    if genres.len() > MAX_GENRES {
+        Err(ValidationError::new("genres")
+            .with_message(format!("At most {} genres are allowed", MAX_GENRES).into()))
+    } else if !distinct {
+        Err(ValidationError::new("genres").with_message("Genres must not repeat".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// The category of a listing together with the attributes required for that category.
+///
+/// Serialized as an object tagged with the category, e.g.
+/// `{"category": "console", "model": "PS4 Pro", "storage_gb": 1000, "firmware": "11.00"}`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "category", rename_all = "snake_case")]
+pub enum OfferAttributes {
+    /// A game. Games have no additional attributes.
+    #[default]
+    Game,
+    /// A console.
+    Console {
+        /// The exact model (e.g. "PS4 Pro", "Switch OLED").
+        model: String,
+        /// The built-in storage size in gigabytes.
+        storage_gb: u32,
+        /// The installed firmware version.
+        firmware: String,
+        /// The console's serial number, checked against the stolen-serial blacklist.
+        #[serde(default)]
+        serial_number: Option<String>,
+    },
+    /// A controller.
+    Controller {
+        /// Whether the controller is wireless.
+        wireless: bool,
+        /// The consoles the controller works with.
+        compatible_with: String,
+    },
+    /// Any other accessory.
+    Accessory {
+        /// What kind of accessory it is (e.g. "memory card", "charging station").
+        accessory_type: String,
+        /// The consoles the accessory works with.
+        compatible_with: String,
+    },
+}
+
+impl OfferAttributes {
+    /// Returns the category these attributes belong to.
+    pub fn category(&self) -> Category {
+        match self {
+            OfferAttributes::Game => Category::Game,
+            OfferAttributes::Console { .. } => Category::Console,
+            OfferAttributes::Controller { .. } => Category::Controller,
+            OfferAttributes::Accessory { .. } => Category::Accessory,
+        }
+    }
+
+    /// Returns the serial number, if the listing has one.
+    pub fn serial_number(&self) -> Option<&str> {
+        match self {
+            OfferAttributes::Console { serial_number, .. } => serial_number.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Converts the attributes to the object stored in the database.
+    fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert("category".into(), Value::from(self.category().as_str()));
+        match self {
+            OfferAttributes::Game => {}
+            OfferAttributes::Console {
+                model,
+                storage_gb,
+                firmware,
+                serial_number,
+            } => {
+                object.insert("model".into(), Value::from(model.as_str()));
+                object.insert("storage_gb".into(), Value::from(*storage_gb));
+                object.insert("firmware".into(), Value::from(firmware.as_str()));
+                if let Some(serial_number) = serial_number {
+                    object.insert("serial_number".into(), Value::from(serial_number.as_str()));
+                }
+            }
+            OfferAttributes::Controller {
+                wireless,
+                compatible_with,
+            } => {
+                object.insert("wireless".into(), Value::from(*wireless));
+                object.insert(
+                    "compatible_with".into(),
+                    Value::from(compatible_with.as_str()),
+                );
+            }
+            OfferAttributes::Accessory {
+                accessory_type,
+                compatible_with,
+            } => {
+                object.insert(
+                    "accessory_type".into(),
+                    Value::from(accessory_type.as_str()),
+                );
+                object.insert(
+                    "compatible_with".into(),
+                    Value::from(compatible_with.as_str()),
+                );
+            }
+        }
+        Value::from(object)
+    }
+}
+
+/// Ensures the required text attributes of a category are filled in and the numbers are plausible.
+pub fn validate_attributes(attributes: &OfferAttributes) -> Result<(), ValidationError> {
+    let invalid = |message: &'static str| {
+        Err(ValidationError::new("attributes").with_message(message.into()))
+    };
+    match attributes {
+        OfferAttributes::Game => Ok(()),
+        OfferAttributes::Console {
+            model,
+            storage_gb,
+            firmware,
+            serial_number,
+        } => {
+            if model.trim().is_empty() {
+                invalid("Console model is required")
+            } else if *storage_gb == 0 {
+                invalid("Console storage size must be greater than 0")
+            } else if firmware.trim().is_empty() {
+                invalid("Console firmware version is required")
+            } else if serial_number
+                .as_deref()
+                .is_some_and(|serial| normalize_serial(serial).is_empty() || serial.len() > 64)
+            {
+                invalid("Console serial number must be 1 to 64 characters long")
+            } else {
+                Ok(())
+            }
+        }
+        OfferAttributes::Controller {
+            compatible_with, ..
+        } => {
+            if compatible_with.trim().is_empty() {
+                invalid("Compatible consoles are required")
+            } else {
+                Ok(())
+            }
+        }
+        OfferAttributes::Accessory {
+            accessory_type,
+            compatible_with,
+        } => {
+            if accessory_type.trim().is_empty() {
+                invalid("Accessory type is required")
+            } else if compatible_with.trim().is_empty() {
+                invalid("Compatible consoles are required")
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The region coding of a game or console.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Region {
+    /// PAL (Europe, Australia and parts of Asia and Africa).
+    Pal,
+    /// NTSC (North America, Japan and parts of South America and Asia).
+    Ntsc,
+    /// Plays on consoles of any region.
+    RegionFree,
+}
+
+impl Region {
+    /// Returns the string stored in the database for this region.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Region::Pal => "pal",
+            Region::Ntsc => "ntsc",
+            Region::RegionFree => "region_free",
+        }
+    }
+}
+
+/// The language of a game's box or manual, as an ISO 639-1 code.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// English.
+    En,
+    /// German.
+    De,
+    /// French.
+    Fr,
+    /// Spanish.
+    Es,
+    /// Italian.
+    It,
+    /// Dutch.
+    Nl,
+    /// Portuguese.
+    Pt,
+    /// Polish.
+    Pl,
+    /// Swedish.
+    Sv,
+    /// Russian.
+    Ru,
+    /// Japanese.
+    Ja,
+    /// Korean.
+    Ko,
+    /// Chinese.
+    Zh,
+    /// Printed in several languages.
+    Multi,
+}
+
+impl Language {
+    /// Returns the string stored in the database for this language.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::De => "de",
+            Language::Fr => "fr",
+            Language::Es => "es",
+            Language::It => "it",
+            Language::Nl => "nl",
+            Language::Pt => "pt",
+            Language::Pl => "pl",
+            Language::Sv => "sv",
+            Language::Ru => "ru",
+            Language::Ja => "ja",
+            Language::Ko => "ko",
+            Language::Zh => "zh",
+            Language::Multi => "multi",
+        }
+    }
+}
+
+/// The PEGI age rating of a game.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AgeRating {
+    /// Suitable for all ages.
+    #[serde(rename = "pegi_3")]
+    Pegi3,
+    /// Suitable for ages 7 and up.
+    #[serde(rename = "pegi_7")]
+    Pegi7,
+    /// Suitable for ages 12 and up.
+    #[serde(rename = "pegi_12")]
+    Pegi12,
+    /// Suitable for ages 16 and up.
+    #[serde(rename = "pegi_16")]
+    Pegi16,
+    /// Mature content, adults only.
+    #[serde(rename = "pegi_18")]
+    Pegi18,
+}
+
+/// The age from which a viewer may see mature-rated listings.
+pub const MATURE_AGE: u32 = 18;
+
+impl AgeRating {
+    /// Returns the string stored in the database for this rating.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AgeRating::Pegi3 => "pegi_3",
+            AgeRating::Pegi7 => "pegi_7",
+            AgeRating::Pegi12 => "pegi_12",
+            AgeRating::Pegi16 => "pegi_16",
+            AgeRating::Pegi18 => "pegi_18",
+        }
+    }
+
+    /// Returns whether the rating marks mature content, which is hidden from minors and anonymous viewers.
+    pub fn is_mature(&self) -> bool {
+        *self == AgeRating::Pegi18
+    }
+}
+
+/// Calculates a person's age in full years.
+///
+/// # Arguments
+///
+/// * `date_of_birth` - The person's date of birth.
+/// * `today` - The date to calculate the age on.
+///
+/// # Returns
+///
+/// The age in full years, or 0 if the date of birth lies after `today`.
+pub fn age_on(date_of_birth: NaiveDate, today: NaiveDate) -> u32 {
+    let had_birthday = (today.month(), today.day()) >= (date_of_birth.month(), date_of_birth.day());
+    let years = today.year() - date_of_birth.year() - i32::from(!had_birthday);
+    u32::try_from(years).unwrap_or(0)
+}
+
+/// What a listing photo shows.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PhotoKind {
+    /// The front of the item or its box.
+    Front,
+    /// The back of the item or its box.
+    Back,
+    /// The cartridge, disc or device itself.
+    Media,
+    /// The intact shrink-wrap of a sealed item.
+    ShrinkWrap,
+    /// The label with the serial number.
+    SerialLabel,
+    /// The manual.
+    Manual,
+    /// Anything else (e.g. close-ups of damage).
+    Other,
+}
+
+impl PhotoKind {
+    /// Returns the string stored in the database for this kind of photo.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PhotoKind::Front => "front",
+            PhotoKind::Back => "back",
+            PhotoKind::Media => "media",
+            PhotoKind::ShrinkWrap => "shrink_wrap",
+            PhotoKind::SerialLabel => "serial_label",
+            PhotoKind::Manual => "manual",
+            PhotoKind::Other => "other",
+        }
+    }
+}
+
+/// A photo attached to a listing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OfferPhoto {
+    /// The HTTPS link to the photo.
+    pub url: String,
+    /// What the photo shows.
+    pub kind: PhotoKind,
+}
+
+impl OfferPhoto {
+    /// Converts the photo to the object stored in the database.
+    fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert("url".into(), Value::from(self.url.as_str()));
+        object.insert("kind".into(), Value::from(self.kind.as_str()));
+        Value::from(object)
+    }
+}
+
+/// The maximum number of photos per listing.
+pub const MAX_PHOTOS: usize = 20;
+
+/// Ensures a listing has at most `MAX_PHOTOS` photos and every photo is an HTTPS link.
+pub fn validate_photos(photos: &[OfferPhoto]) -> Result<(), ValidationError> {
+    if photos.len() > MAX_PHOTOS {
+        Err(ValidationError::new("photos")
+            .with_message(format!("At most {} photos are allowed", MAX_PHOTOS).into()))
+    } else if photos
+        .iter()
+        .any(|photo| !photo.url.starts_with("https://") || photo.url.len() > 2048)
+    {
+        Err(ValidationError::new("photos")
+            .with_message("Photos must be HTTPS links of at most 2048 characters".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// A game sold as part of a bundle listing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BundleItem {
+    /// The title of the game.
+    pub game_title: String,
+    /// The platform the game is for.
+    pub platform: Platform,
+    /// The condition of this game.
+    pub condition: Condition,
+    /// Details on this game's condition, such as scratches or a missing manual.
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+impl BundleItem {
+    /// Converts the item to the object stored in the database.
+    fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert("game_title".into(), Value::from(self.game_title.trim()));
+        object.insert("platform".into(), Value::from(self.platform.as_str()));
+        object.insert("condition".into(), Value::from(self.condition.as_str()));
+        if let Some(notes) = &self.notes {
+            object.insert("notes".into(), Value::from(notes.trim()));
+        }
+        Value::from(object)
+    }
+}
+
+/// The maximum number of games in a bundle.
+pub const MAX_BUNDLE_ITEMS: usize = 20;
+
+/// Ensures a bundle has 2 to `MAX_BUNDLE_ITEMS` games with a title each.
+///
+/// An empty list is a listing of a single game.
+pub fn validate_bundle_items(items: &[BundleItem]) -> Result<(), ValidationError> {
+    if items.len() == 1 || items.len() > MAX_BUNDLE_ITEMS {
+        Err(ValidationError::new("bundle_items")
+            .with_message(format!("A bundle must have 2 to {} games", MAX_BUNDLE_ITEMS).into()))
+    } else if items
+        .iter()
+        .any(|item| !(3..=200).contains(&item.game_title.trim().chars().count()))
+    {
+        Err(ValidationError::new("bundle_items")
+            .with_message("Every game of a bundle needs a title of 3 to 200 characters".into()))
+    } else if items
+        .iter()
+        .filter_map(|item| item.notes.as_ref())
+        .any(|notes| notes.chars().count() > 500)
+    {
+        Err(ValidationError::new("bundle_items")
+            .with_message("Notes on a game must be at most 500 characters long".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// The catalog metadata of an offer.
+///
+/// When creating an offer, missing fields are left unset (and the offer is listed as a game). When
+/// updating an offer, missing fields are left unchanged.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OfferMetadata {
+    /// The category and category-specific attributes.
+    pub attributes: Option<OfferAttributes>,
+    /// The region coding.
+    pub region: Option<Region>,
+    /// The language printed on the box.
+    pub box_language: Option<Language>,
+    /// The language of the manual.
+    pub manual_language: Option<Language>,
+    /// The photos of the item. When updating, replaces all previous photos.
+    pub photos: Option<Vec<OfferPhoto>>,
+    /// The age rating of the game.
+    pub age_rating: Option<AgeRating>,
+    /// The genres of the game. When updating, replaces all previous genres.
+    pub genres: Option<Vec<Genre>>,
+    /// The currency the price is in. Offers without a currency are priced in euros.
+    pub currency: Option<Currency>,
+    /// The games of a bundle listing. When updating, replaces all previous games; an empty list
+    /// turns the bundle back into a listing of a single game.
+    pub bundle_items: Option<Vec<BundleItem>>,
+    /// Where the offer can be picked up. Stored rounded, see `src/database/pickup.rs`.
+    pub pickup: Option<PickupLocation>,
+    /// The number of copies for sale. Offers without a quantity sell one copy.
+    pub quantity: Option<u32>,
+}
+
+impl OfferMetadata {
+    /// Adds an assignment for every set field to a `SET` clause and binds its value.
+    ///
+    /// # Arguments
+    ///
+    /// * `updates` - The assignments of the `SET` clause.
+    /// * `vars` - The variables bound to the query.
+    pub(super) fn push_assignments(
+        &self,
+        updates: &mut Vec<String>,
+        vars: &mut BTreeMap<String, Value>,
+    ) {
+        if let Some(attributes) = &self.attributes {
+            updates.push("attributes = $attributes".to_string());
+            vars.insert("attributes".into(), attributes.to_value());
+        }
+        if let Some(region) = self.region {
+            updates.push("region = $region".to_string());
+            vars.insert("region".into(), Value::from(region.as_str()));
+        }
+        if let Some(language) = self.box_language {
+            updates.push("box_language = $box_language".to_string());
+            vars.insert("box_language".into(), Value::from(language.as_str()));
+        }
+        if let Some(language) = self.manual_language {
+            updates.push("manual_language = $manual_language".to_string());
+            vars.insert("manual_language".into(), Value::from(language.as_str()));
+        }
+        if let Some(rating) = self.age_rating {
+            updates.push("age_rating = $age_rating".to_string());
+            vars.insert("age_rating".into(), Value::from(rating.as_str()));
+        }
+        if let Some(genres) = &self.genres {
+            updates.push("genres = $genres".to_string());
+            vars.insert(
+                "genres".into(),
+                Value::from(
+                    genres
+                        .iter()
+                        .map(|genre| Value::from(genre.as_str()))
+                        .collect::<Vec<Value>>(),
+                ),
+            );
+        }
+        if let Some(currency) = self.currency {
+            updates.push("currency = $currency".to_string());
+            vars.insert("currency".into(), Value::from(currency.as_str()));
+        }
+        if let Some(photos) = &self.photos {
+            updates.push("photos = $photos".to_string());
+            vars.insert(
+                "photos".into(),
+                Value::from(
+                    photos
+                        .iter()
+                        .map(OfferPhoto::to_value)
+                        .collect::<Vec<Value>>(),
+                ),
+            );
+        }
+        if let Some(quantity) = self.quantity {
+            updates.push("quantity = $quantity".to_string());
+            vars.insert("quantity".into(), Value::from(quantity));
+        }
+        if let Some(pickup) = &self.pickup {
+            updates.push("pickup = $pickup".to_string());
+            vars.insert("pickup".into(), pickup.to_value());
+        }
+        if let Some(items) = &self.bundle_items {
+            // The titles and platforms of the games are also stored flat for search and filters
+            updates.push("bundle_items = $bundle_items".to_string());
+            updates.push("bundle_titles = $bundle_titles".to_string());
+            updates.push("bundle_platforms = $bundle_platforms".to_string());
+            vars.insert(
+                "bundle_items".into(),
+                Value::from(
+                    items
+                        .iter()
+                        .map(BundleItem::to_value)
+                        .collect::<Vec<Value>>(),
+                ),
+            );
+            vars.insert(
+                "bundle_titles".into(),
+                Value::from(
+                    items
+                        .iter()
+                        .map(|item| item.game_title.trim())
+                        .collect::<Vec<&str>>()
+                        .join("\n"),
+                ),
+            );
+            vars.insert(
+                "bundle_platforms".into(),
+                Value::from(
+                    items
+                        .iter()
+                        .map(|item| Value::from(item.platform.as_str()))
+                        .collect::<Vec<Value>>(),
+                ),
+            );
+        }
+    }
+}
+
+/// Defines the indexes used to filter offers by their catalog metadata.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_offer_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE INDEX offers_region ON offers FIELDS region",
+        "offers_region index on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_category ON offers FIELDS attributes.category",
+        "offers_category index on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_genres ON offers FIELDS genres",
+        "offers_genres index on offers",
+    )
+    .await;
+}
+
+/// Filters applied when searching the public offer listings.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Validate)]
+#[validate(schema(function = "validate_price_range"))]
+#[validate(schema(function = "validate_radius"))]
+pub struct OfferFilter {
+    /// Only return offers of this category.
+    pub category: Option<Category>,
+    /// Only return offers of this genre.
+    pub genre: Option<Genre>,
+    /// Only return offers with this region coding.
+    pub region: Option<Region>,
+    /// Only return offers whose box or manual is in this language.
+    pub language: Option<Language>,
+    /// Only return offers that do (or do not) carry the "authenticated" badge.
+    pub authenticated: Option<bool>,
+    /// Only return offers of sellers who do (or do not) carry the verified seller badge.
+    pub verified: Option<bool>,
+    /// Only return bundles (`true`) or listings of a single item (`false`).
+    pub bundle: Option<bool>,
+    /// Only return offers for this platform. Bundles match if any of their games is for the
+    /// platform.
+    pub platform: Option<Platform>,
+    /// Only return offers in this condition.
+    pub condition: Option<Condition>,
+    /// Only return offers costing at least this much in `currency`.
+    #[validate(range(min = 0.0, message = "Minimum price must not be negative"))]
+    pub min_price: Option<f64>,
+    /// Only return offers costing at most this much in `currency`.
+    #[validate(range(min = 0.0, message = "Maximum price must not be negative"))]
+    pub max_price: Option<f64>,
+    /// Only return offers that can be picked up near this `<latitude>,<longitude>` point.
+    #[validate(custom(function = "validate_near"))]
+    pub near: Option<String>,
+    /// The distance from `near` in kilometres. Defaults to `DEFAULT_RADIUS_KM`.
+    #[validate(range(
+        exclusive_min = 0.0,
+        max = MAX_RADIUS_KM,
+        message = "Radius must be greater than 0 and at most 200 km"
+    ))]
+    pub radius: Option<f64>,
+    /// Only return offers of this seller (user ID).
+    #[validate(length(min = 1, message = "Seller must not be empty"))]
+    pub seller: Option<String>,
+    /// The currency prices are compared, sorted and displayed in. Defaults to the viewer's
+    /// preferred currency.
+    pub currency: Option<Currency>,
+    /// The order of the offers. Defaults to the newest first.
+    #[serde(default)]
+    pub sort: OfferSort,
+}
+
+/// Ensures the minimum price of a filter does not exceed its maximum price.
+fn validate_price_range(filter: &OfferFilter) -> Result<(), ValidationError> {
+    match (filter.min_price, filter.max_price) {
+        (Some(min), Some(max)) if min > max => Err(ValidationError::new("price_range")
+            .with_message("Minimum price must not exceed maximum price".into())),
+        _ => Ok(()),
+    }
+}
+
+/// Ensures a filter only has a radius together with a point to measure it from.
+fn validate_radius(filter: &OfferFilter) -> Result<(), ValidationError> {
+    if filter.radius.is_some() && filter.near.is_none() {
+        Err(ValidationError::new("radius").with_message("radius requires near".into()))
+    } else {
+        Ok(())
+    }
+}
+
+impl OfferFilter {
+    /// Adds a condition for every set filter to a `WHERE` clause and binds its value.
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - The conditions of the `WHERE` clause.
+    /// * `vars` - The variables bound to the query.
+    /// * `price` - The expression of the offer's price in the filter's currency, see
+    ///   `converted_price_sql`.
+    pub(super) fn push_conditions(
+        &self,
+        conditions: &mut Vec<String>,
+        vars: &mut BTreeMap<String, Value>,
+        price: &str,
+    ) {
+        if let Some(category) = self.category {
+            // Offers listed before categories existed have no attributes and are games
+            conditions.push("(attributes.category ?? 'game') = $category".to_string());
+            vars.insert("category".into(), Value::from(category.as_str()));
+        }
+        if let Some(genre) = self.genre {
+            conditions.push("genres CONTAINS $genre".to_string());
+            vars.insert("genre".into(), Value::from(genre.as_str()));
+        }
+        if let Some(region) = self.region {
+            conditions.push("region = $region".to_string());
+            vars.insert("region".into(), Value::from(region.as_str()));
+        }
+        if let Some(language) = self.language {
+            conditions
+                .push("(box_language = $language OR manual_language = $language)".to_string());
+            vars.insert("language".into(), Value::from(language.as_str()));
+        }
+        if let Some(authenticated) = self.authenticated {
+            conditions.push("(authenticated ?? false) = $authenticated".to_string());
+            vars.insert("authenticated".into(), Value::from(authenticated));
+        }
+        if let Some(verified) = self.verified {
+            conditions.push("(seller_verified ?? false) = $verified".to_string());
+            vars.insert("verified".into(), Value::from(verified));
+        }
+        if let Some(bundle) = self.bundle {
+            conditions.push("(array::len(bundle_items ?? []) > 0) = $bundle".to_string());
+            vars.insert("bundle".into(), Value::from(bundle));
+        }
+        if let Some(platform) = self.platform {
+            conditions.push(
+                "(platform = $platform OR (bundle_platforms ?? []) CONTAINS $platform)".to_string(),
+            );
+            vars.insert("platform".into(), Value::from(platform.as_str()));
+        }
+        if let Some(condition) = self.condition {
+            conditions.push("condition = $condition".to_string());
+            vars.insert("condition".into(), Value::from(condition.as_str()));
+        }
+        if let Some(min_price) = self.min_price {
+            conditions.push(format!("{} >= $min_price", price));
+            vars.insert("min_price".into(), Value::from(min_price));
+        }
+        if let Some(max_price) = self.max_price {
+            conditions.push(format!("{} <= $max_price", price));
+            vars.insert("max_price".into(), Value::from(max_price));
+        }
+        if let Some(near) = self.near.as_deref().and_then(parse_near) {
+            let radius = self.radius.unwrap_or(DEFAULT_RADIUS_KM);
+            push_distance_condition(near, radius, conditions, vars);
+        }
+        if let Some(seller) = &self.seller {
+            conditions.push("seller_id = $seller".to_string());
+            vars.insert(
+                "seller".into(),
+                Value::from(UserId::new(seller.trim()).to_reference()),
+            );
+        }
+    }
+}
+
+/// The order offers are listed in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferSort {
+    /// The newest offers first, after the featured offers.
+    #[default]
+    Newest,
+    /// The cheapest offers first.
+    PriceAsc,
+    /// The most expensive offers first.
+    PriceDesc,
+}
+
+impl OfferSort {
+    /// Returns the `ORDER BY` clause of the order. Price orders sort by the `sort_price` field
+    /// and the newest first order by the `is_featured` field, which must be selected.
+    pub(super) fn order_by(&self) -> &'static str {
+        match self {
+            OfferSort::Newest => "is_featured DESC, created_at DESC",
+            OfferSort::PriceAsc => "sort_price ASC, created_at DESC",
+            OfferSort::PriceDesc => "sort_price DESC, created_at DESC",
+        }
+    }
+}
+
+/// Builds the expression converting an offer's price into a currency and binds the rates.
+///
+/// Offers without a currency are priced in euros. Without exchange rates, or for currencies
+/// without a rate, prices are compared unconverted.
+///
+/// # Arguments
+///
+/// * `rates` - The current exchange rates, if known.
+/// * `to` - The currency to convert into.
+/// * `vars` - The variables bound to the query.
+///
+/// # Returns
+///
+/// The SurrealQL expression.
+pub(super) fn converted_price_sql(
+    rates: Option<&ExchangeRates>,
+    to: Currency,
+    vars: &mut BTreeMap<String, Value>,
+) -> String {
+    let Some(rates) = rates else {
+        return "price".to_string();
+    };
+    let mut cases = Vec::new();
+    for from in Currency::ALL {
+        if let Some(factor) = rates.factor(from, to).filter(|factor| *factor != 1.0) {
+            let name = format!("rate_{}", from.as_str().to_lowercase());
+            cases.push(format!(
+                "IF (currency ?? 'EUR') = '{}' THEN price * ${}",
+                from.as_str(),
+                name
+            ));
+            vars.insert(name, Value::from(factor));
+        }
+    }
+    if cases.is_empty() {
+        return "price".to_string();
+    }
+    format!("({} ELSE price END)", cases.join(" ELSE "))
+}
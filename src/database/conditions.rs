@@ -0,0 +1,138 @@
+//! src/database/conditions.rs
+//!
+//! This module defines the conditions items are listed in. Conditions used to be free text; values
+//! stored before the list existed are mapped to the closest condition on startup.
+
+use super::platforms::normalize_field;
+
+use serde::{Deserialize, Serialize};
+use std::process::exit;
+use surrealdb::{Surreal, engine::local::Db};
+
+/// The condition of a listed item.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case", try_from = "String")]
+pub enum Condition {
+    /// Unused, usually still sealed.
+    New,
+    /// Used, without visible wear.
+    LikeNew,
+    /// Used, with light signs of use.
+    Good,
+    /// Used, with clear signs of use but fully working.
+    Acceptable,
+    /// Not working, sold for repair or spare parts.
+    ForParts,
+}
+
+impl Condition {
+    /// Every condition, from best to worst.
+    pub const ALL: [Condition; 5] = [
+        Condition::New,
+        Condition::LikeNew,
+        Condition::Good,
+        Condition::Acceptable,
+        Condition::ForParts,
+    ];
+
+    /// Returns the string stored in the database for this condition.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Condition::New => "new",
+            Condition::LikeNew => "like_new",
+            Condition::Good => "good",
+            Condition::Acceptable => "acceptable",
+            Condition::ForParts => "for_parts",
+        }
+    }
+
+    /// Returns the human-readable name of this condition.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Condition::New => "New",
+            Condition::LikeNew => "Like New",
+            Condition::Good => "Good",
+            Condition::Acceptable => "Acceptable",
+            Condition::ForParts => "For Parts",
+        }
+    }
+
+    /// Finds the condition a name refers to.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The stored value or label, compared without case, spaces and punctuation, e.g.
+    ///   `like_new`, `Like New` or `LikeNew`.
+    ///
+    /// # Returns
+    ///
+    /// The `Condition`, or `None` if the name matches none.
+    pub fn parse(name: &str) -> Option<Condition> {
+        let key = compact(name);
+        Condition::ALL
+            .into_iter()
+            .find(|condition| compact(condition.as_str()) == key)
+    }
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Condition::parse(&name).ok_or_else(|| {
+            format!(
+                "unknown condition `{}`, expected one of new, like_new, good, acceptable, for_parts",
+                name.trim()
+            )
+        })
+    }
+}
+
+/// Lowercases a name and removes everything but letters and digits.
+fn compact(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Returns the condition closest to a value stored before the condition list existed.
+///
+/// Values that match no condition become `Acceptable`, so no listing is presented as better than
+/// its seller described it.
+pub(super) fn normalize_stored(name: &str) -> Condition {
+    if let Some(condition) = Condition::parse(name) {
+        return condition;
+    }
+    match compact(name).as_str() {
+        "sealed" | "mint" | "brandnew" | "factorysealed" | "unopened" => Condition::New,
+        "verygood" | "excellent" | "nearmint" | "asnew" => Condition::LikeNew,
+        "used" | "complete" | "cib" | "completeinbox" => Condition::Good,
+        "broken" | "defective" | "damaged" | "parts" | "notworking" | "forrepair" => {
+            Condition::ForParts
+        }
+        _ => Condition::Acceptable,
+    }
+}
+
+/// Normalizes the conditions of offers, listing rules and the price history stored before the
+/// condition list existed. The conditions of bundle games are normalized with their platforms,
+/// see `src/database/platforms.rs`.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    let normalized = async {
+        for table in ["offers", "listing_rules", "price_history"] {
+            normalize_field(db, table, "condition", |name| {
+                normalize_stored(name).as_str()
+            })
+            .await?;
+        }
+        Ok::<_, surrealdb::Error>(())
+    }
+    .await;
+    if let Err(error) = normalized {
+        tracing::error!("Error normalizing stored conditions: {}", error);
+        exit(1);
+    }
+}
@@ -0,0 +1,300 @@
+//! src/database/config_bundle.rs
+//!
+//! This module exports the admin-managed platform configuration (the fee schedule, the listing
+//! rules and the serial blacklist) as a single bundle and imports it again, so settings tested on
+//! staging can be promoted to production without entering them again.
+//!
+//! Bundles are signed with HMAC-SHA256 using the `CONFIG_BUNDLE_SECRET` shared by the
+//! environments, so only bundles exported by one of them are accepted. Feature flags are included
+//! for comparison only; they come from the environment and aren't changed by an import.
+
+use super::catalog::{Category, PhotoKind};
+use super::conditions::Condition;
+use super::fees::FeeRuleFields;
+use super::listing_rules::RequiredField;
+use super::{Database, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use base64::{Engine as base64Engine, engine::general_purpose};
+use chrono::Utc;
+use dotenvy::var;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeSet;
+
+/// The format version of exported bundles. Bundles of other versions are rejected.
+pub const CONFIG_BUNDLE_VERSION: u32 = 1;
+/// The minimum length of the signing secret, in bytes.
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// The values of a listing rule in a bundle.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ListingRuleFields {
+    /// The category the rule applies to.
+    pub category: Category,
+    /// The condition the rule applies to, or `None` for every condition.
+    pub condition: Option<Condition>,
+    /// The kinds of photos the listing must include.
+    pub required_photos: Vec<PhotoKind>,
+    /// The fields the listing must fill in.
+    pub required_fields: Vec<RequiredField>,
+}
+
+/// A blacklisted serial number in a bundle.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BlacklistEntry {
+    /// The normalized serial number.
+    pub serial: String,
+    /// Where the report came from.
+    pub note: String,
+}
+
+/// The platform configuration.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConfigBundle {
+    /// The format version (`CONFIG_BUNDLE_VERSION`).
+    pub version: u32,
+    /// The timestamp when the bundle was exported.
+    pub exported_at: String,
+    /// The fee schedule.
+    pub fee_rules: Vec<FeeRuleFields>,
+    /// The listing rules.
+    pub listing_rules: Vec<ListingRuleFields>,
+    /// The serial blacklist.
+    pub serial_blacklist: Vec<BlacklistEntry>,
+    /// The feature flags enabled where the bundle was exported.
+    pub feature_flags: BTreeSet<String>,
+}
+
+/// A bundle with the signature of its JSON encoding.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SignedConfigBundle {
+    /// The configuration.
+    pub bundle: ConfigBundle,
+    /// The base64-encoded HMAC-SHA256 of the bundle's JSON encoding.
+    pub signature: String,
+}
+
+/// The outcome of importing a bundle.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ConfigImport {
+    /// The number of imported fee rules.
+    pub fee_rules: usize,
+    /// The number of imported listing rules.
+    pub listing_rules: usize,
+    /// The number of imported blacklisted serials.
+    pub serial_blacklist: usize,
+    /// The feature flags of the bundle that aren't enabled here.
+    pub flags_missing: BTreeSet<String>,
+    /// The feature flags enabled here that the bundle doesn't enable.
+    pub flags_extra: BTreeSet<String>,
+}
+
+/// Reads the secret bundles are signed with (`CONFIG_BUNDLE_SECRET`).
+///
+/// # Returns
+///
+/// A `Result` containing the secret, or an `EnvironmentVariableError` if it is unset or shorter
+/// than 32 bytes.
+pub fn bundle_secret() -> Result<Vec<u8>, CustomError> {
+    let secret = var("CONFIG_BUNDLE_SECRET")?;
+    if secret.len() < MIN_SECRET_LENGTH {
+        return Err(CustomError::EnvironmentVariableError(format!(
+            "CONFIG_BUNDLE_SECRET must be at least {} bytes long",
+            MIN_SECRET_LENGTH
+        )));
+    }
+    Ok(secret.into_bytes())
+}
+
+/// Computes the MAC of a bundle's JSON encoding.
+fn bundle_mac(bundle: &ConfigBundle, secret: &[u8]) -> Result<Hmac<Sha256>, CustomError> {
+    let encoded = serde_json::to_vec(bundle).map_err(|_| CustomError::EncryptionError)?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret).map_err(|_| CustomError::EncryptionError)?;
+    mac.update(b"gameshop config bundle v1:");
+    mac.update(&encoded);
+    Ok(mac)
+}
+
+impl ConfigBundle {
+    /// Signs the bundle.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The signing secret (see `bundle_secret`).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the signed bundle or a `CustomError` if it can't be encoded.
+    pub fn sign(self, secret: &[u8]) -> Result<SignedConfigBundle, CustomError> {
+        let signature = bundle_mac(&self, secret)?.finalize().into_bytes();
+        Ok(SignedConfigBundle {
+            bundle: self,
+            signature: general_purpose::STANDARD.encode(signature),
+        })
+    }
+}
+
+impl SignedConfigBundle {
+    /// Returns whether the signature matches the bundle, comparing in constant time.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The signing secret (see `bundle_secret`).
+    pub fn verify(&self, secret: &[u8]) -> Result<bool, CustomError> {
+        let Ok(signature) = general_purpose::STANDARD.decode(&self.signature) else {
+            return Ok(false);
+        };
+        Ok(bundle_mac(&self.bundle, secret)?
+            .verify_slice(&signature)
+            .is_ok())
+    }
+}
+
+impl Database {
+    /// Exports the platform configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `feature_flags` - The feature flags enabled in the running configuration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the unsigned `ConfigBundle` or a `CustomError` if retrieval fails.
+    pub async fn export_config_bundle(
+        &self,
+        feature_flags: BTreeSet<String>,
+    ) -> Result<ConfigBundle, CustomError> {
+        let fee_rules = self
+            .get_fee_rules()
+            .await?
+            .into_iter()
+            .map(|rule| FeeRuleFields {
+                category: rule.category,
+                seller_tier: rule.seller_tier,
+                percent: rule.percent,
+                fixed: rule.fixed,
+                promotion: rule.promotion,
+                description: rule.description,
+                effective_from: rule.effective_from,
+                effective_until: rule.effective_until,
+            })
+            .collect();
+        let listing_rules = self
+            .get_listing_rules(None)
+            .await?
+            .into_iter()
+            .map(|rule| ListingRuleFields {
+                category: rule.category,
+                condition: rule.condition,
+                required_photos: rule.required_photos,
+                required_fields: rule.required_fields,
+            })
+            .collect();
+        let serial_blacklist = self
+            .get_blacklisted_serials()
+            .await?
+            .into_iter()
+            .map(|entry| BlacklistEntry {
+                serial: entry.serial,
+                note: entry.note,
+            })
+            .collect();
+
+        Ok(ConfigBundle {
+            version: CONFIG_BUNDLE_VERSION,
+            exported_at: Utc::now().to_rfc3339(),
+            fee_rules,
+            listing_rules,
+            serial_blacklist,
+            feature_flags,
+        })
+    }
+
+    /// Replaces the platform configuration with the contents of a bundle.
+    ///
+    /// Every section is replaced as a whole, so entries missing from the bundle are removed.
+    /// Imported entries are recorded as created by the importing admin. Orders keep the fee they
+    /// were placed with.
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle` - The verified bundle.
+    /// * `feature_flags` - The feature flags enabled in the running configuration.
+    /// * `admin_id` - The ID of the admin importing the bundle.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ConfigImport` summary or a `CustomError` if a step fails. The
+    /// import isn't atomic; running it again after a failure completes it.
+    pub async fn import_config_bundle(
+        &self,
+        bundle: &ConfigBundle,
+        feature_flags: &BTreeSet<String>,
+        admin_id: &str,
+    ) -> Result<ConfigImport, CustomError> {
+        tracing::info!(
+            "Importing configuration bundle exported at {}",
+            bundle.exported_at
+        );
+        for rule in self.get_fee_rules().await? {
+            self.delete_fee_rule(record_key(&rule.id)).await?;
+        }
+        for fields in &bundle.fee_rules {
+            self.create_fee_rule(fields, admin_id.to_string()).await?;
+        }
+
+        for rule in self.get_listing_rules(None).await? {
+            self.delete_listing_rule(record_key(&rule.id)).await?;
+        }
+        for fields in &bundle.listing_rules {
+            self.create_listing_rule(
+                fields.category,
+                fields.condition,
+                fields.required_photos.clone(),
+                fields.required_fields.clone(),
+                admin_id.to_string(),
+            )
+            .await?;
+        }
+
+        let imported: BTreeSet<&str> = bundle
+            .serial_blacklist
+            .iter()
+            .map(|entry| entry.serial.as_str())
+            .collect();
+        let existing = self.get_blacklisted_serials().await?;
+        for entry in &existing {
+            if !imported.contains(entry.serial.as_str()) {
+                self.remove_blacklisted_serial(entry.serial.clone()).await?;
+            }
+        }
+        for entry in &bundle.serial_blacklist {
+            if !existing.iter().any(|e| e.serial == entry.serial) {
+                self.add_blacklisted_serial(
+                    entry.serial.clone(),
+                    entry.note.clone(),
+                    admin_id.to_string(),
+                )
+                .await?;
+            }
+        }
+
+        Ok(ConfigImport {
+            fee_rules: bundle.fee_rules.len(),
+            listing_rules: bundle.listing_rules.len(),
+            serial_blacklist: bundle.serial_blacklist.len(),
+            flags_missing: bundle
+                .feature_flags
+                .difference(feature_flags)
+                .cloned()
+                .collect(),
+            flags_extra: feature_flags
+                .difference(&bundle.feature_flags)
+                .cloned()
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,459 @@
+//! src/database/conversations.rs
+//!
+//! This module handles direct messages between a buyer and a seller. Every conversation belongs
+//! to an offer and, once the offer was ordered, to the order. Message bodies are stored encrypted,
+//! each bound to its message record (see `encrypt_bound`).
+//!
+//! New messages, read receipts and typing indicators are published as `ChatEvent`s through an
+//! in-process channel, from which the chat WebSockets deliver them to connected participants.
+
+use super::{Database, define, encrypt_fields_blocking, record_key};
+use crate::cpu_pool::CpuPool;
+use crate::encryption::{decrypt_bound_strict, field_aad, generate_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// The maximum length of a message, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// The field a message body is encrypted in.
+const BODY_FIELD: &str = "encrypted_body";
+
+/// The number of chat events buffered for slow WebSockets before they miss some.
+const CHAT_EVENT_CAPACITY: usize = 1024;
+
+/// Represents a conversation between the buyer and the seller of an offer.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Conversation {
+    /// The conversation's ID.
+    pub id: Thing,
+    /// The ID of the offer the conversation is about.
+    pub offer_id: String,
+    /// The ID of the order the conversation is about, if the offer was ordered.
+    #[serde(default)]
+    pub order_id: Option<String>,
+    /// The ID of the (prospective) buyer.
+    pub buyer_id: String,
+    /// The ID of the seller.
+    pub seller_id: String,
+    /// The timestamp when the conversation was started.
+    pub created_at: String,
+    /// The timestamp of the latest message.
+    pub last_message_at: String,
+    /// The timestamp when the buyer last read the conversation.
+    #[serde(default)]
+    pub buyer_read_at: Option<String>,
+    /// The timestamp when the seller last read the conversation.
+    #[serde(default)]
+    pub seller_read_at: Option<String>,
+}
+
+impl Conversation {
+    /// Returns whether the given user is the conversation's buyer or seller.
+    pub fn involves(&self, user_id: &str) -> bool {
+        self.buyer_id == user_id || self.seller_id == user_id
+    }
+
+    /// Returns the other participant of the conversation.
+    pub fn other_participant(&self, user_id: &str) -> &str {
+        if self.buyer_id == user_id {
+            &self.seller_id
+        } else {
+            &self.buyer_id
+        }
+    }
+}
+
+/// Represents a decrypted message.
+#[derive(Debug, Serialize, Clone)]
+pub struct Message {
+    /// The message's ID.
+    pub id: String,
+    /// The ID of the conversation.
+    pub conversation_id: String,
+    /// The ID of the user who sent the message.
+    pub sender_id: String,
+    /// The message text.
+    pub body: String,
+    /// The timestamp when the message was sent.
+    pub created_at: String,
+}
+
+/// A live update of a conversation, as sent to the chat WebSockets.
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatEvent {
+    /// A participant sent a message.
+    Message {
+        /// The sent message.
+        message: Message,
+    },
+    /// A participant is typing. Typing indicators aren't stored.
+    Typing {
+        /// The ID of the conversation.
+        conversation_id: String,
+        /// The ID of the typing participant.
+        user_id: String,
+    },
+    /// A participant read the conversation up to `read_at`.
+    Read {
+        /// The ID of the conversation.
+        conversation_id: String,
+        /// The ID of the participant who read the conversation.
+        user_id: String,
+        /// The timestamp when the conversation was read.
+        read_at: String,
+    },
+}
+
+/// A chat event with the users it is delivered to.
+#[derive(Debug, Clone)]
+pub struct ChatDelivery {
+    /// The IDs of the users receiving the event.
+    pub recipients: Vec<String>,
+    /// The event.
+    pub event: ChatEvent,
+}
+
+/// Publishes chat events to the connected WebSockets.
+#[derive(Debug, Clone)]
+pub struct ChatEvents(broadcast::Sender<ChatDelivery>);
+
+impl Default for ChatEvents {
+    fn default() -> Self {
+        ChatEvents(broadcast::channel(CHAT_EVENT_CAPACITY).0)
+    }
+}
+
+impl ChatEvents {
+    /// Publishes an event to the given users.
+    pub fn publish(&self, recipients: Vec<String>, event: ChatEvent) {
+        // Sending only fails if no WebSocket is connected
+        let _ = self.0.send(ChatDelivery { recipients, event });
+    }
+
+    /// Subscribes to the events of all users.
+    pub fn subscribe(&self) -> broadcast::Receiver<ChatDelivery> {
+        self.0.subscribe()
+    }
+}
+
+/// A message as stored in the database.
+#[derive(Debug, Deserialize)]
+struct StoredMessage {
+    id: Thing,
+    conversation_id: String,
+    sender_id: String,
+    encrypted_body: String,
+    created_at: String,
+}
+
+/// Defines the `conversations` and `messages` tables.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE conversations SCHEMALESS;",
+        "conversations table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD last_message_at ON conversations TYPE datetime;",
+        "last_message_at field on conversations",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD buyer_read_at ON conversations TYPE option<datetime>;",
+        "buyer_read_at field on conversations",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD seller_read_at ON conversations TYPE option<datetime>;",
+        "seller_read_at field on conversations",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX conversations_offer_buyer ON conversations FIELDS offer_id, buyer_id UNIQUE",
+        "conversations_offer_buyer index on conversations",
+    )
+    .await;
+    define(db, "DEFINE TABLE messages SCHEMALESS;", "messages table").await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON messages TYPE datetime;",
+        "created_at field on messages",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX messages_conversation ON messages FIELDS conversation_id, created_at",
+        "messages_conversation index on messages",
+    )
+    .await;
+}
+
+/// Decrypts stored messages on the `CpuPool`.
+///
+/// # Arguments
+///
+/// * `stored` - The stored messages.
+///
+/// # Returns
+///
+/// A `Result` containing the decrypted messages in the same order, or a `DecryptionError` or
+/// `UnboundValue` if a body is invalid.
+async fn decrypt_messages(stored: Vec<StoredMessage>) -> Result<Vec<Message>, CustomError> {
+    CpuPool::global()
+        .run(move || {
+            let key_bytes: [u8; 32] = generate_key()?.into();
+            stored
+                .into_iter()
+                .map(|message| {
+                    let id = record_key(&message.id);
+                    let body = decrypt_bound_strict(
+                        &key_bytes,
+                        &message.encrypted_body,
+                        &field_aad(&id, BODY_FIELD),
+                    )?;
+                    Ok(Message {
+                        id,
+                        conversation_id: message.conversation_id,
+                        sender_id: message.sender_id,
+                        body,
+                        created_at: message.created_at,
+                    })
+                })
+                .collect()
+        })
+        .await?
+}
+
+impl Database {
+    /// Starts a conversation about an offer, or returns the existing one between the buyer and
+    /// the seller.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `order_id` - The ID of the order, if the conversation is about one. It is also recorded
+    ///   on an existing conversation.
+    /// * `buyer_id` - The ID of the buyer.
+    /// * `seller_id` - The ID of the seller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Conversation` or a `CustomError` if it cannot be created.
+    pub async fn start_conversation(
+        &self,
+        offer_id: &str,
+        order_id: Option<&str>,
+        buyer_id: &str,
+        seller_id: &str,
+    ) -> Result<Conversation, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "LET $existing = (SELECT * FROM conversations WHERE offer_id = $offer_id AND buyer_id = $buyer_id); IF $existing = [] THEN (CREATE conversations SET offer_id = $offer_id, order_id = $order_id, buyer_id = $buyer_id, seller_id = $seller_id, created_at = time::now(), last_message_at = time::now()) ELSE IF $order_id != NONE THEN (UPDATE conversations SET order_id = $order_id WHERE offer_id = $offer_id AND buyer_id = $buyer_id RETURN AFTER) ELSE $existing END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+        vars.insert("order_id".into(), Value::from(order_id));
+        vars.insert("buyer_id".into(), Value::from(buyer_id));
+        vars.insert("seller_id".into(), Value::from(seller_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let conversation: Option<Conversation> = response.take(1)?;
+        conversation
+            .ok_or_else(|| CustomError::DatabaseError("Failed to start conversation".to_string()))
+    }
+
+    /// Retrieves a conversation by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation_id` - The ID of the conversation.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Conversation`, or `None` if it does not exist.
+    pub async fn get_conversation(
+        &self,
+        conversation_id: &str,
+    ) -> Result<Option<Conversation>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM type::thing('conversations', $conversation_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("conversation_id".into(), Value::from(conversation_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let conversation: Option<Conversation> = response.take(0)?;
+        Ok(conversation)
+    }
+
+    /// Retrieves the conversations a user takes part in, most recently active first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Conversation` structs or a `CustomError` if retrieval
+    /// fails.
+    pub async fn get_conversations_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<Conversation>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM conversations WHERE buyer_id = $user_id OR seller_id = $user_id ORDER BY last_message_at DESC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let conversations: Vec<Conversation> = response.take(0)?;
+        Ok(conversations)
+    }
+
+    /// Stores an encrypted message in a conversation and publishes it to both participants.
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation` - The conversation.
+    /// * `sender_id` - The ID of the participant sending the message.
+    /// * `body` - The message text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the sent `Message` or a `CustomError` if it cannot be stored.
+    pub async fn send_message(
+        &self,
+        conversation: &Conversation,
+        sender_id: &str,
+        body: String,
+    ) -> Result<Message, CustomError> {
+        let message_id = Uuid::new_v4().to_string();
+        let [encrypted_body] =
+            encrypt_fields_blocking(message_id.clone(), [(BODY_FIELD, body.clone())]).await?;
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let conversation_id = record_key(&conversation.id);
+        tracing::info!(
+            "Sending message {} in conversation {}",
+            message_id,
+            conversation_id
+        );
+        let sql = "CREATE type::thing('messages', $message_id) SET conversation_id = $conversation_id, sender_id = $sender_id, encrypted_body = $encrypted_body, created_at = time::now(); UPDATE type::thing('conversations', $conversation_id) SET last_message_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("message_id".into(), Value::from(message_id.as_str()));
+        vars.insert(
+            "conversation_id".into(),
+            Value::from(conversation_id.as_str()),
+        );
+        vars.insert("sender_id".into(), Value::from(sender_id));
+        vars.insert("encrypted_body".into(), Value::from(encrypted_body));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<StoredMessage> = response.take(0)?;
+        let created = created
+            .ok_or_else(|| CustomError::DatabaseError("Failed to send message".to_string()))?;
+        let message = Message {
+            id: message_id,
+            conversation_id,
+            sender_id: created.sender_id,
+            body,
+            created_at: created.created_at,
+        };
+        self.chat_events.publish(
+            vec![
+                conversation.buyer_id.clone(),
+                conversation.seller_id.clone(),
+            ],
+            ChatEvent::Message {
+                message: message.clone(),
+            },
+        );
+        Ok(message)
+    }
+
+    /// Records that a participant read a conversation and publishes the read receipt to both
+    /// participants.
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation` - The conversation.
+    /// * `user_id` - The ID of the participant who read it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the timestamp the conversation was read at or a `CustomError` if it
+    /// cannot be stored.
+    pub async fn mark_conversation_read(
+        &self,
+        conversation: &Conversation,
+        user_id: &str,
+    ) -> Result<String, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let field = if conversation.buyer_id == user_id {
+            "buyer_read_at"
+        } else {
+            "seller_read_at"
+        };
+        let conversation_id = record_key(&conversation.id);
+        let sql = format!(
+            "UPDATE type::thing('conversations', $conversation_id) SET {field} = time::now() RETURN VALUE {field};"
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "conversation_id".into(),
+            Value::from(conversation_id.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let read_at: Option<String> = response.take(0)?;
+        let read_at = read_at.ok_or_else(|| {
+            CustomError::DatabaseError("Failed to mark conversation as read".to_string())
+        })?;
+        self.chat_events.publish(
+            vec![
+                conversation.buyer_id.clone(),
+                conversation.seller_id.clone(),
+            ],
+            ChatEvent::Read {
+                conversation_id,
+                user_id: user_id.to_string(),
+                read_at: read_at.clone(),
+            },
+        );
+        Ok(read_at)
+    }
+
+    /// Retrieves and decrypts the messages of a conversation, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation_id` - The ID of the conversation.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the messages or a `CustomError` if retrieval or decryption fails.
+    pub async fn get_messages(&self, conversation_id: &str) -> Result<Vec<Message>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM messages WHERE conversation_id = $conversation_id ORDER BY created_at ASC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("conversation_id".into(), Value::from(conversation_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let stored: Vec<StoredMessage> = response.take(0)?;
+        decrypt_messages(stored).await
+    }
+}
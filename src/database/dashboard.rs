@@ -0,0 +1,192 @@
+//! src/database/dashboard.rs
+//!
+//! This module computes the platform-wide key figures of the admin dashboard: new users, new
+//! offers, gross merchandise volume (GMV, the value of all orders that weren't cancelled) and
+//! abuse reports per day, and the sellers with the highest GMV. Everything is aggregated by the
+//! database.
+
+use super::catalog::converted_price_sql;
+use super::preferences::Currency;
+use super::{Count, Database};
+use crate::errors::custom_errors::CustomError;
+use crate::exchange_rates::ExchangeRateCache;
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+
+/// The default number of days the dashboard covers.
+pub const DASHBOARD_DEFAULT_DAYS: u32 = 30;
+
+/// The maximum number of days the dashboard covers.
+pub const DASHBOARD_MAX_DAYS: u32 = 365;
+
+/// A count on one day.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DailyCount {
+    /// The day, as `YYYY-MM-DD` in UTC.
+    pub day: String,
+    /// The count on the day.
+    pub count: u64,
+}
+
+/// The gross merchandise volume of one day.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DailyVolume {
+    /// The day, as `YYYY-MM-DD` in UTC.
+    pub day: String,
+    /// The number of orders placed on the day that weren't cancelled.
+    pub orders: u64,
+    /// The sum of the prices of the orders, in the dashboard's currency.
+    pub volume: f64,
+}
+
+/// The platform-wide key figures per day.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DashboardKpis {
+    /// The number of days covered, including today.
+    pub days: u32,
+    /// The currency the volumes are in.
+    pub currency: Currency,
+    /// The users who registered per day.
+    pub new_users: Vec<DailyCount>,
+    /// The offers created per day. Renewed offers count on the day of their renewal.
+    pub new_offers: Vec<DailyCount>,
+    /// The orders and their value per day of placement.
+    pub gmv: Vec<DailyVolume>,
+    /// The abuse reports filed per day, by users and by the automatic checks.
+    pub reports: Vec<DailyCount>,
+    /// The number of reports currently waiting for a moderator.
+    pub open_reports: u64,
+    /// The timestamp when the figures were computed.
+    pub computed_at: String,
+}
+
+/// A seller ranked by the value of their orders.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TopSeller {
+    /// The ID of the seller.
+    pub seller_id: String,
+    /// The username of the seller, or `None` if the account was deleted.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// The number of orders of the seller's offers that weren't cancelled.
+    pub orders: u64,
+    /// The sum of the prices of the orders, in the dashboard's currency.
+    pub volume: f64,
+}
+
+/// Returns the first instant of the period of the given number of days that ends today.
+///
+/// # Arguments
+///
+/// * `days` - The number of days, including today.
+///
+/// # Returns
+///
+/// The start of the period, as an RFC 3339 timestamp.
+pub fn period_start(days: u32) -> String {
+    let first_day = Utc::now().date_naive() - Duration::days(i64::from(days.max(1)) - 1);
+    format!("{}T00:00:00Z", first_day.format("%Y-%m-%d"))
+}
+
+/// Rounds an amount of money to cents.
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+impl Database {
+    /// Computes the platform-wide key figures per day.
+    ///
+    /// Order prices in other currencies are converted to euros with the current exchange rates.
+    ///
+    /// # Arguments
+    ///
+    /// * `days` - The number of days covered, including today.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DashboardKpis` or a `CustomError` if an aggregation fails.
+    pub async fn compute_dashboard_kpis(&self, days: u32) -> Result<DashboardKpis, CustomError> {
+        let since = period_start(days);
+        let rates = ExchangeRateCache::global().rates().await;
+
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT time::format(created_at, '%Y-%m-%d') AS day, count() AS count FROM users WHERE created_at >= type::datetime($since) GROUP BY day ORDER BY day;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("since".into(), Value::from(since.as_str()));
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let new_users: Vec<DailyCount> = response.take(0)?;
+
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let price = converted_price_sql(rates.as_ref(), Currency::Eur, &mut vars);
+        let sql = format!(
+            "SELECT time::format(created_at, '%Y-%m-%d') AS day, count() AS count FROM offers WHERE created_at >= type::datetime($since) GROUP BY day ORDER BY day;
+            SELECT time::format(created_at, '%Y-%m-%d') AS day, count() AS orders, math::sum({price}) AS volume FROM orders WHERE state != 'cancelled' AND created_at >= type::datetime($since) GROUP BY day ORDER BY day;
+            SELECT time::format(created_at, '%Y-%m-%d') AS day, count() AS count FROM reports WHERE created_at >= type::datetime($since) GROUP BY day ORDER BY day;
+            SELECT count() FROM reports WHERE status = 'open' GROUP ALL;"
+        );
+        vars.insert("since".into(), Value::from(since.as_str()));
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let new_offers: Vec<DailyCount> = response.take(0)?;
+        let mut gmv: Vec<DailyVolume> = response.take(1)?;
+        let reports: Vec<DailyCount> = response.take(2)?;
+        let open_reports: Option<Count> = response.take(3)?;
+        for day in &mut gmv {
+            day.volume = round_cents(day.volume);
+        }
+
+        Ok(DashboardKpis {
+            days,
+            currency: Currency::Eur,
+            new_users,
+            new_offers,
+            gmv,
+            reports,
+            open_reports: open_reports.map_or(0, |count| count.count),
+            computed_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Ranks the sellers by the value of the orders placed in a period.
+    ///
+    /// Order prices in other currencies are converted to euros with the current exchange rates.
+    ///
+    /// # Arguments
+    ///
+    /// * `days` - The number of days covered, including today.
+    /// * `limit` - The maximum number of sellers returned.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the sellers, the highest volume first, or a `CustomError` if the
+    /// aggregation fails.
+    pub async fn get_top_sellers(
+        &self,
+        days: u32,
+        limit: u32,
+    ) -> Result<Vec<TopSeller>, CustomError> {
+        let rates = ExchangeRateCache::global().rates().await;
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let price = converted_price_sql(rates.as_ref(), Currency::Eur, &mut vars);
+        let sql = format!(
+            "SELECT seller_id, count() AS orders, math::sum({price}) AS volume FROM orders WHERE state != 'cancelled' AND created_at >= type::datetime($since) GROUP BY seller_id ORDER BY volume DESC LIMIT $limit;"
+        );
+        vars.insert("since".into(), Value::from(period_start(days)));
+        vars.insert("limit".into(), Value::from(limit));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let mut sellers: Vec<TopSeller> = response.take(0)?;
+        for seller in &mut sellers {
+            seller.volume = round_cents(seller.volume);
+            seller.username = self
+                .get_user_by_id(seller.seller_id.clone())
+                .await?
+                .map(|user| user.username);
+        }
+        Ok(sellers)
+    }
+}
@@ -0,0 +1,216 @@
+//! src/database/dead_letters.rs
+//!
+//! This module handles the dead-lettered background jobs: jobs that failed on their queue are
+//! stored with the error, so admins can inspect them and retry or discard them later.
+
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+use crate::job_queue::Queue;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The maximum number of dead letters listed at once.
+pub const MAX_LISTED_DEAD_LETTERS: usize = 500;
+
+/// A failed job, with everything needed to run it again.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeadLetterJob {
+    /// The generation of the thumbnail of an offer image.
+    Thumbnail {
+        /// The ID of the offer the image belongs to.
+        offer_id: String,
+        /// The path the image is served from.
+        url: String,
+    },
+}
+
+impl DeadLetterJob {
+    /// Returns the queue the job runs on.
+    pub fn queue(&self) -> Queue {
+        match self {
+            DeadLetterJob::Thumbnail { .. } => Queue::Images,
+        }
+    }
+
+    /// Converts the job to the object stored in the database.
+    fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        match self {
+            DeadLetterJob::Thumbnail { offer_id, url } => {
+                object.insert("kind".into(), Value::from("thumbnail"));
+                object.insert("offer_id".into(), Value::from(offer_id.as_str()));
+                object.insert("url".into(), Value::from(url.as_str()));
+            }
+        }
+        Value::from(object)
+    }
+}
+
+/// Represents a dead-lettered job.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeadLetter {
+    /// The dead letter's ID.
+    pub id: Thing,
+    /// The queue the job ran on.
+    pub queue: Queue,
+    /// The job.
+    pub job: DeadLetterJob,
+    /// Why the job failed.
+    pub error: String,
+    /// The timestamp when the job failed.
+    pub failed_at: String,
+}
+
+/// Defines the `dead_letters` table.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE dead_letters SCHEMALESS;",
+        "dead_letters table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD failed_at ON dead_letters TYPE datetime;",
+        "failed_at field on dead_letters",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX dead_letters_queue ON dead_letters FIELDS queue",
+        "dead_letters_queue index on dead_letters",
+    )
+    .await;
+}
+
+impl Database {
+    /// Stores a failed job.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - The failed job.
+    /// * `error` - Why the job failed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `DeadLetter` or a `CustomError` if creation fails.
+    pub async fn record_dead_letter(
+        &self,
+        job: &DeadLetterJob,
+        error: &str,
+    ) -> Result<DeadLetter, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::warn!(
+            "Dead-lettering job on queue {}: {}",
+            job.queue().as_str(),
+            error
+        );
+        let sql = "CREATE dead_letters SET queue = $queue, job = $job, error = $error, failed_at = time::now();";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("queue".into(), Value::from(job.queue().as_str()));
+        vars.insert("job".into(), job.to_value());
+        vars.insert("error".into(), Value::from(error));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<DeadLetter> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created dead letter after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created dead letter".to_string())
+        })
+    }
+
+    /// Retrieves the most recent dead letters, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - Restricts the list to the jobs of this queue (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing at most `MAX_LISTED_DEAD_LETTERS` dead letters or a `CustomError` if
+    /// retrieval fails.
+    pub async fn get_dead_letters(
+        &self,
+        queue: Option<Queue>,
+    ) -> Result<Vec<DeadLetter>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let filter = match queue {
+            Some(queue) => {
+                vars.insert("queue".into(), Value::from(queue.as_str()));
+                "WHERE queue = $queue"
+            }
+            None => "",
+        };
+        let sql = format!(
+            "SELECT * FROM dead_letters {} ORDER BY failed_at DESC LIMIT {};",
+            filter, MAX_LISTED_DEAD_LETTERS
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let letters: Vec<DeadLetter> = response.take(0)?;
+        Ok(letters)
+    }
+
+    /// Retrieves a single dead letter by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead_letter_id` - The ID of the dead letter to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `DeadLetter` or a `CustomError` if retrieval fails.
+    pub async fn get_dead_letter(
+        &self,
+        dead_letter_id: String,
+    ) -> Result<Option<DeadLetter>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM type::thing('dead_letters', $dead_letter_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "dead_letter_id".into(),
+            Value::from(dead_letter_id.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let letter: Option<DeadLetter> = response.take(0)?;
+        Ok(letter)
+    }
+
+    /// Deletes a dead letter, e.g. after its job was requeued or discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead_letter_id` - The ID of the dead letter to delete.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the dead letter existed and was deleted, or `false` if it
+    /// was already gone (e.g. retried by another admin).
+    pub async fn delete_dead_letter(&self, dead_letter_id: String) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Deleting dead letter with ID: {}", dead_letter_id);
+        let sql = "DELETE type::thing('dead_letters', $dead_letter_id) RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "dead_letter_id".into(),
+            Value::from(dead_letter_id.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Option<DeadLetter> = response.take(0)?;
+        Ok(deleted.is_some())
+    }
+}
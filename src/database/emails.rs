@@ -0,0 +1,108 @@
+//! src/database/emails.rs
+//!
+//! This module addresses emails to users. The address is decrypted only to render the message,
+//! which is then sent on the `emails` job queue, so requests don't wait for the mail server.
+
+use super::Database;
+use super::field_encryption::decrypt_user_field;
+use crate::cpu_pool::CpuPool;
+use crate::email::EmailTemplate;
+use crate::encryption::{field_aad, generate_key};
+use crate::errors::custom_errors::CustomError;
+use crate::job_queue::{JobQueues, Queue};
+use crate::metrics::TaskOutcome;
+
+impl Database {
+    /// Queues an email to a user.
+    ///
+    /// Deleted accounts don't receive emails. A message that can't be sent is logged and recorded
+    /// as a failure of the `emails` queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the recipient.
+    /// * `template` - The email to send.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the email was queued, `false` if the user doesn't exist or
+    /// deleted their account, or a `CustomError` if the address can't be decrypted.
+    pub async fn queue_email(
+        &self,
+        user_id: &str,
+        template: EmailTemplate,
+    ) -> Result<bool, CustomError> {
+        let Some(user) = self
+            .get_user_by_id(user_id.to_string())
+            .await?
+            .filter(|user| user.deleted_at.is_none())
+        else {
+            return Ok(false);
+        };
+
+        let aad = field_aad(user_id, "encrypted_email");
+        let encrypted = user.encrypted_email;
+        let strict = self.user_fields_bound();
+        let address = CpuPool::global()
+            .run(move || {
+                let key_bytes: [u8; 32] = generate_key()?.into();
+                decrypt_user_field(strict, &key_bytes, &encrypted, &aad)
+            })
+            .await??;
+        let message = template.render(address, &user.username);
+
+        let sender = self.email_sender.clone();
+        JobQueues::global().enqueue(Queue::Emails, async move {
+            match sender.send(&message).await {
+                Ok(()) => TaskOutcome::Success,
+                Err(e) => {
+                    tracing::error!("Failed to send email \"{}\": {:?}", message.subject, e);
+                    TaskOutcome::Failure
+                }
+            }
+        });
+        Ok(true)
+    }
+
+    /// Forwards a notification by email if the user enabled email notifications and didn't mute
+    /// its kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the notified user.
+    /// * `kind` - The notification's kind.
+    /// * `title` - The notification's title.
+    /// * `body` - The notification's body.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if an email was queued, or a `CustomError` if the user or the
+    /// address can't be loaded.
+    pub async fn email_notification(
+        &self,
+        user_id: &str,
+        kind: &str,
+        title: &str,
+        body: &str,
+    ) -> Result<bool, CustomError> {
+        let wants_email = self
+            .get_user_preferences(user_id.to_string())
+            .await?
+            .is_some_and(|preferences| {
+                preferences.notifications.email
+                    && !preferences
+                        .notifications
+                        .muted_kinds
+                        .iter()
+                        .any(|muted| muted == kind)
+            });
+        if !wants_email {
+            return Ok(false);
+        }
+        let template = EmailTemplate::Notification {
+            title: title.to_string(),
+            body: body.to_string(),
+        };
+        self.queue_email(user_id, template).await
+    }
+}
@@ -0,0 +1,143 @@
+//! src/database/favorites.rs
+//!
+//! This module handles users' favorite offers, stored as `favorites` relations from the user to
+//! the offer. Each offer keeps a count of the users who favorited it, so every offer response
+//! includes it without counting the relations again.
+
+use super::ids::{OfferId, UserId};
+use super::{Database, Offer, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// Defines the `favorites` relation table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE favorites TYPE RELATION IN user OUT offers SCHEMALESS;",
+        "favorites table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX favorites_unique ON favorites FIELDS in, out UNIQUE",
+        "favorites_unique index on favorites",
+    )
+    .await;
+}
+
+/// A favorited offer, as selected from the `favorites` relation.
+#[derive(Debug, Deserialize)]
+struct FavoriteRow {
+    /// The offer, missing if it was deleted.
+    offer: Option<Offer>,
+}
+
+/// Returns the bound variables identifying a user and, optionally, an offer.
+fn favorite_vars(user_id: &str, offer_id: Option<&str>) -> BTreeMap<String, Value> {
+    let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+    vars.insert(
+        "user".into(),
+        Value::from(UserId::new(user_id).to_reference()),
+    );
+    if let Some(offer_id) = offer_id {
+        vars.insert(
+            "offer".into(),
+            Value::from(OfferId::new(offer_id).to_thing()),
+        );
+    }
+    vars
+}
+
+impl Database {
+    /// Adds an offer to a user's favorites. Favoriting an offer twice has no effect.
+    ///
+    /// The count is recomputed from the relations, so concurrent changes can't skew it.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, `None` if the offer doesn't exist, or a
+    /// `CustomError` if the update fails.
+    pub async fn add_favorite(
+        &self,
+        user_id: &str,
+        offer_id: &str,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("User {} favorites offer {}", user_id, offer_id);
+        let sql = "IF record::exists($offer) AND array::len(SELECT id FROM favorites WHERE in = $user AND out = $offer) = 0 {
+                RELATE $user->favorites->$offer SET created_at = time::now();
+            };
+            UPDATE $offer SET favorites_count = count(<-favorites) RETURN AFTER;";
+
+        let mut response: surrealdb::Response = self
+            .db
+            .query(sql)
+            .bind(favorite_vars(user_id, Some(offer_id)))
+            .await?;
+        let updated: Option<Offer> = response.take(1)?;
+        Ok(updated)
+    }
+
+    /// Removes an offer from a user's favorites.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, `None` if the offer doesn't exist, or a
+    /// `CustomError` if the update fails.
+    pub async fn remove_favorite(
+        &self,
+        user_id: &str,
+        offer_id: &str,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("User {} unfavorites offer {}", user_id, offer_id);
+        let sql = "DELETE favorites WHERE in = $user AND out = $offer;
+            UPDATE $offer SET favorites_count = count(<-favorites) RETURN AFTER;";
+
+        let mut response: surrealdb::Response = self
+            .db
+            .query(sql)
+            .bind(favorite_vars(user_id, Some(offer_id)))
+            .await?;
+        let updated: Option<Offer> = response.take(1)?;
+        Ok(updated)
+    }
+
+    /// Retrieves a user's favorite offers, most recently favorited first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the favorited offers or a `CustomError` if retrieval fails.
+    pub async fn get_favorite_offers(&self, user_id: &str) -> Result<Vec<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Retrieving favorite offers of user {}", user_id);
+        let sql = "SELECT out.* AS offer, created_at FROM favorites WHERE in = $user ORDER BY created_at DESC;";
+
+        let mut response: surrealdb::Response = self
+            .db
+            .query(sql)
+            .bind(favorite_vars(user_id, None))
+            .await?;
+        let rows: Vec<FavoriteRow> = response.take(0)?;
+        Ok(rows.into_iter().filter_map(|row| row.offer).collect())
+    }
+}
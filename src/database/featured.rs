@@ -0,0 +1,127 @@
+//! src/database/featured.rs
+//!
+//! This module handles featured offers. Admins feature an offer for a number of days, for curated
+//! or paid placements. Featured offers are listed by the featured offers route and ranked first
+//! among the newest offers until `featured_until` has passed.
+
+use super::offer_status::OfferStatus;
+use super::pagination::{PageInfo, Pagination};
+use super::{Count, Database, Offer, define, listed_offer_conditions};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The maximum number of days an offer can be featured for at once.
+pub const MAX_FEATURED_DAYS: u32 = 90;
+
+/// The condition of an offer being featured right now.
+pub(super) const FEATURED_CONDITION: &str = "featured_until > time::now()";
+
+/// Defines the `featured_until` field on `offers` and the index featured offers are found with.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD featured_until ON offers TYPE option<datetime>;",
+        "featured_until field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_featured_until ON offers FIELDS featured_until",
+        "offers_featured_until index on offers",
+    )
+    .await;
+}
+
+impl Database {
+    /// Features an active offer for a number of days from now, replacing an earlier end.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `days` - How many days the offer is featured for.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the featured offer, or `None` if the offer doesn't exist, is deleted
+    /// or isn't active.
+    pub async fn feature_offer(
+        &self,
+        offer_id: &str,
+        days: u32,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Featuring offer {} for {} days", offer_id, days);
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET featured_until = time::now() + {}d WHERE deleted_at = NONE AND (status ?? $active) = $active RETURN AFTER;",
+            days
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let featured: Option<Offer> = response.take(0)?;
+        Ok(featured)
+    }
+
+    /// Ends the featured placement of an offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the offer, or `None` if the offer isn't featured.
+    pub async fn unfeature_offer(&self, offer_id: &str) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Ending featured placement of offer {}", offer_id);
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET featured_until = NONE WHERE {} RETURN AFTER;",
+            FEATURED_CONDITION
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let unfeatured: Option<Offer> = response.take(0)?;
+        Ok(unfeatured)
+    }
+
+    /// Retrieves the publicly listed offers that are featured right now.
+    ///
+    /// # Arguments
+    ///
+    /// * `include_mature` - Whether mature-rated offers are included (only for adult viewers).
+    /// * `pagination` - The page to return.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the requested page of offers, the most recently listed first, and its
+    /// pagination details, or a `CustomError` if retrieval fails.
+    pub async fn query_featured_offers(
+        &self,
+        include_mature: bool,
+        pagination: &Pagination,
+    ) -> Result<(Vec<Offer>, PageInfo), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let mut conditions = listed_offer_conditions(include_mature, &mut vars);
+        conditions.push(FEATURED_CONDITION.to_string());
+        pagination.bind(&mut vars);
+
+        let sql = format!(
+            "SELECT * FROM offers WHERE {conditions} ORDER BY created_at DESC LIMIT $limit START $start; SELECT count() FROM offers WHERE {conditions} GROUP ALL;",
+            conditions = conditions.join(" AND ")
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Vec<Offer> = response.take(0)?;
+        let total: Option<Count> = response.take(1)?;
+        let total = total.map_or(0, |total| total.count);
+        Ok((offers, PageInfo::new(pagination, total)))
+    }
+}
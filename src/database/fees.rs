@@ -0,0 +1,408 @@
+//! src/database/fees.rs
+//!
+//! This module handles the platform fee schedule. Admins maintain fee rules per category and
+//! seller tier, including time-limited promotional rates, and the pricing evaluates them when an
+//! order is placed to determine the fee the platform keeps from the sale.
+
+use super::catalog::Category;
+use super::{Count, Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The number of completed sales from which a seller is established.
+pub const ESTABLISHED_SELLER_SALES: u64 = 10;
+/// The number of completed sales from which a seller is a top seller.
+pub const TOP_SELLER_SALES: u64 = 100;
+
+/// The tier of a seller, derived from the number of their completed sales.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SellerTier {
+    /// Fewer than `ESTABLISHED_SELLER_SALES` completed sales.
+    New,
+    /// Fewer than `TOP_SELLER_SALES` completed sales.
+    Established,
+    /// At least `TOP_SELLER_SALES` completed sales.
+    Top,
+}
+
+impl SellerTier {
+    /// Returns the string stored in the database for this tier.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SellerTier::New => "new",
+            SellerTier::Established => "established",
+            SellerTier::Top => "top",
+        }
+    }
+
+    /// Returns the tier of a seller with the given number of completed sales.
+    pub fn for_completed_sales(sales: u64) -> SellerTier {
+        if sales >= TOP_SELLER_SALES {
+            SellerTier::Top
+        } else if sales >= ESTABLISHED_SELLER_SALES {
+            SellerTier::Established
+        } else {
+            SellerTier::New
+        }
+    }
+}
+
+/// Represents a rule of the fee schedule.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FeeRule {
+    /// The rule's ID.
+    pub id: Thing,
+    /// The category the rule applies to, or `None` for every category.
+    #[serde(default)]
+    pub category: Option<Category>,
+    /// The seller tier the rule applies to, or `None` for every tier.
+    #[serde(default)]
+    pub seller_tier: Option<SellerTier>,
+    /// The share of the price the platform keeps, in percent.
+    pub percent: f64,
+    /// The fixed amount added to the fee of every sale.
+    pub fixed: f64,
+    /// Whether the rule is a promotion. Promotions take precedence over regular rules.
+    #[serde(default)]
+    pub promotion: bool,
+    /// A description of the rule shown to admins.
+    pub description: String,
+    /// The timestamp from which the rule applies.
+    pub effective_from: String,
+    /// The timestamp from which the rule no longer applies, or `None` if it doesn't expire.
+    #[serde(default)]
+    pub effective_until: Option<String>,
+    /// The ID of the admin who created the rule.
+    pub created_by: String,
+    /// The timestamp when the rule was created.
+    pub created_at: String,
+    /// The timestamp of the last change.
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// The values of a fee rule set by an admin.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FeeRuleFields {
+    /// The category the rule applies to, or `None` for every category.
+    pub category: Option<Category>,
+    /// The seller tier the rule applies to, or `None` for every tier.
+    pub seller_tier: Option<SellerTier>,
+    /// The share of the price the platform keeps, in percent.
+    pub percent: f64,
+    /// The fixed amount added to the fee of every sale.
+    pub fixed: f64,
+    /// Whether the rule is a promotion.
+    pub promotion: bool,
+    /// A description of the rule.
+    pub description: String,
+    /// The RFC 3339 timestamp from which the rule applies.
+    pub effective_from: String,
+    /// The RFC 3339 timestamp from which the rule no longer applies.
+    pub effective_until: Option<String>,
+}
+
+/// Parses a timestamp stored in the database.
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|parsed| parsed.with_timezone(&Utc))
+}
+
+impl FeeRule {
+    /// Returns whether the rule applies to a sale in the given category by a seller of the given
+    /// tier at the given time.
+    pub fn applies_to(&self, category: Category, tier: SellerTier, now: DateTime<Utc>) -> bool {
+        let started = parse_timestamp(&self.effective_from).is_some_and(|from| from <= now);
+        let ended = self
+            .effective_until
+            .as_deref()
+            .is_some_and(|until| parse_timestamp(until).is_none_or(|until| until <= now));
+        started
+            && !ended
+            && self.category.is_none_or(|c| c == category)
+            && self.seller_tier.is_none_or(|t| t == tier)
+    }
+
+    /// Returns how specific the rule is: the number of criteria it restricts.
+    pub fn specificity(&self) -> u8 {
+        u8::from(self.category.is_some()) + u8::from(self.seller_tier.is_some())
+    }
+}
+
+/// Selects the fee rule that applies to a sale.
+///
+/// Promotions take precedence over regular rules. Among those, the most specific rule wins, and
+/// among equally specific rules the one that took effect last.
+///
+/// # Arguments
+///
+/// * `rules` - The fee schedule.
+/// * `category` - The category of the sold offer.
+/// * `tier` - The tier of the seller.
+/// * `now` - The time of the sale.
+///
+/// # Returns
+///
+/// The rule to apply, or `None` if no rule applies and the sale is free of fees.
+pub fn select_fee_rule(
+    rules: &[FeeRule],
+    category: Category,
+    tier: SellerTier,
+    now: DateTime<Utc>,
+) -> Option<&FeeRule> {
+    rules
+        .iter()
+        .filter(|rule| rule.applies_to(category, tier, now))
+        .max_by_key(|rule| {
+            (
+                rule.promotion,
+                rule.specificity(),
+                parse_timestamp(&rule.effective_from),
+            )
+        })
+}
+
+/// The platform fee of a sale.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FeeQuote {
+    /// The ID of the applied fee rule, or `None` if no rule applied.
+    pub rule_id: Option<String>,
+    /// The fee the platform keeps.
+    pub fee: f64,
+    /// The amount the seller receives.
+    pub seller_payout: f64,
+}
+
+impl FeeQuote {
+    /// Computes the fee of a sale at the given price.
+    ///
+    /// The fee is rounded to cents and never exceeds the price.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` - The price of the sale.
+    /// * `rule` - The applied fee rule, or `None` for a sale free of fees.
+    ///
+    /// # Returns
+    ///
+    /// The `FeeQuote` of the sale.
+    pub fn for_price(price: f64, rule: Option<&FeeRule>) -> FeeQuote {
+        let fee = rule.map_or(0.0, |rule| {
+            let fee = price * rule.percent / 100.0 + rule.fixed;
+            ((fee * 100.0).round() / 100.0).clamp(0.0, price)
+        });
+        FeeQuote {
+            rule_id: rule.map(|rule| record_key(&rule.id)),
+            fee,
+            seller_payout: ((price - fee) * 100.0).round() / 100.0,
+        }
+    }
+}
+
+/// Defines the `fee_rules` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE fee_rules SCHEMALESS;", "fee_rules table").await;
+    define(
+        db,
+        "DEFINE FIELD effective_from ON fee_rules TYPE datetime;",
+        "effective_from field on fee_rules",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD effective_until ON fee_rules TYPE option<datetime>;",
+        "effective_until field on fee_rules",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON fee_rules TYPE datetime;",
+        "created_at field on fee_rules",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD updated_at ON fee_rules TYPE option<datetime>;",
+        "updated_at field on fee_rules",
+    )
+    .await;
+}
+
+/// Binds the fields of a fee rule to query variables.
+fn bind_fee_rule_fields(vars: &mut BTreeMap<String, Value>, fields: &FeeRuleFields) {
+    vars.insert(
+        "category".into(),
+        Value::from(fields.category.map(|c| c.as_str())),
+    );
+    vars.insert(
+        "seller_tier".into(),
+        Value::from(fields.seller_tier.map(|t| t.as_str())),
+    );
+    vars.insert("percent".into(), Value::from(fields.percent));
+    vars.insert("fixed".into(), Value::from(fields.fixed));
+    vars.insert("promotion".into(), Value::from(fields.promotion));
+    vars.insert(
+        "description".into(),
+        Value::from(fields.description.as_str()),
+    );
+    vars.insert(
+        "effective_from".into(),
+        Value::from(fields.effective_from.as_str()),
+    );
+    vars.insert(
+        "effective_until".into(),
+        Value::from(fields.effective_until.clone()),
+    );
+}
+
+impl Database {
+    /// Creates a new fee rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The values of the rule.
+    /// * `admin_id` - The ID of the admin creating the rule.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `FeeRule` or a `CustomError` if creation fails.
+    pub async fn create_fee_rule(
+        &self,
+        fields: &FeeRuleFields,
+        admin_id: String,
+    ) -> Result<FeeRule, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Creating fee rule \"{}\"", fields.description);
+        let sql = "CREATE fee_rules SET category = $category, seller_tier = $seller_tier, percent = $percent, fixed = $fixed, promotion = $promotion, description = $description, effective_from = <datetime> $effective_from, effective_until = IF $effective_until = NONE THEN NONE ELSE <datetime> $effective_until END, created_by = $admin_id, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        bind_fee_rule_fields(&mut vars, fields);
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<FeeRule> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created fee rule after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created fee rule".to_string())
+        })
+    }
+
+    /// Replaces the values of a fee rule.
+    ///
+    /// Orders placed before the change keep the fee they were placed with.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule_id` - The ID of the rule to update.
+    /// * `fields` - The new values of the rule.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated `FeeRule`, or `None` if the rule doesn't exist.
+    pub async fn update_fee_rule(
+        &self,
+        rule_id: String,
+        fields: &FeeRuleFields,
+    ) -> Result<Option<FeeRule>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Updating fee rule {}", rule_id);
+        let sql = "UPDATE type::thing('fee_rules', $rule_id) SET category = $category, seller_tier = $seller_tier, percent = $percent, fixed = $fixed, promotion = $promotion, description = $description, effective_from = <datetime> $effective_from, effective_until = IF $effective_until = NONE THEN NONE ELSE <datetime> $effective_until END, updated_at = time::now() RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        bind_fee_rule_fields(&mut vars, fields);
+        vars.insert("rule_id".into(), Value::from(rule_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<FeeRule> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Deletes a fee rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule_id` - The ID of the rule to delete.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the rule existed.
+    pub async fn delete_fee_rule(&self, rule_id: String) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Deleting fee rule {}", rule_id);
+        let sql = "DELETE type::thing('fee_rules', $rule_id) RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("rule_id".into(), Value::from(rule_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Option<FeeRule> = response.take(0)?;
+        Ok(deleted.is_some())
+    }
+
+    /// Retrieves the fee schedule, including expired and upcoming rules.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `FeeRule` structs or a `CustomError` if retrieval fails.
+    pub async fn get_fee_rules(&self) -> Result<Vec<FeeRule>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM fee_rules ORDER BY effective_from DESC;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let rules: Vec<FeeRule> = response.take(0)?;
+        Ok(rules)
+    }
+
+    /// Counts the completed sales of a seller.
+    ///
+    /// # Arguments
+    ///
+    /// * `seller_id` - The ID of the seller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of completed orders the user sold.
+    pub async fn count_completed_sales(&self, seller_id: &str) -> Result<u64, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT count() FROM orders WHERE seller_id = $seller_id AND state = 'completed' GROUP ALL;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("seller_id".into(), Value::from(seller_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let count: Option<Count> = response.take(0)?;
+        Ok(count.map_or(0, |count| count.count))
+    }
+
+    /// Computes the platform fee of selling an offer at the given price.
+    ///
+    /// The rule is selected from the rules in effect now, by the offer's category and the
+    /// seller's current tier.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The sold offer.
+    /// * `price` - The price the offer is sold at.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FeeQuote` or a `CustomError` if the schedule can't be read.
+    pub async fn quote_fee(&self, offer: &Offer, price: f64) -> Result<FeeQuote, CustomError> {
+        let sales = self
+            .count_completed_sales(&record_key(&offer.seller_id))
+            .await?;
+        let tier = SellerTier::for_completed_sales(sales);
+        let rules = self.get_fee_rules().await?;
+        let rule = select_fee_rule(&rules, offer.attributes.category(), tier, Utc::now());
+        Ok(FeeQuote::for_price(price, rule))
+    }
+}
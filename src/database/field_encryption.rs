@@ -0,0 +1,269 @@
+//! src/database/field_encryption.rs
+//!
+//! This module re-encrypts users' personal information stored before ciphertexts were bound to
+//! their record and field, so swapping encrypted values between users or fields is detected. It
+//! also fills in the blind indexes of names stored before they could be looked up.
+//!
+//! Once no unbound values are left, a marker is stored and user fields are decrypted strictly, so
+//! an unbound value written later can't skip the binding check.
+
+use super::blind_index::blind_index;
+use super::{Count, Database, User, define, record_key};
+use crate::cpu_pool::CpuPool;
+use crate::encryption::{
+    BOUND_PREFIX, decrypt_bound, decrypt_bound_strict, encrypt_bound, field_aad, generate_key,
+    is_bound,
+};
+use crate::errors::custom_errors::CustomError;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The encrypted fields of a user.
+pub const ENCRYPTED_USER_FIELDS: [&str; 4] = [
+    "encrypted_firstname",
+    "encrypted_lastname",
+    "encrypted_email",
+    "encrypted_date_of_birth",
+];
+
+/// The outcome of a field encryption migration.
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FieldEncryptionMigration {
+    /// The number of users whose fields were re-encrypted or indexed.
+    pub migrated: usize,
+    /// The number of users whose fields could not be re-encrypted, e.g. because they were
+    /// changed during the migration. Running the migration again retries them.
+    pub failed: usize,
+}
+
+/// Defines the `migrations` table holding the completion marker of the migration.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE migrations SCHEMALESS;",
+        "migrations table",
+    )
+    .await;
+}
+
+/// Returns the conditions matching users with an encrypted field that isn't bound yet. The
+/// `$bound_prefix` variable must be bound to `BOUND_PREFIX`.
+fn unbound_field_conditions() -> Vec<String> {
+    ENCRYPTED_USER_FIELDS
+        .iter()
+        .map(|field| {
+            format!(
+                "({} != NONE AND !string::starts_with({}, $bound_prefix))",
+                field, field
+            )
+        })
+        .collect()
+}
+
+/// Decrypts an encrypted user field.
+///
+/// # Arguments
+///
+/// * `strict` - Whether unbound values are rejected, see `Database::user_fields_bound`.
+/// * `key_bytes` - The encryption key.
+/// * `encoded` - The stored value.
+/// * `aad` - The associated data of the user and field.
+///
+/// # Returns
+///
+/// A `Result` containing the decrypted string or a `CustomError` if the value is invalid.
+pub(super) fn decrypt_user_field(
+    strict: bool,
+    key_bytes: &[u8; 32],
+    encoded: &str,
+    aad: &[u8],
+) -> Result<String, CustomError> {
+    if strict {
+        decrypt_bound_strict(key_bytes, encoded, aad)
+    } else {
+        decrypt_bound(key_bytes, encoded, aad)
+    }
+}
+
+/// Returns the stored values of a user's encrypted fields, in the order of
+/// `ENCRYPTED_USER_FIELDS`.
+fn encrypted_fields(user: &User) -> [Option<&String>; 4] {
+    [
+        Some(&user.encrypted_firstname),
+        Some(&user.encrypted_lastname),
+        Some(&user.encrypted_email),
+        user.encrypted_date_of_birth.as_ref(),
+    ]
+}
+
+impl Database {
+    /// Returns whether every encrypted user field is bound to its record and field, so unbound
+    /// values are rejected when user fields are decrypted.
+    pub fn user_fields_bound(&self) -> bool {
+        self.user_fields_bound.load(Ordering::Acquire)
+    }
+
+    /// Loads whether the migration completed, storing the marker if no unbound user fields are
+    /// left. Called on startup and after every migration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing whether user fields are decrypted strictly from now on, or a
+    /// `CustomError` if the check fails.
+    pub(super) async fn refresh_user_fields_bound(&self) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = format!(
+            "SELECT count() FROM migrations WHERE id = type::thing('migrations', 'field_encryption') GROUP ALL; SELECT count() FROM (SELECT id FROM users WHERE {} LIMIT 1) GROUP ALL;",
+            unbound_field_conditions().join(" OR ")
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("bound_prefix".into(), Value::from(BOUND_PREFIX));
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let marker: Option<Count> = response.take(0)?;
+        let unbound: Option<Count> = response.take(1)?;
+
+        if marker.is_none_or(|marker| marker.count == 0) {
+            if unbound.is_some_and(|unbound| unbound.count > 0) {
+                tracing::warn!("Unbound user fields are left, run the field encryption migration");
+                return Ok(false);
+            }
+            let sql = "UPSERT type::thing('migrations', 'field_encryption') SET completed_at = time::now();";
+            self.db.query(sql).await?.check()?;
+            tracing::info!(
+                "Field encryption migration completed, unbound user fields are rejected"
+            );
+        }
+        self.user_fields_bound.store(true, Ordering::Release);
+        Ok(true)
+    }
+
+    /// Re-encrypts every encrypted user field that isn't bound to its record and field yet, and
+    /// adds missing blind indexes of names.
+    ///
+    /// Each user is updated only if the re-encrypted fields haven't changed in the meantime, so
+    /// the migration can run while the shop is online. Once every user is migrated, user fields
+    /// are decrypted strictly.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of migrated and failed users, or a `CustomError` if the
+    /// users cannot be retrieved.
+    pub async fn migrate_field_encryption(&self) -> Result<FieldEncryptionMigration, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let mut conditions = unbound_field_conditions();
+        conditions.push("firstname_index = NONE".to_string());
+        conditions.push("lastname_index = NONE".to_string());
+        let sql = format!("SELECT * FROM users WHERE {};", conditions.join(" OR "));
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("bound_prefix".into(), Value::from(BOUND_PREFIX));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let users: Vec<User> = response.take(0)?;
+        tracing::info!("Migrating field encryption of {} users", users.len());
+
+        let mut migration = FieldEncryptionMigration::default();
+        for user in users {
+            match self.migrate_user_fields(&user).await {
+                Ok(true) => migration.migrated += 1,
+                Ok(false) => {
+                    tracing::warn!("User {} changed during the migration", user.id);
+                    migration.failed += 1;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to migrate fields of user {}: {:?}", user.id, e);
+                    migration.failed += 1;
+                }
+            }
+        }
+        if migration.failed == 0 {
+            self.refresh_user_fields_bound().await?;
+        }
+        Ok(migration)
+    }
+
+    /// Re-encrypts the unbound fields of one user and adds missing blind indexes.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user, as read before the migration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the user was updated, or `false` if one of the fields
+    /// changed since it was read.
+    async fn migrate_user_fields(&self, user: &User) -> Result<bool, CustomError> {
+        let user_id = record_key(&user.id);
+        let fields: Vec<(&'static str, String)> = ENCRYPTED_USER_FIELDS
+            .into_iter()
+            .zip(encrypted_fields(user))
+            .filter_map(|(field, value)| Some((field, value?.clone())))
+            .collect();
+
+        // Decrypt every field, which also verifies already bound ones, and re-encrypt the rest
+        let job_user_id = user_id.clone();
+        let job_fields = fields.clone();
+        let migrated = CpuPool::global()
+            .run(move || {
+                let key_bytes: [u8; 32] = generate_key()?.into();
+                job_fields
+                    .into_iter()
+                    .map(|(field, value)| {
+                        let aad = field_aad(&job_user_id, field);
+                        let plaintext = decrypt_bound(&key_bytes, &value, &aad)?;
+                        let reencrypted = if is_bound(&value) {
+                            None
+                        } else {
+                            Some(encrypt_bound(&key_bytes, &plaintext, &aad)?)
+                        };
+                        Ok((plaintext, reencrypted))
+                    })
+                    .collect::<Result<Vec<(String, Option<String>)>, CustomError>>()
+            })
+            .await??;
+
+        let mut assignments = Vec::new();
+        let mut unchanged = Vec::new();
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        for ((field, old), (plaintext, reencrypted)) in fields.iter().zip(migrated) {
+            let name = field.trim_start_matches("encrypted_");
+            let missing_index = match name {
+                "firstname" => user.firstname_index.is_none(),
+                "lastname" => user.lastname_index.is_none(),
+                _ => false,
+            };
+            if missing_index {
+                assignments.push(format!("{}_index = ${}_index", name, name));
+                vars.insert(
+                    format!("{}_index", name),
+                    Value::from(blind_index(name, &plaintext)?.as_str()),
+                );
+            }
+            if let Some(new) = reencrypted {
+                assignments.push(format!("{} = $new_{}", field, field));
+                vars.insert(format!("new_{}", field), Value::from(new.as_str()));
+            }
+            unchanged.push(format!("{} = $old_{}", field, field));
+            vars.insert(format!("old_{}", field), Value::from(old.as_str()));
+        }
+        if assignments.is_empty() {
+            return Ok(true);
+        }
+
+        let sql = format!(
+            "UPDATE type::thing('users', $user_id) SET {} WHERE {} RETURN AFTER;",
+            assignments.join(", "),
+            unchanged.join(" AND ")
+        );
+        // Other requests switch the namespace while the pool decrypts
+        self.use_user_namespace().await?;
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<User> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+}
@@ -0,0 +1,148 @@
+//! src/database/games.rs
+//!
+//! This module caches the game details looked up in the metadata API in the `games` table, keyed
+//! by the normalized title, and attaches them to new offers. Titles the API doesn't know are
+//! cached as well, so they aren't looked up again for every listing.
+
+use super::blind_index::normalize_name;
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::game_metadata::GameMetadata;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The number of days a lookup is cached before the metadata API is asked again.
+pub const GAME_CACHE_DAYS: u32 = 30;
+
+/// A cached lookup.
+#[derive(Debug, Deserialize)]
+struct CachedGame {
+    /// The game's details, or `None` if the metadata API didn't know the title.
+    #[serde(default)]
+    metadata: Option<GameMetadata>,
+}
+
+/// Defines the `games` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE games SCHEMALESS;", "games table").await;
+}
+
+/// Converts game details to the object stored in the database.
+fn metadata_value(metadata: &GameMetadata) -> Value {
+    let mut object: BTreeMap<String, Value> = BTreeMap::new();
+    object.insert("title".into(), Value::from(metadata.title.as_str()));
+    object.insert(
+        "cover_url".into(),
+        Value::from(metadata.cover_url.as_deref()),
+    );
+    object.insert("release_year".into(), Value::from(metadata.release_year));
+    object.insert("genres".into(), genres_value(metadata));
+    Value::from(object)
+}
+
+/// Converts the genres of game details to the array stored in the database.
+fn genres_value(metadata: &GameMetadata) -> Value {
+    Value::from(
+        metadata
+            .genres
+            .iter()
+            .map(|genre| Value::from(genre.as_str()))
+            .collect::<Vec<Value>>(),
+    )
+}
+
+impl Database {
+    /// Retrieves the details of a game, from the cache or the metadata API.
+    ///
+    /// Lookups older than `GAME_CACHE_DAYS` are repeated. Failed lookups are not cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - The title of the game.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the game's details, `None` if the metadata API doesn't know the
+    /// title, or a `CustomError` if the cache or the API can't be queried.
+    pub async fn get_game_metadata(
+        &self,
+        title: &str,
+    ) -> Result<Option<GameMetadata>, CustomError> {
+        let key = normalize_name(title);
+        if key.is_empty() {
+            return Ok(None);
+        }
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = format!(
+            "SELECT metadata FROM type::thing('games', $key) WHERE fetched_at >= time::now() - {}d;",
+            GAME_CACHE_DAYS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("key".into(), Value::from(key.as_str()));
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars.clone()).await?;
+        let cached: Option<CachedGame> = response.take(0)?;
+        if let Some(cached) = cached {
+            return Ok(cached.metadata);
+        }
+
+        tracing::info!("Looking up metadata for game: {}", title);
+        let metadata = self.metadata_provider.lookup(title).await?;
+        vars.insert(
+            "metadata".into(),
+            metadata.as_ref().map_or(Value::None, metadata_value),
+        );
+        self.use_offer_namespace().await?; // Switch back in case another request switched meanwhile
+        self.db
+            .query("UPSERT type::thing('games', $key) SET metadata = $metadata, fetched_at = time::now();")
+            .bind(vars)
+            .await?
+            .check()?;
+        Ok(metadata)
+    }
+
+    /// Attaches the details of its game to an offer.
+    ///
+    /// The offer's genres are taken from the metadata if the seller didn't pick any. Bundles are
+    /// not enriched, as their title names the bundle rather than a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer to enrich.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, `None` if no details were found, or a
+    /// `CustomError` if the lookup or the update fails.
+    pub async fn enrich_offer(&self, offer: &Offer) -> Result<Option<Offer>, CustomError> {
+        if offer.is_bundle() {
+            return Ok(None);
+        }
+        let Some(metadata) = self.get_game_metadata(&offer.game_title).await? else {
+            return Ok(None);
+        };
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut updates = vec!["game = $game"];
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "offer_id".into(),
+            Value::from(record_key(&offer.id).as_str()),
+        );
+        vars.insert("game".into(), metadata_value(&metadata));
+        if offer.genres.is_empty() && !metadata.genres.is_empty() {
+            updates.push("genres = $genres");
+            vars.insert("genres".into(), genres_value(&metadata));
+        }
+
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET {} RETURN AFTER;",
+            updates.join(", ")
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        Ok(updated)
+    }
+}
@@ -0,0 +1,112 @@
+//! src/database/ids.rs
+//!
+//! This module provides typed IDs of users and offers and their conversion from and to record
+//! IDs, so the table a record lives in is decided in one place.
+//!
+//! Users are stored in the `users` table of the user namespace. Offers reference their seller
+//! from the offer namespace as a `user` record, which is how existing offers are stored.
+
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use surrealdb::sql::{Id, Thing};
+
+/// Returns the key of a record ID if it belongs to one of the given tables.
+fn key_in(thing: &Thing, tables: &[&str]) -> Result<String, CustomError> {
+    if !tables.contains(&thing.tb.as_str()) {
+        return Err(CustomError::InvalidRecordId(thing.to_string()));
+    }
+    match &thing.id {
+        Id::String(key) => Ok(key.clone()),
+        Id::Uuid(uuid) => Ok(uuid.to_string()),
+        _ => Err(CustomError::InvalidRecordId(thing.to_string())),
+    }
+}
+
+/// The ID of a user, i.e. the key of their record (a UUID).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserId(String);
+
+impl UserId {
+    /// The table users are stored in.
+    pub const TABLE: &'static str = "users";
+    /// The table offers reference their seller in.
+    pub const REFERENCE_TABLE: &'static str = "user";
+
+    /// Creates a user ID from the key of the user's record.
+    pub fn new(key: impl Into<String>) -> Self {
+        UserId(key.into())
+    }
+
+    /// Returns the key of the user's record.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the record ID of the user in the `users` table.
+    pub fn to_thing(&self) -> Thing {
+        Thing::from((Self::TABLE.to_string(), self.0.clone()))
+    }
+
+    /// Returns the record ID offers use to reference the user as their seller.
+    pub fn to_reference(&self) -> Thing {
+        Thing::from((Self::REFERENCE_TABLE.to_string(), self.0.clone()))
+    }
+}
+
+impl TryFrom<&Thing> for UserId {
+    type Error = CustomError;
+
+    /// Converts a user record or a seller reference to a user ID.
+    fn try_from(thing: &Thing) -> Result<Self, Self::Error> {
+        key_in(thing, &[Self::TABLE, Self::REFERENCE_TABLE]).map(UserId)
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The ID of an offer, i.e. the key of its record (a UUID).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OfferId(String);
+
+impl OfferId {
+    /// The table offers are stored in.
+    pub const TABLE: &'static str = "offers";
+
+    /// Creates an offer ID from the key of the offer's record.
+    pub fn new(key: impl Into<String>) -> Self {
+        OfferId(key.into())
+    }
+
+    /// Returns the key of the offer's record.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the record ID of the offer.
+    pub fn to_thing(&self) -> Thing {
+        Thing::from((Self::TABLE.to_string(), self.0.clone()))
+    }
+}
+
+impl TryFrom<&Thing> for OfferId {
+    type Error = CustomError;
+
+    /// Converts an offer record to an offer ID.
+    fn try_from(thing: &Thing) -> Result<Self, Self::Error> {
+        key_in(thing, &[Self::TABLE]).map(OfferId)
+    }
+}
+
+impl fmt::Display for OfferId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -0,0 +1,150 @@
+//! src/database/image_blobs.rs
+//!
+//! This module deduplicates the stored files of uploaded images. Files are named by the SHA-256
+//! hash of their content, so an image uploaded again (e.g. for a relisted item) reuses the stored
+//! file and its thumbnail. Every file has a reference count of the offer images using it, and is
+//! only deleted once the last of them is gone. Files also keep the perceptual hash of their image,
+//! which `photo_matching` compares to find copies uploaded by other sellers.
+
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// A stored image file shared by the offer images with the same content.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageBlob {
+    /// The blob's ID. Its key is the content hash.
+    pub id: Thing,
+    /// The number of offer images using the file.
+    pub refs: i64,
+    /// The path of the file's thumbnail, once it was generated.
+    #[serde(default)]
+    pub thumbnail_url: Option<String>,
+    /// The perceptual hash of the image, used to find copies of it. Missing for files stored
+    /// before perceptual hashes were computed, or if the image couldn't be decoded.
+    #[serde(default)]
+    pub perceptual_hash: Option<String>,
+}
+
+/// Returns the hex-encoded SHA-256 hash of an image's content.
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Returns the content hash of a stored image from the path it is served from, or `None` for
+/// images stored before deduplication, whose files are not named by their hash.
+pub fn content_hash_of_url(url: &str) -> Option<&str> {
+    let file_name = url.rsplit('/').next()?;
+    let hash = file_name.split('.').next()?;
+    (hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())).then_some(hash)
+}
+
+/// Defines the `image_blobs` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE image_blobs SCHEMALESS;",
+        "image_blobs table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD refs ON image_blobs TYPE int DEFAULT 0;",
+        "refs field on image_blobs",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX image_blobs_perceptual_hash_bands ON image_blobs FIELDS perceptual_hash_bands",
+        "image_blobs_perceptual_hash_bands index on image_blobs",
+    )
+    .await;
+}
+
+impl Database {
+    /// Adds a reference to the stored file with the given content hash, creating its blob if
+    /// this is the first one.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The content hash of the file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the blob after the update or a `CustomError` if the update fails.
+    pub async fn acquire_image_blob(&self, hash: &str) -> Result<ImageBlob, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql =
+            "UPSERT type::thing('image_blobs', $hash) SET refs = (refs ?? 0) + 1 RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("hash".into(), Value::from(hash));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let blob: Option<ImageBlob> = response.take(0)?;
+        blob.ok_or_else(|| {
+            tracing::error!("Failed to retrieve image blob {} after update.", hash);
+            CustomError::DatabaseError("Failed to retrieve image blob".to_string())
+        })
+    }
+
+    /// Removes a reference to the stored file with the given content hash, deleting its blob once
+    /// no references are left.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The content hash of the file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of references left, `None` if no blob exists for the
+    /// hash, or a `CustomError` if the update fails. The file can be deleted if it's `Some(0)`.
+    pub async fn release_image_blob(&self, hash: &str) -> Result<Option<i64>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('image_blobs', $hash) SET refs -= 1 RETURN AFTER;
+            DELETE image_blobs WHERE id = type::thing('image_blobs', $hash) AND refs <= 0;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("hash".into(), Value::from(hash));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let blob: Option<ImageBlob> = response.take(0)?;
+        Ok(blob.map(|blob| blob.refs.max(0)))
+    }
+
+    /// Records the thumbnail generated for a stored file, so later uploads of the same content
+    /// reuse it.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The content hash of the file.
+    /// * `thumbnail_url` - The path of the thumbnail.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the blob still exists, or a `CustomError` if the update
+    /// fails.
+    pub async fn set_image_blob_thumbnail(
+        &self,
+        hash: &str,
+        thumbnail_url: &str,
+    ) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('image_blobs', $hash) SET thumbnail_url = $thumbnail_url RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("hash".into(), Value::from(hash));
+        vars.insert("thumbnail_url".into(), Value::from(thumbnail_url));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let blob: Option<ImageBlob> = response.take(0)?;
+        Ok(blob.is_some())
+    }
+}
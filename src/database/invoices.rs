@@ -0,0 +1,67 @@
+//! src/database/invoices.rs
+//!
+//! This module assigns the sequential numbers of order invoices. A completed order gets its number
+//! when its invoice is first issued; downloading the invoice again reuses it. Numbers are drawn
+//! from a counter in the same statement that stores them on the order, so no number is skipped or
+//! given out twice.
+
+use super::orders::Order;
+use super::{Database, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// Defines the `counters` table and the invoice fields on `orders`.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE counters SCHEMALESS;", "counters table").await;
+    define(
+        db,
+        "DEFINE FIELD invoiced_at ON orders TYPE option<datetime>;",
+        "invoiced_at field on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX orders_invoice_number ON orders FIELDS invoice_number",
+        "orders_invoice_number index on orders",
+    )
+    .await;
+}
+
+impl Database {
+    /// Assigns the next invoice number to a completed order, unless it already has one.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The completed order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the order with its invoice number, or `None` if the order doesn't
+    /// exist or isn't completed.
+    pub async fn issue_invoice_number(&self, order: &Order) -> Result<Option<Order>, CustomError> {
+        if order.invoice_number.is_some() {
+            return Ok(Some(order.clone()));
+        }
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let order_id = record_key(&order.id);
+        tracing::info!("Issuing invoice number for order {}", order_id);
+        let sql = "UPDATE type::thing('orders', $order_id) SET invoice_number = (UPSERT type::thing('counters', 'invoices') SET value = (value ?? 0) + 1 RETURN VALUE value)[0], invoiced_at = time::now() WHERE state = 'completed' AND invoice_number = NONE RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("order_id".into(), Value::from(order_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Order> = response.take(0)?;
+        match updated {
+            Some(updated) => Ok(Some(updated)),
+            // A concurrent request may have issued the number in the meantime
+            None => Ok(self
+                .get_order(order_id)
+                .await?
+                .filter(|order| order.invoice_number.is_some())),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! src/database/key_check.rs
+//!
+//! This module verifies on startup that `ENCRYPTION_KEY` is the key the stored data was encrypted
+//! with. A known constant is encrypted on first boot and decrypted on every later boot, so a
+//! misconfigured key stops the server immediately instead of surfacing as decryption errors
+//! whenever personal information is read.
+
+use super::{Database, User, record_key};
+use crate::encryption::{
+    decrypt_bound, decrypt_bound_strict, encrypt_bound, field_aad, generate_key,
+};
+use crate::errors::custom_errors::CustomError;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+
+/// The constant encrypted as the key-check value.
+const KEY_CHECK_PLAINTEXT: &str = "gameshop encryption key check";
+
+/// The stored key-check value.
+#[derive(Debug, Deserialize)]
+struct KeyCheck {
+    /// The encrypted `KEY_CHECK_PLAINTEXT`.
+    value: String,
+}
+
+/// Returns the encryption key and the associated data of the key-check value.
+fn key_and_aad() -> Result<([u8; 32], Vec<u8>), CustomError> {
+    Ok((generate_key()?.into(), field_aad("key_check", "value")))
+}
+
+impl Database {
+    /// Verifies the encryption key against the stored key-check value.
+    ///
+    /// On first boot the value is created. If users were stored before the key-check value
+    /// existed, one of their encrypted fields is decrypted first, so an existing deployment
+    /// can't store a value for the wrong key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success, `CustomError::EncryptionKeyMismatch` if the key can't
+    /// decrypt the stored data, or another `CustomError` if the check fails.
+    pub(super) async fn verify_encryption_key(&self) -> Result<(), CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT value FROM type::thing('key_check', 'current');";
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let stored: Option<KeyCheck> = response.take(0)?;
+        let (key_bytes, aad) = key_and_aad()?;
+
+        if let Some(stored) = stored {
+            return match decrypt_bound_strict(&key_bytes, &stored.value, &aad) {
+                Ok(plaintext) if plaintext == KEY_CHECK_PLAINTEXT => {
+                    tracing::info!("Encryption key verified");
+                    Ok(())
+                }
+                _ => Err(CustomError::EncryptionKeyMismatch),
+            };
+        }
+
+        // Data from before the key-check value existed must still be readable
+        let sql = "SELECT * FROM users LIMIT 1;";
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let existing: Option<User> = response.take(0)?;
+        if let Some(user) = existing {
+            let aad = field_aad(&record_key(&user.id), "encrypted_email");
+            decrypt_bound(&key_bytes, &user.encrypted_email, &aad)
+                .map_err(|_| CustomError::EncryptionKeyMismatch)?;
+        }
+
+        let value = encrypt_bound(&key_bytes, KEY_CHECK_PLAINTEXT, &aad)?;
+        let sql = "CREATE type::thing('key_check', 'current') SET value = $value, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("value".into(), Value::from(value.as_str()));
+        self.db.query(sql).bind(vars).await?;
+        tracing::info!("Stored key-check value for the encryption key");
+        Ok(())
+    }
+}
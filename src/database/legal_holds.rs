@@ -0,0 +1,207 @@
+//! src/database/legal_holds.rs
+//!
+//! This module handles legal holds, which exempt records from purging and anonymization by the
+//! retention jobs until the hold is lifted.
+
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The kind of record a legal hold applies to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LegalHoldTarget {
+    /// A user account.
+    User,
+}
+
+impl LegalHoldTarget {
+    /// Returns the string stored in the database for this target kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LegalHoldTarget::User => "user",
+        }
+    }
+}
+
+/// Represents a legal hold placed on a record.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LegalHold {
+    /// The hold's ID.
+    pub id: Thing,
+    /// The kind of record the hold applies to.
+    pub target_kind: LegalHoldTarget,
+    /// The ID of the record the hold applies to.
+    pub target_id: String,
+    /// Why the hold was placed (e.g. a case reference).
+    pub reason: String,
+    /// The ID of the admin who placed the hold.
+    pub placed_by: String,
+    /// Whether the hold is still in effect.
+    pub active: bool,
+    /// The timestamp when the hold was placed.
+    pub created_at: String,
+    /// The ID of the admin who lifted the hold.
+    #[serde(default)]
+    pub lifted_by: Option<String>,
+    /// The timestamp when the hold was lifted.
+    #[serde(default)]
+    pub lifted_at: Option<String>,
+}
+
+/// Defines the `legal_holds` table.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE legal_holds SCHEMALESS;",
+        "legal_holds table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX legal_holds_target ON legal_holds FIELDS target_kind, target_id",
+        "legal_holds_target index on legal_holds",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON legal_holds TYPE datetime;",
+        "created_at field on legal_holds",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD lifted_at ON legal_holds TYPE option<datetime>;",
+        "lifted_at field on legal_holds",
+    )
+    .await;
+}
+
+impl Database {
+    /// Places a legal hold on a record.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_kind` - The kind of record to hold.
+    /// * `target_id` - The ID of the record to hold.
+    /// * `reason` - Why the hold is placed.
+    /// * `admin_id` - The ID of the admin placing the hold.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `LegalHold` or a `CustomError` if creation fails.
+    pub async fn place_legal_hold(
+        &self,
+        target_kind: LegalHoldTarget,
+        target_id: String,
+        reason: String,
+        admin_id: String,
+    ) -> Result<LegalHold, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!(
+            "Placing legal hold on {} {}",
+            target_kind.as_str(),
+            target_id
+        );
+        let sql = "CREATE legal_holds SET target_kind = $target_kind, target_id = $target_id, reason = $reason, placed_by = $admin_id, active = true, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("target_kind".into(), Value::from(target_kind.as_str()));
+        vars.insert("target_id".into(), Value::from(target_id.as_str()));
+        vars.insert("reason".into(), Value::from(reason.as_str()));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<LegalHold> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created legal hold after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created legal hold".to_string())
+        })
+    }
+
+    /// Lifts an active legal hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `hold_id` - The ID of the hold to lift.
+    /// * `admin_id` - The ID of the admin lifting the hold.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the lifted `LegalHold`, or `None` if no active hold with the given ID exists.
+    pub async fn lift_legal_hold(
+        &self,
+        hold_id: String,
+        admin_id: String,
+    ) -> Result<Option<LegalHold>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Lifting legal hold {}", hold_id);
+        let sql = "UPDATE type::thing('legal_holds', $hold_id) SET active = false, lifted_by = $admin_id, lifted_at = time::now() WHERE active = true RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("hold_id".into(), Value::from(hold_id.as_str()));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let lifted: Option<LegalHold> = response.take(0)?;
+        Ok(lifted)
+    }
+
+    /// Retrieves legal holds, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `active_only` - Whether lifted holds should be left out.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `LegalHold` structs or a `CustomError` if retrieval fails.
+    pub async fn get_legal_holds(&self, active_only: bool) -> Result<Vec<LegalHold>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = if active_only {
+            "SELECT * FROM legal_holds WHERE active = true ORDER BY created_at DESC;"
+        } else {
+            "SELECT * FROM legal_holds ORDER BY created_at DESC;"
+        };
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let holds: Vec<LegalHold> = response.take(0)?;
+        Ok(holds)
+    }
+
+    /// Checks whether a record is under an active legal hold.
+    ///
+    /// Retention jobs must call this before purging or anonymizing a record and skip it if it is held.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_kind` - The kind of record.
+    /// * `target_id` - The ID of the record.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if at least one active hold applies to the record.
+    pub async fn has_active_legal_hold(
+        &self,
+        target_kind: LegalHoldTarget,
+        target_id: String,
+    ) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM legal_holds WHERE target_kind = $target_kind AND target_id = $target_id AND active = true LIMIT 1;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("target_kind".into(), Value::from(target_kind.as_str()));
+        vars.insert("target_id".into(), Value::from(target_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let holds: Vec<LegalHold> = response.take(0)?;
+        Ok(!holds.is_empty())
+    }
+}
@@ -0,0 +1,284 @@
+//! src/database/legal_texts.rs
+//!
+//! This module handles the versioned, jurisdiction-specific legal text templates, such as the
+//! consumer withdrawal and return rights shown to buyers, and the site's legal documents (terms,
+//! privacy policy and imprint). Bodies are written in Markdown and rendered to HTML when served.
+
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use pulldown_cmark::{Event, Parser, html};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The kind of legal text.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LegalTextKind {
+    /// The buyer's statutory right to withdraw from a distance purchase.
+    WithdrawalRights,
+    /// The conditions under which items can be returned.
+    ReturnPolicy,
+    /// The terms of service users accept when registering.
+    Terms,
+    /// How the shop processes personal data.
+    PrivacyPolicy,
+    /// The legally required information about the operator of the shop.
+    Imprint,
+}
+
+impl LegalTextKind {
+    /// Returns the string stored in the database for this kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LegalTextKind::WithdrawalRights => "withdrawal_rights",
+            LegalTextKind::ReturnPolicy => "return_policy",
+            LegalTextKind::Terms => "terms",
+            LegalTextKind::PrivacyPolicy => "privacy_policy",
+            LegalTextKind::Imprint => "imprint",
+        }
+    }
+}
+
+/// Represents one version of a legal text for a jurisdiction.
+///
+/// Versions are immutable; editing a text creates a new version, so documents that referenced
+/// an older version keep pointing at the exact wording the buyer was shown.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LegalText {
+    /// The text's ID.
+    pub id: Thing,
+    /// The kind of legal text.
+    pub kind: LegalTextKind,
+    /// The ISO 3166-1 alpha-2 country code the text applies to, or `None` for the fallback text.
+    #[serde(default)]
+    pub country: Option<String>,
+    /// The version number, starting at 1 for every kind and country.
+    pub version: i64,
+    /// The text's title.
+    pub title: String,
+    /// The text's body.
+    pub body: String,
+    /// The ID of the admin who created this version.
+    pub created_by: String,
+    /// The timestamp when this version was created.
+    pub created_at: String,
+}
+
+/// Escapes text for use in HTML element content and attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl LegalText {
+    /// Renders the Markdown body to HTML.
+    ///
+    /// Raw HTML in the body is escaped and shown as text, so a text can't inject markup or
+    /// scripts into the page it is served on.
+    pub fn body_html(&self) -> String {
+        let events = Parser::new(&self.body).map(|event| match event {
+            Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+            event => event,
+        });
+        let mut rendered = String::with_capacity(self.body.len() * 3 / 2);
+        html::push_html(&mut rendered, events);
+        rendered
+    }
+
+    /// Renders the text as a standalone HTML page, stating its version.
+    pub fn to_html_page(&self) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<article>\n<h1>{title}</h1>\n{body}</article>\n<footer>Version {version} of {date}</footer>\n</body>\n</html>\n",
+            title = escape_html(&self.title),
+            body = self.body_html(),
+            version = self.version,
+            date = escape_html(self.created_at.get(..10).unwrap_or(&self.created_at)),
+        )
+    }
+}
+
+/// Defines the `legal_texts` table.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE legal_texts SCHEMALESS;",
+        "legal_texts table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX legal_texts_version ON legal_texts FIELDS kind, country, version UNIQUE",
+        "legal_texts_version index on legal_texts",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON legal_texts TYPE datetime;",
+        "created_at field on legal_texts",
+    )
+    .await;
+}
+
+impl Database {
+    /// Retrieves the latest version of a legal text for a country, without falling back.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of legal text.
+    /// * `country` - The country code, or `None` for the fallback text.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the latest `LegalText`, or `None` if no version exists.
+    async fn get_latest_legal_text(
+        &self,
+        kind: LegalTextKind,
+        country: Option<&str>,
+    ) -> Result<Option<LegalText>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM legal_texts WHERE kind = $kind AND country = $country ORDER BY version DESC LIMIT 1;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("kind".into(), Value::from(kind.as_str()));
+        vars.insert("country".into(), Value::from(country));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let mut texts: Vec<LegalText> = response.take(0)?;
+        Ok(texts.pop())
+    }
+
+    /// Retrieves the current legal text that applies to a buyer from the given country.
+    ///
+    /// Falls back to the text without a country if no country-specific text exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of legal text.
+    /// * `country` - The buyer's country code (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the applicable `LegalText`, or `None` if neither a country-specific
+    /// nor a fallback text exists.
+    pub async fn get_current_legal_text(
+        &self,
+        kind: LegalTextKind,
+        country: Option<String>,
+    ) -> Result<Option<LegalText>, CustomError> {
+        if let Some(country) = &country
+            && let Some(text) = self.get_latest_legal_text(kind, Some(country)).await?
+        {
+            return Ok(Some(text));
+        }
+        self.get_latest_legal_text(kind, None).await
+    }
+
+    /// Retrieves a specific version of a legal text by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of legal text the version must belong to.
+    /// * `text_id` - The ID of the version.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LegalText`, or `None` if no version of this kind has the ID.
+    pub async fn get_legal_text_version(
+        &self,
+        kind: LegalTextKind,
+        text_id: &str,
+    ) -> Result<Option<LegalText>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM type::thing('legal_texts', $text_id) WHERE kind = $kind;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("text_id".into(), Value::from(text_id));
+        vars.insert("kind".into(), Value::from(kind.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let text: Option<LegalText> = response.take(0)?;
+        Ok(text)
+    }
+
+    /// Retrieves all versions of all legal texts.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `LegalText` structs or a `CustomError` if retrieval fails.
+    pub async fn get_legal_texts(&self) -> Result<Vec<LegalText>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM legal_texts ORDER BY kind, country, version DESC;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let texts: Vec<LegalText> = response.take(0)?;
+        Ok(texts)
+    }
+
+    /// Creates a new version of a legal text.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of legal text.
+    /// * `country` - The country code, or `None` for the fallback text.
+    /// * `title` - The text's title.
+    /// * `body` - The text's body.
+    /// * `admin_id` - The ID of the admin creating the version.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `LegalText` or a `CustomError` if creation fails
+    /// (e.g. because another version was created concurrently).
+    pub async fn create_legal_text_version(
+        &self,
+        kind: LegalTextKind,
+        country: Option<String>,
+        title: String,
+        body: String,
+        admin_id: String,
+    ) -> Result<LegalText, CustomError> {
+        let version = self
+            .get_latest_legal_text(kind, country.as_deref())
+            .await?
+            .map_or(1, |text| text.version + 1);
+
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!(
+            "Creating version {} of {} legal text for {:?}",
+            version,
+            kind.as_str(),
+            country
+        );
+        let sql = "CREATE legal_texts SET kind = $kind, country = $country, version = $version, title = $title, body = $body, created_by = $admin_id, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("kind".into(), Value::from(kind.as_str()));
+        vars.insert("country".into(), Value::from(country));
+        vars.insert("version".into(), Value::from(version));
+        vars.insert("title".into(), Value::from(title.as_str()));
+        vars.insert("body".into(), Value::from(body.as_str()));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<LegalText> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created legal text after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created legal text".to_string())
+        })
+    }
+}
@@ -0,0 +1,84 @@
+//! src/database/list_cache.rs
+//!
+//! This module provides the in-memory cache of admin-managed lists, such as the serial
+//! blacklist. A list is loaded from its table on first use and kept in memory, so checks don't
+//! query the database. Every change through the `Database` invalidates the cache and notifies
+//! subscribers, so edits by admins take effect immediately without a redeploy.
+
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+
+/// The cached entries of an admin-managed list.
+#[derive(Debug, Clone)]
+pub struct ListCache {
+    /// The loaded entries, or `None` until the list is loaded or after it changed.
+    entries: Arc<RwLock<Option<Arc<HashSet<String>>>>>,
+    /// The version of the list, increased on every change.
+    version: Arc<watch::Sender<u64>>,
+}
+
+impl Default for ListCache {
+    fn default() -> Self {
+        ListCache {
+            entries: Arc::new(RwLock::new(None)),
+            version: Arc::new(watch::Sender::new(0)),
+        }
+    }
+}
+
+impl ListCache {
+    /// Returns the cached entries, or `None` if the list has to be loaded first.
+    pub fn get(&self) -> Option<Arc<HashSet<String>>> {
+        self.entries
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Returns the current version of the list. Read it before loading the entries and pass it
+    /// to `fill`.
+    pub fn version(&self) -> u64 {
+        *self.version.borrow()
+    }
+
+    /// Caches the entries loaded from the database.
+    ///
+    /// The entries are discarded if the list changed while they were loaded, so a stale copy
+    /// is never cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version of the list read before loading the entries.
+    /// * `entries` - The loaded entries.
+    ///
+    /// # Returns
+    ///
+    /// The entries, to be used for the current check either way.
+    pub fn fill(&self, version: u64, entries: HashSet<String>) -> Arc<HashSet<String>> {
+        let entries = Arc::new(entries);
+        let mut cached = self
+            .entries
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.version() == version {
+            *cached = Some(entries.clone());
+        }
+        entries
+    }
+
+    /// Drops the cached entries after the list changed and notifies subscribers.
+    pub fn invalidate(&self) {
+        let mut cached = self
+            .entries
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *cached = None;
+        self.version.send_modify(|version| *version += 1);
+    }
+
+    /// Returns a receiver that is notified whenever the list changes.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+}
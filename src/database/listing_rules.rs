@@ -0,0 +1,284 @@
+//! src/database/listing_rules.rs
+//!
+//! This module handles the admin-configurable requirements listings must meet before they are
+//! published, such as a shrink-wrap photo for sealed items, and the rules engine evaluating them.
+
+use super::catalog::{Category, Language, OfferAttributes, OfferPhoto, PhotoKind, Region};
+use super::conditions::Condition;
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// A listing field a rule can require to be filled in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RequiredField {
+    /// The region coding.
+    Region,
+    /// The language printed on the box.
+    BoxLanguage,
+    /// The language of the manual.
+    ManualLanguage,
+    /// The serial number of a console.
+    SerialNumber,
+}
+
+impl RequiredField {
+    /// Returns the string stored in the database for this field.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequiredField::Region => "region",
+            RequiredField::BoxLanguage => "box_language",
+            RequiredField::ManualLanguage => "manual_language",
+            RequiredField::SerialNumber => "serial_number",
+        }
+    }
+}
+
+/// Represents a requirement rule for listings of a category (and optionally a condition).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ListingRule {
+    /// The rule's ID.
+    pub id: Thing,
+    /// The category the rule applies to.
+    pub category: Category,
+    /// The condition the rule applies to, or `None` for every condition.
+    #[serde(default)]
+    pub condition: Option<Condition>,
+    /// The kinds of photos the listing must include.
+    #[serde(default)]
+    pub required_photos: Vec<PhotoKind>,
+    /// The fields the listing must fill in.
+    #[serde(default)]
+    pub required_fields: Vec<RequiredField>,
+    /// The ID of the admin who created the rule.
+    pub created_by: String,
+    /// The timestamp when the rule was created.
+    pub created_at: String,
+}
+
+/// A requirement a listing does not meet.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MissingRequirement {
+    /// A photo of this kind is missing.
+    Photo {
+        /// The missing kind of photo.
+        photo: PhotoKind,
+    },
+    /// This field is not filled in.
+    Field {
+        /// The missing field.
+        field: RequiredField,
+    },
+}
+
+/// The parts of a listing the rules are evaluated against.
+#[derive(Debug, Clone, Copy)]
+pub struct ListingFacts<'a> {
+    /// The category and category-specific attributes.
+    pub attributes: &'a OfferAttributes,
+    /// The condition of the item.
+    pub condition: Condition,
+    /// The region coding.
+    pub region: Option<Region>,
+    /// The language printed on the box.
+    pub box_language: Option<Language>,
+    /// The language of the manual.
+    pub manual_language: Option<Language>,
+    /// The photos of the item.
+    pub photos: &'a [OfferPhoto],
+}
+
+impl ListingRule {
+    /// Returns whether the rule applies to the given listing.
+    pub fn applies_to(&self, facts: &ListingFacts) -> bool {
+        self.category == facts.attributes.category()
+            && self
+                .condition
+                .is_none_or(|condition| condition == facts.condition)
+    }
+}
+
+/// Evaluates the rules against a listing.
+///
+/// # Arguments
+///
+/// * `rules` - The rules to evaluate. Rules that do not apply to the listing are skipped.
+/// * `facts` - The listing to check.
+///
+/// # Returns
+///
+/// Every requirement the listing does not meet, without duplicates. Empty if the listing can be published.
+pub fn missing_requirements(
+    rules: &[ListingRule],
+    facts: &ListingFacts,
+) -> Vec<MissingRequirement> {
+    let mut missing = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.applies_to(facts)) {
+        for &photo in &rule.required_photos {
+            if !facts.photos.iter().any(|p| p.kind == photo) {
+                missing.push(MissingRequirement::Photo { photo });
+            }
+        }
+        for &field in &rule.required_fields {
+            let present = match field {
+                RequiredField::Region => facts.region.is_some(),
+                RequiredField::BoxLanguage => facts.box_language.is_some(),
+                RequiredField::ManualLanguage => facts.manual_language.is_some(),
+                RequiredField::SerialNumber => facts.attributes.serial_number().is_some(),
+            };
+            if !present {
+                missing.push(MissingRequirement::Field { field });
+            }
+        }
+    }
+    let mut unique = Vec::with_capacity(missing.len());
+    for requirement in missing {
+        if !unique.contains(&requirement) {
+            unique.push(requirement);
+        }
+    }
+    unique
+}
+
+/// Defines the `listing_rules` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE listing_rules SCHEMALESS;",
+        "listing_rules table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX listing_rules_category ON listing_rules FIELDS category",
+        "listing_rules_category index on listing_rules",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON listing_rules TYPE datetime;",
+        "created_at field on listing_rules",
+    )
+    .await;
+}
+
+impl Database {
+    /// Creates a new listing rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - The category the rule applies to.
+    /// * `condition` - The condition the rule applies to, or `None` for every condition.
+    /// * `required_photos` - The kinds of photos the listing must include.
+    /// * `required_fields` - The fields the listing must fill in.
+    /// * `admin_id` - The ID of the admin creating the rule.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `ListingRule` or a `CustomError` if creation fails.
+    pub async fn create_listing_rule(
+        &self,
+        category: Category,
+        condition: Option<Condition>,
+        required_photos: Vec<PhotoKind>,
+        required_fields: Vec<RequiredField>,
+        admin_id: String,
+    ) -> Result<ListingRule, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Creating listing rule for {}", category.as_str());
+        let sql = "CREATE listing_rules SET category = $category, condition = $condition, required_photos = $required_photos, required_fields = $required_fields, created_by = $admin_id, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("category".into(), Value::from(category.as_str()));
+        vars.insert(
+            "condition".into(),
+            Value::from(condition.map(|condition| condition.as_str())),
+        );
+        vars.insert(
+            "required_photos".into(),
+            Value::from(
+                required_photos
+                    .iter()
+                    .map(|photo| Value::from(photo.as_str()))
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+        vars.insert(
+            "required_fields".into(),
+            Value::from(
+                required_fields
+                    .iter()
+                    .map(|field| Value::from(field.as_str()))
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<ListingRule> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created listing rule after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created listing rule".to_string())
+        })
+    }
+
+    /// Deletes a listing rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule_id` - The ID of the rule to delete.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the rule existed.
+    pub async fn delete_listing_rule(&self, rule_id: String) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Deleting listing rule {}", rule_id);
+        let sql = "DELETE type::thing('listing_rules', $rule_id) RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("rule_id".into(), Value::from(rule_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Option<ListingRule> = response.take(0)?;
+        Ok(deleted.is_some())
+    }
+
+    /// Retrieves the listing rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - Only return rules for this category (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `ListingRule` structs or a `CustomError` if retrieval fails.
+    pub async fn get_listing_rules(
+        &self,
+        category: Option<Category>,
+    ) -> Result<Vec<ListingRule>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let sql = match category {
+            Some(category) => {
+                vars.insert("category".into(), Value::from(category.as_str()));
+                "SELECT * FROM listing_rules WHERE category = $category ORDER BY created_at ASC;"
+            }
+            None => "SELECT * FROM listing_rules ORDER BY category, created_at ASC;",
+        };
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let rules: Vec<ListingRule> = response.take(0)?;
+        Ok(rules)
+    }
+}
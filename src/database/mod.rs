@@ -0,0 +1,1685 @@
+//! src/database/mod.rs
+//!
+//! This module handles all database interactions for the application, using SurrealDB.
+
+/// Soft deletion and restoration of user accounts.
+pub mod account_deletion;
+/// Encrypted shipping addresses of users and orders.
+pub mod addresses;
+/// Appeals against moderation actions.
+pub mod appeals;
+/// Timed auctions of offers.
+pub mod auctions;
+/// Audit log persistence.
+pub mod audit;
+/// Authenticity verification of high-value listings.
+pub mod authenticity;
+/// Private price proposals on offers and their negotiation.
+pub mod bids;
+/// Keyed hashes of encrypted names, for exact-match lookups.
+pub mod blind_index;
+/// Users' shopping carts.
+pub mod cart;
+/// Structured catalog metadata of offers.
+pub mod catalog;
+/// The conditions items are listed in.
+pub mod conditions;
+/// Signed export and import of the platform configuration.
+pub mod config_bundle;
+/// Conversations between buyers and sellers and their encrypted messages.
+pub mod conversations;
+/// Platform-wide key figures of the admin dashboard.
+pub mod dashboard;
+/// Failed background jobs kept for inspection and retries.
+pub mod dead_letters;
+/// Emails addressed to users.
+pub mod emails;
+/// Users' favorite offers.
+pub mod favorites;
+/// Offers featured by admins for curated or paid placements.
+pub mod featured;
+/// The admin-configurable platform fee schedule.
+pub mod fees;
+/// Re-encryption of personal information bound to its record and field.
+pub mod field_encryption;
+/// Cached details of games from the metadata API.
+pub mod games;
+/// Typed IDs of users and offers.
+pub mod ids;
+/// Deduplicated, reference-counted image files.
+pub mod image_blobs;
+/// Sequential numbers of order invoices.
+pub mod invoices;
+/// Verification of the encryption key on startup.
+pub mod key_check;
+/// Legal holds exempting records from the retention jobs.
+pub mod legal_holds;
+/// Versioned, jurisdiction-specific legal text templates.
+pub mod legal_texts;
+/// In-memory caches of admin-managed lists.
+pub mod list_cache;
+/// Configurable photo and field requirements for listings.
+pub mod listing_rules;
+/// Moderation persistence (roles, bans, hidden offers, reports, reason templates).
+pub mod moderation;
+/// In-app notification persistence.
+pub mod notifications;
+/// Soft deletion, restoration and purging of offers.
+pub mod offer_deletion;
+/// Images uploaded for offers.
+pub mod offer_images;
+/// Public questions about offers and the sellers' answers.
+pub mod offer_questions;
+/// The readable slugs offers can be looked up by.
+pub mod offer_slugs;
+/// The status lifecycle of offers.
+pub mod offer_status;
+/// The stock of offers selling several copies.
+pub mod offer_stock;
+/// View counters of offers.
+pub mod offer_views;
+/// Orders placed for offers.
+pub mod orders;
+/// Page-based pagination of list endpoints.
+pub mod pagination;
+/// Detection of listing photos reused across sellers.
+pub mod photo_matching;
+/// Coarse pickup locations of offers and the distance filter.
+pub mod pickup;
+/// The canonical list of platforms.
+pub mod platforms;
+/// Per-user preferences (preferred platforms, currency, notification settings).
+pub mod preferences;
+/// Price history and statistics per game title.
+pub mod price_history;
+/// The weekly price index of sold titles.
+pub mod price_index;
+/// Admin-managed promo codes and their redemptions at checkout.
+pub mod promo_codes;
+/// Aggregate marketplace statistics, published for community sites and per platform.
+pub mod public_stats;
+/// Time-limited reservations of offers by buyers.
+pub mod reservations;
+/// Searches users saved to be alerted about new listings.
+pub mod saved_searches;
+/// Full-text search over offers.
+pub mod search;
+/// Analytics of sellers' offers and the revenue of their sales.
+pub mod seller_analytics;
+/// The verified seller badge and sellers' applications for it.
+pub mod seller_verification;
+/// Blacklist of serial numbers reported as stolen.
+pub mod serial_blacklist;
+/// The offers and sellers listed in the sitemaps.
+pub mod sitemap;
+/// VAT rates of countries and the tax computed at checkout.
+pub mod taxes;
+/// The trending score of offers, computed from their recent views and favorites.
+pub mod trending;
+/// Games users want to buy, matched against new offers.
+pub mod wishlist;
+
+use crate::cpu_pool::CpuPool;
+use crate::email::{EmailSender, email_sender_from_env};
+use crate::encryption::{encrypt_bound, field_aad, generate_key};
+use crate::errors::custom_errors::CustomError;
+use crate::exchange_rates::{ConvertedPrice, ExchangeRateCache, ExchangeRates};
+#[cfg(feature = "fault-injection")]
+use crate::fault_injection::{Dependency, inject_dependency_fault};
+use crate::game_metadata::{GameMetadata, MetadataProvider, metadata_provider_from_env};
+use crate::hashing::{dummy_password_hash, hash_random_salt, needs_rehash, verify_password}; // Assuming hash_random_salt can be used for email hashing too, or you'd add a separate email hashing function.
+use blind_index::blind_index;
+use catalog::{
+    AgeRating, BundleItem, Genre, Language, OfferAttributes, OfferFilter, OfferMetadata,
+    OfferPhoto, Region, age_on, converted_price_sql,
+};
+use chrono::{NaiveDate, Utc};
+use conditions::Condition;
+use conversations::ChatEvents;
+use field_encryption::decrypt_user_field;
+use ids::UserId;
+use list_cache::ListCache;
+use notifications::NotificationSignal;
+use offer_images::OfferImage;
+use offer_questions::OfferQuestion;
+use offer_status::OfferStatus;
+use pagination::{PageInfo, Pagination};
+use pickup::PickupLocation;
+use platforms::Platform;
+use preferences::{Currency, UserPreferences};
+use price_history::PriceEvent;
+use sha2::{Digest, Sha256}; // Added for email hashing
+use taxes::SellerType;
+
+use dotenvy::var;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::exit;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use surrealdb::{
+    Surreal,
+    engine::local::{Db, RocksDb},
+    sql::{Id, Thing, Value}, // Import Thing here
+};
+use uuid::Uuid;
+
+/// The format dates of birth are entered and stored in.
+pub const DATE_OF_BIRTH_FORMAT: &str = "%Y-%m-%d";
+
+/// The role of a user, determining which administrative endpoints they may access.
+///
+/// New accounts are always created as `User`; the first admin has to be promoted directly in the database.
+/// Admins can promote users to `Moderator` and demote them again.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// A regular user.
+    #[default]
+    User,
+    /// A community moderator who can review reported offers but has no admin rights.
+    Moderator,
+    /// An administrator with access to the moderation endpoints.
+    Admin,
+}
+
+impl Role {
+    /// Returns the string stored in the database for this role.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// Represents a user in the database.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct User {
+    /// The user's ID. This is a SurrealDB Thing, representing the full record ID (e.g., 'users:uuid').
+    pub id: Thing,
+    /// The user's encrypted first name.
+    pub encrypted_firstname: String,
+    /// The user's encrypted last name.
+    pub encrypted_lastname: String,
+    /// The blind index of the user's first name. Missing until the field encryption migration
+    /// ran for accounts created before names could be looked up.
+    #[serde(default)]
+    pub firstname_index: Option<String>,
+    /// The blind index of the user's last name.
+    #[serde(default)]
+    pub lastname_index: Option<String>,
+    /// The user's username.
+    pub username: String,
+    /// The user's password hash.
+    pub password_hash: String,
+    /// The user's encrypted email. This can be decrypted on the server if needed for specific operations (e.g., sending emails).
+    pub encrypted_email: String,
+    /// The hash of the user's email address, used for lookups and uniqueness checks.
+    pub email_hash: String,
+    /// The user's creation timestamp.
+    pub created_at: String,
+    /// The timestamp of the last change to the user's personal details, if they were ever changed.
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    /// The user's encrypted date of birth (`YYYY-MM-DD`). Missing for accounts created before it was collected.
+    #[serde(default)]
+    pub encrypted_date_of_birth: Option<String>,
+    /// The user's role.
+    #[serde(default)]
+    pub role: Role,
+    /// Whether the user has been banned by an admin.
+    #[serde(default)]
+    pub banned: bool,
+    /// The user's preferences. Defaults apply until the user saves their own.
+    #[serde(default)]
+    pub preferences: UserPreferences,
+    /// The timestamp when the user deleted their account, if it is deleted.
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    /// The timestamp when the personal data of the deleted account was anonymized.
+    #[serde(default)]
+    pub anonymized_at: Option<String>,
+    /// Whether the user sells privately or as a business, which decides whether VAT is charged
+    /// on their sales.
+    #[serde(default)]
+    pub seller_type: SellerType,
+    /// Whether the user carries the verified seller badge.
+    #[serde(default)]
+    pub verified: bool,
+    /// The timestamp when the user was verified, if they are.
+    #[serde(default)]
+    pub verified_at: Option<String>,
+}
+
+/// Represents a game offer in the database.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Offer {
+    /// The offer's ID.
+    pub id: Thing,
+    /// The title of the game being offered.
+    pub game_title: String,
+    /// The platform the game is for.
+    pub platform: Platform,
+    /// The condition of the item.
+    pub condition: Condition,
+    /// The price of the game.
+    pub price: f64,
+    /// A detailed description of the offer.
+    pub description: String,
+    /// The ID of the user who created this offer. This is a Uuid reference to the 'user' table.
+    pub seller_id: Thing,
+    /// The timestamp when the offer was created.
+    pub created_at: String,
+    /// Whether the offer has been hidden by a moderator.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Whether the seller's account has been deleted, which hides the offer until it is restored.
+    #[serde(default)]
+    pub seller_deleted: bool,
+    /// The timestamp when the offer was deleted, if it is deleted.
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    /// The ID of the seller or moderator who deleted the offer.
+    #[serde(default)]
+    pub deleted_by: Option<String>,
+    /// Whether the seller carries the verified seller badge.
+    #[serde(default)]
+    pub seller_verified: bool,
+    /// The category of the listing and its category-specific attributes.
+    #[serde(default)]
+    pub attributes: OfferAttributes,
+    /// The region coding of the game.
+    #[serde(default)]
+    pub region: Option<Region>,
+    /// The language printed on the box.
+    #[serde(default)]
+    pub box_language: Option<Language>,
+    /// The language of the manual.
+    #[serde(default)]
+    pub manual_language: Option<Language>,
+    /// Whether the listing passed the platform's authenticity check.
+    #[serde(default)]
+    pub authenticated: bool,
+    /// The photos of the item.
+    #[serde(default)]
+    pub photos: Vec<OfferPhoto>,
+    /// The images uploaded by the seller.
+    #[serde(default)]
+    pub images: Vec<OfferImage>,
+    /// The age rating of the game.
+    #[serde(default)]
+    pub age_rating: Option<AgeRating>,
+    /// The genres of the game.
+    #[serde(default)]
+    pub genres: Vec<Genre>,
+    /// Where the offer can be picked up, if the seller offers local pickup. Only the rounded
+    /// location and its area are stored.
+    #[serde(default)]
+    pub pickup: Option<PickupLocation>,
+    /// The games of a bundle listing, which sells them together for one price. Empty for
+    /// listings of a single item.
+    #[serde(default)]
+    pub bundle_items: Vec<BundleItem>,
+    /// The readable slug the offer can be looked up by instead of its ID, such as
+    /// `elden-ring-ps5-AB12`. Missing on offers created before offers had slugs.
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// The status of the offer in its lifecycle.
+    #[serde(default)]
+    pub status: OfferStatus,
+    /// The number of copies still for sale. Offers listed before offers had a quantity sell one
+    /// copy.
+    #[serde(default = "offer_stock::default_quantity")]
+    pub quantity: u32,
+    /// The ID of the buyer who reserved the offer. Never sent to clients.
+    #[serde(default, skip_serializing)]
+    pub reserved_by: Option<String>,
+    /// The timestamp when a buyer's reservation of the offer ends, unless the buyer ordered it.
+    /// Missing on offers the seller reserved themselves.
+    #[serde(default)]
+    pub reserved_until: Option<String>,
+    /// The timestamp when the offer expires unless the seller lists it again. Missing on drafts
+    /// and on offers created before offers expired.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    /// The timestamp when the seller last renewed the offer, which also reset `created_at`.
+    #[serde(default)]
+    pub renewed_at: Option<String>,
+    /// The timestamp until which the offer is featured. Missing on offers that were never
+    /// featured.
+    #[serde(default)]
+    pub featured_until: Option<String>,
+    /// The number of users who favorited the offer.
+    #[serde(default)]
+    pub favorites_count: u64,
+    /// The details of the game looked up in the metadata API, missing until the offer has been
+    /// enriched or if the API doesn't know the game.
+    #[serde(default)]
+    pub game: Option<GameMetadata>,
+    /// The currency the price is in. Offers listed before offers had a currency are priced in
+    /// euros.
+    #[serde(default)]
+    pub currency: Currency,
+    /// The price in the viewer's preferred currency. Not stored; filled in by the offer routes
+    /// with `convert_price`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converted_price: Option<ConvertedPrice>,
+    /// The public questions about the offer and their answers. Not stored; filled in by the
+    /// offer detail route.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub questions: Vec<OfferQuestion>,
+}
+
+impl Offer {
+    /// Returns whether the offer is a bundle of several games.
+    pub fn is_bundle(&self) -> bool {
+        !self.bundle_items.is_empty()
+    }
+
+    /// Returns the ID of the offer's seller.
+    pub fn seller(&self) -> Result<UserId, CustomError> {
+        UserId::try_from(&self.seller_id)
+    }
+
+    /// Returns whether the given user is the offer's seller.
+    pub fn is_seller(&self, user_id: &str) -> bool {
+        self.seller().is_ok_and(|seller| seller.as_str() == user_id)
+    }
+
+    /// Returns whether the offer is publicly visible, i.e. neither hidden by a moderator nor
+    /// deleted nor belonging to a deleted account, nor a draft or withdrawn by the seller.
+    ///
+    /// Reserved, sold-out, sold and expired offers stay visible so links to them keep working, but
+    /// only active offers appear in the listings.
+    pub fn is_listed(&self) -> bool {
+        !self.hidden
+            && !self.seller_deleted
+            && self.deleted_at.is_none()
+            && !matches!(self.status, OfferStatus::Draft | OfferStatus::Removed)
+    }
+
+    /// Sets `converted_price` to the price in another currency.
+    ///
+    /// # Arguments
+    ///
+    /// * `rates` - The current exchange rates.
+    /// * `currency` - The currency to convert into.
+    pub fn convert_price(&mut self, rates: &ExchangeRates, currency: Currency) {
+        self.converted_price = rates.convert(self.price, self.currency, currency);
+    }
+}
+
+/// Returns the `WHERE` conditions selecting the publicly listed offers and binds their values.
+///
+/// # Arguments
+///
+/// * `include_mature` - Whether mature-rated offers are included (only for adult viewers).
+/// * `vars` - The variables bound to the query.
+fn listed_offer_conditions(
+    include_mature: bool,
+    vars: &mut BTreeMap<String, Value>,
+) -> Vec<String> {
+    let mut conditions = vec![
+        "hidden != true".to_string(),
+        "seller_deleted != true".to_string(),
+        "deleted_at = NONE".to_string(),
+        "(status ?? $active_status) = $active_status".to_string(),
+    ];
+    vars.insert(
+        "active_status".into(),
+        Value::from(OfferStatus::Active.as_str()),
+    );
+    if !include_mature {
+        conditions.push("age_rating != $mature_rating".to_string());
+        vars.insert(
+            "mature_rating".into(),
+            Value::from(AgeRating::Pegi18.as_str()),
+        );
+    }
+    conditions
+}
+
+/// The result row of a `SELECT count() ... GROUP ALL` query.
+#[derive(Debug, Deserialize)]
+struct Count {
+    count: u64,
+}
+
+/// Represents the single database connection for all application data.
+#[derive(Clone)]
+pub struct Database {
+    /// The SurrealDB database connection.
+    pub db: Surreal<Db>,
+    /// Wakes up requests waiting for a user's notifications.
+    pub notification_signal: NotificationSignal,
+    /// Delivers conversation updates to the chat WebSockets.
+    pub chat_events: ChatEvents,
+    /// Sends the emails queued with `queue_email`.
+    pub email_sender: Arc<dyn EmailSender>,
+    /// Looks up the details of games new offers are enriched with.
+    pub metadata_provider: Arc<dyn MetadataProvider>,
+    /// The cached serial blacklist.
+    pub serial_blacklist: ListCache,
+    /// The cached IDs of banned and deleted users.
+    pub blocked_users: ListCache,
+    /// Whether every encrypted user field is bound, see `user_fields_bound`.
+    user_fields_bound: Arc<AtomicBool>,
+}
+
+impl Database {
+    /// Creates a new database connection and defines schemas for both users and offers.
+    ///
+    /// This function initializes a connection to the SurrealDB database using the path, namespace,
+    /// and database name specified in the environment variables. It defines unique indexes and
+    /// schemas for both the `users` and `offers` tables in their respective namespaces.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new database connection or an error if the connection fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CustomError` if:
+    /// - The `DATABASE_PATH`, `DATABASE_NAME`, `USER_DATABASE_NAMESPACE`, or `OFFER_DB_NAMESPACE`
+    /// - The connection to the database fails.
+    /// - Defining any of the schemas or indexes fails.
+    /// - `ENCRYPTION_KEY` is not the key the stored data was encrypted with.
+    pub async fn new() -> Result<Self, CustomError> {
+        // Get the database path from the environment variables.
+        let database_path = match var("DATABASE_PATH") {
+            Ok(path) => path,
+            Err(error) => {
+                tracing::error!("Error getting DATABASE_PATH: {}", error);
+                exit(1);
+            }
+        };
+
+        Self::connect(database_path).await
+    }
+
+    /// Opens the database at the given path and defines the schemas, like `new`.
+    ///
+    /// The database name, namespaces and encryption key are still read from the environment.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_path` - The path of the RocksDB database.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new database connection or an error if the connection fails.
+    pub async fn connect(database_path: String) -> Result<Self, CustomError> {
+        // Connect to the database.
+        let db = Surreal::new::<RocksDb>(database_path)
+            .await
+            .map_err(|e| CustomError::DatabaseError(e.to_string()))?;
+
+        // Get database name from environment variables.
+        let database_name =
+            var("DATABASE_NAME").map_err(|e| CustomError::DatabaseError(e.to_string()))?;
+
+        // Use the common database name for the connection.
+        db.use_db(&database_name)
+            .await
+            .map_err(|e| CustomError::DatabaseError(e.to_string()))?;
+
+        // --- Define schema for 'users' table in USER_DATABASE_NAMESPACE ---
+        let user_namespace = var("USER_DATABASE_NAMESPACE").map_err(|e| {
+            CustomError::DatabaseError(format!("USER_DATABASE_NAMESPACE not set: {}", e))
+        })?;
+        db.use_ns(&user_namespace).await.map_err(|e| {
+            CustomError::DatabaseError(format!("Failed to use user namespace: {}", e))
+        })?;
+
+        match db.query("DEFINE TABLE users SCHEMALESS;").await {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining users table: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE INDEX users_id ON users FIELDS id UNIQUE")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining users_id index on users: {}", error);
+                exit(1);
+            }
+        };
+        // Define email_hash field and unique index
+        match db
+            .query("DEFINE FIELD email_hash ON users TYPE string;")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining email_hash field on users: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE INDEX users_email_hash ON users FIELDS email_hash UNIQUE")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining users_email_hash index on users: {}", error);
+                exit(1);
+            }
+        };
+        moderation::define_user_schema(&db).await;
+        appeals::define_schema(&db).await;
+        audit::define_schema(&db).await;
+        dead_letters::define_schema(&db).await;
+        legal_holds::define_schema(&db).await;
+        legal_texts::define_schema(&db).await;
+        notifications::define_schema(&db).await;
+        blind_index::define_schema(&db).await;
+        addresses::define_schema(&db).await;
+        platforms::define_user_schema(&db).await;
+        field_encryption::define_schema(&db).await;
+
+        // --- Define schema for 'offers' table in OFFER_DB_NAMESPACE ---
+        let offer_namespace = var("OFFER_DB_NAMESPACE").map_err(|e| {
+            CustomError::DatabaseError(format!("OFFER_DB_NAMESPACE not set: {}", e))
+        })?;
+        db.use_ns(&offer_namespace).await.map_err(|e| {
+            CustomError::DatabaseError(format!("Failed to use offer namespace: {}", e))
+        })?;
+
+        match db.query("DEFINE TABLE offers SCHEMALESS;").await {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining offers table: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE INDEX offers_id ON offers FIELDS id UNIQUE")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining offers_id index on offers: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE FIELD game_title ON offers TYPE string;")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining game_title field on offers: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE FIELD platform ON offers TYPE string;")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining platform field on offers: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE FIELD condition ON offers TYPE string;")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining condition field on offers: {}", error);
+                exit(1);
+            }
+        };
+        match db.query("DEFINE FIELD price ON offers TYPE float;").await {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining price field on offers: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE FIELD description ON offers TYPE string;")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining description field on offers: {}", error);
+                exit(1);
+            }
+        };
+        // This defines a link to the 'user' table. Note: This link assumes 'user' is in the 'users' namespace.
+        // This setup (same database, different namespaces) allows this.
+        match db
+            .query("DEFINE FIELD seller_id ON offers TYPE record<user>;")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining seller_id field on offers: {}", error);
+                exit(1);
+            }
+        };
+        match db
+            .query("DEFINE FIELD created_at ON offers TYPE datetime;")
+            .await
+        {
+            Ok(_) => {}
+            Err(error) => {
+                tracing::error!("Error defining created_at field on offers: {}", error);
+                exit(1);
+            }
+        };
+        moderation::define_offer_schema(&db).await;
+        catalog::define_offer_schema(&db).await;
+        serial_blacklist::define_schema(&db).await;
+        authenticity::define_schema(&db).await;
+        listing_rules::define_schema(&db).await;
+        search::define_schema(&db).await;
+        offer_status::define_schema(&db).await;
+        offer_deletion::define_schema(&db).await;
+        reservations::define_schema(&db).await;
+        offer_slugs::define_schema(&db).await;
+        featured::define_schema(&db).await;
+        offer_questions::define_schema(&db).await;
+        favorites::define_schema(&db).await;
+        price_history::define_schema(&db).await;
+        offer_views::define_schema(&db).await;
+        image_blobs::define_schema(&db).await;
+        bids::define_schema(&db).await;
+        auctions::define_schema(&db).await;
+        orders::define_schema(&db).await;
+        invoices::define_schema(&db).await;
+        fees::define_schema(&db).await;
+        cart::define_schema(&db).await;
+        price_index::define_schema(&db).await;
+        conversations::define_schema(&db).await;
+        saved_searches::define_schema(&db).await;
+        wishlist::define_schema(&db).await;
+        games::define_schema(&db).await;
+        taxes::define_schema(&db).await;
+        promo_codes::define_schema(&db).await;
+        trending::define_schema(&db).await;
+        conditions::define_schema(&db).await;
+        platforms::define_schema(&db).await;
+        seller_verification::define_schema(&db).await;
+
+        let database = Database {
+            db,
+            notification_signal: NotificationSignal::default(),
+            chat_events: ChatEvents::default(),
+            email_sender: email_sender_from_env(),
+            metadata_provider: metadata_provider_from_env(),
+            serial_blacklist: ListCache::default(),
+            blocked_users: ListCache::default(),
+            user_fields_bound: Arc::new(AtomicBool::new(false)),
+        };
+        database.verify_encryption_key().await?;
+        database.refresh_user_fields_bound().await?;
+        Ok(database)
+    }
+
+    /// Replaces the email sender, e.g. with a recording `NoopEmailSender` in tests.
+    ///
+    /// # Arguments
+    ///
+    /// * `email_sender` - The sender to use for queued emails.
+    pub fn with_email_sender(mut self, email_sender: Arc<dyn EmailSender>) -> Self {
+        self.email_sender = email_sender;
+        self
+    }
+
+    /// Replaces the metadata provider, e.g. with a `NoopMetadataProvider` in tests.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata_provider` - The provider to look up games with.
+    pub fn with_metadata_provider(mut self, metadata_provider: Arc<dyn MetadataProvider>) -> Self {
+        self.metadata_provider = metadata_provider;
+        self
+    }
+
+    /// Helper to set the user namespace.
+    ///
+    /// Queries select their namespace before they run, so this is also where database faults
+    /// are injected when built with the `fault-injection` feature.
+    async fn use_user_namespace(&self) -> Result<(), CustomError> {
+        #[cfg(feature = "fault-injection")]
+        inject_dependency_fault(Dependency::Database).await?;
+        let user_namespace = var("USER_DATABASE_NAMESPACE").map_err(|e| {
+            CustomError::DatabaseError(format!("USER_DATABASE_NAMESPACE not set: {}", e))
+        })?;
+        self.db.use_ns(&user_namespace).await.map_err(|e| {
+            CustomError::DatabaseError(format!("Failed to switch to user namespace: {}", e))
+        })?;
+        Ok(())
+    }
+
+    /// Helper to set the offer namespace.
+    async fn use_offer_namespace(&self) -> Result<(), CustomError> {
+        #[cfg(feature = "fault-injection")]
+        inject_dependency_fault(Dependency::Database).await?;
+        let offer_namespace = var("OFFER_DB_NAMESPACE").map_err(|e| {
+            CustomError::DatabaseError(format!("OFFER_DB_NAMESPACE not set: {}", e))
+        })?;
+        self.db.use_ns(&offer_namespace).await.map_err(|e| {
+            CustomError::DatabaseError(format!("Failed to switch to offer namespace: {}", e))
+        })?;
+        Ok(())
+    }
+
+    /// Checks that both namespaces can be queried.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` describing why the database is unavailable.
+    pub async fn ping(&self) -> Result<(), CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        self.db.query("RETURN true;").await?.check()?;
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        self.db.query("RETURN true;").await?.check()?;
+        Ok(())
+    }
+
+    /// Registers a new user in the database.
+    ///
+    /// This function takes user details as input, encrypts sensitive information, hashes the password,
+    /// and stores the user data in the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `firstname` - The user's first name.
+    /// * `lastname` - The user's last name.
+    /// * `username` - The user's username.
+    /// * `password` - The user's password.
+    /// * `email` - The user's email address.
+    /// * `date_of_birth` - The user's date of birth, used for age-gating mature listings.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a boolean indicating success or failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CustomError` if:
+    /// - A user with the given email already exists.
+    /// - Encryption fails.
+    /// - Hashing the password fails.
+    /// - Creating the user in the database fails.
+    pub async fn register(
+        &self,
+        firstname: String,
+        lastname: String,
+        username: String,
+        password: String,
+        email: String,
+        date_of_birth: NaiveDate,
+    ) -> Result<bool, CustomError> {
+        tracing::info!("Registering user with email: {}", email);
+
+        // Hash the email for lookup and storage
+        let email_hash = hash_email(&email);
+
+        // Generate a new UUID for the user.
+        let uuid = Uuid::new_v4().to_string();
+        // Index the names for lookups before they are encrypted.
+        let firstname_index = blind_index("firstname", &firstname)?;
+        let lastname_index = blind_index("lastname", &lastname)?;
+        // Encrypt the user's personal information, bound to the user and field.
+        let [
+            encrypted_firstname,
+            encrypted_lastname,
+            encrypted_email,
+            encrypted_date_of_birth,
+        ] = encrypt_fields_blocking(
+            uuid.clone(),
+            [
+                ("encrypted_firstname", firstname),
+                ("encrypted_lastname", lastname),
+                ("encrypted_email", email),
+                (
+                    "encrypted_date_of_birth",
+                    date_of_birth.format(DATE_OF_BIRTH_FORMAT).to_string(),
+                ),
+            ],
+        )
+        .await?;
+
+        // Hash the password.
+        let password_hash = hash_password_blocking(password).await?;
+
+        // The CPU pool work is done first: other requests switch the namespace while it runs
+        self.use_user_namespace().await?; // Switch to user namespace
+
+        let sql = "SELECT * FROM users WHERE email_hash = $email_hash";
+
+        // Bind the parameters to the query.
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("email_hash".into(), Value::from(email_hash.as_str()));
+
+        // Execute the query.
+        let mut response = self.db.query(sql).bind(vars).await?;
+        let mut users: Vec<User> = response.take(0)?;
+
+        if let Some(_user) = users.pop() {
+            tracing::warn!("User with email hash {} already exists", email_hash);
+            return Err(CustomError::UserAlreadyExists);
+        }
+
+        // Create the SQL query.
+        let sql = "CREATE users SET id = $id, encrypted_firstname = $encrypted_firstname, encrypted_lastname = $encrypted_lastname, firstname_index = $firstname_index, lastname_index = $lastname_index, username = $username, password_hash = $password_hash, encrypted_email = $encrypted_email, email_hash = $email_hash, encrypted_date_of_birth = $encrypted_date_of_birth, role = 'user', banned = false, created_at = time::now();";
+
+        // Bind the parameters to the query.
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("id".into(), Value::from(uuid.as_str()));
+        vars.insert(
+            "encrypted_firstname".into(),
+            Value::from(encrypted_firstname.as_str()),
+        );
+        vars.insert(
+            "encrypted_lastname".into(),
+            Value::from(encrypted_lastname.as_str()),
+        );
+        vars.insert(
+            "firstname_index".into(),
+            Value::from(firstname_index.as_str()),
+        );
+        vars.insert(
+            "lastname_index".into(),
+            Value::from(lastname_index.as_str()),
+        );
+        vars.insert("username".into(), Value::from(username.as_str()));
+        vars.insert("password_hash".into(), Value::from(password_hash.as_str()));
+        vars.insert(
+            "encrypted_email".into(),
+            Value::from(encrypted_email.as_str()),
+        );
+        vars.insert("email_hash".into(), Value::from(email_hash.as_str()));
+        vars.insert(
+            "encrypted_date_of_birth".into(),
+            Value::from(encrypted_date_of_birth.as_str()),
+        );
+
+        // Execute the query.
+        let created: Result<surrealdb::Response, surrealdb::Error> =
+            self.db.query(sql).bind(vars).await;
+
+        // Return the result.
+        match created {
+            Ok(_) => {
+                tracing::info!(
+                    "User registered successfully with email hash: {}",
+                    email_hash
+                );
+                Ok(true)
+            }
+            Err(error) => {
+                tracing::error!("Error creating user: {}", error);
+                Err(CustomError::DatabaseError(error.to_string()))
+            }
+        }
+    }
+
+    /// Authenticates a user.
+    ///
+    /// This function authenticates a user by verifying the provided email and password against the
+    /// stored user data in the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `email` - The user's email address.
+    /// * `password` - The user's password.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the user's data or a `CustomError` if authentication fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CustomError` if:
+    /// - The user is not found.
+    /// - The password is invalid.
+    /// - The user is banned.
+    /// - The account has been deleted (reported as not found).
+    ///
+    /// If the stored password hash uses outdated Argon2 settings, it is transparently upgraded.
+    pub async fn authenticate_user(
+        &self,
+        email: String,
+        password: String,
+    ) -> Result<User, CustomError> {
+        let user = self.verify_credentials(email, password.clone()).await?;
+        if user.deleted_at.is_some() {
+            tracing::warn!("Deleted user attempted to log in: {}", user.email_hash);
+            return Err(CustomError::UserNotFound);
+        }
+        if user.banned {
+            tracing::warn!("Banned user attempted to log in: {}", user.email_hash);
+            return Err(CustomError::UserBanned);
+        }
+        if needs_rehash(&user.password_hash) {
+            self.upgrade_password_hash(&user, password).await;
+        }
+        Ok(user)
+    }
+
+    /// Re-hashes a user's password with the current Argon2 parameters.
+    ///
+    /// Called after a successful login, when the plaintext password is available. Failures are
+    /// logged but don't affect the login, as the old hash stays valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user whose password hash is outdated.
+    /// * `password` - The user's verified password.
+    async fn upgrade_password_hash(&self, user: &User, password: String) {
+        let Ok(password_hash) = hash_password_blocking(password).await else {
+            return;
+        };
+
+        // Hashing takes a while, and other requests switch the namespace in the meantime
+        if let Err(e) = self.use_user_namespace().await {
+            tracing::error!("Failed to upgrade password hash: {:?}", e);
+            return;
+        }
+        let sql = "UPDATE $user_id SET password_hash = $password_hash RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user.id.clone()));
+        vars.insert("password_hash".into(), Value::from(password_hash.as_str()));
+
+        let updated = match self.db.query(sql).bind(vars).await {
+            Ok(mut response) => response.take::<Option<User>>(0),
+            Err(e) => Err(e),
+        };
+        match updated {
+            Ok(Some(_)) => tracing::info!("Upgraded password hash for user: {}", user.id),
+            Ok(None) => tracing::warn!(
+                "User {} no longer exists, password hash not upgraded",
+                user.id
+            ),
+            Err(e) => tracing::error!("Failed to upgrade password hash: {:?}", e),
+        }
+    }
+
+    /// Verifies a user's email and password without checking whether the account may log in.
+    ///
+    /// This is used by `authenticate_user` and by the limited pre-auth endpoints available to
+    /// banned users, such as submitting an appeal.
+    ///
+    /// # Arguments
+    ///
+    /// * `email` - The user's email address.
+    /// * `password` - The user's password.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the user's data or a `CustomError` if verification fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CustomError` if:
+    /// - The user is not found.
+    /// - The password is invalid.
+    pub async fn verify_credentials(
+        &self,
+        email: String,
+        password: String,
+    ) -> Result<User, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!(
+            "Authenticating user with email (hashed for lookup): {}",
+            email
+        );
+
+        // Hash the incoming email for lookup
+        let email_hash = hash_email(&email);
+
+        // Create the SQL query.
+        let sql = "SELECT * FROM users WHERE email_hash = $email_hash";
+
+        // Bind the parameters to the query.
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("email_hash".into(), Value::from(email_hash.as_str()));
+
+        // Execute the query.
+        let mut response = self.db.query(sql).bind(vars).await?;
+        let mut users: Vec<User> = response.take(0)?;
+
+        if let Some(user) = users.pop() {
+            if verify_password_blocking(password, user.password_hash.clone()).await? {
+                tracing::info!(
+                    "User authenticated successfully with email hash: {}",
+                    email_hash
+                );
+                Ok(user)
+            } else {
+                tracing::warn!("Invalid password for user with email hash: {}", email_hash);
+                Err(CustomError::InvalidPassword)
+            }
+        } else {
+            // Spend as long as a real verification so response times don't reveal which emails exist
+            verify_password_blocking(password, dummy_password_hash().to_string()).await?;
+            tracing::warn!("User not found with email hash: {}", email_hash);
+            Err(CustomError::UserNotFound)
+        }
+    }
+
+    /// Changes the username of a user.
+    ///
+    /// This function updates the username of an existing user in the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to update.
+    /// * `new_username` - The new username.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CustomError` if:
+    /// - The update operation fails.
+    pub async fn change_username(
+        &self,
+        user_id: String,
+        new_username: String,
+    ) -> Result<(), CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        // Create the SQL query.
+        let sql = "UPDATE users SET username = $new_username WHERE id = $user_id;";
+
+        // Bind the parameters to the query.
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("new_username".into(), Value::from(new_username.as_str()));
+
+        // Execute the query.
+        self.db.query(sql).bind(vars).await?;
+        Ok(())
+    }
+
+    /// Changes the password of a user.
+    ///
+    /// This function updates the password of an existing user in the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to update.
+    /// * `new_password` - The new password.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CustomError` if:
+    /// - The update operation fails.
+    pub async fn change_password(
+        &self,
+        user_id: String,
+        new_password: String,
+    ) -> Result<(), CustomError> {
+        // Hash the new password.
+        let password_hash = hash_password_blocking(new_password).await?;
+
+        self.use_user_namespace().await?; // Switch to user namespace
+
+        // Create the SQL query.
+        let sql = "UPDATE users SET password_hash = $password_hash WHERE id = $user_id;";
+
+        // Bind the parameters to the query.
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("password_hash".into(), Value::from(password_hash.as_str()));
+
+        // Execute the query.
+        self.db.query(sql).bind(vars).await?;
+        Ok(())
+    }
+
+    /// Changes the personal details of a user.
+    ///
+    /// The new names are encrypted and indexed like at registration. Values passed as `None` are
+    /// left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to update.
+    /// * `firstname` - The new first name (optional).
+    /// * `lastname` - The new last name (optional).
+    /// * `seller_type` - Whether the user now sells privately or as a business (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the user exists and was updated.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CustomError` if:
+    /// - The encryption key cannot be loaded or the encryption fails.
+    /// - The update operation fails.
+    pub async fn update_profile(
+        &self,
+        user_id: String,
+        firstname: Option<String>,
+        lastname: Option<String>,
+        seller_type: Option<SellerType>,
+    ) -> Result<bool, CustomError> {
+        let mut assignments = vec!["updated_at = time::now()".to_string()];
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        if let Some(seller_type) = seller_type {
+            assignments.push("seller_type = $seller_type".to_string());
+            vars.insert("seller_type".into(), Value::from(seller_type.as_str()));
+        }
+        for (name, field, value) in [
+            ("firstname", "encrypted_firstname", firstname),
+            ("lastname", "encrypted_lastname", lastname),
+        ] {
+            if let Some(value) = value {
+                let index = blind_index(name, &value)?;
+                assignments.push(format!("{}_index = ${}_index", name, name));
+                vars.insert(format!("{}_index", name), Value::from(index.as_str()));
+                let [encrypted] =
+                    encrypt_fields_blocking(user_id.clone(), [(field, value)]).await?;
+                assignments.push(format!("{} = ${}", field, field));
+                vars.insert(field.into(), Value::from(encrypted.as_str()));
+            }
+        }
+
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Updating personal details of user {}", user_id);
+        let sql = format!(
+            "UPDATE type::thing('users', $user_id) SET {} RETURN AFTER;",
+            assignments.join(", ")
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<User> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+
+    /// Calculates a user's current age from their encrypted date of birth.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the user's age, or `None` if the user does not exist or never
+    /// provided a date of birth.
+    pub async fn get_user_age(&self, user_id: String) -> Result<Option<u32>, CustomError> {
+        let Some(encrypted) = self
+            .get_user_by_id(user_id.clone())
+            .await?
+            .and_then(|user| user.encrypted_date_of_birth)
+        else {
+            return Ok(None);
+        };
+
+        let strict = self.user_fields_bound();
+        let date_of_birth = CpuPool::global()
+            .run(move || {
+                let key_bytes: [u8; 32] = generate_key()?.into();
+                let aad = field_aad(&user_id, "encrypted_date_of_birth");
+                decrypt_user_field(strict, &key_bytes, &encrypted, &aad)
+            })
+            .await??;
+        let date_of_birth = NaiveDate::parse_from_str(&date_of_birth, DATE_OF_BIRTH_FORMAT)
+            .map_err(|_| CustomError::DecryptionError)?;
+        Ok(Some(age_on(date_of_birth, Utc::now().date_naive())))
+    }
+
+    /// Creates a new game offer in the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_title` - The title of the game.
+    /// * `platform` - The platform of the game.
+    /// * `condition` - The condition of the game.
+    /// * `price` - The price of the game.
+    /// * `description` - The description of the offer.
+    /// * `seller_id` - The ID of the user selling the game.
+    /// * `metadata` - The category, region and language metadata of the listing.
+    /// * `status` - The initial status, either `Active` or `Draft`.
+    ///
+    /// Offers whose serial number is blacklisted are created hidden and reported to the moderators.
+    /// The offer gets a slug built from the game title and platform, which never changes.
+    /// Offers of verified sellers carry the verified seller badge. The price of an active offer is recorded in the price history of its title.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Offer` or a `CustomError` if creation fails.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_offer(
+        &self,
+        game_title: String,
+        platform: Platform,
+        condition: Condition,
+        price: f64,
+        description: String,
+        seller_id: String, // This is the UUID string
+        metadata: OfferMetadata,
+        status: OfferStatus,
+    ) -> Result<Offer, CustomError> {
+        let flagged_serial = self.blacklisted_serial(&metadata).await?;
+        let seller_verified = self
+            .get_user_by_id(seller_id.clone())
+            .await?
+            .is_some_and(|seller| seller.verified);
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Creating offer for game: {}", game_title);
+
+        let offer_id = Uuid::new_v4().to_string();
+        let slug = self
+            .unique_offer_slug(&game_title, platform, &offer_id)
+            .await?;
+
+        let seller_id_thing = UserId::new(seller_id).to_reference();
+
+        let mut assignments = vec![
+            "id = $id".to_string(),
+            "game_title = $game_title".to_string(),
+            "slug = $slug".to_string(),
+            "platform = $platform".to_string(),
+            "condition = $condition".to_string(),
+            "price = $price".to_string(),
+            "description = $description".to_string(),
+            "seller_id = $seller_id_thing".to_string(),
+            "hidden = $hidden".to_string(),
+            "authenticated = false".to_string(),
+            "seller_verified = $seller_verified".to_string(),
+            "status = $status".to_string(),
+            "created_at = time::now()".to_string(),
+        ];
+        if status == OfferStatus::Active {
+            assignments.push(format!(
+                "expires_at = time::now() + {}d",
+                offer_status::OFFER_LIFETIME_DAYS
+            ));
+        }
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        metadata.push_assignments(&mut assignments, &mut vars);
+        vars.insert("id".into(), Value::from(offer_id.as_str()));
+        vars.insert("game_title".into(), Value::from(game_title.as_str()));
+        vars.insert("slug".into(), Value::from(slug.as_str()));
+        vars.insert("platform".into(), Value::from(platform.as_str()));
+        vars.insert("condition".into(), Value::from(condition.as_str()));
+        vars.insert("price".into(), Value::from(price));
+        vars.insert("description".into(), Value::from(description.as_str()));
+        vars.insert("hidden".into(), Value::from(flagged_serial.is_some()));
+        vars.insert("status".into(), Value::from(status.as_str()));
+        vars.insert("seller_verified".into(), Value::from(seller_verified));
+        // Bind the constructed Thing for seller_id
+        vars.insert("seller_id_thing".into(), Value::from(seller_id_thing));
+
+        let sql = format!("CREATE offers SET {};", assignments.join(", "));
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created_offer: Option<Offer> = response.take(0)?;
+
+        let offer = created_offer.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created offer after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created offer".to_string())
+        })?;
+        if let Some(serial) = flagged_serial {
+            self.report_blacklisted_serial(&offer, &serial).await?;
+        }
+        self.report_reused_photos(&offer).await?;
+        if offer.status == OfferStatus::Active {
+            self.record_price(&offer, PriceEvent::Listed).await?;
+        }
+        Ok(offer)
+    }
+
+    /// Retrieves the publicly listed offers matching the given filter from the database.
+    ///
+    /// Every filter is translated into a parameterized `WHERE` condition. Prices are compared and
+    /// sorted in the filter's currency, converted with the current exchange rates. Sorted newest
+    /// first, featured offers are ranked before the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The catalog metadata, platform, condition, price range and seller the offers must match, and their order.
+    /// * `include_mature` - Whether mature-rated offers are included (only for adult viewers).
+    /// * `pagination` - The page to return.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the requested page of `Offer` structs and its pagination details, or a
+    /// `CustomError` if retrieval fails.
+    pub async fn query_offers(
+        &self,
+        filter: &OfferFilter,
+        include_mature: bool,
+        pagination: &Pagination,
+    ) -> Result<(Vec<Offer>, PageInfo), CustomError> {
+        // Fetching the rates may take a while, so it happens before a namespace is selected
+        let rates = ExchangeRateCache::global().rates().await;
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Retrieving all offers.");
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let price = converted_price_sql(
+            rates.as_ref(),
+            filter.currency.unwrap_or_default(),
+            &mut vars,
+        );
+        let mut conditions = listed_offer_conditions(include_mature, &mut vars);
+        filter.push_conditions(&mut conditions, &mut vars, &price);
+        pagination.bind(&mut vars);
+
+        let sql = format!(
+            "SELECT *, {price} AS sort_price, {featured} AS is_featured FROM offers WHERE {conditions} ORDER BY {order} LIMIT $limit START $start; SELECT count() FROM offers WHERE {conditions} GROUP ALL;",
+            conditions = conditions.join(" AND "),
+            featured = featured::FEATURED_CONDITION,
+            order = filter.sort.order_by()
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Vec<Offer> = response.take(0)?;
+        let total: Option<Count> = response.take(1)?;
+        let total = total.map_or(0, |total| total.count);
+        Ok((offers, PageInfo::new(pagination, total)))
+    }
+
+    /// Retrieves a single offer by its ID. Deleted offers are treated as missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `Offer` struct or a `CustomError` if retrieval fails.
+    pub async fn get_offer_by_id(&self, offer_id: String) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Retrieving offer with ID: {}", offer_id);
+        let sql = "SELECT * FROM offers WHERE id = $offer_id AND deleted_at = NONE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offer: Option<Offer> = response.take(0)?;
+        Ok(offer)
+    }
+
+    /// Retrieves all offers made by a specific seller, except deleted ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `seller_id` - The ID of the seller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Offer` structs or a `CustomError` if retrieval fails.
+    pub async fn get_offers_by_seller_id(
+        &self,
+        seller_id: String,
+    ) -> Result<Vec<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Retrieving offers for seller ID: {}", seller_id);
+        let seller_id_thing = UserId::new(seller_id).to_reference();
+        let sql = "SELECT * FROM offers WHERE seller_id = $seller_id_thing AND deleted_at = NONE ORDER BY created_at DESC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("seller_id_thing".into(), Value::from(seller_id_thing));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Vec<Offer> = response.take(0)?;
+        Ok(offers)
+    }
+
+    /// Updates an existing offer in the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer to update.
+    /// * `game_title` - The new game title (optional).
+    /// * `platform` - The new platform (optional).
+    /// * `condition` - The new condition (optional).
+    /// * `price` - The new price (optional).
+    /// * `description` - The new description (optional).
+    /// * `metadata` - The new category, region and language metadata. Unset fields are left unchanged.
+    ///
+    /// Offers updated with a blacklisted serial number are hidden and reported to the moderators.
+    /// Setting the quantity of a sold-out offer lists it again.
+    /// Changing anything but the price removes the "authenticated" badge. A new price of a listed
+    /// offer is recorded in the price history of its title.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated `Offer` or a `CustomError` if update fails.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_offer(
+        &self,
+        offer_id: String,
+        game_title: Option<String>,
+        platform: Option<Platform>,
+        condition: Option<Condition>,
+        price: Option<f64>,
+        description: Option<String>,
+        metadata: OfferMetadata,
+    ) -> Result<Offer, CustomError> {
+        let flagged_serial = self.blacklisted_serial(&metadata).await?;
+        let changes_photos = metadata.photos.is_some();
+        let changes_price = price.is_some() || metadata.currency.is_some();
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Updating offer with ID: {}", offer_id);
+        let changes_item = game_title.is_some()
+            || platform.is_some()
+            || condition.is_some()
+            || description.is_some()
+            || metadata.attributes.is_some()
+            || metadata.region.is_some()
+            || metadata.box_language.is_some()
+            || metadata.manual_language.is_some()
+            || metadata.photos.is_some()
+            || metadata.bundle_items.is_some();
+        let mut updates = Vec::new();
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+
+        if let Some(gt) = game_title {
+            updates.push("game_title = $game_title".to_string());
+            vars.insert("game_title".into(), Value::from(gt));
+        }
+        if let Some(p) = platform {
+            updates.push("platform = $platform".to_string());
+            vars.insert("platform".into(), Value::from(p.as_str()));
+        }
+        if let Some(c) = condition {
+            updates.push("condition = $condition".to_string());
+            vars.insert("condition".into(), Value::from(c.as_str()));
+        }
+        if let Some(pr) = price {
+            updates.push("price = $price".to_string());
+            vars.insert("price".into(), Value::from(pr));
+        }
+        if let Some(d) = description {
+            updates.push("description = $description".to_string());
+            vars.insert("description".into(), Value::from(d));
+        }
+        // Changing anything but the price invalidates a previous authenticity check
+        if changes_item {
+            updates.push("authenticated = false".to_string());
+        }
+        metadata.push_assignments(&mut updates, &mut vars);
+        // Restocking a sold-out offer lists it again
+        if metadata.quantity.is_some() {
+            updates.push(format!(
+                "expires_at = IF status = 'sold_out' THEN time::now() + {}d ELSE expires_at END",
+                offer_status::OFFER_LIFETIME_DAYS
+            ));
+            updates.push(
+                "status_changed_at = IF status = 'sold_out' THEN time::now() ELSE status_changed_at END"
+                    .to_string(),
+            );
+            updates
+                .push("status = IF status = 'sold_out' THEN 'active' ELSE status END".to_string());
+        }
+        if flagged_serial.is_some() {
+            updates.push("hidden = true".to_string());
+        }
+
+        if updates.is_empty() {
+            tracing::warn!("No fields provided for update for offer ID: {}", offer_id);
+            return Err(CustomError::DatabaseError(
+                "No fields to update".to_string(),
+            ));
+        }
+
+        let sql = format!(
+            "UPDATE offers SET {} WHERE id = $offer_id RETURN *;",
+            updates.join(", ")
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated_offer: Option<Offer> = response.take(0)?;
+
+        let offer = updated_offer.ok_or_else(|| {
+            tracing::error!("Failed to retrieve updated offer for ID: {}", offer_id);
+            CustomError::DatabaseError("Failed to update or retrieve offer".to_string())
+        })?;
+        if let Some(serial) = flagged_serial {
+            self.report_blacklisted_serial(&offer, &serial).await?;
+        }
+        if changes_photos {
+            self.report_reused_photos(&offer).await?;
+        }
+        if changes_price && matches!(offer.status, OfferStatus::Active | OfferStatus::Reserved) {
+            self.record_price(&offer, PriceEvent::PriceChange).await?;
+        }
+        Ok(offer)
+    }
+}
+
+/// Returns the key part of a record ID as a string (e.g. the UUID of `users:⟨uuid⟩`).
+///
+/// # Arguments
+///
+/// * `thing` - The record ID.
+///
+/// # Returns
+///
+/// The key of the record.
+pub fn record_key(thing: &Thing) -> String {
+    match &thing.id {
+        Id::String(key) => key.clone(),
+        Id::Uuid(uuid) => uuid.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Executes a schema definition statement, exiting the process if it fails.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `statement` - The SurrealQL `DEFINE` statement to execute.
+/// * `description` - A short description of what is being defined, used for logging.
+pub(crate) async fn define(db: &Surreal<Db>, statement: &str, description: &str) {
+    if let Err(error) = db.query(statement).await {
+        tracing::error!("Error defining {}: {}", description, error);
+        exit(1);
+    }
+}
+
+/// Hashes an email address for lookup and storage.
+///
+/// # Arguments
+///
+/// * `email` - The email address to hash.
+///
+/// # Returns
+///
+/// The hex-encoded SHA-256 hash of the email address.
+pub fn hash_email(email: &str) -> String {
+    format!("{:x}", Sha256::digest(email.as_bytes()))
+}
+
+/// Hashes a password on the `CpuPool`.
+///
+/// Argon2 is deliberately expensive, so running it directly on the async executor would stall
+/// the worker and every other request scheduled on it.
+///
+/// # Arguments
+///
+/// * `password` - The password to hash.
+///
+/// # Returns
+///
+/// A `Result` containing the password hash, `CustomError::Overloaded` if the pool is saturated, or
+/// `CustomError::HashingError` if hashing fails.
+async fn hash_password_blocking(password: String) -> Result<String, CustomError> {
+    CpuPool::global()
+        .run(move || hash_random_salt(&password))
+        .await?
+        .map_err(|e| {
+            tracing::error!("Error hashing password: {}", e);
+            CustomError::HashingError
+        })
+}
+
+/// Verifies a password against a password hash on the `CpuPool`.
+///
+/// # Arguments
+///
+/// * `password` - The password to verify.
+/// * `password_hash` - The password hash to compare against.
+///
+/// # Returns
+///
+/// A `Result` containing whether the password matches, or `CustomError::Overloaded` if the pool
+/// is saturated.
+async fn verify_password_blocking(
+    password: String,
+    password_hash: String,
+) -> Result<bool, CustomError> {
+    CpuPool::global()
+        .run(move || verify_password(&password, &password_hash).is_ok())
+        .await
+}
+
+/// Encrypts several fields of a user with the encryption key on the `CpuPool`.
+///
+/// Every value is bound to the user and its field (see `encrypt_bound`), so it can't be moved to
+/// another user or field without decryption failing.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user the values belong to.
+/// * `fields` - The names of the fields and their plaintext values.
+///
+/// # Returns
+///
+/// A `Result` containing the encrypted values in the same order, or a `CustomError` if the key
+/// cannot be loaded, the encryption fails or the pool is saturated.
+async fn encrypt_fields_blocking<const N: usize>(
+    user_id: String,
+    fields: [(&'static str, String); N],
+) -> Result<[String; N], CustomError> {
+    CpuPool::global()
+        .run(move || {
+            let key_bytes: [u8; 32] = generate_key()?.into();
+            let mut encrypted = Vec::with_capacity(N);
+            for (field, value) in &fields {
+                encrypted.push(
+                    encrypt_bound(&key_bytes, value, &field_aad(&user_id, field))
+                        .map_err(|_| CustomError::EncryptionError)?,
+                );
+            }
+            encrypted
+                .try_into()
+                .map_err(|_| CustomError::EncryptionError)
+        })
+        .await?
+}
@@ -0,0 +1,924 @@
+//! src/database/moderation.rs
+//!
+//! This module handles the moderation-related database interactions: user roles, bans,
+//! hidden offers, abuse reports, moderation reason templates and the record of moderation actions.
+
+use super::{Database, Offer, Role, User, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The state of an abuse report.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    /// The report has not been handled yet.
+    #[default]
+    Open,
+    /// The report was reviewed and dismissed without action.
+    Dismissed,
+    /// The report was reviewed and acted upon.
+    Resolved,
+}
+
+impl ReportStatus {
+    /// Returns the string stored in the database for this status.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportStatus::Open => "open",
+            ReportStatus::Dismissed => "dismissed",
+            ReportStatus::Resolved => "resolved",
+        }
+    }
+}
+
+/// The reason a user gives when reporting an offer.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    /// The offer looks like a scam, e.g. the seller asks for payment outside the shop.
+    Scam,
+    /// The offer sells an item that may not be sold on the shop.
+    ProhibitedItem,
+    /// The offer sells a counterfeit or pirated copy.
+    Counterfeit,
+    /// The offer contains offensive text or images.
+    OffensiveContent,
+    /// The offer is spam or a duplicate listing.
+    Spam,
+    /// Any other reason, explained in the details.
+    Other,
+}
+
+impl ReportReason {
+    /// Returns the string stored in the database for this reason.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportReason::Scam => "scam",
+            ReportReason::ProhibitedItem => "prohibited_item",
+            ReportReason::Counterfeit => "counterfeit",
+            ReportReason::OffensiveContent => "offensive_content",
+            ReportReason::Spam => "spam",
+            ReportReason::Other => "other",
+        }
+    }
+}
+
+/// Represents an abuse report filed against an offer.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Report {
+    /// The report's ID.
+    pub id: Thing,
+    /// The ID of the reported offer.
+    pub offer_id: String,
+    /// The ID of the user who filed the report.
+    pub reporter_id: String,
+    /// The reason given for the report: a `ReportReason` for reports filed by users,
+    /// `offer_question` for reported questions about the offer, or the check that raised it
+    /// (e.g. `blacklisted_serial`) for automatic reports.
+    pub reason: String,
+    /// Additional details provided by the reporter.
+    pub details: String,
+    /// The current state of the report.
+    #[serde(default)]
+    pub status: ReportStatus,
+    /// The timestamp when the report was filed.
+    pub created_at: String,
+}
+
+/// A reason template moderators must pick from when acting on content.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModerationReason {
+    /// The reason's ID.
+    pub id: Thing,
+    /// The unique, machine-readable code of the reason (e.g. `scam`).
+    pub code: String,
+    /// A short title shown to the affected user.
+    pub title: String,
+    /// The explanation sent to the affected user.
+    pub message: String,
+    /// Whether moderators can currently pick this reason.
+    #[serde(default)]
+    pub active: bool,
+    /// The timestamp when the reason was created.
+    pub created_at: String,
+}
+
+/// The kind of sanction a moderation action applied.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SanctionKind {
+    /// An offer was hidden from the public listings.
+    OfferHidden,
+    /// An offer was deleted.
+    OfferDeleted,
+    /// A user was banned.
+    UserBanned,
+}
+
+impl SanctionKind {
+    /// Returns the string stored in the database for this sanction.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SanctionKind::OfferHidden => "offer_hidden",
+            SanctionKind::OfferDeleted => "offer_deleted",
+            SanctionKind::UserBanned => "user_banned",
+        }
+    }
+}
+
+/// The state of the appeal against a moderation action.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AppealState {
+    /// The affected user has not appealed.
+    #[default]
+    None,
+    /// An appeal was submitted and awaits a decision.
+    Open,
+    /// The appeal was approved and the sanction lifted.
+    Approved,
+    /// The appeal was rejected.
+    Rejected,
+}
+
+impl AppealState {
+    /// Returns the string stored in the database for this state.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppealState::None => "none",
+            AppealState::Open => "open",
+            AppealState::Approved => "approved",
+            AppealState::Rejected => "rejected",
+        }
+    }
+}
+
+/// A record of a sanction applied to a user or to one of their offers.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModerationAction {
+    /// The action's ID.
+    pub id: Thing,
+    /// The ID of the user affected by the action.
+    pub user_id: String,
+    /// The ID of the moderator who performed the action.
+    pub moderator_id: String,
+    /// The kind of sanction applied.
+    pub kind: SanctionKind,
+    /// The ID of the sanctioned record (the offer or the user).
+    pub target_id: String,
+    /// The code of the reason template that was used.
+    pub reason_code: String,
+    /// The reason message as it was sent to the user.
+    pub reason_message: String,
+    /// The state of the appeal against this action.
+    #[serde(default)]
+    pub appeal_state: AppealState,
+    /// The timestamp when the action was performed.
+    pub created_at: String,
+}
+
+/// Defines the moderation fields on the `users` table and the moderation reason and action tables.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_user_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD role ON users TYPE string DEFAULT 'user';",
+        "role field on users",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD banned ON users TYPE bool DEFAULT false;",
+        "banned field on users",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE TABLE moderation_reasons SCHEMALESS;",
+        "moderation_reasons table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX moderation_reasons_code ON moderation_reasons FIELDS code UNIQUE",
+        "moderation_reasons_code index on moderation_reasons",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON moderation_reasons TYPE datetime;",
+        "created_at field on moderation_reasons",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE TABLE moderation_actions SCHEMALESS;",
+        "moderation_actions table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD appeal_state ON moderation_actions TYPE string DEFAULT 'none';",
+        "appeal_state field on moderation_actions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON moderation_actions TYPE datetime;",
+        "created_at field on moderation_actions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX moderation_actions_user_id ON moderation_actions FIELDS user_id",
+        "moderation_actions_user_id index on moderation_actions",
+    )
+    .await;
+}
+
+/// Defines the moderation fields on the `offers` table and the `reports` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_offer_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD hidden ON offers TYPE bool DEFAULT false;",
+        "hidden field on offers",
+    )
+    .await;
+    define(db, "DEFINE TABLE reports SCHEMALESS;", "reports table").await;
+    define(
+        db,
+        "DEFINE FIELD offer_id ON reports TYPE string;",
+        "offer_id field on reports",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD status ON reports TYPE string DEFAULT 'open';",
+        "status field on reports",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON reports TYPE datetime;",
+        "created_at field on reports",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX reports_status ON reports FIELDS status",
+        "reports_status index on reports",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX reports_offer_reporter ON reports FIELDS offer_id, reporter_id",
+        "reports_offer_reporter index on reports",
+    )
+    .await;
+}
+
+impl Database {
+    /// Retrieves a single user by their ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `User` struct or a `CustomError` if retrieval fails.
+    pub async fn get_user_by_id(&self, user_id: String) -> Result<Option<User>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM type::thing('users', $user_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let user: Option<User> = response.take(0)?;
+        Ok(user)
+    }
+
+    /// Bans or unbans a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to update.
+    /// * `banned` - Whether the user should be banned.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the user exists and was updated, or `false` if no user with the given ID exists.
+    pub async fn set_user_banned(
+        &self,
+        user_id: String,
+        banned: bool,
+    ) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Setting banned = {} for user with ID: {}", banned, user_id);
+        let sql = "UPDATE type::thing('users', $user_id) SET banned = $banned RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("banned".into(), Value::from(banned));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<User> = response.take(0)?;
+        if updated.is_some() {
+            self.blocked_users.invalidate();
+        }
+        Ok(updated.is_some())
+    }
+
+    /// Checks whether a user is banned or deleted.
+    ///
+    /// The IDs of banned and deleted users are loaded into the cache on the first check after a
+    /// user was banned, unbanned, deleted or restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to check.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the user is banned or deleted.
+    pub async fn is_user_blocked(&self, user_id: &str) -> Result<bool, CustomError> {
+        let blocked = match self.blocked_users.get() {
+            Some(blocked) => blocked,
+            None => {
+                let version = self.blocked_users.version();
+                self.use_user_namespace().await?; // Switch to user namespace
+                let sql = "SELECT VALUE record::id(id) FROM users WHERE banned = true OR deleted_at != NONE;";
+                let mut response: surrealdb::Response = self.db.query(sql).await?;
+                let blocked: Vec<String> = response.take(0)?;
+                tracing::info!("Loaded {} banned or deleted users", blocked.len());
+                self.blocked_users
+                    .fill(version, blocked.into_iter().collect::<HashSet<String>>())
+            }
+        };
+        Ok(blocked.contains(user_id))
+    }
+
+    /// Changes a user's role, but only if the user currently has the expected role.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to update.
+    /// * `expected` - The role the user must currently have.
+    /// * `role` - The new role.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the role was changed, or `false` if the user doesn't exist
+    /// or doesn't have the expected role.
+    pub async fn change_user_role(
+        &self,
+        user_id: String,
+        expected: Role,
+        role: Role,
+    ) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!(
+            "Changing role of user {} from {} to {}",
+            user_id,
+            expected.as_str(),
+            role.as_str()
+        );
+        let sql = "UPDATE type::thing('users', $user_id) SET role = $role WHERE role = $expected RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("expected".into(), Value::from(expected.as_str()));
+        vars.insert("role".into(), Value::from(role.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<User> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+
+    /// Hides or unhides an offer from the public listings.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer to update.
+    /// * `hidden` - Whether the offer should be hidden.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated `Offer`, or `None` if no offer with the given ID exists.
+    pub async fn set_offer_hidden(
+        &self,
+        offer_id: String,
+        hidden: bool,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Setting hidden = {} for offer with ID: {}",
+            hidden,
+            offer_id
+        );
+        let sql = "UPDATE type::thing('offers', $offer_id) SET hidden = $hidden RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("hidden".into(), Value::from(hidden));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Files a report against an offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the reported offer.
+    /// * `reporter_id` - The ID of the reporting user, or `"system"` for automatic checks.
+    /// * `reason` - The reason for the report.
+    /// * `details` - Additional details.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Report` or a `CustomError` if creation fails.
+    pub async fn create_report(
+        &self,
+        offer_id: String,
+        reporter_id: String,
+        reason: String,
+        details: String,
+    ) -> Result<Report, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Filing {} report for offer with ID: {}", reason, offer_id);
+        let sql = "CREATE reports SET offer_id = $offer_id, reporter_id = $reporter_id, reason = $reason, details = $details, status = 'open', created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("reporter_id".into(), Value::from(reporter_id.as_str()));
+        vars.insert("reason".into(), Value::from(reason.as_str()));
+        vars.insert("details".into(), Value::from(details.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<Report> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created report after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created report".to_string())
+        })
+    }
+
+    /// Files a user's report against an offer, unless the user already has an open report
+    /// against it.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the reported offer.
+    /// * `reporter_id` - The ID of the reporting user.
+    /// * `reason` - The reason for the report.
+    /// * `details` - The reporter's explanation (may be empty).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Report`, `None` if the user already has an open report
+    /// against the offer, or a `CustomError` if creation fails.
+    pub async fn create_user_report(
+        &self,
+        offer_id: String,
+        reporter_id: String,
+        reason: ReportReason,
+        details: String,
+    ) -> Result<Option<Report>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "User {} reports offer with ID: {} as {}",
+            reporter_id,
+            offer_id,
+            reason.as_str()
+        );
+        let sql = "IF array::len(SELECT id FROM reports WHERE offer_id = $offer_id AND reporter_id = $reporter_id AND status = 'open') = 0 {
+                CREATE reports SET offer_id = $offer_id, reporter_id = $reporter_id, reason = $reason, details = $details, status = 'open', created_at = time::now();
+            };";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("reporter_id".into(), Value::from(reporter_id.as_str()));
+        vars.insert("reason".into(), Value::from(reason.as_str()));
+        vars.insert("details".into(), Value::from(details.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<Report> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Changes the status of an open report.
+    ///
+    /// Reports that were already dismissed or resolved are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `report_id` - The ID of the report to update.
+    /// * `status` - The new status of the report.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if an open report was updated, or `false` otherwise.
+    pub async fn close_report(
+        &self,
+        report_id: String,
+        status: ReportStatus,
+    ) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Closing report with ID: {} as {}",
+            report_id,
+            status.as_str()
+        );
+        let sql = "UPDATE type::thing('reports', $report_id) SET status = $status WHERE status = 'open' RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("report_id".into(), Value::from(report_id.as_str()));
+        vars.insert("status".into(), Value::from(status.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Report> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+
+    /// Closes all open reports against an offer, e.g. after a moderator acted on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the reported offer.
+    /// * `status` - The new status of the reports.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of closed reports or a `CustomError` if the update fails.
+    pub async fn close_reports_for_offer(
+        &self,
+        offer_id: String,
+        status: ReportStatus,
+    ) -> Result<usize, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Closing open reports for offer with ID: {} as {}",
+            offer_id,
+            status.as_str()
+        );
+        let sql = "UPDATE reports SET status = $status WHERE offer_id = $offer_id AND status = 'open' RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("status".into(), Value::from(status.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Vec<Report> = response.take(0)?;
+        Ok(updated.len())
+    }
+
+    /// Retrieves a single report by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `report_id` - The ID of the report to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `Report` or a `CustomError` if retrieval fails.
+    pub async fn get_report(&self, report_id: String) -> Result<Option<Report>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM type::thing('reports', $report_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("report_id".into(), Value::from(report_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let report: Option<Report> = response.take(0)?;
+        Ok(report)
+    }
+
+    /// Retrieves all reports against an offer, including closed ones, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the reported offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Report` structs or a `CustomError` if retrieval fails.
+    pub async fn get_reports_for_offer(
+        &self,
+        offer_id: String,
+    ) -> Result<Vec<Report>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM reports WHERE offer_id = $offer_id ORDER BY created_at DESC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let reports: Vec<Report> = response.take(0)?;
+        Ok(reports)
+    }
+
+    /// Retrieves all open reports, oldest first.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Report` structs or a `CustomError` if retrieval fails.
+    pub async fn get_open_reports(&self) -> Result<Vec<Report>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM reports WHERE status = 'open' ORDER BY created_at ASC;";
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let reports: Vec<Report> = response.take(0)?;
+        Ok(reports)
+    }
+
+    /// Retrieves the moderation reason templates.
+    ///
+    /// # Arguments
+    ///
+    /// * `include_inactive` - Whether to include reasons that were deactivated.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `ModerationReason` structs or a `CustomError` if retrieval fails.
+    pub async fn get_moderation_reasons(
+        &self,
+        include_inactive: bool,
+    ) -> Result<Vec<ModerationReason>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = if include_inactive {
+            "SELECT * FROM moderation_reasons ORDER BY code ASC;"
+        } else {
+            "SELECT * FROM moderation_reasons WHERE active = true ORDER BY code ASC;"
+        };
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let reasons: Vec<ModerationReason> = response.take(0)?;
+        Ok(reasons)
+    }
+
+    /// Retrieves an active moderation reason template by its code.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the reason.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `ModerationReason` or a `CustomError` if retrieval fails.
+    pub async fn get_active_moderation_reason(
+        &self,
+        code: String,
+    ) -> Result<Option<ModerationReason>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM moderation_reasons WHERE code = $code AND active = true;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let reason: Option<ModerationReason> = response.take(0)?;
+        Ok(reason)
+    }
+
+    /// Creates a new moderation reason template.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The unique code of the reason.
+    /// * `title` - A short title shown to the affected user.
+    /// * `message` - The explanation sent to the affected user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `ModerationReason` or a `CustomError` if creation fails
+    /// (e.g. because the code is already taken).
+    pub async fn create_moderation_reason(
+        &self,
+        code: String,
+        title: String,
+        message: String,
+    ) -> Result<ModerationReason, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Creating moderation reason: {}", code);
+        let sql = "CREATE moderation_reasons SET code = $code, title = $title, message = $message, active = true, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code.as_str()));
+        vars.insert("title".into(), Value::from(title.as_str()));
+        vars.insert("message".into(), Value::from(message.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<ModerationReason> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created moderation reason after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created moderation reason".to_string())
+        })
+    }
+
+    /// Updates a moderation reason template.
+    ///
+    /// Actions that were already taken keep the message that was sent at the time.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code of the reason to update.
+    /// * `title` - The new title (optional).
+    /// * `message` - The new message (optional).
+    /// * `active` - Whether the reason can be picked (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated `ModerationReason`, or `None` if no reason with the given code exists.
+    pub async fn update_moderation_reason(
+        &self,
+        code: String,
+        title: Option<String>,
+        message: Option<String>,
+        active: Option<bool>,
+    ) -> Result<Option<ModerationReason>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Updating moderation reason: {}", code);
+        let mut updates = Vec::new();
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code.as_str()));
+
+        if let Some(t) = title {
+            updates.push("title = $title");
+            vars.insert("title".into(), Value::from(t));
+        }
+        if let Some(m) = message {
+            updates.push("message = $message");
+            vars.insert("message".into(), Value::from(m));
+        }
+        if let Some(a) = active {
+            updates.push("active = $active");
+            vars.insert("active".into(), Value::from(a));
+        }
+
+        if updates.is_empty() {
+            return Err(CustomError::DatabaseError(
+                "No fields to update".to_string(),
+            ));
+        }
+
+        let sql = format!(
+            "UPDATE moderation_reasons SET {} WHERE code = $code RETURN AFTER;",
+            updates.join(", ")
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<ModerationReason> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Records a sanction applied to a user or to one of their offers.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the affected user.
+    /// * `moderator_id` - The ID of the moderator who performed the action.
+    /// * `kind` - The kind of sanction applied.
+    /// * `target_id` - The ID of the sanctioned record.
+    /// * `reason` - The reason template that was used.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `ModerationAction` or a `CustomError` if creation fails.
+    pub async fn record_moderation_action(
+        &self,
+        user_id: String,
+        moderator_id: String,
+        kind: SanctionKind,
+        target_id: String,
+        reason: &ModerationReason,
+    ) -> Result<ModerationAction, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "CREATE moderation_actions SET user_id = $user_id, moderator_id = $moderator_id, kind = $kind, target_id = $target_id, reason_code = $reason_code, reason_message = $reason_message, appeal_state = 'none', created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("moderator_id".into(), Value::from(moderator_id.as_str()));
+        vars.insert("kind".into(), Value::from(kind.as_str()));
+        vars.insert("target_id".into(), Value::from(target_id.as_str()));
+        vars.insert("reason_code".into(), Value::from(reason.code.as_str()));
+        vars.insert(
+            "reason_message".into(),
+            Value::from(reason.message.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<ModerationAction> = response.take(0)?;
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created moderation action after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created moderation action".to_string())
+        })
+    }
+
+    /// Retrieves a single moderation action by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `action_id` - The ID of the moderation action.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `ModerationAction` or a `CustomError` if retrieval fails.
+    pub async fn get_moderation_action(
+        &self,
+        action_id: String,
+    ) -> Result<Option<ModerationAction>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM type::thing('moderation_actions', $action_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("action_id".into(), Value::from(action_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let action: Option<ModerationAction> = response.take(0)?;
+        Ok(action)
+    }
+
+    /// Retrieves all moderation actions affecting a user, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the affected user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `ModerationAction` structs or a `CustomError` if retrieval fails.
+    pub async fn get_moderation_actions_for_user(
+        &self,
+        user_id: String,
+    ) -> Result<Vec<ModerationAction>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql =
+            "SELECT * FROM moderation_actions WHERE user_id = $user_id ORDER BY created_at DESC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let actions: Vec<ModerationAction> = response.take(0)?;
+        Ok(actions)
+    }
+
+    /// Retrieves the latest ban of a user.
+    ///
+    /// Banned users can't log in to look up the action ID, so their appeals are filed against it.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the banned user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `ModerationAction` or a `CustomError` if retrieval fails.
+    pub async fn get_latest_ban_action(
+        &self,
+        user_id: String,
+    ) -> Result<Option<ModerationAction>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT * FROM moderation_actions WHERE user_id = $user_id AND kind = $kind ORDER BY created_at DESC LIMIT 1;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert(
+            "kind".into(),
+            Value::from(SanctionKind::UserBanned.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let action: Option<ModerationAction> = response.take(0)?;
+        Ok(action)
+    }
+
+    /// Moves the appeal state of a moderation action from `expected` to `state`.
+    ///
+    /// The update only happens if the action is currently in the `expected` state, which
+    /// guarantees that an action can't be appealed twice even under concurrent requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `action_id` - The ID of the moderation action.
+    /// * `expected` - The state the action must currently be in.
+    /// * `state` - The new appeal state.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the state was changed, or `false` if the action doesn't exist
+    /// or isn't in the expected state.
+    pub async fn transition_appeal_state(
+        &self,
+        action_id: String,
+        expected: AppealState,
+        state: AppealState,
+    ) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "UPDATE type::thing('moderation_actions', $action_id) SET appeal_state = $state WHERE appeal_state = $expected RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("action_id".into(), Value::from(action_id.as_str()));
+        vars.insert("expected".into(), Value::from(expected.as_str()));
+        vars.insert("state".into(), Value::from(state.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<ModerationAction> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+}
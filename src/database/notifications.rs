@@ -0,0 +1,357 @@
+//! src/database/notifications.rs
+//!
+//! This module handles the in-app notifications delivered to users, such as "offer sold",
+//! "new bid" or "message received". Users list them page by page, mark them as read and show the
+//! number of unread ones.
+//!
+//! Creating a notification wakes up the long-poll requests of its user through an in-process
+//! signal, which is enough because the embedded database is only ever opened by one process.
+
+use super::pagination::{PageInfo, Pagination};
+use super::{Count, Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::Instant;
+
+/// The number of notification signals buffered for slow waiters before they miss some.
+const NOTIFICATION_SIGNAL_CAPACITY: usize = 256;
+
+/// The maximum number of notifications returned by one poll.
+pub const MAX_POLLED_NOTIFICATIONS: u32 = 50;
+
+/// Signals waiting requests that a user received a notification.
+#[derive(Debug, Clone)]
+pub struct NotificationSignal(broadcast::Sender<String>);
+
+impl Default for NotificationSignal {
+    fn default() -> Self {
+        NotificationSignal(broadcast::channel(NOTIFICATION_SIGNAL_CAPACITY).0)
+    }
+}
+
+impl NotificationSignal {
+    /// Wakes up the requests waiting for notifications of the user.
+    pub fn notify(&self, user_id: &str) {
+        // Sending only fails if nobody is waiting
+        let _ = self.0.send(user_id.to_string());
+    }
+
+    /// Subscribes to the signals of all users.
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.0.subscribe()
+    }
+
+    /// Waits until the user receives a notification or the deadline passes.
+    ///
+    /// Also returns early if signals were missed, so the caller checks the database again.
+    ///
+    /// # Arguments
+    ///
+    /// * `receiver` - A receiver subscribed before the database was last checked.
+    /// * `user_id` - The ID of the user.
+    /// * `deadline` - When to stop waiting.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the caller should check for notifications again, `false` if the deadline passed.
+    pub async fn wait(
+        receiver: &mut broadcast::Receiver<String>,
+        user_id: &str,
+        deadline: Instant,
+    ) -> bool {
+        loop {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Ok(notified)) if notified == user_id => return true,
+                Ok(Ok(_)) => continue,
+                Ok(Err(RecvError::Lagged(_))) => return true,
+                Ok(Err(RecvError::Closed)) | Err(_) => return false,
+            }
+        }
+    }
+}
+
+/// Represents a notification addressed to a single user.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Notification {
+    /// The notification's ID.
+    pub id: Thing,
+    /// The ID of the user the notification is addressed to.
+    pub user_id: String,
+    /// A short machine-readable name of the event that caused the notification (e.g. `offer_hidden`).
+    pub kind: String,
+    /// The notification's title.
+    pub title: String,
+    /// The notification's body.
+    pub body: String,
+    /// Whether the user has read the notification.
+    #[serde(default)]
+    pub read: bool,
+    /// The timestamp when the notification was created.
+    pub created_at: String,
+}
+
+/// Defines the `notifications` table.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE notifications SCHEMALESS;",
+        "notifications table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD read ON notifications TYPE bool DEFAULT false;",
+        "read field on notifications",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON notifications TYPE datetime;",
+        "created_at field on notifications",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX notifications_user_id ON notifications FIELDS user_id",
+        "notifications_user_id index on notifications",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX notifications_user_read ON notifications FIELDS user_id, read",
+        "notifications_user_read index on notifications",
+    )
+    .await;
+}
+
+impl Database {
+    /// Creates a notification for a user, and emails it if the user enabled email notifications.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to notify.
+    /// * `kind` - A short machine-readable name of the event.
+    /// * `title` - The notification's title.
+    /// * `body` - The notification's body.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Notification` or a `CustomError` if creation fails.
+    pub async fn create_notification(
+        &self,
+        user_id: String,
+        kind: &str,
+        title: String,
+        body: String,
+    ) -> Result<Notification, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Creating '{}' notification for user {}", kind, user_id);
+        let sql = "CREATE notifications SET user_id = $user_id, kind = $kind, title = $title, body = $body, read = false, created_at = time::now();";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("kind".into(), Value::from(kind));
+        vars.insert("title".into(), Value::from(title.as_str()));
+        vars.insert("body".into(), Value::from(body.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<Notification> = response.take(0)?;
+
+        let created = created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve created notification after insertion.");
+            CustomError::DatabaseError("Failed to retrieve created notification".to_string())
+        })?;
+        self.notification_signal.notify(&user_id);
+        if let Err(e) = self.email_notification(&user_id, kind, &title, &body).await {
+            tracing::error!("Failed to email notification to user {}: {:?}", user_id, e);
+        }
+        Ok(created)
+    }
+
+    /// Retrieves a page of a user's notifications, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `unread_only` - Whether to skip notifications the user has read.
+    /// * `pagination` - The page to return.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the requested page of notifications and its pagination details, or
+    /// a `CustomError` if retrieval fails.
+    pub async fn get_notifications(
+        &self,
+        user_id: &str,
+        unread_only: bool,
+        pagination: &Pagination,
+    ) -> Result<(Vec<Notification>, PageInfo), CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let mut conditions = vec!["user_id = $user_id"];
+        if unread_only {
+            conditions.push("read = false");
+        }
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        pagination.bind(&mut vars);
+        let sql = format!(
+            "SELECT * FROM notifications WHERE {conditions} ORDER BY created_at DESC LIMIT $limit START $start; SELECT count() FROM notifications WHERE {conditions} GROUP ALL;",
+            conditions = conditions.join(" AND ")
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let notifications: Vec<Notification> = response.take(0)?;
+        let total: Option<Count> = response.take(1)?;
+        let total = total.map_or(0, |total| total.count);
+        Ok((notifications, PageInfo::new(pagination, total)))
+    }
+
+    /// Counts a user's unread notifications.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of unread notifications or a `CustomError` if counting
+    /// fails.
+    pub async fn count_unread_notifications(&self, user_id: &str) -> Result<u64, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "SELECT count() FROM notifications WHERE user_id = $user_id AND read = false GROUP ALL;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let count: Option<Count> = response.take(0)?;
+        Ok(count.map_or(0, |count| count.count))
+    }
+
+    /// Marks one of a user's notifications as read.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `notification_id` - The ID of the notification.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated `Notification`, `None` if the user has no such
+    /// notification, or a `CustomError` if the update fails.
+    pub async fn mark_notification_read(
+        &self,
+        user_id: &str,
+        notification_id: &str,
+    ) -> Result<Option<Notification>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "UPDATE type::thing('notifications', $notification_id) SET read = true WHERE user_id = $user_id RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert("notification_id".into(), Value::from(notification_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let notification: Option<Notification> = response.take(0)?;
+        Ok(notification)
+    }
+
+    /// Marks all of a user's notifications as read.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of notifications marked as read or a `CustomError` if
+    /// the update fails.
+    pub async fn mark_all_notifications_read(&self, user_id: &str) -> Result<u64, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Marking all notifications of user {} as read", user_id);
+        let sql = "RETURN array::len((UPDATE notifications SET read = true WHERE user_id = $user_id AND read = false RETURN id));";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<u64> = response.take(0)?;
+        Ok(updated.unwrap_or(0))
+    }
+
+    /// Retrieves a user's unread notifications, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `since` - Only return notifications created after this RFC 3339 timestamp, if given.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing at most `MAX_POLLED_NOTIFICATIONS` notifications or a `CustomError`
+    /// if retrieval fails.
+    pub async fn get_unread_notifications(
+        &self,
+        user_id: &str,
+        since: Option<&str>,
+    ) -> Result<Vec<Notification>, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let mut conditions = vec!["user_id = $user_id", "read = false"];
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert("limit".into(), Value::from(MAX_POLLED_NOTIFICATIONS));
+        if let Some(since) = since {
+            conditions.push("created_at > <datetime> $since");
+            vars.insert("since".into(), Value::from(since));
+        }
+        let sql = format!(
+            "SELECT * FROM notifications WHERE {} ORDER BY created_at ASC LIMIT $limit;",
+            conditions.join(" AND ")
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let notifications: Vec<Notification> = response.take(0)?;
+        Ok(notifications)
+    }
+
+    /// Waits until a user has unread notifications or the timeout elapses.
+    ///
+    /// Returns immediately if there already are unread notifications.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `since` - Only return notifications created after this RFC 3339 timestamp, if given.
+    /// * `timeout` - How long to wait for a notification.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the unread notifications, empty if none arrived in time, or a
+    /// `CustomError` if retrieval fails.
+    pub async fn wait_for_notifications(
+        &self,
+        user_id: &str,
+        since: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Vec<Notification>, CustomError> {
+        let deadline = Instant::now() + timeout;
+        // Subscribe before checking, so a notification created in between isn't missed
+        let mut receiver = self.notification_signal.subscribe();
+        loop {
+            let notifications = self.get_unread_notifications(user_id, since).await?;
+            if !notifications.is_empty()
+                || !NotificationSignal::wait(&mut receiver, user_id, deadline).await
+            {
+                return Ok(notifications);
+            }
+        }
+    }
+}
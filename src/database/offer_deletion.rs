@@ -0,0 +1,150 @@
+//! src/database/offer_deletion.rs
+//!
+//! This module handles the soft deletion of offers. Deleted offers disappear from the listings
+//! and can no longer be read or changed, but stay stored: sellers can restore offers they deleted
+//! themselves until the restore window has passed, after which the purge job removes them. Offers
+//! that were ordered are never purged, so the order history keeps its offers.
+
+use super::{Database, Offer, define};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The number of days a deleted offer can be restored before the purge job may remove it.
+pub const OFFER_RESTORE_DAYS: i64 = 30;
+
+/// Defines the deletion fields on `offers` and the index used to find offers due for purging.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD deleted_at ON offers TYPE option<datetime>;",
+        "deleted_at field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD purge_after ON offers TYPE option<datetime>;",
+        "purge_after field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_purge_after ON offers FIELDS purge_after",
+        "offers_purge_after index on offers",
+    )
+    .await;
+}
+
+impl Database {
+    /// Marks an offer as deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer to delete.
+    /// * `deleted_by` - The ID of the seller or moderator deleting the offer. Sellers can only
+    ///   restore offers they deleted themselves.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the deleted `Offer`, or `None` if no offer with the given ID exists
+    /// or it is already deleted.
+    pub async fn delete_offer(
+        &self,
+        offer_id: String,
+        deleted_by: String,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Deleting offer with ID: {}", offer_id);
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET deleted_at = time::now(), deleted_by = $deleted_by, purge_after = time::now() + {}d WHERE deleted_at = NONE RETURN AFTER;",
+            OFFER_RESTORE_DAYS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("deleted_by".into(), Value::from(deleted_by.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Option<Offer> = response.take(0)?;
+        Ok(deleted)
+    }
+
+    /// Retrieves the offers a seller deleted and can still restore, most recently deleted first.
+    ///
+    /// # Arguments
+    ///
+    /// * `seller_id` - The ID of the seller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the deleted offers or a `CustomError` if retrieval fails.
+    pub async fn get_deleted_offers(&self, seller_id: &str) -> Result<Vec<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM offers WHERE deleted_by = $seller_id AND deleted_at != NONE AND purge_after > time::now() ORDER BY deleted_at DESC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("seller_id".into(), Value::from(seller_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Vec<Offer> = response.take(0)?;
+        Ok(offers)
+    }
+
+    /// Restores a deleted offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer to restore.
+    /// * `seller_id` - The ID of the seller restoring the offer, who must have deleted it within
+    ///   the last `OFFER_RESTORE_DAYS` days, or `None` if a moderator restores it, e.g. after an
+    ///   appeal. Moderators can restore any offer that wasn't purged yet.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the restored `Offer`, or `None` if no restorable offer with the
+    /// given ID exists.
+    pub async fn restore_offer(
+        &self,
+        offer_id: String,
+        seller_id: Option<String>,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Restoring offer with ID: {}", offer_id);
+        let restorable = if seller_id.is_some() {
+            "deleted_by = $seller_id AND purge_after > time::now()"
+        } else {
+            "true"
+        };
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET deleted_at = NONE, deleted_by = NONE, purge_after = NONE WHERE deleted_at != NONE AND {} RETURN AFTER;",
+            restorable
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("seller_id".into(), Value::from(seller_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let restored: Option<Offer> = response.take(0)?;
+        Ok(restored)
+    }
+
+    /// Permanently removes the deleted offers whose restore window has passed.
+    ///
+    /// Offers that were ordered are kept, so their orders keep referring to them.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the removed offers or a `CustomError` if the deletion fails.
+    pub async fn purge_deleted_offers(&self) -> Result<Vec<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "DELETE offers WHERE deleted_at != NONE AND purge_after <= time::now() AND array::len((SELECT VALUE id FROM orders WHERE offer_id = record::id($parent.id) LIMIT 1)) = 0 RETURN BEFORE;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let purged: Vec<Offer> = response.take(0)?;
+        if !purged.is_empty() {
+            tracing::info!("Purged {} deleted offers", purged.len());
+        }
+        Ok(purged)
+    }
+}
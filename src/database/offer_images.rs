@@ -0,0 +1,223 @@
+//! src/database/offer_images.rs
+//!
+//! This module provides the images sellers upload for their offers. The image files are stored
+//! on disk by the server; the database only keeps where they are served from.
+
+use super::{Database, Offer};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+
+/// The maximum number of uploaded images per offer.
+pub const MAX_OFFER_IMAGES: usize = 10;
+
+/// The maximum size of an uploaded image in bytes (5 MiB).
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// The maximum width and height of image thumbnails in pixels.
+pub const THUMBNAIL_SIZE: u32 = 320;
+
+/// The maximum length of the alternative text of an image in characters.
+pub const MAX_ALT_TEXT_LENGTH: usize = 300;
+
+/// The image formats that can be uploaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    /// All formats that can be uploaded.
+    pub const ALL: [ImageFormat; 3] = [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::Webp];
+
+    /// Returns the format for a MIME type, if it is an accepted image format.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "image/jpeg" => Some(ImageFormat::Jpeg),
+            "image/png" => Some(ImageFormat::Png),
+            "image/webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    /// Returns the MIME type of the format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    /// Returns the file extension used when storing images of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    /// Checks whether the data starts with the signature of the format, so a file can't be
+    /// uploaded under a MIME type it doesn't have.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            ImageFormat::Jpeg => data.starts_with(&[0xFF, 0xD8, 0xFF]),
+            ImageFormat::Png => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+            ImageFormat::Webp => {
+                data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP"
+            }
+        }
+    }
+}
+
+/// An image uploaded for an offer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OfferImage {
+    /// The path the image is served from.
+    pub url: String,
+    /// The MIME type of the image.
+    pub content_type: String,
+    /// The size of the image in bytes.
+    pub size: u64,
+    /// The path of a downscaled WebP version for listing pages. It is generated in the
+    /// background after the upload, so it is missing until then or if generation failed.
+    #[serde(default)]
+    pub thumbnail_url: Option<String>,
+    /// A description of the image for screen readers, e.g. "Cartridge label, front".
+    #[serde(default)]
+    pub alt_text: Option<String>,
+}
+
+impl OfferImage {
+    /// Converts the image to the object stored in the database.
+    fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert("url".into(), Value::from(self.url.as_str()));
+        object.insert(
+            "content_type".into(),
+            Value::from(self.content_type.as_str()),
+        );
+        object.insert("size".into(), Value::from(self.size as i64));
+        if let Some(thumbnail_url) = &self.thumbnail_url {
+            object.insert("thumbnail_url".into(), Value::from(thumbnail_url.as_str()));
+        }
+        if let Some(alt_text) = &self.alt_text {
+            object.insert("alt_text".into(), Value::from(alt_text.as_str()));
+        }
+        Value::from(object)
+    }
+}
+
+impl Database {
+    /// Adds uploaded images to an offer.
+    ///
+    /// The images are only added if the offer stays within `MAX_OFFER_IMAGES` images, which is
+    /// checked in the same statement so concurrent uploads can't exceed it.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `images` - The images to add.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, `None` if the offer doesn't exist or would have
+    /// too many images, or a `CustomError` if the update fails.
+    pub async fn add_offer_images(
+        &self,
+        offer_id: String,
+        images: Vec<OfferImage>,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Adding {} images to offer {}", images.len(), offer_id);
+        let sql = "UPDATE type::thing('offers', $offer_id) SET images = array::concat(images ?? [], $images) WHERE array::len(images ?? []) + $count <= $max RETURN AFTER;";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("count".into(), Value::from(images.len() as i64));
+        vars.insert("max".into(), Value::from(MAX_OFFER_IMAGES as i64));
+        vars.insert(
+            "images".into(),
+            Value::from(
+                images
+                    .iter()
+                    .map(OfferImage::to_value)
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Sets the thumbnail of an uploaded image.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `url` - The path of the image.
+    /// * `thumbnail_url` - The path of the image's thumbnail.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the offer still has the image, or a `CustomError` if the
+    /// update fails.
+    pub async fn set_image_thumbnail(
+        &self,
+        offer_id: String,
+        url: String,
+        thumbnail_url: String,
+    ) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('offers', $offer_id) SET images = array::map(images, |$image| IF $image.url = $url THEN { url: $image.url, content_type: $image.content_type, size: $image.size, thumbnail_url: $thumbnail_url, alt_text: $image.alt_text } ELSE $image END) WHERE images.url CONTAINS $url RETURN AFTER;";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("url".into(), Value::from(url.as_str()));
+        vars.insert("thumbnail_url".into(), Value::from(thumbnail_url.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+
+    /// Sets or removes the alternative text of an uploaded image.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `url` - The path of the image.
+    /// * `alt_text` - The new alternative text, or `None` to remove it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, `None` if the offer doesn't have the image, or a
+    /// `CustomError` if the update fails.
+    pub async fn set_image_alt_text(
+        &self,
+        offer_id: String,
+        url: String,
+        alt_text: Option<String>,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('offers', $offer_id) SET images = array::map(images, |$image| IF $image.url = $url THEN { url: $image.url, content_type: $image.content_type, size: $image.size, thumbnail_url: $image.thumbnail_url, alt_text: $alt_text } ELSE $image END) WHERE images.url CONTAINS $url RETURN AFTER;";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("url".into(), Value::from(url.as_str()));
+        vars.insert(
+            "alt_text".into(),
+            alt_text.map_or(Value::None, |alt_text| Value::from(alt_text.as_str())),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        Ok(updated)
+    }
+}
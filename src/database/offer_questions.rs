@@ -0,0 +1,209 @@
+//! src/database/offer_questions.rs
+//!
+//! This module handles the public questions buyers ask about offers and the seller's answers.
+//! Questions are shown on the offer page with their answers. Moderators can hide questions and
+//! answers that break the rules; hidden questions are only shown to moderators.
+
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The maximum number of unanswered questions a user can have on one offer.
+pub const MAX_UNANSWERED_QUESTIONS: u64 = 3;
+
+/// Represents a public question about an offer and the seller's answer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OfferQuestion {
+    /// The question's ID.
+    pub id: Thing,
+    /// The ID of the offer the question is about.
+    pub offer_id: String,
+    /// The ID of the user who asked the question.
+    pub asker_id: String,
+    /// The question.
+    pub question: String,
+    /// The seller's answer, or `None` until the seller answers.
+    #[serde(default)]
+    pub answer: Option<String>,
+    /// The timestamp when the seller last answered.
+    #[serde(default)]
+    pub answered_at: Option<String>,
+    /// Whether a moderator hid the question from the offer page.
+    #[serde(default)]
+    pub hidden: bool,
+    /// The timestamp when the question was asked.
+    pub created_at: String,
+}
+
+/// Defines the `questions` table and the index questions are listed with.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE questions SCHEMALESS;", "questions table").await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON questions TYPE datetime;",
+        "created_at field on questions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD answered_at ON questions TYPE option<datetime>;",
+        "answered_at field on questions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX questions_offer_id ON questions FIELDS offer_id, created_at",
+        "questions_offer_id index on questions",
+    )
+    .await;
+}
+
+impl Database {
+    /// Asks a question about an offer, unless the user already has
+    /// `MAX_UNANSWERED_QUESTIONS` unanswered questions on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    /// * `asker_id` - The ID of the user asking.
+    /// * `question` - The question.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created question, `None` if the user has too many unanswered
+    /// questions on the offer, or a `CustomError` if creation fails.
+    pub async fn create_question(
+        &self,
+        offer_id: &str,
+        asker_id: &str,
+        question: &str,
+    ) -> Result<Option<OfferQuestion>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("User {} asks a question about offer {}", asker_id, offer_id);
+        let sql = "IF array::len(SELECT id FROM questions WHERE offer_id = $offer_id AND asker_id = $asker_id AND answer = NONE) < $max_unanswered {
+                CREATE questions SET offer_id = $offer_id, asker_id = $asker_id, question = $question, hidden = false, created_at = time::now();
+            };";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+        vars.insert("asker_id".into(), Value::from(asker_id));
+        vars.insert("question".into(), Value::from(question));
+        vars.insert(
+            "max_unanswered".into(),
+            Value::from(MAX_UNANSWERED_QUESTIONS),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<OfferQuestion> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Retrieves a question by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `question_id` - The ID of the question.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the question, or `None` if it doesn't exist.
+    pub async fn get_question(
+        &self,
+        question_id: &str,
+    ) -> Result<Option<OfferQuestion>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM type::thing('questions', $question_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("question_id".into(), Value::from(question_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let question: Option<OfferQuestion> = response.take(0)?;
+        Ok(question)
+    }
+
+    /// Retrieves the questions about an offer that moderators didn't hide, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the questions or a `CustomError` if retrieval fails.
+    pub async fn get_offer_questions(
+        &self,
+        offer_id: &str,
+    ) -> Result<Vec<OfferQuestion>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM questions WHERE offer_id = $offer_id AND hidden != true ORDER BY created_at;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let questions: Vec<OfferQuestion> = response.take(0)?;
+        Ok(questions)
+    }
+
+    /// Sets or replaces the seller's answer to a question that isn't hidden.
+    ///
+    /// # Arguments
+    ///
+    /// * `question_id` - The ID of the question.
+    /// * `answer` - The answer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the answered question, or `None` if it doesn't exist or is hidden.
+    pub async fn answer_question(
+        &self,
+        question_id: &str,
+        answer: &str,
+    ) -> Result<Option<OfferQuestion>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Answering question {}", question_id);
+        let sql = "UPDATE type::thing('questions', $question_id) SET answer = $answer, answered_at = time::now() WHERE hidden != true RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("question_id".into(), Value::from(question_id));
+        vars.insert("answer".into(), Value::from(answer));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let answered: Option<OfferQuestion> = response.take(0)?;
+        Ok(answered)
+    }
+
+    /// Hides a question and its answer from the offer page, or shows it again.
+    ///
+    /// # Arguments
+    ///
+    /// * `question_id` - The ID of the question.
+    /// * `hidden` - Whether the question is hidden.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated question, or `None` if it doesn't exist.
+    pub async fn set_question_hidden(
+        &self,
+        question_id: &str,
+        hidden: bool,
+    ) -> Result<Option<OfferQuestion>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Setting hidden = {} on question {}", hidden, question_id);
+        let sql =
+            "UPDATE type::thing('questions', $question_id) SET hidden = $hidden RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("question_id".into(), Value::from(question_id));
+        vars.insert("hidden".into(), Value::from(hidden));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<OfferQuestion> = response.take(0)?;
+        Ok(updated)
+    }
+}
@@ -0,0 +1,198 @@
+//! src/database/offer_slugs.rs
+//!
+//! This module handles the readable slugs of offers, such as `elden-ring-ps5-AB12`. The slug is
+//! generated from the game title and platform when the offer is created and never changes, so
+//! links stay valid when the title is edited. Offers can be looked up by their slug as well as by
+//! their ID.
+
+use super::platforms::Platform;
+use super::{Database, Offer, define};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+use uuid::Uuid;
+
+/// The maximum length of the part of a slug taken from the game title.
+const SLUG_TITLE_MAX_LEN: usize = 60;
+
+/// The lengths of the suffix taken from the offer ID, tried in order until the slug is unique.
+const SLUG_SUFFIX_LENS: [usize; 3] = [4, 8, 32];
+
+/// Defines the slug field on `offers` and the unique index slugs are looked up with.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD slug ON offers TYPE option<string>;",
+        "slug field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_slug ON offers FIELDS slug UNIQUE",
+        "offers_slug index on offers",
+    )
+    .await;
+}
+
+/// Returns the ASCII letter an accented Latin letter is written as in slugs, if it is one.
+fn fold_accent(c: char) -> Option<char> {
+    let folded = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        _ => return None,
+    };
+    Some(folded)
+}
+
+/// Turns text into lowercase words of ASCII letters and digits joined by hyphens.
+///
+/// # Arguments
+///
+/// * `text` - The text, such as a game title.
+/// * `max_len` - The maximum length of the slug. Longer slugs are cut at a word boundary.
+///
+/// # Returns
+///
+/// The slug, which is empty if the text has no letters or digits that can be written in ASCII.
+pub fn slugify(text: &str, max_len: usize) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match fold_accent(c).unwrap_or(c) {
+            c if c.is_ascii_alphanumeric() => slug.push(c),
+            _ if !slug.is_empty() && !slug.ends_with('-') => slug.push('-'),
+            _ => {}
+        }
+    }
+    if slug.len() > max_len {
+        let cut = slug[..=max_len].rfind('-').unwrap_or(max_len);
+        slug.truncate(cut);
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Builds the slug of an offer from its game title, its platform and a suffix of its ID.
+///
+/// # Arguments
+///
+/// * `game_title` - The title of the game.
+/// * `platform` - The platform of the game.
+/// * `offer_id` - The ID of the offer.
+/// * `suffix_len` - How many characters of the ID the suffix has.
+///
+/// # Returns
+///
+/// The slug, such as `elden-ring-ps5-AB12`.
+pub fn offer_slug(
+    game_title: &str,
+    platform: Platform,
+    offer_id: &str,
+    suffix_len: usize,
+) -> String {
+    let title = slugify(game_title, SLUG_TITLE_MAX_LEN);
+    let title = if title.is_empty() {
+        "offer".to_string()
+    } else {
+        title
+    };
+    let suffix: String = offer_id
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(suffix_len)
+        .collect();
+    format!(
+        "{}-{}-{}",
+        title,
+        slugify(platform.as_str(), SLUG_TITLE_MAX_LEN),
+        suffix.to_ascii_uppercase()
+    )
+}
+
+impl Database {
+    /// Finds a slug for a new offer that no other offer has, lengthening the suffix taken from the
+    /// offer ID on collisions.
+    ///
+    /// Must be called while the offer namespace is selected.
+    ///
+    /// # Arguments
+    ///
+    /// * `game_title` - The title of the game.
+    /// * `platform` - The platform of the game.
+    /// * `offer_id` - The ID of the new offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the slug or a `CustomError` if the lookup fails.
+    pub(super) async fn unique_offer_slug(
+        &self,
+        game_title: &str,
+        platform: Platform,
+        offer_id: &str,
+    ) -> Result<String, CustomError> {
+        let mut slug = String::new();
+        for suffix_len in SLUG_SUFFIX_LENS {
+            slug = offer_slug(game_title, platform, offer_id, suffix_len);
+            let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+            vars.insert("slug".into(), Value::from(slug.as_str()));
+            let mut response: surrealdb::Response = self
+                .db
+                .query("SELECT VALUE id FROM offers WHERE slug = $slug LIMIT 1;")
+                .bind(vars)
+                .await?;
+            let taken: Vec<Value> = response.take(0)?;
+            if taken.is_empty() {
+                break;
+            }
+        }
+        // The full ID is unique, so the last slug tried is too
+        Ok(slug)
+    }
+
+    /// Retrieves an offer by its slug, except deleted ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `slug` - The slug of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option<Offer>` or a `CustomError` if retrieval fails.
+    pub async fn get_offer_by_slug(&self, slug: &str) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Retrieving offer with slug: {}", slug);
+        let sql = "SELECT * FROM offers WHERE slug = $slug AND deleted_at = NONE LIMIT 1;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("slug".into(), Value::from(slug));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offer: Option<Offer> = response.take(0)?;
+        Ok(offer)
+    }
+
+    /// Retrieves an offer by its ID or, if no offer has the given value as its ID, by its slug.
+    ///
+    /// # Arguments
+    ///
+    /// * `id_or_slug` - The ID or slug of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option<Offer>` or a `CustomError` if retrieval fails.
+    pub async fn resolve_offer(&self, id_or_slug: &str) -> Result<Option<Offer>, CustomError> {
+        match self.get_offer_by_id(id_or_slug.to_string()).await? {
+            // Slugs are never UUIDs, so a missing UUID isn't looked up again
+            None if Uuid::parse_str(id_or_slug).is_err() => {
+                self.get_offer_by_slug(id_or_slug).await
+            }
+            offer => Ok(offer),
+        }
+    }
+}
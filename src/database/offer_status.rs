@@ -0,0 +1,223 @@
+//! src/database/offer_status.rs
+//!
+//! This module defines the lifecycle of an offer, from draft to sold, and which status changes
+//! are allowed. Only active offers appear in the public listings, and active offers expire after
+//! `OFFER_LIFETIME_DAYS` days unless the seller lists them again.
+
+use super::price_history::PriceEvent;
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The number of days an offer stays listed before it expires.
+pub const OFFER_LIFETIME_DAYS: i64 = 60;
+
+/// The number of days after which a renewed offer can be renewed again.
+pub const OFFER_RENEWAL_DAYS: i64 = 7;
+
+/// Defines the expiry and renewal dates of offers and the index used to find expired offers.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD expires_at ON offers TYPE option<datetime>;",
+        "expires_at field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD renewed_at ON offers TYPE option<datetime>;",
+        "renewed_at field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_status_expires_at ON offers FIELDS status, expires_at",
+        "offers_status_expires_at index on offers",
+    )
+    .await;
+}
+
+/// The status of an offer.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferStatus {
+    /// Being prepared by the seller and only visible to them.
+    Draft,
+    /// Listed and available. Offers created before statuses existed are active.
+    #[default]
+    Active,
+    /// Promised to a buyer, still visible but no longer listed.
+    Reserved,
+    /// Every copy is ordered, still visible but no longer listed. Listed again if an order is
+    /// cancelled or the seller adds copies.
+    SoldOut,
+    /// Sold. Final.
+    Sold,
+    /// No longer listed because it wasn't renewed in time. The seller can list it again.
+    Expired,
+    /// Withdrawn by the seller. Final.
+    Removed,
+}
+
+impl OfferStatus {
+    /// Returns the string stored in the database for this status.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OfferStatus::Draft => "draft",
+            OfferStatus::Active => "active",
+            OfferStatus::Reserved => "reserved",
+            OfferStatus::SoldOut => "sold_out",
+            OfferStatus::Sold => "sold",
+            OfferStatus::Expired => "expired",
+            OfferStatus::Removed => "removed",
+        }
+    }
+
+    /// Returns whether an offer in this status may change to the given status.
+    pub fn can_transition_to(&self, next: OfferStatus) -> bool {
+        use OfferStatus::*;
+        matches!(
+            (self, next),
+            (Draft, Active | Removed)
+                | (Active, Reserved | Sold | Expired | Removed)
+                | (Reserved, Active | Sold | Removed)
+                | (SoldOut, Sold | Removed)
+                | (Expired, Active | Removed)
+        )
+    }
+}
+
+impl Database {
+    /// Changes the status of an offer.
+    ///
+    /// The change is only applied if it is a valid transition from the offer's current status
+    /// and the status hasn't changed since the offer was read. Offers that become active expire
+    /// `OFFER_LIFETIME_DAYS` days later. Leaving the reserved status ends a buyer's reservation.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer, as read before the change.
+    /// * `next` - The new status.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, `None` if its status changed concurrently, or a
+    /// `CustomError::InvalidStatusTransition` if the transition isn't allowed.
+    pub async fn transition_offer_status(
+        &self,
+        offer: &Offer,
+        next: OfferStatus,
+    ) -> Result<Option<Offer>, CustomError> {
+        if !offer.status.can_transition_to(next) {
+            return Err(CustomError::InvalidStatusTransition(
+                offer.status.as_str().to_string(),
+                next.as_str().to_string(),
+            ));
+        }
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Changing status of offer {} from {} to {}",
+            offer.id,
+            offer.status.as_str(),
+            next.as_str()
+        );
+        let mut assignments = vec!["status = $next", "status_changed_at = time::now()"];
+        let expires_at = format!("expires_at = time::now() + {}d", OFFER_LIFETIME_DAYS);
+        if next == OfferStatus::Active {
+            assignments.push(&expires_at);
+        }
+        // A buyer's reservation ends with the reserved status
+        if offer.status == OfferStatus::Reserved {
+            assignments.push("reserved_by = NONE");
+            assignments.push("reserved_until = NONE");
+        }
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET {} WHERE (status ?? 'active') = $current RETURN AFTER;",
+            assignments.join(", ")
+        );
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "offer_id".into(),
+            Value::from(record_key(&offer.id).as_str()),
+        );
+        vars.insert("current".into(), Value::from(offer.status.as_str()));
+        vars.insert("next".into(), Value::from(next.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        if let Some(updated) = &updated {
+            match next {
+                // A reservation that fell through doesn't list the offer at a new price
+                OfferStatus::Active if offer.status != OfferStatus::Reserved => {
+                    self.record_price(updated, PriceEvent::Listed).await?
+                }
+                OfferStatus::Sold => self.record_price(updated, PriceEvent::Sold).await?,
+                _ => {}
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Renews an active offer, moving it to the top of the newest listings and starting a new
+    /// listing period of `OFFER_LIFETIME_DAYS` days.
+    ///
+    /// The renewal is only applied if the offer is still active and wasn't renewed in the last
+    /// `OFFER_RENEWAL_DAYS` days.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer to renew.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the renewed offer, or `None` if it can't be renewed now.
+    pub async fn renew_offer(&self, offer: &Offer) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let offer_id = record_key(&offer.id);
+        tracing::info!("Renewing offer {}", offer_id);
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET created_at = time::now(), renewed_at = time::now(), expires_at = time::now() + {}d WHERE (status ?? $active) = $active AND (renewed_at = NONE OR renewed_at <= time::now() - {}d) RETURN AFTER;",
+            OFFER_LIFETIME_DAYS, OFFER_RENEWAL_DAYS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let renewed: Option<Offer> = response.take(0)?;
+        Ok(renewed)
+    }
+
+    /// Marks every active offer whose listing period has ended as expired.
+    ///
+    /// Offers created before offers had an expiry date expire `OFFER_LIFETIME_DAYS` days after
+    /// they were created.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the offers that expired or a `CustomError` if the update fails.
+    pub async fn expire_stale_offers(&self) -> Result<Vec<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = format!(
+            "UPDATE offers SET status = $expired, status_changed_at = time::now() WHERE (status ?? $active) = $active AND deleted_at = NONE AND (expires_at ?? created_at + {}d) <= time::now() RETURN AFTER;",
+            OFFER_LIFETIME_DAYS
+        );
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+        vars.insert("expired".into(), Value::from(OfferStatus::Expired.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let expired: Vec<Offer> = response.take(0)?;
+        if !expired.is_empty() {
+            tracing::info!("Expired {} offers", expired.len());
+        }
+        Ok(expired)
+    }
+}
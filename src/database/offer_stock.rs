@@ -0,0 +1,161 @@
+//! src/database/offer_stock.rs
+//!
+//! This module handles the stock of offers. An offer can sell several identical copies: every
+//! purchase at the asking price takes one copy from its `quantity`, and the offer stays listed
+//! until the last copy is ordered, when it becomes sold out. Cancelling an order puts its copy back
+//! and lists a sold-out offer again. Once every order of a sold-out offer is completed, the offer
+//! is sold.
+
+use super::offer_status::{OFFER_LIFETIME_DAYS, OfferStatus};
+use super::orders::{Order, OrderState};
+use super::price_history::PriceEvent;
+use super::{Database, Offer, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+
+/// The maximum number of copies a single offer can sell.
+pub const MAX_OFFER_QUANTITY: u32 = 999;
+
+/// The number of copies of offers listed before offers had a quantity.
+pub(super) fn default_quantity() -> u32 {
+    1
+}
+
+impl Database {
+    /// Takes one copy of an active offer for an order, marking the offer sold out if it was the
+    /// last one.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer to take a copy of.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, or `None` if the offer is no longer active or has
+    /// no copy left.
+    pub async fn take_offer_copy(&self, offer: &Offer) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let offer_id = record_key(&offer.id);
+        tracing::info!("Taking a copy of offer {}", offer_id);
+        // The status is decided before the quantity is decremented
+        let sql = "UPDATE type::thing('offers', $offer_id) SET status_changed_at = IF (quantity ?? 1) <= 1 THEN time::now() ELSE status_changed_at END, status = IF (quantity ?? 1) <= 1 THEN $sold_out ELSE status END, quantity = (quantity ?? 1) - 1 WHERE (status ?? $active) = $active AND (quantity ?? 1) >= 1 RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+        vars.insert(
+            "sold_out".into(),
+            Value::from(OfferStatus::SoldOut.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Puts back the copy of a cancelled order, listing the offer again if it was sold out.
+    ///
+    /// Offers the seller has since marked sold or withdrawn are left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated offer, or `None` if the offer is neither active, sold out
+    /// nor expired.
+    pub async fn return_offer_copy(&self, offer_id: &str) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Returning a copy of offer {}", offer_id);
+        // The listing period and status change are decided before the status is changed
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET expires_at = IF status = $sold_out THEN time::now() + {}d ELSE expires_at END, status_changed_at = IF status = $sold_out THEN time::now() ELSE status_changed_at END, status = IF status = $sold_out THEN $active ELSE status END, quantity = (quantity ?? 0) + 1 WHERE (status ?? $active) IN [$active, $sold_out, $expired] RETURN AFTER;",
+            OFFER_LIFETIME_DAYS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+        vars.insert(
+            "sold_out".into(),
+            Value::from(OfferStatus::SoldOut.as_str()),
+        );
+        vars.insert("expired".into(), Value::from(OfferStatus::Expired.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Offer> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Marks a sold-out offer sold once none of its orders is still being processed.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the sold offer, or `None` if the offer isn't sold out or still has
+    /// orders in progress.
+    pub async fn complete_sold_out_offer(
+        &self,
+        offer_id: &str,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('offers', $offer_id) SET status = $sold, status_changed_at = time::now() WHERE status = $sold_out AND array::len((SELECT VALUE id FROM orders WHERE offer_id = $offer_id AND state IN ['pending', 'paid', 'shipped'])) = 0 RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+        vars.insert("sold".into(), Value::from(OfferStatus::Sold.as_str()));
+        vars.insert(
+            "sold_out".into(),
+            Value::from(OfferStatus::SoldOut.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let sold: Option<Offer> = response.take(0)?;
+        if let Some(sold) = &sold {
+            tracing::info!("Sold the last copy of offer {}", offer_id);
+            self.record_price(sold, PriceEvent::Sold).await?;
+        }
+        Ok(sold)
+    }
+
+    /// Moves the offer of an order along with it: a completed order marks a reserved offer sold,
+    /// and a sold-out offer once its last order is completed. A cancelled order lists a reserved
+    /// offer again, or puts its copy back into the offer's stock.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The updated order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the offer couldn't be updated.
+    pub async fn update_offer_for_order(&self, order: &Order) -> Result<(), CustomError> {
+        if !matches!(order.state, OrderState::Completed | OrderState::Cancelled) {
+            return Ok(());
+        }
+        let Some(offer) = self.get_offer_by_id(order.offer_id.clone()).await? else {
+            return Ok(());
+        };
+        match (order.state, offer.status) {
+            (OrderState::Completed, OfferStatus::Reserved) => {
+                self.transition_offer_status(&offer, OfferStatus::Sold)
+                    .await?;
+            }
+            (OrderState::Completed, OfferStatus::SoldOut) => {
+                self.complete_sold_out_offer(&order.offer_id).await?;
+            }
+            (OrderState::Cancelled, OfferStatus::Reserved) => {
+                self.transition_offer_status(&offer, OfferStatus::Active)
+                    .await?;
+            }
+            (OrderState::Cancelled, _) => {
+                self.return_offer_copy(&order.offer_id).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
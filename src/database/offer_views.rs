@@ -0,0 +1,115 @@
+//! src/database/offer_views.rs
+//!
+//! This module counts the views of offers, so sellers get feedback on how visible their
+//! listings are. A viewer is counted once per offer and day.
+//!
+//! Viewers are logged-in users or, for anonymous visitors, IP addresses. Only a keyed hash of
+//! the viewer and the day is stored, so neither IP addresses nor a viewer's visits on different
+//! days can be read from the table.
+
+use super::blind_index::blind_index;
+use super::{Count, Database, Offer, define};
+use crate::errors::custom_errors::CustomError;
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The number of days of daily view counts returned to the seller.
+pub const OFFER_VIEW_DAYS: i64 = 30;
+
+/// Defines the `offer_views` table and the unique index deduplicating views.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE offer_views SCHEMALESS;",
+        "offer_views table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offer_views_unique ON offer_views FIELDS offer, day, viewer UNIQUE",
+        "offer_views_unique index on offer_views",
+    )
+    .await;
+}
+
+/// The number of views of an offer on one day.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DailyViews {
+    /// The day, as `YYYY-MM-DD` in UTC.
+    pub day: String,
+    /// The number of viewers on the day.
+    pub views: u64,
+}
+
+/// The view statistics of an offer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OfferViews {
+    /// The number of views since the offer was created.
+    pub total: u64,
+    /// The views per day over the last `OFFER_VIEW_DAYS` days, oldest first. Days without views
+    /// are left out.
+    pub daily: Vec<DailyViews>,
+}
+
+impl Database {
+    /// Counts a view of an offer, unless the viewer already viewed it today.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The viewed offer.
+    /// * `viewer` - The user ID of a logged-in viewer or the IP address of an anonymous one.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the view couldn't be recorded.
+    pub async fn record_offer_view(&self, offer: &Offer, viewer: &str) -> Result<(), CustomError> {
+        let day = Utc::now().format("%Y-%m-%d").to_string();
+        let viewer = blind_index("offer_view", &format!("{}:{}", viewer, day))?;
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "IF array::len(SELECT id FROM offer_views WHERE offer = $offer AND day = $day AND viewer = $viewer) = 0 {
+                CREATE offer_views SET offer = $offer, day = $day, viewer = $viewer;
+            };";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer".into(), Value::from(offer.id.clone()));
+        vars.insert("day".into(), Value::from(day));
+        vars.insert("viewer".into(), Value::from(viewer));
+
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(())
+    }
+
+    /// Retrieves the view statistics of an offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the view statistics or a `CustomError` if retrieval fails.
+    pub async fn get_offer_views(&self, offer: &Offer) -> Result<OfferViews, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let since = (Utc::now() - Duration::days(OFFER_VIEW_DAYS - 1))
+            .format("%Y-%m-%d")
+            .to_string();
+        let sql = "SELECT count() FROM offer_views WHERE offer = $offer GROUP ALL; SELECT day, count() AS views FROM offer_views WHERE offer = $offer AND day >= $since GROUP BY day ORDER BY day;";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer".into(), Value::from(offer.id.clone()));
+        vars.insert("since".into(), Value::from(since));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let total: Option<Count> = response.take(0)?;
+        let daily: Vec<DailyViews> = response.take(1)?;
+        Ok(OfferViews {
+            total: total.map_or(0, |total| total.count),
+            daily,
+        })
+    }
+}
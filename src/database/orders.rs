@@ -0,0 +1,503 @@
+//! src/database/orders.rs
+//!
+//! This module handles orders: the purchase of an offer by a buyer. An order records the price
+//! the offer was bought at and moves from pending through paid and shipped to completed, or is
+//! cancelled on the way.
+//!
+//! Once paid, the buyer's payment is held in escrow. It is released to the seller when the buyer
+//! confirms receipt, or automatically `ESCROW_RELEASE_DAYS` days after shipping. Cancelling a paid
+//! order refunds it.
+//!
+//! Every order keeps a snapshot of the offer as it was listed when the order was placed, so later
+//! edits by the seller don't change the order history.
+
+use super::catalog::{AgeRating, BundleItem, Genre, Language, OfferAttributes, OfferPhoto, Region};
+use super::fees::FeeQuote;
+use super::preferences::Currency;
+use super::promo_codes::AppliedPromo;
+use super::taxes::{TaxBreakdown, bind_tax};
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The number of days after shipping after which the escrowed payment is released to the seller,
+/// unless the buyer confirmed receipt earlier.
+pub const ESCROW_RELEASE_DAYS: u32 = 14;
+
+/// The state of an order.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderState {
+    /// Placed by the buyer and waiting for payment.
+    Pending,
+    /// The seller confirmed the payment.
+    Paid,
+    /// The seller shipped the item.
+    Shipped,
+    /// The buyer confirmed receipt. Final.
+    Completed,
+    /// Cancelled before the item was shipped. Final.
+    Cancelled,
+}
+
+impl OrderState {
+    /// Returns the string stored in the database for this state.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderState::Pending => "pending",
+            OrderState::Paid => "paid",
+            OrderState::Shipped => "shipped",
+            OrderState::Completed => "completed",
+            OrderState::Cancelled => "cancelled",
+        }
+    }
+
+    /// Returns whether an order in this state is still being processed, i.e. neither completed
+    /// nor cancelled.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            OrderState::Pending | OrderState::Paid | OrderState::Shipped
+        )
+    }
+
+    /// Returns whether an order in this state may change to the given state.
+    pub fn can_transition_to(&self, next: OrderState) -> bool {
+        use OrderState::*;
+        matches!(
+            (self, next),
+            (Pending, Paid | Cancelled) | (Paid, Shipped | Cancelled) | (Shipped, Completed)
+        )
+    }
+}
+
+/// The state of the buyer's payment while the order is processed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EscrowState {
+    /// The payment is held until the buyer confirms receipt or the release date passes.
+    Held,
+    /// The payment was released to the seller. Final.
+    Released,
+    /// The order was cancelled and the payment returned to the buyer. Final.
+    Refunded,
+}
+
+/// The side of an order a user is on.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderRole {
+    /// The user bought the offer.
+    Buyer,
+    /// The user sold the offer.
+    Seller,
+}
+
+/// Selects which of a user's orders are listed.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct OrderFilter {
+    /// Only list orders the user is on this side of, or both sides if `None`.
+    pub role: Option<OrderRole>,
+    /// Only list orders in this state.
+    pub state: Option<OrderState>,
+    /// Only list orders placed on or after this day (UTC).
+    pub from: Option<NaiveDate>,
+    /// Only list orders placed on or before this day (UTC).
+    pub to: Option<NaiveDate>,
+}
+
+/// The offer as it was listed when it was ordered.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OfferSnapshot {
+    /// The title of the game.
+    pub game_title: String,
+    /// The platform of the game.
+    pub platform: String,
+    /// The condition of the item.
+    pub condition: String,
+    /// The description of the offer.
+    pub description: String,
+    /// The asking price of the offer.
+    pub price: f64,
+    /// The currency the price is in.
+    #[serde(default)]
+    pub currency: Currency,
+    /// The category and category-specific attributes.
+    #[serde(default)]
+    pub attributes: OfferAttributes,
+    /// The region coding.
+    #[serde(default)]
+    pub region: Option<Region>,
+    /// The language printed on the box.
+    #[serde(default)]
+    pub box_language: Option<Language>,
+    /// The language of the manual.
+    #[serde(default)]
+    pub manual_language: Option<Language>,
+    /// The age rating of the game.
+    #[serde(default)]
+    pub age_rating: Option<AgeRating>,
+    /// The genres of the game.
+    #[serde(default)]
+    pub genres: Vec<Genre>,
+    /// The photos of the item.
+    #[serde(default)]
+    pub photos: Vec<OfferPhoto>,
+    /// The games of a bundle, with their conditions. Empty for single items.
+    #[serde(default)]
+    pub bundle_items: Vec<BundleItem>,
+}
+
+/// Represents the purchase of an offer.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Order {
+    /// The order's ID.
+    pub id: Thing,
+    /// The ID of the bought offer.
+    pub offer_id: String,
+    /// The title of the game, as listed when the order was placed.
+    pub game_title: String,
+    /// The ID of the buyer.
+    pub buyer_id: String,
+    /// The ID of the seller.
+    pub seller_id: String,
+    /// The price the offer was bought at, after the discount of a promo code.
+    pub price: f64,
+    /// The promo code redeemed for the order, if any.
+    #[serde(default)]
+    pub promo: Option<AppliedPromo>,
+    /// The currency the price and the platform fee are in. Orders placed before offers had a
+    /// currency are in euros.
+    #[serde(default)]
+    pub currency: Currency,
+    /// The platform fee kept from the price, as quoted when the order was placed.
+    #[serde(default)]
+    pub platform_fee: f64,
+    /// The ID of the fee rule the platform fee was computed with, or `None` if no rule applied.
+    #[serde(default)]
+    pub fee_rule_id: Option<String>,
+    /// The VAT contained in the price, as computed at checkout. `None` if the buyer's country
+    /// wasn't known or the order was placed before taxes were computed.
+    #[serde(default)]
+    pub tax: Option<TaxBreakdown>,
+    /// The state of the order.
+    pub state: OrderState,
+    /// The state of the payment, or `None` until the order is paid.
+    #[serde(default)]
+    pub escrow: Option<EscrowState>,
+    /// The timestamp when the item was shipped.
+    #[serde(default)]
+    pub shipped_at: Option<String>,
+    /// The timestamp after which the payment is released automatically.
+    #[serde(default)]
+    pub release_after: Option<String>,
+    /// The timestamp when the payment was released to the seller.
+    #[serde(default)]
+    pub released_at: Option<String>,
+    /// The sequential number of the order's invoice, assigned when the invoice is first issued.
+    #[serde(default)]
+    pub invoice_number: Option<u64>,
+    /// The timestamp when the invoice was first issued.
+    #[serde(default)]
+    pub invoiced_at: Option<String>,
+    /// The offer as it was listed when the order was placed. Missing on orders placed before
+    /// snapshots were taken.
+    #[serde(default)]
+    pub offer_snapshot: Option<OfferSnapshot>,
+    /// The encrypted address the buyer wants the order shipped to. Never sent to clients; see
+    /// `Database::get_shipping_address`.
+    #[serde(default, skip_serializing)]
+    pub encrypted_shipping_address: Option<String>,
+    /// The timestamp when the order was placed.
+    pub created_at: String,
+    /// The timestamp of the last state change.
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Order {
+    /// Returns whether the given user is the order's buyer or seller.
+    pub fn involves(&self, user_id: &str) -> bool {
+        self.buyer_id == user_id || self.seller_id == user_id
+    }
+}
+
+/// Returns the assignments moving the escrow of an order along with its new state.
+fn escrow_assignments(next: OrderState) -> String {
+    match next {
+        OrderState::Pending => String::new(),
+        OrderState::Paid => ", escrow = 'held'".to_string(),
+        OrderState::Shipped => format!(
+            ", shipped_at = time::now(), release_after = time::now() + {}d",
+            ESCROW_RELEASE_DAYS
+        ),
+        OrderState::Completed => ", escrow = 'released', released_at = time::now()".to_string(),
+        OrderState::Cancelled => {
+            ", escrow = IF escrow = 'held' THEN 'refunded' ELSE escrow END".to_string()
+        }
+    }
+}
+
+/// Defines the `orders` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE orders SCHEMALESS;", "orders table").await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON orders TYPE datetime;",
+        "created_at field on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD updated_at ON orders TYPE option<datetime>;",
+        "updated_at field on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD shipped_at ON orders TYPE option<datetime>;",
+        "shipped_at field on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD release_after ON orders TYPE option<datetime>;",
+        "release_after field on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD released_at ON orders TYPE option<datetime>;",
+        "released_at field on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX orders_escrow_release_after ON orders FIELDS escrow, release_after",
+        "orders_escrow_release_after index on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX orders_offer_id ON orders FIELDS offer_id",
+        "orders_offer_id index on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX orders_buyer_id ON orders FIELDS buyer_id",
+        "orders_buyer_id index on orders",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX orders_seller_id ON orders FIELDS seller_id",
+        "orders_seller_id index on orders",
+    )
+    .await;
+}
+
+/// Formats midnight UTC of a day as an RFC 3339 timestamp.
+fn start_of_day(day: NaiveDate) -> String {
+    day.and_time(NaiveTime::MIN).and_utc().to_rfc3339()
+}
+
+impl Database {
+    /// Places an order for an offer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The bought offer.
+    /// * `buyer_id` - The ID of the buyer.
+    /// * `price` - The price the offer is bought at.
+    /// * `fee` - The platform fee of the sale.
+    /// * `tax` - The VAT of the sale, or `None` if the buyer's country isn't known.
+    /// * `promo` - The promo code redeemed for the order, if any. `price` is already discounted.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Order`, or `None` if the offer is reserved and already
+    /// has an order that wasn't cancelled. Active offers take one order per copy, see
+    /// `take_offer_copy`.
+    pub async fn create_order(
+        &self,
+        offer: &Offer,
+        buyer_id: String,
+        price: f64,
+        fee: &FeeQuote,
+        tax: Option<&TaxBreakdown>,
+        promo: Option<&AppliedPromo>,
+    ) -> Result<Option<Order>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let offer_id = record_key(&offer.id);
+        tracing::info!("Creating order of user {} for offer {}", buyer_id, offer_id);
+        let sql = "IF (SELECT VALUE status FROM ONLY type::thing('offers', $offer_id)) != 'reserved' OR (SELECT * FROM orders WHERE offer_id = $offer_id AND state != 'cancelled') = [] THEN (CREATE orders SET offer_id = $offer_id, game_title = $game_title, buyer_id = $buyer_id, seller_id = $seller_id, price = $price, currency = $currency, platform_fee = $platform_fee, fee_rule_id = $fee_rule_id, tax = $tax, promo = $promo, state = 'pending', offer_snapshot = (SELECT game_title, platform, condition, description, price, currency ?? 'EUR' AS currency, attributes, region, box_language, manual_language, age_rating, genres, photos, bundle_items ?? [] AS bundle_items FROM ONLY type::thing('offers', $offer_id)), created_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("game_title".into(), Value::from(offer.game_title.as_str()));
+        vars.insert("buyer_id".into(), Value::from(buyer_id.as_str()));
+        vars.insert(
+            "seller_id".into(),
+            Value::from(record_key(&offer.seller_id).as_str()),
+        );
+        vars.insert("price".into(), Value::from(price));
+        vars.insert("currency".into(), Value::from(offer.currency.as_str()));
+        vars.insert("platform_fee".into(), Value::from(fee.fee));
+        vars.insert("fee_rule_id".into(), Value::from(fee.rule_id.clone()));
+        bind_tax(&mut vars, tax);
+        vars.insert(
+            "promo".into(),
+            promo.map_or(Value::None, AppliedPromo::to_value),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<Order> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Retrieves a single order by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` - The ID of the order to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Option` of the `Order` or a `CustomError` if retrieval fails.
+    pub async fn get_order(&self, order_id: String) -> Result<Option<Order>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM type::thing('orders', $order_id);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("order_id".into(), Value::from(order_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let order: Option<Order> = response.take(0)?;
+        Ok(order)
+    }
+
+    /// Retrieves the orders a user bought or sold, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `filter` - Which of the user's orders to list.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Order` structs or a `CustomError` if retrieval fails.
+    pub async fn get_orders_for_user(
+        &self,
+        user_id: &str,
+        filter: &OrderFilter,
+    ) -> Result<Vec<Order>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        let mut conditions = vec![
+            match filter.role {
+                Some(OrderRole::Buyer) => "buyer_id = $user_id",
+                Some(OrderRole::Seller) => "seller_id = $user_id",
+                None => "(buyer_id = $user_id OR seller_id = $user_id)",
+            }
+            .to_string(),
+        ];
+        if let Some(state) = filter.state {
+            conditions.push("state = $state".to_string());
+            vars.insert("state".into(), Value::from(state.as_str()));
+        }
+        if let Some(from) = filter.from {
+            conditions.push("created_at >= <datetime> $from".to_string());
+            vars.insert("from".into(), Value::from(start_of_day(from)));
+        }
+        if let Some(to) = filter.to.and_then(|to| to.succ_opt()) {
+            conditions.push("created_at < <datetime> $to".to_string());
+            vars.insert("to".into(), Value::from(start_of_day(to)));
+        }
+        let sql = format!(
+            "SELECT * FROM orders WHERE {} ORDER BY created_at DESC;",
+            conditions.join(" AND ")
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let orders: Vec<Order> = response.take(0)?;
+        Ok(orders)
+    }
+
+    /// Changes the state of an order.
+    ///
+    /// The change is only applied if it is a valid transition and the state hasn't changed since
+    /// the order was read. Paying an order puts the payment in escrow, shipping it starts the
+    /// release period, completing it releases the payment and cancelling it refunds the payment.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order, as read before the change.
+    /// * `next` - The new state.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the updated order, `None` if its state changed concurrently, or a
+    /// `CustomError::InvalidStatusTransition` if the transition isn't allowed.
+    pub async fn transition_order_state(
+        &self,
+        order: &Order,
+        next: OrderState,
+    ) -> Result<Option<Order>, CustomError> {
+        if !order.state.can_transition_to(next) {
+            return Err(CustomError::InvalidStatusTransition(
+                order.state.as_str().to_string(),
+                next.as_str().to_string(),
+            ));
+        }
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Changing state of order {} from {} to {}",
+            order.id,
+            order.state.as_str(),
+            next.as_str()
+        );
+        let sql = format!(
+            "UPDATE type::thing('orders', $order_id) SET state = $next, updated_at = time::now(){} WHERE state = $current RETURN AFTER;",
+            escrow_assignments(next)
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "order_id".into(),
+            Value::from(record_key(&order.id).as_str()),
+        );
+        vars.insert("current".into(), Value::from(order.state.as_str()));
+        vars.insert("next".into(), Value::from(next.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<Order> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Completes every shipped order whose release date has passed, releasing the escrowed
+    /// payment to the seller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the completed orders or a `CustomError` if the update fails.
+    pub async fn release_due_escrows(&self) -> Result<Vec<Order>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE orders SET state = 'completed', escrow = 'released', released_at = time::now(), updated_at = time::now() WHERE state = 'shipped' AND escrow = 'held' AND release_after <= time::now() RETURN AFTER;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let released: Vec<Order> = response.take(0)?;
+        if !released.is_empty() {
+            tracing::info!("Released the escrow of {} orders", released.len());
+        }
+        Ok(released)
+    }
+}
@@ -0,0 +1,83 @@
+//! src/database/pagination.rs
+//!
+//! This module provides the page-based pagination used by list endpoints.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+
+/// The number of items per page if the client does not ask for a page size.
+pub const DEFAULT_PER_PAGE: u32 = 20;
+
+/// The largest page size a client can ask for.
+pub const MAX_PER_PAGE: u32 = 100;
+
+/// The `page` and `per_page` query parameters of a list endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Pagination {
+    /// The 1-based number of the page to return (defaults to 1).
+    pub page: Option<u32>,
+    /// The number of items per page (defaults to `DEFAULT_PER_PAGE`, at most `MAX_PER_PAGE`).
+    pub per_page: Option<u32>,
+}
+
+impl Pagination {
+    /// Returns the requested page number, at least 1.
+    pub fn page(&self) -> u32 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    /// Returns the requested page size, clamped to `1..=MAX_PER_PAGE`.
+    pub fn per_page(&self) -> u32 {
+        self.per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE)
+    }
+
+    /// Returns the number of items to skip (the `START` of the query).
+    pub fn start(&self) -> u64 {
+        u64::from(self.page() - 1) * u64::from(self.per_page())
+    }
+
+    /// Binds the `$limit` and `$start` variables used by `LIMIT $limit START $start`.
+    pub(super) fn bind(&self, vars: &mut BTreeMap<String, Value>) {
+        vars.insert("limit".into(), Value::from(self.per_page()));
+        vars.insert("start".into(), Value::from(self.start()));
+    }
+}
+
+/// The pagination details returned alongside a page of items.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PageInfo {
+    /// The number of the returned page.
+    pub page: u32,
+    /// The number of items per page.
+    pub per_page: u32,
+    /// The total number of items across all pages.
+    pub total: u64,
+    /// The total number of pages.
+    pub total_pages: u64,
+    /// The number of the next page, or `None` if this is the last page.
+    pub next_page: Option<u32>,
+}
+
+impl PageInfo {
+    /// Computes the pagination details of a page.
+    ///
+    /// # Arguments
+    ///
+    /// * `pagination` - The requested page.
+    /// * `total` - The total number of items across all pages.
+    pub fn new(pagination: &Pagination, total: u64) -> Self {
+        let per_page = pagination.per_page();
+        let page = pagination.page();
+        let total_pages = total.div_ceil(u64::from(per_page));
+        PageInfo {
+            page,
+            per_page,
+            total,
+            total_pages,
+            next_page: (u64::from(page) < total_pages).then(|| page + 1),
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! src/database/photo_matching.rs
+//!
+//! This module flags listings reusing photos from other accounts' listings, a common sign of
+//! scams using stolen photos, by filing reports for the moderation queue.
+//!
+//! Uploaded images are compared by a perceptual hash (dHash) of their content, so a copy that was
+//! re-encoded, resized or slightly edited still matches. The 64-bit hashes are split into
+//! `PERCEPTUAL_HASH_BANDS` bands: two hashes within `PERCEPTUAL_HASH_MAX_DISTANCE` bits of each
+//! other share at least one band, so candidates are found by band and then compared exactly.
+//! Photos linked by URL are never fetched, so they can only be matched by their URL.
+
+use super::{Database, Offer, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use image::DynamicImage;
+use image::imageops::FilterType;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use surrealdb::sql::{Thing, Value};
+
+/// The maximum number of differing bits for two perceptual hashes to count as the same photo.
+pub const PERCEPTUAL_HASH_MAX_DISTANCE: u32 = 3;
+
+/// The number of bands perceptual hashes are split into to find candidates. Must be greater
+/// than `PERCEPTUAL_HASH_MAX_DISTANCE`.
+const PERCEPTUAL_HASH_BANDS: u32 = 4;
+
+/// A stored image file with a perceptual hash, as read to compare hashes.
+#[derive(Debug, Deserialize)]
+struct HashedBlob {
+    id: Thing,
+    perceptual_hash: String,
+}
+
+/// Computes the difference hash (dHash) of an image.
+///
+/// The image is reduced to 9x8 grayscale pixels, and every bit of the hash tells whether a pixel
+/// is darker than its right neighbour. The hash only depends on the gradients of the picture, so
+/// it survives re-encoding, resizing and small changes of brightness or colour.
+///
+/// # Arguments
+///
+/// * `image` - The decoded image.
+///
+/// # Returns
+///
+/// The 64-bit hash.
+pub fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let pixels = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = pixels.get_pixel(x, y).0[0];
+            let right = pixels.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left < right);
+        }
+    }
+    hash
+}
+
+/// Returns the number of bits two perceptual hashes differ in.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Formats a perceptual hash as it is stored.
+pub fn format_perceptual_hash(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+/// Parses a stored perceptual hash.
+pub fn parse_perceptual_hash(hash: &str) -> Option<u64> {
+    u64::from_str_radix(hash, 16).ok()
+}
+
+/// Returns the bands of a perceptual hash, each prefixed with its position, e.g. `2:9f3c`.
+fn perceptual_hash_bands(hash: u64) -> Vec<Value> {
+    let bits = u64::BITS / PERCEPTUAL_HASH_BANDS;
+    (0..PERCEPTUAL_HASH_BANDS)
+        .map(|band| {
+            let value = (hash >> (band * bits)) & ((1 << bits) - 1);
+            Value::from(format!("{}:{:x}", band, value))
+        })
+        .collect()
+}
+
+impl Database {
+    /// Stores the perceptual hash of a stored image file, so later uploads can be compared to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_hash` - The content hash of the file.
+    /// * `perceptual_hash` - The perceptual hash of the image.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the file still exists, or a `CustomError` if the update
+    /// fails.
+    pub async fn set_image_blob_perceptual_hash(
+        &self,
+        content_hash: &str,
+        perceptual_hash: u64,
+    ) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('image_blobs', $hash) SET perceptual_hash = $perceptual_hash, perceptual_hash_bands = $bands RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("hash".into(), Value::from(content_hash));
+        vars.insert(
+            "perceptual_hash".into(),
+            Value::from(format_perceptual_hash(perceptual_hash)),
+        );
+        vars.insert(
+            "bands".into(),
+            Value::from(perceptual_hash_bands(perceptual_hash)),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<HashedBlob> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+
+    /// Retrieves the stored image files that look like an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `perceptual_hash` - The perceptual hash of the image.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the content hashes of the files whose perceptual hash differs in at
+    /// most `PERCEPTUAL_HASH_MAX_DISTANCE` bits, or a `CustomError` if the lookup fails.
+    pub async fn find_similar_image_blobs(
+        &self,
+        perceptual_hash: u64,
+    ) -> Result<Vec<String>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT id, perceptual_hash FROM image_blobs WHERE perceptual_hash_bands CONTAINSANY $bands;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "bands".into(),
+            Value::from(perceptual_hash_bands(perceptual_hash)),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let candidates: Vec<HashedBlob> = response.take(0)?;
+        Ok(candidates
+            .into_iter()
+            .filter(|blob| {
+                parse_perceptual_hash(&blob.perceptual_hash).is_some_and(|hash| {
+                    hamming_distance(hash, perceptual_hash) <= PERCEPTUAL_HASH_MAX_DISTANCE
+                })
+            })
+            .map(|blob| record_key(&blob.id))
+            .collect())
+    }
+
+    /// Retrieves the offers of other sellers showing any of the given photos or images.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer whose photos are checked.
+    /// * `urls` - The URLs of the linked photos and paths of the uploaded images to look for.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the IDs of the matching offers or a `CustomError` if the lookup fails.
+    pub async fn find_offers_showing(
+        &self,
+        offer: &Offer,
+        urls: Vec<String>,
+    ) -> Result<Vec<Thing>, CustomError> {
+        if urls.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT VALUE id FROM offers WHERE seller_id != $seller_id AND id != $offer_id AND deleted_at = NONE AND (photos.url CONTAINSANY $urls OR images.url CONTAINSANY $urls);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("seller_id".into(), Value::from(offer.seller_id.clone()));
+        vars.insert("offer_id".into(), Value::from(offer.id.clone()));
+        vars.insert(
+            "urls".into(),
+            Value::from(urls.into_iter().map(Value::from).collect::<Vec<Value>>()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let matches: Vec<Thing> = response.take(0)?;
+        Ok(matches)
+    }
+
+    /// Files a report for moderators if an offer's linked photos appear in other sellers' offers.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer to check.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the check or the report failed.
+    pub(super) async fn report_reused_photos(&self, offer: &Offer) -> Result<(), CustomError> {
+        let urls = offer.photos.iter().map(|photo| photo.url.clone()).collect();
+        let matches = self.find_offers_showing(offer, urls).await?;
+        self.report_photo_reuse(offer, matches).await
+    }
+
+    /// Files a report for moderators about an offer showing the same photos as other sellers'
+    /// offers.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer reusing the photos.
+    /// * `matches` - The IDs of the other offers showing them. Nothing is reported without any.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the report failed.
+    pub async fn report_photo_reuse(
+        &self,
+        offer: &Offer,
+        matches: Vec<Thing>,
+    ) -> Result<(), CustomError> {
+        if matches.is_empty() {
+            return Ok(());
+        }
+
+        let matches: Vec<String> = matches.iter().map(record_key).collect();
+        tracing::warn!(
+            "Offer {} reuses photos of offers {}",
+            record_key(&offer.id),
+            matches.join(", ")
+        );
+        self.create_report(
+            record_key(&offer.id),
+            "system".to_string(),
+            "reused_photo".to_string(),
+            format!(
+                "Photos also appear in listings of other sellers: {}",
+                matches.join(", ")
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+}
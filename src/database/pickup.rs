@@ -0,0 +1,121 @@
+//! src/database/pickup.rs
+//!
+//! This module handles offers available for local pickup. Sellers give a coarse location, such as
+//! the centre of their postcode, which is rounded to about a kilometre before it is stored, so no
+//! offer reveals where exactly its seller lives. Buyers filter offers by their distance to a point.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+use validator::ValidationError;
+
+/// The number of decimal places coordinates are rounded to (about 1 km).
+const COORDINATE_DECIMALS: i32 = 2;
+/// The kilometres per degree of latitude.
+const KM_PER_DEGREE: f64 = 111.32;
+/// The search radius used when a buyer gives none, in kilometres.
+pub const DEFAULT_RADIUS_KM: f64 = 25.0;
+/// The largest search radius, in kilometres.
+pub const MAX_RADIUS_KM: f64 = 200.0;
+
+/// Where an offer can be picked up.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PickupLocation {
+    /// The latitude, rounded to `COORDINATE_DECIMALS` decimal places.
+    pub latitude: f64,
+    /// The longitude, rounded to `COORDINATE_DECIMALS` decimal places.
+    pub longitude: f64,
+    /// The area shown to buyers, such as "10115 Berlin".
+    pub area: String,
+}
+
+/// Rounds a coordinate to `COORDINATE_DECIMALS` decimal places.
+fn coarse(coordinate: f64) -> f64 {
+    let factor = 10_f64.powi(COORDINATE_DECIMALS);
+    (coordinate * factor).round() / factor
+}
+
+impl PickupLocation {
+    /// Converts the location to the object stored in the database, with rounded coordinates.
+    pub(super) fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert("latitude".into(), Value::from(coarse(self.latitude)));
+        object.insert("longitude".into(), Value::from(coarse(self.longitude)));
+        object.insert("area".into(), Value::from(self.area.trim()));
+        Value::from(object)
+    }
+}
+
+/// Ensures a pickup location has valid coordinates and names its area.
+pub fn validate_pickup(location: &PickupLocation) -> Result<(), ValidationError> {
+    if !(-90.0..=90.0).contains(&location.latitude)
+        || !(-180.0..=180.0).contains(&location.longitude)
+    {
+        Err(ValidationError::new("pickup")
+            .with_message("Latitude must be within ±90 and longitude within ±180".into()))
+    } else if !(2..=100).contains(&location.area.trim().chars().count()) {
+        Err(ValidationError::new("pickup")
+            .with_message("Area must be 2 to 100 characters long".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses a `<latitude>,<longitude>` point.
+///
+/// # Arguments
+///
+/// * `near` - The point, e.g. `52.52,13.40`.
+///
+/// # Returns
+///
+/// The latitude and longitude, or `None` if the point is malformed or out of range.
+pub fn parse_near(near: &str) -> Option<(f64, f64)> {
+    let (latitude, longitude) = near.split_once(',')?;
+    let latitude: f64 = latitude.trim().parse().ok()?;
+    let longitude: f64 = longitude.trim().parse().ok()?;
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+/// Ensures a `near` filter is a valid point.
+pub fn validate_near(near: &str) -> Result<(), ValidationError> {
+    match parse_near(near) {
+        Some(_) => Ok(()),
+        None => {
+            Err(ValidationError::new("near")
+                .with_message("near must be <latitude>,<longitude>".into()))
+        }
+    }
+}
+
+/// Adds a condition keeping the offers that can be picked up within a radius of a point.
+///
+/// The distance is approximated on a plane, which is accurate to well within the rounding of the
+/// locations for radii up to `MAX_RADIUS_KM`.
+///
+/// # Arguments
+///
+/// * `near` - The latitude and longitude of the point.
+/// * `radius_km` - The radius in kilometres.
+/// * `conditions` - The conditions of the `WHERE` clause.
+/// * `vars` - The variables bound to the query.
+pub(super) fn push_distance_condition(
+    near: (f64, f64),
+    radius_km: f64,
+    conditions: &mut Vec<String>,
+    vars: &mut BTreeMap<String, Value>,
+) {
+    let (latitude, longitude) = near;
+    // A degree of longitude shrinks towards the poles
+    let longitude_scale = latitude.to_radians().cos();
+    let radius = radius_km / KM_PER_DEGREE;
+    conditions.push(
+        "(pickup != NONE AND math::pow((pickup.longitude - $near_longitude) * $longitude_scale, 2) + math::pow(pickup.latitude - $near_latitude, 2) <= $radius_squared)"
+            .to_string(),
+    );
+    vars.insert("near_latitude".into(), Value::from(latitude));
+    vars.insert("near_longitude".into(), Value::from(longitude));
+    vars.insert("longitude_scale".into(), Value::from(longitude_scale));
+    vars.insert("radius_squared".into(), Value::from(radius * radius));
+}
@@ -0,0 +1,411 @@
+//! src/database/platforms.rs
+//!
+//! This module defines the canonical list of platforms offers are listed for. Platforms used to be
+//! free text, which split the same console across spellings ("PS5", "Playstation 5"); common
+//! spellings are still accepted and mapped to their platform, and values stored before the list
+//! existed are normalized on startup.
+
+use super::catalog::{BundleItem, OfferMetadata};
+use super::conditions;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::exit;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// A platform games and hardware are listed for.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case", try_from = "String")]
+pub enum Platform {
+    /// PlayStation 5.
+    Ps5,
+    /// PlayStation 4.
+    Ps4,
+    /// PlayStation 3.
+    Ps3,
+    /// PlayStation 2.
+    Ps2,
+    /// The original PlayStation.
+    Ps1,
+    /// PlayStation Portable.
+    Psp,
+    /// PlayStation Vita.
+    PsVita,
+    /// Xbox Series X and Series S.
+    XboxSeries,
+    /// Xbox One.
+    XboxOne,
+    /// Xbox 360.
+    Xbox360,
+    /// The original Xbox.
+    Xbox,
+    /// Nintendo Switch.
+    Switch,
+    /// Wii U.
+    WiiU,
+    /// Wii.
+    Wii,
+    /// GameCube.
+    Gamecube,
+    /// Nintendo 64.
+    N64,
+    /// Super Nintendo.
+    Snes,
+    /// Nintendo Entertainment System.
+    Nes,
+    /// Nintendo 3DS.
+    Nintendo3ds,
+    /// Nintendo DS.
+    NintendoDs,
+    /// Game Boy Advance.
+    GameBoyAdvance,
+    /// Game Boy and Game Boy Color.
+    GameBoy,
+    /// Sega Mega Drive / Genesis.
+    MegaDrive,
+    /// Sega Dreamcast.
+    Dreamcast,
+    /// Windows, macOS and Linux computers.
+    Pc,
+    /// Any other platform.
+    Other,
+}
+
+impl Platform {
+    /// Every platform, in the order they are presented to sellers and buyers.
+    pub const ALL: [Platform; 26] = [
+        Platform::Ps5,
+        Platform::Ps4,
+        Platform::Ps3,
+        Platform::Ps2,
+        Platform::Ps1,
+        Platform::Psp,
+        Platform::PsVita,
+        Platform::XboxSeries,
+        Platform::XboxOne,
+        Platform::Xbox360,
+        Platform::Xbox,
+        Platform::Switch,
+        Platform::WiiU,
+        Platform::Wii,
+        Platform::Gamecube,
+        Platform::N64,
+        Platform::Snes,
+        Platform::Nes,
+        Platform::Nintendo3ds,
+        Platform::NintendoDs,
+        Platform::GameBoyAdvance,
+        Platform::GameBoy,
+        Platform::MegaDrive,
+        Platform::Dreamcast,
+        Platform::Pc,
+        Platform::Other,
+    ];
+
+    /// Returns the string stored in the database for this platform.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Ps5 => "ps5",
+            Platform::Ps4 => "ps4",
+            Platform::Ps3 => "ps3",
+            Platform::Ps2 => "ps2",
+            Platform::Ps1 => "ps1",
+            Platform::Psp => "psp",
+            Platform::PsVita => "ps_vita",
+            Platform::XboxSeries => "xbox_series",
+            Platform::XboxOne => "xbox_one",
+            Platform::Xbox360 => "xbox360",
+            Platform::Xbox => "xbox",
+            Platform::Switch => "switch",
+            Platform::WiiU => "wii_u",
+            Platform::Wii => "wii",
+            Platform::Gamecube => "gamecube",
+            Platform::N64 => "n64",
+            Platform::Snes => "snes",
+            Platform::Nes => "nes",
+            Platform::Nintendo3ds => "nintendo3ds",
+            Platform::NintendoDs => "nintendo_ds",
+            Platform::GameBoyAdvance => "game_boy_advance",
+            Platform::GameBoy => "game_boy",
+            Platform::MegaDrive => "mega_drive",
+            Platform::Dreamcast => "dreamcast",
+            Platform::Pc => "pc",
+            Platform::Other => "other",
+        }
+    }
+
+    /// Returns the human-readable name of this platform.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Platform::Ps5 => "PlayStation 5",
+            Platform::Ps4 => "PlayStation 4",
+            Platform::Ps3 => "PlayStation 3",
+            Platform::Ps2 => "PlayStation 2",
+            Platform::Ps1 => "PlayStation",
+            Platform::Psp => "PlayStation Portable",
+            Platform::PsVita => "PlayStation Vita",
+            Platform::XboxSeries => "Xbox Series X|S",
+            Platform::XboxOne => "Xbox One",
+            Platform::Xbox360 => "Xbox 360",
+            Platform::Xbox => "Xbox",
+            Platform::Switch => "Nintendo Switch",
+            Platform::WiiU => "Wii U",
+            Platform::Wii => "Wii",
+            Platform::Gamecube => "GameCube",
+            Platform::N64 => "Nintendo 64",
+            Platform::Snes => "Super Nintendo",
+            Platform::Nes => "NES",
+            Platform::Nintendo3ds => "Nintendo 3DS",
+            Platform::NintendoDs => "Nintendo DS",
+            Platform::GameBoyAdvance => "Game Boy Advance",
+            Platform::GameBoy => "Game Boy",
+            Platform::MegaDrive => "Mega Drive / Genesis",
+            Platform::Dreamcast => "Dreamcast",
+            Platform::Pc => "PC",
+            Platform::Other => "Other",
+        }
+    }
+
+    /// Other common spellings of this platform, compared without case, spaces and punctuation.
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Platform::Ps5 => &["playstation5"],
+            Platform::Ps4 => &["playstation4"],
+            Platform::Ps3 => &["playstation3"],
+            Platform::Ps2 => &["playstation2"],
+            Platform::Ps1 => &["psx", "psone", "playstation1"],
+            Platform::PsVita => &["vita"],
+            Platform::XboxSeries => &["xboxseriesx", "xboxseriess", "xsx", "seriesx"],
+            Platform::XboxOne => &["xb1"],
+            Platform::Switch => &["ns", "switcholed", "switchlite"],
+            Platform::Gamecube => &["gc", "ngc", "nintendogamecube"],
+            Platform::Snes => &["supernes", "supernintendo", "superfamicom"],
+            Platform::Nes => &["famicom"],
+            Platform::Nintendo3ds => &["3ds", "2ds", "new3ds"],
+            Platform::NintendoDs => &["ds", "dslite", "dsi"],
+            Platform::GameBoyAdvance => &["gba"],
+            Platform::GameBoy => &["gb", "gbc", "gameboycolor"],
+            Platform::MegaDrive => &["genesis", "segagenesis", "segamegadrive"],
+            Platform::Dreamcast => &["segadreamcast"],
+            Platform::Pc => &["windows", "mac", "macos", "linux", "steam"],
+            _ => &[],
+        }
+    }
+
+    /// Finds the platform a name refers to.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The stored value, label or a common spelling, e.g. `ps5`, `PlayStation 5`,
+    ///   `PS-5`.
+    ///
+    /// # Returns
+    ///
+    /// The `Platform`, or `None` if the name matches none.
+    pub fn parse(name: &str) -> Option<Platform> {
+        let key = compact(name);
+        if key.is_empty() {
+            return None;
+        }
+        Platform::ALL.into_iter().find(|platform| {
+            compact(platform.as_str()) == key
+                || compact(platform.label()) == key
+                || platform.aliases().contains(&key.as_str())
+        })
+    }
+}
+
+impl TryFrom<String> for Platform {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Platform::parse(&name).ok_or_else(|| {
+            format!(
+                "unknown platform `{}`, see GET /api/platforms for the supported platforms",
+                name.trim()
+            )
+        })
+    }
+}
+
+/// Lowercases a name and removes everything but letters and digits.
+fn compact(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Returns the platform a value stored before the platform list existed refers to, `Other` if it
+/// matches none.
+fn normalize_stored(name: &str) -> Platform {
+    Platform::parse(name).unwrap_or(Platform::Other)
+}
+
+/// A game of a stored bundle, with the platform and condition as stored.
+#[derive(Debug, Deserialize)]
+struct StoredBundleItem {
+    game_title: String,
+    platform: String,
+    condition: String,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+/// The games of a stored bundle.
+#[derive(Debug, Deserialize)]
+struct StoredBundle {
+    id: Thing,
+    bundle_items: Vec<StoredBundleItem>,
+}
+
+/// The preferred platforms of a user, as stored.
+#[derive(Debug, Deserialize)]
+struct StoredPreferences {
+    id: Thing,
+    platforms: Vec<String>,
+}
+
+/// Replaces every distinct stored value of a field by its normalized value.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `table` - The table to update.
+/// * `field` - The field holding the value.
+/// * `normalize` - Returns the value to store for a stored value.
+pub(super) async fn normalize_field(
+    db: &Surreal<Db>,
+    table: &str,
+    field: &str,
+    normalize: fn(&str) -> &'static str,
+) -> Result<(), surrealdb::Error> {
+    let mut response = db
+        .query(format!(
+            "SELECT {field} FROM {table} WHERE {field} != NONE GROUP BY {field};"
+        ))
+        .await?;
+    let stored: Vec<BTreeMap<String, String>> = response.take(0)?;
+    for stored in stored.iter().filter_map(|row| row.get(field)) {
+        let normalized = normalize(stored);
+        if normalized == stored {
+            continue;
+        }
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("from".into(), Value::from(stored.as_str()));
+        vars.insert("to".into(), Value::from(normalized));
+        db.query(format!(
+            "UPDATE {table} SET {field} = $to WHERE {field} = $from;"
+        ))
+        .bind(vars)
+        .await?
+        .check()?;
+    }
+    Ok(())
+}
+
+/// Replaces the platforms and conditions of bundle games that aren't IDs by their platforms and
+/// conditions.
+async fn normalize_bundles(db: &Surreal<Db>) -> Result<(), surrealdb::Error> {
+    let bundles: Vec<StoredBundle> = db
+        .query("SELECT id, bundle_items FROM offers WHERE array::len(bundle_items ?? []) > 0;")
+        .await?
+        .take(0)?;
+    for bundle in bundles {
+        if bundle.bundle_items.iter().all(|item| {
+            normalize_stored(&item.platform).as_str() == item.platform
+                && conditions::normalize_stored(&item.condition).as_str() == item.condition
+        }) {
+            continue;
+        }
+        let items = bundle
+            .bundle_items
+            .into_iter()
+            .map(|item| BundleItem {
+                platform: normalize_stored(&item.platform),
+                game_title: item.game_title,
+                condition: conditions::normalize_stored(&item.condition),
+                notes: item.notes,
+            })
+            .collect();
+        let metadata = OfferMetadata {
+            bundle_items: Some(items),
+            ..OfferMetadata::default()
+        };
+        let mut updates = Vec::new();
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        metadata.push_assignments(&mut updates, &mut vars);
+        vars.insert("id".into(), Value::from(bundle.id));
+        db.query(format!("UPDATE $id SET {};", updates.join(", ")))
+            .bind(vars)
+            .await?
+            .check()?;
+    }
+    Ok(())
+}
+
+/// Normalizes the platforms of offers, bundles, saved searches and the price history stored
+/// before the platform list existed, and the conditions of bundle games. Unknown platforms become
+/// `Other`.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    let normalized = async {
+        for table in ["offers", "saved_searches", "price_history"] {
+            normalize_field(db, table, "platform", |name| {
+                normalize_stored(name).as_str()
+            })
+            .await?;
+        }
+        normalize_bundles(db).await
+    }
+    .await;
+    if let Err(error) = normalized {
+        tracing::error!("Error normalizing stored platforms: {}", error);
+        exit(1);
+    }
+}
+
+/// Normalizes the preferred platforms of users stored before the platform list existed. Unknown
+/// platforms are dropped.
+///
+/// Must be called while the user namespace is selected.
+pub(super) async fn define_user_schema(db: &Surreal<Db>) {
+    let normalized = async {
+        let users: Vec<StoredPreferences> = db
+            .query("SELECT id, preferences.preferred_platforms AS platforms FROM users WHERE array::len(preferences.preferred_platforms ?? []) > 0;")
+            .await?
+            .take(0)?;
+        for user in users {
+            let mut platforms: Vec<&str> = Vec::new();
+            for platform in user.platforms.iter().filter_map(|p| Platform::parse(p)) {
+                if !platforms.contains(&platform.as_str()) {
+                    platforms.push(platform.as_str());
+                }
+            }
+            if platforms.iter().eq(user.platforms.iter()) {
+                continue;
+            }
+            let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+            vars.insert("id".into(), Value::from(user.id));
+            vars.insert(
+                "platforms".into(),
+                Value::from(platforms.into_iter().map(Value::from).collect::<Vec<Value>>()),
+            );
+            db.query("UPDATE $id SET preferences.preferred_platforms = $platforms;")
+                .bind(vars)
+                .await?
+                .check()?;
+        }
+        Ok::<_, surrealdb::Error>(())
+    }
+    .await;
+    if let Err(error) = normalized {
+        tracing::error!("Error normalizing preferred platforms: {}", error);
+        exit(1);
+    }
+}
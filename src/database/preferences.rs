@@ -0,0 +1,207 @@
+//! src/database/preferences.rs
+//!
+//! This module handles the per-user preferences stored on the user record, such as preferred
+//! platforms, display currency and notification settings.
+
+use super::platforms::Platform;
+use super::{Database, User};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+use validator::ValidationError;
+
+/// The maximum number of preferred platforms a user can store.
+pub const MAX_PREFERRED_PLATFORMS: usize = 20;
+
+/// A currency offers can be listed and prices displayed in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    /// Euro.
+    #[default]
+    Eur,
+    /// US dollar.
+    Usd,
+    /// Pound sterling.
+    Gbp,
+    /// Swiss franc.
+    Chf,
+    /// Polish złoty.
+    Pln,
+    /// Swedish krona.
+    Sek,
+}
+
+impl Currency {
+    /// Every currency.
+    pub const ALL: [Currency; 6] = [
+        Currency::Eur,
+        Currency::Usd,
+        Currency::Gbp,
+        Currency::Chf,
+        Currency::Pln,
+        Currency::Sek,
+    ];
+
+    /// Returns the ISO 4217 code stored in the database for this currency.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Currency::Eur => "EUR",
+            Currency::Usd => "USD",
+            Currency::Gbp => "GBP",
+            Currency::Chf => "CHF",
+            Currency::Pln => "PLN",
+            Currency::Sek => "SEK",
+        }
+    }
+}
+
+/// How a user wants to be notified.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct NotificationSettings {
+    /// Whether in-app notifications are shown.
+    #[serde(default = "default_true")]
+    pub in_app: bool,
+    /// Whether notifications are also sent by email.
+    #[serde(default)]
+    pub email: bool,
+    /// The notification kinds (e.g. `offer_hidden`) the user does not want to receive.
+    #[serde(default)]
+    pub muted_kinds: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            in_app: true,
+            email: false,
+            muted_kinds: Vec::new(),
+        }
+    }
+}
+
+/// The preferences of a user, stored as the `preferences` sub-document of the user record.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct UserPreferences {
+    /// The platforms the user is interested in, used as search defaults.
+    #[serde(default)]
+    pub preferred_platforms: Vec<Platform>,
+    /// The currency prices are displayed in.
+    #[serde(default)]
+    pub currency: Currency,
+    /// How the user wants to be notified.
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+}
+
+impl UserPreferences {
+    /// Converts the preferences to the object stored in the database.
+    fn to_value(&self) -> Value {
+        let strings = |values: &[String]| {
+            Value::from(
+                values
+                    .iter()
+                    .map(|value| Value::from(value.as_str()))
+                    .collect::<Vec<Value>>(),
+            )
+        };
+        let mut notifications: BTreeMap<String, Value> = BTreeMap::new();
+        notifications.insert("in_app".into(), Value::from(self.notifications.in_app));
+        notifications.insert("email".into(), Value::from(self.notifications.email));
+        notifications.insert(
+            "muted_kinds".into(),
+            strings(&self.notifications.muted_kinds),
+        );
+
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert(
+            "preferred_platforms".into(),
+            Value::from(
+                self.preferred_platforms
+                    .iter()
+                    .map(|platform| Value::from(platform.as_str()))
+                    .collect::<Vec<Value>>(),
+            ),
+        );
+        object.insert("currency".into(), Value::from(self.currency.as_str()));
+        object.insert("notifications".into(), Value::from(notifications));
+        Value::from(object)
+    }
+}
+
+/// Ensures the preferences stay within the stored limits.
+pub fn validate_preferences(preferences: &UserPreferences) -> Result<(), ValidationError> {
+    let invalid =
+        |message: String| Err(ValidationError::new("preferences").with_message(message.into()));
+    if preferences.preferred_platforms.len() > MAX_PREFERRED_PLATFORMS {
+        invalid(format!(
+            "At most {} preferred platforms are allowed",
+            MAX_PREFERRED_PLATFORMS
+        ))
+    } else if preferences
+        .notifications
+        .muted_kinds
+        .iter()
+        .any(|kind| kind.trim().is_empty() || kind.len() > 50)
+    {
+        invalid("Muted notification kinds must be 1 to 50 characters long".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Retrieves the preferences of a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the user's preferences (the defaults if they never saved any), or
+    /// `None` if the user does not exist.
+    pub async fn get_user_preferences(
+        &self,
+        user_id: String,
+    ) -> Result<Option<UserPreferences>, CustomError> {
+        Ok(self
+            .get_user_by_id(user_id)
+            .await?
+            .map(|user| user.preferences))
+    }
+
+    /// Replaces the preferences of a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `preferences` - The new preferences.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the user exists and was updated.
+    pub async fn update_user_preferences(
+        &self,
+        user_id: String,
+        preferences: &UserPreferences,
+    ) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        tracing::info!("Updating preferences of user {}", user_id);
+        let sql =
+            "UPDATE type::thing('users', $user_id) SET preferences = $preferences RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id.as_str()));
+        vars.insert("preferences".into(), preferences.to_value());
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<User> = response.take(0)?;
+        Ok(updated.is_some())
+    }
+}
@@ -0,0 +1,232 @@
+//! src/database/price_history.rs
+//!
+//! This module records the prices offers are listed and sold at, per game title, and summarizes
+//! them over time, so sellers can price competitively and buyers can spot fair deals.
+//!
+//! Titles are matched case-insensitively and ignoring repeated whitespace, so "Zelda  BOTW" and
+//! "zelda botw" share a history.
+
+use super::blind_index::normalize_name;
+use super::platforms::Platform;
+use super::{Database, Offer, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+use validator_derive::Validate;
+
+/// The number of days of history returned if the client does not ask for a period.
+pub const DEFAULT_PRICE_HISTORY_DAYS: u32 = 365;
+
+/// Defines the `price_history` table and the index used to summarize a title.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE price_history SCHEMALESS;",
+        "price_history table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD recorded_at ON price_history TYPE datetime DEFAULT time::now();",
+        "recorded_at field on price_history",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX price_history_title ON price_history FIELDS title_key, recorded_at",
+        "price_history_title index on price_history",
+    )
+    .await;
+}
+
+/// What happened to an offer's price.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceEvent {
+    /// The offer was listed, or listed again, at its price.
+    Listed,
+    /// The seller changed the price of a listed offer.
+    PriceChange,
+    /// The offer was sold at its price.
+    Sold,
+}
+
+impl PriceEvent {
+    /// Returns the value stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriceEvent::Listed => "listed",
+            PriceEvent::PriceChange => "price_change",
+            PriceEvent::Sold => "sold",
+        }
+    }
+}
+
+/// The length of the periods a price history is summarized in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceInterval {
+    /// One period per day.
+    Day,
+    /// One period per week.
+    #[default]
+    Week,
+}
+
+impl PriceInterval {
+    /// Returns the SurrealQL duration of a period.
+    fn as_duration(&self) -> &'static str {
+        match self {
+            PriceInterval::Day => "1d",
+            PriceInterval::Week => "1w",
+        }
+    }
+}
+
+/// The query parameters of a price history request.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Validate)]
+pub struct PriceHistoryQuery {
+    /// Only include offers for this platform.
+    pub platform: Option<Platform>,
+    /// The number of days of history to include (defaults to `DEFAULT_PRICE_HISTORY_DAYS`).
+    #[validate(range(min = 1, max = 3650, message = "Days must be between 1 and 3650"))]
+    pub days: Option<u32>,
+    /// The length of the periods the history is summarized in (defaults to a week).
+    #[serde(default)]
+    pub interval: PriceInterval,
+    /// Only include the prices offers were sold at, leaving out asking prices.
+    #[serde(default)]
+    pub sold_only: bool,
+}
+
+/// The price statistics of a set of recorded prices.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PriceStats {
+    /// The number of recorded prices.
+    pub count: u64,
+    /// The lowest price.
+    pub min: f64,
+    /// The average price.
+    pub avg: f64,
+    /// The median price.
+    pub median: f64,
+}
+
+/// The price statistics of one period.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PricePeriod {
+    /// The start of the period.
+    pub period: String,
+    /// The statistics of the prices recorded in the period.
+    #[serde(flatten)]
+    pub stats: PriceStats,
+}
+
+/// The price history of a game title.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PriceHistory {
+    /// The normalized title the history belongs to.
+    pub title: String,
+    /// The statistics across the whole requested history, or `None` if no prices were recorded.
+    pub overall: Option<PriceStats>,
+    /// The statistics per period, oldest first. Periods without prices are left out.
+    pub periods: Vec<PricePeriod>,
+}
+
+impl Database {
+    /// Records an offer's current price in the price history of its title.
+    ///
+    /// Offers hidden by a moderator are left out, so suspicious listings don't skew the history.
+    /// Bundles are left out as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer, with the price to record.
+    /// * `event` - What happened to the price.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the price couldn't be recorded.
+    pub async fn record_price(&self, offer: &Offer, event: PriceEvent) -> Result<(), CustomError> {
+        // A bundle's price says nothing about the price of any one of its games
+        if offer.hidden || offer.is_bundle() {
+            return Ok(());
+        }
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Recording {} price of offer {}", event.as_str(), offer.id);
+        let sql = "CREATE price_history SET offer = $offer, game_title = $game_title, title_key = $title_key, platform = $platform, condition = $condition, price = $price, event = $event;";
+
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer".into(), Value::from(offer.id.clone()));
+        vars.insert("game_title".into(), Value::from(offer.game_title.as_str()));
+        vars.insert(
+            "title_key".into(),
+            Value::from(normalize_name(&offer.game_title)),
+        );
+        vars.insert("platform".into(), Value::from(offer.platform.as_str()));
+        vars.insert("condition".into(), Value::from(offer.condition.as_str()));
+        vars.insert("price".into(), Value::from(offer.price));
+        vars.insert("event".into(), Value::from(event.as_str()));
+
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(())
+    }
+
+    /// Summarizes the recorded prices of a game title, overall and per period.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - The game title.
+    /// * `query` - The platform, period and kind of prices to include.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the price history or a `CustomError` if the query fails.
+    pub async fn get_price_history(
+        &self,
+        title: &str,
+        query: &PriceHistoryQuery,
+    ) -> Result<PriceHistory, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let title = normalize_name(title);
+        tracing::info!("Retrieving price history of {}", title);
+
+        let mut conditions = vec![
+            "title_key = $title_key".to_string(),
+            format!(
+                "recorded_at >= time::now() - {}d",
+                query.days.unwrap_or(DEFAULT_PRICE_HISTORY_DAYS)
+            ),
+        ];
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("title_key".into(), Value::from(title.as_str()));
+        if let Some(platform) = query.platform {
+            conditions.push("platform = $platform".to_string());
+            vars.insert("platform".into(), Value::from(platform.as_str()));
+        }
+        if query.sold_only {
+            conditions.push("event = $sold".to_string());
+            vars.insert("sold".into(), Value::from(PriceEvent::Sold.as_str()));
+        }
+
+        let stats = "count() AS count, math::min(price) AS min, math::mean(price) AS avg, math::median(price) AS median";
+        let sql = format!(
+            "SELECT {stats} FROM price_history WHERE {conditions} GROUP ALL; SELECT time::floor(recorded_at, {interval}) AS period, {stats} FROM price_history WHERE {conditions} GROUP BY period ORDER BY period;",
+            conditions = conditions.join(" AND "),
+            interval = query.interval.as_duration()
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let overall: Option<PriceStats> = response.take(0)?;
+        let periods: Vec<PricePeriod> = response.take(1)?;
+        Ok(PriceHistory {
+            title,
+            overall,
+            periods,
+        })
+    }
+}
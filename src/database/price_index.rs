@@ -0,0 +1,151 @@
+//! src/database/price_index.rs
+//!
+//! This module computes the weekly price index: the median price each title sold at during a
+//! week, per platform and condition. Every week is stored as a snapshot, so the published index
+//! keeps its history even though the underlying price history is summarized differently over
+//! time. Combinations with fewer than `MIN_INDEX_SALES` sales are left out, so single sales can't
+//! be traced back to a buyer or seller.
+
+use super::price_history::PriceEvent;
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use chrono::{Datelike, Days, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+use validator_derive::Validate;
+
+/// The minimum number of sales of a title, platform and condition to be included in the index.
+pub const MIN_INDEX_SALES: u64 = 3;
+/// The number of weekly snapshots returned if the client does not ask for a number.
+pub const DEFAULT_INDEX_WEEKS: u32 = 12;
+
+/// The query parameters of a price index request.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Validate)]
+pub struct PriceIndexQuery {
+    /// The number of most recent weekly snapshots to return (defaults to `DEFAULT_INDEX_WEEKS`).
+    #[validate(range(min = 1, max = 104, message = "Weeks must be between 1 and 104"))]
+    pub weeks: Option<u32>,
+}
+
+/// The median sold price of a title on one platform in one condition.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PriceIndexEntry {
+    /// The normalized game title.
+    #[serde(alias = "title_key")]
+    pub title: String,
+    /// The normalized platform.
+    pub platform: String,
+    /// The normalized condition, or `None` for sales recorded before conditions were.
+    #[serde(default)]
+    pub condition: Option<String>,
+    /// The median price the title sold at.
+    pub median: f64,
+    /// The number of sales.
+    pub sales: u64,
+}
+
+/// The price index of one week.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PriceIndexSnapshot {
+    /// The start of the week (Monday, 00:00 UTC).
+    pub week: String,
+    /// The index entries, sorted by title, platform and condition.
+    pub entries: Vec<PriceIndexEntry>,
+    /// The timestamp when the snapshot was computed.
+    pub created_at: String,
+}
+
+/// Defines the `price_index_snapshots` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE price_index_snapshots SCHEMALESS;",
+        "price_index_snapshots table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD week ON price_index_snapshots TYPE datetime;",
+        "week field on price_index_snapshots",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX price_index_snapshots_week ON price_index_snapshots FIELDS week UNIQUE",
+        "price_index_snapshots_week index on price_index_snapshots",
+    )
+    .await;
+}
+
+/// Returns the Monday starting the last week that has fully passed on the given day.
+///
+/// # Arguments
+///
+/// * `today` - The current day (UTC).
+pub fn last_completed_week(today: NaiveDate) -> NaiveDate {
+    let this_week = today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+    this_week - Days::new(7)
+}
+
+impl Database {
+    /// Computes and stores the price index of a week, unless it was stored before.
+    ///
+    /// # Arguments
+    ///
+    /// * `week` - The Monday starting the week.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new snapshot, or `None` if the week already has one.
+    pub async fn create_price_index_snapshot(
+        &self,
+        week: NaiveDate,
+    ) -> Result<Option<PriceIndexSnapshot>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let start = week.and_time(NaiveTime::MIN).and_utc();
+        tracing::info!("Computing the price index of the week starting {}", week);
+        let sql = "LET $entries = (SELECT title_key, platform, condition, math::median(price) AS median, count() AS sales FROM price_history WHERE event = $sold AND recorded_at >= <datetime> $start AND recorded_at < <datetime> $end GROUP BY title_key, platform, condition ORDER BY title_key, platform, condition); IF (SELECT * FROM price_index_snapshots WHERE week = <datetime> $start) = [] THEN (CREATE price_index_snapshots SET week = <datetime> $start, entries = (SELECT * FROM $entries WHERE sales >= $min_sales), created_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("sold".into(), Value::from(PriceEvent::Sold.as_str()));
+        vars.insert("start".into(), Value::from(start.to_rfc3339()));
+        vars.insert(
+            "end".into(),
+            Value::from((start + Days::new(7)).to_rfc3339()),
+        );
+        vars.insert("min_sales".into(), Value::from(MIN_INDEX_SALES as i64));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<PriceIndexSnapshot> = response.take(1)?;
+        Ok(created)
+    }
+
+    /// Retrieves the most recent weekly price index snapshots, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The number of weeks to return.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the snapshots or a `CustomError` if retrieval fails.
+    pub async fn get_price_index_snapshots(
+        &self,
+        query: &PriceIndexQuery,
+    ) -> Result<Vec<PriceIndexSnapshot>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM price_index_snapshots ORDER BY week DESC LIMIT $limit;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "limit".into(),
+            Value::from(i64::from(query.weeks.unwrap_or(DEFAULT_INDEX_WEEKS))),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let snapshots: Vec<PriceIndexSnapshot> = response.take(0)?;
+        Ok(snapshots)
+    }
+}
@@ -0,0 +1,458 @@
+//! src/database/promo_codes.rs
+//!
+//! This module handles promo codes. Admins create codes granting a percentage or a fixed amount
+//! off, optionally limited in the number of uses and in time, and buyers redeem them when they
+//! place an order. A use is counted atomically when it is redeemed, so a code can't be used more
+//! often than its limit even if buyers check out at the same time. Every redemption is recorded
+//! for reporting.
+
+use super::orders::Order;
+use super::preferences::Currency;
+use super::{Database, define, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::exchange_rates::ExchangeRates;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// How a promo code reduces the price.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountKind {
+    /// A share of the price, in percent.
+    Percent,
+    /// A fixed amount in the code's currency.
+    Fixed,
+}
+
+impl DiscountKind {
+    /// Returns the string stored in the database for this kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscountKind::Percent => "percent",
+            DiscountKind::Fixed => "fixed",
+        }
+    }
+}
+
+/// Represents a promo code.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromoCode {
+    /// The code's ID, the code itself.
+    pub id: Thing,
+    /// The code buyers enter, in upper case.
+    pub code: String,
+    /// How the code reduces the price.
+    pub kind: DiscountKind,
+    /// The percentage or the amount taken off the price.
+    pub value: f64,
+    /// The currency of a fixed amount.
+    #[serde(default)]
+    pub currency: Currency,
+    /// The number of times the code can be redeemed, or `None` if unlimited.
+    #[serde(default)]
+    pub max_uses: Option<u64>,
+    /// The number of times the code was redeemed.
+    #[serde(default)]
+    pub uses: u64,
+    /// The timestamp from which the code can no longer be redeemed, or `None` if it doesn't expire.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    /// Whether the code can be redeemed. Deactivated codes are kept for reporting.
+    pub active: bool,
+    /// A description of the code shown to admins.
+    pub description: String,
+    /// The ID of the admin who created the code.
+    pub created_by: String,
+    /// The timestamp when the code was created.
+    pub created_at: String,
+}
+
+/// The values of a promo code set by an admin.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PromoCodeFields {
+    /// The normalized code.
+    pub code: String,
+    /// How the code reduces the price.
+    pub kind: DiscountKind,
+    /// The percentage or the amount taken off the price.
+    pub value: f64,
+    /// The currency of a fixed amount.
+    pub currency: Currency,
+    /// The number of times the code can be redeemed.
+    pub max_uses: Option<u64>,
+    /// The RFC 3339 timestamp from which the code can no longer be redeemed.
+    pub expires_at: Option<String>,
+    /// A description of the code.
+    pub description: String,
+}
+
+/// Why a promo code can't be redeemed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromoCodeProblem {
+    /// The code was deactivated.
+    Inactive,
+    /// The code has expired.
+    Expired,
+    /// The code was redeemed as often as allowed.
+    UsedUp,
+}
+
+impl PromoCode {
+    /// Checks whether the code can currently be redeemed.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or the `PromoCodeProblem` preventing the redemption.
+    pub fn check_redeemable(&self, now: DateTime<Utc>) -> Result<(), PromoCodeProblem> {
+        let expired = self.expires_at.as_deref().is_some_and(|expires_at| {
+            DateTime::parse_from_rfc3339(expires_at).is_ok_and(|expires_at| expires_at <= now)
+        });
+        if !self.active {
+            Err(PromoCodeProblem::Inactive)
+        } else if expired {
+            Err(PromoCodeProblem::Expired)
+        } else if self.max_uses.is_some_and(|max_uses| self.uses >= max_uses) {
+            Err(PromoCodeProblem::UsedUp)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Computes the discount the code grants on a price.
+    ///
+    /// Fixed amounts in another currency are converted with the given rates. The discount is
+    /// rounded to cents and never exceeds the price.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` - The price to discount.
+    /// * `currency` - The currency of the price.
+    /// * `rates` - The current exchange rates, if known.
+    ///
+    /// # Returns
+    ///
+    /// The discount, or `None` if a fixed amount can't be converted into the price's currency.
+    pub fn discount_for(
+        &self,
+        price: f64,
+        currency: Currency,
+        rates: Option<&ExchangeRates>,
+    ) -> Option<f64> {
+        let discount = match self.kind {
+            DiscountKind::Percent => price * self.value / 100.0,
+            DiscountKind::Fixed if self.currency == currency => self.value,
+            DiscountKind::Fixed => rates?.convert(self.value, self.currency, currency)?.amount,
+        };
+        Some(((discount * 100.0).round() / 100.0).clamp(0.0, price))
+    }
+}
+
+/// A promo code applied to an order.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AppliedPromo {
+    /// The redeemed code.
+    pub code: String,
+    /// The price before the discount.
+    pub list_price: f64,
+    /// The amount taken off the price.
+    pub discount: f64,
+}
+
+impl AppliedPromo {
+    /// Converts the applied promo code to the object stored on the order.
+    pub(super) fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert("code".into(), Value::from(self.code.as_str()));
+        object.insert("list_price".into(), Value::from(self.list_price));
+        object.insert("discount".into(), Value::from(self.discount));
+        Value::from(object)
+    }
+}
+
+/// Represents the redemption of a promo code for an order.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromoRedemption {
+    /// The redemption's ID.
+    pub id: Thing,
+    /// The redeemed code.
+    pub code: String,
+    /// The ID of the order the code was redeemed for.
+    pub order_id: String,
+    /// The ID of the buyer who redeemed the code.
+    pub user_id: String,
+    /// The amount taken off the price.
+    pub discount: f64,
+    /// The currency of the discount.
+    #[serde(default)]
+    pub currency: Currency,
+    /// The timestamp of the redemption.
+    pub created_at: String,
+}
+
+/// Normalizes a promo code entered by a buyer or admin.
+///
+/// # Arguments
+///
+/// * `code` - The entered code.
+///
+/// # Returns
+///
+/// The upper-case code, or `None` if it isn't 3 to 32 letters, digits and dashes.
+pub fn normalize_promo_code(code: &str) -> Option<String> {
+    let code = code.trim();
+    ((3..=32).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        .then(|| code.to_ascii_uppercase())
+}
+
+/// Defines the `promo_codes` and `promo_redemptions` tables.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE promo_codes SCHEMALESS;",
+        "promo_codes table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD expires_at ON promo_codes TYPE option<datetime>;",
+        "expires_at field on promo_codes",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON promo_codes TYPE datetime;",
+        "created_at field on promo_codes",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE TABLE promo_redemptions SCHEMALESS;",
+        "promo_redemptions table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON promo_redemptions TYPE datetime;",
+        "created_at field on promo_redemptions",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX promo_redemptions_code ON promo_redemptions FIELDS code",
+        "promo_redemptions_code index on promo_redemptions",
+    )
+    .await;
+}
+
+impl Database {
+    /// Creates a new promo code.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The values of the code.
+    /// * `admin_id` - The ID of the admin creating the code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `PromoCode`, or `None` if the code already exists.
+    pub async fn create_promo_code(
+        &self,
+        fields: &PromoCodeFields,
+        admin_id: String,
+    ) -> Result<Option<PromoCode>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Creating promo code {}", fields.code);
+        let sql = "IF (SELECT * FROM type::thing('promo_codes', $code)) = [] THEN (CREATE type::thing('promo_codes', $code) SET code = $code, kind = $kind, value = $value, currency = $currency, max_uses = $max_uses, uses = 0, expires_at = IF $expires_at = NONE THEN NONE ELSE <datetime> $expires_at END, active = true, description = $description, created_by = $admin_id, created_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(fields.code.as_str()));
+        vars.insert("kind".into(), Value::from(fields.kind.as_str()));
+        vars.insert("value".into(), Value::from(fields.value));
+        vars.insert("currency".into(), Value::from(fields.currency.as_str()));
+        vars.insert(
+            "max_uses".into(),
+            fields.max_uses.map_or(Value::None, |max_uses| {
+                Value::from(i64::try_from(max_uses).unwrap_or(i64::MAX))
+            }),
+        );
+        vars.insert("expires_at".into(), Value::from(fields.expires_at.clone()));
+        vars.insert(
+            "description".into(),
+            Value::from(fields.description.as_str()),
+        );
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<PromoCode> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Retrieves a promo code.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The normalized code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PromoCode`, or `None` if it doesn't exist.
+    pub async fn get_promo_code(&self, code: &str) -> Result<Option<PromoCode>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM type::thing('promo_codes', $code);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let promo: Option<PromoCode> = response.take(0)?;
+        Ok(promo)
+    }
+
+    /// Retrieves every promo code, newest first.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `PromoCode` structs or a `CustomError` if retrieval
+    /// fails.
+    pub async fn get_promo_codes(&self) -> Result<Vec<PromoCode>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM promo_codes ORDER BY created_at DESC;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let codes: Vec<PromoCode> = response.take(0)?;
+        Ok(codes)
+    }
+
+    /// Deactivates a promo code, so it can no longer be redeemed.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The normalized code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the deactivated `PromoCode`, or `None` if it doesn't exist.
+    pub async fn deactivate_promo_code(
+        &self,
+        code: &str,
+    ) -> Result<Option<PromoCode>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Deactivating promo code {}", code);
+        let sql = "UPDATE type::thing('promo_codes', $code) SET active = false RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<PromoCode> = response.take(0)?;
+        Ok(updated)
+    }
+
+    /// Counts a use of a promo code, if it can still be redeemed.
+    ///
+    /// The check and the count happen in one statement, so concurrent redemptions can't exceed
+    /// the code's limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The normalized code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PromoCode` after the use was counted, or `None` if the code
+    /// doesn't exist or can no longer be redeemed.
+    pub async fn redeem_promo_code(&self, code: &str) -> Result<Option<PromoCode>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('promo_codes', $code) SET uses += 1 WHERE active = true AND (expires_at = NONE OR expires_at > time::now()) AND (max_uses = NONE OR uses < max_uses) RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let redeemed: Option<PromoCode> = response.take(0)?;
+        Ok(redeemed)
+    }
+
+    /// Takes back a use of a promo code whose order couldn't be placed.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The normalized code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the update fails.
+    pub async fn release_promo_code(&self, code: &str) -> Result<(), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "UPDATE type::thing('promo_codes', $code) SET uses -= 1 WHERE uses > 0;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code));
+
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(())
+    }
+
+    /// Records the redemption of a promo code for a placed order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order the code was redeemed for.
+    /// * `promo` - The applied code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the record can't be created.
+    pub async fn record_promo_redemption(
+        &self,
+        order: &Order,
+        promo: &AppliedPromo,
+    ) -> Result<(), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "CREATE promo_redemptions SET code = $code, order_id = $order_id, user_id = $user_id, discount = $discount, currency = $currency, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(promo.code.as_str()));
+        vars.insert(
+            "order_id".into(),
+            Value::from(record_key(&order.id).as_str()),
+        );
+        vars.insert("user_id".into(), Value::from(order.buyer_id.as_str()));
+        vars.insert("discount".into(), Value::from(promo.discount));
+        vars.insert("currency".into(), Value::from(order.currency.as_str()));
+
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(())
+    }
+
+    /// Retrieves the redemptions of a promo code, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The normalized code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `PromoRedemption` structs or a `CustomError` if
+    /// retrieval fails.
+    pub async fn get_promo_redemptions(
+        &self,
+        code: &str,
+    ) -> Result<Vec<PromoRedemption>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM promo_redemptions WHERE code = $code ORDER BY created_at DESC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("code".into(), Value::from(code));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let redemptions: Vec<PromoRedemption> = response.take(0)?;
+        Ok(redemptions)
+    }
+}
@@ -0,0 +1,117 @@
+//! src/database/public_stats.rs
+//!
+//! This module computes the aggregate marketplace statistics published for community sites and
+//! shown on the marketplace. They contain counts and averages only, never data of individual users
+//! or offers, and are computed by aggregate queries.
+
+use super::catalog::converted_price_sql;
+use super::platforms::Platform;
+use super::preferences::Currency;
+use super::{Count, Database, listed_offer_conditions};
+use crate::errors::custom_errors::CustomError;
+use crate::exchange_rates::ExchangeRateCache;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+
+/// The public marketplace statistics.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct PublicStats {
+    /// The number of listed, active offers.
+    pub active_listings: u64,
+    /// The number of sellers with at least one active offer.
+    pub sellers: u64,
+    /// The number of distinct game titles in the price history.
+    pub games_tracked: u64,
+    /// The timestamp when the statistics were computed.
+    pub updated_at: String,
+}
+
+/// The listed, active offers of one platform.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PlatformStats {
+    /// The platform.
+    pub platform: Platform,
+    /// The number of active offers for the platform.
+    pub offers: u64,
+    /// The average asking price of the offers, in euros.
+    pub average_price: f64,
+}
+
+/// The statistics of the listed offers on the marketplace.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct MarketplaceStats {
+    /// The number of listed, active offers.
+    pub active_offers: u64,
+    /// The currency the average prices are in.
+    pub currency: Currency,
+    /// The active offers per platform, the platform with the most offers first.
+    pub platforms: Vec<PlatformStats>,
+    /// The timestamp when the statistics were computed.
+    pub updated_at: String,
+}
+
+impl Database {
+    /// Computes the public marketplace statistics.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PublicStats` or a `CustomError` if a count fails.
+    pub async fn compute_public_stats(&self) -> Result<PublicStats, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let conditions = listed_offer_conditions(true, &mut vars).join(" AND ");
+        let sql = format!(
+            "SELECT count() FROM offers WHERE {conditions} GROUP ALL; SELECT count() FROM (SELECT seller_id FROM offers WHERE {conditions} GROUP BY seller_id) GROUP ALL; SELECT count() FROM (SELECT title_key FROM price_history GROUP BY title_key) GROUP ALL;"
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let active_listings: Option<Count> = response.take(0)?;
+        let sellers: Option<Count> = response.take(1)?;
+        let games_tracked: Option<Count> = response.take(2)?;
+        Ok(PublicStats {
+            active_listings: active_listings.map_or(0, |count| count.count),
+            sellers: sellers.map_or(0, |count| count.count),
+            games_tracked: games_tracked.map_or(0, |count| count.count),
+            updated_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Computes the statistics of the listed offers, grouped by platform in the database.
+    ///
+    /// Prices in other currencies are converted to euros with the current exchange rates before
+    /// they are averaged.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `MarketplaceStats` or a `CustomError` if the query fails.
+    pub async fn compute_marketplace_stats(&self) -> Result<MarketplaceStats, CustomError> {
+        let rates = ExchangeRateCache::global().rates().await;
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let price = converted_price_sql(rates.as_ref(), Currency::Eur, &mut vars);
+        let conditions = listed_offer_conditions(true, &mut vars).join(" AND ");
+        let sql = format!(
+            "SELECT platform, count() AS offers, math::mean({price}) AS average_price FROM offers WHERE {conditions} GROUP BY platform;"
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let mut platforms: Vec<PlatformStats> = response.take(0)?;
+        for stats in &mut platforms {
+            stats.average_price = (stats.average_price * 100.0).round() / 100.0;
+        }
+        platforms.sort_by(|a, b| {
+            b.offers
+                .cmp(&a.offers)
+                .then_with(|| a.platform.as_str().cmp(b.platform.as_str()))
+        });
+        Ok(MarketplaceStats {
+            active_offers: platforms.iter().map(|stats| stats.offers).sum(),
+            currency: Currency::Eur,
+            platforms,
+            updated_at: Utc::now().to_rfc3339(),
+        })
+    }
+}
@@ -0,0 +1,140 @@
+//! src/database/reservations.rs
+//!
+//! This module handles reservations buyers make while they arrange the payment of an offer. A
+//! reservation moves the offer to `reserved` for a limited time, during which only the reserving
+//! buyer can buy it. Reservations the buyer hasn't turned into an order in time are released by
+//! the reservation expiry job, which lists the offer again.
+
+use super::offer_status::OfferStatus;
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The condition of an offer having no order that is still being processed.
+const NO_ORDER_IN_PROGRESS: &str = "array::len((SELECT VALUE id FROM orders WHERE offer_id = record::id($parent.id) AND state IN ['pending', 'paid', 'shipped'])) = 0";
+
+/// Defines the reservation fields on `offers` and the index used to find expired reservations.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD reserved_until ON offers TYPE option<datetime>;",
+        "reserved_until field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_status_reserved_until ON offers FIELDS status, reserved_until",
+        "offers_status_reserved_until index on offers",
+    )
+    .await;
+}
+
+impl Database {
+    /// Reserves an active offer of a single copy for a buyer.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The offer, as read before the reservation.
+    /// * `buyer_id` - The ID of the buyer.
+    /// * `hours` - How many hours the reservation lasts.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the reserved offer, or `None` if the offer is no longer active.
+    pub async fn reserve_offer(
+        &self,
+        offer: &Offer,
+        buyer_id: &str,
+        hours: u32,
+    ) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let offer_id = record_key(&offer.id);
+        tracing::info!(
+            "Reserving offer {} for user {} for {} hours",
+            offer_id,
+            buyer_id,
+            hours
+        );
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET status = $reserved, status_changed_at = time::now(), reserved_by = $buyer_id, reserved_until = time::now() + {}h WHERE (status ?? $active) = $active AND (quantity ?? 1) = 1 RETURN AFTER;",
+            hours
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id.as_str()));
+        vars.insert("buyer_id".into(), Value::from(buyer_id));
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+        vars.insert(
+            "reserved".into(),
+            Value::from(OfferStatus::Reserved.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let reserved: Option<Offer> = response.take(0)?;
+        Ok(reserved)
+    }
+
+    /// Releases a buyer's reservation of an offer before it expires, listing the offer again.
+    ///
+    /// Reservations the buyer already placed an order for are kept; cancelling the order
+    /// releases them.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer_id` - The ID of the offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the listed offer, or `None` if the offer has no releasable
+    /// reservation.
+    pub async fn release_reservation(&self, offer_id: &str) -> Result<Option<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Releasing reservation of offer {}", offer_id);
+        let sql = format!(
+            "UPDATE type::thing('offers', $offer_id) SET status = $active, status_changed_at = time::now(), reserved_by = NONE, reserved_until = NONE WHERE status = $reserved AND reserved_until != NONE AND {} RETURN AFTER;",
+            NO_ORDER_IN_PROGRESS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("offer_id".into(), Value::from(offer_id));
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+        vars.insert(
+            "reserved".into(),
+            Value::from(OfferStatus::Reserved.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let released: Option<Offer> = response.take(0)?;
+        Ok(released)
+    }
+
+    /// Releases every reservation whose time has run out without an order, listing the offers
+    /// again.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the offers as they were reserved, or a `CustomError` if the update
+    /// fails.
+    pub async fn release_expired_reservations(&self) -> Result<Vec<Offer>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = format!(
+            "UPDATE offers SET status = $active, status_changed_at = time::now(), reserved_by = NONE, reserved_until = NONE WHERE status = $reserved AND reserved_until <= time::now() AND {} RETURN BEFORE;",
+            NO_ORDER_IN_PROGRESS
+        );
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("active".into(), Value::from(OfferStatus::Active.as_str()));
+        vars.insert(
+            "reserved".into(),
+            Value::from(OfferStatus::Reserved.as_str()),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let released: Vec<Offer> = response.take(0)?;
+        if !released.is_empty() {
+            tracing::info!("Released {} expired reservations", released.len());
+        }
+        Ok(released)
+    }
+}
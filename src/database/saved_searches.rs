@@ -0,0 +1,214 @@
+//! src/database/saved_searches.rs
+//!
+//! This module handles the searches users save to be alerted about new listings. When an offer is
+//! listed, the searches it matches are looked up and their users are notified.
+
+use super::platforms::Platform;
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+use validator::ValidationError;
+use validator_derive::Validate;
+
+/// The maximum number of searches a user can save.
+pub const MAX_SAVED_SEARCHES: usize = 20;
+
+/// What a saved search looks for. Every given criterion must match.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Validate)]
+#[validate(schema(function = "validate_criteria"))]
+pub struct SavedSearchCriteria {
+    /// Text the game title must contain (compared case-insensitively).
+    #[serde(default)]
+    #[validate(length(min = 1, max = 100, message = "Title must be 1 to 100 characters long"))]
+    pub title: Option<String>,
+    /// The platform the offer must be for.
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    /// The highest price the offer may have.
+    #[serde(default)]
+    #[validate(range(min = 0.0, message = "Maximum price must not be negative"))]
+    pub max_price: Option<f64>,
+}
+
+/// Ensures a saved search has at least one criterion, so it doesn't match every new offer.
+fn validate_criteria(criteria: &SavedSearchCriteria) -> Result<(), ValidationError> {
+    let blank = |value: &Option<String>| value.as_deref().is_none_or(|v| v.trim().is_empty());
+    if blank(&criteria.title) && criteria.platform.is_none() && criteria.max_price.is_none() {
+        Err(ValidationError::new("criteria")
+            .with_message("Give a title, a platform or a maximum price".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Represents a saved search.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedSearch {
+    /// The saved search's ID.
+    pub id: Thing,
+    /// The ID of the user who saved the search.
+    pub user_id: String,
+    /// What the search looks for.
+    #[serde(flatten)]
+    pub criteria: SavedSearchCriteria,
+    /// The timestamp when the search was saved.
+    pub created_at: String,
+}
+
+/// A user whose saved search matched an offer, as selected by `get_saved_search_matches`.
+#[derive(Debug, Deserialize)]
+struct SearchMatch {
+    user_id: String,
+}
+
+/// Defines the `saved_searches` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE saved_searches SCHEMALESS;",
+        "saved_searches table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX saved_searches_user_id ON saved_searches FIELDS user_id",
+        "saved_searches_user_id index on saved_searches",
+    )
+    .await;
+}
+
+impl Database {
+    /// Saves a search for a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `criteria` - The validated criteria.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SavedSearch` or a `CustomError` if it cannot be stored.
+    pub async fn create_saved_search(
+        &self,
+        user_id: &str,
+        criteria: &SavedSearchCriteria,
+    ) -> Result<SavedSearch, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Saving a search for user {}", user_id);
+        let sql = "CREATE saved_searches SET user_id = $user_id, title = $title, platform = $platform, max_price = $max_price, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert(
+            "title".into(),
+            Value::from(criteria.title.as_deref().map(str::trim)),
+        );
+        vars.insert(
+            "platform".into(),
+            Value::from(criteria.platform.map(|platform| platform.as_str())),
+        );
+        vars.insert("max_price".into(), Value::from(criteria.max_price));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<SavedSearch> = response.take(0)?;
+        created.ok_or_else(|| CustomError::DatabaseError("Failed to save search".to_string()))
+    }
+
+    /// Retrieves a user's saved searches, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the saved searches or a `CustomError` if retrieval fails.
+    pub async fn get_saved_searches(&self, user_id: &str) -> Result<Vec<SavedSearch>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM saved_searches WHERE user_id = $user_id ORDER BY created_at ASC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let searches: Vec<SavedSearch> = response.take(0)?;
+        Ok(searches)
+    }
+
+    /// Deletes one of a user's saved searches.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `search_id` - The ID of the saved search.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the search was deleted, or `false` if the user has no such
+    /// search.
+    pub async fn delete_saved_search(
+        &self,
+        user_id: &str,
+        search_id: &str,
+    ) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Deleting saved search {} of user {}", search_id, user_id);
+        let sql = "DELETE type::thing('saved_searches', $search_id) WHERE user_id = $user_id RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("search_id".into(), Value::from(search_id));
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Vec<SavedSearch> = response.take(0)?;
+        Ok(!deleted.is_empty())
+    }
+
+    /// Finds the users with a saved search matching a newly listed offer.
+    ///
+    /// The seller's own searches are left out. Bundles match searches for any of their games.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The listed offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the IDs of the users, each once, or a `CustomError` if retrieval
+    /// fails.
+    pub async fn get_saved_search_matches(
+        &self,
+        offer: &Offer,
+    ) -> Result<Vec<String>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT user_id FROM saved_searches WHERE user_id != $seller_id AND (title = NONE OR string::contains($titles, string::lowercase(title))) AND (platform = NONE OR $platforms CONTAINS platform) AND (max_price = NONE OR max_price >= $price) GROUP BY user_id;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "seller_id".into(),
+            Value::from(record_key(&offer.seller_id).as_str()),
+        );
+        // One title per line, so a search never matches across two titles
+        let titles = std::iter::once(&offer.game_title)
+            .chain(offer.bundle_items.iter().map(|item| &item.game_title))
+            .map(|title| title.to_lowercase())
+            .collect::<Vec<String>>()
+            .join("\n");
+        let platforms = std::iter::once(offer.platform)
+            .chain(offer.bundle_items.iter().map(|item| item.platform))
+            .map(|platform| Value::from(platform.as_str()))
+            .collect::<Vec<Value>>();
+        vars.insert("titles".into(), Value::from(titles));
+        vars.insert("platforms".into(), Value::from(platforms));
+        vars.insert("price".into(), Value::from(offer.price));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let matches: Vec<SearchMatch> = response.take(0)?;
+        Ok(matches.into_iter().map(|m| m.user_id).collect())
+    }
+}
@@ -0,0 +1,90 @@
+//! src/database/search.rs
+//!
+//! This module handles the full-text search over offer titles and descriptions, backed by
+//! SurrealDB search indexes with BM25 relevance scoring. Bundles are also found by the titles of
+//! their games.
+
+use super::pagination::{PageInfo, Pagination};
+use super::{Count, Database, Offer, define, listed_offer_conditions};
+use crate::errors::custom_errors::CustomError;
+
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::local::Db, sql::Value};
+
+/// The maximum length of a search query.
+pub const MAX_SEARCH_QUERY_LENGTH: usize = 200;
+
+/// Defines the analyzer and the search indexes on `offers`.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE ANALYZER offer_search TOKENIZERS blank, class FILTERS lowercase, ascii, snowball(english);",
+        "offer_search analyzer",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_game_title_search ON offers FIELDS game_title SEARCH ANALYZER offer_search BM25;",
+        "offers_game_title_search index on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_description_search ON offers FIELDS description SEARCH ANALYZER offer_search BM25;",
+        "offers_description_search index on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX offers_bundle_titles_search ON offers FIELDS bundle_titles SEARCH ANALYZER offer_search BM25;",
+        "offers_bundle_titles_search index on offers",
+    )
+    .await;
+}
+
+impl Database {
+    /// Searches the publicly listed offers by title and description.
+    ///
+    /// Matches in the title, or in the title of a game of a bundle, weigh twice as much as matches
+    /// in the description.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The search terms.
+    /// * `include_mature` - Whether mature-rated offers are included (only for adult viewers).
+    /// * `pagination` - The page to return.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the requested page of matching offers, most relevant first, and its
+    /// pagination details, or a `CustomError` if the search fails.
+    pub async fn search_offers(
+        &self,
+        query: &str,
+        include_mature: bool,
+        pagination: &Pagination,
+    ) -> Result<(Vec<Offer>, PageInfo), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Searching offers for: {}", query);
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let mut conditions = listed_offer_conditions(include_mature, &mut vars);
+        conditions.push(
+            "(game_title @0@ $query OR description @1@ $query OR bundle_titles @2@ $query)"
+                .to_string(),
+        );
+        vars.insert("query".into(), Value::from(query));
+        pagination.bind(&mut vars);
+
+        let sql = format!(
+            "SELECT *, (search::score(0) + search::score(2)) * 2 + search::score(1) AS relevance FROM offers WHERE {conditions} ORDER BY relevance DESC LIMIT $limit START $start; SELECT count() FROM offers WHERE {conditions} GROUP ALL;",
+            conditions = conditions.join(" AND ")
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Vec<Offer> = response.take(0)?;
+        let total: Option<Count> = response.take(1)?;
+        let total = total.map_or(0, |total| total.count);
+        Ok((offers, PageInfo::new(pagination, total)))
+    }
+}
@@ -0,0 +1,192 @@
+//! src/database/seller_analytics.rs
+//!
+//! This module aggregates the analytics sellers get about their offers: how often each offer was
+//! viewed, favorited, written about and ordered, how many views turned into sales, and the revenue
+//! of their completed sales per month.
+
+use super::ids::UserId;
+use super::offer_status::OfferStatus;
+use super::preferences::Currency;
+use super::{Database, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use chrono::{Datelike, Months, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use surrealdb::sql::{Thing, Value};
+
+/// The number of months of revenue returned to the seller, including the current one.
+pub const ANALYTICS_MONTHS: u32 = 12;
+
+/// The analytics of one of a seller's offers.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OfferAnalytics {
+    /// The ID of the offer.
+    pub offer_id: String,
+    /// The title of the game.
+    pub game_title: String,
+    /// The status of the offer.
+    pub status: OfferStatus,
+    /// The number of views since the offer was created, counting a viewer once per day.
+    pub views: u64,
+    /// The number of users who favorited the offer.
+    pub favorites: u64,
+    /// The number of messages written in the conversations about the offer.
+    pub messages: u64,
+    /// The number of orders of the offer that weren't cancelled.
+    pub orders: u64,
+    /// The number of completed orders of the offer.
+    pub sales: u64,
+    /// The share of views that turned into a completed sale, or `None` if the offer has no views.
+    pub conversion_rate: Option<f64>,
+}
+
+/// The revenue of a seller's completed sales in one month and currency.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MonthlyRevenue {
+    /// The month the sales were completed in, as `YYYY-MM` in UTC.
+    pub month: String,
+    /// The currency of the sales.
+    pub currency: Currency,
+    /// The number of completed sales.
+    pub sales: u64,
+    /// The sum of the prices the offers were bought at.
+    pub revenue: f64,
+    /// The sum of the platform fees kept from the prices.
+    pub platform_fees: f64,
+}
+
+/// The analytics of all of a seller's offers.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SellerAnalytics {
+    /// The analytics of every offer that isn't deleted, newest first.
+    pub offers: Vec<OfferAnalytics>,
+    /// The revenue per month over the last `ANALYTICS_MONTHS` months, oldest first. Months
+    /// without sales are left out.
+    pub revenue: Vec<MonthlyRevenue>,
+}
+
+/// An offer of the seller, as read for the analytics.
+#[derive(Debug, Deserialize)]
+struct OfferRow {
+    id: Thing,
+    game_title: String,
+    #[serde(default)]
+    status: OfferStatus,
+    #[serde(default)]
+    favorites_count: u64,
+}
+
+/// The number of views of an offer.
+#[derive(Debug, Deserialize)]
+struct ViewRow {
+    offer: Thing,
+    count: u64,
+}
+
+/// The number of messages about an offer.
+#[derive(Debug, Deserialize)]
+struct MessageRow {
+    offer_id: String,
+    count: u64,
+}
+
+/// The number of orders and sales of an offer.
+#[derive(Debug, Deserialize)]
+struct OrderRow {
+    offer_id: String,
+    orders: u64,
+    sales: u64,
+}
+
+/// Returns the share of views that turned into sales, or `None` without views.
+///
+/// # Arguments
+///
+/// * `sales` - The number of completed sales.
+/// * `views` - The number of views.
+pub fn conversion_rate(sales: u64, views: u64) -> Option<f64> {
+    (views > 0).then(|| sales as f64 / views as f64)
+}
+
+impl Database {
+    /// Aggregates the analytics of a seller's offers and the revenue of their sales.
+    ///
+    /// # Arguments
+    ///
+    /// * `seller_id` - The ID of the seller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the analytics or a `CustomError` if an aggregation fails.
+    pub async fn get_seller_analytics(
+        &self,
+        seller_id: &str,
+    ) -> Result<SellerAnalytics, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let today = Utc::now().date_naive();
+        let since = today
+            .with_day(1)
+            .and_then(|first| first.checked_sub_months(Months::new(ANALYTICS_MONTHS - 1)))
+            .unwrap_or(today);
+        // Sales count in the month their payment was released; older orders without a release
+        // time count in the month of their last update
+        let sql = "SELECT id, game_title, status, favorites_count FROM offers WHERE seller_id = $seller_id_thing AND deleted_at = NONE ORDER BY created_at DESC;
+            SELECT offer, count() AS count FROM offer_views WHERE offer IN (SELECT VALUE id FROM offers WHERE seller_id = $seller_id_thing) GROUP BY offer;
+            SELECT offer_id, math::sum(messages) AS count FROM (SELECT offer_id, array::len((SELECT VALUE id FROM messages WHERE conversation_id = record::id($parent.id))) AS messages FROM conversations WHERE seller_id = $seller_id) GROUP BY offer_id;
+            SELECT offer_id, count() AS orders, count(state = 'completed') AS sales FROM orders WHERE seller_id = $seller_id AND state != 'cancelled' GROUP BY offer_id;
+            SELECT time::format(released_at ?? updated_at ?? created_at, '%Y-%m') AS month, currency ?? 'EUR' AS currency, count() AS sales, math::sum(price) AS revenue, math::sum(platform_fee ?? 0) AS platform_fees FROM orders WHERE seller_id = $seller_id AND state = 'completed' AND (released_at ?? updated_at ?? created_at) >= type::datetime($since) GROUP BY month, currency ORDER BY month, currency;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("seller_id".into(), Value::from(seller_id));
+        vars.insert(
+            "seller_id_thing".into(),
+            Value::from(UserId::new(seller_id).to_reference()),
+        );
+        vars.insert(
+            "since".into(),
+            Value::from(format!("{}T00:00:00Z", since.format("%Y-%m-%d"))),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Vec<OfferRow> = response.take(0)?;
+        let views: Vec<ViewRow> = response.take(1)?;
+        let messages: Vec<MessageRow> = response.take(2)?;
+        let orders: Vec<OrderRow> = response.take(3)?;
+        let revenue: Vec<MonthlyRevenue> = response.take(4)?;
+
+        let views: HashMap<String, u64> = views
+            .into_iter()
+            .map(|row| (record_key(&row.offer), row.count))
+            .collect();
+        let messages: HashMap<String, u64> = messages
+            .into_iter()
+            .map(|row| (row.offer_id, row.count))
+            .collect();
+        let orders: HashMap<String, OrderRow> = orders
+            .into_iter()
+            .map(|row| (row.offer_id.clone(), row))
+            .collect();
+        let offers = offers
+            .into_iter()
+            .map(|offer| {
+                let offer_id = record_key(&offer.id);
+                let views = views.get(&offer_id).copied().unwrap_or(0);
+                let (order_count, sales) = orders
+                    .get(&offer_id)
+                    .map_or((0, 0), |row| (row.orders, row.sales));
+                OfferAnalytics {
+                    game_title: offer.game_title,
+                    status: offer.status,
+                    views,
+                    favorites: offer.favorites_count,
+                    messages: messages.get(&offer_id).copied().unwrap_or(0),
+                    orders: order_count,
+                    sales,
+                    conversion_rate: conversion_rate(sales, views),
+                    offer_id,
+                }
+            })
+            .collect();
+        Ok(SellerAnalytics { offers, revenue })
+    }
+}
@@ -0,0 +1,276 @@
+//! src/database/seller_verification.rs
+//!
+//! This module handles the verified seller badge. Sellers apply for the badge; sellers with at
+//! least `VERIFIED_MIN_COMPLETED_SALES` completed sales are verified right away, all others wait
+//! for an admin to review their application. The badge is stored on the user and copied to their
+//! offers as `seller_verified`, so listings can show it and be filtered by it.
+
+use super::ids::UserId;
+use super::{Database, define};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The number of completed sales that verify a seller without an admin review.
+pub const VERIFIED_MIN_COMPLETED_SALES: u64 = 10;
+
+/// The state of a verification application.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationState {
+    /// Waiting for an admin to review the application.
+    Pending,
+    /// The seller was verified.
+    Approved,
+    /// An admin declined the application.
+    Rejected,
+}
+
+impl VerificationState {
+    /// Returns the string stored in the database for this state.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerificationState::Pending => "pending",
+            VerificationState::Approved => "approved",
+            VerificationState::Rejected => "rejected",
+        }
+    }
+}
+
+/// Represents a seller's application for the verified badge.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VerificationRequest {
+    /// The application's ID.
+    pub id: Thing,
+    /// The ID of the applying seller.
+    pub seller_id: String,
+    /// The number of completed sales of the seller when they applied.
+    pub completed_sales: u64,
+    /// What the seller told the admins about themselves.
+    #[serde(default)]
+    pub message: Option<String>,
+    /// The state of the application.
+    pub state: VerificationState,
+    /// The ID of the admin who reviewed the application, `None` if it met the criteria.
+    #[serde(default)]
+    pub reviewed_by: Option<String>,
+    /// The note the admin left with their review.
+    #[serde(default)]
+    pub review_note: Option<String>,
+    /// The timestamp when the seller applied.
+    pub created_at: String,
+    /// The timestamp when the application was decided.
+    #[serde(default)]
+    pub reviewed_at: Option<String>,
+}
+
+/// Defines the `verification_requests` table and the badge field on `offers`.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE FIELD seller_verified ON offers TYPE bool DEFAULT false;",
+        "seller_verified field on offers",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE TABLE verification_requests SCHEMALESS;",
+        "verification_requests table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX verification_requests_seller ON verification_requests FIELDS seller_id, state",
+        "verification_requests_seller index on verification_requests",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON verification_requests TYPE datetime;",
+        "created_at field on verification_requests",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD reviewed_at ON verification_requests TYPE option<datetime>;",
+        "reviewed_at field on verification_requests",
+    )
+    .await;
+}
+
+impl Database {
+    /// Records a seller's application for the verified badge.
+    ///
+    /// Sellers with at least `VERIFIED_MIN_COMPLETED_SALES` completed sales are verified right
+    /// away; the application is then recorded as approved.
+    ///
+    /// # Arguments
+    ///
+    /// * `seller_id` - The ID of the applying seller.
+    /// * `message` - What the seller tells the admins about themselves (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `VerificationRequest`, or `None` if the seller already
+    /// has an application waiting for review.
+    pub async fn request_seller_verification(
+        &self,
+        seller_id: String,
+        message: Option<String>,
+    ) -> Result<Option<VerificationRequest>, CustomError> {
+        let completed_sales = self.count_completed_sales(&seller_id).await?;
+        let state = if completed_sales >= VERIFIED_MIN_COMPLETED_SALES {
+            VerificationState::Approved
+        } else {
+            VerificationState::Pending
+        };
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!(
+            "Seller {} applied for verification ({})",
+            seller_id,
+            state.as_str()
+        );
+        let sql = "IF (SELECT * FROM verification_requests WHERE seller_id = $seller_id AND state = 'pending') = [] THEN (CREATE verification_requests SET seller_id = $seller_id, completed_sales = $completed_sales, message = $message, state = $state, created_at = time::now(), reviewed_at = IF $state = 'approved' THEN time::now() ELSE NONE END) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("seller_id".into(), Value::from(seller_id.as_str()));
+        vars.insert("completed_sales".into(), Value::from(completed_sales));
+        vars.insert("message".into(), Value::from(message));
+        vars.insert("state".into(), Value::from(state.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<VerificationRequest> = response.take(0)?;
+        if let Some(request) = &created
+            && request.state == VerificationState::Approved
+        {
+            self.set_seller_verified(seller_id, true).await?;
+        }
+        Ok(created)
+    }
+
+    /// Retrieves verification applications, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - Only return applications in this state (optional).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `VerificationRequest` structs or a `CustomError` if
+    /// retrieval fails.
+    pub async fn get_verification_requests(
+        &self,
+        state: Option<VerificationState>,
+    ) -> Result<Vec<VerificationRequest>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let sql = match state {
+            Some(state) => {
+                vars.insert("state".into(), Value::from(state.as_str()));
+                "SELECT * FROM verification_requests WHERE state = $state ORDER BY created_at ASC;"
+            }
+            None => "SELECT * FROM verification_requests ORDER BY created_at ASC;",
+        };
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let requests: Vec<VerificationRequest> = response.take(0)?;
+        Ok(requests)
+    }
+
+    /// Records an admin's decision on a pending verification application.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_id` - The ID of the application.
+    /// * `approve` - Whether the seller is verified.
+    /// * `admin_id` - The ID of the reviewing admin.
+    /// * `note` - An optional note explaining the decision.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the reviewed `VerificationRequest`, or `None` if no pending
+    /// application with the given ID exists.
+    pub async fn review_verification_request(
+        &self,
+        request_id: String,
+        approve: bool,
+        admin_id: String,
+        note: Option<String>,
+    ) -> Result<Option<VerificationRequest>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let state = if approve {
+            VerificationState::Approved
+        } else {
+            VerificationState::Rejected
+        };
+        tracing::info!(
+            "Reviewing verification request {} as {}",
+            request_id,
+            state.as_str()
+        );
+        let sql = "UPDATE type::thing('verification_requests', $request_id) SET state = $state, reviewed_by = $admin_id, review_note = $note, reviewed_at = time::now() WHERE state = 'pending' RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("request_id".into(), Value::from(request_id.as_str()));
+        vars.insert("state".into(), Value::from(state.as_str()));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+        vars.insert("note".into(), Value::from(note));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let reviewed: Option<VerificationRequest> = response.take(0)?;
+
+        if let Some(request) = &reviewed
+            && request.state == VerificationState::Approved
+        {
+            self.set_seller_verified(request.seller_id.clone(), true)
+                .await?;
+        }
+        Ok(reviewed)
+    }
+
+    /// Grants or removes the verified badge of a seller, on their account and all their offers.
+    ///
+    /// # Arguments
+    ///
+    /// * `seller_id` - The ID of the seller.
+    /// * `verified` - Whether the seller carries the badge.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `false` if no user with the given ID exists, or a `CustomError` if
+    /// the update fails.
+    pub async fn set_seller_verified(
+        &self,
+        seller_id: String,
+        verified: bool,
+    ) -> Result<bool, CustomError> {
+        self.use_user_namespace().await?; // Switch to user namespace
+        let sql = "UPDATE type::thing('users', $user_id) SET verified = $verified, verified_at = IF $verified THEN time::now() ELSE NONE END RETURN AFTER;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(seller_id.as_str()));
+        vars.insert("verified".into(), Value::from(verified));
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let updated: Option<super::User> = response.take(0)?;
+        if updated.is_none() {
+            return Ok(false);
+        }
+
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql =
+            "UPDATE offers SET seller_verified = $verified WHERE seller_id = $seller_id_thing;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "seller_id_thing".into(),
+            Value::from(UserId::new(seller_id).to_reference()),
+        );
+        vars.insert("verified".into(), Value::from(verified));
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,235 @@
+//! src/database/serial_blacklist.rs
+//!
+//! This module handles the admin-maintained blacklist of serial numbers reported as stolen, which
+//! console listings are checked against before they go live. Listings are checked against a
+//! cached copy of the blacklist that is reloaded whenever an admin changes it.
+
+use super::catalog::OfferMetadata;
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// Represents a serial number on the blacklist.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlacklistedSerial {
+    /// The entry's ID.
+    pub id: Thing,
+    /// The normalized serial number.
+    pub serial: String,
+    /// Where the report came from (e.g. a police case reference).
+    pub note: String,
+    /// The ID of the admin who added the entry.
+    pub added_by: String,
+    /// The timestamp when the entry was added.
+    pub created_at: String,
+}
+
+/// Normalizes a serial number so that differently formatted spellings of the same serial match.
+///
+/// Keeps only letters and digits and converts letters to uppercase, e.g. `"cuh-7016 b"` becomes
+/// `"CUH7016B"`.
+pub fn normalize_serial(serial: &str) -> String {
+    serial
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Defines the `serial_blacklist` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE serial_blacklist SCHEMALESS;",
+        "serial_blacklist table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX serial_blacklist_serial ON serial_blacklist FIELDS serial UNIQUE",
+        "serial_blacklist_serial index on serial_blacklist",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE FIELD created_at ON serial_blacklist TYPE datetime;",
+        "created_at field on serial_blacklist",
+    )
+    .await;
+}
+
+impl Database {
+    /// Adds a serial number to the blacklist.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - The serial number (normalized before storing).
+    /// * `note` - Where the report came from.
+    /// * `admin_id` - The ID of the admin adding the entry.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `BlacklistedSerial` or a `CustomError` if creation fails
+    /// (e.g. because the serial is already blacklisted).
+    pub async fn add_blacklisted_serial(
+        &self,
+        serial: String,
+        note: String,
+        admin_id: String,
+    ) -> Result<BlacklistedSerial, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let serial = normalize_serial(&serial);
+        tracing::info!("Adding serial {} to the blacklist", serial);
+        let sql = "CREATE serial_blacklist SET serial = $serial, note = $note, added_by = $admin_id, created_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("serial".into(), Value::from(serial.as_str()));
+        vars.insert("note".into(), Value::from(note.as_str()));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<BlacklistedSerial> = response.take(0)?;
+        self.serial_blacklist.invalidate();
+
+        created.ok_or_else(|| {
+            tracing::error!("Failed to retrieve blacklisted serial after insertion.");
+            CustomError::DatabaseError("Failed to retrieve blacklisted serial".to_string())
+        })
+    }
+
+    /// Removes a serial number from the blacklist.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - The serial number (normalized before matching).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the serial was on the blacklist.
+    pub async fn remove_blacklisted_serial(&self, serial: String) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let serial = normalize_serial(&serial);
+        tracing::info!("Removing serial {} from the blacklist", serial);
+        let sql = "DELETE serial_blacklist WHERE serial = $serial RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("serial".into(), Value::from(serial.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let removed: Vec<BlacklistedSerial> = response.take(0)?;
+        if !removed.is_empty() {
+            self.serial_blacklist.invalidate();
+        }
+        Ok(!removed.is_empty())
+    }
+
+    /// Retrieves all blacklisted serial numbers, newest first.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `BlacklistedSerial` structs or a `CustomError` if retrieval fails.
+    pub async fn get_blacklisted_serials(&self) -> Result<Vec<BlacklistedSerial>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM serial_blacklist ORDER BY created_at DESC;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let serials: Vec<BlacklistedSerial> = response.take(0)?;
+        Ok(serials)
+    }
+
+    /// Checks whether a serial number is on the blacklist.
+    ///
+    /// The blacklist is loaded into the cache on the first check after it changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - The serial number (normalized before matching).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the serial is blacklisted.
+    pub async fn is_serial_blacklisted(&self, serial: &str) -> Result<bool, CustomError> {
+        let serials = match self.serial_blacklist.get() {
+            Some(serials) => serials,
+            None => {
+                let version = self.serial_blacklist.version();
+                self.use_offer_namespace().await?; // Switch to offer namespace
+                let sql = "SELECT VALUE serial FROM serial_blacklist;";
+                let mut response: surrealdb::Response = self.db.query(sql).await?;
+                let serials: Vec<String> = response.take(0)?;
+                tracing::info!("Loaded {} blacklisted serials", serials.len());
+                self.serial_blacklist
+                    .fill(version, serials.into_iter().collect::<HashSet<String>>())
+            }
+        };
+        Ok(serials.contains(&normalize_serial(serial)))
+    }
+
+    /// Returns the serial number of a listing if it is on the blacklist.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - The catalog metadata of the listing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the blacklisted serial number, or `None` if the listing has no serial
+    /// number or it is not blacklisted.
+    pub(super) async fn blacklisted_serial(
+        &self,
+        metadata: &OfferMetadata,
+    ) -> Result<Option<String>, CustomError> {
+        let Some(serial) = metadata
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.serial_number())
+        else {
+            return Ok(None);
+        };
+        if self.is_serial_blacklisted(serial).await? {
+            Ok(Some(serial.to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Files a report for moderators against an offer whose serial number is blacklisted.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The flagged offer.
+    /// * `serial` - The blacklisted serial number.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or a `CustomError` if the report could not be filed.
+    pub(super) async fn report_blacklisted_serial(
+        &self,
+        offer: &Offer,
+        serial: &str,
+    ) -> Result<(), CustomError> {
+        tracing::warn!(
+            "Offer {} lists blacklisted serial {}",
+            record_key(&offer.id),
+            normalize_serial(serial)
+        );
+        self.create_report(
+            record_key(&offer.id),
+            "system".to_string(),
+            "blacklisted_serial".to_string(),
+            format!(
+                "Serial number {} is on the stolen-serial blacklist.",
+                normalize_serial(serial)
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! src/database/sitemap.rs
+//!
+//! This module selects the offers and sellers listed in the sitemaps. Only what anonymous visitors
+//! can see is listed: active offers that aren't rated for adults, and the sellers of such offers.
+
+use super::{Count, Database, listed_offer_conditions, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::sitemap::{SITEMAP_PAGE_SIZE, SitemapKind};
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use surrealdb::sql::{Thing, Value};
+
+/// An offer or seller listed in a sitemap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitemapRecord {
+    /// The slug of the offer, or the ID of offers without a slug and of sellers.
+    pub id: String,
+    /// The timestamp when the offer was listed, or the newest offer of the seller was.
+    pub last_modified: String,
+}
+
+/// A row of the sitemap queries, before the ID is reduced to its key.
+#[derive(Debug, Deserialize)]
+struct SitemapRow {
+    #[serde(alias = "seller_id")]
+    id: Thing,
+    #[serde(default)]
+    slug: Option<String>,
+    last_modified: String,
+}
+
+impl Database {
+    /// Counts the offers and sellers listed in the sitemaps.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of offers and the number of sellers, or a `CustomError`
+    /// if a count fails.
+    pub async fn count_sitemap_records(&self) -> Result<(u64, u64), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let conditions = listed_offer_conditions(false, &mut vars).join(" AND ");
+        let sql = format!(
+            "SELECT count() FROM offers WHERE {conditions} GROUP ALL; SELECT count() FROM (SELECT seller_id FROM offers WHERE {conditions} GROUP BY seller_id) GROUP ALL;"
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Option<Count> = response.take(0)?;
+        let sellers: Option<Count> = response.take(1)?;
+        Ok((
+            offers.map_or(0, |count| count.count),
+            sellers.map_or(0, |count| count.count),
+        ))
+    }
+
+    /// Retrieves one sitemap's worth of offers or sellers, ordered by ID so pages stay stable.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Whether offers or sellers are listed.
+    /// * `page` - The 1-based number of the sitemap, of `SITEMAP_PAGE_SIZE` records each.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the records or a `CustomError` if retrieval fails.
+    pub async fn get_sitemap_records(
+        &self,
+        kind: SitemapKind,
+        page: u32,
+    ) -> Result<Vec<SitemapRecord>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let conditions = listed_offer_conditions(false, &mut vars).join(" AND ");
+        let sql = match kind {
+            SitemapKind::Offers => format!(
+                "SELECT id, slug, created_at AS last_modified FROM offers WHERE {conditions} ORDER BY id LIMIT $limit START $start;"
+            ),
+            SitemapKind::Sellers => format!(
+                "SELECT seller_id, time::max(created_at) AS last_modified FROM offers WHERE {conditions} GROUP BY seller_id ORDER BY seller_id LIMIT $limit START $start;"
+            ),
+        };
+        vars.insert("limit".into(), Value::from(SITEMAP_PAGE_SIZE));
+        vars.insert(
+            "start".into(),
+            Value::from(u64::from(page.max(1) - 1) * u64::from(SITEMAP_PAGE_SIZE)),
+        );
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let rows: Vec<SitemapRow> = response.take(0)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| SitemapRecord {
+                id: row.slug.unwrap_or_else(|| record_key(&row.id)),
+                last_modified: row.last_modified,
+            })
+            .collect())
+    }
+}
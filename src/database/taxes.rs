@@ -0,0 +1,266 @@
+//! src/database/taxes.rs
+//!
+//! This module handles the VAT charged on sales. Admins maintain a VAT rate per country, and the
+//! checkout computes the tax of an order from the rate of the buyer's country and the seller's
+//! type: business sellers charge VAT, private sellers don't. Listed prices include the VAT, so the
+//! tax is taken out of the price rather than added to it. The breakdown is stored on the order, so
+//! later rate changes don't change the order history.
+
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// Whether a seller sells privately or as a business.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SellerType {
+    /// A private person. Private sales are not subject to VAT.
+    #[default]
+    Private,
+    /// A registered business charging VAT on its sales.
+    Business,
+}
+
+impl SellerType {
+    /// Returns the string stored in the database for this seller type.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SellerType::Private => "private",
+            SellerType::Business => "business",
+        }
+    }
+}
+
+/// The VAT rate of a country.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaxRate {
+    /// The rate's ID, the country code.
+    pub id: Thing,
+    /// The ISO 3166-1 alpha-2 code of the country.
+    pub country: String,
+    /// The VAT rate in percent.
+    pub rate_percent: f64,
+    /// The ID of the admin who last set the rate.
+    pub updated_by: String,
+    /// The timestamp of the last change.
+    pub updated_at: String,
+}
+
+/// The tax of an order, as computed at checkout.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TaxBreakdown {
+    /// The ISO 3166-1 alpha-2 code of the country the tax was computed for.
+    pub country: String,
+    /// The type of the seller at checkout.
+    pub seller_type: SellerType,
+    /// The applied VAT rate in percent, 0 if no VAT is charged.
+    pub rate_percent: f64,
+    /// The price without VAT.
+    pub net: f64,
+    /// The VAT contained in the price.
+    pub tax: f64,
+    /// The price including VAT, i.e. the price the buyer pays.
+    pub gross: f64,
+}
+
+impl TaxBreakdown {
+    /// Computes the tax contained in a price.
+    ///
+    /// Private sales and countries without a configured rate are free of VAT. The tax is rounded
+    /// to cents and the net price is whatever remains of the price.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` - The price including VAT.
+    /// * `country` - The country code of the buyer.
+    /// * `seller_type` - The type of the seller.
+    /// * `rate_percent` - The VAT rate of the country, or `None` if none is configured.
+    ///
+    /// # Returns
+    ///
+    /// The `TaxBreakdown` of the sale.
+    pub fn for_price(
+        price: f64,
+        country: &str,
+        seller_type: SellerType,
+        rate_percent: Option<f64>,
+    ) -> TaxBreakdown {
+        let rate_percent = match seller_type {
+            SellerType::Business => rate_percent.unwrap_or(0.0),
+            SellerType::Private => 0.0,
+        };
+        let tax = ((price * rate_percent / (100.0 + rate_percent)) * 100.0).round() / 100.0;
+        TaxBreakdown {
+            country: country.to_string(),
+            seller_type,
+            rate_percent,
+            net: ((price - tax) * 100.0).round() / 100.0,
+            tax,
+            gross: price,
+        }
+    }
+
+    /// Converts the breakdown to the object stored in the database.
+    fn to_value(&self) -> Value {
+        let mut object: BTreeMap<String, Value> = BTreeMap::new();
+        object.insert("country".into(), Value::from(self.country.as_str()));
+        object.insert("seller_type".into(), Value::from(self.seller_type.as_str()));
+        object.insert("rate_percent".into(), Value::from(self.rate_percent));
+        object.insert("net".into(), Value::from(self.net));
+        object.insert("tax".into(), Value::from(self.tax));
+        object.insert("gross".into(), Value::from(self.gross));
+        Value::from(object)
+    }
+}
+
+/// Normalizes a country code entered by a user or admin.
+///
+/// # Arguments
+///
+/// * `country` - The entered code, e.g. `de`.
+///
+/// # Returns
+///
+/// The upper-case ISO 3166-1 alpha-2 code, or `None` if the input is not two letters.
+pub fn normalize_country(country: &str) -> Option<String> {
+    let country = country.trim();
+    (country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()))
+        .then(|| country.to_ascii_uppercase())
+}
+
+/// Binds a tax breakdown to the `tax` query variable.
+pub(super) fn bind_tax(vars: &mut BTreeMap<String, Value>, tax: Option<&TaxBreakdown>) {
+    vars.insert(
+        "tax".into(),
+        tax.map_or(Value::None, TaxBreakdown::to_value),
+    );
+}
+
+/// Defines the `tax_rates` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(db, "DEFINE TABLE tax_rates SCHEMALESS;", "tax_rates table").await;
+    define(
+        db,
+        "DEFINE FIELD updated_at ON tax_rates TYPE datetime;",
+        "updated_at field on tax_rates",
+    )
+    .await;
+}
+
+impl Database {
+    /// Sets the VAT rate of a country, replacing its previous rate.
+    ///
+    /// Orders placed before the change keep the tax they were placed with.
+    ///
+    /// # Arguments
+    ///
+    /// * `country` - The normalized country code.
+    /// * `rate_percent` - The VAT rate in percent.
+    /// * `admin_id` - The ID of the admin setting the rate.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the stored `TaxRate` or a `CustomError` if storing fails.
+    pub async fn set_tax_rate(
+        &self,
+        country: &str,
+        rate_percent: f64,
+        admin_id: String,
+    ) -> Result<TaxRate, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Setting VAT rate of {} to {}%", country, rate_percent);
+        let sql = "UPSERT type::thing('tax_rates', $country) SET country = $country, rate_percent = $rate_percent, updated_by = $admin_id, updated_at = time::now();";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("country".into(), Value::from(country));
+        vars.insert("rate_percent".into(), Value::from(rate_percent));
+        vars.insert("admin_id".into(), Value::from(admin_id.as_str()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let stored: Option<TaxRate> = response.take(0)?;
+
+        stored.ok_or_else(|| {
+            tracing::error!("Failed to retrieve tax rate after storing it.");
+            CustomError::DatabaseError("Failed to retrieve stored tax rate".to_string())
+        })
+    }
+
+    /// Deletes the VAT rate of a country. Sales to the country are free of VAT afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `country` - The normalized country code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the country had a rate.
+    pub async fn delete_tax_rate(&self, country: &str) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Deleting VAT rate of {}", country);
+        let sql = "DELETE type::thing('tax_rates', $country) RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("country".into(), Value::from(country));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Option<TaxRate> = response.take(0)?;
+        Ok(deleted.is_some())
+    }
+
+    /// Retrieves the VAT rates of all countries, ordered by country code.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `TaxRate` structs or a `CustomError` if retrieval fails.
+    pub async fn get_tax_rates(&self) -> Result<Vec<TaxRate>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM tax_rates ORDER BY country ASC;";
+
+        let mut response: surrealdb::Response = self.db.query(sql).await?;
+        let rates: Vec<TaxRate> = response.take(0)?;
+        Ok(rates)
+    }
+
+    /// Computes the VAT of selling an offer at the given price to a buyer in the given country.
+    ///
+    /// The seller's type is read from their account now, and the rate is the one configured for
+    /// the country now.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The sold offer.
+    /// * `price` - The price the offer is sold at.
+    /// * `country` - The normalized country code of the buyer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TaxBreakdown` or a `CustomError` if the seller or the rate
+    /// can't be read.
+    pub async fn quote_tax(
+        &self,
+        offer: &Offer,
+        price: f64,
+        country: &str,
+    ) -> Result<TaxBreakdown, CustomError> {
+        let seller_type = self
+            .get_user_by_id(record_key(&offer.seller_id))
+            .await?
+            .map_or(SellerType::default(), |seller| seller.seller_type);
+
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT VALUE rate_percent FROM type::thing('tax_rates', $country);";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("country".into(), Value::from(country));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let rate: Option<f64> = response.take(0)?;
+        Ok(TaxBreakdown::for_price(price, country, seller_type, rate))
+    }
+}
@@ -0,0 +1,178 @@
+//! src/database/trending.rs
+//!
+//! This module ranks offers by how popular they were recently. Every view and favorite of the
+//! last `TRENDING_WINDOW_DAYS` days adds to an offer's score, weighted down by its age with a
+//! half-life of `TRENDING_HALF_LIFE_DAYS` days, so offers that are popular right now rank above
+//! offers that were popular last week. A background job stores the score on the offers as
+//! `trending_score`; offers without recent activity have none.
+
+use super::pagination::{PageInfo, Pagination};
+use super::{Count, Database, Offer, define, listed_offer_conditions};
+use crate::errors::custom_errors::CustomError;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+
+/// The number of days of views and favorites counted towards the score.
+pub const TRENDING_WINDOW_DAYS: i64 = 7;
+/// The number of days after which a view or favorite counts half.
+pub const TRENDING_HALF_LIFE_DAYS: f64 = 2.0;
+/// How many views a favorite is worth.
+pub const FAVORITE_WEIGHT: f64 = 5.0;
+
+/// Defines the index the trending offers are ordered by.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE INDEX offers_trending_score ON offers FIELDS trending_score",
+        "offers_trending_score index on offers",
+    )
+    .await;
+}
+
+/// The number of viewers of an offer on one day.
+#[derive(Debug, Deserialize)]
+struct ViewActivity {
+    offer: Thing,
+    day: String,
+    views: u64,
+}
+
+/// A favorite of an offer.
+#[derive(Debug, Deserialize)]
+struct FavoriteActivity {
+    offer: Thing,
+    created_at: String,
+}
+
+/// Returns the weight of activity of the given age.
+///
+/// # Arguments
+///
+/// * `age_days` - The age of the view or favorite in days.
+///
+/// # Returns
+///
+/// 1 for activity happening now, halving every `TRENDING_HALF_LIFE_DAYS` days.
+pub fn trending_weight(age_days: f64) -> f64 {
+    0.5_f64.powf(age_days.max(0.0) / TRENDING_HALF_LIFE_DAYS)
+}
+
+/// Returns the age of a day's views in days, counted from the middle of the day.
+fn day_age(day: &str, now: DateTime<Utc>) -> Option<f64> {
+    let noon = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .ok()?
+        .and_hms_opt(12, 0, 0)?
+        .and_utc();
+    Some((now - noon).num_minutes() as f64 / (24.0 * 60.0))
+}
+
+/// Returns the age of a timestamp in days.
+fn timestamp_age(timestamp: &str, now: DateTime<Utc>) -> Option<f64> {
+    let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some((now - timestamp.with_timezone(&Utc)).num_minutes() as f64 / (24.0 * 60.0))
+}
+
+impl Database {
+    /// Recomputes the trending score of every offer from its recent views and favorites.
+    ///
+    /// Scores of offers without activity in the last `TRENDING_WINDOW_DAYS` days are removed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of offers with a score, or a `CustomError` if the
+    /// activity can't be read or the scores can't be stored.
+    pub async fn refresh_trending_scores(&self) -> Result<usize, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let now = Utc::now();
+        let since = now - Duration::days(TRENDING_WINDOW_DAYS);
+        let sql = "SELECT offer, day, count() AS views FROM offer_views WHERE day >= $since_day GROUP BY offer, day; SELECT out AS offer, created_at FROM favorites WHERE created_at >= <datetime> $since;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert(
+            "since_day".into(),
+            Value::from(since.format("%Y-%m-%d").to_string()),
+        );
+        vars.insert("since".into(), Value::from(since.to_rfc3339()));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let views: Vec<ViewActivity> = response.take(0)?;
+        let favorites: Vec<FavoriteActivity> = response.take(1)?;
+
+        let mut scores: HashMap<Thing, f64> = HashMap::new();
+        for activity in views {
+            if let Some(age) = day_age(&activity.day, now) {
+                *scores.entry(activity.offer).or_default() +=
+                    activity.views as f64 * trending_weight(age);
+            }
+        }
+        for activity in favorites {
+            if let Some(age) = timestamp_age(&activity.created_at, now) {
+                *scores.entry(activity.offer).or_default() +=
+                    FAVORITE_WEIGHT * trending_weight(age);
+            }
+        }
+
+        let entries: Vec<Value> = scores
+            .iter()
+            .map(|(offer, score)| {
+                let mut entry: BTreeMap<String, Value> = BTreeMap::new();
+                entry.insert("offer".into(), Value::from(offer.clone()));
+                entry.insert(
+                    "score".into(),
+                    Value::from((score * 1000.0).round() / 1000.0),
+                );
+                Value::from(entry)
+            })
+            .collect();
+        let sql = "UPDATE offers SET trending_score = NONE WHERE trending_score != NONE;
+            FOR $entry IN $scores {
+                UPDATE $entry.offer SET trending_score = $entry.score;
+            };";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("scores".into(), Value::from(entries));
+        self.use_offer_namespace().await?; // Switch back in case another request switched meanwhile
+        self.db.query(sql).bind(vars).await?.check()?;
+        Ok(scores.len())
+    }
+
+    /// Retrieves the publicly listed offers with the highest trending score.
+    ///
+    /// # Arguments
+    ///
+    /// * `include_mature` - Whether mature-rated offers are included (only for adult viewers).
+    /// * `pagination` - The page to return.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the requested page of offers, most popular first, and its
+    /// pagination details, or a `CustomError` if retrieval fails.
+    pub async fn query_trending_offers(
+        &self,
+        include_mature: bool,
+        pagination: &Pagination,
+    ) -> Result<(Vec<Offer>, PageInfo), CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        let mut conditions = listed_offer_conditions(include_mature, &mut vars);
+        conditions.push("trending_score > 0".to_string());
+        pagination.bind(&mut vars);
+
+        let sql = format!(
+            "SELECT * FROM offers WHERE {conditions} ORDER BY trending_score DESC, created_at DESC LIMIT $limit START $start; SELECT count() FROM offers WHERE {conditions} GROUP ALL;",
+            conditions = conditions.join(" AND ")
+        );
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let offers: Vec<Offer> = response.take(0)?;
+        let total: Option<Count> = response.take(1)?;
+        let total = total.map_or(0, |total| total.count);
+        Ok((offers, PageInfo::new(pagination, total)))
+    }
+}
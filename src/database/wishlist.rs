@@ -0,0 +1,228 @@
+//! src/database/wishlist.rs
+//!
+//! This module handles users' wishlists: the games a user wants to buy, optionally on a given
+//! platform and up to a price. Unlike favorites, which track offers that already exist, wishlist
+//! items are matched against offers as they are listed. Unlike saved searches, which match any
+//! title containing their text, a wishlist item names one game and matches its whole title,
+//! ignoring case and spacing.
+
+use super::platforms::Platform;
+use super::{Database, Offer, define, record_key};
+use crate::errors::custom_errors::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use surrealdb::{
+    Surreal,
+    engine::local::Db,
+    sql::{Thing, Value},
+};
+use validator_derive::Validate;
+
+/// The maximum number of games a user can have on their wishlist.
+pub const MAX_WISHLIST_ITEMS: usize = 50;
+
+/// A game a user wants to buy.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Validate)]
+pub struct WishlistEntry {
+    /// The title of the game.
+    #[validate(length(min = 1, max = 200, message = "Title must be 1 to 200 characters long"))]
+    pub title: String,
+    /// The platform the game must be for, or any platform if `None`.
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    /// The highest price the user wants to pay.
+    #[serde(default)]
+    #[validate(range(min = 0.0, message = "Maximum price must not be negative"))]
+    pub max_price: Option<f64>,
+}
+
+/// Represents a game on a user's wishlist.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WishlistItem {
+    /// The wishlist item's ID.
+    pub id: Thing,
+    /// The ID of the user.
+    pub user_id: String,
+    /// The wanted game.
+    #[serde(flatten)]
+    pub entry: WishlistEntry,
+    /// The timestamp when the game was added.
+    pub created_at: String,
+}
+
+/// A user whose wishlist matched an offer, as selected by `get_wishlist_matches`.
+#[derive(Debug, Deserialize)]
+struct WishlistMatch {
+    user_id: String,
+}
+
+/// Returns the key game titles are matched by: lowercased, with runs of whitespace collapsed.
+///
+/// # Arguments
+///
+/// * `title` - The title of a game.
+///
+/// # Returns
+///
+/// The key, e.g. `super mario 64` for ` Super  Mario 64`.
+pub fn title_key(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Defines the `wishlist_items` table.
+///
+/// Must be called while the offer namespace is selected.
+pub(super) async fn define_schema(db: &Surreal<Db>) {
+    define(
+        db,
+        "DEFINE TABLE wishlist_items SCHEMALESS;",
+        "wishlist_items table",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX wishlist_items_user_id ON wishlist_items FIELDS user_id",
+        "wishlist_items_user_id index on wishlist_items",
+    )
+    .await;
+    define(
+        db,
+        "DEFINE INDEX wishlist_items_title_key ON wishlist_items FIELDS title_key",
+        "wishlist_items_title_key index on wishlist_items",
+    )
+    .await;
+}
+
+impl Database {
+    /// Adds a game to a user's wishlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `entry` - The validated game.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `WishlistItem`, or `None` if the user already wishes for the
+    /// game on the same platform.
+    pub async fn create_wishlist_item(
+        &self,
+        user_id: &str,
+        entry: &WishlistEntry,
+    ) -> Result<Option<WishlistItem>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Adding a game to the wishlist of user {}", user_id);
+        let sql = "IF (SELECT * FROM wishlist_items WHERE user_id = $user_id AND title_key = $title_key AND platform = $platform) = [] THEN (CREATE wishlist_items SET user_id = $user_id, title = $title, title_key = $title_key, platform = $platform, max_price = $max_price, created_at = time::now()) END;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+        vars.insert("title".into(), Value::from(entry.title.trim()));
+        vars.insert("title_key".into(), Value::from(title_key(&entry.title)));
+        vars.insert(
+            "platform".into(),
+            Value::from(entry.platform.map(|platform| platform.as_str())),
+        );
+        vars.insert("max_price".into(), Value::from(entry.max_price));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let created: Option<WishlistItem> = response.take(0)?;
+        Ok(created)
+    }
+
+    /// Retrieves a user's wishlist, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the wishlist items or a `CustomError` if retrieval fails.
+    pub async fn get_wishlist(&self, user_id: &str) -> Result<Vec<WishlistItem>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let sql = "SELECT * FROM wishlist_items WHERE user_id = $user_id ORDER BY created_at ASC;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let items: Vec<WishlistItem> = response.take(0)?;
+        Ok(items)
+    }
+
+    /// Removes a game from a user's wishlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user.
+    /// * `item_id` - The ID of the wishlist item.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the item was removed, or `false` if the user has no such
+    /// item.
+    pub async fn delete_wishlist_item(
+        &self,
+        user_id: &str,
+        item_id: &str,
+    ) -> Result<bool, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        tracing::info!("Removing wishlist item {} of user {}", item_id, user_id);
+        let sql = "DELETE type::thing('wishlist_items', $item_id) WHERE user_id = $user_id RETURN BEFORE;";
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        vars.insert("item_id".into(), Value::from(item_id));
+        vars.insert("user_id".into(), Value::from(user_id));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let deleted: Vec<WishlistItem> = response.take(0)?;
+        Ok(!deleted.is_empty())
+    }
+
+    /// Finds the users with a wishlist item matching a newly listed offer.
+    ///
+    /// The seller's own wishlist is left out. Bundles match the wishlists of any of their games.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The listed offer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the IDs of the users, each once, or a `CustomError` if retrieval
+    /// fails.
+    pub async fn get_wishlist_matches(&self, offer: &Offer) -> Result<Vec<String>, CustomError> {
+        self.use_offer_namespace().await?; // Switch to offer namespace
+        let mut conditions = Vec::new();
+        let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+        // Every game of the offer is matched with its own platform
+        let games = std::iter::once((&offer.game_title, offer.platform)).chain(
+            offer
+                .bundle_items
+                .iter()
+                .map(|item| (&item.game_title, item.platform)),
+        );
+        for (index, (title, platform)) in games.enumerate() {
+            conditions.push(format!(
+                "(title_key = $title_{index} AND (platform = NONE OR platform = $platform_{index}))"
+            ));
+            vars.insert(format!("title_{index}"), Value::from(title_key(title)));
+            vars.insert(format!("platform_{index}"), Value::from(platform.as_str()));
+        }
+        let sql = format!(
+            "SELECT user_id FROM wishlist_items WHERE user_id != $seller_id AND ({}) AND (max_price = NONE OR max_price >= $price) GROUP BY user_id;",
+            conditions.join(" OR ")
+        );
+        vars.insert(
+            "seller_id".into(),
+            Value::from(record_key(&offer.seller_id).as_str()),
+        );
+        vars.insert("price".into(), Value::from(offer.price));
+
+        let mut response: surrealdb::Response = self.db.query(sql).bind(vars).await?;
+        let matches: Vec<WishlistMatch> = response.take(0)?;
+        Ok(matches.into_iter().map(|m| m.user_id).collect())
+    }
+}
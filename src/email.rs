@@ -0,0 +1,263 @@
+//! src/email.rs
+//!
+//! This module sends emails. The `EmailSender` trait is implemented by the `SmtpEmailSender`,
+//! configured with the `SMTP_*` environment variables, and by the `NoopEmailSender`, which drops
+//! the messages and is used in tests and when SMTP isn't configured.
+//!
+//! Messages are rendered from `EmailTemplate`s as plain text.
+
+use crate::database::preferences::Currency;
+use crate::errors::custom_errors::CustomError;
+#[cfg(feature = "fault-injection")]
+use crate::fault_injection::{Dependency, inject_dependency_fault};
+
+use dotenvy::var;
+use futures::future::BoxFuture;
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::sync::{Arc, Mutex};
+
+/// The port used if `SMTP_PORT` is not set (SMTP submission with STARTTLS).
+const DEFAULT_SMTP_PORT: u16 = 587;
+
+/// A rendered email.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    /// The recipient's address.
+    pub to: String,
+    /// The subject line.
+    pub subject: String,
+    /// The plain-text body.
+    pub body: String,
+}
+
+/// The emails the shop sends.
+#[derive(Debug, Clone)]
+pub enum EmailTemplate {
+    /// Confirms an order to its buyer.
+    OrderConfirmation {
+        /// The ID of the order.
+        order_id: String,
+        /// The title of the ordered game.
+        game_title: String,
+        /// The price the buyer pays.
+        price: f64,
+        /// The currency of the price.
+        currency: Currency,
+        /// The VAT contained in the price, or `None` if no VAT is charged.
+        tax: Option<f64>,
+    },
+    /// Forwards an in-app notification to users who enabled email notifications.
+    Notification {
+        /// The notification's title.
+        title: String,
+        /// The notification's body.
+        body: String,
+    },
+}
+
+impl EmailTemplate {
+    /// Renders the template.
+    ///
+    /// # Arguments
+    ///
+    /// * `to` - The recipient's address.
+    /// * `username` - The recipient's username, used in the greeting.
+    ///
+    /// # Returns
+    ///
+    /// The `EmailMessage` to send.
+    pub fn render(&self, to: String, username: &str) -> EmailMessage {
+        let (subject, body) = match self {
+            EmailTemplate::OrderConfirmation {
+                order_id,
+                game_title,
+                price,
+                currency,
+                tax,
+            } => (
+                format!("Your order of \"{}\"", game_title),
+                format!(
+                    "Hello {},\n\nthank you for your order of \"{}\" for {:.2} {}{}.\n\nYour order number is {}. You can follow it under \"My orders\". The seller ships the game once they have received the payment.\n\nYour gameshop team",
+                    username,
+                    game_title,
+                    price,
+                    currency.as_str(),
+                    tax.filter(|tax| *tax > 0.0)
+                        .map_or(String::new(), |tax| format!(
+                            " (including {:.2} {} VAT)",
+                            tax,
+                            currency.as_str()
+                        )),
+                    order_id
+                ),
+            ),
+            EmailTemplate::Notification { title, body } => (
+                title.clone(),
+                format!(
+                    "Hello {},\n\n{}\n\nYour gameshop team\n\nYou receive this email because you enabled email notifications. You can turn them off in your preferences.",
+                    username, body
+                ),
+            ),
+        };
+        EmailMessage { to, subject, body }
+    }
+}
+
+/// Sends emails.
+pub trait EmailSender: Send + Sync {
+    /// Sends a message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is empty on success, or an `EmailError` if the message can't be sent.
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, Result<(), CustomError>>;
+
+    /// Checks that messages can be sent right now, e.g. for the health endpoint.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is empty if the sender works, or an `EmailError` if it can't send.
+    fn check(&self) -> BoxFuture<'_, Result<(), CustomError>>;
+}
+
+/// Sends emails through an SMTP server.
+pub struct SmtpEmailSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpEmailSender {
+    /// Creates a sender from the environment.
+    ///
+    /// Reads `SMTP_HOST`, `SMTP_PORT` (defaults to 587), `SMTP_USERNAME`, `SMTP_PASSWORD` and
+    /// `EMAIL_FROM` (e.g. `gameshop <noreply@example.com>`). Connections use STARTTLS.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the sender, or an `EnvironmentVariableError` if a variable is
+    /// missing or invalid.
+    pub fn from_env() -> Result<Self, CustomError> {
+        let host = var("SMTP_HOST")?;
+        let port = match var("SMTP_PORT") {
+            Ok(port) => port.trim().parse().map_err(|_| {
+                CustomError::EnvironmentVariableError("SMTP_PORT must be a port number".to_string())
+            })?,
+            Err(_) => DEFAULT_SMTP_PORT,
+        };
+        let credentials = Credentials::new(var("SMTP_USERNAME")?, var("SMTP_PASSWORD")?);
+        let from = var("EMAIL_FROM")?.parse().map_err(|_| {
+            CustomError::EnvironmentVariableError("EMAIL_FROM must be an email address".to_string())
+        })?;
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+            .map_err(|e| CustomError::EnvironmentVariableError(format!("SMTP_HOST: {}", e)))?
+            .port(port)
+            .credentials(credentials)
+            .build();
+        Ok(SmtpEmailSender { transport, from })
+    }
+}
+
+impl EmailSender for SmtpEmailSender {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, Result<(), CustomError>> {
+        Box::pin(async move {
+            #[cfg(feature = "fault-injection")]
+            inject_dependency_fault(Dependency::Mailer).await?;
+            let to: Mailbox = message
+                .to
+                .parse()
+                .map_err(|_| CustomError::EmailError("Invalid recipient address".to_string()))?;
+            let email = lettre::Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(message.subject.as_str())
+                .header(ContentType::TEXT_PLAIN)
+                .body(message.body.clone())
+                .map_err(|e| CustomError::EmailError(e.to_string()))?;
+            self.transport
+                .send(email)
+                .await
+                .map_err(|e| CustomError::EmailError(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<(), CustomError>> {
+        Box::pin(async move {
+            #[cfg(feature = "fault-injection")]
+            inject_dependency_fault(Dependency::Mailer).await?;
+            match self.transport.test_connection().await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(CustomError::EmailError(
+                    "The SMTP server did not accept a connection".to_string(),
+                )),
+                Err(e) => Err(CustomError::EmailError(e.to_string())),
+            }
+        })
+    }
+}
+
+/// Drops emails instead of sending them, optionally recording them for tests.
+#[derive(Debug, Default)]
+pub struct NoopEmailSender {
+    sent: Option<Mutex<Vec<EmailMessage>>>,
+}
+
+impl NoopEmailSender {
+    /// Creates a sender that records the dropped messages, see `sent`.
+    pub fn recording() -> Self {
+        NoopEmailSender {
+            sent: Some(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns the messages dropped so far, if the sender records them.
+    pub fn sent(&self) -> Vec<EmailMessage> {
+        self.sent.as_ref().map_or_else(Vec::new, |sent| {
+            sent.lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone()
+        })
+    }
+}
+
+impl EmailSender for NoopEmailSender {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, Result<(), CustomError>> {
+        tracing::debug!("Not sending email \"{}\"", message.subject);
+        if let Some(sent) = &self.sent {
+            sent.lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .push(message.clone());
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn check(&self) -> BoxFuture<'_, Result<(), CustomError>> {
+        // Dropping emails always works
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Creates the email sender configured in the environment.
+///
+/// Uses SMTP if `SMTP_HOST` is set. Otherwise, or if the SMTP configuration is invalid, the error
+/// is logged and emails are dropped by a `NoopEmailSender`.
+pub fn email_sender_from_env() -> Arc<dyn EmailSender> {
+    if var("SMTP_HOST").is_err() {
+        tracing::warn!("SMTP_HOST is not set, emails will not be sent");
+        return Arc::new(NoopEmailSender::default());
+    }
+    match SmtpEmailSender::from_env() {
+        Ok(sender) => Arc::new(sender),
+        Err(e) => {
+            tracing::error!("Invalid SMTP configuration, emails will not be sent: {}", e);
+            Arc::new(NoopEmailSender::default())
+        }
+    }
+}
@@ -1,11 +1,12 @@
 //! src/encryption.rs
 //!
-//! This module provides encryption and decryption functionalities using the ChaCha20Poly1305 algorithm.
+//! This module provides encryption and decryption functionalities using the ChaCha20Poly1305 and
+//! XChaCha20Poly1305 algorithms.
 
 use base64::{Engine as base64Engine, engine::general_purpose};
 use chacha20poly1305::{
-    ChaCha20Poly1305, Key, Nonce,
-    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce, XChaCha20Poly1305, XNonce,
+    aead::{Aead, KeyInit, Payload},
 };
 use dotenvy::var;
 use rand::RngCore;
@@ -99,8 +100,35 @@ fn generate_nonce() -> Nonce {
     *Nonce::from_slice(&nonce)
 }
 
+/// The prefix of stored values encrypted with XChaCha20Poly1305.
+///
+/// Values without a prefix are version 1, encrypted with ChaCha20Poly1305. The prefix can't be
+/// confused with version 1 data, as `:` is not part of the base64 alphabet.
+const XCHACHA20_PREFIX: &str = "v2:";
+
+/// The prefix of stored values encrypted with XChaCha20Poly1305 and bound to their record and
+/// field through associated data (version 3, `v3:base64(nonce || ciphertext)`).
+pub const BOUND_PREFIX: &str = "v3:";
+
+/// The algorithm used to encrypt stored values, which determines the stored format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncryptionMode {
+    /// ChaCha20Poly1305 with 12-byte random nonces (version 1, `base64(nonce || ciphertext)`).
+    ///
+    /// Only kept to write data for older readers; random 12-byte nonces risk a collision once
+    /// billions of values are encrypted with the same key.
+    ChaCha20Poly1305,
+    /// XChaCha20Poly1305 with 24-byte random nonces (version 2, `v2:base64(nonce || ciphertext)`).
+    ///
+    /// The longer nonces make random collisions negligible, so this is the default.
+    #[default]
+    XChaCha20Poly1305,
+}
+
 /// Encrypts the given plaintext with a random nonce and returns a base64-encoded string.
 ///
+/// New data is encrypted with the default `EncryptionMode`.
+///
 /// # Arguments
 ///
 /// * `key_bytes` - The encryption key.
@@ -113,30 +141,79 @@ pub fn encrypt_with_random_nonce(
     key_bytes: &[u8; 32],
     plaintext: &str,
 ) -> Result<String, CustomError> {
-    let cipher = ChaCha20Poly1305::new(Key::from_slice(key_bytes));
+    encrypt_with_mode(key_bytes, plaintext, EncryptionMode::default())
+}
 
-    // Generate random nonce
-    let mut nonce_bytes = [0u8; 12];
-    // let mut rng = OsRng::new().expect("Failed to get OS random number generator");
-    rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
+/// Encrypts the given plaintext with a random nonce using the given algorithm.
+///
+/// # Arguments
+///
+/// * `key_bytes` - The encryption key.
+/// * `plaintext` - The plaintext to encrypt.
+/// * `mode` - The algorithm to encrypt with.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded string (see `EncryptionMode` for the formats) or an
+/// `EncryptionError` if an error occurs.
+pub fn encrypt_with_mode(
+    key_bytes: &[u8; 32],
+    plaintext: &str,
+    mode: EncryptionMode,
+) -> Result<String, CustomError> {
+    let key = Key::from_slice(key_bytes);
+    let mut combined = Vec::new();
+    match mode {
+        EncryptionMode::ChaCha20Poly1305 => {
+            // Generate random nonce
+            let mut nonce_bytes = [0u8; 12];
+            rng().fill_bytes(&mut nonce_bytes);
+            let ciphertext = ChaCha20Poly1305::new(key)
+                .encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_bytes())
+                .map_err(|_| CustomError::EncryptionError)?;
 
-    // Encrypt
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext.as_bytes())
-        .map_err(|_| CustomError::EncryptionError)?;
+            // Combine nonce + ciphertext
+            combined.extend_from_slice(&nonce_bytes);
+            combined.extend_from_slice(&ciphertext);
+        }
+        EncryptionMode::XChaCha20Poly1305 => {
+            let mut nonce_bytes = [0u8; 24];
+            rng().fill_bytes(&mut nonce_bytes);
+            let ciphertext = XChaCha20Poly1305::new(key)
+                .encrypt(XNonce::from_slice(&nonce_bytes), plaintext.as_bytes())
+                .map_err(|_| CustomError::EncryptionError)?;
 
-    // Combine nonce + ciphertext
-    let mut combined = Vec::new();
-    combined.extend_from_slice(&nonce_bytes);
-    combined.extend_from_slice(&ciphertext);
+            combined.extend_from_slice(&nonce_bytes);
+            combined.extend_from_slice(&ciphertext);
+        }
+    }
 
     // Encode combined data as Base64 for storage
-    Ok(general_purpose::STANDARD.encode(combined))
+    let encoded = general_purpose::STANDARD.encode(combined);
+    Ok(match mode {
+        EncryptionMode::ChaCha20Poly1305 => encoded,
+        EncryptionMode::XChaCha20Poly1305 => format!("{}{}", XCHACHA20_PREFIX, encoded),
+    })
+}
+
+/// Returns the algorithm a stored value was encrypted with.
+///
+/// # Arguments
+///
+/// * `encoded` - The stored value.
+pub fn encryption_mode_of(encoded: &str) -> EncryptionMode {
+    if encoded.starts_with(XCHACHA20_PREFIX) || encoded.starts_with(BOUND_PREFIX) {
+        EncryptionMode::XChaCha20Poly1305
+    } else {
+        EncryptionMode::ChaCha20Poly1305
+    }
 }
 
 /// Decrypts the given base64-encoded string with the given key.
 ///
+/// Both formats are accepted, so values encrypted before XChaCha20Poly1305 became the default
+/// stay readable. Values bound to associated data can only be decrypted with `decrypt_bound`.
+///
 /// # Arguments
 ///
 /// * `key_bytes` - The encryption key.
@@ -144,26 +221,170 @@ pub fn encrypt_with_random_nonce(
 ///
 /// # Returns
 ///
-/// A `Result` containing the decrypted string or an `EncryptionError` if an error occurs.
+/// A `Result` containing the decrypted string, `AssociatedDataRequired` for a bound value, or a
+/// `DecryptionError` if an error occurs.
 pub fn decrypt_with_nonce(
     key_bytes: &[u8; 32],
     combined_base64: &str,
 ) -> Result<String, CustomError> {
-    let cipher = ChaCha20Poly1305::new(Key::from_slice(key_bytes));
+    if is_bound(combined_base64) {
+        return Err(CustomError::AssociatedDataRequired);
+    }
+    let key = Key::from_slice(key_bytes);
+    let mode = encryption_mode_of(combined_base64);
+    let encoded = combined_base64
+        .strip_prefix(XCHACHA20_PREFIX)
+        .unwrap_or(combined_base64);
 
     // Decode from Base64
     let combined = general_purpose::STANDARD
-        .decode(combined_base64)
+        .decode(encoded)
         .map_err(|_| CustomError::DecryptionError)?;
 
-    // Split into nonce + ciphertext
-    let (nonce_bytes, ciphertext) = combined.split_at(12);
-    let nonce = Nonce::from_slice(nonce_bytes);
+    // Split into nonce + ciphertext and decrypt
+    let plaintext_bytes = match mode {
+        EncryptionMode::ChaCha20Poly1305 => {
+            if combined.len() < 12 {
+                return Err(CustomError::DecryptionError);
+            }
+            let (nonce_bytes, ciphertext) = combined.split_at(12);
+            ChaCha20Poly1305::new(key).decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+        }
+        EncryptionMode::XChaCha20Poly1305 => {
+            if combined.len() < 24 {
+                return Err(CustomError::DecryptionError);
+            }
+            let (nonce_bytes, ciphertext) = combined.split_at(24);
+            XChaCha20Poly1305::new(key).decrypt(XNonce::from_slice(nonce_bytes), ciphertext)
+        }
+    }
+    .map_err(|_| CustomError::DecryptionError)?;
 
-    // Decrypt
-    let plaintext_bytes = cipher
-        .decrypt(nonce, ciphertext)
+    String::from_utf8(plaintext_bytes).map_err(|_| CustomError::DecryptionError)
+}
+
+/// Returns the associated data binding an encrypted field to its record.
+///
+/// # Arguments
+///
+/// * `record_id` - The ID of the record the value belongs to, e.g. the user ID.
+/// * `field` - The name of the field the value is stored in.
+pub fn field_aad(record_id: &str, field: &str) -> Vec<u8> {
+    format!("{}:{}", record_id, field).into_bytes()
+}
+
+/// Encrypts the given plaintext bound to associated data, e.g. the record and field it is stored
+/// in (see `field_aad`).
+///
+/// The associated data isn't stored, but decryption fails unless the same data is given, so a
+/// ciphertext copied into another record or field is detected.
+///
+/// # Arguments
+///
+/// * `key_bytes` - The encryption key.
+/// * `plaintext` - The plaintext to encrypt.
+/// * `aad` - The associated data to bind the ciphertext to.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded string or an `EncryptionError` if an error occurs.
+pub fn encrypt_bound(
+    key_bytes: &[u8; 32],
+    plaintext: &str,
+    aad: &[u8],
+) -> Result<String, CustomError> {
+    let mut nonce_bytes = [0u8; 24];
+    rng().fill_bytes(&mut nonce_bytes);
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key_bytes))
+        .encrypt(
+            XNonce::from_slice(&nonce_bytes),
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad,
+            },
+        )
+        .map_err(|_| CustomError::EncryptionError)?;
+
+    let mut combined = Vec::with_capacity(nonce_bytes.len() + ciphertext.len());
+    combined.extend_from_slice(&nonce_bytes);
+    combined.extend_from_slice(&ciphertext);
+    Ok(format!(
+        "{}{}",
+        BOUND_PREFIX,
+        general_purpose::STANDARD.encode(combined)
+    ))
+}
+
+/// Decrypts a value encrypted with `encrypt_bound`.
+///
+/// Values from before associated data was used are still accepted, without the binding check,
+/// until they are re-encrypted (see `is_bound`). Data that can't hold such values must be read
+/// with `decrypt_bound_strict`, as an unbound value would skip the check.
+///
+/// # Arguments
+///
+/// * `key_bytes` - The encryption key.
+/// * `encoded` - The stored value.
+/// * `aad` - The associated data the value was bound to.
+///
+/// # Returns
+///
+/// A `Result` containing the decrypted string or a `DecryptionError` if the value is invalid or
+/// was bound to different associated data.
+pub fn decrypt_bound(
+    key_bytes: &[u8; 32],
+    encoded: &str,
+    aad: &[u8],
+) -> Result<String, CustomError> {
+    let Some(encoded) = encoded.strip_prefix(BOUND_PREFIX) else {
+        return decrypt_with_nonce(key_bytes, encoded);
+    };
+    let combined = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|_| CustomError::DecryptionError)?;
+    if combined.len() < 24 {
+        return Err(CustomError::DecryptionError);
+    }
+    let (nonce_bytes, ciphertext) = combined.split_at(24);
+    let plaintext_bytes = XChaCha20Poly1305::new(Key::from_slice(key_bytes))
+        .decrypt(
+            XNonce::from_slice(nonce_bytes),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
         .map_err(|_| CustomError::DecryptionError)?;
 
     String::from_utf8(plaintext_bytes).map_err(|_| CustomError::DecryptionError)
 }
+
+/// Decrypts a value encrypted with `encrypt_bound`, rejecting values that aren't bound.
+///
+/// # Arguments
+///
+/// * `key_bytes` - The encryption key.
+/// * `encoded` - The stored value.
+/// * `aad` - The associated data the value was bound to.
+///
+/// # Returns
+///
+/// A `Result` containing the decrypted string, `UnboundValue` if the value isn't bound to
+/// associated data, or a `DecryptionError` if the value is invalid or was bound to different
+/// associated data.
+pub fn decrypt_bound_strict(
+    key_bytes: &[u8; 32],
+    encoded: &str,
+    aad: &[u8],
+) -> Result<String, CustomError> {
+    if !is_bound(encoded) {
+        return Err(CustomError::UnboundValue);
+    }
+    decrypt_bound(key_bytes, encoded, aad)
+}
+
+/// Returns whether a stored value is bound to associated data, i.e. was encrypted with
+/// `encrypt_bound`.
+pub fn is_bound(encoded: &str) -> bool {
+    encoded.starts_with(BOUND_PREFIX)
+}
@@ -25,12 +25,48 @@ pub enum CustomError {
     /// Represents a database error.
     #[error("Database error: {0}")]
     DatabaseError(String),
+    /// Represents a record ID from an unexpected table or with an unexpected key type.
+    #[error("Invalid record ID: {0}")]
+    InvalidRecordId(String),
+    /// Represents an error when the configured encryption key can't decrypt the stored data.
+    #[error("ENCRYPTION_KEY does not match the key the stored data was encrypted with")]
+    EncryptionKeyMismatch,
+    /// Represents a value bound to associated data that was decrypted without it.
+    #[error("The value is bound to associated data and must be decrypted with decrypt_bound")]
+    AssociatedDataRequired,
+    /// Represents a value that must be bound to associated data but isn't.
+    #[error("The value is not bound to associated data")]
+    UnboundValue,
     /// Represents an invalid password error.
     #[error("Invalid password")]
     InvalidPassword,
     /// Represents a user not found error.
     #[error("User not found")]
     UserNotFound,
+    /// Represents an error when an email can't be sent.
+    #[error("Email error: {0}")]
+    EmailError(String),
+    /// Represents an error when the game metadata API can't be queried.
+    #[error("Metadata error: {0}")]
+    MetadataError(String),
+    /// Represents an error when the exchange rates can't be fetched.
+    #[error("Exchange rate error: {0}")]
+    ExchangeRateError(String),
+    /// Represents an error when the upload storage can't be used.
+    #[error("Storage error: {0}")]
+    StorageError(String),
+    /// Represents an error when the server is too busy to accept more CPU-heavy work.
+    #[error("Server is busy, please try again later")]
+    Overloaded,
+    /// Represents an offer status change that its lifecycle doesn't allow.
+    #[error("An offer cannot change from {0} to {1}")]
+    InvalidStatusTransition(String, String),
+    /// Represents an error when a banned user tries to authenticate.
+    #[error("User is banned")]
+    UserBanned,
+    /// Represents an error when a user who isn't banned tries to appeal a ban.
+    #[error("User is not banned")]
+    UserNotBanned,
     /// Represents an error during tracing initialization.
     #[error("Tracing initialization error: {0}")]
     TracingInitializationError(String),
@@ -49,6 +85,37 @@ pub enum CustomError {
     GovernorCreationError(String),
 }
 
+impl CustomError {
+    /// Returns the message shown to clients for a failed login, registration or ban appeal.
+    ///
+    /// In privacy mode, errors that reveal whether an account exists are collapsed into generic
+    /// messages. Ban appeals of users who aren't banned are answered like a wrong password, so
+    /// the appeal can't be used to check passwords. The precise error should still be logged and
+    /// audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `privacy_mode` - Whether account existence must be hidden from clients.
+    ///
+    /// # Returns
+    ///
+    /// The message to include in the response.
+    pub fn public_auth_message(&self, privacy_mode: bool) -> String {
+        match self {
+            CustomError::UserNotFound | CustomError::InvalidPassword if privacy_mode => {
+                "Invalid email or password".to_string()
+            }
+            CustomError::UserAlreadyExists if privacy_mode => {
+                "Registration failed. If you already have an account, please log in.".to_string()
+            }
+            CustomError::UserNotBanned => {
+                CustomError::InvalidPassword.public_auth_message(privacy_mode)
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
 impl From<surrealdb::Error> for CustomError {
     fn from(error: surrealdb::Error) -> Self {
         tracing::error!("Database error: {}", error);
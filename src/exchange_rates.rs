@@ -0,0 +1,265 @@
+//! src/exchange_rates.rs
+//!
+//! This module converts prices between the currencies offers are listed and displayed in. The
+//! European Central Bank's reference rates are fetched from `EXCHANGE_RATES_URL` (defaults to the
+//! Frankfurter API) and cached for `RATE_CACHE_TTL`, so listings don't wait for the API on every
+//! request. If the API can't be reached, the last rates are kept and fetching is paused for
+//! `FAILURE_BACKOFF`, so listings don't wait for a provider that is down.
+
+use crate::database::preferences::Currency;
+use crate::errors::custom_errors::CustomError;
+#[cfg(feature = "fault-injection")]
+use crate::fault_injection::{Dependency, inject_dependency_fault};
+
+use dotenvy::var;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// The API the rates are fetched from if `EXCHANGE_RATES_URL` is not set.
+pub const DEFAULT_EXCHANGE_RATES_URL: &str = "https://api.frankfurter.app/latest?from=EUR";
+
+/// How long fetched rates are used before they are fetched again.
+const RATE_CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How long fetching the rates may take before it is given up.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long no fetch is attempted after a failed one.
+const FAILURE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A price converted into another currency.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ConvertedPrice {
+    /// The converted amount, rounded to cents.
+    pub amount: f64,
+    /// The currency the amount is in.
+    pub currency: Currency,
+}
+
+/// The exchange rates of the supported currencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeRates {
+    /// The units of every currency one euro buys.
+    per_euro: HashMap<Currency, f64>,
+    /// The day the rates were published.
+    pub date: String,
+}
+
+/// The response of the Frankfurter API.
+#[derive(Debug, Deserialize)]
+struct FrankfurterResponse {
+    base: String,
+    date: String,
+    rates: HashMap<String, f64>,
+}
+
+impl ExchangeRates {
+    /// Parses the rates returned by the Frankfurter API for the base `EUR`.
+    ///
+    /// Currencies the shop doesn't support are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - The JSON response of the API.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the rates, or an `ExchangeRateError` if the response can't be parsed
+    /// or isn't based on the euro.
+    pub fn from_frankfurter(body: &[u8]) -> Result<Self, CustomError> {
+        let response: FrankfurterResponse = serde_json::from_slice(body)
+            .map_err(|e| CustomError::ExchangeRateError(e.to_string()))?;
+        if response.base != Currency::Eur.as_str() {
+            return Err(CustomError::ExchangeRateError(format!(
+                "Expected rates based on EUR, got {}",
+                response.base
+            )));
+        }
+        let mut per_euro: HashMap<Currency, f64> = Currency::ALL
+            .into_iter()
+            .filter_map(|currency| {
+                let rate = *response.rates.get(currency.as_str())?;
+                (rate.is_finite() && rate > 0.0).then_some((currency, rate))
+            })
+            .collect();
+        per_euro.insert(Currency::Eur, 1.0);
+        Ok(ExchangeRates {
+            per_euro,
+            date: response.date,
+        })
+    }
+
+    /// Returns the factor an amount in one currency is multiplied with to convert it into another.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The currency of the amount.
+    /// * `to` - The currency to convert into.
+    ///
+    /// # Returns
+    ///
+    /// The factor, or `None` if no rate is known for one of the currencies.
+    pub fn factor(&self, from: Currency, to: Currency) -> Option<f64> {
+        if from == to {
+            return Some(1.0);
+        }
+        Some(self.per_euro.get(&to)? / self.per_euro.get(&from)?)
+    }
+
+    /// Converts an amount into another currency.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - The amount to convert.
+    /// * `from` - The currency of the amount.
+    /// * `to` - The currency to convert into.
+    ///
+    /// # Returns
+    ///
+    /// The converted price, rounded to cents, or `None` if no rate is known for one of the
+    /// currencies.
+    pub fn convert(&self, amount: f64, from: Currency, to: Currency) -> Option<ConvertedPrice> {
+        let factor = self.factor(from, to)?;
+        Some(ConvertedPrice {
+            amount: (amount * factor * 100.0).round() / 100.0,
+            currency: to,
+        })
+    }
+}
+
+/// Fetches the exchange rates and caches them.
+pub struct ExchangeRateCache {
+    client: reqwest::Client,
+    url: String,
+    cached: RwLock<Option<(ExchangeRates, Instant)>>,
+    failed_at: RwLock<Option<Instant>>,
+    refresh: tokio::sync::Mutex<()>,
+}
+
+impl ExchangeRateCache {
+    /// Creates a cache fetching the rates from a URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The Frankfurter-compatible endpoint returning the rates for the base `EUR`.
+    pub fn new(url: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        ExchangeRateCache {
+            client,
+            url,
+            cached: RwLock::new(None),
+            failed_at: RwLock::new(None),
+            refresh: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Returns the shared cache, fetching from `EXCHANGE_RATES_URL` (defaults to
+    /// `DEFAULT_EXCHANGE_RATES_URL`).
+    pub fn global() -> &'static ExchangeRateCache {
+        static CACHE: OnceLock<ExchangeRateCache> = OnceLock::new();
+        CACHE.get_or_init(|| {
+            ExchangeRateCache::new(
+                var("EXCHANGE_RATES_URL")
+                    .unwrap_or_else(|_| DEFAULT_EXCHANGE_RATES_URL.to_string()),
+            )
+        })
+    }
+
+    /// Returns the current rates, fetching them if the cached ones are missing or expired.
+    ///
+    /// Only one request fetches at a time; the others wait for its result. After a failed fetch,
+    /// no new one is attempted for `FAILURE_BACKOFF`.
+    ///
+    /// # Returns
+    ///
+    /// The rates, the expired ones if fetching fails or is backing off, or `None` if no rates were
+    /// ever fetched.
+    pub async fn rates(&self) -> Option<ExchangeRates> {
+        if let Some(rates) = self.fresh() {
+            return Some(rates);
+        }
+        if self.backing_off() {
+            return self.stale();
+        }
+        let _refresh = self.refresh.lock().await;
+        if let Some(rates) = self.fresh() {
+            return Some(rates);
+        }
+        // The request holding the lock before may just have failed
+        if self.backing_off() {
+            return self.stale();
+        }
+        match self.fetch().await {
+            Ok(rates) => {
+                tracing::info!("Fetched exchange rates of {}", rates.date);
+                *self
+                    .cached
+                    .write()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+                    Some((rates.clone(), Instant::now()));
+                *self
+                    .failed_at
+                    .write()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+                Some(rates)
+            }
+            Err(e) => {
+                tracing::error!("Failed to fetch exchange rates: {}", e);
+                *self
+                    .failed_at
+                    .write()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Instant::now());
+                self.stale()
+            }
+        }
+    }
+
+    /// Returns the cached rates, even if they have expired.
+    fn stale(&self) -> Option<ExchangeRates> {
+        self.cached
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_ref()
+            .map(|(rates, _)| rates.clone())
+    }
+
+    /// Returns whether the last fetch failed less than `FAILURE_BACKOFF` ago.
+    fn backing_off(&self) -> bool {
+        self.failed_at
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .is_some_and(|failed_at| failed_at.elapsed() < FAILURE_BACKOFF)
+    }
+
+    /// Returns the cached rates if they haven't expired.
+    fn fresh(&self) -> Option<ExchangeRates> {
+        self.cached
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_ref()
+            .filter(|(_, fetched_at)| fetched_at.elapsed() < RATE_CACHE_TTL)
+            .map(|(rates, _)| rates.clone())
+    }
+
+    /// Fetches the rates from the API.
+    async fn fetch(&self) -> Result<ExchangeRates, CustomError> {
+        #[cfg(feature = "fault-injection")]
+        inject_dependency_fault(Dependency::ExchangeRates).await?;
+        let body = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| CustomError::ExchangeRateError(e.to_string()))?
+            .bytes()
+            .await
+            .map_err(|e| CustomError::ExchangeRateError(e.to_string()))?;
+        ExchangeRates::from_frankfurter(&body)
+    }
+}
@@ -0,0 +1,258 @@
+//! src/fault_injection.rs
+//!
+//! This module provides fault injection for resilience testing in staging. It is only compiled
+//! with the `fault-injection` feature and must never be enabled in production.
+//!
+//! Faults are configured in `FAULT_INJECTION_RULES`, as `;`-separated
+//! `target:latency_ms:error_rate` rules, e.g. `/api/offers:250:0.1;db:50:0.01;mailer:2000:0.5`.
+//! A target is either a route prefix or one of the dependencies `db`, `exchange_rates`,
+//! `game_metadata` and `mailer`. A matching request is delayed by `latency_ms` and then fails
+//! with `503 Service Unavailable` with probability `error_rate`. A call to a matching dependency
+//! is delayed the same way and then fails with the error the dependency itself would return.
+
+use crate::errors::custom_errors::CustomError;
+use crate::response::ApiError;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{Error, web};
+use dotenvy::var;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// An external dependency faults can be injected into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dependency {
+    /// The queries of `Database`.
+    Database,
+    /// The exchange rate API.
+    ExchangeRates,
+    /// The game metadata API.
+    GameMetadata,
+    /// The SMTP server emails are sent through.
+    Mailer,
+}
+
+impl Dependency {
+    /// Every dependency, in the order of `as_str`.
+    pub const ALL: [Dependency; 4] = [
+        Dependency::Database,
+        Dependency::ExchangeRates,
+        Dependency::GameMetadata,
+        Dependency::Mailer,
+    ];
+
+    /// Returns the name of the dependency in fault rules.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dependency::Database => "db",
+            Dependency::ExchangeRates => "exchange_rates",
+            Dependency::GameMetadata => "game_metadata",
+            Dependency::Mailer => "mailer",
+        }
+    }
+
+    /// Returns the error an injected fault of the dependency is reported as.
+    fn injected_error(self) -> CustomError {
+        let message = "Injected fault".to_string();
+        match self {
+            Dependency::Database => CustomError::DatabaseError(message),
+            Dependency::ExchangeRates => CustomError::ExchangeRateError(message),
+            Dependency::GameMetadata => CustomError::MetadataError(message),
+            Dependency::Mailer => CustomError::EmailError(message),
+        }
+    }
+}
+
+/// What a fault rule applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FaultTarget {
+    /// Requests whose path starts with the prefix.
+    Route(String),
+    /// Every call to the dependency.
+    Dependency(Dependency),
+}
+
+/// The faults injected into the requests or dependency calls matching a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultRule {
+    /// The requests or calls the rule applies to.
+    pub target: FaultTarget,
+    /// The artificial latency added to every matching request or call.
+    pub latency: Duration,
+    /// The probability (`0.0..=1.0`) that a matching request or call fails.
+    pub error_rate: f64,
+}
+
+impl FaultRule {
+    /// Waits for the rule's latency and decides whether the request or call fails.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a fault is injected.
+    async fn strike(&self) -> bool {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        rand::random::<f64>() < self.error_rate
+    }
+}
+
+/// The rules applied to dependency calls, installed when the server starts.
+static GLOBAL_RULES: OnceLock<FaultRules> = OnceLock::new();
+
+/// The configured fault rules.
+#[derive(Debug, Clone, Default)]
+pub struct FaultRules(pub Vec<FaultRule>);
+
+impl FaultRules {
+    /// Parses rules in the `target:latency_ms:error_rate;...` format.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The rules to parse.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the rules or a `CustomError` naming the first invalid rule.
+    pub fn parse(rules: &str) -> Result<Self, CustomError> {
+        rules
+            .split(';')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| {
+                let invalid = || {
+                    CustomError::EnvironmentVariableError(format!("Invalid fault rule: {}", rule))
+                };
+                let mut parts = rule.rsplitn(3, ':');
+                let error_rate: f64 = parts
+                    .next()
+                    .and_then(|rate| rate.trim().parse().ok())
+                    .filter(|rate| (0.0..=1.0).contains(rate))
+                    .ok_or_else(invalid)?;
+                let latency_ms: u64 = parts
+                    .next()
+                    .and_then(|latency| latency.trim().parse().ok())
+                    .ok_or_else(invalid)?;
+                let target = match parts.next().map(str::trim) {
+                    Some(prefix) if prefix.starts_with('/') => {
+                        FaultTarget::Route(prefix.to_string())
+                    }
+                    Some(name) => Dependency::ALL
+                        .into_iter()
+                        .find(|dependency| dependency.as_str() == name)
+                        .map(FaultTarget::Dependency)
+                        .ok_or_else(invalid)?,
+                    None => return Err(invalid()),
+                };
+                Ok(FaultRule {
+                    target,
+                    latency: Duration::from_millis(latency_ms),
+                    error_rate,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(FaultRules)
+    }
+
+    /// Reads the rules from `FAULT_INJECTION_RULES`. No rules are configured if it is unset.
+    pub fn from_env() -> Result<Self, CustomError> {
+        match var("FAULT_INJECTION_RULES") {
+            Ok(rules) => Self::parse(&rules),
+            Err(_) => Ok(FaultRules::default()),
+        }
+    }
+
+    /// Returns the route rule with the longest prefix matching the path, if any.
+    pub fn rule_for(&self, path: &str) -> Option<&FaultRule> {
+        self.0
+            .iter()
+            .filter_map(|rule| match &rule.target {
+                FaultTarget::Route(prefix) if path.starts_with(prefix) => Some((prefix, rule)),
+                _ => None,
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, rule)| rule)
+    }
+
+    /// Returns the first rule of a dependency, if any.
+    pub fn rule_for_dependency(&self, dependency: Dependency) -> Option<&FaultRule> {
+        self.0
+            .iter()
+            .find(|rule| rule.target == FaultTarget::Dependency(dependency))
+    }
+
+    /// Installs the rules applied to dependency calls. Only the first call has an effect.
+    pub fn install(self) {
+        if GLOBAL_RULES.set(self).is_err() {
+            tracing::warn!("Fault injection rules were already installed");
+        }
+    }
+
+    /// Returns the installed rules, or no rules if none were installed.
+    pub fn global() -> &'static FaultRules {
+        GLOBAL_RULES.get_or_init(FaultRules::default)
+    }
+}
+
+/// Delays and randomly fails a call to a dependency according to the installed `FaultRules`.
+///
+/// Called at the start of every call to the dependency, before any work is done.
+///
+/// # Arguments
+///
+/// * `dependency` - The dependency being called.
+///
+/// # Returns
+///
+/// A `Result` that is empty if the call may go ahead, or the dependency's error if a fault is
+/// injected.
+pub async fn inject_dependency_fault(dependency: Dependency) -> Result<(), CustomError> {
+    let Some(rule) = FaultRules::global().rule_for_dependency(dependency) else {
+        return Ok(());
+    };
+    if rule.strike().await {
+        tracing::warn!("Injecting fault into {}", dependency.as_str());
+        return Err(dependency.injected_error());
+    }
+    Ok(())
+}
+
+/// Delays and randomly fails requests according to the `FaultRules` in the app data.
+///
+/// Use with `actix_web::middleware::from_fn`.
+///
+/// # Arguments
+///
+/// * `req` - The service request.
+/// * `next` - The rest of the middleware chain.
+///
+/// # Returns
+///
+/// A `Result` containing the response of the wrapped service, or an injected
+/// `503 Service Unavailable` response.
+pub async fn inject_faults(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let rule = req
+        .app_data::<web::Data<FaultRules>>()
+        .and_then(|rules| rules.rule_for(req.path()).cloned());
+    let Some(rule) = rule else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+
+    if rule.strike().await {
+        tracing::warn!("Injecting fault into {} {}", req.method(), req.path());
+        let response = ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Injected fault.")
+            .into_http_response(req.request());
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
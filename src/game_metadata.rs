@@ -0,0 +1,229 @@
+//! src/game_metadata.rs
+//!
+//! This module looks up details of games (cover art, release year, genres) in an external
+//! metadata API, so listings get them without the seller typing them. The `MetadataProvider` trait
+//! is implemented by the `RawgMetadataProvider`, configured with `RAWG_API_KEY`, and by the
+//! `NoopMetadataProvider`, which knows no games and is used in tests and when no API key is set.
+//!
+//! Lookups are cached in the `games` table, see `src/database/games.rs`.
+
+use crate::database::catalog::{Genre, MAX_GENRES};
+use crate::errors::custom_errors::CustomError;
+#[cfg(feature = "fault-injection")]
+use crate::fault_injection::{Dependency, inject_dependency_fault};
+
+use dotenvy::var;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The RAWG endpoint games are searched at.
+const RAWG_SEARCH_URL: &str = "https://api.rawg.io/api/games";
+
+/// How long a lookup may take before it is given up.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The details of a game, as attached to its offers.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GameMetadata {
+    /// The game's title as spelled by the metadata API.
+    pub title: String,
+    /// A link to the game's cover art.
+    #[serde(default)]
+    pub cover_url: Option<String>,
+    /// The year the game was first released.
+    #[serde(default)]
+    pub release_year: Option<i32>,
+    /// The game's genres that have a counterpart in the shop's genre list.
+    #[serde(default)]
+    pub genres: Vec<Genre>,
+}
+
+/// Looks up games in a metadata API.
+pub trait MetadataProvider: Send + Sync {
+    /// Looks up the game best matching a title.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - The title the seller entered.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the game's details, `None` if the API knows no such game, or a
+    /// `MetadataError` if the API can't be reached.
+    fn lookup<'a>(
+        &'a self,
+        title: &'a str,
+    ) -> BoxFuture<'a, Result<Option<GameMetadata>, CustomError>>;
+}
+
+/// Looks up games in the RAWG video game database (https://rawg.io/apidocs).
+pub struct RawgMetadataProvider {
+    client: reqwest::Client,
+    api_key: String,
+}
+
+/// A page of RAWG search results.
+#[derive(Debug, Deserialize)]
+struct RawgSearchResponse {
+    #[serde(default)]
+    results: Vec<RawgGame>,
+}
+
+/// A game in the RAWG search results.
+#[derive(Debug, Deserialize)]
+struct RawgGame {
+    name: String,
+    #[serde(default)]
+    released: Option<String>,
+    #[serde(default)]
+    background_image: Option<String>,
+    #[serde(default)]
+    genres: Vec<RawgGenre>,
+}
+
+/// A genre of a RAWG game.
+#[derive(Debug, Deserialize)]
+struct RawgGenre {
+    slug: String,
+}
+
+impl RawgMetadataProvider {
+    /// Creates a provider from the environment.
+    ///
+    /// Reads `RAWG_API_KEY`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the provider, or an `EnvironmentVariableError` if the key is missing.
+    pub fn from_env() -> Result<Self, CustomError> {
+        let api_key = var("RAWG_API_KEY")?;
+        let client = reqwest::Client::builder()
+            .timeout(LOOKUP_TIMEOUT)
+            .build()
+            .map_err(|e| CustomError::MetadataError(e.to_string()))?;
+        Ok(RawgMetadataProvider { client, api_key })
+    }
+}
+
+impl MetadataProvider for RawgMetadataProvider {
+    fn lookup<'a>(
+        &'a self,
+        title: &'a str,
+    ) -> BoxFuture<'a, Result<Option<GameMetadata>, CustomError>> {
+        Box::pin(async move {
+            #[cfg(feature = "fault-injection")]
+            inject_dependency_fault(Dependency::GameMetadata).await?;
+            let body = self
+                .client
+                .get(RAWG_SEARCH_URL)
+                .query(&[
+                    ("key", self.api_key.as_str()),
+                    ("search", title),
+                    ("page_size", "1"),
+                ])
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| CustomError::MetadataError(e.without_url().to_string()))?
+                .bytes()
+                .await
+                .map_err(|e| CustomError::MetadataError(e.without_url().to_string()))?;
+            metadata_from_rawg(&body)
+        })
+    }
+}
+
+/// Extracts the best match from a RAWG search response.
+///
+/// # Arguments
+///
+/// * `body` - The JSON response of the search.
+///
+/// # Returns
+///
+/// A `Result` containing the first game found, `None` if there is none, or a `MetadataError` if
+/// the response can't be parsed.
+pub fn metadata_from_rawg(body: &[u8]) -> Result<Option<GameMetadata>, CustomError> {
+    let response: RawgSearchResponse =
+        serde_json::from_slice(body).map_err(|e| CustomError::MetadataError(e.to_string()))?;
+    Ok(response.results.into_iter().next().map(rawg_metadata))
+}
+
+/// Converts a RAWG search result to the details attached to offers.
+fn rawg_metadata(game: RawgGame) -> GameMetadata {
+    let mut genres: Vec<Genre> = Vec::new();
+    for genre in game
+        .genres
+        .iter()
+        .filter_map(|genre| rawg_genre(&genre.slug))
+    {
+        if !genres.contains(&genre) && genres.len() < MAX_GENRES {
+            genres.push(genre);
+        }
+    }
+    GameMetadata {
+        title: game.name,
+        cover_url: game
+            .background_image
+            .filter(|url| url.starts_with("https://")),
+        release_year: game
+            .released
+            .and_then(|released| released.get(..4)?.parse().ok()),
+        genres,
+    }
+}
+
+/// Maps a RAWG genre to the shop's genre, if it has one.
+fn rawg_genre(slug: &str) -> Option<Genre> {
+    match slug {
+        "action" => Some(Genre::Action),
+        "adventure" => Some(Genre::Adventure),
+        "role-playing-games-rpg" => Some(Genre::Rpg),
+        "strategy" => Some(Genre::Strategy),
+        "simulation" => Some(Genre::Simulation),
+        "sports" => Some(Genre::Sports),
+        "racing" => Some(Genre::Racing),
+        "shooter" => Some(Genre::Shooter),
+        "fighting" => Some(Genre::Fighting),
+        "platformer" => Some(Genre::Platformer),
+        "puzzle" => Some(Genre::Puzzle),
+        "family" => Some(Genre::Party),
+        _ => None,
+    }
+}
+
+/// Knows no games. Used when no metadata API is configured.
+#[derive(Debug, Default)]
+pub struct NoopMetadataProvider;
+
+impl MetadataProvider for NoopMetadataProvider {
+    fn lookup<'a>(
+        &'a self,
+        _title: &'a str,
+    ) -> BoxFuture<'a, Result<Option<GameMetadata>, CustomError>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// Creates the metadata provider configured in the environment.
+///
+/// Uses RAWG if `RAWG_API_KEY` is set. Otherwise, or if the client can't be created, the error is
+/// logged and offers are not enriched.
+pub fn metadata_provider_from_env() -> Arc<dyn MetadataProvider> {
+    if var("RAWG_API_KEY").is_err() {
+        tracing::warn!("RAWG_API_KEY is not set, offers will not be enriched with game metadata");
+        return Arc::new(NoopMetadataProvider);
+    }
+    match RawgMetadataProvider::from_env() {
+        Ok(provider) => Arc::new(provider),
+        Err(e) => {
+            tracing::error!(
+                "Invalid metadata API configuration, offers will not be enriched: {}",
+                e
+            );
+            Arc::new(NoopMetadataProvider)
+        }
+    }
+}
@@ -3,7 +3,7 @@
 //! This module provides password hashing and verification functionalities using the Argon2id algorithm.
 
 use argon2::{
-    Argon2,
+    Algorithm, Argon2, Params, Version,
     password_hash::{
         Error as Argon2Error, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
         rand_core::OsRng,
@@ -11,6 +11,28 @@ use argon2::{
 };
 
 use std::error::Error as StdError;
+use std::sync::OnceLock;
+
+/// Returns the Argon2id hasher configured with the current parameters.
+///
+/// The parameters are encoded in every PHC hash string it produces (`$argon2id$v=19$m=...,t=...,p=...`),
+/// so hashes created with older settings can be recognized by `needs_rehash`.
+fn current_hasher() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::default())
+}
+
+/// Returns a fixed password hash that no real password is checked against.
+///
+/// Verifying a password against it costs as much as verifying against a stored hash, so a login
+/// for an unknown email takes as long as one for a known email. It is computed once, with the
+/// current parameters, so it stays as expensive as freshly stored hashes.
+pub fn dummy_password_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        hash_random_salt("dummy password for timing equalization")
+            .expect("hashing a fixed password with default parameters cannot fail")
+    })
+}
 
 /// Hashes the given string with a random salt using Argon2.
 ///
@@ -26,11 +48,7 @@ pub fn hash_random_salt(unhashed: &str) -> Result<String, Argon2Error> {
     let salt = SaltString::generate(&mut OsRng);
 
     // Configure Argon2id.
-    let argon2 = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        argon2::Params::default(),
-    );
+    let argon2 = current_hasher();
 
     // Hash the password with the salt.
     let hashed_password = argon2
@@ -68,3 +86,29 @@ pub fn verify_password(unhashed: &str, password_hash: &str) -> Result<(), Argon2
         Err(_) => Err(Argon2Error::Password),
     }
 }
+
+/// Checks whether a password hash was created with outdated Argon2 settings.
+///
+/// # Arguments
+///
+/// * `password_hash` - The PHC hash string to check.
+///
+/// # Returns
+///
+/// `true` if the algorithm, version or cost parameters stored in the hash differ from the current ones.
+pub fn needs_rehash(password_hash: &str) -> bool {
+    let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
+        return true;
+    };
+    let Ok(params) = Params::try_from(&parsed_hash) else {
+        return true;
+    };
+    let current = current_hasher();
+    let current_params = current.params();
+
+    parsed_hash.algorithm != Algorithm::Argon2id.ident()
+        || parsed_hash.version != Some(Version::V0x13.into())
+        || params.m_cost() != current_params.m_cost()
+        || params.t_cost() != current_params.t_cost()
+        || params.p_cost() != current_params.p_cost()
+}
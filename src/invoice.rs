@@ -0,0 +1,459 @@
+//! src/invoice.rs
+//!
+//! This module renders the invoices of completed orders as PDF documents. The documents are
+//! written directly rather than with a PDF library: an invoice is a single A4 page set in the
+//! standard Helvetica and Courier fonts, which every PDF viewer provides, so no font has to be
+//! embedded. Text is encoded in WinAnsi; characters outside of it are replaced by `?`.
+
+use crate::database::conditions::Condition;
+use crate::database::orders::Order;
+use crate::database::platforms::Platform;
+use crate::database::preferences::Currency;
+use crate::database::promo_codes::AppliedPromo;
+use crate::database::record_key;
+use crate::database::taxes::TaxBreakdown;
+
+/// The width of an A4 page in points.
+const PAGE_WIDTH: f64 = 595.0;
+/// The height of an A4 page in points.
+const PAGE_HEIGHT: f64 = 842.0;
+/// The left and right margin in points.
+const MARGIN: f64 = 50.0;
+/// The longest description of a line item, in characters.
+const MAX_DESCRIPTION_CHARS: usize = 70;
+
+/// Formats the sequential number of an invoice.
+///
+/// # Arguments
+///
+/// * `sequence` - The number assigned to the order, starting at 1.
+///
+/// # Returns
+///
+/// The invoice number printed on the invoice, e.g. `INV-000042`.
+pub fn invoice_number(sequence: u64) -> String {
+    format!("INV-{:06}", sequence)
+}
+
+/// The seller or buyer named on an invoice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvoiceParty {
+    /// The name of the party.
+    pub name: String,
+    /// The lines of the party's postal address. Empty if the address isn't known.
+    pub address: Vec<String>,
+}
+
+/// A line of an invoice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvoiceLine {
+    /// What was sold.
+    pub description: String,
+    /// The amount charged for the line, or `None` for the games listed under a bundle.
+    pub amount: Option<f64>,
+}
+
+/// The invoice of a completed order.
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    /// The sequential invoice number, see `invoice_number`.
+    pub number: String,
+    /// The day the invoice was first issued (`YYYY-MM-DD`).
+    pub issued_on: String,
+    /// The ID of the invoiced order.
+    pub order_id: String,
+    /// The seller, who issues the invoice.
+    pub seller: InvoiceParty,
+    /// The buyer.
+    pub buyer: InvoiceParty,
+    /// The items sold.
+    pub lines: Vec<InvoiceLine>,
+    /// The currency of all amounts.
+    pub currency: Currency,
+    /// The promo code redeemed for the order, if any.
+    pub promo: Option<AppliedPromo>,
+    /// The VAT contained in the total, or `None` if it wasn't computed at checkout.
+    pub tax: Option<TaxBreakdown>,
+    /// The amount the buyer paid.
+    pub total: f64,
+}
+
+/// Returns the label of a platform or condition stored on an order, or the stored value if it
+/// names none.
+fn stored_label(value: &str, label: Option<&'static str>) -> String {
+    label.map_or_else(|| value.to_string(), str::to_string)
+}
+
+impl Invoice {
+    /// Prepares the invoice of an order.
+    ///
+    /// The items are taken from the snapshot of the offer taken when the order was placed; orders
+    /// placed before snapshots were taken list the game title only.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The completed order, with its invoice number assigned.
+    /// * `sequence` - The invoice number assigned to the order.
+    /// * `seller` - The seller to name on the invoice.
+    /// * `buyer` - The buyer to name on the invoice.
+    ///
+    /// # Returns
+    ///
+    /// The `Invoice` of the order.
+    pub fn for_order(
+        order: &Order,
+        sequence: u64,
+        seller: InvoiceParty,
+        buyer: InvoiceParty,
+    ) -> Invoice {
+        let list_price = order
+            .promo
+            .as_ref()
+            .map_or(order.price, |promo| promo.list_price);
+        let mut lines = Vec::new();
+        match &order.offer_snapshot {
+            Some(snapshot) if !snapshot.bundle_items.is_empty() => {
+                lines.push(InvoiceLine {
+                    description: format!(
+                        "{} (bundle of {} games)",
+                        snapshot.game_title,
+                        snapshot.bundle_items.len()
+                    ),
+                    amount: Some(list_price),
+                });
+                lines.extend(snapshot.bundle_items.iter().map(|item| InvoiceLine {
+                    description: format!(
+                        "  - {} ({}, {})",
+                        item.game_title,
+                        item.platform.label(),
+                        item.condition.label()
+                    ),
+                    amount: None,
+                }));
+            }
+            Some(snapshot) => lines.push(InvoiceLine {
+                description: format!(
+                    "{} ({}, {})",
+                    snapshot.game_title,
+                    stored_label(
+                        &snapshot.platform,
+                        Platform::parse(&snapshot.platform).map(|p| p.label())
+                    ),
+                    stored_label(
+                        &snapshot.condition,
+                        Condition::parse(&snapshot.condition).map(|c| c.label())
+                    )
+                ),
+                amount: Some(list_price),
+            }),
+            None => lines.push(InvoiceLine {
+                description: order.game_title.clone(),
+                amount: Some(list_price),
+            }),
+        }
+
+        Invoice {
+            number: invoice_number(sequence),
+            issued_on: order
+                .invoiced_at
+                .as_deref()
+                .and_then(|issued_at| issued_at.get(..10))
+                .unwrap_or_default()
+                .to_string(),
+            order_id: record_key(&order.id),
+            seller,
+            buyer,
+            lines,
+            currency: order.currency,
+            promo: order.promo.clone(),
+            tax: order.tax.clone(),
+            total: order.price,
+        }
+    }
+
+    /// Formats an amount with the invoice's currency.
+    fn money(&self, amount: f64) -> String {
+        format!("{:.2} {}", amount, self.currency.as_str())
+    }
+
+    /// Renders the invoice as a PDF document.
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the PDF document.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let mut page = PageWriter::new();
+        page.text(Font::Bold, 20.0, MARGIN, "Invoice");
+        page.advance(28.0);
+        page.text(
+            Font::Regular,
+            10.0,
+            MARGIN,
+            &format!("Invoice number: {}", self.number),
+        );
+        page.advance(14.0);
+        page.text(
+            Font::Regular,
+            10.0,
+            MARGIN,
+            &format!("Date: {}", self.issued_on),
+        );
+        page.advance(14.0);
+        page.text(
+            Font::Regular,
+            10.0,
+            MARGIN,
+            &format!("Order: {}", self.order_id),
+        );
+        page.advance(30.0);
+
+        let parties_top = page.y;
+        page.party(MARGIN, "Seller", &self.seller);
+        let seller_bottom = page.y;
+        page.y = parties_top;
+        page.party(PAGE_WIDTH / 2.0, "Buyer", &self.buyer);
+        page.y = page.y.min(seller_bottom);
+        page.advance(16.0);
+
+        page.text(Font::Bold, 10.0, MARGIN, "Description");
+        page.text_right(Font::Bold, 10.0, PAGE_WIDTH - MARGIN, "Amount");
+        page.advance(6.0);
+        page.rule();
+        page.advance(14.0);
+        for line in &self.lines {
+            page.text(
+                Font::Regular,
+                10.0,
+                MARGIN,
+                &truncate(&line.description, MAX_DESCRIPTION_CHARS),
+            );
+            if let Some(amount) = line.amount {
+                page.text_right(Font::Mono, 10.0, PAGE_WIDTH - MARGIN, &self.money(amount));
+            }
+            page.advance(14.0);
+        }
+        page.rule();
+        page.advance(16.0);
+
+        if let Some(promo) = &self.promo {
+            page.total_line(
+                &format!("Discount (code {})", promo.code),
+                &self.money(-promo.discount),
+            );
+        }
+        match &self.tax {
+            Some(tax) if tax.tax > 0.0 => {
+                page.total_line("Net amount", &self.money(tax.net));
+                page.total_line(
+                    &format!("VAT {}% ({})", tax.rate_percent, tax.country),
+                    &self.money(tax.tax),
+                );
+            }
+            _ => {}
+        }
+        page.text(Font::Bold, 11.0, MARGIN, "Total");
+        page.text_right(
+            Font::Mono,
+            11.0,
+            PAGE_WIDTH - MARGIN,
+            &self.money(self.total),
+        );
+        page.advance(28.0);
+
+        let note = match &self.tax {
+            Some(tax) if tax.tax > 0.0 => "The total includes VAT.",
+            _ => "No VAT is charged on this sale.",
+        };
+        page.text(Font::Regular, 9.0, MARGIN, note);
+        page.advance(12.0);
+        page.text(
+            Font::Regular,
+            9.0,
+            MARGIN,
+            "This invoice was issued by the seller through gameshop.",
+        );
+
+        assemble_pdf(&page.content)
+    }
+}
+
+/// Shortens a text to at most `max_chars` characters, marking the cut with `...`.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let kept: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+        format!("{}...", kept.trim_end())
+    }
+}
+
+/// The fonts used on an invoice, with their resource names.
+#[derive(Debug, Clone, Copy)]
+enum Font {
+    /// Helvetica.
+    Regular,
+    /// Helvetica-Bold.
+    Bold,
+    /// Courier, used for amounts so they can be right-aligned without font metrics.
+    Mono,
+}
+
+impl Font {
+    /// Returns the name the font is referenced by in the page's resources.
+    fn resource(&self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+}
+
+/// Writes the content stream of a page from top to bottom.
+struct PageWriter {
+    /// The content stream.
+    content: Vec<u8>,
+    /// The baseline of the next line, in points from the bottom of the page.
+    y: f64,
+}
+
+impl PageWriter {
+    /// Starts a page at its top margin.
+    fn new() -> PageWriter {
+        PageWriter {
+            content: Vec::new(),
+            y: PAGE_HEIGHT - MARGIN - 20.0,
+        }
+    }
+
+    /// Moves the baseline down by the given number of points.
+    fn advance(&mut self, points: f64) {
+        self.y -= points;
+    }
+
+    /// Writes a text starting at `x` on the current baseline.
+    fn text(&mut self, font: Font, size: f64, x: f64, text: &str) {
+        self.content.extend_from_slice(
+            format!(
+                "BT /{} {} Tf {:.2} {:.2} Td (",
+                font.resource(),
+                size,
+                x,
+                self.y
+            )
+            .as_bytes(),
+        );
+        self.content.extend(encode_text(text));
+        self.content.extend_from_slice(b") Tj ET\n");
+    }
+
+    /// Writes a text ending at `right` on the current baseline.
+    ///
+    /// The width is only known for Courier, whose glyphs are all 0.6 em wide; other fonts are
+    /// treated as if they had the same width.
+    fn text_right(&mut self, font: Font, size: f64, right: f64, text: &str) {
+        let width = text.chars().count() as f64 * size * 0.6;
+        self.text(font, size, right - width, text);
+    }
+
+    /// Draws a horizontal line across the page on the current baseline.
+    fn rule(&mut self) {
+        self.content.extend_from_slice(
+            format!(
+                "0.5 w {:.2} {:.2} m {:.2} {:.2} l S\n",
+                MARGIN,
+                self.y,
+                PAGE_WIDTH - MARGIN,
+                self.y
+            )
+            .as_bytes(),
+        );
+    }
+
+    /// Writes the name and address of a party under a heading.
+    fn party(&mut self, x: f64, heading: &str, party: &InvoiceParty) {
+        self.text(Font::Bold, 10.0, x, heading);
+        self.advance(14.0);
+        for line in std::iter::once(&party.name).chain(&party.address) {
+            self.text(Font::Regular, 10.0, x, &truncate(line, 40));
+            self.advance(13.0);
+        }
+    }
+
+    /// Writes a labelled amount of the totals section.
+    fn total_line(&mut self, label: &str, amount: &str) {
+        self.text(Font::Regular, 10.0, MARGIN, label);
+        self.text_right(Font::Mono, 10.0, PAGE_WIDTH - MARGIN, amount);
+        self.advance(14.0);
+    }
+}
+
+/// Encodes a text as the contents of a PDF string in WinAnsi encoding.
+fn encode_text(text: &str) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                encoded.push(b'\\');
+                encoded.push(c as u8);
+            }
+            ' '..='~' => encoded.push(c as u8),
+            '€' => encoded.push(0x80),
+            '\u{a0}'..='\u{ff}' => encoded.push(c as u32 as u8),
+            _ => encoded.push(b'?'),
+        }
+    }
+    encoded
+}
+
+/// Wraps the content stream of a single page into a complete PDF document.
+fn assemble_pdf(content: &[u8]) -> Vec<u8> {
+    let font = |name: &str| {
+        format!(
+            "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+            name
+        )
+        .into_bytes()
+    };
+    let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+    stream.extend_from_slice(content);
+    stream.extend_from_slice(b"\nendstream");
+    let objects: [Vec<u8>; 7] = [
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 4 0 R /F2 5 0 R /F3 6 0 R >> >> /Contents 7 0 R >>",
+            PAGE_WIDTH, PAGE_HEIGHT
+        )
+        .into_bytes(),
+        font("Helvetica"),
+        font("Helvetica-Bold"),
+        font("Courier"),
+        stream,
+    ];
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    pdf
+}
@@ -0,0 +1,282 @@
+//! src/job_queue.rs
+//!
+//! This module provides the named queues background jobs (emails, images, webhooks) run on.
+//! Every queue has its own concurrency limit and a priority, configured at startup with
+//! `JOB_QUEUES` (`name:concurrency:priority`, comma-separated), and all queues share
+//! `JOB_WORKERS` workers. Whenever a worker is free, the waiting job of the highest-priority queue
+//! that hasn't reached its concurrency limit runs next, so a retry storm on one queue can't
+//! occupy the workers other queues need.
+
+use crate::errors::custom_errors::CustomError;
+use crate::metrics::{PendingJob, TaskMetrics, TaskOutcome};
+use dotenvy::var;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// The default number of jobs that may run at the same time across all queues.
+const DEFAULT_WORKERS: usize = 4;
+
+/// The default queue configuration, in the format of `JOB_QUEUES`.
+pub const DEFAULT_QUEUES: &str = "emails:2:10,images:2:5,webhooks:1:1";
+
+/// A named queue.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Queue {
+    /// Transactional emails.
+    Emails,
+    /// Image processing, e.g. thumbnail generation.
+    Images,
+    /// Webhook deliveries.
+    Webhooks,
+}
+
+impl Queue {
+    /// All queues.
+    pub const ALL: [Queue; 3] = [Queue::Emails, Queue::Images, Queue::Webhooks];
+
+    /// Returns the name of the queue, as used in `JOB_QUEUES` and the task metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Queue::Emails => "emails",
+            Queue::Images => "images",
+            Queue::Webhooks => "webhooks",
+        }
+    }
+}
+
+impl FromStr for Queue {
+    type Err = CustomError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Queue::ALL
+            .into_iter()
+            .find(|queue| queue.as_str() == name)
+            .ok_or_else(|| {
+                CustomError::EnvironmentVariableError(format!("Unknown job queue: {}", name))
+            })
+    }
+}
+
+/// The limits of a queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueConfig {
+    /// The queue.
+    pub queue: Queue,
+    /// The number of jobs of the queue that may run at the same time (at least 1).
+    pub concurrency: usize,
+    /// Jobs of queues with a higher priority run first.
+    pub priority: u8,
+}
+
+/// Parses a queue configuration in the format of `JOB_QUEUES`.
+///
+/// Queues missing from the configuration keep their defaults.
+///
+/// # Arguments
+///
+/// * `spec` - The configuration, e.g. `emails:4:10,webhooks:1:1`.
+///
+/// # Returns
+///
+/// A `Result` containing the configuration of every queue, or a `CustomError` naming the first
+/// invalid entry.
+pub fn parse_queue_configs(spec: &str) -> Result<Vec<QueueConfig>, CustomError> {
+    let mut configs = Vec::new();
+    for entry in DEFAULT_QUEUES.split(',').chain(spec.split(',')) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let invalid =
+            || CustomError::EnvironmentVariableError(format!("Invalid job queue entry: {}", entry));
+        let mut parts = entry.split(':').map(str::trim);
+        let (Some(name), Some(concurrency), Some(priority), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let config = QueueConfig {
+            queue: name.parse()?,
+            concurrency: concurrency
+                .parse()
+                .ok()
+                .filter(|concurrency| *concurrency > 0)
+                .ok_or_else(invalid)?,
+            priority: priority.parse().map_err(|_| invalid())?,
+        };
+        configs.retain(|existing: &QueueConfig| existing.queue != config.queue);
+        configs.push(config);
+    }
+    Ok(configs)
+}
+
+/// A job waiting in or running on a queue.
+type Job = Pin<Box<dyn Future<Output = TaskOutcome> + Send>>;
+
+/// The state of a queue.
+struct QueueState {
+    config: QueueConfig,
+    running: usize,
+    waiting: VecDeque<(PendingJob<'static>, Job)>,
+}
+
+/// The named queues background jobs run on.
+pub struct JobQueues {
+    workers: usize,
+    state: Mutex<JobQueuesState>,
+}
+
+struct JobQueuesState {
+    running: usize,
+    // Sorted by descending priority
+    queues: Vec<QueueState>,
+}
+
+impl JobQueues {
+    /// Creates the queues.
+    ///
+    /// # Arguments
+    ///
+    /// * `workers` - The number of jobs that may run at the same time across all queues.
+    /// * `configs` - The configuration of every queue.
+    pub fn new(workers: usize, configs: Vec<QueueConfig>) -> Self {
+        let mut queues: Vec<QueueState> = configs
+            .into_iter()
+            .map(|config| QueueState {
+                config,
+                running: 0,
+                waiting: VecDeque::new(),
+            })
+            .collect();
+        queues.sort_by_key(|queue| std::cmp::Reverse(queue.config.priority));
+        JobQueues {
+            workers: workers.max(1),
+            state: Mutex::new(JobQueuesState { running: 0, queues }),
+        }
+    }
+
+    /// Returns the shared queues, configured by `JOB_WORKERS` (defaults to 4) and `JOB_QUEUES`
+    /// (defaults to `DEFAULT_QUEUES`). An invalid `JOB_QUEUES` is logged and the defaults are used.
+    pub fn global() -> &'static JobQueues {
+        static QUEUES: OnceLock<JobQueues> = OnceLock::new();
+        QUEUES.get_or_init(|| {
+            let workers = var("JOB_WORKERS")
+                .ok()
+                .and_then(|workers| workers.trim().parse().ok())
+                .unwrap_or(DEFAULT_WORKERS);
+            let configs = parse_queue_configs(&var("JOB_QUEUES").unwrap_or_default())
+                .unwrap_or_else(|e| {
+                    tracing::error!("Invalid JOB_QUEUES, using defaults: {}", e);
+                    parse_queue_configs("").unwrap_or_default()
+                });
+            for config in &configs {
+                tracing::info!(
+                    "Job queue {}: concurrency {}, priority {}",
+                    config.queue.as_str(),
+                    config.concurrency,
+                    config.priority
+                );
+            }
+            JobQueues::new(workers, configs)
+        })
+    }
+
+    /// Adds a job to a queue. It runs as soon as a worker is free and the queue is below its
+    /// concurrency limit.
+    ///
+    /// The job is a pending job in the task metrics of the queue until it starts, and its run is
+    /// recorded there. A job that panics is recorded as a failure.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The queue to add the job to.
+    /// * `job` - The job.
+    pub fn enqueue<F>(&'static self, queue: Queue, job: F)
+    where
+        F: Future<Output = TaskOutcome> + Send + 'static,
+    {
+        let pending = TaskMetrics::global().pending(queue.as_str()).enqueue();
+        {
+            let mut state = self.lock();
+            match state
+                .queues
+                .iter_mut()
+                .find(|state| state.config.queue == queue)
+            {
+                Some(state) => state.waiting.push_back((pending, Box::pin(job))),
+                None => {
+                    tracing::error!("Job queue {} is not configured", queue.as_str());
+                    return;
+                }
+            }
+        }
+        self.dispatch();
+    }
+
+    /// Returns the number of jobs waiting in a queue.
+    pub fn waiting(&self, queue: Queue) -> usize {
+        self.lock()
+            .queues
+            .iter()
+            .find(|state| state.config.queue == queue)
+            .map_or(0, |state| state.waiting.len())
+    }
+
+    /// Starts waiting jobs while workers are free, highest-priority queue first.
+    fn dispatch(&'static self) {
+        let mut state = self.lock();
+        while state.running < self.workers {
+            let Some(queue) = state.queues.iter_mut().find(|queue| {
+                queue.running < queue.config.concurrency && !queue.waiting.is_empty()
+            }) else {
+                break;
+            };
+            let Some((pending, job)) = queue.waiting.pop_front() else {
+                break;
+            };
+            queue.running += 1;
+            let name = queue.config.queue;
+            state.running += 1;
+            drop(pending);
+
+            tokio::spawn(async move {
+                let started = Instant::now();
+                // Spawned separately, so a panicking job still frees its worker
+                let outcome = tokio::spawn(job).await.unwrap_or_else(|e| {
+                    tracing::error!("Job on queue {} failed: {}", name.as_str(), e);
+                    TaskOutcome::Failure
+                });
+                TaskMetrics::global().record_run(name.as_str(), outcome, started.elapsed());
+                self.finish(name);
+            });
+        }
+    }
+
+    /// Frees the worker of a finished job and starts the next waiting jobs.
+    fn finish(&'static self, queue: Queue) {
+        {
+            let mut state = self.lock();
+            state.running -= 1;
+            if let Some(state) = state
+                .queues
+                .iter_mut()
+                .find(|state| state.config.queue == queue)
+            {
+                state.running -= 1;
+            }
+        }
+        self.dispatch();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, JobQueuesState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
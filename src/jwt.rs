@@ -2,6 +2,7 @@
 //!
 //! This module provides JWT (JSON Web Token) generation and validation functionalities.
 
+use crate::scopes::{ADMIN, ALL_SCOPES, MODERATION};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::Error};
 use serde::{Deserialize, Serialize};
@@ -16,10 +17,68 @@ pub struct Claims {
     exp: usize,
     /// The issued at timestamp of the JWT.
     iat: usize,
+    /// The permission scopes granted to the bearer of the JWT (e.g. `offers:write`).
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// The ID of the admin acting as the subject, if this is an impersonation token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<String>,
 }
 
 const SECRET_KEY_ENV: &str = "JWT_SECRET";
 
+/// The name of the cookie the JWT is stored in when cookie-based authentication is used.
+pub const AUTH_COOKIE_NAME: &str = "auth_token";
+
+/// The number of days a JWT stays valid after it was issued.
+pub const TOKEN_VALIDITY_DAYS: i64 = 1;
+
+/// The number of minutes an impersonation token stays valid after it was issued.
+pub const IMPERSONATION_VALIDITY_MINUTES: i64 = 30;
+
+/// The number of minutes an image upload token stays valid after it was issued.
+pub const IMAGE_UPLOAD_VALIDITY_MINUTES: i64 = 15;
+
+/// The audience of image upload tokens. Session tokens have no audience, so `validate_jwt`
+/// rejects upload tokens and they can never be used to authenticate.
+const IMAGE_UPLOAD_AUDIENCE: &str = "image_upload";
+
+/// Represents the claims of a token that lets its bearer upload one image for an offer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ImageUploadClaims {
+    /// The ID of the seller who requested the upload.
+    pub sub: String,
+    /// The ID of the offer the image is uploaded for.
+    pub offer_id: String,
+    /// The name the uploaded file is stored under.
+    pub file_name: String,
+    /// The declared content type of the image.
+    pub content_type: String,
+    /// The audience of the token, always `IMAGE_UPLOAD_AUDIENCE`.
+    aud: String,
+    /// The expiration timestamp of the token.
+    exp: usize,
+}
+
+/// The number of minutes a signed download URL stays valid after it was issued.
+pub const DOWNLOAD_VALIDITY_MINUTES: i64 = 5;
+
+/// The audience of download tokens. Like upload tokens, they can never be used to authenticate.
+const DOWNLOAD_AUDIENCE: &str = "download";
+
+/// Represents the claims of a token that lets its bearer download one file, e.g. an invoice.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DownloadClaims {
+    /// The ID of the user the download URL was issued to.
+    pub sub: String,
+    /// The path the token grants access to.
+    pub path: String,
+    /// The audience of the token, always `DOWNLOAD_AUDIENCE`.
+    aud: String,
+    /// The expiration timestamp of the token.
+    exp: usize,
+}
+
 /// Retrieves the secret key used for JWT signing and validation from the environment.
 ///
 /// # Panics
@@ -29,7 +88,7 @@ fn get_secret_key() -> String {
     env::var(SECRET_KEY_ENV).expect("JWT_SECRET not found in environment")
 }
 
-/// Generates a new JWT for the given user ID.
+/// Generates a new JWT for the given user ID carrying all scopes.
 ///
 /// # Arguments
 ///
@@ -39,9 +98,74 @@ fn get_secret_key() -> String {
 ///
 /// A `Result` containing the generated JWT or an error if generation fails.
 pub fn generate_jwt(user_id: String) -> Result<String, Error> {
+    let scopes = ALL_SCOPES.iter().map(|scope| scope.to_string()).collect();
+    generate_scoped_jwt(user_id, scopes)
+}
+
+/// Generates a new JWT for the given user ID that is restricted to the given scopes.
+///
+/// Used for API keys and third-party integrations that should only access specific capabilities.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user to generate the JWT for.
+/// * `scopes` - The scopes to grant (see `crate::scopes`).
+///
+/// # Returns
+///
+/// A `Result` containing the generated JWT or an error if generation fails.
+pub fn generate_scoped_jwt(user_id: String, scopes: Vec<String>) -> Result<String, Error> {
+    encode_claims(user_id, scopes, Duration::days(TOKEN_VALIDITY_DAYS), None)
+}
+
+/// Generates a short-lived JWT that lets an admin act as the given user.
+///
+/// The token carries every scope except `admin` and `moderation`, so an impersonation session can
+/// never be used to reach the staff routes, even when impersonating a staff member.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the impersonated user.
+/// * `admin_id` - The ID of the admin doing the impersonation.
+///
+/// # Returns
+///
+/// A `Result` containing the generated JWT or an error if generation fails.
+pub fn generate_impersonation_jwt(user_id: String, admin_id: String) -> Result<String, Error> {
+    let scopes = ALL_SCOPES
+        .iter()
+        .filter(|scope| ![ADMIN, MODERATION].contains(scope))
+        .map(|scope| scope.to_string())
+        .collect();
+    encode_claims(
+        user_id,
+        scopes,
+        Duration::minutes(IMPERSONATION_VALIDITY_MINUTES),
+        Some(admin_id),
+    )
+}
+
+/// Signs a JWT with the given claims.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user to generate the JWT for.
+/// * `scopes` - The scopes to grant.
+/// * `validity` - How long the JWT stays valid.
+/// * `impersonated_by` - The ID of the impersonating admin, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the generated JWT or an error if generation fails.
+fn encode_claims(
+    user_id: String,
+    scopes: Vec<String>,
+    validity: Duration,
+    impersonated_by: Option<String>,
+) -> Result<String, Error> {
     let secret_key = get_secret_key();
     let expiration = Utc::now()
-        .checked_add_signed(Duration::days(1))
+        .checked_add_signed(validity)
         .expect("valid timestamp")
         .timestamp();
 
@@ -49,6 +173,8 @@ pub fn generate_jwt(user_id: String) -> Result<String, Error> {
         sub: user_id,
         exp: expiration as usize,
         iat: Utc::now().timestamp() as usize,
+        scopes,
+        impersonated_by,
     };
 
     let header = Header::default();
@@ -88,3 +214,106 @@ pub fn extract_user_id_from_jwt(token: &str) -> Result<String, Error> {
     let claims = validate_jwt(token)?;
     Ok(claims.sub)
 }
+
+/// Generates a short-lived token that lets the seller of an offer upload one image.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the seller.
+/// * `offer_id` - The ID of the offer the image is uploaded for.
+/// * `file_name` - The name the uploaded file is stored under.
+/// * `content_type` - The declared content type of the image.
+///
+/// # Returns
+///
+/// A `Result` containing the generated token or an error if generation fails.
+pub fn generate_image_upload_jwt(
+    user_id: String,
+    offer_id: String,
+    file_name: String,
+    content_type: String,
+) -> Result<String, Error> {
+    let expiration = Utc::now()
+        .checked_add_signed(Duration::minutes(IMAGE_UPLOAD_VALIDITY_MINUTES))
+        .expect("valid timestamp")
+        .timestamp();
+
+    let claims = ImageUploadClaims {
+        sub: user_id,
+        offer_id,
+        file_name,
+        content_type,
+        aud: IMAGE_UPLOAD_AUDIENCE.to_string(),
+        exp: expiration as usize,
+    };
+
+    let encoding_key = EncodingKey::from_secret(get_secret_key().as_bytes());
+    encode(&Header::default(), &claims, &encoding_key)
+}
+
+/// Validates an image upload token.
+///
+/// # Arguments
+///
+/// * `token` - The token to validate.
+///
+/// # Returns
+///
+/// A `Result` containing the claims if the token is valid or an error if validation fails.
+pub fn validate_image_upload_jwt(token: &str) -> Result<ImageUploadClaims, Error> {
+    let decoding_key = DecodingKey::from_secret(get_secret_key().as_bytes());
+
+    let mut validation = Validation::default();
+    validation.set_audience(&[IMAGE_UPLOAD_AUDIENCE]);
+    validation.set_required_spec_claims(&["exp", "aud"]);
+    let token_data = decode::<ImageUploadClaims>(token, &decoding_key, &validation)?;
+
+    Ok(token_data.claims)
+}
+
+/// Generates a token for a signed download URL, valid for `DOWNLOAD_VALIDITY_MINUTES`.
+///
+/// # Arguments
+///
+/// * `user_id` - The ID of the user the URL is issued to.
+/// * `path` - The path the token grants access to.
+///
+/// # Returns
+///
+/// A `Result` containing the generated token or an error if generation fails.
+pub fn generate_download_jwt(user_id: String, path: String) -> Result<String, Error> {
+    let expiration = Utc::now()
+        .checked_add_signed(Duration::minutes(DOWNLOAD_VALIDITY_MINUTES))
+        .expect("valid timestamp")
+        .timestamp();
+
+    let claims = DownloadClaims {
+        sub: user_id,
+        path,
+        aud: DOWNLOAD_AUDIENCE.to_string(),
+        exp: expiration as usize,
+    };
+
+    let encoding_key = EncodingKey::from_secret(get_secret_key().as_bytes());
+    encode(&Header::default(), &claims, &encoding_key)
+}
+
+/// Validates a download token.
+///
+/// # Arguments
+///
+/// * `token` - The token to validate.
+///
+/// # Returns
+///
+/// A `Result` containing the claims if the token is valid or an error if validation fails.
+pub fn validate_download_jwt(token: &str) -> Result<DownloadClaims, Error> {
+    let decoding_key = DecodingKey::from_secret(get_secret_key().as_bytes());
+
+    let mut validation = Validation::default();
+    validation.set_audience(&[DOWNLOAD_AUDIENCE]);
+    validation.set_required_spec_claims(&["exp", "aud"]);
+    let token_data = decode::<DownloadClaims>(token, &decoding_key, &validation)?;
+
+    Ok(token_data.claims)
+}
@@ -6,19 +6,51 @@
 #[cfg(test)]
 pub mod tests;
 
+/// The runtime configuration module
+pub mod config;
+/// The CPU pool module
+pub mod cpu_pool;
 /// The database module
 pub mod database;
+/// The email module
+pub mod email;
 /// The encryption module
 pub mod encryption;
 /// The errors module
 pub mod errors;
+/// The exchange rates module
+pub mod exchange_rates;
+/// The fault injection module (resilience testing only)
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
+/// The game metadata module
+pub mod game_metadata;
 /// The hashing module
 pub mod hashing;
+/// The invoice module
+pub mod invoice;
+/// The job queue module
+pub mod job_queue;
 /// The jwt module
 pub mod jwt;
 /// The logging module
 pub mod logging;
+/// The metrics module
+pub mod metrics;
 /// The middleware module
 pub mod middleware;
+/// The response module
+pub mod response;
+/// The scopes module
+pub mod scopes;
 /// The server module
 pub mod server;
+/// The sitemap module
+pub mod sitemap;
+/// The test fixtures module (tests and the testing feature only)
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+/// The upload storage module
+pub mod upload_storage;
+/// The watermark module
+pub mod watermark;
@@ -0,0 +1,330 @@
+//! src/metrics.rs
+//!
+//! This module collects metrics of the background work (the `CpuPool` and the scheduled and
+//! queued tasks) and renders them in the Prometheus text format for `GET /metrics`.
+//!
+//! Tasks report every run with its outcome and duration, and every retry. Queued tasks also
+//! report their pending jobs, so the age of the oldest pending job can be alerted on before a
+//! stuck queue is noticed by users.
+
+use crate::cpu_pool::CpuPool;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Tracks the jobs waiting in a queue, so the age of the oldest one can be reported.
+#[derive(Debug, Default)]
+pub struct PendingJobs {
+    next_ticket: AtomicU64,
+    enqueued_at: Mutex<BTreeMap<u64, Instant>>,
+}
+
+/// A pending job. It leaves the queue when dropped.
+#[derive(Debug)]
+pub struct PendingJob<'a> {
+    jobs: &'a PendingJobs,
+    ticket: u64,
+}
+
+impl PendingJobs {
+    /// Adds a job to the queue.
+    pub fn enqueue(&self) -> PendingJob<'_> {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(ticket, Instant::now());
+        PendingJob { jobs: self, ticket }
+    }
+
+    /// Returns the number of pending jobs.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns whether no jobs are pending.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Returns how long the oldest pending job has been waiting, or zero if none is pending.
+    pub fn oldest_age(&self) -> Duration {
+        // Tickets increase, so the first job is the oldest
+        self.lock()
+            .values()
+            .next()
+            .map_or(Duration::ZERO, |enqueued_at| enqueued_at.elapsed())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, Instant>> {
+        self.enqueued_at
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for PendingJob<'_> {
+    fn drop(&mut self) {
+        self.jobs.lock().remove(&self.ticket);
+    }
+}
+
+/// The outcome of a task run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskOutcome {
+    /// The run did its work.
+    Success,
+    /// The run failed. It may be retried.
+    Failure,
+}
+
+/// The metrics of one task.
+#[derive(Debug, Default, Clone)]
+struct TaskStats {
+    succeeded: u64,
+    failed: u64,
+    retried: u64,
+    duration_sum: Duration,
+    last_success_at: Option<i64>,
+}
+
+/// The metrics of the background tasks.
+#[derive(Debug, Default)]
+pub struct TaskMetrics {
+    tasks: Mutex<BTreeMap<&'static str, TaskStats>>,
+    pending: Mutex<BTreeMap<&'static str, &'static PendingJobs>>,
+}
+
+impl TaskMetrics {
+    /// Returns the metrics shared by all tasks.
+    pub fn global() -> &'static TaskMetrics {
+        static METRICS: OnceLock<TaskMetrics> = OnceLock::new();
+        METRICS.get_or_init(TaskMetrics::default)
+    }
+
+    /// Records a run of a task.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - The name of the task, e.g. `offer_expiration`.
+    /// * `outcome` - Whether the run succeeded.
+    /// * `duration` - How long the run took.
+    pub fn record_run(&self, task: &'static str, outcome: TaskOutcome, duration: Duration) {
+        let mut tasks = self.lock_tasks();
+        let stats = tasks.entry(task).or_default();
+        match outcome {
+            TaskOutcome::Success => {
+                stats.succeeded += 1;
+                stats.last_success_at = Some(Utc::now().timestamp());
+            }
+            TaskOutcome::Failure => stats.failed += 1,
+        }
+        stats.duration_sum += duration;
+    }
+
+    /// Records that a failed run of a task will be retried.
+    pub fn record_retry(&self, task: &'static str) {
+        self.lock_tasks().entry(task).or_default().retried += 1;
+    }
+
+    /// Returns the queue of a task, creating it on first use.
+    pub fn pending(&self, task: &'static str) -> &'static PendingJobs {
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Leaked once per task name, so the queue can be shared with spawned tasks
+        pending
+            .entry(task)
+            .or_insert_with(|| Box::leak(Box::default()))
+    }
+
+    fn lock_tasks(&self) -> std::sync::MutexGuard<'_, BTreeMap<&'static str, TaskStats>> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Renders the metrics of the `CpuPool` and all tasks in the Prometheus text format.
+    pub fn render(&self, pool: &CpuPool) -> String {
+        let mut out = String::new();
+        let cpu_pool = pool.metrics();
+        let gauges = [
+            (
+                "gameshop_cpu_pool_size",
+                "The number of CPU pool jobs that may run at the same time.",
+                cpu_pool.size as f64,
+            ),
+            (
+                "gameshop_cpu_pool_max_queue_depth",
+                "The number of waiting CPU pool jobs at which new jobs are rejected.",
+                cpu_pool.max_queue_depth as f64,
+            ),
+            (
+                "gameshop_cpu_pool_queue_depth",
+                "The number of CPU pool jobs waiting for a free slot.",
+                cpu_pool.queued as f64,
+            ),
+            (
+                "gameshop_cpu_pool_running",
+                "The number of CPU pool jobs currently running.",
+                cpu_pool.running as f64,
+            ),
+            (
+                "gameshop_cpu_pool_oldest_pending_job_age_seconds",
+                "How long the oldest waiting CPU pool job has been waiting.",
+                cpu_pool.oldest_queued_micros as f64 / 1e6,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            metric_header(&mut out, name, help, "gauge");
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        let counters = [
+            (
+                "gameshop_cpu_pool_jobs_completed_total",
+                "The number of CPU pool jobs that finished, including failed ones.",
+                cpu_pool.completed,
+            ),
+            (
+                "gameshop_cpu_pool_jobs_failed_total",
+                "The number of CPU pool jobs that panicked.",
+                cpu_pool.failed,
+            ),
+            (
+                "gameshop_cpu_pool_jobs_rejected_total",
+                "The number of CPU pool jobs rejected because the queue was full.",
+                cpu_pool.rejected,
+            ),
+        ];
+        for (name, help, value) in counters {
+            metric_header(&mut out, name, help, "counter");
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        metric_header(
+            &mut out,
+            "gameshop_cpu_pool_job_duration_seconds",
+            "The time CPU pool jobs spent running.",
+            "summary",
+        );
+        let _ = writeln!(
+            out,
+            "gameshop_cpu_pool_job_duration_seconds_sum {}",
+            cpu_pool.total_run_time_micros as f64 / 1e6
+        );
+        let _ = writeln!(
+            out,
+            "gameshop_cpu_pool_job_duration_seconds_count {}",
+            cpu_pool.completed
+        );
+
+        let tasks = self.lock_tasks().clone();
+        metric_header(
+            &mut out,
+            "gameshop_task_runs_total",
+            "The number of runs of a background task by outcome.",
+            "counter",
+        );
+        for (task, stats) in &tasks {
+            let _ = writeln!(
+                out,
+                "gameshop_task_runs_total{{task=\"{}\",outcome=\"success\"}} {}",
+                task, stats.succeeded
+            );
+            let _ = writeln!(
+                out,
+                "gameshop_task_runs_total{{task=\"{}\",outcome=\"failure\"}} {}",
+                task, stats.failed
+            );
+        }
+        metric_header(
+            &mut out,
+            "gameshop_task_retries_total",
+            "The number of retries of failed background task runs.",
+            "counter",
+        );
+        for (task, stats) in &tasks {
+            let _ = writeln!(
+                out,
+                "gameshop_task_retries_total{{task=\"{}\"}} {}",
+                task, stats.retried
+            );
+        }
+        metric_header(
+            &mut out,
+            "gameshop_task_duration_seconds",
+            "The time runs of a background task took.",
+            "summary",
+        );
+        for (task, stats) in &tasks {
+            let _ = writeln!(
+                out,
+                "gameshop_task_duration_seconds_sum{{task=\"{}\"}} {}",
+                task,
+                stats.duration_sum.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "gameshop_task_duration_seconds_count{{task=\"{}\"}} {}",
+                task,
+                stats.succeeded + stats.failed
+            );
+        }
+        metric_header(
+            &mut out,
+            "gameshop_task_last_success_timestamp_seconds",
+            "The Unix time of the last successful run of a background task.",
+            "gauge",
+        );
+        for (task, stats) in &tasks {
+            if let Some(last_success_at) = stats.last_success_at {
+                let _ = writeln!(
+                    out,
+                    "gameshop_task_last_success_timestamp_seconds{{task=\"{}\"}} {}",
+                    task, last_success_at
+                );
+            }
+        }
+
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        metric_header(
+            &mut out,
+            "gameshop_task_pending_jobs",
+            "The number of jobs waiting for a background task.",
+            "gauge",
+        );
+        for (task, jobs) in &pending {
+            let _ = writeln!(
+                out,
+                "gameshop_task_pending_jobs{{task=\"{}\"}} {}",
+                task,
+                jobs.len()
+            );
+        }
+        metric_header(
+            &mut out,
+            "gameshop_task_oldest_pending_job_age_seconds",
+            "How long the oldest job waiting for a background task has been waiting.",
+            "gauge",
+        );
+        for (task, jobs) in &pending {
+            let _ = writeln!(
+                out,
+                "gameshop_task_oldest_pending_job_age_seconds{{task=\"{}\"}} {}",
+                task,
+                jobs.oldest_age().as_secs_f64()
+            );
+        }
+        out
+    }
+}
+
+/// Writes the `HELP` and `TYPE` lines of a metric.
+fn metric_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
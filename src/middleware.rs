@@ -2,20 +2,43 @@
 //!
 //! This module provides authentication middleware for Actix Web applications.
 
-use crate::jwt::{extract_user_id_from_jwt, validate_jwt};
+use crate::config::{ConfigHandle, RateLimit};
+use crate::database::Database;
+use crate::jwt::{AUTH_COOKIE_NAME, Claims, extract_user_id_from_jwt, validate_jwt};
+use crate::response::ApiError;
+use crate::scopes::GrantedScopes;
+use actix_web::body::MessageBody;
 use actix_web::dev::Transform;
+use actix_web::middleware::Next;
 use actix_web::{
     Error, HttpMessage,
     dev::{Service, ServiceRequest, ServiceResponse, forward_ready},
-    error::ErrorUnauthorized,
-    http::Method,
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized},
+    http::header::{HeaderName, HeaderValue, RETRY_AFTER},
+    http::{Method, StatusCode},
+    web,
 };
 use futures::future::err;
+use std::collections::HashMap;
 use std::future::Future;
+use std::net::IpAddr;
 use std::pin::Pin;
 use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tracing::info;
 
+/// The response header naming the admin behind an impersonation session, so the frontend can
+/// show that the session is not the user's own.
+pub const IMPERSONATED_BY_HEADER: &str = "x-impersonated-by";
+
+/// The ID of the admin impersonating the authenticated user.
+///
+/// Stored in the request extensions by the `AuthenticationMiddlewareFactory` when the token is an
+/// impersonation token.
+#[derive(Debug, Clone)]
+pub struct ImpersonatedBy(pub String);
+
 /// Authentication middleware that checks for a valid JWT in the request header.
 pub struct AuthenticationMiddleware<S> {
     service: Rc<S>,
@@ -53,51 +76,38 @@ where
     ///
     /// * `req` - The service request to process.
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        // Skip authentication for OPTIONS requests or specific routes
+        // Don't require authentication for OPTIONS requests or specific routes, but still
+        // identify the user if a valid token is present so handlers can personalize responses
         if *req.method() == Method::OPTIONS
             || req.path() == "/"
             || req.path().starts_with("/web/")
             || req.path().starts_with("/auth/")
-            || req.path() == "/api/offers"
             || *req.method() == Method::GET
         {
-            return Box::pin(self.service.call(req));
+            let claims = extract_token(&req)
+                .and_then(|token| validate_jwt(&token).map_err(|_| "Invalid token"))
+                .ok();
+            let user_id = claims.as_ref().map(|claims| claims.sub.clone());
+            let impersonated_by = claims.and_then(|claims| identify(&req, claims));
+            return self.forward(req, user_id, impersonated_by);
         }
 
-        let auth_header = req.headers().get("Authorization");
-        let auth_header = match auth_header {
-            Some(header) => header,
-            None => {
-                tracing::error!("Missing authorization header");
-                return Box::pin(err(ErrorUnauthorized("Missing authorization header")));
-            }
-        };
-
-        let auth_value = match auth_header.to_str() {
-            Ok(value) => value,
-            Err(_) => {
-                tracing::error!("Invalid authorization header value");
-                return Box::pin(err(ErrorUnauthorized("Invalid authorization header value")));
-            }
-        };
-
-        let token = match auth_value.strip_prefix("Bearer ") {
-            Some(token) => token.trim(),
-            None => {
-                tracing::error!("Invalid authorization format");
-                return Box::pin(err(ErrorUnauthorized("Invalid authorization format")));
+        let token = match extract_token(&req) {
+            Ok(token) => token,
+            Err(message) => {
+                tracing::error!("{}", message);
+                return Box::pin(err(ErrorUnauthorized(message)));
             }
         };
-
-        match validate_jwt(token) {
-            Ok(_) => {}
+        let claims = match validate_jwt(&token) {
+            Ok(claims) => claims,
             Err(e) => {
                 tracing::error!("Invalid token: {}", e);
                 return Box::pin(err(ErrorUnauthorized("Invalid token")));
             }
         };
 
-        let user_id = match extract_user_id_from_jwt(token) {
+        let user_id = match extract_user_id_from_jwt(&token) {
             Ok(user_id) => user_id,
             Err(e) => {
                 tracing::error!("Failed to extract user ID: {}", e);
@@ -106,15 +116,113 @@ where
         };
 
         info!("Authenticated user with ID: {}", user_id);
-        req.extensions_mut().insert(user_id.clone()); // Store user_id in extensions
-        let fut = self.service.call(req);
+        let impersonated_by = identify(&req, claims);
+        self.forward(req, Some(user_id), impersonated_by)
+    }
+}
+
+impl<S, B> AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    /// Passes the request on to the wrapped service, marking the response of impersonation
+    /// sessions with the `IMPERSONATED_BY_HEADER`.
+    ///
+    /// Tokens stay valid until they expire, so the account behind an identified token is checked
+    /// against the cached IDs of banned and deleted users first: their requests are rejected with
+    /// `403 Forbidden`. Apps without a `Database` in their app data skip the check.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - The service request to forward.
+    /// * `user_id` - The ID of the user identified by the token, if any.
+    /// * `impersonated_by` - The ID of the impersonating admin, if any.
+    fn forward(
+        &self,
+        req: ServiceRequest,
+        user_id: Option<String>,
+        impersonated_by: Option<String>,
+    ) -> <Self as Service<ServiceRequest>>::Future {
+        let db = req.app_data::<web::Data<Database>>().cloned();
+        let service = Rc::clone(&self.service);
         Box::pin(async move {
-            let res = fut.await?;
+            if let (Some(db), Some(user_id)) = (db, user_id) {
+                match db.is_user_blocked(&user_id).await {
+                    Ok(true) => {
+                        tracing::warn!("Rejected token of banned or deleted user: {}", user_id);
+                        return Err(ErrorForbidden("Account is banned or deleted"));
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        tracing::error!("Failed to look up authenticated user: {:?}", e);
+                        return Err(ErrorInternalServerError("Failed to authenticate"));
+                    }
+                }
+            }
+
+            let mut res = service.call(req).await?;
+            if let Some(admin_id) = impersonated_by
+                && let Ok(value) = HeaderValue::from_str(&admin_id)
+            {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(IMPERSONATED_BY_HEADER), value);
+            }
             Ok(res)
         })
     }
 }
 
+/// Stores the identity carried by validated claims in the request extensions.
+///
+/// # Arguments
+///
+/// * `req` - The service request to annotate.
+/// * `claims` - The validated claims of the request's token.
+///
+/// # Returns
+///
+/// The ID of the impersonating admin, if the token is an impersonation token.
+fn identify(req: &ServiceRequest, claims: Claims) -> Option<String> {
+    if let Some(admin_id) = &claims.impersonated_by {
+        info!("Admin {} is impersonating user {}", admin_id, claims.sub);
+        req.extensions_mut()
+            .insert(ImpersonatedBy(admin_id.clone()));
+    }
+    req.extensions_mut().insert(claims.sub); // Store user_id in extensions
+    req.extensions_mut().insert(GrantedScopes(claims.scopes)); // Store scopes for RequireScope
+    claims.impersonated_by
+}
+
+/// Extracts the JWT from the request.
+///
+/// The `Authorization: Bearer` header takes precedence; if it is absent, the token is read
+/// from the HttpOnly authentication cookie set on login.
+///
+/// # Arguments
+///
+/// * `req` - The service request to extract the token from.
+///
+/// # Returns
+///
+/// A `Result` containing the token or a message describing why no token could be extracted.
+fn extract_token(req: &ServiceRequest) -> Result<String, &'static str> {
+    if let Some(auth_header) = req.headers().get("Authorization") {
+        let auth_value = auth_header
+            .to_str()
+            .map_err(|_| "Invalid authorization header value")?;
+        let token = auth_value
+            .strip_prefix("Bearer ")
+            .ok_or("Invalid authorization format")?;
+        return Ok(token.trim().to_string());
+    }
+
+    match req.cookie(AUTH_COOKIE_NAME) {
+        Some(cookie) => Ok(cookie.value().to_string()),
+        None => Err("Missing authorization header"),
+    }
+}
+
 /// Factory for creating `AuthenticationMiddleware` instances.
 #[derive(Default)]
 pub struct AuthenticationMiddlewareFactory;
@@ -146,3 +254,129 @@ where
         std::future::ready(Ok(AuthenticationMiddleware::new(Rc::new(service))))
     }
 }
+
+/// Rejects changes while the shop is in maintenance mode.
+///
+/// Read-only requests are still served, and admins can keep working through the `/api/admin/`
+/// routes (logging in is allowed for that reason). Use with `actix_web::middleware::from_fn`.
+///
+/// # Arguments
+///
+/// * `req` - The service request to check.
+/// * `next` - The rest of the middleware chain.
+///
+/// # Returns
+///
+/// A `Result` containing the response of the wrapped service, or a `503 Service Unavailable`
+/// response in maintenance mode.
+pub async fn maintenance_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let maintenance_mode = req
+        .app_data::<web::Data<ConfigHandle>>()
+        .is_some_and(|config| config.current().maintenance_mode);
+    let read_only = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let exempt = req.path().starts_with("/api/admin/") || req.path().starts_with("/auth/log");
+
+    if maintenance_mode && !read_only && !exempt {
+        let response = ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The shop is in maintenance mode. Please try again later.",
+        )
+        .into_http_response(req.request());
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+/// The number of tracked clients above which clients that are back to a full burst are dropped.
+const RATE_LIMIT_CLEANUP_THRESHOLD: usize = 10_000;
+
+/// Per-client request budgets for the `rate_limit` middleware.
+///
+/// Stores the time at which each client has used up its budget (the generic cell rate
+/// algorithm). The limit itself is passed on every check, so a reloaded `RateLimit` applies to
+/// the next request without losing the clients' state.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    clients: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl RateLimiter {
+    /// Counts a request of a client against the given limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The IP address of the client.
+    /// * `limit` - The current rate limit.
+    /// * `now` - The time of the request.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the request is allowed, or `Err` with the time to wait before the next one.
+    pub fn check(&self, client: IpAddr, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        let interval = Duration::from_secs(limit.seconds_per_request);
+        let burst = interval * limit.burst_size;
+        let mut clients = self
+            .clients
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if clients.len() >= RATE_LIMIT_CLEANUP_THRESHOLD {
+            clients.retain(|_, exhausted_at| *exhausted_at > now);
+        }
+        let exhausted_at = clients.get(&client).map_or(now, |at| (*at).max(now)) + interval;
+        let wait = exhausted_at.saturating_duration_since(now + burst);
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        clients.insert(client, exhausted_at);
+        Ok(())
+    }
+}
+
+/// Limits the request rate of every client, keyed by its IP address.
+///
+/// The limit is read from the `ConfigHandle` on every request, so it can be changed with a
+/// reload. Requests pass unchecked if the `RateLimiter` or the `ConfigHandle` isn't registered,
+/// or the peer address is unknown. Use with `actix_web::middleware::from_fn`.
+///
+/// # Arguments
+///
+/// * `req` - The service request to check.
+/// * `next` - The rest of the middleware chain.
+///
+/// # Returns
+///
+/// A `Result` containing the response of the wrapped service, or a `429 Too Many Requests`
+/// response with a `Retry-After` header if the client exceeded the limit.
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let limiter = req.app_data::<web::Data<RateLimiter>>();
+    let limit = req
+        .app_data::<web::Data<ConfigHandle>>()
+        .map(|config| config.current().rate_limit);
+    let client = req.peer_addr().map(|addr| addr.ip());
+
+    if let (Some(limiter), Some(limit), Some(client)) = (limiter, limit, client)
+        && let Err(wait) = limiter.check(client, limit, Instant::now())
+    {
+        let retry_after = wait.as_secs_f64().ceil() as u64;
+        let mut response = ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Too many requests, retry in {}s", retry_after),
+        )
+        .into_http_response(req.request());
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,235 @@
+//! src/response.rs
+//!
+//! This module provides the envelope every API response is wrapped in, so clients get one
+//! predictable shape:
+//!
+//! ```json
+//! { "success": true, "data": ..., "message": "...", "request_id": "...", "pagination": { ... } }
+//! { "success": false, "error": { "message": "...", "details": ... }, "request_id": "..." }
+//! ```
+//!
+//! Fields without a value are left out. Every request is assigned an ID, which is returned in the
+//! body and the `X-Request-Id` header, so a client-reported failure can be found in the logs.
+
+use crate::database::pagination::PageInfo;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::cookie::Cookie;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse, Responder};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// The response header carrying the ID of the request.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The ID assigned to a request by `assign_request_id`, stored in the request extensions.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Assigns every request an ID, stores it in the request extensions and returns it in the
+/// `X-Request-Id` header. Use with `actix_web::middleware::from_fn`.
+///
+/// # Arguments
+///
+/// * `req` - The service request.
+/// * `next` - The rest of the middleware chain.
+///
+/// # Returns
+///
+/// A `Result` containing the response of the wrapped service.
+pub async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = Uuid::new_v4().to_string();
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+    tracing::debug!(
+        "Request {} {} has ID {}",
+        req.method(),
+        req.path(),
+        request_id
+    );
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    Ok(res)
+}
+
+/// The reason a request failed, such as a failed check shared by several handlers.
+///
+/// Converts into an `ApiResponse` of any data type.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiError {
+    /// The status code of the response.
+    #[serde(skip)]
+    pub status: StatusCode,
+    /// The error message shown to the client.
+    pub message: String,
+    /// Machine-readable details, e.g. which requirements a listing is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    /// Creates an error with the given status code and message.
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// Adds machine-readable details to the error.
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    /// Converts the error into an HTTP response for the given request.
+    ///
+    /// Used where no handler return value is involved, e.g. in middleware and extractors.
+    pub fn into_http_response(self, req: &HttpRequest) -> HttpResponse {
+        ApiResponse::<()>::from(self).into_http_response(req)
+    }
+}
+
+/// The response of an API handler.
+#[derive(Debug, Serialize)]
+pub struct ApiResponse<T> {
+    /// The status code of the response.
+    #[serde(skip)]
+    status: StatusCode,
+    /// The cookies set by the response.
+    #[serde(skip)]
+    cookies: Vec<Cookie<'static>>,
+    /// Additional headers of the response.
+    #[serde(skip)]
+    headers: Vec<(&'static str, &'static str)>,
+    /// Whether the request succeeded.
+    success: bool,
+    /// The requested or created data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<T>,
+    /// A message describing the outcome of a successful request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    /// The reason the request failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ApiError>,
+    /// The ID of the request, filled in when the response is sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    /// The pagination details of a page of items.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pagination: Option<PageInfo>,
+}
+
+impl<T: Serialize> ApiResponse<T> {
+    /// Creates a successful response.
+    fn success(status: StatusCode, data: Option<T>) -> Self {
+        ApiResponse {
+            status,
+            cookies: Vec::new(),
+            headers: Vec::new(),
+            success: true,
+            data,
+            message: None,
+            error: None,
+            request_id: None,
+            pagination: None,
+        }
+    }
+
+    /// Creates a `200 OK` response containing the data.
+    pub fn ok(data: T) -> Self {
+        Self::success(StatusCode::OK, Some(data))
+    }
+
+    /// Creates a `201 Created` response containing the created data.
+    pub fn created(data: T) -> Self {
+        Self::success(StatusCode::CREATED, Some(data))
+    }
+
+    /// Creates a `200 OK` response containing only a message.
+    pub fn message(message: impl Into<String>) -> Self {
+        Self::success(StatusCode::OK, None).with_message(message)
+    }
+
+    /// Creates a failed response.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status code of the response.
+    /// * `error` - The reason the request failed.
+    pub fn error(status: StatusCode, error: impl Into<String>) -> Self {
+        ApiError::new(status, error).into()
+    }
+
+    /// Adds a message to the response.
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Adds the pagination details of the returned page to the response.
+    pub fn with_pagination(mut self, pagination: PageInfo) -> Self {
+        self.pagination = Some(pagination);
+        self
+    }
+
+    /// Sets a cookie with the response.
+    pub fn with_cookie(mut self, cookie: Cookie<'static>) -> Self {
+        self.cookies.push(cookie);
+        self
+    }
+
+    /// Adds a header to the response.
+    pub fn with_header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Converts the response into an HTTP response for the given request, filling in its ID.
+    pub fn into_http_response(mut self, req: &HttpRequest) -> HttpResponse {
+        self.request_id = req
+            .extensions()
+            .get::<RequestId>()
+            .map(|request_id| request_id.0.clone());
+        let mut builder = HttpResponse::build(self.status);
+        for cookie in self.cookies.drain(..) {
+            builder.cookie(cookie);
+        }
+        for header in self.headers.drain(..) {
+            builder.insert_header(header);
+        }
+        builder.json(self)
+    }
+}
+
+impl<T> From<ApiError> for ApiResponse<T>
+where
+    T: Serialize,
+{
+    fn from(error: ApiError) -> Self {
+        let status = error.status;
+        ApiResponse {
+            success: false,
+            error: Some(error),
+            ..Self::success(status, None)
+        }
+    }
+}
+
+impl<T: Serialize> Responder for ApiResponse<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        self.into_http_response(req)
+    }
+}
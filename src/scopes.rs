@@ -0,0 +1,143 @@
+//! src/scopes.rs
+//!
+//! This module defines the permission scopes carried by JWTs and the extractor enforcing them per route.
+
+use crate::response::ApiError;
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload};
+use std::future::{Ready, ready};
+use std::marker::PhantomData;
+
+/// Allows reading the user's own profile data, such as the moderation actions affecting them.
+pub const PROFILE_READ: &str = "profile:read";
+/// Allows changing the user's profile and submitting appeals.
+pub const PROFILE_WRITE: &str = "profile:write";
+/// Allows listing the user's own offers.
+pub const OFFERS_READ: &str = "offers:read";
+/// Allows creating, updating and deleting the user's offers.
+pub const OFFERS_WRITE: &str = "offers:write";
+/// Allows using the moderator routes. The user must still have the moderator or admin role.
+pub const MODERATION: &str = "moderation";
+/// Allows using the admin routes. The user must still have the admin role.
+pub const ADMIN: &str = "admin";
+
+/// All known scopes. Tokens issued on login carry all of them.
+pub const ALL_SCOPES: &[&str] = &[
+    PROFILE_READ,
+    PROFILE_WRITE,
+    OFFERS_READ,
+    OFFERS_WRITE,
+    MODERATION,
+    ADMIN,
+];
+
+/// The scopes granted to the token of the current request.
+///
+/// Stored in the request extensions by the `AuthenticationMiddlewareFactory`.
+#[derive(Debug, Clone, Default)]
+pub struct GrantedScopes(pub Vec<String>);
+
+impl GrantedScopes {
+    /// Returns whether the given scope was granted.
+    pub fn allows(&self, scope: &str) -> bool {
+        self.0.iter().any(|granted| granted == scope)
+    }
+}
+
+/// A scope a route can require with the `RequireScope` extractor.
+pub trait Scope {
+    /// The name of the scope as it appears in the `scopes` claim.
+    const NAME: &'static str;
+}
+
+/// Marker type for the `profile:read` scope.
+pub struct ProfileRead;
+/// Marker type for the `profile:write` scope.
+pub struct ProfileWrite;
+/// Marker type for the `offers:read` scope.
+pub struct OffersRead;
+/// Marker type for the `offers:write` scope.
+pub struct OffersWrite;
+/// Marker type for the `moderation` scope.
+pub struct Moderation;
+/// Marker type for the `admin` scope.
+pub struct Admin;
+
+impl Scope for ProfileRead {
+    const NAME: &'static str = PROFILE_READ;
+}
+impl Scope for ProfileWrite {
+    const NAME: &'static str = PROFILE_WRITE;
+}
+impl Scope for OffersRead {
+    const NAME: &'static str = OFFERS_READ;
+}
+impl Scope for OffersWrite {
+    const NAME: &'static str = OFFERS_WRITE;
+}
+impl Scope for Moderation {
+    const NAME: &'static str = MODERATION;
+}
+impl Scope for Admin {
+    const NAME: &'static str = ADMIN;
+}
+
+/// Extractor that rejects the request unless its token was granted the scope `S`.
+///
+/// Unauthenticated requests are rejected with `401 Unauthorized`, authenticated requests missing
+/// the scope with `403 Forbidden`.
+pub struct RequireScope<S: Scope> {
+    /// The ID of the authenticated user.
+    pub user_id: String,
+    _scope: PhantomData<S>,
+}
+
+impl<S: Scope> RequireScope<S> {
+    /// Checks the scope against the request extensions set by the authentication middleware.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - HTTP request to access extensions.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the extractor, or the `ApiError` to return if the check fails.
+    pub fn check(req: &HttpRequest) -> Result<Self, ApiError> {
+        let extensions = req.extensions();
+        let Some(user_id) = extensions.get::<String>() else {
+            return Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "Authentication required.",
+            ));
+        };
+
+        let granted = extensions
+            .get::<GrantedScopes>()
+            .cloned()
+            .unwrap_or_default();
+        if !granted.allows(S::NAME) {
+            tracing::warn!("Token of user {} lacks the '{}' scope", user_id, S::NAME);
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                format!("Missing required scope: {}", S::NAME),
+            ));
+        }
+
+        Ok(RequireScope {
+            user_id: user_id.clone(),
+            _scope: PhantomData,
+        })
+    }
+}
+
+impl<S: Scope> FromRequest for RequireScope<S> {
+    type Error = InternalError<&'static str>;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Self::check(req).map_err(|error| {
+            InternalError::from_response("Scope check failed", error.into_http_response(req))
+        }))
+    }
+}
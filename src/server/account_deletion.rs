@@ -0,0 +1,38 @@
+//! src/server/account_deletion.rs
+//!
+//! This module defines the background job anonymizing deleted accounts whose grace period has
+//! passed. Accounts under a legal hold are skipped until the hold is lifted.
+
+use crate::database::Database;
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use std::time::{Duration, Instant};
+
+/// How often the anonymization job looks for deleted accounts past their grace period.
+const ANONYMIZATION_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// The name of the anonymization job in the task metrics.
+const ANONYMIZATION_TASK: &str = "account_anonymization";
+
+/// Starts the background job that anonymizes deleted accounts whose grace period has passed.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+pub(super) fn spawn_anonymization_job(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ANONYMIZATION_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            let outcome = match db.anonymize_deleted_users().await {
+                Ok(_) => TaskOutcome::Success,
+                Err(e) => {
+                    tracing::error!("Failed to anonymize deleted accounts: {:?}", e);
+                    TaskOutcome::Failure
+                }
+            };
+            TaskMetrics::global().record_run(ANONYMIZATION_TASK, outcome, started.elapsed());
+        }
+    });
+}
@@ -0,0 +1,236 @@
+//! src/server/addresses.rs
+//!
+//! This module defines the routes managing users' shipping addresses and attaching them to
+//! orders. The seller of an order can read its shipping address only while the order is active.
+
+use super::orders::require_own_order;
+use crate::database::Database;
+use crate::database::addresses::{Address, MAX_ADDRESSES, ShippingAddress};
+use crate::database::orders::{Order, OrderState};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersRead, OffersWrite, ProfileRead, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web};
+use serde::Deserialize;
+use validator::Validate;
+
+/// Struct representing the request body of attaching an address to an order.
+#[derive(Debug, Deserialize)]
+pub(super) struct AttachAddressRequest {
+    address_id: String,
+}
+
+/// Handles requests to add a shipping address for the authenticated user.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the address.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the stored address or an error.
+#[post("user/addresses")]
+pub(super) async fn create_address(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<ShippingAddress>,
+) -> ApiResponse<Address> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Address validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    match db.get_addresses(&auth.user_id).await {
+        Ok(addresses) if addresses.len() >= MAX_ADDRESSES => {
+            return ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!("You can store at most {} addresses.", MAX_ADDRESSES),
+            );
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Failed to retrieve addresses: {:?}", e);
+            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to add address.");
+        }
+    }
+
+    match db.create_address(&auth.user_id, &body).await {
+        Ok(address) => ApiResponse::created(address).with_message("Address added."),
+        Err(e) => {
+            tracing::error!("Failed to add address: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to add address.")
+        }
+    }
+}
+
+/// Handles requests for the authenticated user's shipping addresses.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the addresses, oldest first, or an error.
+#[get("user/addresses")]
+pub(super) async fn get_addresses(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+) -> ApiResponse<Vec<Address>> {
+    match db.get_addresses(&auth.user_id).await {
+        Ok(addresses) => ApiResponse::ok(addresses),
+        Err(e) => {
+            tracing::error!("Failed to retrieve addresses: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve addresses.",
+            )
+        }
+    }
+}
+
+/// Handles requests to delete one of the authenticated user's shipping addresses.
+///
+/// Orders the address was attached to keep shipping to it.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the address ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the deletion.
+#[delete("user/addresses/{address_id}")]
+pub(super) async fn delete_address(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    match db.delete_address(&auth.user_id, &path.into_inner()).await {
+        Ok(true) => ApiResponse::message("Address deleted."),
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "Address not found."),
+        Err(e) => {
+            tracing::error!("Failed to delete address: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete address.",
+            )
+        }
+    }
+}
+
+/// Handles requests to ship an order to one of the buyer's addresses.
+///
+/// Only the buyer can attach an address, and only until the order is shipped. The order keeps a
+/// copy of the address, so later changes to the stored address don't affect it.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the order ID.
+/// * `body` - JSON payload containing the address ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated order or an error.
+#[put("orders/{order_id}/shipping-address")]
+pub(super) async fn attach_shipping_address(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<AttachAddressRequest>,
+) -> ApiResponse<Order> {
+    let order = match require_own_order(&db, &auth.user_id, path.into_inner()).await {
+        Ok(order) => order,
+        Err(error) => return error.into(),
+    };
+    if order.buyer_id != auth.user_id {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "Only the buyer can choose the shipping address.",
+        );
+    }
+    if !matches!(order.state, OrderState::Pending | OrderState::Paid) {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "The shipping address can't be changed after the order was shipped.",
+        );
+    }
+    let address = match db.get_address(&auth.user_id, &body.address_id).await {
+        Ok(Some(address)) => address,
+        Ok(None) => return ApiResponse::error(StatusCode::NOT_FOUND, "Address not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve address: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve address.",
+            );
+        }
+    };
+
+    match db.attach_shipping_address(&order, &address.address).await {
+        Ok(Some(order)) => ApiResponse::ok(order).with_message("Shipping address saved."),
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "The order was shipped or closed in the meantime.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to attach shipping address: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to save shipping address.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the shipping address of an order.
+///
+/// The buyer can always read it; the seller only while the order is active.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `path` - Path containing the order ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the shipping address or an error.
+#[get("orders/{order_id}/shipping-address")]
+pub(super) async fn get_shipping_address(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    path: web::Path<String>,
+) -> ApiResponse<ShippingAddress> {
+    let order = match require_own_order(&db, &auth.user_id, path.into_inner()).await {
+        Ok(order) => order,
+        Err(error) => return error.into(),
+    };
+    if order.buyer_id != auth.user_id && !order.state.is_active() {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "The shipping address is no longer available for this order.",
+        );
+    }
+
+    match db.get_shipping_address(&order).await {
+        Ok(Some(address)) => ApiResponse::ok(address),
+        Ok(None) => ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            "The buyer hasn't chosen a shipping address yet.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to decrypt shipping address: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve shipping address.",
+            )
+        }
+    }
+}
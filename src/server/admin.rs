@@ -0,0 +1,1771 @@
+//! src/server/admin.rs
+//!
+//! This module defines the admin-only routes, such as the bulk moderation endpoints, the
+//! management of moderation reason templates, the appeal queue, legal holds, dead-lettered
+//! background jobs and support impersonation.
+
+use crate::config::{ConfigHandle, RuntimeConfig};
+use crate::database::appeals::Appeal;
+use crate::database::dead_letters::{DeadLetter, DeadLetterJob};
+use crate::database::field_encryption::FieldEncryptionMigration;
+use crate::database::legal_holds::{LegalHold, LegalHoldTarget};
+use crate::database::moderation::{AppealState, ModerationReason, ReportStatus, SanctionKind};
+use crate::database::{Database, Role, record_key};
+use crate::job_queue::Queue;
+use crate::jwt::{IMPERSONATION_VALIDITY_MINUTES, generate_impersonation_jwt};
+use crate::response::{ApiError, ApiResponse};
+use crate::scopes::{Admin, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, delete, get, post, put, web};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use validator::{Validate, ValidationError};
+use validator_derive::Validate;
+
+/// The moderation action to apply to a batch of offers.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum BulkOfferAction {
+    /// Hide the offers from the public listings.
+    Hide,
+    /// Make previously hidden offers visible again.
+    Unhide,
+    /// Delete the offers. They stay stored until the restore window has passed, so they can
+    /// still be restored if an appeal is upheld.
+    Delete,
+}
+
+/// The moderation action to apply to a batch of users.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum BulkUserAction {
+    /// Ban the users, preventing them from logging in.
+    Ban,
+    /// Lift an existing ban.
+    Unban,
+}
+
+/// Struct representing the bulk offer moderation request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct BulkOfferRequest {
+    action: BulkOfferAction,
+    #[validate(length(min = 1, message = "Reason code is required"))]
+    reason_code: String,
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Between 1 and 100 offer IDs are required"
+    ))]
+    offer_ids: Vec<String>,
+}
+
+/// Struct representing the bulk user moderation request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct BulkUserRequest {
+    action: BulkUserAction,
+    #[validate(length(min = 1, message = "Reason code is required"))]
+    reason_code: String,
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Between 1 and 100 user IDs are required"
+    ))]
+    user_ids: Vec<String>,
+}
+
+/// Struct representing the bulk report dismissal request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct BulkReportRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Between 1 and 100 report IDs are required"
+    ))]
+    report_ids: Vec<String>,
+}
+
+/// Struct representing the query parameters of the dead letter list
+#[derive(Debug, Deserialize)]
+struct DeadLetterQuery {
+    queue: Option<Queue>,
+}
+
+/// Struct representing the dead letter retry and discard request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct BulkDeadLetterRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Between 1 and 100 dead letter IDs are required"
+    ))]
+    dead_letter_ids: Vec<String>,
+}
+
+/// Struct representing the create moderation reason request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct CreateModerationReasonRequest {
+    #[validate(
+        length(min = 2, max = 50, message = "Code must be 2 to 50 characters long"),
+        custom(function = "validate_reason_code")
+    )]
+    code: String,
+    #[validate(length(min = 3, max = 100, message = "Title must be 3 to 100 characters long"))]
+    title: String,
+    #[validate(length(
+        min = 10,
+        max = 2000,
+        message = "Message must be 10 to 2000 characters long"
+    ))]
+    message: String,
+}
+
+/// Struct representing the update moderation reason request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct UpdateModerationReasonRequest {
+    #[validate(length(min = 3, max = 100, message = "Title must be 3 to 100 characters long"))]
+    title: Option<String>,
+    #[validate(length(
+        min = 10,
+        max = 2000,
+        message = "Message must be 10 to 2000 characters long"
+    ))]
+    message: Option<String>,
+    active: Option<bool>,
+}
+
+/// Struct representing the query parameters of the appeal queue
+#[derive(Debug, Deserialize)]
+struct AppealQueueQuery {
+    status: Option<AppealState>,
+}
+
+/// Struct representing the appeal decision request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct AppealDecisionRequest {
+    approve: bool,
+    #[validate(length(max = 2000, message = "Note must be at most 2000 characters long"))]
+    note: Option<String>,
+}
+
+/// Struct representing the query parameters of the legal hold list
+#[derive(Debug, Deserialize)]
+struct LegalHoldQuery {
+    #[serde(default)]
+    active: bool,
+}
+
+/// Struct representing the place legal hold request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct PlaceLegalHoldRequest {
+    target_kind: LegalHoldTarget,
+    #[validate(length(min = 1, message = "Target ID is required"))]
+    target_id: String,
+    #[validate(length(
+        min = 3,
+        max = 500,
+        message = "Reason must be 3 to 500 characters long"
+    ))]
+    reason: String,
+}
+
+/// Struct representing the impersonation request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct ImpersonationRequest {
+    #[validate(length(
+        min = 3,
+        max = 500,
+        message = "Reason must be 3 to 500 characters long"
+    ))]
+    reason: String,
+}
+
+/// Ensures a reason code only consists of lowercase letters, digits and underscores.
+fn validate_reason_code(code: &str) -> Result<(), ValidationError> {
+    if code
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("reason_code")
+            .with_message("Code may only contain lowercase letters, digits and underscores".into()))
+    }
+}
+
+/// The outcome of a bulk action for a single item.
+#[derive(Debug, Serialize)]
+struct BulkItemResult {
+    /// The ID of the item.
+    id: String,
+    /// Whether the action succeeded for this item.
+    success: bool,
+    /// Why the action failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BulkItemResult {
+    /// Builds a result from the outcome of a database call that reports whether the item existed.
+    fn from_outcome<E: std::fmt::Debug>(
+        id: String,
+        outcome: Result<bool, E>,
+        not_found: &str,
+    ) -> Self {
+        match outcome {
+            Ok(true) => BulkItemResult {
+                id,
+                success: true,
+                error: None,
+            },
+            Ok(false) => BulkItemResult {
+                id,
+                success: false,
+                error: Some(not_found.to_string()),
+            },
+            Err(e) => {
+                tracing::error!("Bulk action failed for {}: {:?}", id, e);
+                BulkItemResult {
+                    id,
+                    success: false,
+                    error: Some("Internal error.".to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Ensures the authenticated user is an admin and their token carries the `admin` scope.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// A `Result` containing the admin's user ID, or the `ApiError` to return if the user is not an admin.
+pub(super) async fn require_admin(db: &Database, req: &HttpRequest) -> Result<String, ApiError> {
+    let user_id = RequireScope::<Admin>::check(req)?.user_id;
+
+    match db.get_user_by_id(user_id.clone()).await {
+        Ok(Some(user)) if user.role == Role::Admin && !user.banned => Ok(user_id),
+        Ok(_) => {
+            tracing::warn!("Non-admin user {} tried to access an admin route", user_id);
+            Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "Admin privileges required.",
+            ))
+        }
+        Err(e) => {
+            tracing::error!("Failed to load user for admin check: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to verify admin privileges.",
+            ))
+        }
+    }
+}
+
+/// Looks up the active moderation reason template a moderation action refers to.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `code` - The code of the reason.
+///
+/// # Returns
+///
+/// A `Result` containing the reason, or the `ApiError` to return if the code is unknown or inactive.
+pub(super) async fn require_reason(
+    db: &Database,
+    code: &str,
+) -> Result<ModerationReason, ApiError> {
+    match db.get_active_moderation_reason(code.to_string()).await {
+        Ok(Some(reason)) => Ok(reason),
+        Ok(None) => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Unknown or inactive reason code: {}", code),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to load moderation reason {}: {:?}", code, e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load moderation reason.",
+            ))
+        }
+    }
+}
+
+/// Records a sanction and notifies the affected user of the reason and how to appeal it.
+///
+/// Failures are logged but don't undo the sanction itself.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `moderator_id` - The ID of the moderator who applied the sanction.
+/// * `user_id` - The ID of the affected user.
+/// * `kind` - The kind of sanction applied.
+/// * `target_id` - The ID of the sanctioned record.
+/// * `reason` - The reason template that was used.
+pub(super) async fn notify_sanction(
+    db: &Database,
+    moderator_id: &str,
+    user_id: String,
+    kind: SanctionKind,
+    target_id: &str,
+    reason: &ModerationReason,
+) {
+    let action = match db
+        .record_moderation_action(
+            user_id.clone(),
+            moderator_id.to_string(),
+            kind,
+            target_id.to_string(),
+            reason,
+        )
+        .await
+    {
+        Ok(action) => action,
+        Err(e) => {
+            tracing::error!(
+                "Failed to record moderation action on {}: {:?}",
+                target_id,
+                e
+            );
+            return;
+        }
+    };
+
+    let title = match kind {
+        SanctionKind::OfferHidden => "One of your offers was hidden by a moderator",
+        SanctionKind::OfferDeleted => "One of your offers was removed by a moderator",
+        SanctionKind::UserBanned => "Your account was banned",
+    };
+    let body = format!(
+        "{}: {}\n\nIf you believe this decision is wrong, you can appeal it once, referencing moderation action {}.",
+        reason.title,
+        reason.message,
+        record_key(&action.id)
+    );
+
+    if let Err(e) = db
+        .create_notification(user_id, kind.as_str(), title.to_string(), body)
+        .await
+    {
+        tracing::error!("Failed to notify user about moderation action: {:?}", e);
+    }
+}
+
+/// Records a single audit entry for a whole batch and builds the response listing the per-item results.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `admin_id` - The ID of the admin who performed the batch.
+/// * `action` - The name of the action recorded in the audit log.
+/// * `reason_code` - The moderation reason the batch was performed for, if any.
+/// * `results` - The per-item results of the batch.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the per-item results.
+async fn finish_batch(
+    db: &Database,
+    admin_id: String,
+    action: &str,
+    reason_code: Option<&str>,
+    results: Vec<BulkItemResult>,
+) -> ApiResponse<serde_json::Value> {
+    let succeeded = results.iter().filter(|result| result.success).count();
+    let failed = results.len() - succeeded;
+    let targets = results.iter().map(|result| result.id.clone()).collect();
+    let mut details = format!("{} succeeded, {} failed", succeeded, failed);
+    if let Some(code) = reason_code {
+        details.push_str(&format!(" (reason: {})", code));
+    }
+
+    if let Err(e) = db
+        .record_audit_entry(admin_id, action, targets, details)
+        .await
+    {
+        tracing::error!("Failed to record audit entry for {}: {:?}", action, e);
+    }
+
+    ApiResponse::ok(json!({
+        "succeeded": succeeded,
+        "failed": failed,
+        "results": results
+    }))
+}
+
+/// Handles requests to hide, unhide or delete multiple offers at once.
+///
+/// This route is restricted to admins and requires an active moderation reason code. Every offer is
+/// processed independently and the outcome for each one is reported in the response. Sellers of hidden
+/// or deleted offers are notified with the reason. The batch is recorded as a single audit entry.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the action and the offer IDs.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the per-offer results.
+#[post("admin/offers/bulk")]
+pub(crate) async fn bulk_offer_action(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<BulkOfferRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Bulk offer request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let reason = match require_reason(&db, &body.reason_code).await {
+        Ok(reason) => reason,
+        Err(error) => return error.into(),
+    };
+
+    let sanction = match body.action {
+        BulkOfferAction::Hide => Some(SanctionKind::OfferHidden),
+        BulkOfferAction::Unhide => None,
+        BulkOfferAction::Delete => Some(SanctionKind::OfferDeleted),
+    };
+    let mut results = Vec::with_capacity(body.offer_ids.len());
+    for offer_id in &body.offer_ids {
+        let outcome = match body.action {
+            BulkOfferAction::Hide => db.set_offer_hidden(offer_id.clone(), true).await,
+            BulkOfferAction::Unhide => db.set_offer_hidden(offer_id.clone(), false).await,
+            BulkOfferAction::Delete => db.delete_offer(offer_id.clone(), admin_id.clone()).await,
+        };
+        if let (Ok(Some(offer)), Some(kind)) = (&outcome, sanction) {
+            let seller_id = record_key(&offer.seller_id);
+            notify_sanction(&db, &admin_id, seller_id, kind, offer_id, &reason).await;
+        }
+        results.push(BulkItemResult::from_outcome(
+            offer_id.clone(),
+            outcome.map(|offer| offer.is_some()),
+            "Offer not found.",
+        ));
+    }
+
+    let action = match body.action {
+        BulkOfferAction::Hide => "bulk_hide_offers",
+        BulkOfferAction::Unhide => "bulk_unhide_offers",
+        BulkOfferAction::Delete => "bulk_soft_delete_offers",
+    };
+    finish_batch(&db, admin_id, action, Some(&reason.code), results).await
+}
+
+/// Handles requests to ban or unban multiple users at once.
+///
+/// This route is restricted to admins and requires an active moderation reason code. Admins cannot
+/// ban themselves. Banned users are notified with the reason. The batch is recorded as a single audit entry.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the action and the user IDs.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the per-user results.
+#[post("admin/users/bulk")]
+pub(crate) async fn bulk_user_action(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<BulkUserRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Bulk user request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let reason = match require_reason(&db, &body.reason_code).await {
+        Ok(reason) => reason,
+        Err(error) => return error.into(),
+    };
+
+    let banned = matches!(body.action, BulkUserAction::Ban);
+    let mut results = Vec::with_capacity(body.user_ids.len());
+    for user_id in &body.user_ids {
+        if banned && *user_id == admin_id {
+            results.push(BulkItemResult {
+                id: user_id.clone(),
+                success: false,
+                error: Some("You cannot ban yourself.".to_string()),
+            });
+            continue;
+        }
+        let outcome = db.set_user_banned(user_id.clone(), banned).await;
+        if banned && matches!(outcome, Ok(true)) {
+            notify_sanction(
+                &db,
+                &admin_id,
+                user_id.clone(),
+                SanctionKind::UserBanned,
+                user_id,
+                &reason,
+            )
+            .await;
+        }
+        results.push(BulkItemResult::from_outcome(
+            user_id.clone(),
+            outcome,
+            "User not found.",
+        ));
+    }
+
+    let action = if banned {
+        "bulk_ban_users"
+    } else {
+        "bulk_unban_users"
+    };
+    finish_batch(&db, admin_id, action, Some(&reason.code), results).await
+}
+
+/// Handles requests to dismiss multiple open reports at once.
+///
+/// This route is restricted to admins. Reports that are not open are reported as failures.
+/// The batch is recorded as a single audit entry.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the report IDs.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the per-report results.
+#[post("admin/reports/bulk-dismiss")]
+pub(super) async fn bulk_dismiss_reports(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<BulkReportRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Bulk report request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let mut results = Vec::with_capacity(body.report_ids.len());
+    for report_id in &body.report_ids {
+        let outcome = db
+            .close_report(report_id.clone(), ReportStatus::Dismissed)
+            .await;
+        results.push(BulkItemResult::from_outcome(
+            report_id.clone(),
+            outcome,
+            "Open report not found.",
+        ));
+    }
+
+    finish_batch(&db, admin_id, "bulk_dismiss_reports", None, results).await
+}
+
+/// Handles requests to list the dead-lettered background jobs, newest first.
+///
+/// This route is restricted to admins. Dead letters can be filtered by queue with the `queue`
+/// query parameter.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the optional queue filter.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the dead letters with their errors, or an error.
+#[get("admin/jobs/dead-letters")]
+pub(super) async fn get_dead_letters(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<DeadLetterQuery>,
+) -> ApiResponse<Vec<DeadLetter>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_dead_letters(query.queue).await {
+        Ok(letters) => ApiResponse::ok(letters),
+        Err(e) => {
+            tracing::error!("Failed to retrieve dead letters: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve dead letters.",
+            )
+        }
+    }
+}
+
+/// Puts the job of a dead letter back on its queue and deletes the dead letter.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `dead_letter_id` - The ID of the dead letter.
+///
+/// # Returns
+///
+/// The result of the retry for the dead letter.
+async fn requeue_dead_letter(db: &web::Data<Database>, dead_letter_id: String) -> BulkItemResult {
+    let letter = match db.get_dead_letter(dead_letter_id.clone()).await {
+        Ok(Some(letter)) => letter,
+        outcome => {
+            return BulkItemResult::from_outcome(
+                dead_letter_id,
+                outcome.map(|_| false),
+                "Dead letter not found.",
+            );
+        }
+    };
+
+    // Everything the job needs is loaded before the dead letter is deleted, so a job that can't
+    // be requeued stays available
+    let requeue = match letter.job {
+        DeadLetterJob::Thumbnail { offer_id, url } => {
+            match super::offer_images::read_image_file(&url).await {
+                Ok(data) => {
+                    move || super::offer_images::enqueue_thumbnail(db.clone(), offer_id, url, data)
+                }
+                Err(e) => {
+                    return BulkItemResult {
+                        id: dead_letter_id,
+                        success: false,
+                        error: Some(format!("Failed to read image file: {}", e)),
+                    };
+                }
+            }
+        }
+    };
+
+    let outcome = db.delete_dead_letter(dead_letter_id.clone()).await;
+    if matches!(outcome, Ok(true)) {
+        requeue();
+    }
+    BulkItemResult::from_outcome(dead_letter_id, outcome, "Dead letter not found.")
+}
+
+/// Handles requests to put the jobs of dead letters back on their queues.
+///
+/// This route is restricted to admins. Every dead letter is processed independently and the
+/// outcome for each one is reported in the response. Requeued jobs that fail again are
+/// dead-lettered again. The batch is recorded as a single audit entry.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the dead letter IDs.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the per-dead-letter results.
+#[post("admin/jobs/dead-letters/retry")]
+pub(super) async fn retry_dead_letters(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<BulkDeadLetterRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Dead letter retry request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let mut results = Vec::with_capacity(body.dead_letter_ids.len());
+    for dead_letter_id in &body.dead_letter_ids {
+        results.push(requeue_dead_letter(&db, dead_letter_id.clone()).await);
+    }
+
+    finish_batch(&db, admin_id, "retry_dead_letters", None, results).await
+}
+
+/// Handles requests to discard dead letters without running their jobs again.
+///
+/// This route is restricted to admins. The batch is recorded as a single audit entry.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the dead letter IDs.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the per-dead-letter results.
+#[post("admin/jobs/dead-letters/discard")]
+pub(super) async fn discard_dead_letters(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<BulkDeadLetterRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Dead letter discard request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let mut results = Vec::with_capacity(body.dead_letter_ids.len());
+    for dead_letter_id in &body.dead_letter_ids {
+        let outcome = db.delete_dead_letter(dead_letter_id.clone()).await;
+        results.push(BulkItemResult::from_outcome(
+            dead_letter_id.clone(),
+            outcome,
+            "Dead letter not found.",
+        ));
+    }
+
+    finish_batch(&db, admin_id, "discard_dead_letters", None, results).await
+}
+
+/// Handles requests to list all moderation reason templates, including inactive ones.
+///
+/// This route is restricted to admins.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of reasons or an error.
+#[get("admin/moderation-reasons")]
+pub(super) async fn get_moderation_reasons(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<Vec<ModerationReason>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_moderation_reasons(true).await {
+        Ok(reasons) => ApiResponse::ok(reasons),
+        Err(e) => {
+            tracing::error!("Failed to retrieve moderation reasons: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve moderation reasons.",
+            )
+        }
+    }
+}
+
+/// Handles requests to create a moderation reason template.
+///
+/// This route is restricted to admins. The creation is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the reason's code, title and message.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created reason or an error.
+#[post("admin/moderation-reasons")]
+pub(super) async fn create_moderation_reason(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<CreateModerationReasonRequest>,
+) -> ApiResponse<ModerationReason> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!(
+            "Create moderation reason request validation failed: {:?}",
+            e
+        );
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db
+        .create_moderation_reason(body.code.clone(), body.title.clone(), body.message.clone())
+        .await
+    {
+        Ok(reason) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "create_moderation_reason",
+                    vec![reason.code.clone()],
+                    format!("Created moderation reason '{}'", reason.title),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::created(reason).with_message("Moderation reason created successfully.")
+        }
+        Err(e) => {
+            tracing::warn!("Failed to create moderation reason: {:?}", e);
+            ApiResponse::error(
+                StatusCode::CONFLICT,
+                "Failed to create moderation reason. The code may already be in use.",
+            )
+        }
+    }
+}
+
+/// Handles requests to update or (de)activate a moderation reason template.
+///
+/// This route is restricted to admins. The update is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the reason code.
+/// * `body` - JSON payload containing the fields to update.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated reason or an error.
+#[put("admin/moderation-reasons/{code}")]
+pub(super) async fn update_moderation_reason(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<UpdateModerationReasonRequest>,
+) -> ApiResponse<ModerationReason> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!(
+            "Update moderation reason request validation failed: {:?}",
+            e
+        );
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let code = path.into_inner();
+    match db
+        .update_moderation_reason(
+            code.clone(),
+            body.title.clone(),
+            body.message.clone(),
+            body.active,
+        )
+        .await
+    {
+        Ok(Some(reason)) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "update_moderation_reason",
+                    vec![code],
+                    format!("Updated moderation reason (active: {})", reason.active),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(reason).with_message("Moderation reason updated successfully.")
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Moderation reason not found."),
+        Err(e) => {
+            tracing::error!("Failed to update moderation reason: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update moderation reason.",
+            )
+        }
+    }
+}
+
+/// Handles requests to list appeals, oldest first.
+///
+/// This route is restricted to admins. Appeals can be filtered by status with the `status` query parameter.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the optional status filter.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of appeals or an error.
+#[get("admin/appeals")]
+pub(super) async fn get_appeals(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<AppealQueueQuery>,
+) -> ApiResponse<Vec<Appeal>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_appeals(query.status).await {
+        Ok(appeals) => ApiResponse::ok(appeals),
+        Err(e) => {
+            tracing::error!("Failed to retrieve appeals: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve appeals.",
+            )
+        }
+    }
+}
+
+/// Lifts the sanction a moderation action applied.
+///
+/// Deleted offers are restored unless they have been purged in the meantime.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `kind` - The kind of sanction that was applied.
+/// * `target_id` - The ID of the sanctioned record.
+///
+/// # Returns
+///
+/// `true` if the sanctioned record was reinstated, or `false` otherwise.
+async fn reinstate(db: &Database, kind: SanctionKind, target_id: &str) -> bool {
+    let outcome = match kind {
+        SanctionKind::OfferHidden => db
+            .set_offer_hidden(target_id.to_string(), false)
+            .await
+            .map(|offer| offer.is_some()),
+        SanctionKind::UserBanned => db.set_user_banned(target_id.to_string(), false).await,
+        SanctionKind::OfferDeleted => db
+            .restore_offer(target_id.to_string(), None)
+            .await
+            .map(|offer| offer.is_some()),
+    };
+
+    outcome.unwrap_or_else(|e| {
+        tracing::error!("Failed to reinstate {}: {:?}", target_id, e);
+        false
+    })
+}
+
+/// Handles requests to approve or reject an open appeal.
+///
+/// This route is restricted to admins. Approving an appeal automatically lifts the sanction where
+/// possible. The user is notified of the decision and the decision is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the appeal ID.
+/// * `body` - JSON payload containing the decision and an optional note.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the decided appeal or an error.
+#[post("admin/appeals/{id}/decision")]
+pub(super) async fn decide_appeal(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<AppealDecisionRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Appeal decision request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let appeal_id = path.into_inner();
+    let appeal = match db
+        .decide_appeal(
+            appeal_id.clone(),
+            body.approve,
+            admin_id.clone(),
+            body.note.clone(),
+        )
+        .await
+    {
+        Ok(Some(appeal)) => appeal,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Open appeal not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to decide appeal: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to decide appeal.",
+            );
+        }
+    };
+
+    let action = match db.get_moderation_action(appeal.action_id.clone()).await {
+        Ok(action) => action,
+        Err(e) => {
+            tracing::error!("Failed to load appealed moderation action: {:?}", e);
+            None
+        }
+    };
+
+    let mut reinstated = false;
+    if let Some(action) = &action {
+        if body.approve {
+            reinstated = reinstate(&db, action.kind, &action.target_id).await;
+        }
+        let state = if body.approve {
+            AppealState::Approved
+        } else {
+            AppealState::Rejected
+        };
+        if let Err(e) = db
+            .transition_appeal_state(appeal.action_id.clone(), AppealState::Open, state)
+            .await
+        {
+            tracing::error!(
+                "Failed to update appeal state of moderation action: {:?}",
+                e
+            );
+        }
+    }
+
+    let (title, mut body_text) = if body.approve {
+        (
+            "Your appeal was approved",
+            if reinstated {
+                "Your appeal was approved and the moderation action has been reverted.".to_string()
+            } else {
+                "Your appeal was approved, but the affected content could not be restored."
+                    .to_string()
+            },
+        )
+    } else {
+        (
+            "Your appeal was rejected",
+            "Your appeal was reviewed and rejected. This decision is final.".to_string(),
+        )
+    };
+    if let Some(note) = &appeal.decision_note {
+        body_text.push_str(&format!("\n\nNote from the moderator: {}", note));
+    }
+    if let Err(e) = db
+        .create_notification(
+            appeal.user_id.clone(),
+            "appeal_decided",
+            title.to_string(),
+            body_text,
+        )
+        .await
+    {
+        tracing::error!("Failed to notify user about appeal decision: {:?}", e);
+    }
+
+    if let Err(e) = db
+        .record_audit_entry(
+            admin_id,
+            if body.approve {
+                "approve_appeal"
+            } else {
+                "reject_appeal"
+            },
+            vec![appeal_id, appeal.action_id.clone()],
+            format!("Appeal decided (reinstated: {})", reinstated),
+        )
+        .await
+    {
+        tracing::error!("Failed to record audit entry: {:?}", e);
+    }
+
+    ApiResponse::ok(json!({
+        "reinstated": reinstated,
+        "appeal": appeal
+    }))
+    .with_message("Appeal decided successfully.")
+}
+
+/// Handles requests to list legal holds, newest first.
+///
+/// This route is restricted to admins. Pass `active=true` to leave out lifted holds.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the active filter.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of legal holds or an error.
+#[get("admin/legal-holds")]
+pub(super) async fn get_legal_holds(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<LegalHoldQuery>,
+) -> ApiResponse<Vec<LegalHold>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_legal_holds(query.active).await {
+        Ok(holds) => ApiResponse::ok(holds),
+        Err(e) => {
+            tracing::error!("Failed to retrieve legal holds: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve legal holds.",
+            )
+        }
+    }
+}
+
+/// Handles requests to place a legal hold on a record.
+///
+/// This route is restricted to admins. Held records are skipped by the retention jobs until the
+/// hold is lifted. The hold is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the target and the reason for the hold.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created hold or an error.
+#[post("admin/legal-holds")]
+pub(super) async fn place_legal_hold(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<PlaceLegalHoldRequest>,
+) -> ApiResponse<LegalHold> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Place legal hold request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let exists = match body.target_kind {
+        LegalHoldTarget::User => db
+            .get_user_by_id(body.target_id.clone())
+            .await
+            .map(|user| user.is_some()),
+    };
+    match exists {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Target not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to look up legal hold target: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to place legal hold.",
+            );
+        }
+    }
+
+    match db
+        .place_legal_hold(
+            body.target_kind,
+            body.target_id.clone(),
+            body.reason.clone(),
+            admin_id.clone(),
+        )
+        .await
+    {
+        Ok(hold) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "place_legal_hold",
+                    vec![record_key(&hold.id), hold.target_id.clone()],
+                    format!(
+                        "Placed legal hold on {} ({})",
+                        hold.target_kind.as_str(),
+                        hold.reason
+                    ),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::created(hold).with_message("Legal hold placed successfully.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to place legal hold: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to place legal hold.",
+            )
+        }
+    }
+}
+
+/// Handles requests to lift an active legal hold.
+///
+/// This route is restricted to admins. Lifting the hold is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the hold ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the lifted hold or an error.
+#[post("admin/legal-holds/{id}/lift")]
+pub(super) async fn lift_legal_hold(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<LegalHold> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let hold_id = path.into_inner();
+    match db.lift_legal_hold(hold_id.clone(), admin_id.clone()).await {
+        Ok(Some(hold)) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "lift_legal_hold",
+                    vec![hold_id, hold.target_id.clone()],
+                    format!("Lifted legal hold on {}", hold.target_kind.as_str()),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(hold).with_message("Legal hold lifted successfully.")
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Active legal hold not found."),
+        Err(e) => {
+            tracing::error!("Failed to lift legal hold: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to lift legal hold.",
+            )
+        }
+    }
+}
+
+/// Handles requests to promote a user to moderator.
+///
+/// This route is restricted to admins. Only regular users can be promoted. The promotion is
+/// recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the user ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the promotion.
+#[post("admin/users/{id}/moderator")]
+pub(super) async fn grant_moderator(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    change_role(
+        &db,
+        admin_id,
+        path.into_inner(),
+        Role::User,
+        Role::Moderator,
+    )
+    .await
+}
+
+/// Handles requests to demote a moderator to a regular user.
+///
+/// This route is restricted to admins. The demotion is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the user ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the demotion.
+#[delete("admin/users/{id}/moderator")]
+pub(super) async fn revoke_moderator(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    change_role(
+        &db,
+        admin_id,
+        path.into_inner(),
+        Role::Moderator,
+        Role::User,
+    )
+    .await
+}
+
+/// Handles requests to restore a deleted user account.
+///
+/// This route is restricted to admins. Accounts can only be restored within the grace period after
+/// their deletion. The restoration is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the user ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the restoration.
+#[post("admin/users/{id}/restore")]
+pub(super) async fn restore_user(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let user_id = path.into_inner();
+    match db.restore_user(user_id.clone()).await {
+        Ok(true) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "restore_user",
+                    vec![user_id],
+                    "Restored deleted account".to_string(),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::message("Account restored successfully.")
+        }
+        Ok(false) => ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            "No deleted account within its grace period found.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to restore account: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to restore account.",
+            )
+        }
+    }
+}
+
+/// Handles requests to view the runtime configuration.
+///
+/// This route is restricted to admins.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `config` - Web data containing the runtime configuration.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the current configuration or an error.
+#[get("admin/config")]
+pub(super) async fn get_runtime_config(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    req: HttpRequest,
+) -> ApiResponse<RuntimeConfig> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    let current = config.current().clone();
+    ApiResponse::ok(current)
+}
+
+/// Handles requests to reload the runtime configuration from the environment and `.env` file.
+///
+/// This route is restricted to admins. It has the same effect as sending `SIGHUP` to the server.
+/// If the new configuration is invalid, the current one is kept. The reload is recorded in the
+/// audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `config` - Web data containing the runtime configuration.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the new configuration or an error.
+#[post("admin/config/reload")]
+pub(super) async fn reload_runtime_config(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    req: HttpRequest,
+) -> ApiResponse<RuntimeConfig> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    match config.reload() {
+        Ok(reloaded) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "reload_config",
+                    Vec::new(),
+                    format!("Reloaded runtime configuration: {:?}", reloaded),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(reloaded).with_message("Configuration reloaded successfully.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to reload configuration: {:?}", e);
+            ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string())
+        }
+    }
+}
+
+/// Struct representing the query parameters of the user lookup by name
+#[derive(Debug, Deserialize, Validate)]
+struct UserNameLookupQuery {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "First name must be 1 to 100 characters long"
+    ))]
+    firstname: Option<String>,
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Last name must be 1 to 100 characters long"
+    ))]
+    lastname: Option<String>,
+}
+
+/// Handles requests to look up users by their exact first and/or last name.
+///
+/// This route is restricted to admins and meant for support requests. Names are encrypted, so
+/// they are matched through their blind indexes, ignoring case and whitespace. Every lookup is
+/// recorded in the audit log, as it searches personal information.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - The names to look for.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the matching users or an error.
+#[get("admin/users/lookup")]
+pub(super) async fn lookup_users_by_name(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<UserNameLookupQuery>,
+) -> ApiResponse<Vec<serde_json::Value>> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    if let Err(e) = query.validate() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let query = query.into_inner();
+    if query.firstname.is_none() && query.lastname.is_none() {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "Provide a first name, a last name or both.",
+        );
+    }
+
+    match db.find_users_by_name(query.firstname, query.lastname).await {
+        Ok(users) => {
+            let targets: Vec<String> = users.iter().map(|user| record_key(&user.id)).collect();
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "lookup_users_by_name",
+                    targets,
+                    format!("Looked up users by name, {} found", users.len()),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            let users: Vec<serde_json::Value> = users
+                .iter()
+                .map(|user| {
+                    json!({
+                        "id": record_key(&user.id),
+                        "username": user.username,
+                        "role": user.role,
+                        "banned": user.banned,
+                        "deleted_at": user.deleted_at,
+                        "created_at": user.created_at
+                    })
+                })
+                .collect();
+            ApiResponse::ok(users)
+        }
+        Err(e) => {
+            tracing::error!("Failed to look up users by name: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to look up users.",
+            )
+        }
+    }
+}
+
+/// Handles requests to re-encrypt users' personal information bound to its record and field.
+///
+/// This route is restricted to admins. Values encrypted before the binding was introduced stay
+/// readable, but aren't protected against being swapped between users or fields until they are
+/// migrated. Missing blind indexes of names are added as well. The migration is recorded in the
+/// audit log and can be repeated safely.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the number of migrated and failed users or an error.
+#[post("admin/encryption/migrate")]
+pub(super) async fn migrate_field_encryption(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<FieldEncryptionMigration> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    match db.migrate_field_encryption().await {
+        Ok(migration) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "migrate_field_encryption",
+                    Vec::new(),
+                    format!(
+                        "Re-encrypted personal information of {} users, {} failed",
+                        migration.migrated, migration.failed
+                    ),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(migration).with_message("Field encryption migrated.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to migrate field encryption: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to migrate field encryption.",
+            )
+        }
+    }
+}
+
+/// Changes a user's role and records the change in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `admin_id` - The ID of the admin changing the role.
+/// * `user_id` - The ID of the user whose role is changed.
+/// * `expected` - The role the user must currently have.
+/// * `role` - The new role.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the change.
+async fn change_role(
+    db: &Database,
+    admin_id: String,
+    user_id: String,
+    expected: Role,
+    role: Role,
+) -> ApiResponse<()> {
+    match db.change_user_role(user_id.clone(), expected, role).await {
+        Ok(true) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "change_user_role",
+                    vec![user_id],
+                    format!(
+                        "Changed role from {} to {}",
+                        expected.as_str(),
+                        role.as_str()
+                    ),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::message(format!("User is now a {}.", role.as_str()))
+        }
+        Ok(false) => ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            format!("No user with the {} role found.", expected.as_str()),
+        ),
+        Err(e) => {
+            tracing::error!("Failed to change user role: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to change user role.",
+            )
+        }
+    }
+}
+
+/// Handles requests to mint a time-limited token for acting as a user.
+///
+/// This route is restricted to admins and lets support reproduce user-reported issues. The token
+/// expires after `IMPERSONATION_VALIDITY_MINUTES`, cannot reach staff routes and names the admin
+/// in its `impersonated_by` claim. Every impersonation is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the user ID.
+/// * `body` - JSON payload containing the reason for the impersonation.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the impersonation token or an error.
+#[post("admin/users/{id}/impersonate")]
+pub(super) async fn impersonate_user(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ImpersonationRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Impersonation request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let user_id = path.into_inner();
+    if user_id == admin_id {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "You cannot impersonate yourself.");
+    }
+    match db.get_user_by_id(user_id.clone()).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "User not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to load user for impersonation: {:?}", e);
+            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load user.");
+        }
+    }
+
+    // Record the impersonation before handing out the token, so no session goes unaudited
+    if let Err(e) = db
+        .record_audit_entry(
+            admin_id.clone(),
+            "impersonate_user",
+            vec![user_id.clone()],
+            format!(
+                "Impersonation for {} minutes: {}",
+                IMPERSONATION_VALIDITY_MINUTES, body.reason
+            ),
+        )
+        .await
+    {
+        tracing::error!("Failed to record audit entry: {:?}", e);
+        return ApiResponse::error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to record impersonation.",
+        );
+    }
+
+    match generate_impersonation_jwt(user_id, admin_id) {
+        Ok(token) => ApiResponse::ok(json!({
+            "token": token,
+            "expires_in_minutes": IMPERSONATION_VALIDITY_MINUTES
+        }))
+        .with_message("Impersonation token created."),
+        Err(e) => {
+            tracing::error!("Failed to generate impersonation token: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create impersonation token.",
+            )
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! src/server/appeals.rs
+//!
+//! This module defines the routes users use to review moderation actions against them and to appeal them.
+
+use super::{overloaded, record_auth_failure};
+use crate::config::ConfigHandle;
+use crate::database::appeals::Appeal;
+use crate::database::moderation::{AppealState, ModerationAction, SanctionKind};
+use crate::database::{Database, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileRead, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the appeal request body for logged-in users
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct AppealRequest {
+    #[validate(length(min = 1, message = "Moderation action ID is required"))]
+    action_id: String,
+    #[validate(length(
+        min = 20,
+        max = 2000,
+        message = "Message must be 20 to 2000 characters long"
+    ))]
+    message: String,
+}
+
+/// Struct representing the appeal request body for banned users, who can't log in
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct BannedAppealRequest {
+    #[validate(email(message = "Email is invalid"))]
+    email: String,
+    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
+    password: String,
+    #[validate(length(
+        min = 20,
+        max = 2000,
+        message = "Message must be 20 to 2000 characters long"
+    ))]
+    message: String,
+}
+
+/// Submits an appeal on behalf of a user after checking that the moderation action affects them.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the user submitting the appeal.
+/// * `action_id` - The ID of the appealed moderation action.
+/// * `message` - The user's explanation.
+/// * `only_kind` - Restricts the appeal to actions of this kind (optional).
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the submission.
+async fn submit_appeal(
+    db: &Database,
+    user_id: String,
+    action_id: String,
+    message: String,
+    only_kind: Option<SanctionKind>,
+) -> ApiResponse<Appeal> {
+    let action = match db.get_moderation_action(action_id.clone()).await {
+        Ok(Some(action))
+            if action.user_id == user_id && only_kind.is_none_or(|kind| kind == action.kind) =>
+        {
+            action
+        }
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Moderation action not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve moderation action: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to submit appeal.",
+            );
+        }
+    };
+
+    if action.appeal_state != AppealState::None {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "This moderation action has already been appealed.",
+        );
+    }
+
+    match db.create_appeal(action_id, user_id, message).await {
+        Ok(Some(appeal)) => {
+            ApiResponse::created(appeal).with_message("Appeal submitted successfully.")
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "This moderation action has already been appealed.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to create appeal: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to submit appeal.",
+            )
+        }
+    }
+}
+
+/// Handles appeals submitted by logged-in users, e.g. against the removal of one of their offers.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the moderation action ID and the appeal message.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the submission.
+#[post("appeals")]
+pub(super) async fn create_appeal(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<AppealRequest>,
+) -> ApiResponse<Appeal> {
+    let user_id = auth.user_id;
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Appeal request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let body = body.into_inner();
+    submit_appeal(&db, user_id, body.action_id, body.message, None).await
+}
+
+/// Handles ban appeals from banned users.
+///
+/// Banned users can't log in, so this limited pre-auth endpoint verifies their credentials
+/// directly and only allows appealing the ban itself. They can't read the notification naming
+/// the moderation action either, so the appeal is filed against their latest ban. Failures are audited like failed logins,
+/// deleted accounts are treated as unknown like at login, and users who aren't banned get the
+/// same response as for a wrong password.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `config` - The runtime configuration, deciding whether failures hide account existence.
+/// * `body` - JSON payload containing the user's credentials and the appeal message.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the submission.
+#[post("/auth/appeal")]
+pub(super) async fn create_ban_appeal(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    body: web::Json<BannedAppealRequest>,
+) -> ApiResponse<Appeal> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Ban appeal request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let body = body.into_inner();
+    let result = db
+        .verify_credentials(body.email.clone(), body.password)
+        .await
+        .and_then(|user| {
+            if user.deleted_at.is_some() {
+                tracing::warn!(
+                    "Deleted user attempted to appeal a ban: {}",
+                    user.email_hash
+                );
+                Err(CustomError::UserNotFound)
+            } else if user.banned {
+                Ok(user)
+            } else {
+                Err(CustomError::UserNotBanned)
+            }
+        });
+    let user = match result {
+        Ok(user) => user,
+        Err(CustomError::Overloaded) => return overloaded(),
+        Err(e) => {
+            tracing::warn!("Ban appeal credential check failed: {:?}", e);
+            record_auth_failure(&db, "appeal_auth_failed", &body.email, &e).await;
+            let privacy_mode = config.current().auth_privacy_mode;
+            return ApiResponse::error(
+                StatusCode::UNAUTHORIZED,
+                e.public_auth_message(privacy_mode),
+            );
+        }
+    };
+
+    let user_id = record_key(&user.id);
+    let action = match db.get_latest_ban_action(user_id.clone()).await {
+        Ok(Some(action)) => action,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Moderation action not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve ban of user {}: {:?}", user_id, e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to submit appeal.",
+            );
+        }
+    };
+
+    submit_appeal(
+        &db,
+        user_id,
+        record_key(&action.id),
+        body.message,
+        Some(SanctionKind::UserBanned),
+    )
+    .await
+}
+
+/// Handles requests to list the moderation actions affecting the authenticated user.
+///
+/// The response includes the appeal state of every action.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of moderation actions or an error.
+#[get("user/moderation-actions")]
+pub(super) async fn get_my_moderation_actions(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+) -> ApiResponse<Vec<ModerationAction>> {
+    let user_id = auth.user_id;
+
+    match db.get_moderation_actions_for_user(user_id).await {
+        Ok(actions) => ApiResponse::ok(actions),
+        Err(e) => {
+            tracing::error!("Failed to retrieve moderation actions: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve moderation actions.",
+            )
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! src/server/auctions.rs
+//!
+//! This module defines the routes of timed auctions: sellers start an auction for an active
+//! offer, buyers bid on it, and anyone can follow the bids. It also runs the background job that
+//! closes ended auctions, reserves the offer for the winner and notifies both parties.
+
+use super::notify;
+use crate::database::auctions::{
+    ANTI_SNIPING_MINUTES, Auction, AuctionBid, MAX_AUCTION_DAYS, MIN_AUCTION_HOURS,
+};
+use crate::database::offer_status::OfferStatus;
+use crate::database::{Database, record_key};
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersRead, OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// How often the closing job looks for ended auctions.
+const CLOSING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The name of the closing job in the task metrics.
+const CLOSING_TASK: &str = "auction_closing";
+
+/// Struct representing the request body to start an auction
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct StartAuctionRequest {
+    #[validate(range(min = 0.0, message = "The starting price cannot be negative"))]
+    starting_price: f64,
+    #[validate(range(exclusive_min = 0.0, message = "The bid increment must be positive"))]
+    bid_increment: f64,
+    /// The RFC 3339 timestamp when the auction ends.
+    ends_at: String,
+}
+
+/// Struct representing the request body of an auction bid
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct AuctionBidRequest {
+    #[validate(range(exclusive_min = 0.0, message = "The amount must be positive"))]
+    amount: f64,
+}
+
+/// An auction with its most recent bids.
+#[derive(Debug, Serialize)]
+pub(super) struct AuctionDetails {
+    auction: Auction,
+    /// The lowest amount the next bid may have.
+    minimum_bid: f64,
+    bids: Vec<AuctionBid>,
+}
+
+/// Starts the background job that closes ended auctions.
+///
+/// The offer of an auction that closed with bids is reserved for the winner. The winner and the
+/// seller are notified either way.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+pub(super) fn spawn_closing_job(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLOSING_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            let closed = match db.close_ended_auctions().await {
+                Ok(closed) => {
+                    let metrics = TaskMetrics::global();
+                    metrics.record_run(CLOSING_TASK, TaskOutcome::Success, started.elapsed());
+                    closed
+                }
+                Err(e) => {
+                    TaskMetrics::global().record_run(
+                        CLOSING_TASK,
+                        TaskOutcome::Failure,
+                        started.elapsed(),
+                    );
+                    tracing::error!("Failed to close ended auctions: {:?}", e);
+                    continue;
+                }
+            };
+            for auction in closed {
+                settle_auction(&db, &auction).await;
+            }
+        }
+    });
+}
+
+/// Reserves the offer of a closed auction for its winner and notifies the winner and the seller.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `auction` - The closed auction.
+async fn settle_auction(db: &Database, auction: &Auction) {
+    let title = match db.get_offer_by_id(auction.offer_id.clone()).await {
+        Ok(Some(offer)) => {
+            if auction.winner_id.is_some() && offer.status == OfferStatus::Active {
+                let reserved = db
+                    .transition_offer_status(&offer, OfferStatus::Reserved)
+                    .await;
+                if let Err(e) = reserved {
+                    tracing::error!(
+                        "Failed to reserve offer {} for auction winner: {:?}",
+                        auction.offer_id,
+                        e
+                    );
+                }
+            }
+            offer.game_title
+        }
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer of closed auction: {:?}", e);
+            return;
+        }
+    };
+
+    let (Some(winner_id), Some(highest_bid)) = (&auction.winner_id, auction.highest_bid) else {
+        let body = format!("The auction of \"{}\" ended without bids.", title);
+        notify(
+            db,
+            &auction.seller_id,
+            "auction_ended",
+            "Your auction ended",
+            body,
+        )
+        .await;
+        return;
+    };
+    let body = format!(
+        "You won the auction of \"{}\" with {:.2}. The offer is reserved for you.",
+        title, highest_bid
+    );
+    notify(db, winner_id, "auction_won", "You won an auction", body).await;
+    let body = format!(
+        "The auction of \"{}\" ended with a winning bid of {:.2} after {} bids. The offer is reserved for the winner.",
+        title, highest_bid, auction.bid_count
+    );
+    notify(
+        db,
+        &auction.seller_id,
+        "auction_ended",
+        "Your auction ended",
+        body,
+    )
+    .await;
+}
+
+/// Handles requests to sell an offer by auction.
+///
+/// Only the seller can start an auction, only for an active offer of a single copy, and only
+/// once. The auction
+/// must run between `MIN_AUCTION_HOURS` hours and `MAX_AUCTION_DAYS` days. Open price proposals
+/// on the offer are declined.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the starting price, bid increment and end time.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created auction or an error.
+#[post("offers/{offer_id}/auction")]
+pub(super) async fn start_auction(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<StartAuctionRequest>,
+) -> ApiResponse<Auction> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Auction request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let Ok(ends_at) = DateTime::parse_from_rfc3339(&body.ends_at) else {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "ends_at must be an RFC 3339 timestamp.",
+        );
+    };
+    let ends_at = ends_at.with_timezone(&Utc);
+    let now = Utc::now();
+    if ends_at < now + ChronoDuration::hours(MIN_AUCTION_HOURS)
+        || ends_at > now + ChronoDuration::days(MAX_AUCTION_DAYS)
+    {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "An auction must run between {} hour(s) and {} days.",
+                MIN_AUCTION_HOURS, MAX_AUCTION_DAYS
+            ),
+        );
+    }
+
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for auction: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    if !offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to auction this offer.",
+        );
+    }
+    if !offer.is_listed() || offer.status != OfferStatus::Active {
+        return ApiResponse::error(StatusCode::CONFLICT, "Only active offers can be auctioned.");
+    }
+    if offer.quantity > 1 {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Offers of several copies are only sold at their asking price.",
+        );
+    }
+
+    match db
+        .create_auction(
+            offer_id.clone(),
+            auth.user_id,
+            body.starting_price,
+            body.bid_increment,
+            ends_at.to_rfc3339(),
+        )
+        .await
+    {
+        Ok(Some(auction)) => {
+            match db.decline_open_bids(offer_id).await {
+                Ok(declined) => {
+                    for bid in declined {
+                        let body = format!(
+                            "\"{}\" is now sold by auction, so your price proposal was declined. You can bid in the auction instead.",
+                            offer.game_title
+                        );
+                        notify(
+                            &db,
+                            &bid.buyer_id,
+                            "bid_declined",
+                            "Your bid was declined",
+                            body,
+                        )
+                        .await;
+                    }
+                }
+                Err(e) => tracing::error!("Failed to decline bids on auctioned offer: {:?}", e),
+            }
+            ApiResponse::created(auction).with_message("Auction started.")
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "This offer has already been auctioned.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to start auction: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start auction.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the auction of an offer and its most recent bids.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the auction details or an error.
+#[get("offers/{offer_id}/auction")]
+pub(super) async fn get_auction(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    path: web::Path<String>,
+) -> ApiResponse<AuctionDetails> {
+    let offer_id = path.into_inner();
+    match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) if offer.is_listed() || offer.is_seller(&auth.user_id) => {}
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for auction: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    }
+
+    let auction = match db.get_auction(offer_id.clone()).await {
+        Ok(Some(auction)) => auction,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "This offer is not auctioned.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve auction: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve auction.",
+            );
+        }
+    };
+    match db.get_auction_bids(offer_id).await {
+        Ok(bids) => ApiResponse::ok(AuctionDetails {
+            minimum_bid: auction.minimum_bid(),
+            auction,
+            bids,
+        }),
+        Err(e) => {
+            tracing::error!("Failed to retrieve auction bids: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve auction.",
+            )
+        }
+    }
+}
+
+/// Handles requests to bid in the auction of an offer.
+///
+/// The first bid must be at least the starting price, every later bid at least one increment
+/// above the highest bid. A bid in the last `ANTI_SNIPING_MINUTES` minutes extends the auction.
+/// The seller and the outbid bidder are notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the amount.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the auction after the bid or an error.
+#[post("offers/{offer_id}/auction/bids")]
+pub(super) async fn place_auction_bid(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<AuctionBidRequest>,
+) -> ApiResponse<Auction> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Auction bid validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) if offer.is_listed() => offer,
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to bid on: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    if offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "You cannot bid on your own offer.");
+    }
+
+    match db
+        .place_auction_bid(offer_id.clone(), auth.user_id.clone(), body.amount)
+        .await
+    {
+        Ok(Some((before, after))) => {
+            if let Some(outbid) = before
+                .highest_bidder_id
+                .as_deref()
+                .filter(|outbid| *outbid != auth.user_id)
+            {
+                let body = format!(
+                    "Someone bid {:.2} on \"{}\". Bid at least {:.2} to stay in the auction.",
+                    body.amount,
+                    offer.game_title,
+                    after.minimum_bid()
+                );
+                notify(&db, outbid, "auction_outbid", "You were outbid", body).await;
+            }
+            let seller_body = format!(
+                "Someone bid {:.2} on your auction of \"{}\".",
+                body.amount, offer.game_title
+            );
+            notify(
+                &db,
+                &record_key(&offer.seller_id),
+                "auction_bid_received",
+                "New bid on your auction",
+                seller_body,
+            )
+            .await;
+            let message = if after.ends_at != before.ends_at {
+                format!(
+                    "Bid placed. The auction was extended by up to {} minutes.",
+                    ANTI_SNIPING_MINUTES
+                )
+            } else {
+                "Bid placed.".to_string()
+            };
+            ApiResponse::ok(after).with_message(message)
+        }
+        // Re-read the auction to tell why the bid was rejected
+        Ok(None) => match db.get_auction(offer_id).await {
+            Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "This offer is not auctioned."),
+            Ok(Some(auction)) if auction.minimum_bid() > body.amount => ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!("The bid must be at least {:.2}.", auction.minimum_bid()),
+            ),
+            Ok(Some(_)) => ApiResponse::error(StatusCode::CONFLICT, "The auction has ended."),
+            Err(e) => {
+                tracing::error!("Failed to retrieve auction after rejected bid: {:?}", e);
+                ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to place bid.")
+            }
+        },
+        Err(e) => {
+            tracing::error!("Failed to place auction bid: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to place bid.")
+        }
+    }
+}
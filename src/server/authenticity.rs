@@ -0,0 +1,282 @@
+//! src/server/authenticity.rs
+//!
+//! This module defines the routes of the authenticity verification workflow: sellers submit
+//! photos of a high-value listing, and admins review them against a checklist to grant the
+//! "authenticated" badge.
+
+use super::admin::require_admin;
+use crate::database::Database;
+use crate::database::authenticity::{
+    AuthenticationRequest, AuthenticationState, AuthenticityChecklist, MIN_AUTHENTICATION_PRICE,
+};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, post, web};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+use validator_derive::Validate;
+
+/// Struct representing the request authentication request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct RequestAuthenticationRequest {
+    #[validate(
+        length(min = 1, max = 10, message = "Provide 1 to 10 photos"),
+        custom(function = "validate_photo_urls")
+    )]
+    photo_urls: Vec<String>,
+}
+
+/// Struct representing the query parameters of the authentication queue
+#[derive(Debug, Deserialize)]
+struct AuthenticationQueueQuery {
+    state: Option<AuthenticationState>,
+}
+
+/// Struct representing the authentication review request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct AuthenticationReviewRequest {
+    checklist: AuthenticityChecklist,
+    #[validate(length(max = 2000, message = "Note must be at most 2000 characters long"))]
+    note: Option<String>,
+}
+
+/// Ensures every photo link is an HTTPS URL.
+fn validate_photo_urls(photo_urls: &[String]) -> Result<(), ValidationError> {
+    if photo_urls
+        .iter()
+        .all(|url| url.starts_with("https://") && url.len() <= 2048)
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("photo_urls")
+            .with_message("Photos must be HTTPS links of at most 2048 characters".into()))
+    }
+}
+
+/// Handles requests to submit a listing for authentication.
+///
+/// Only the seller can submit a listing, and only if its price is at least
+/// `MIN_AUTHENTICATION_PRICE`.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing links to the photos of the item.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created authentication request or an error.
+#[post("offers/{offer_id}/authentication")]
+pub(super) async fn request_authentication(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<RequestAuthenticationRequest>,
+) -> ApiResponse<AuthenticationRequest> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Request authentication validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for authentication: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+
+    if !offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to submit this offer for authentication.",
+        );
+    }
+    if offer.authenticated {
+        return ApiResponse::error(StatusCode::CONFLICT, "Offer is already authenticated.");
+    }
+    if offer.price < MIN_AUTHENTICATION_PRICE {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Only offers priced at {:.2} or more can be authenticated.",
+                MIN_AUTHENTICATION_PRICE
+            ),
+        );
+    }
+
+    match db
+        .request_authentication(offer_id, auth.user_id, body.into_inner().photo_urls)
+        .await
+    {
+        Ok(Some(request)) => {
+            ApiResponse::created(request).with_message("Offer submitted for authentication.")
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Offer is already waiting for authentication.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to request authentication: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to submit offer for authentication.",
+            )
+        }
+    }
+}
+
+/// Handles requests to list authentication requests, oldest first.
+///
+/// This route is restricted to admins. Pass `state=pending` to only list requests awaiting review.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the state to filter by (optional).
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of authentication requests or an error.
+#[get("admin/authentications")]
+pub(super) async fn get_authentication_requests(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<AuthenticationQueueQuery>,
+) -> ApiResponse<Vec<AuthenticationRequest>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_authentication_requests(query.state).await {
+        Ok(requests) => ApiResponse::ok(requests),
+        Err(e) => {
+            tracing::error!("Failed to retrieve authentication requests: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve authentication requests.",
+            )
+        }
+    }
+}
+
+/// Handles requests to review a pending authentication request.
+///
+/// This route is restricted to admins. The listing is authenticated only if every item of the
+/// checklist passed. The seller is notified of the outcome and the review is recorded in the
+/// audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the authentication request ID.
+/// * `body` - JSON payload containing the checklist and an optional note.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the reviewed authentication request or an error.
+#[post("admin/authentications/{id}/review")]
+pub(super) async fn review_authentication_request(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<AuthenticationReviewRequest>,
+) -> ApiResponse<AuthenticationRequest> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Authentication review request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let request_id = path.into_inner();
+    let body = body.into_inner();
+    let request = match db
+        .review_authentication_request(
+            request_id.clone(),
+            body.checklist,
+            admin_id.clone(),
+            body.note,
+        )
+        .await
+    {
+        Ok(Some(request)) => request,
+        Ok(None) => {
+            return ApiResponse::error(
+                StatusCode::NOT_FOUND,
+                "Pending authentication request not found.",
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to review authentication request: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to review authentication request.",
+            );
+        }
+    };
+
+    let authenticated = request.state == AuthenticationState::Authenticated;
+    let (title, mut body_text) = if authenticated {
+        (
+            "Your offer was authenticated",
+            "Your offer passed the authenticity check and now carries the \"authenticated\" badge."
+                .to_string(),
+        )
+    } else {
+        (
+            "Your offer could not be authenticated",
+            "Your offer did not pass the authenticity check.".to_string(),
+        )
+    };
+    if let Some(note) = &request.review_note {
+        body_text.push_str(&format!("\n\nNote from the reviewer: {}", note));
+    }
+    if let Err(e) = db
+        .create_notification(
+            request.seller_id.clone(),
+            "authentication_reviewed",
+            title.to_string(),
+            body_text,
+        )
+        .await
+    {
+        tracing::error!(
+            "Failed to notify seller about authentication review: {:?}",
+            e
+        );
+    }
+
+    if let Err(e) = db
+        .record_audit_entry(
+            admin_id,
+            if authenticated {
+                "approve_authentication"
+            } else {
+                "reject_authentication"
+            },
+            vec![request_id, request.offer_id.clone()],
+            format!("Authentication checklist: {:?}", body.checklist),
+        )
+        .await
+    {
+        tracing::error!("Failed to record audit entry: {:?}", e);
+    }
+
+    ApiResponse::ok(request).with_message("Authentication request reviewed successfully.")
+}
@@ -0,0 +1,459 @@
+//! src/server/bids.rs
+//!
+//! This module defines the routes of the best-offer negotiation: buyers bid on offers, sellers
+//! accept, decline or counter bids, and buyers answer counters. Accepting a bid reserves the
+//! offer and declines every other open bid on it. Both parties are notified of every step.
+
+use super::notify;
+use crate::database::bids::{Bid, BidStatus};
+use crate::database::offer_status::OfferStatus;
+use crate::database::{Database, Offer, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::response::{ApiError, ApiResponse};
+use crate::scopes::{OffersRead, OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the request body of a bid or a counter.
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct BidRequest {
+    #[validate(range(exclusive_min = 0.0, message = "The amount must be positive"))]
+    amount: f64,
+}
+
+/// Loads an offer and one of its open bids, and ensures the bid is waiting for the authenticated
+/// user's answer.
+///
+/// Bids are private, so users who are neither the buyer nor the seller get a 404.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the authenticated user.
+/// * `offer_id` - The ID of the offer.
+/// * `bid_id` - The ID of the bid.
+///
+/// # Returns
+///
+/// A `Result` containing the offer and the bid, or the `ApiError` to return.
+async fn require_bid_awaiting(
+    db: &Database,
+    user_id: &str,
+    offer_id: String,
+    bid_id: String,
+) -> Result<(Offer, Bid), ApiError> {
+    let internal = |e: CustomError| {
+        tracing::error!("Failed to retrieve bid: {:?}", e);
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to retrieve bid.")
+    };
+    let not_found = || ApiError::new(StatusCode::NOT_FOUND, "Bid not found.");
+    let bid = db
+        .get_bid(bid_id)
+        .await
+        .map_err(internal)?
+        .filter(|bid| {
+            bid.offer_id == offer_id && (bid.buyer_id == user_id || bid.seller_id == user_id)
+        })
+        .ok_or_else(not_found)?;
+    let offer = db
+        .get_offer_by_id(offer_id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(not_found)?;
+
+    match bid.awaiting() {
+        Some(awaiting) if awaiting == user_id => Ok((offer, bid)),
+        Some(_) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "The bid is waiting for the other party's answer.",
+        )),
+        None => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "The bid is no longer open.",
+        )),
+    }
+}
+
+/// Returns the ID of the party that isn't the given user.
+fn other_party<'a>(bid: &'a Bid, user_id: &str) -> &'a str {
+    if bid.buyer_id == user_id {
+        &bid.seller_id
+    } else {
+        &bid.buyer_id
+    }
+}
+
+/// The response to an answer that lost the race against another answer to the same bid.
+fn bid_changed() -> ApiResponse<Bid> {
+    ApiResponse::error(
+        StatusCode::CONFLICT,
+        "The bid changed in the meantime. Please try again.",
+    )
+}
+
+/// Handles requests to bid on an offer.
+///
+/// Only active offers of a single copy by other sellers can be bid on, the amount must not exceed
+/// the asking price, and each buyer can have one open bid per offer. The seller is notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the proposed price.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created bid or an error.
+#[post("offers/{offer_id}/bids")]
+pub(super) async fn create_bid(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<BidRequest>,
+) -> ApiResponse<Bid> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Bid request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) if offer.is_listed() => offer,
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to bid on: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    if offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "You cannot bid on your own offer.");
+    }
+    if offer.status != OfferStatus::Active {
+        return ApiResponse::error(StatusCode::CONFLICT, "The offer is no longer available.");
+    }
+    if offer.quantity > 1 {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Offers of several copies are only sold at their asking price.",
+        );
+    }
+    match db.get_auction(offer_id.clone()).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return ApiResponse::error(
+                StatusCode::CONFLICT,
+                "This offer is sold by auction. Bid in the auction instead.",
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to check for an auction of the offer: {:?}", e);
+            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create bid.");
+        }
+    }
+    if body.amount > offer.price {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "The amount cannot exceed the asking price.",
+        );
+    }
+
+    let seller_id = record_key(&offer.seller_id);
+    match db
+        .create_bid(offer_id, auth.user_id, seller_id.clone(), body.amount)
+        .await
+    {
+        Ok(Some(bid)) => {
+            let body = format!(
+                "You received a bid of {:.2} on your offer \"{}\" (asking {:.2}).",
+                bid.amount, offer.game_title, offer.price
+            );
+            notify(
+                &db,
+                &seller_id,
+                "bid_received",
+                "New bid on your offer",
+                body,
+            )
+            .await;
+            ApiResponse::created(bid).with_message("Bid sent to the seller.")
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "You already have an open bid on this offer.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to create bid: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create bid.")
+        }
+    }
+}
+
+/// Handles requests to list the bids on an offer.
+///
+/// The seller sees every bid on the offer; anyone else sees only their own bids.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the bids, newest first, or an error.
+#[get("offers/{offer_id}/bids")]
+pub(super) async fn get_bids(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    path: web::Path<String>,
+) -> ApiResponse<Vec<Bid>> {
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for bids: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    let buyer_id = (!offer.is_seller(&auth.user_id)).then_some(auth.user_id);
+
+    match db.get_bids_for_offer(offer_id, buyer_id).await {
+        Ok(bids) => ApiResponse::ok(bids),
+        Err(e) => {
+            tracing::error!("Failed to retrieve bids: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve bids.",
+            )
+        }
+    }
+}
+
+/// Handles requests to accept a bid.
+///
+/// The seller accepts pending bids, the buyer accepts counters. The offer is reserved for the
+/// buyer at the agreed price, every other open bid on it is declined, and everyone involved is
+/// notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID and the bid ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the accepted bid or an error.
+#[post("offers/{offer_id}/bids/{bid_id}/accept")]
+pub(super) async fn accept_bid(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<(String, String)>,
+) -> ApiResponse<Bid> {
+    let (offer_id, bid_id) = path.into_inner();
+    let (offer, bid) = match require_bid_awaiting(&db, &auth.user_id, offer_id, bid_id).await {
+        Ok(loaded) => loaded,
+        Err(error) => return error.into(),
+    };
+
+    // Reserving first means a bid is never accepted for an offer that is gone
+    let reserved = match db
+        .transition_offer_status(&offer, OfferStatus::Reserved)
+        .await
+    {
+        Ok(Some(reserved)) => reserved,
+        Ok(None) | Err(CustomError::InvalidStatusTransition(..)) => {
+            return ApiResponse::error(StatusCode::CONFLICT, "The offer is no longer available.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to reserve offer for bid: {:?}", e);
+            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to accept bid.");
+        }
+    };
+    let accepted = match db.answer_bid(&bid, BidStatus::Accepted, None).await {
+        Ok(Some(accepted)) => accepted,
+        result => {
+            if let Err(e) = &result {
+                tracing::error!("Failed to accept bid: {:?}", e);
+            }
+            if let Err(e) = db
+                .transition_offer_status(&reserved, OfferStatus::Active)
+                .await
+            {
+                tracing::error!(
+                    "Failed to release reservation of offer {}: {:?}",
+                    offer.id,
+                    e
+                );
+            }
+            return match result {
+                Ok(_) => bid_changed(),
+                Err(_) => {
+                    ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to accept bid.")
+                }
+            };
+        }
+    };
+
+    let body = format!(
+        "Your bid on \"{}\" was accepted at {:.2}. The offer is reserved for you.",
+        offer.game_title,
+        accepted.agreed_amount()
+    );
+    let other = other_party(&accepted, &auth.user_id);
+    notify(&db, other, "bid_accepted", "A bid was accepted", body).await;
+
+    match db.decline_open_bids(accepted.offer_id.clone()).await {
+        Ok(declined) => {
+            for bid in declined {
+                let body = format!(
+                    "\"{}\" was reserved for another buyer, so your bid was declined.",
+                    offer.game_title
+                );
+                notify(
+                    &db,
+                    &bid.buyer_id,
+                    "bid_declined",
+                    "Your bid was declined",
+                    body,
+                )
+                .await;
+            }
+        }
+        Err(e) => tracing::error!(
+            "Failed to decline other bids on offer {}: {:?}",
+            offer.id,
+            e
+        ),
+    }
+
+    ApiResponse::ok(accepted).with_message("Bid accepted. The offer is now reserved.")
+}
+
+/// Handles requests to decline a bid.
+///
+/// The seller declines pending bids, the buyer declines counters. The other party is notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID and the bid ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the declined bid or an error.
+#[post("offers/{offer_id}/bids/{bid_id}/decline")]
+pub(super) async fn decline_bid(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<(String, String)>,
+) -> ApiResponse<Bid> {
+    let (offer_id, bid_id) = path.into_inner();
+    let (offer, bid) = match require_bid_awaiting(&db, &auth.user_id, offer_id, bid_id).await {
+        Ok(loaded) => loaded,
+        Err(error) => return error.into(),
+    };
+
+    match db.answer_bid(&bid, BidStatus::Declined, None).await {
+        Ok(Some(declined)) => {
+            let body = format!(
+                "Your proposal of {:.2} on \"{}\" was declined.",
+                bid.agreed_amount(),
+                offer.game_title
+            );
+            let other = other_party(&declined, &auth.user_id);
+            notify(&db, other, "bid_declined", "A bid was declined", body).await;
+            ApiResponse::ok(declined).with_message("Bid declined.")
+        }
+        Ok(None) => bid_changed(),
+        Err(e) => {
+            tracing::error!("Failed to decline bid: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to decline bid.")
+        }
+    }
+}
+
+/// Handles requests to counter a bid with another price.
+///
+/// Only the seller can counter, and only pending bids. The counter must lie between the bid and
+/// the asking price. The buyer is notified and can accept or decline the counter.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID and the bid ID.
+/// * `body` - JSON payload containing the counter price.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the countered bid or an error.
+#[post("offers/{offer_id}/bids/{bid_id}/counter")]
+pub(super) async fn counter_bid(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<(String, String)>,
+    body: web::Json<BidRequest>,
+) -> ApiResponse<Bid> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Counter request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let (offer_id, bid_id) = path.into_inner();
+    let (offer, bid) = match require_bid_awaiting(&db, &auth.user_id, offer_id, bid_id).await {
+        Ok(loaded) => loaded,
+        Err(error) => return error.into(),
+    };
+    if bid.status != BidStatus::Pending {
+        return ApiResponse::error(StatusCode::CONFLICT, "Only the seller can counter a bid.");
+    }
+    if body.amount <= bid.amount || body.amount > offer.price {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "The counter must be above the bid and at most the asking price.",
+        );
+    }
+
+    match db
+        .answer_bid(&bid, BidStatus::Countered, Some(body.amount))
+        .await
+    {
+        Ok(Some(countered)) => {
+            let body = format!(
+                "The seller of \"{}\" proposed {:.2} instead of your bid of {:.2}.",
+                offer.game_title, body.amount, bid.amount
+            );
+            notify(
+                &db,
+                &countered.buyer_id,
+                "bid_countered",
+                "Your bid was countered",
+                body,
+            )
+            .await;
+            ApiResponse::ok(countered).with_message("Counter sent to the buyer.")
+        }
+        Ok(None) => bid_changed(),
+        Err(e) => {
+            tracing::error!("Failed to counter bid: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to counter bid.")
+        }
+    }
+}
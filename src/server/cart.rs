@@ -0,0 +1,425 @@
+//! src/server/cart.rs
+//!
+//! This module defines the routes of the shopping cart: buyers collect active offers and check
+//! them out together. Checkout re-validates every offer and its price, and places one order per
+//! offer through the regular purchase flow.
+
+use super::orders::{CheckoutQuery, place_order, tax_country};
+use super::promo_codes::checkout_promo;
+use crate::database::Database;
+use crate::database::cart::{CartItem, MAX_CART_ITEMS};
+use crate::database::offer_status::OfferStatus;
+use crate::database::orders::Order;
+use crate::database::preferences::Currency;
+use crate::database::{Offer, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::exchange_rates::ExchangeRateCache;
+use crate::response::{ApiError, ApiResponse};
+use crate::scopes::{OffersRead, OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web};
+use serde::{Deserialize, Serialize};
+
+/// Struct representing the add cart item request body
+#[derive(Debug, Deserialize)]
+pub(super) struct AddCartItemRequest {
+    offer_id: String,
+}
+
+/// An item of the cart together with the current state of its offer.
+#[derive(Debug, Serialize)]
+pub(super) struct CartEntry {
+    /// The cart item, with the price the buyer last saw.
+    #[serde(flatten)]
+    item: CartItem,
+    /// The offer, or `None` if it no longer exists.
+    offer: Option<Offer>,
+    /// Why the offer can't be bought, or `None` if it can.
+    unavailable: Option<String>,
+}
+
+impl CartEntry {
+    /// Returns whether the price of the offer changed since the buyer last saw it.
+    fn price_changed(&self) -> bool {
+        self.offer
+            .as_ref()
+            .is_some_and(|offer| offer.price != self.item.price)
+    }
+}
+
+/// The cart of a user.
+#[derive(Debug, Serialize)]
+pub(super) struct Cart {
+    /// The items, oldest first.
+    items: Vec<CartEntry>,
+    /// The sum of the current prices of the offers that can be bought, in `currency`, or `None`
+    /// if the offers are in other currencies and no exchange rates are available.
+    total: Option<f64>,
+    /// The buyer's preferred currency.
+    currency: Currency,
+}
+
+/// An offer that couldn't be ordered at checkout.
+#[derive(Debug, Serialize)]
+pub(super) struct CheckoutFailure {
+    /// The ID of the offer.
+    offer_id: String,
+    /// Why the order failed.
+    message: String,
+}
+
+/// The outcome of a checkout.
+#[derive(Debug, Serialize)]
+pub(super) struct Checkout {
+    /// The placed orders. Their offers were removed from the cart.
+    orders: Vec<Order>,
+    /// The offers that couldn't be ordered. They stay in the cart.
+    failed: Vec<CheckoutFailure>,
+}
+
+/// Returns why a user can't buy an offer, or `None` if they can.
+///
+/// # Arguments
+///
+/// * `offer` - The offer, or `None` if it doesn't exist.
+/// * `auctioned` - Whether the offer is sold by auction.
+/// * `user_id` - The ID of the buyer.
+fn purchase_blocker(offer: Option<&Offer>, auctioned: bool, user_id: &str) -> Option<ApiError> {
+    let Some(offer) = offer.filter(|offer| offer.is_listed()) else {
+        return Some(ApiError::new(StatusCode::NOT_FOUND, "Offer not found."));
+    };
+    if offer.is_seller(user_id) {
+        return Some(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "You cannot buy your own offer.",
+        ));
+    }
+    if offer.status != OfferStatus::Active {
+        return Some(ApiError::new(
+            StatusCode::CONFLICT,
+            "The offer is no longer available.",
+        ));
+    }
+    if auctioned {
+        return Some(ApiError::new(
+            StatusCode::CONFLICT,
+            "This offer is sold by auction. Bid in the auction instead.",
+        ));
+    }
+    None
+}
+
+/// Loads an offer and returns why the user can't buy it.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer_id` - The ID of the offer.
+/// * `user_id` - The ID of the buyer.
+///
+/// # Returns
+///
+/// A `Result` containing the offer (if it exists) and the reason it can't be bought (if any).
+async fn load_offer(
+    db: &Database,
+    offer_id: &str,
+    user_id: &str,
+) -> Result<(Option<Offer>, Option<ApiError>), CustomError> {
+    let offer = db.get_offer_by_id(offer_id.to_string()).await?;
+    let auctioned = match offer {
+        Some(_) => db.get_auction(offer_id.to_string()).await?.is_some(),
+        None => false,
+    };
+    let blocker = purchase_blocker(offer.as_ref(), auctioned, user_id);
+    Ok((offer, blocker))
+}
+
+/// Loads a user's cart with the current state of every offer.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the user.
+///
+/// # Returns
+///
+/// A `Result` containing the `Cart`, or the `ApiError` to return.
+async fn load_cart(db: &Database, user_id: &str) -> Result<Cart, ApiError> {
+    let internal = |e: CustomError| {
+        tracing::error!("Failed to load cart of user {}: {:?}", user_id, e);
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load cart.")
+    };
+    let items = db.get_cart_items(user_id).await.map_err(internal)?;
+    let mut entries = Vec::with_capacity(items.len());
+    for item in items {
+        let (offer, blocker) = load_offer(db, &item.offer_id, user_id)
+            .await
+            .map_err(internal)?;
+        entries.push(CartEntry {
+            item,
+            offer,
+            unavailable: blocker.map(|error| error.message),
+        });
+    }
+    let currency = db
+        .get_user_preferences(user_id.to_string())
+        .await
+        .map_err(internal)?
+        .map(|preferences| preferences.currency)
+        .unwrap_or_default();
+    let rates = ExchangeRateCache::global().rates().await;
+    let total = entries
+        .iter()
+        .filter(|entry| entry.unavailable.is_none())
+        .filter_map(|entry| entry.offer.as_ref())
+        .map(|offer| {
+            if offer.currency == currency {
+                Some(offer.price)
+            } else {
+                rates
+                    .as_ref()?
+                    .convert(offer.price, offer.currency, currency)
+                    .map(|converted| converted.amount)
+            }
+        })
+        .sum::<Option<f64>>()
+        .map(|total| (total * 100.0).round() / 100.0);
+    Ok(Cart {
+        items: entries,
+        total,
+        currency,
+    })
+}
+
+/// Handles requests for the authenticated user's cart.
+///
+/// Every item shows the current state of its offer, so offers that were sold or changed their
+/// price since they were added can be told apart. The total is in the buyer's preferred currency.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the cart or an error.
+#[get("cart")]
+pub(super) async fn get_cart(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+) -> ApiResponse<Cart> {
+    match load_cart(&db, &auth.user_id).await {
+        Ok(cart) => ApiResponse::ok(cart),
+        Err(error) => error.into(),
+    }
+}
+
+/// Handles requests to add an offer to the authenticated user's cart.
+///
+/// Only active offers of other sellers that aren't sold by auction can be added. Adding an offer
+/// doesn't reserve it.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `body` - JSON payload containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created cart item or an error.
+#[post("cart/items")]
+pub(super) async fn add_cart_item(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    body: web::Json<AddCartItemRequest>,
+) -> ApiResponse<CartItem> {
+    let offer = match load_offer(&db, &body.offer_id, &auth.user_id).await {
+        Ok((Some(offer), None)) => offer,
+        Ok((_, blocker)) => {
+            return blocker
+                .unwrap_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Offer not found."))
+                .into();
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for cart: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    match db.get_cart_items(&auth.user_id).await {
+        Ok(items) if items.len() >= MAX_CART_ITEMS => {
+            return ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!("A cart can hold at most {} offers.", MAX_CART_ITEMS),
+            );
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Failed to retrieve cart items: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to add offer to cart.",
+            );
+        }
+    }
+
+    match db.add_cart_item(&auth.user_id, &offer).await {
+        Ok(Some(item)) => ApiResponse::created(item).with_message("Offer added to cart."),
+        Ok(None) => ApiResponse::error(StatusCode::CONFLICT, "This offer is already in your cart."),
+        Err(e) => {
+            tracing::error!("Failed to add offer to cart: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to add offer to cart.",
+            )
+        }
+    }
+}
+
+/// Handles requests to remove an offer from the authenticated user's cart.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the removal.
+#[delete("cart/items/{offer_id}")]
+pub(super) async fn remove_cart_item(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    match db.remove_cart_item(&auth.user_id, &path.into_inner()).await {
+        Ok(true) => ApiResponse::message("Offer removed from cart."),
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "This offer is not in your cart."),
+        Err(e) => {
+            tracing::error!("Failed to remove offer from cart: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to remove offer from cart.",
+            )
+        }
+    }
+}
+
+/// Handles requests to check out the authenticated user's cart.
+///
+/// Every offer must still be available, and its price must match the price the buyer last saw.
+/// Otherwise nothing is ordered and the cart is returned in the error details; changed prices are
+/// updated in the cart, so checking out again confirms them. Each offer is then ordered like a
+/// direct purchase. Ordered offers are removed from the cart, while offers that were bought by
+/// someone else in the meantime stay in it and are reported. The VAT is computed for the country
+/// given as `?country=`, like for a direct purchase. A promo code given as `?promo_code=` is
+/// redeemed once per order; orders it can no longer be redeemed for fail and stay in the cart.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `query` - Query parameters containing the buyer's country and promo code.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the placed orders and the failed offers, or an error.
+#[post("cart/checkout")]
+pub(crate) async fn checkout_cart(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    query: web::Query<CheckoutQuery>,
+) -> ApiResponse<Checkout> {
+    let country = match tax_country(&db, &auth.user_id, &query).await {
+        Ok(country) => country,
+        Err(error) => return error.into(),
+    };
+    let promo = match checkout_promo(&db, query.promo_code.as_deref()).await {
+        Ok(promo) => promo,
+        Err(error) => return error.into(),
+    };
+    let cart = match load_cart(&db, &auth.user_id).await {
+        Ok(cart) => cart,
+        Err(error) => return error.into(),
+    };
+    if cart.items.is_empty() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "Your cart is empty.");
+    }
+    if cart.items.iter().any(|entry| entry.unavailable.is_some()) {
+        return ApiError::new(
+            StatusCode::CONFLICT,
+            "Some offers in your cart are no longer available. Remove them to check out.",
+        )
+        .with_details(cart)
+        .into();
+    }
+    let changed: Vec<&CartEntry> = cart
+        .items
+        .iter()
+        .filter(|entry| entry.price_changed())
+        .collect();
+    if !changed.is_empty() {
+        for entry in changed {
+            let price = entry.offer.as_ref().map_or(entry.item.price, |o| o.price);
+            if let Err(e) = db.update_cart_item_price(&entry.item, price).await {
+                tracing::error!("Failed to update cart item price: {:?}", e);
+            }
+        }
+        return ApiError::new(
+            StatusCode::CONFLICT,
+            "Prices in your cart have changed. Review the new total and check out again.",
+        )
+        .with_details(cart)
+        .into();
+    }
+
+    let mut checkout = Checkout {
+        orders: Vec::new(),
+        failed: Vec::new(),
+    };
+    for entry in cart.items {
+        let Some(offer) = entry.offer else {
+            continue;
+        };
+        match place_order(
+            &db,
+            &offer,
+            &auth.user_id,
+            country.as_deref(),
+            promo.as_ref(),
+        )
+        .await
+        {
+            Ok(order) => {
+                let removed = db
+                    .remove_cart_item(&auth.user_id, &entry.item.offer_id)
+                    .await;
+                if let Err(e) = removed {
+                    tracing::error!("Failed to remove ordered offer from cart: {:?}", e);
+                }
+                checkout.orders.push(order);
+            }
+            Err(error) => checkout.failed.push(CheckoutFailure {
+                offer_id: record_key(&offer.id),
+                message: error.message,
+            }),
+        }
+    }
+
+    if checkout.orders.is_empty() {
+        return ApiError::new(StatusCode::CONFLICT, "None of the offers could be ordered.")
+            .with_details(checkout.failed)
+            .into();
+    }
+    let message = if checkout.failed.is_empty() {
+        "Orders placed."
+    } else {
+        "Some orders were placed. The remaining offers are no longer available."
+    };
+    ApiResponse::created(checkout).with_message(message)
+}
@@ -0,0 +1,273 @@
+//! src/server/chat.rs
+//!
+//! This module defines the WebSocket delivering conversation messages live. Clients send JSON
+//! frames to send messages, show that they are typing and mark conversations as read, and receive
+//! the `ChatEvent`s of all their conversations.
+
+use super::conversations::{deliver_message, require_own_conversation};
+use crate::database::Database;
+use crate::database::conversations::{ChatDelivery, ChatEvent, Conversation, MAX_MESSAGE_LENGTH};
+use crate::response::ApiError;
+use crate::scopes::{OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use actix_ws::{CloseCode, CloseReason, Message as WsMessage, MessageStream, Session};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+/// How often the server pings the client.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// How long the client may stay silent before the connection is closed.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(90);
+/// The largest frame a client may send, in bytes.
+const MAX_FRAME_SIZE: usize = 16 * 1024;
+
+/// A frame sent by the client.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientFrame {
+    /// Sends a message in a conversation.
+    Message {
+        conversation_id: String,
+        body: String,
+    },
+    /// Tells the other participant that the user is typing.
+    Typing { conversation_id: String },
+    /// Marks a conversation as read.
+    Read { conversation_id: String },
+}
+
+/// A frame sent to the client.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ServerFrame<'a> {
+    /// A live update of one of the user's conversations.
+    Event(&'a ChatEvent),
+    /// A client frame was rejected.
+    Error {
+        r#type: &'static str,
+        message: String,
+    },
+}
+
+impl ServerFrame<'_> {
+    /// Creates an error frame.
+    fn error(message: impl Into<String>) -> Self {
+        ServerFrame::Error {
+            r#type: "error",
+            message: message.into(),
+        }
+    }
+}
+
+/// Handles WebSocket upgrade requests for the authenticated user's chat.
+///
+/// The upgrade request is authenticated like other API requests, with the `Authorization` header
+/// or the authentication cookie. Frames are JSON objects with a `type` of `message`, `typing` or
+/// `read` and a `conversation_id`; `message` frames also carry the `body`. Messages sent through
+/// the REST routes are delivered here as well.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `req` - The upgrade request.
+/// * `body` - The request payload carrying the WebSocket frames.
+///
+/// # Returns
+///
+/// The `101 Switching Protocols` response, or an error if the request isn't a WebSocket upgrade.
+#[get("/chat")]
+pub(super) async fn chat_socket(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> HttpResponse {
+    let (response, session, stream) = match actix_ws::handle(&req, body) {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            tracing::warn!("Rejected chat WebSocket handshake: {}", e);
+            return ApiError::new(StatusCode::BAD_REQUEST, "Expected a WebSocket upgrade.")
+                .into_http_response(&req);
+        }
+    };
+    tracing::info!("User {} connected to the chat", auth.user_id);
+    actix_web::rt::spawn(run_chat(
+        db.get_ref().clone(),
+        auth.user_id,
+        session,
+        stream.max_frame_size(MAX_FRAME_SIZE),
+    ));
+    response
+}
+
+/// Serves a chat WebSocket until either side closes it or the client stops answering pings.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the connected user.
+/// * `session` - The WebSocket session for sending frames.
+/// * `stream` - The frames sent by the client.
+async fn run_chat(db: Database, user_id: String, mut session: Session, mut stream: MessageStream) {
+    // Subscribe before reading any frame, so replies to the first frames aren't missed
+    let mut events = db.chat_events.subscribe();
+    let mut conversations: HashMap<String, Conversation> = HashMap::new();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    let reason = loop {
+        tokio::select! {
+            frame = stream.recv() => {
+                last_seen = Instant::now();
+                let reply = match frame {
+                    Some(Ok(WsMessage::Text(text))) => {
+                        handle_frame(&db, &user_id, &mut conversations, &text).await.err()
+                    }
+                    Some(Ok(WsMessage::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break None;
+                        }
+                        None
+                    }
+                    Some(Ok(WsMessage::Pong(_))) => None,
+                    Some(Ok(WsMessage::Close(reason))) => break reason,
+                    Some(Ok(_)) => Some(ServerFrame::error("Frames must be JSON text.")),
+                    Some(Err(e)) => {
+                        tracing::warn!("Chat WebSocket of user {} failed: {}", user_id, e);
+                        break Some(CloseCode::Protocol.into());
+                    }
+                    None => break None,
+                };
+                if let Some(reply) = reply
+                    && send_frame(&mut session, &reply).await.is_err()
+                {
+                    break None;
+                }
+            }
+            delivery = events.recv() => {
+                let delivery: ChatDelivery = match delivery {
+                    Ok(delivery) => delivery,
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("Chat WebSocket of user {} missed {} events", user_id, missed);
+                        // The client reloads the conversations after reconnecting
+                        break Some(CloseReason {
+                            code: CloseCode::Again,
+                            description: Some("Missed events, please reconnect.".to_string()),
+                        });
+                    }
+                    Err(RecvError::Closed) => break Some(CloseCode::Restart.into()),
+                };
+                if delivery.recipients.contains(&user_id)
+                    && send_frame(&mut session, &ServerFrame::Event(&delivery.event)).await.is_err()
+                {
+                    break None;
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > CLIENT_TIMEOUT {
+                    tracing::info!("Chat WebSocket of user {} timed out", user_id);
+                    break Some(CloseCode::Normal.into());
+                }
+                if session.ping(b"").await.is_err() {
+                    break None;
+                }
+            }
+        }
+    };
+
+    tracing::info!("User {} disconnected from the chat", user_id);
+    // Closing fails if the client already closed the connection
+    let _ = session.close(reason).await;
+}
+
+/// Sends a frame as JSON.
+async fn send_frame(
+    session: &mut Session,
+    frame: &ServerFrame<'_>,
+) -> Result<(), actix_ws::Closed> {
+    match serde_json::to_string(frame) {
+        Ok(text) => session.text(text).await,
+        Err(e) => {
+            tracing::error!("Failed to encode chat frame: {:?}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Handles a frame sent by the client.
+///
+/// The user's conversations are looked up once per connection and cached in `conversations`.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the connected user.
+/// * `conversations` - The conversations the user already used on this connection.
+/// * `text` - The frame's JSON text.
+///
+/// # Returns
+///
+/// A `Result` that is empty on success, or contains the error frame to send back.
+async fn handle_frame(
+    db: &Database,
+    user_id: &str,
+    conversations: &mut HashMap<String, Conversation>,
+    text: &str,
+) -> Result<(), ServerFrame<'static>> {
+    let frame: ClientFrame = serde_json::from_str(text)
+        .map_err(|e| ServerFrame::error(format!("Invalid frame: {}", e)))?;
+    let conversation_id = match &frame {
+        ClientFrame::Message {
+            conversation_id, ..
+        }
+        | ClientFrame::Typing { conversation_id }
+        | ClientFrame::Read { conversation_id } => conversation_id.clone(),
+    };
+    let conversation = match conversations.get(&conversation_id) {
+        Some(conversation) => conversation.clone(),
+        None => {
+            let conversation = require_own_conversation(db, user_id, &conversation_id)
+                .await
+                .map_err(|error| ServerFrame::error(error.message))?;
+            conversations.insert(conversation_id.clone(), conversation.clone());
+            conversation
+        }
+    };
+
+    match frame {
+        ClientFrame::Message { body, .. } => {
+            if body.trim().is_empty() || body.chars().count() > MAX_MESSAGE_LENGTH {
+                return Err(ServerFrame::error(format!(
+                    "Message must be 1 to {} characters long",
+                    MAX_MESSAGE_LENGTH
+                )));
+            }
+            // The sent message comes back to this socket as an event
+            deliver_message(db, &conversation, user_id, body)
+                .await
+                .map_err(|error| ServerFrame::error(error.message))?;
+        }
+        ClientFrame::Typing { .. } => {
+            db.chat_events.publish(
+                vec![conversation.other_participant(user_id).to_string()],
+                ChatEvent::Typing {
+                    conversation_id,
+                    user_id: user_id.to_string(),
+                },
+            );
+        }
+        ClientFrame::Read { .. } => {
+            db.mark_conversation_read(&conversation, user_id)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to mark conversation as read: {:?}", e);
+                    ServerFrame::error("Failed to mark conversation as read.")
+                })?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! src/server/config_bundle.rs
+//!
+//! This module defines the admin routes exporting the platform configuration as a signed bundle
+//! and importing such a bundle, e.g. to promote settings from staging to production.
+
+use super::admin::require_admin;
+use crate::config::ConfigHandle;
+use crate::database::Database;
+use crate::database::config_bundle::{
+    CONFIG_BUNDLE_VERSION, ConfigImport, SignedConfigBundle, bundle_secret,
+};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, post, web};
+
+/// Handles requests to export the platform configuration.
+///
+/// This route is restricted to admins. The bundle contains the fee schedule, the listing rules,
+/// the serial blacklist and the enabled feature flags, signed with `CONFIG_BUNDLE_SECRET`.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `config` - Web data containing the runtime configuration.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the signed bundle or an error.
+#[get("admin/config-bundle")]
+pub(super) async fn export_config_bundle(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    req: HttpRequest,
+) -> ApiResponse<SignedConfigBundle> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+    let secret = match bundle_secret() {
+        Ok(secret) => secret,
+        Err(e) => {
+            tracing::error!("Cannot sign configuration bundles: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Configuration bundles are not set up on this server.",
+            );
+        }
+    };
+
+    let feature_flags = config.current().feature_flags.clone();
+    match db
+        .export_config_bundle(feature_flags)
+        .await
+        .and_then(|bundle| bundle.sign(&secret))
+    {
+        Ok(signed) => ApiResponse::ok(signed),
+        Err(e) => {
+            tracing::error!("Failed to export configuration: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to export configuration.",
+            )
+        }
+    }
+}
+
+/// Handles requests to import a configuration bundle.
+///
+/// This route is restricted to admins. The bundle must be signed with this server's
+/// `CONFIG_BUNDLE_SECRET`. It replaces the fee schedule, the listing rules and the serial
+/// blacklist; feature flags are only compared, since they are set in the environment. The import
+/// is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `config` - Web data containing the runtime configuration.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the signed bundle.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing a summary of the import and the differing feature flags, or an
+/// error.
+#[post("admin/config-bundle")]
+pub(super) async fn import_config_bundle(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    req: HttpRequest,
+    body: web::Json<SignedConfigBundle>,
+) -> ApiResponse<ConfigImport> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    let secret = match bundle_secret() {
+        Ok(secret) => secret,
+        Err(e) => {
+            tracing::error!("Cannot verify configuration bundles: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Configuration bundles are not set up on this server.",
+            );
+        }
+    };
+    match body.verify(&secret) {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::warn!("Rejected configuration bundle with an invalid signature");
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                "The bundle's signature is invalid.",
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to verify configuration bundle: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to verify the bundle.",
+            );
+        }
+    }
+    if body.bundle.version != CONFIG_BUNDLE_VERSION {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Unsupported bundle version {}. Expected version {}.",
+                body.bundle.version, CONFIG_BUNDLE_VERSION
+            ),
+        );
+    }
+
+    let feature_flags = config.current().feature_flags.clone();
+    match db
+        .import_config_bundle(&body.bundle, &feature_flags, &admin_id)
+        .await
+    {
+        Ok(import) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "import_config_bundle",
+                    Vec::new(),
+                    format!(
+                        "Imported the configuration exported at {}: {} fee rules, {} listing rules, {} blacklisted serials",
+                        body.bundle.exported_at,
+                        import.fee_rules,
+                        import.listing_rules,
+                        import.serial_blacklist
+                    ),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(import).with_message("Configuration imported.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to import configuration: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to import configuration. Import the bundle again to complete it.",
+            )
+        }
+    }
+}
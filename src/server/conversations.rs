@@ -0,0 +1,309 @@
+//! src/server/conversations.rs
+//!
+//! This module defines the routes for direct messages between buyers and sellers. Conversations
+//! are started about an offer or an order and are only visible to their two participants.
+
+use super::orders::require_own_order;
+use crate::database::conversations::{Conversation, MAX_MESSAGE_LENGTH, Message};
+use crate::database::{Database, record_key};
+use crate::response::{ApiError, ApiResponse};
+use crate::scopes::{OffersRead, OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web};
+use serde::Deserialize;
+use validator::{Validate, ValidationError};
+use validator_derive::Validate;
+
+/// Struct representing the request body of starting a conversation
+#[derive(Debug, Deserialize, Validate)]
+pub(super) struct StartConversationRequest {
+    offer_id: Option<String>,
+    order_id: Option<String>,
+    #[validate(custom(function = "validate_message"))]
+    message: String,
+}
+
+/// Struct representing the request body of sending a message
+#[derive(Debug, Deserialize, Validate)]
+pub(super) struct SendMessageRequest {
+    #[validate(custom(function = "validate_message"))]
+    message: String,
+}
+
+/// Ensures a message isn't blank and at most `MAX_MESSAGE_LENGTH` characters long.
+fn validate_message(message: &str) -> Result<(), ValidationError> {
+    if !message.trim().is_empty() && message.chars().count() <= MAX_MESSAGE_LENGTH {
+        Ok(())
+    } else {
+        Err(ValidationError::new("message").with_message(
+            format!(
+                "Message must be 1 to {} characters long",
+                MAX_MESSAGE_LENGTH
+            )
+            .into(),
+        ))
+    }
+}
+
+/// Loads a conversation the authenticated user takes part in.
+///
+/// Conversations are private, so other users get a 404.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the authenticated user.
+/// * `conversation_id` - The ID of the conversation.
+///
+/// # Returns
+///
+/// A `Result` containing the conversation, or the `ApiError` to return.
+pub(super) async fn require_own_conversation(
+    db: &Database,
+    user_id: &str,
+    conversation_id: &str,
+) -> Result<Conversation, ApiError> {
+    match db.get_conversation(conversation_id).await {
+        Ok(Some(conversation)) if conversation.involves(user_id) => Ok(conversation),
+        Ok(_) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "Conversation not found.",
+        )),
+        Err(e) => {
+            tracing::error!("Failed to retrieve conversation: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve conversation.",
+            ))
+        }
+    }
+}
+
+/// Stores a message and notifies the other participant.
+///
+/// The notification doesn't contain the message, which is only stored encrypted.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `conversation` - The conversation.
+/// * `sender_id` - The ID of the participant sending the message.
+/// * `body` - The message text.
+///
+/// # Returns
+///
+/// A `Result` containing the sent message, or the `ApiError` to return.
+pub(super) async fn deliver_message(
+    db: &Database,
+    conversation: &Conversation,
+    sender_id: &str,
+    body: String,
+) -> Result<Message, ApiError> {
+    let message = db
+        .send_message(conversation, sender_id, body)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to send message: {:?}", e);
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send message.")
+        })?;
+    let recipient = conversation.other_participant(sender_id).to_string();
+    if let Err(e) = db
+        .create_notification(
+            recipient,
+            "message_received",
+            "New message".to_string(),
+            format!(
+                "You received a new message in conversation {}.",
+                message.conversation_id
+            ),
+        )
+        .await
+    {
+        tracing::error!("Failed to notify user about message: {:?}", e);
+    }
+    Ok(message)
+}
+
+/// Handles requests to start a conversation and send its first message.
+///
+/// Give either an `offer_id` to ask the seller of a listed offer, or an `order_id` to message the
+/// other party of an order. If the buyer and seller already talk about the offer, the message is
+/// added to that conversation.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `body` - JSON payload containing the offer or order ID and the message.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the conversation or an error.
+#[post("conversations")]
+pub(super) async fn start_conversation(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    body: web::Json<StartConversationRequest>,
+) -> ApiResponse<Conversation> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Start conversation request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let body = body.into_inner();
+
+    let (offer_id, order_id, buyer_id, seller_id) = match (body.offer_id, body.order_id) {
+        (Some(offer_id), None) => {
+            let offer = match db.get_offer_by_id(offer_id.clone()).await {
+                Ok(Some(offer)) if offer.is_listed() => offer,
+                Ok(_) => return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+                Err(e) => {
+                    tracing::error!("Failed to retrieve offer: {:?}", e);
+                    return ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to retrieve offer.",
+                    );
+                }
+            };
+            if offer.is_seller(&auth.user_id) {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    "You cannot message yourself about your own offer.",
+                );
+            }
+            let seller_id = record_key(&offer.seller_id);
+            (offer_id, None, auth.user_id.clone(), seller_id)
+        }
+        (None, Some(order_id)) => {
+            let order = match require_own_order(&db, &auth.user_id, order_id.clone()).await {
+                Ok(order) => order,
+                Err(error) => return error.into(),
+            };
+            (
+                order.offer_id,
+                Some(order_id),
+                order.buyer_id,
+                order.seller_id,
+            )
+        }
+        _ => {
+            return ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                "Give either an offer ID or an order ID.",
+            );
+        }
+    };
+
+    let conversation = match db
+        .start_conversation(&offer_id, order_id.as_deref(), &buyer_id, &seller_id)
+        .await
+    {
+        Ok(conversation) => conversation,
+        Err(e) => {
+            tracing::error!("Failed to start conversation: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start conversation.",
+            );
+        }
+    };
+    match deliver_message(&db, &conversation, &auth.user_id, body.message).await {
+        Ok(_) => ApiResponse::created(conversation).with_message("Message sent."),
+        Err(error) => error.into(),
+    }
+}
+
+/// Handles requests for the authenticated user's conversations.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the conversations, most recently active first, or an error.
+#[get("conversations")]
+pub(super) async fn get_conversations(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+) -> ApiResponse<Vec<Conversation>> {
+    match db.get_conversations_for_user(&auth.user_id).await {
+        Ok(conversations) => ApiResponse::ok(conversations),
+        Err(e) => {
+            tracing::error!("Failed to retrieve conversations: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve conversations.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the messages of a conversation.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `path` - Path containing the conversation ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the messages, oldest first, or an error.
+#[get("conversations/{conversation_id}/messages")]
+pub(crate) async fn get_messages(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    path: web::Path<String>,
+) -> ApiResponse<Vec<Message>> {
+    let conversation_id = path.into_inner();
+    if let Err(error) = require_own_conversation(&db, &auth.user_id, &conversation_id).await {
+        return error.into();
+    }
+
+    match db.get_messages(&conversation_id).await {
+        Ok(messages) => ApiResponse::ok(messages),
+        Err(e) => {
+            tracing::error!("Failed to retrieve messages: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve messages.",
+            )
+        }
+    }
+}
+
+/// Handles requests to send a message in a conversation.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the conversation ID.
+/// * `body` - JSON payload containing the message.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the sent message or an error.
+#[post("conversations/{conversation_id}/messages")]
+pub(super) async fn send_message(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<SendMessageRequest>,
+) -> ApiResponse<Message> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Send message request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let conversation = match require_own_conversation(&db, &auth.user_id, &path.into_inner()).await
+    {
+        Ok(conversation) => conversation,
+        Err(error) => return error.into(),
+    };
+
+    match deliver_message(&db, &conversation, &auth.user_id, body.into_inner().message).await {
+        Ok(message) => ApiResponse::created(message).with_message("Message sent."),
+        Err(error) => error.into(),
+    }
+}
@@ -0,0 +1,123 @@
+//! src/server/dashboard.rs
+//!
+//! This module defines the admin routes exposing the platform-wide key figures that drive the
+//! operations dashboard.
+
+use super::admin::require_admin;
+use crate::database::Database;
+use crate::database::dashboard::{
+    DASHBOARD_DEFAULT_DAYS, DASHBOARD_MAX_DAYS, DashboardKpis, TopSeller,
+};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, web};
+use serde::Deserialize;
+use validator::Validate;
+use validator_derive::Validate;
+
+/// The default number of top sellers returned.
+const DEFAULT_TOP_SELLERS: u32 = 10;
+
+/// Struct representing the query parameters of the dashboard key figures
+#[derive(Debug, Deserialize, Validate)]
+struct DashboardQuery {
+    #[validate(range(min = 1, max = 365, message = "Days must be between 1 and 365"))]
+    days: Option<u32>,
+}
+
+/// Struct representing the query parameters of the top sellers
+#[derive(Debug, Deserialize, Validate)]
+struct TopSellersQuery {
+    #[validate(range(min = 1, max = 365, message = "Days must be between 1 and 365"))]
+    days: Option<u32>,
+    #[validate(range(min = 1, max = 100, message = "Limit must be between 1 and 100"))]
+    limit: Option<u32>,
+}
+
+/// Handles requests for the platform-wide key figures per day.
+///
+/// Returns the new users, new offers, gross merchandise volume and abuse reports of every day of
+/// the last `?days=` days (30 by default, at most `DASHBOARD_MAX_DAYS`), and the number of open
+/// reports. Days without any are left out of the series.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the number of days (optional).
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the key figures or an error.
+#[get("admin/dashboard")]
+pub(super) async fn get_dashboard(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<DashboardQuery>,
+) -> ApiResponse<DashboardKpis> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+    if let Err(e) = query.validate() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let days = query
+        .days
+        .unwrap_or(DASHBOARD_DEFAULT_DAYS)
+        .min(DASHBOARD_MAX_DAYS);
+    match db.compute_dashboard_kpis(days).await {
+        Ok(kpis) => ApiResponse::ok(kpis),
+        Err(e) => {
+            tracing::error!("Failed to compute dashboard key figures: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to compute dashboard.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the sellers with the highest gross merchandise volume.
+///
+/// Ranks the sellers by the value of the orders placed in the last `?days=` days (30 by default)
+/// and returns the first `?limit=` (10 by default).
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the number of days and sellers (optional).
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the top sellers or an error.
+#[get("admin/dashboard/top-sellers")]
+pub(super) async fn get_top_sellers(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<TopSellersQuery>,
+) -> ApiResponse<Vec<TopSeller>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+    if let Err(e) = query.validate() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let days = query
+        .days
+        .unwrap_or(DASHBOARD_DEFAULT_DAYS)
+        .min(DASHBOARD_MAX_DAYS);
+    let limit = query.limit.unwrap_or(DEFAULT_TOP_SELLERS);
+    match db.get_top_sellers(days, limit).await {
+        Ok(sellers) => ApiResponse::ok(sellers),
+        Err(e) => {
+            tracing::error!("Failed to rank top sellers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve top sellers.",
+            )
+        }
+    }
+}
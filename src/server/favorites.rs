@@ -0,0 +1,123 @@
+//! src/server/favorites.rs
+//!
+//! This module defines the routes users use to keep a watchlist of favorite offers.
+
+use crate::database::{Database, Offer};
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileRead, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web};
+use serde_json::json;
+
+/// Handles requests to add an offer to the authenticated user's favorites.
+///
+/// Only publicly visible offers can be favorited. Favoriting an offer twice has no effect.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the offer with its updated favorites count, or an error.
+#[post("offers/{offer_id}/favorite")]
+pub(super) async fn favorite_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    let offer_id = path.into_inner();
+    match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) if offer.is_listed() => {}
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to favorite: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    }
+
+    match db.add_favorite(&auth.user_id, &offer_id).await {
+        Ok(Some(offer)) => ApiResponse::ok(offer).with_message("Offer added to favorites."),
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+        Err(e) => {
+            tracing::error!("Failed to favorite offer: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to add offer to favorites.",
+            )
+        }
+    }
+}
+
+/// Handles requests to remove an offer from the authenticated user's favorites.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the removal.
+#[delete("offers/{offer_id}/favorite")]
+pub(super) async fn unfavorite_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<serde_json::Value> {
+    match db.remove_favorite(&auth.user_id, &path.into_inner()).await {
+        Ok(Some(offer)) => ApiResponse::ok(json!({ "favorites_count": offer.favorites_count }))
+            .with_message("Offer removed from favorites."),
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+        Err(e) => {
+            tracing::error!("Failed to unfavorite offer: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to remove offer from favorites.",
+            )
+        }
+    }
+}
+
+/// Handles requests to get the authenticated user's favorite offers.
+///
+/// Offers that are no longer publicly visible are left out; sold and expired offers are
+/// included, so users can see what happened to them.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the favorite offers or an error.
+#[get("user/favorites")]
+pub(super) async fn get_favorites(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+) -> ApiResponse<Vec<Offer>> {
+    match db.get_favorite_offers(&auth.user_id).await {
+        Ok(offers) => ApiResponse::ok(
+            offers
+                .into_iter()
+                .filter(|offer| offer.is_listed())
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => {
+            tracing::error!("Failed to retrieve favorite offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve favorite offers.",
+            )
+        }
+    }
+}
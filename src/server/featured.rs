@@ -0,0 +1,177 @@
+//! src/server/featured.rs
+//!
+//! This module defines the route listing featured offers and the admin routes featuring offers
+//! for curated or paid placements.
+
+use super::admin::require_admin;
+use super::{convert_prices, viewer_currency, viewer_is_adult};
+use crate::database::featured::MAX_FEATURED_DAYS;
+use crate::database::pagination::Pagination;
+use crate::database::{Database, Offer};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, delete, get, put, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the feature offer request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct FeatureOfferRequest {
+    #[validate(range(
+        min = 1,
+        max = MAX_FEATURED_DAYS,
+        message = "Days must be between 1 and 90"
+    ))]
+    days: u32,
+}
+
+/// Handles requests for the offers featured right now.
+///
+/// Mature-rated offers are only included for logged-in adults. Prices are also shown in the
+/// viewer's preferred currency.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `pagination` - Query parameters containing the requested page.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing a page of featured offers, newest first, and the pagination
+/// details, or an error.
+#[get("offers/featured")]
+pub(super) async fn get_featured_offers(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    pagination: web::Query<Pagination>,
+) -> ApiResponse<Vec<Offer>> {
+    let include_mature = viewer_is_adult(&db, &req).await;
+    match db.query_featured_offers(include_mature, &pagination).await {
+        Ok((mut offers, page_info)) => {
+            convert_prices(&mut offers, viewer_currency(&db, &req).await).await;
+            ApiResponse::ok(offers).with_pagination(page_info)
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve featured offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve featured offers.",
+            )
+        }
+    }
+}
+
+/// Handles requests to feature an offer for a number of days.
+///
+/// This route is restricted to admins. Only active offers can be featured; featuring an offer
+/// again replaces the end of its placement. The placement is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the number of days.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the featured offer or an error.
+#[put("admin/offers/{offer_id}/featured")]
+pub(super) async fn feature_offer(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<FeatureOfferRequest>,
+) -> ApiResponse<Offer> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Feature offer request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let offer_id = path.into_inner();
+    match db.feature_offer(&offer_id, body.days).await {
+        Ok(Some(offer)) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "feature_offer",
+                    vec![offer_id],
+                    format!("Featured offer for {} days", body.days),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(offer).with_message("Offer featured.")
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            "No active offer with this ID was found.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to feature offer: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to feature offer.",
+            )
+        }
+    }
+}
+
+/// Handles requests to end the featured placement of an offer early.
+///
+/// This route is restricted to admins. The removal is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the offer or an error.
+#[delete("admin/offers/{offer_id}/featured")]
+pub(super) async fn unfeature_offer(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let offer_id = path.into_inner();
+    match db.unfeature_offer(&offer_id).await {
+        Ok(Some(offer)) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "unfeature_offer",
+                    vec![offer_id],
+                    "Ended featured placement".to_string(),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(offer).with_message("Offer is no longer featured.")
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "The offer isn't featured."),
+        Err(e) => {
+            tracing::error!("Failed to end featured placement: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to end featured placement.",
+            )
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! src/server/fees.rs
+//!
+//! This module defines the admin routes managing the platform fee schedule. Rules can be scheduled
+//! ahead of time and promotions must end, so a rule only applies between its effective dates.
+//! Orders keep the fee they were placed with when the schedule changes.
+
+use super::admin::require_admin;
+use crate::database::catalog::Category;
+use crate::database::fees::{FeeRule, FeeRuleFields, SellerTier};
+use crate::database::{Database, record_key};
+use crate::response::{ApiError, ApiResponse};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, delete, get, post, put, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the create and update fee rule request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct FeeRuleRequest {
+    category: Option<Category>,
+    seller_tier: Option<SellerTier>,
+    #[validate(range(min = 0.0, max = 50.0, message = "Percent must be between 0 and 50"))]
+    percent: f64,
+    #[serde(default)]
+    #[validate(range(
+        min = 0.0,
+        max = 100.0,
+        message = "Fixed fee must be between 0 and 100"
+    ))]
+    fixed: f64,
+    #[serde(default)]
+    promotion: bool,
+    #[validate(length(
+        min = 3,
+        max = 200,
+        message = "Description must be 3 to 200 characters long"
+    ))]
+    description: String,
+    effective_from: Option<String>,
+    effective_until: Option<String>,
+}
+
+/// Parses an RFC 3339 timestamp of a request.
+fn parse_request_timestamp(timestamp: &str, field: &str) -> Result<DateTime<Utc>, ApiError> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|parsed| parsed.with_timezone(&Utc))
+        .map_err(|_| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("{field} must be an RFC 3339 timestamp."),
+            )
+        })
+}
+
+/// Validates a fee rule request and converts it to the values stored in the schedule.
+///
+/// A rule without a start date applies immediately. Promotions must have an end date.
+///
+/// # Arguments
+///
+/// * `body` - The request body.
+///
+/// # Returns
+///
+/// A `Result` containing the `FeeRuleFields`, or the `ApiError` to return.
+fn fee_rule_fields(body: FeeRuleRequest) -> Result<FeeRuleFields, ApiError> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Fee rule request validation failed: {:?}", e);
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, e.to_string()));
+    }
+    let effective_from = match body.effective_from.as_deref() {
+        Some(from) => parse_request_timestamp(from, "effective_from")?,
+        None => Utc::now(),
+    };
+    let effective_until = match body.effective_until.as_deref() {
+        Some(until) => Some(parse_request_timestamp(until, "effective_until")?),
+        None => None,
+    };
+    if effective_until.is_some_and(|until| until <= effective_from) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "effective_until must be after effective_from.",
+        ));
+    }
+    if body.promotion && effective_until.is_none() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "A promotion must have an end date.",
+        ));
+    }
+
+    Ok(FeeRuleFields {
+        category: body.category,
+        seller_tier: body.seller_tier,
+        percent: body.percent,
+        fixed: body.fixed,
+        promotion: body.promotion,
+        description: body.description.trim().to_string(),
+        effective_from: effective_from.to_rfc3339(),
+        effective_until: effective_until.map(|until| until.to_rfc3339()),
+    })
+}
+
+/// Describes a fee rule for the audit log.
+fn describe_fee_rule(rule: &FeeRule) -> String {
+    format!(
+        "{}{}% + {:.2} for {} sellers of {} from {} until {}",
+        if rule.promotion { "Promotion: " } else { "" },
+        rule.percent,
+        rule.fixed,
+        rule.seller_tier.map_or("all", |tier| tier.as_str()),
+        rule.category.map_or("all categories", |c| c.as_str()),
+        rule.effective_from,
+        rule.effective_until.as_deref().unwrap_or("further notice")
+    )
+}
+
+/// Handles requests to list the fee schedule.
+///
+/// This route is restricted to admins. Expired and upcoming rules are included.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of fee rules or an error.
+#[get("admin/fee-rules")]
+pub(super) async fn get_fee_rules(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<Vec<FeeRule>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_fee_rules().await {
+        Ok(rules) => ApiResponse::ok(rules),
+        Err(e) => {
+            tracing::error!("Failed to retrieve fee rules: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve fee rules.",
+            )
+        }
+    }
+}
+
+/// Handles requests to create a fee rule.
+///
+/// This route is restricted to admins. The new rule is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the scope, rate and effective dates of the rule.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created fee rule or an error.
+#[post("admin/fee-rules")]
+pub(super) async fn create_fee_rule(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<FeeRuleRequest>,
+) -> ApiResponse<FeeRule> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    let fields = match fee_rule_fields(body.into_inner()) {
+        Ok(fields) => fields,
+        Err(error) => return error.into(),
+    };
+
+    match db.create_fee_rule(&fields, admin_id.clone()).await {
+        Ok(rule) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "create_fee_rule",
+                    vec![record_key(&rule.id)],
+                    format!("Created fee rule: {}", describe_fee_rule(&rule)),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::created(rule).with_message("Fee rule created successfully.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to create fee rule: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create fee rule.",
+            )
+        }
+    }
+}
+
+/// Handles requests to update a fee rule.
+///
+/// This route is restricted to admins. Every value of the rule is replaced. Orders placed before
+/// the change keep their fee. The change is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the rule ID.
+/// * `body` - JSON payload containing the new values of the rule.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated fee rule or an error.
+#[put("admin/fee-rules/{id}")]
+pub(super) async fn update_fee_rule(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<FeeRuleRequest>,
+) -> ApiResponse<FeeRule> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    let fields = match fee_rule_fields(body.into_inner()) {
+        Ok(fields) => fields,
+        Err(error) => return error.into(),
+    };
+
+    match db.update_fee_rule(path.into_inner(), &fields).await {
+        Ok(Some(rule)) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "update_fee_rule",
+                    vec![record_key(&rule.id)],
+                    format!("Updated fee rule: {}", describe_fee_rule(&rule)),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(rule).with_message("Fee rule updated successfully.")
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Fee rule not found."),
+        Err(e) => {
+            tracing::error!("Failed to update fee rule: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update fee rule.",
+            )
+        }
+    }
+}
+
+/// Handles requests to delete a fee rule.
+///
+/// This route is restricted to admins. To end a rule while keeping it in the schedule, set its
+/// end date instead. The deletion is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the rule ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the deletion.
+#[delete("admin/fee-rules/{id}")]
+pub(super) async fn delete_fee_rule(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let rule_id = path.into_inner();
+    match db.delete_fee_rule(rule_id.clone()).await {
+        Ok(true) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "delete_fee_rule",
+                    vec![rule_id],
+                    "Deleted fee rule".to_string(),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::message("Fee rule deleted successfully.")
+        }
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "Fee rule not found."),
+        Err(e) => {
+            tracing::error!("Failed to delete fee rule: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete fee rule.",
+            )
+        }
+    }
+}
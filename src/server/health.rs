@@ -0,0 +1,228 @@
+//! src/server/health.rs
+//!
+//! This module defines the health endpoint, which reports the status of every subsystem
+//! individually so partial outages can be alerted on.
+
+use crate::config::ConfigHandle;
+use crate::cpu_pool::CpuPool;
+use crate::database::Database;
+use crate::job_queue::Queue;
+use crate::metrics::TaskMetrics;
+use crate::response::{ApiError, ApiResponse};
+use actix_web::http::StatusCode;
+use actix_web::{get, web};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long the SMTP server may take to accept a connection before the mailer is down.
+const MAILER_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the oldest job of a queue may wait before the job queue is degraded.
+const JOB_QUEUE_DEGRADED_AFTER: Duration = Duration::from_secs(60);
+
+/// How long the oldest job of a queue may wait before the job queue is down, as its workers
+/// are presumably stuck.
+const JOB_QUEUE_DOWN_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// The status of a subsystem, from best to worst.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum SubsystemStatus {
+    /// The subsystem works.
+    Ok,
+    /// The subsystem works with reduced functionality.
+    Degraded,
+    /// The subsystem is unavailable.
+    Down,
+}
+
+/// The health of a single subsystem.
+#[derive(Debug, Serialize, Clone)]
+pub struct SubsystemHealth {
+    /// The status determined by the last check.
+    pub status: SubsystemStatus,
+    /// The error of the last failed check, kept after the subsystem recovered.
+    pub last_error: Option<String>,
+    /// The timestamp of the last failed check.
+    pub last_error_at: Option<String>,
+}
+
+/// Remembers the last error of every subsystem between health checks.
+#[derive(Debug, Default)]
+pub struct HealthRegistry {
+    subsystems: Mutex<BTreeMap<&'static str, SubsystemHealth>>,
+}
+
+impl HealthRegistry {
+    /// Records the result of a subsystem check.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subsystem.
+    /// * `status` - The status determined by the check.
+    /// * `error` - The error of the check, if it failed.
+    ///
+    /// # Returns
+    ///
+    /// The subsystem's health including the last error.
+    fn record(
+        &self,
+        name: &'static str,
+        status: SubsystemStatus,
+        error: Option<String>,
+    ) -> SubsystemHealth {
+        let mut subsystems = self
+            .subsystems
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let health = subsystems.entry(name).or_insert(SubsystemHealth {
+            status,
+            last_error: None,
+            last_error_at: None,
+        });
+        health.status = status;
+        if let Some(error) = error {
+            health.last_error = Some(error);
+            health.last_error_at = Some(Utc::now().to_rfc3339());
+        }
+        health.clone()
+    }
+}
+
+/// Determines the status of the job queues from how long their oldest jobs have been waiting.
+///
+/// # Returns
+///
+/// The worst status of any queue, and a description of the queues that aren't ok.
+fn job_queue_status() -> (SubsystemStatus, Option<String>) {
+    let mut status = SubsystemStatus::Ok;
+    let mut problems = Vec::new();
+    for queue in Queue::ALL {
+        let waiting = TaskMetrics::global().pending(queue.as_str()).oldest_age();
+        let queue_status = if waiting >= JOB_QUEUE_DOWN_AFTER {
+            SubsystemStatus::Down
+        } else if waiting >= JOB_QUEUE_DEGRADED_AFTER {
+            SubsystemStatus::Degraded
+        } else {
+            continue;
+        };
+        status = status.max(queue_status);
+        problems.push(format!(
+            "The oldest job on queue {} has been waiting for {} seconds",
+            queue.as_str(),
+            waiting.as_secs()
+        ));
+    }
+    (status, (!problems.is_empty()).then(|| problems.join("; ")))
+}
+
+/// Handles health check requests.
+///
+/// Every subsystem is reported with its own status and last error: the database, the API, the
+/// `CpuPool`, the mailer and the job queues. The overall status is `ok` if every subsystem is ok
+/// and `degraded` otherwise. The response is `503 Service Unavailable` only
+/// if the database is down, as the shop cannot serve anything without it.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `config` - Web data containing the runtime configuration.
+/// * `registry` - Web data containing the last errors of the subsystems.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the health of every subsystem. If the database is down, the
+/// health is returned as the error details of a `503 Service Unavailable` response.
+#[get("/health")]
+pub(super) async fn get_health(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    registry: web::Data<HealthRegistry>,
+) -> ApiResponse<serde_json::Value> {
+    let mut subsystems = BTreeMap::new();
+
+    let database = match db.ping().await {
+        Ok(()) => registry.record("database", SubsystemStatus::Ok, None),
+        Err(e) => {
+            tracing::error!("Health check failed for database: {:?}", e);
+            registry.record("database", SubsystemStatus::Down, Some(e.to_string()))
+        }
+    };
+    let database_down = database.status == SubsystemStatus::Down;
+    subsystems.insert("database", database);
+
+    let maintenance_mode = config.current().maintenance_mode;
+    let api_status = if maintenance_mode {
+        SubsystemStatus::Degraded
+    } else {
+        SubsystemStatus::Ok
+    };
+    subsystems.insert("api", registry.record("api", api_status, None));
+
+    // The pool is degraded once its queue is half full, before it starts rejecting work
+    let cpu_pool = CpuPool::global().metrics();
+    let cpu_pool_status = if cpu_pool.queued * 2 >= cpu_pool.max_queue_depth {
+        SubsystemStatus::Degraded
+    } else {
+        SubsystemStatus::Ok
+    };
+    subsystems.insert(
+        "cpu_pool",
+        registry.record("cpu_pool", cpu_pool_status, None),
+    );
+
+    let mailer = match tokio::time::timeout(MAILER_CHECK_TIMEOUT, db.email_sender.check()).await {
+        Ok(Ok(())) => registry.record("mailer", SubsystemStatus::Ok, None),
+        Ok(Err(e)) => {
+            tracing::error!("Health check failed for mailer: {:?}", e);
+            registry.record("mailer", SubsystemStatus::Down, Some(e.to_string()))
+        }
+        Err(_) => {
+            tracing::error!("Health check timed out for mailer");
+            registry.record(
+                "mailer",
+                SubsystemStatus::Down,
+                Some("The SMTP server did not respond in time".to_string()),
+            )
+        }
+    };
+    subsystems.insert("mailer", mailer);
+
+    let (job_queue_status, job_queue_error) = job_queue_status();
+    if let Some(error) = &job_queue_error {
+        tracing::warn!("Health check found slow job queues: {}", error);
+    }
+    subsystems.insert(
+        "job_queues",
+        registry.record("job_queues", job_queue_status, job_queue_error),
+    );
+
+    let status = if subsystems
+        .values()
+        .all(|subsystem| subsystem.status == SubsystemStatus::Ok)
+    {
+        "ok"
+    } else {
+        "degraded"
+    };
+    let body = json!({
+        "status": status,
+        "maintenance_mode": maintenance_mode,
+        "subsystems": subsystems,
+        "cpu_pool": cpu_pool
+    });
+    if database_down {
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The database is unavailable.",
+        )
+        .with_details(body)
+        .into()
+    } else {
+        ApiResponse::ok(body)
+    }
+}
@@ -0,0 +1,245 @@
+//! src/server/legal_texts.rs
+//!
+//! This module defines the routes serving the jurisdiction-specific legal texts, the public pages
+//! rendering the legal documents, and the admin routes managing their versions.
+
+use super::admin::require_admin;
+use crate::database::legal_texts::{LegalText, LegalTextKind};
+use crate::database::{Database, record_key};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+use validator_derive::Validate;
+
+/// Struct representing the query parameters of the legal text lookup
+#[derive(Debug, Deserialize, Validate)]
+struct LegalTextQuery {
+    #[validate(custom(function = "validate_country_code"))]
+    country: Option<String>,
+}
+
+/// Struct representing the query parameters of a legal document page
+#[derive(Debug, Deserialize, Validate)]
+struct LegalDocumentQuery {
+    #[validate(custom(function = "validate_country_code"))]
+    country: Option<String>,
+    /// The ID of a specific version, e.g. the one a user accepted.
+    version: Option<String>,
+}
+
+/// Struct representing the create legal text version request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct CreateLegalTextRequest {
+    kind: LegalTextKind,
+    #[validate(custom(function = "validate_country_code"))]
+    country: Option<String>,
+    #[validate(length(min = 3, max = 200, message = "Title must be 3 to 200 characters long"))]
+    title: String,
+    #[validate(length(
+        min = 10,
+        max = 100000,
+        message = "Body must be 10 to 100000 characters long"
+    ))]
+    body: String,
+}
+
+/// Ensures a country code is an ISO 3166-1 alpha-2 code (two letters).
+fn validate_country_code(country: &str) -> Result<(), ValidationError> {
+    if country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("country")
+            .with_message("Country must be a two-letter ISO 3166-1 code".into()))
+    }
+}
+
+/// Handles requests for the legal text that applies to a buyer's country.
+///
+/// Falls back to the generic text if there is none for the given country.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `path` - Path containing the kind of legal text.
+/// * `query` - Query parameters containing the buyer's country code (optional).
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the legal text or an error.
+#[get("legal-texts/{kind}")]
+pub(super) async fn get_legal_text(
+    db: web::Data<Database>,
+    path: web::Path<LegalTextKind>,
+    query: web::Query<LegalTextQuery>,
+) -> ApiResponse<LegalText> {
+    if let Err(e) = query.validate() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let country = query.into_inner().country.map(|c| c.to_ascii_uppercase());
+    match db.get_current_legal_text(path.into_inner(), country).await {
+        Ok(Some(text)) => ApiResponse::ok(text),
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Legal text not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve legal text: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve legal text.",
+            )
+        }
+    }
+}
+
+/// Serves a legal document, such as the terms or the privacy policy, as an HTML page.
+///
+/// This route requires no authentication. It serves the current version for the visitor's country
+/// (falling back to the generic text), or the version given by `?version=`. The ID of the served
+/// version is returned in the `x-legal-text-version` header, so it can be recorded when a user
+/// accepts the document.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `path` - Path containing the kind of legal document.
+/// * `query` - Query parameters containing the country code or version ID (optional).
+///
+/// # Returns
+///
+/// An `HttpResponse` containing the rendered document or a plain-text error.
+#[get("/legal/{doc}")]
+pub(super) async fn get_legal_document(
+    db: web::Data<Database>,
+    path: web::Path<LegalTextKind>,
+    query: web::Query<LegalDocumentQuery>,
+) -> HttpResponse {
+    if let Err(e) = query.validate() {
+        return HttpResponse::BadRequest()
+            .content_type("text/plain; charset=utf-8")
+            .body(e.to_string());
+    }
+
+    let kind = path.into_inner();
+    let query = query.into_inner();
+    let text = match query.version {
+        Some(version) => db.get_legal_text_version(kind, &version).await,
+        None => {
+            let country = query.country.map(|c| c.to_ascii_uppercase());
+            db.get_current_legal_text(kind, country).await
+        }
+    };
+    match text {
+        Ok(Some(text)) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .insert_header(("x-legal-text-version", record_key(&text.id)))
+            .body(text.to_html_page()),
+        Ok(None) => HttpResponse::NotFound()
+            .content_type("text/plain; charset=utf-8")
+            .body("Legal document not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve legal document: {:?}", e);
+            HttpResponse::InternalServerError()
+                .content_type("text/plain; charset=utf-8")
+                .body("Failed to retrieve legal document.")
+        }
+    }
+}
+
+/// Handles requests to list all versions of all legal texts.
+///
+/// This route is restricted to admins.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of legal texts or an error.
+#[get("admin/legal-texts")]
+pub(super) async fn get_legal_texts(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<Vec<LegalText>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_legal_texts().await {
+        Ok(texts) => ApiResponse::ok(texts),
+        Err(e) => {
+            tracing::error!("Failed to retrieve legal texts: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve legal texts.",
+            )
+        }
+    }
+}
+
+/// Handles requests to publish a new version of a legal text.
+///
+/// This route is restricted to admins. Earlier versions are kept unchanged. The new version is
+/// recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the kind, country, title and body of the text.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created version or an error.
+#[post("admin/legal-texts")]
+pub(super) async fn create_legal_text_version(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<CreateLegalTextRequest>,
+) -> ApiResponse<LegalText> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Create legal text request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let body = body.into_inner();
+    let country = body.country.map(|c| c.to_ascii_uppercase());
+    match db
+        .create_legal_text_version(body.kind, country, body.title, body.body, admin_id.clone())
+        .await
+    {
+        Ok(text) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "create_legal_text_version",
+                    vec![record_key(&text.id)],
+                    format!(
+                        "Published version {} of {} for {}",
+                        text.version,
+                        text.kind.as_str(),
+                        text.country.as_deref().unwrap_or("all countries")
+                    ),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::created(text).with_message("Legal text version created successfully.")
+        }
+        Err(e) => {
+            tracing::warn!("Failed to create legal text version: {:?}", e);
+            ApiResponse::error(
+                StatusCode::CONFLICT,
+                "Failed to create legal text version. Another version may have been published concurrently.",
+            )
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! src/server/listing_rules.rs
+//!
+//! This module defines the admin routes managing the listing rules and the check enforcing them
+//! when offers are published or edited.
+
+use super::admin::require_admin;
+use crate::database::catalog::{Category, PhotoKind};
+use crate::database::conditions::Condition;
+use crate::database::listing_rules::{
+    ListingFacts, ListingRule, RequiredField, missing_requirements,
+};
+use crate::database::{Database, record_key};
+use crate::response::{ApiError, ApiResponse};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, delete, get, post, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the query parameters of the listing rule list
+#[derive(Debug, Deserialize)]
+struct ListingRuleQuery {
+    category: Option<Category>,
+}
+
+/// Struct representing the create listing rule request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct CreateListingRuleRequest {
+    category: Category,
+    condition: Option<Condition>,
+    #[serde(default)]
+    required_photos: Vec<PhotoKind>,
+    #[serde(default)]
+    required_fields: Vec<RequiredField>,
+}
+
+/// Checks a listing against the listing rules of its category.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `facts` - The listing as it would be published.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok` if the listing meets every rule, or the `ApiError` listing the
+/// missing requirements otherwise.
+pub(super) async fn enforce_listing_rules(
+    db: &Database,
+    facts: ListingFacts<'_>,
+) -> Result<(), ApiError> {
+    let rules = match db
+        .get_listing_rules(Some(facts.attributes.category()))
+        .await
+    {
+        Ok(rules) => rules,
+        Err(e) => {
+            tracing::error!("Failed to retrieve listing rules: {:?}", e);
+            return Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check listing requirements.",
+            ));
+        }
+    };
+
+    let missing = missing_requirements(&rules, &facts);
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "The listing does not meet the requirements for its category and condition.",
+        )
+        .with_details(missing))
+    }
+}
+
+/// Handles requests to list the listing rules.
+///
+/// This route is restricted to admins. Pass `category` to only list the rules of one category.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the category to filter by (optional).
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of listing rules or an error.
+#[get("admin/listing-rules")]
+pub(super) async fn get_listing_rules(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<ListingRuleQuery>,
+) -> ApiResponse<Vec<ListingRule>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_listing_rules(query.category).await {
+        Ok(rules) => ApiResponse::ok(rules),
+        Err(e) => {
+            tracing::error!("Failed to retrieve listing rules: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve listing rules.",
+            )
+        }
+    }
+}
+
+/// Handles requests to create a listing rule.
+///
+/// This route is restricted to admins. The rule applies to listings published or edited after its
+/// creation. The new rule is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the category, optional condition and the requirements.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created listing rule or an error.
+#[post("admin/listing-rules")]
+pub(super) async fn create_listing_rule(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<CreateListingRuleRequest>,
+) -> ApiResponse<ListingRule> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Create listing rule request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    if body.required_photos.is_empty() && body.required_fields.is_empty() {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "A rule must require at least one photo or field.",
+        );
+    }
+
+    let body = body.into_inner();
+    match db
+        .create_listing_rule(
+            body.category,
+            body.condition,
+            body.required_photos,
+            body.required_fields,
+            admin_id.clone(),
+        )
+        .await
+    {
+        Ok(rule) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "create_listing_rule",
+                    vec![record_key(&rule.id)],
+                    format!(
+                        "Created listing rule for {} ({})",
+                        rule.category.as_str(),
+                        rule.condition
+                            .map_or("any condition", |condition| condition.label())
+                    ),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::created(rule).with_message("Listing rule created successfully.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to create listing rule: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create listing rule.",
+            )
+        }
+    }
+}
+
+/// Handles requests to delete a listing rule.
+///
+/// This route is restricted to admins. The deletion is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the rule ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the deletion.
+#[delete("admin/listing-rules/{id}")]
+pub(super) async fn delete_listing_rule(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let rule_id = path.into_inner();
+    match db.delete_listing_rule(rule_id.clone()).await {
+        Ok(true) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "delete_listing_rule",
+                    vec![rule_id],
+                    "Deleted listing rule".to_string(),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::message("Listing rule deleted successfully.")
+        }
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "Listing rule not found."),
+        Err(e) => {
+            tracing::error!("Failed to delete listing rule: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete listing rule.",
+            )
+        }
+    }
+}
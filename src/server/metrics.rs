@@ -0,0 +1,46 @@
+//! src/server/metrics.rs
+//!
+//! This module defines the endpoint Prometheus scrapes the metrics of the background work from.
+
+use crate::cpu_pool::CpuPool;
+use crate::metrics::TaskMetrics;
+use crate::response::ApiError;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use actix_web::{HttpRequest, HttpResponse, get};
+use dotenvy::var;
+use subtle::ConstantTimeEq;
+
+/// Handles metrics scrapes.
+///
+/// The endpoint is disabled unless `METRICS_TOKEN` is set, and scrapers must send the token as
+/// `Authorization: Bearer <token>`.
+///
+/// # Arguments
+///
+/// * `req` - HTTP request to access the headers.
+///
+/// # Returns
+///
+/// An `HttpResponse` containing the metrics in the Prometheus text format, or an error.
+#[get("/metrics")]
+pub(super) async fn get_metrics(req: HttpRequest) -> HttpResponse {
+    let Some(expected) = var("METRICS_TOKEN").ok().filter(|token| !token.is_empty()) else {
+        return ApiError::new(StatusCode::NOT_FOUND, "Metrics are disabled.")
+            .into_http_response(&req);
+    };
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !bool::from(token.as_bytes().ct_eq(expected.as_bytes())) {
+        return ApiError::new(StatusCode::UNAUTHORIZED, "Invalid metrics token.")
+            .into_http_response(&req);
+    }
+
+    HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8"))
+        .body(TaskMetrics::global().render(CpuPool::global()))
+}
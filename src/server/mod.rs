@@ -0,0 +1,1616 @@
+//! src/server/mod.rs
+//!
+//! This module defines the Actix Web server and its routes for the gameshop project.
+
+/// The job anonymizing deleted accounts.
+mod account_deletion;
+/// Routes for shipping addresses of users and orders.
+mod addresses;
+/// Admin-only routes.
+pub(crate) mod admin;
+/// Routes for reviewing and appealing moderation actions.
+mod appeals;
+/// Routes for timed auctions of offers and the job closing them.
+mod auctions;
+/// Routes for the authenticity verification of high-value listings.
+mod authenticity;
+/// Routes for bidding on offers and negotiating the price.
+mod bids;
+/// Routes for the shopping cart and its checkout.
+pub(crate) mod cart;
+/// WebSocket delivering conversation messages live.
+mod chat;
+/// Admin routes exporting and importing the platform configuration.
+mod config_bundle;
+/// Routes for direct messages between buyers and sellers.
+pub(crate) mod conversations;
+/// Admin routes exposing the platform-wide key figures of the dashboard.
+mod dashboard;
+/// Routes for users' favorite offers.
+mod favorites;
+/// The featured offers route and the admin routes featuring offers.
+mod featured;
+/// Admin routes managing the platform fee schedule.
+mod fees;
+/// The health endpoint reporting the status of every subsystem.
+mod health;
+/// Routes serving and managing the legal texts and documents.
+mod legal_texts;
+/// Admin routes managing the listing rules, and their enforcement.
+mod listing_rules;
+/// The Prometheus metrics endpoint of the background work.
+mod metrics;
+/// Routes available to community moderators.
+mod moderation;
+/// Routes listing notifications, marking them as read and long-polling for new ones.
+mod notifications;
+/// Routes restoring deleted offers and the job purging them.
+mod offer_deletion;
+/// The image upload route of offers.
+mod offer_images;
+/// Routes for public questions about offers and the sellers' answers.
+pub(crate) mod offer_questions;
+/// Offer status routes (publish, reserve, renew, sell, withdraw) and the expiration job.
+mod offer_status;
+/// View counting of offers and the view statistics route for sellers.
+mod offer_views;
+/// Routes for buying offers and following orders.
+pub(crate) mod orders;
+/// Routes for reading and changing user preferences.
+mod preferences;
+/// The price history route of game titles.
+mod price_history;
+/// The public price index feed and the job storing its weekly snapshots.
+mod price_index;
+/// Admin routes managing promo codes, and their redemption at checkout.
+mod promo_codes;
+/// The public and marketplace statistics routes and the job refreshing them.
+mod public_stats;
+/// The route users report offers for abuse with.
+mod reports;
+/// Routes reserving offers for buyers and the job releasing expired reservations.
+mod reservations;
+/// Routes managing saved searches and the alerts about new matching offers.
+mod saved_searches;
+/// The route sellers see the analytics of their offers with.
+mod seller_analytics;
+/// Routes of the verified seller badge and its applications.
+mod seller_verification;
+/// Admin routes managing the stolen-serial blacklist.
+mod serial_blacklist;
+/// The public sitemap index and the sitemaps of offers and sellers.
+mod sitemap;
+/// Admin routes managing the VAT rates of countries.
+mod taxes;
+/// The trending offers route and the job computing the trending scores.
+mod trending;
+/// Routes managing users' wishlists.
+mod wishlist;
+
+#[cfg(unix)]
+use crate::config::reload_on_sighup;
+use crate::config::{ConfigHandle, RuntimeConfig, watch_log_level};
+use crate::database::account_deletion::ACCOUNT_DELETION_GRACE_DAYS;
+use crate::database::catalog::{
+    AgeRating, BundleItem, Category, Genre, Language, MATURE_AGE, OfferAttributes, OfferFilter,
+    OfferMetadata, OfferPhoto, Region, validate_attributes, validate_bundle_items, validate_genres,
+    validate_photos,
+};
+use crate::database::conditions::Condition;
+use crate::database::ids::UserId;
+use crate::database::listing_rules::ListingFacts;
+use crate::database::offer_deletion::OFFER_RESTORE_DAYS;
+use crate::database::offer_status::OfferStatus;
+use crate::database::offer_stock::MAX_OFFER_QUANTITY;
+use crate::database::pagination::Pagination;
+use crate::database::pickup::{PickupLocation, validate_pickup};
+use crate::database::platforms::Platform;
+use crate::database::preferences::Currency;
+use crate::database::search::MAX_SEARCH_QUERY_LENGTH;
+use crate::database::taxes::SellerType;
+use crate::database::{DATE_OF_BIRTH_FORMAT, Database, Offer, hash_email, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::exchange_rates::ExchangeRateCache;
+#[cfg(feature = "fault-injection")]
+use crate::fault_injection::{FaultRules, inject_faults};
+use crate::hashing::dummy_password_hash;
+use crate::job_queue::JobQueues;
+use crate::jwt::{AUTH_COOKIE_NAME, TOKEN_VALIDITY_DAYS};
+use crate::middleware::{
+    AuthenticationMiddlewareFactory, ImpersonatedBy, RateLimiter, maintenance_guard, rate_limit,
+};
+use crate::response::ApiResponse;
+use crate::response::assign_request_id;
+use crate::scopes::{OffersRead, OffersWrite, ProfileWrite, RequireScope};
+use crate::upload_storage::{UploadStorage, upload_storage_from_env};
+use actix_files as fs;
+use actix_files::NamedFile;
+use actix_web::Result;
+use actix_web::cookie::{Cookie, SameSite, time::Duration};
+use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
+use actix_web::{App, HttpMessage, HttpRequest, delete, get, post, put, web};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::env::var;
+use std::path::PathBuf;
+use tracing_appender::rolling::Rotation;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt};
+use validator::{Validate, ValidationError};
+use validator_derive::Validate;
+
+/// Struct representing the login request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct LoginRequest {
+    #[validate(email(message = "Email is invalid"))]
+    email: String,
+    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
+    password: String,
+}
+/// Struct representing the register request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct RegisterRequest {
+    #[validate(length(min = 1, message = "Firstname is required"))]
+    firstname: String,
+    #[validate(length(min = 1, message = "Lastname is required"))]
+    lastname: String,
+    #[validate(length(min = 3, message = "Username must be at least 3 characters long"))]
+    username: String,
+    #[validate(email(message = "Email is invalid"))]
+    email: String,
+    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
+    password: String,
+    #[validate(custom(function = "validate_date_of_birth"))]
+    date_of_birth: String,
+}
+
+/// Struct representing the change username request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct ChangeUsernameRequest {
+    #[validate(length(min = 3, message = "New username must be at least 3 characters long"))]
+    new_username: String,
+}
+
+/// Struct representing the update profile request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct UpdateProfileRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Firstname must be 1 to 100 characters long"
+    ))]
+    firstname: Option<String>,
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "Lastname must be 1 to 100 characters long"
+    ))]
+    lastname: Option<String>,
+    seller_type: Option<SellerType>,
+}
+
+/// Struct representing the change password request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct ChangePasswordRequest {
+    #[validate(length(min = 8, message = "New password must be at least 8 characters long"))]
+    new_password: String,
+}
+
+/// Struct representing the create offer request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct CreateOfferRequest {
+    #[validate(length(min = 3, message = "Game title is required"))]
+    game_title: String,
+    platform: Platform,
+    condition: Condition,
+    #[validate(range(min = 0.0, message = "Price cannot be negative"))]
+    price: f64,
+    #[validate(length(min = 10, message = "Description must be at least 10 characters long"))]
+    description: String,
+    #[validate(custom(function = "validate_attributes"))]
+    attributes: Option<OfferAttributes>,
+    region: Option<Region>,
+    box_language: Option<Language>,
+    manual_language: Option<Language>,
+    #[serde(default)]
+    #[validate(custom(function = "validate_photos"))]
+    photos: Vec<OfferPhoto>,
+    age_rating: Option<AgeRating>,
+    #[serde(default)]
+    #[validate(custom(function = "validate_genres"))]
+    genres: Vec<Genre>,
+    /// The currency the price is in. Defaults to euros.
+    #[serde(default)]
+    currency: Currency,
+    /// The games sold together for the one price, if the offer is a bundle.
+    #[serde(default)]
+    #[validate(custom(function = "validate_bundle_items"))]
+    bundle_items: Vec<BundleItem>,
+    /// Where the offer can be picked up, if the seller offers local pickup.
+    #[validate(custom(function = "validate_pickup"))]
+    pickup: Option<PickupLocation>,
+    /// The number of identical copies for sale. Defaults to one.
+    #[validate(range(
+        min = 1,
+        max = MAX_OFFER_QUANTITY,
+        message = "Quantity must be between 1 and 999"
+    ))]
+    quantity: Option<u32>,
+    /// Whether the offer is saved as a draft instead of being listed right away.
+    #[serde(default)]
+    draft: bool,
+}
+
+/// Struct representing the update offer request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct UpdateOfferRequest {
+    game_title: Option<String>,
+    platform: Option<Platform>,
+    condition: Option<Condition>,
+    price: Option<f64>,
+    description: Option<String>,
+    #[validate(custom(function = "validate_attributes"))]
+    attributes: Option<OfferAttributes>,
+    region: Option<Region>,
+    box_language: Option<Language>,
+    manual_language: Option<Language>,
+    #[validate(custom(function = "validate_photos"))]
+    photos: Option<Vec<OfferPhoto>>,
+    age_rating: Option<AgeRating>,
+    #[validate(custom(function = "validate_genres"))]
+    genres: Option<Vec<Genre>>,
+    currency: Option<Currency>,
+    #[validate(custom(function = "validate_bundle_items"))]
+    bundle_items: Option<Vec<BundleItem>>,
+    #[validate(custom(function = "validate_pickup"))]
+    pickup: Option<PickupLocation>,
+    #[validate(range(
+        min = 1,
+        max = MAX_OFFER_QUANTITY,
+        message = "Quantity must be between 1 and 999"
+    ))]
+    quantity: Option<u32>,
+}
+
+/// Struct representing the query parameters of the offer search
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    q: String,
+}
+
+/// Ensures a date of birth is a valid `YYYY-MM-DD` date in the past.
+fn validate_date_of_birth(date_of_birth: &str) -> Result<(), ValidationError> {
+    match NaiveDate::parse_from_str(date_of_birth, DATE_OF_BIRTH_FORMAT) {
+        Ok(date) if date <= Utc::now().date_naive() && date.year() >= 1900 => Ok(()),
+        _ => Err(ValidationError::new("date_of_birth")
+            .with_message("Date of birth must be a past date in the format YYYY-MM-DD".into())),
+    }
+}
+
+/// Checks whether the viewer of a request may see mature-rated listings.
+///
+/// Anonymous viewers and users who never provided a date of birth are treated as minors.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// `true` if the viewer is logged in and at least `MATURE_AGE` years old.
+async fn viewer_is_adult(db: &Database, req: &HttpRequest) -> bool {
+    let Some(user_id) = req.extensions().get::<String>().cloned() else {
+        return false;
+    };
+    match db.get_user_age(user_id).await {
+        Ok(age) => age.is_some_and(|age| age >= MATURE_AGE),
+        Err(e) => {
+            tracing::error!("Failed to determine viewer age: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Determines the currency prices are displayed in for the viewer of a request.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// The preferred currency of a logged-in viewer, or the default currency (euros).
+async fn viewer_currency(db: &Database, req: &HttpRequest) -> Currency {
+    let Some(user_id) = req.extensions().get::<String>().cloned() else {
+        return Currency::default();
+    };
+    match db.get_user_preferences(user_id).await {
+        Ok(preferences) => preferences
+            .map(|preferences| preferences.currency)
+            .unwrap_or_default(),
+        Err(e) => {
+            tracing::error!("Failed to determine viewer currency: {:?}", e);
+            Currency::default()
+        }
+    }
+}
+
+/// Fills in the prices of offers in a currency, see `Offer::convert_price`.
+///
+/// Without exchange rates, the offers are left without converted prices.
+///
+/// # Arguments
+///
+/// * `offers` - The offers.
+/// * `currency` - The currency to convert into.
+async fn convert_prices(offers: &mut [Offer], currency: Currency) {
+    if let Some(rates) = ExchangeRateCache::global().rates().await {
+        for offer in offers {
+            offer.convert_price(&rates, currency);
+        }
+    }
+}
+
+/// Sends a notification to a user, logging failures.
+///
+/// Notifications are a side effect of the request, so a failure doesn't fail the request.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the user to notify.
+/// * `kind` - A short machine-readable name of the event.
+/// * `title` - The notification's title.
+/// * `body` - The notification's body.
+async fn notify(db: &Database, user_id: &str, kind: &str, title: &str, body: String) {
+    if let Err(e) = db
+        .create_notification(user_id.to_string(), kind, title.to_string(), body)
+        .await
+    {
+        tracing::error!("Failed to send {} notification: {:?}", kind, e);
+    }
+}
+
+/// Builds the response telling the client to retry because the `CpuPool` is saturated.
+fn overloaded<T: Serialize>() -> ApiResponse<T> {
+    ApiResponse::error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        CustomError::Overloaded.to_string(),
+    )
+    .with_header("Retry-After", "1")
+}
+
+/// Builds the HttpOnly cookie carrying the JWT for browser clients.
+///
+/// The cookie is `SameSite=Strict` so it is never sent along with cross-site requests.
+/// It is marked `Secure` unless `AUTH_COOKIE_SECURE` is set to `false` (e.g. for local development over plain HTTP).
+///
+/// # Arguments
+///
+/// * `token` - The JWT to store in the cookie.
+///
+/// # Returns
+///
+/// The authentication cookie.
+fn auth_cookie(token: &str) -> Cookie<'static> {
+    let secure = var("AUTH_COOKIE_SECURE")
+        .map(|value| value != "false")
+        .unwrap_or(true);
+    Cookie::build(AUTH_COOKIE_NAME, token.to_string())
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(secure)
+        .max_age(Duration::days(TOKEN_VALIDITY_DAYS))
+        .finish()
+}
+
+/// Handles user login requests.
+///
+/// This function validates the login credentials (email and password), authenticates the user
+/// against the database, and if successful, generates and returns a JWT.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection pool.
+/// * `config` - The runtime configuration, deciding whether failures hide account existence.
+/// * `req` - JSON payload containing the user's email and password.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the login attempt.
+#[post("/auth/login")]
+async fn login(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    req: web::Json<LoginRequest>,
+) -> ApiResponse<serde_json::Value> {
+    if let Err(e) = req.validate() {
+        tracing::warn!("Login request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db
+        .authenticate_user(req.email.clone(), req.password.clone())
+        .await
+    {
+        Ok(user) => {
+            let user_id = match UserId::try_from(&user.id) {
+                Ok(user_id) => user_id,
+                Err(e) => {
+                    tracing::error!("Unexpected user ID: {:?}", e);
+                    return ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Internal server error: Invalid user ID format.",
+                    );
+                }
+            };
+            let token = crate::jwt::generate_jwt(user_id.to_string()).unwrap(); // Consider handling unwrap more gracefully
+            ApiResponse::ok(json!({
+                "token": token,
+                "username": user.username
+            }))
+            .with_message("Login successful")
+            .with_cookie(auth_cookie(&token))
+        }
+        Err(CustomError::Overloaded) => overloaded(),
+        Err(CustomError::UserBanned) => {
+            tracing::warn!("Login rejected for banned user");
+            ApiResponse::error(StatusCode::FORBIDDEN, CustomError::UserBanned.to_string())
+        }
+        Err(e) => {
+            tracing::warn!("Login failed: {:?}", e);
+            record_auth_failure(&db, "login_failed", &req.email, &e).await;
+            let privacy_mode = config.current().auth_privacy_mode;
+            ApiResponse::error(
+                StatusCode::UNAUTHORIZED,
+                e.public_auth_message(privacy_mode),
+            )
+        }
+    }
+}
+
+/// Records a failed login or registration with its precise cause in the audit log.
+///
+/// Failures are aggregated per email address and hour, so repeated attempts don't grow the log.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `action` - The audit action, e.g. `login_failed`.
+/// * `email` - The email address the attempt was made for. Only its hash is recorded.
+/// * `error` - The precise cause of the failure.
+async fn record_auth_failure(db: &Database, action: &str, email: &str, error: &CustomError) {
+    let email_hash = hash_email(email);
+    if let Err(e) = db
+        .record_anonymous_failure(
+            action,
+            &email_hash,
+            format!("Email hash {}: {}", email_hash, error),
+        )
+        .await
+    {
+        tracing::error!("Failed to record audit entry: {:?}", e);
+    }
+}
+
+/// Handles user logout requests.
+///
+/// This function clears the authentication cookie. Clients using the `Authorization` header
+/// simply discard their token.
+///
+/// # Returns
+///
+/// An `ApiResponse` confirming the logout.
+#[post("/auth/logout")]
+async fn logout() -> ApiResponse<()> {
+    let mut cookie = auth_cookie("");
+    cookie.make_removal();
+    ApiResponse::message("Logout successful").with_cookie(cookie)
+}
+
+/// The response to registrations in privacy mode, whether or not the account already existed.
+const REGISTRATION_RECEIVED: &str =
+    "Registration received. If the email address wasn't registered yet, you can now log in.";
+
+/// Handles user registration requests.
+///
+/// This function validates the registration details, registers the new user in the database,
+/// and if successful, generates and returns a JWT. In privacy mode, registrations for new and
+/// already registered email addresses get the same response without a JWT, so the response
+/// doesn't reveal whether an account exists.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection pool.
+/// * `config` - The runtime configuration, deciding whether failures hide account existence.
+/// * `req` - JSON payload containing the new user's details.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the registration attempt.
+#[post("/auth/register")]
+async fn register(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    req: web::Json<RegisterRequest>,
+) -> ApiResponse<serde_json::Value> {
+    if let Err(e) = req.validate() {
+        tracing::warn!("Register request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let privacy_mode = config.current().auth_privacy_mode;
+
+    match db
+        .register(
+            req.firstname.clone(),
+            req.lastname.clone(),
+            req.username.clone(),
+            req.password.clone(),
+            req.email.clone(),
+            // Validated above
+            NaiveDate::parse_from_str(&req.date_of_birth, DATE_OF_BIRTH_FORMAT).unwrap_or_default(),
+        )
+        .await
+    {
+        Ok(_) if privacy_mode => ApiResponse::message(REGISTRATION_RECEIVED),
+        Err(e @ CustomError::UserAlreadyExists) if privacy_mode => {
+            tracing::warn!("Registration failed: {:?}", e);
+            record_auth_failure(&db, "registration_failed", &req.email, &e).await;
+            ApiResponse::message(REGISTRATION_RECEIVED)
+        }
+        Ok(_) => {
+            // After successful registration, log the user in to get a token
+            match db
+                .authenticate_user(req.email.clone(), req.password.clone())
+                .await
+            {
+                Ok(user) => {
+                    let user_id = match UserId::try_from(&user.id) {
+                        Ok(user_id) => user_id,
+                        Err(e) => {
+                            tracing::error!("Unexpected user ID: {:?}", e);
+                            return ApiResponse::error(
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                "Internal server error: Invalid user ID format.",
+                            );
+                        }
+                    };
+                    let token = crate::jwt::generate_jwt(user_id.to_string()).unwrap(); // Consider handling unwrap more gracefully
+                    ApiResponse::ok(json!({
+                        "token": token,
+                        "username": user.username
+                    }))
+                    .with_message("Registration successful")
+                    .with_cookie(auth_cookie(&token))
+                }
+                Err(e) => {
+                    tracing::error!("Authentication failed after registration: {:?}", e);
+                    ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Registration successful but failed to log in automatically.",
+                    )
+                }
+            }
+        }
+        Err(CustomError::Overloaded) => overloaded(),
+        Err(e) => {
+            tracing::warn!("Registration failed: {:?}", e);
+            record_auth_failure(&db, "registration_failed", &req.email, &e).await;
+            ApiResponse::error(StatusCode::CONFLICT, e.public_auth_message(privacy_mode))
+        }
+    }
+}
+
+/// Handles requests to change a user's username.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+/// It extracts the `user_id` from the authenticated request and updates the username in the database.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the new username.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the username change.
+#[put("/user/change-username")]
+async fn change_username(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<ChangeUsernameRequest>,
+) -> ApiResponse<()> {
+    let user_id = auth.user_id;
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Change username request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db.change_username(user_id, body.new_username.clone()).await {
+        Ok(_) => ApiResponse::message("Username changed successfully."),
+        Err(e) => {
+            tracing::error!("Failed to change username: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to change username.",
+            )
+        }
+    }
+}
+
+/// Handles requests to change a user's password.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+/// It extracts the `user_id` from the authenticated request and updates the password in the database.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the new password.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the password change.
+#[put("/user/change-password")]
+async fn change_password(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<ChangePasswordRequest>,
+) -> ApiResponse<()> {
+    // Add #[derive(Validate)] to ChangePasswordRequest
+    if let Err(e) = body.validate() {
+        tracing::warn!("Change password request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let user_id = auth.user_id;
+
+    match db.change_password(user_id, body.new_password.clone()).await {
+        Ok(_) => ApiResponse::message("Password changed successfully."),
+        Err(e) => {
+            tracing::error!("Failed to change password: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to change password.",
+            )
+        }
+    }
+}
+
+/// Handles requests to change a user's personal details.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+/// It extracts the `user_id` from the authenticated request and re-encrypts the changed names.
+/// The seller type decides whether VAT is charged on the user's future sales.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the new first name, last name and/or seller type.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the profile update.
+#[put("/user/profile")]
+async fn update_profile(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<UpdateProfileRequest>,
+) -> ApiResponse<()> {
+    let body = UpdateProfileRequest {
+        firstname: body.firstname.as_ref().map(|name| name.trim().to_string()),
+        lastname: body.lastname.as_ref().map(|name| name.trim().to_string()),
+        seller_type: body.seller_type,
+    };
+    if let Err(e) = body.validate() {
+        tracing::warn!("Update profile request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    if body.firstname.is_none() && body.lastname.is_none() && body.seller_type.is_none() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "Nothing to update.");
+    }
+
+    match db
+        .update_profile(
+            auth.user_id,
+            body.firstname,
+            body.lastname,
+            body.seller_type,
+        )
+        .await
+    {
+        Ok(true) => ApiResponse::message("Profile updated successfully."),
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            tracing::error!("Failed to update profile: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update profile.",
+            )
+        }
+    }
+}
+
+/// Handles requests to delete the authenticated user's account.
+///
+/// The account is soft-deleted: it can no longer log in and its offers are hidden, but an admin can
+/// restore it within `ACCOUNT_DELETION_GRACE_DAYS` days. Impersonation sessions cannot delete
+/// accounts.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the deletion.
+#[delete("/user")]
+async fn delete_account(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    auth: RequireScope<ProfileWrite>,
+) -> ApiResponse<()> {
+    if req.extensions().get::<ImpersonatedBy>().is_some() {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "Accounts cannot be deleted while impersonating.",
+        );
+    }
+
+    match db.soft_delete_user(auth.user_id).await {
+        Ok(Some(_)) => {
+            let mut cookie = auth_cookie("");
+            cookie.make_removal();
+            ApiResponse::message(format!(
+                "Account deleted. Contact support within {} days to restore it.",
+                ACCOUNT_DELETION_GRACE_DAYS
+            ))
+            .with_cookie(cookie)
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            tracing::error!("Failed to delete account: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete account.",
+            )
+        }
+    }
+}
+
+/// Attaches the details of its game (cover art, release year, genres) to a new offer.
+///
+/// Runs in the background, so the seller's request doesn't wait for the metadata API.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer` - The new offer.
+fn enrich_in_background(db: Database, offer: Offer) {
+    tokio::spawn(async move {
+        if let Err(e) = db.enrich_offer(&offer).await {
+            tracing::warn!("Failed to enrich offer {}: {:?}", offer.id, e);
+        }
+    });
+}
+
+/// Handles requests to create a new game offer.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+/// It extracts the `seller_id` (user_id) from the authenticated request and creates a new offer in the database.
+/// Users whose saved searches or wishlists match a listed offer are notified. The offer is enriched with the
+/// details of its game in the background. Bundles list their games in `bundle_items`, each with
+/// its own condition; `game_title` then names the bundle. Sellers offering local pickup give a
+/// coarse `pickup` location, such as the centre of their postcode, which is rounded before it is
+/// stored.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `body` - JSON payload containing the offer details.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the offer creation.
+#[post("offers")]
+async fn create_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    body: web::Json<CreateOfferRequest>,
+) -> ApiResponse<Offer> {
+    let seller_id = auth.user_id;
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Create offer request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let attributes = body.attributes.clone().unwrap_or_default();
+    if let Err(error) = listing_rules::enforce_listing_rules(
+        &db,
+        ListingFacts {
+            attributes: &attributes,
+            condition: body.condition,
+            region: body.region,
+            box_language: body.box_language,
+            manual_language: body.manual_language,
+            photos: &body.photos,
+        },
+    )
+    .await
+    {
+        return error.into();
+    }
+
+    match db
+        .create_offer(
+            body.game_title.clone(),
+            body.platform,
+            body.condition,
+            body.price,
+            body.description.clone(),
+            seller_id,
+            OfferMetadata {
+                attributes: body.attributes.clone(),
+                region: body.region,
+                box_language: body.box_language,
+                manual_language: body.manual_language,
+                photos: Some(body.photos.clone()),
+                age_rating: body.age_rating,
+                genres: Some(body.genres.clone()),
+                currency: Some(body.currency),
+                bundle_items: Some(body.bundle_items.clone()),
+                pickup: body.pickup.clone(),
+                quantity: body.quantity,
+            },
+            if body.draft {
+                OfferStatus::Draft
+            } else {
+                OfferStatus::Active
+            },
+        )
+        .await
+    {
+        Ok(offer) if offer.hidden => {
+            enrich_in_background(db.get_ref().clone(), offer.clone());
+            ApiResponse::created(offer).with_message(
+                "Offer created and held for review by a moderator before it goes live.",
+            )
+        }
+        Ok(offer) => {
+            enrich_in_background(db.get_ref().clone(), offer.clone());
+            if offer.status == OfferStatus::Active {
+                saved_searches::alert_new_offer(db.get_ref().clone(), offer.clone());
+            }
+            ApiResponse::created(offer).with_message("Offer created successfully.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to create offer: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create offer.")
+        }
+    }
+}
+
+/// Handles requests to get all game offers.
+///
+/// This route retrieves all visible game offers from the database, optionally filtered by
+/// region (`?region=pal`), box or manual language (`?language=de`), the "authenticated"
+/// badge (`?authenticated=true`), the verified seller badge (`?verified=true`), bundles
+/// (`?bundle=true`), platform, condition, price range (`?min_price=&max_price=`), local pickup
+/// (`?near=<lat>,<lon>&radius=<km>`) and seller (`?seller=<user id>`). Mature-rated offers are
+/// only included for logged-in adults. Results are sorted with `?sort=newest|price_asc|price_desc` and paginated with `?page=` and
+/// `?per_page=`. Prices are compared, sorted and shown as `converted_price` in `?currency=`, which
+/// defaults to the viewer's preferred currency.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `filter` - Query parameters containing the optional filters.
+/// * `pagination` - Query parameters containing the requested page.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing a page of offers and the pagination details, or an error.
+#[get("offers")]
+async fn get_all_offers(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    filter: web::Query<OfferFilter>,
+    pagination: web::Query<Pagination>,
+) -> ApiResponse<Vec<Offer>> {
+    if let Err(e) = filter.validate() {
+        tracing::warn!("Offer filter validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let mut filter = filter.into_inner();
+    let currency = match filter.currency {
+        Some(currency) => currency,
+        None => viewer_currency(&db, &req).await,
+    };
+    filter.currency = Some(currency);
+    let include_mature = viewer_is_adult(&db, &req).await;
+    match db.query_offers(&filter, include_mature, &pagination).await {
+        Ok((mut offers, page_info)) => {
+            convert_prices(&mut offers, currency).await;
+            ApiResponse::ok(offers).with_pagination(page_info)
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offers.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the taxonomy offers can be browsed by.
+///
+/// Returns every listing category and game genre with the value used in the `category` and
+/// `genre` filters of `GET /api/offers` and a human-readable label.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the categories and genres.
+#[get("categories")]
+async fn get_categories() -> ApiResponse<serde_json::Value> {
+    let categories: Vec<serde_json::Value> = Category::ALL
+        .iter()
+        .map(|category| json!({ "id": category.as_str(), "label": category.label() }))
+        .collect();
+    let genres: Vec<serde_json::Value> = Genre::ALL
+        .iter()
+        .map(|genre| json!({ "id": genre.as_str(), "label": genre.label() }))
+        .collect();
+    ApiResponse::ok(json!({
+        "categories": categories,
+        "genres": genres
+    }))
+}
+
+/// Handles requests for the platforms offers can be listed for.
+///
+/// Returns every platform with the value used in the `platform` fields of offers and filters and
+/// a human-readable label. Common spellings like "PlayStation 5" are accepted for `ps5` as well.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the platforms.
+#[get("platforms")]
+async fn get_platforms() -> ApiResponse<Vec<serde_json::Value>> {
+    ApiResponse::ok(
+        Platform::ALL
+            .iter()
+            .map(|platform| json!({ "id": platform.as_str(), "label": platform.label() }))
+            .collect(),
+    )
+}
+
+/// Handles full-text search requests over the offers' titles and descriptions.
+///
+/// Results are ordered by relevance and paginated like `GET /api/offers`. Mature-rated offers are
+/// only included for logged-in adults. Prices are also shown in the viewer's preferred currency.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the search terms (`?q=`).
+/// * `pagination` - Query parameters containing the requested page.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing a page of matching offers and the pagination details, or an error.
+#[get("offers/search")]
+async fn search_offers(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    pagination: web::Query<Pagination>,
+) -> ApiResponse<Vec<Offer>> {
+    let terms = query.q.trim();
+    if terms.is_empty() || terms.len() > MAX_SEARCH_QUERY_LENGTH {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Search query must be 1 to {} characters long",
+                MAX_SEARCH_QUERY_LENGTH
+            ),
+        );
+    }
+
+    let include_mature = viewer_is_adult(&db, &req).await;
+    match db.search_offers(terms, include_mature, &pagination).await {
+        Ok((mut offers, page_info)) => {
+            convert_prices(&mut offers, viewer_currency(&db, &req).await).await;
+            ApiResponse::ok(offers).with_pagination(page_info)
+        }
+        Err(e) => {
+            tracing::error!("Failed to search offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to search offers.",
+            )
+        }
+    }
+}
+
+/// Handles requests to get a single game offer by ID or by its slug (`elden-ring-ps5-AB12`).
+///
+/// Mature-rated offers are only shown to logged-in adults. The public questions about the offer
+/// are included with their answers. The view is counted for the seller's view statistics. The price is also shown in the viewer's preferred currency.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the offer ID or slug.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the offer details or an error.
+#[get("offers/{offer_id}")]
+async fn get_offer_by_id(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    let offer_id = path.into_inner();
+    match db.resolve_offer(&offer_id).await {
+        Ok(Some(offer))
+            if offer.is_listed()
+                && offer.age_rating.is_some_and(|rating| rating.is_mature())
+                && !viewer_is_adult(&db, &req).await =>
+        {
+            ApiResponse::error(
+                StatusCode::FORBIDDEN,
+                format!(
+                    "You must be logged in and at least {} years old to view this offer.",
+                    MATURE_AGE
+                ),
+            )
+        }
+        Ok(Some(mut offer)) if offer.is_listed() => {
+            offer_views::record_view(&db, &req, &offer).await;
+            match db.get_offer_questions(&record_key(&offer.id)).await {
+                Ok(questions) => offer.questions = questions,
+                Err(e) => tracing::warn!("Failed to retrieve questions of offer: {:?}", e),
+            }
+            convert_prices(
+                std::slice::from_mut(&mut offer),
+                viewer_currency(&db, &req).await,
+            )
+            .await;
+            ApiResponse::ok(offer)
+        }
+        Ok(_) => ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            )
+        }
+    }
+}
+
+/// Handles requests to get all offers made by a specific seller.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+/// It extracts the `seller_id` (user_id) from the authenticated request.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing a list of offers or an error.
+#[get("my-offers")]
+async fn get_my_offers(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+) -> ApiResponse<Vec<Offer>> {
+    let seller_id = auth.user_id;
+
+    match db.get_offers_by_seller_id(seller_id).await {
+        Ok(offers) => ApiResponse::ok(offers),
+        Err(e) => {
+            tracing::error!("Failed to retrieve user's offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve user's offers.",
+            )
+        }
+    }
+}
+
+/// Handles requests to update an existing game offer.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+/// It checks if the authenticated user is the seller of the offer before allowing the update.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the fields to update.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the offer update.
+#[put("offers/{offer_id}")]
+async fn update_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<UpdateOfferRequest>,
+) -> ApiResponse<Offer> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Update offer request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let offer_id = path.into_inner();
+
+    match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => {
+            // Check if the authenticated user is the seller of this offer
+            if !offer.is_seller(&auth.user_id) {
+                return ApiResponse::error(
+                    StatusCode::FORBIDDEN,
+                    "You do not have permission to update this offer.",
+                );
+            }
+
+            // Check the listing as it will look after the update
+            if let Err(error) = listing_rules::enforce_listing_rules(
+                &db,
+                ListingFacts {
+                    attributes: body.attributes.as_ref().unwrap_or(&offer.attributes),
+                    condition: body.condition.unwrap_or(offer.condition),
+                    region: body.region.or(offer.region),
+                    box_language: body.box_language.or(offer.box_language),
+                    manual_language: body.manual_language.or(offer.manual_language),
+                    photos: body.photos.as_deref().unwrap_or(&offer.photos),
+                },
+            )
+            .await
+            {
+                return error.into();
+            }
+
+            match db
+                .update_offer(
+                    offer_id,
+                    body.game_title.clone(),
+                    body.platform,
+                    body.condition,
+                    body.price,
+                    body.description.clone(),
+                    OfferMetadata {
+                        attributes: body.attributes.clone(),
+                        region: body.region,
+                        box_language: body.box_language,
+                        manual_language: body.manual_language,
+                        photos: body.photos.clone(),
+                        age_rating: body.age_rating,
+                        genres: body.genres.clone(),
+                        currency: body.currency,
+                        bundle_items: body.bundle_items.clone(),
+                        pickup: body.pickup.clone(),
+                        quantity: body.quantity,
+                    },
+                )
+                .await
+            {
+                Ok(updated_offer) if updated_offer.hidden && !offer.hidden => {
+                    ApiResponse::ok(updated_offer)
+                        .with_message("Offer updated and held for review by a moderator.")
+                }
+                Ok(updated_offer) => {
+                    ApiResponse::ok(updated_offer).with_message("Offer updated successfully.")
+                }
+                Err(e) => {
+                    tracing::error!("Failed to update offer: {:?}", e);
+                    ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update offer.")
+                }
+            }
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for update: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer for update.",
+            )
+        }
+    }
+}
+
+/// Handles requests to delete an existing game offer.
+///
+/// This route is protected by the `AuthenticationMiddlewareFactory`.
+/// It checks if the authenticated user is the seller of the offer before allowing the deletion.
+/// The offer is only marked as deleted: the seller can restore it for `OFFER_RESTORE_DAYS` days,
+/// after which it is purged unless it was ordered.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the offer deletion.
+#[delete("offers/{offer_id}")]
+async fn delete_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let offer_id = path.into_inner();
+
+    match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => {
+            // Check if the authenticated user is the seller of this offer
+            if !offer.is_seller(&auth.user_id) {
+                return ApiResponse::error(
+                    StatusCode::FORBIDDEN,
+                    "You do not have permission to delete this offer.",
+                );
+            }
+
+            match db.delete_offer(offer_id, auth.user_id).await {
+                Ok(_) => ApiResponse::message(format!(
+                    "Offer deleted successfully. You can restore it within {} days.",
+                    OFFER_RESTORE_DAYS
+                )),
+                Err(e) => {
+                    tracing::error!("Failed to delete offer: {:?}", e);
+                    ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete offer.")
+                }
+            }
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for deletion: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer for deletion.",
+            )
+        }
+    }
+}
+
+/// Serves the static HTML files.
+///
+/// This function handles requests for static files, primarily HTML pages for the web frontend.
+///
+/// # Arguments
+///
+/// * `file` - The path to the requested file.
+///
+/// # Returns
+///
+/// A `Result` containing a `NamedFile` to be served.
+#[get("")]
+async fn static_files(file: web::Path<String>) -> Result<NamedFile> {
+    let filename = file.into_inner();
+    let path: PathBuf = if filename.is_empty() {
+        "./web/index.html".into() // Default to index.html for root requests
+    } else {
+        format!("./web/{}", filename).into()
+    };
+    Ok(NamedFile::open(path)?)
+}
+
+/// Handles requests for the root path, redirecting to `index.html`.
+#[get("/")]
+async fn index() -> Result<NamedFile> {
+    Ok(NamedFile::open("./web/index.html")?)
+}
+
+/// Configures and runs the Actix Web server.
+///
+/// This function sets up the logging, database connection, JWT secret, and all
+/// the application routes, including static file serving and authentication routes.
+///
+/// # Returns
+///
+/// A `Result` indicating the success or failure of the server startup.
+pub async fn run_server() -> std::io::Result<()> {
+    // Initialize tracing subscriber for logging
+    let file_appender = tracing_appender::rolling::RollingFileAppender::new(
+        Rotation::DAILY,
+        "./logs",
+        "gameshop.log",
+    );
+    let (non_blocking_appender, _guard) = tracing_appender::non_blocking(file_appender);
+    let config_result = RuntimeConfig::from_env();
+    let initial_config = config_result.as_ref().cloned().unwrap_or_default();
+    let (level_filter, level_handle) = reload::Layer::new(initial_config.level_filter());
+    tracing_subscriber::registry()
+        .with(level_filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(non_blocking_appender),
+        )
+        .init();
+
+    tracing::info!("Server starting...");
+    if let Err(e) = config_result {
+        tracing::error!("Invalid runtime configuration, using defaults: {}", e);
+    }
+    let config = ConfigHandle::new(initial_config);
+    watch_log_level(&config, level_handle);
+    #[cfg(unix)]
+    reload_on_sighup(config.clone());
+    let config_data = web::Data::new(config);
+    let health_data = web::Data::new(health::HealthRegistry::default());
+    let public_stats_data = web::Data::new(public_stats::PublicStatsCache::default());
+
+    // Create database connection
+    let db = match Database::new().await {
+        Ok(db) => db,
+        Err(e) => {
+            tracing::error!("Failed to open database: {}", e);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Failed to connect to database",
+            ));
+        }
+    };
+    offer_status::spawn_expiration_job(db.clone(), config_data.get_ref().clone());
+    auctions::spawn_closing_job(db.clone());
+    orders::spawn_escrow_release_job(db.clone());
+    offer_deletion::spawn_purge_job(db.clone());
+    account_deletion::spawn_anonymization_job(db.clone());
+    reservations::spawn_expiry_job(db.clone());
+    public_stats::spawn_refresh_job(db.clone(), public_stats_data.clone());
+    price_index::spawn_snapshot_job(db.clone());
+    trending::spawn_trending_job(db.clone());
+    // Reads and logs the queue configuration before the first job is queued
+    JobQueues::global();
+    let db_data = web::Data::new(db);
+
+    // Uploaded offer images are stored here and served as static files
+    let image_dir = offer_images::upload_dir();
+    if let Err(e) = std::fs::create_dir_all(&image_dir) {
+        tracing::error!("Failed to create image upload directory: {}", e);
+    }
+    // Direct uploads are staged here until they are finalized
+    let upload_storage_data: web::Data<dyn UploadStorage> =
+        web::Data::from(upload_storage_from_env());
+
+    // Compute the dummy hash now, so the first login for an unknown email isn't the slow one
+    dummy_password_hash();
+
+    // Get JWT secret from environment variable
+    let jwt_secret = var("JWT_SECRET").expect("JWT_SECRET must be set.");
+    let jwt_secret_data = web::Data::new(jwt_secret);
+
+    // Shared by all workers, the limit itself is read from the runtime configuration
+    let rate_limiter_data = web::Data::new(RateLimiter::default());
+
+    #[cfg(feature = "fault-injection")]
+    let fault_rules = {
+        let rules = FaultRules::from_env().map_err(|e| {
+            tracing::error!("Failed to parse fault injection rules: {}", e);
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
+        })?;
+        tracing::warn!("Fault injection is enabled with rules: {:?}", rules);
+        rules.clone().install();
+        web::Data::new(rules)
+    };
+
+    // Start the server
+    actix_web::HttpServer::new(move || {
+        let app = App::new();
+        // Innermost app-level middleware, so injected latency shows up in the access log
+        #[cfg(feature = "fault-injection")]
+        let app = app
+            .app_data(fault_rules.clone())
+            .wrap(from_fn(inject_faults));
+        app.app_data(db_data.clone())
+            .app_data(jwt_secret_data.clone())
+            .app_data(config_data.clone())
+            .app_data(health_data.clone())
+            .app_data(public_stats_data.clone())
+            .app_data(upload_storage_data.clone())
+            .app_data(rate_limiter_data.clone())
+            .wrap(from_fn(maintenance_guard))
+            .wrap(actix_web::middleware::Logger::default())
+            .wrap(from_fn(rate_limit)) // Apply rate limiting
+            .wrap(from_fn(assign_request_id)) // Outermost, so every response carries the request ID
+            .service(login)
+            .service(logout)
+            .service(health::get_health)
+            .service(metrics::get_metrics)
+            .service(public_stats::get_public_stats)
+            .service(public_stats::get_marketplace_stats)
+            .service(sitemap::get_sitemap_index)
+            .service(sitemap::get_sitemap)
+            .service(price_index::get_price_index)
+            .service(legal_texts::get_legal_document)
+            .service(offer_images::receive_direct_upload)
+            .service(orders::download_invoice)
+            .service(appeals::create_ban_appeal)
+            .service(static_files)
+            .service(register)
+            .service(index)
+            .service(
+                web::scope("ws") // WebSockets, authenticated on the upgrade request
+                    .wrap(AuthenticationMiddlewareFactory)
+                    .service(chat::chat_socket),
+            )
+            .service(
+                web::scope("api") // API routes that require authentication
+                    .wrap(AuthenticationMiddlewareFactory)
+                    .service(change_username)
+                    .service(change_password)
+                    .service(update_profile)
+                    .service(delete_account)
+                    .service(preferences::get_preferences)
+                    .service(preferences::update_preferences)
+                    .service(create_offer)
+                    .service(get_all_offers) // You might want to make this public or controlled by roles later
+                    .service(get_categories)
+                    .service(get_platforms)
+                    .service(search_offers) // Must be registered before get_offer_by_id
+                    .service(trending::get_trending_offers) // Same as above
+                    .service(featured::get_featured_offers) // Same as above
+                    .service(get_offer_by_id) // Same as above
+                    .service(get_my_offers)
+                    .service(update_offer)
+                    .service(delete_offer)
+                    .service(offer_deletion::get_deleted_offers)
+                    .service(offer_deletion::restore_offer)
+                    .service(offer_deletion::purge_deleted_offers)
+                    .service(reservations::reserve_offer)
+                    .service(reservations::release_reservation)
+                    .service(offer_images::upload_offer_images)
+                    .service(offer_images::create_image_upload_url)
+                    .service(offer_images::finalize_image_upload)
+                    .service(offer_images::update_image_alt_text)
+                    .service(offer_status::update_offer_status)
+                    .service(offer_status::mark_offer_sold)
+                    .service(offer_status::withdraw_offer)
+                    .service(offer_status::renew_offer)
+                    .service(offer_views::get_offer_views)
+                    .service(offer_questions::get_offer_questions)
+                    .service(offer_questions::ask_question)
+                    .service(offer_questions::answer_question)
+                    .service(offer_questions::report_question)
+                    .service(seller_analytics::get_seller_analytics)
+                    .service(favorites::favorite_offer)
+                    .service(favorites::unfavorite_offer)
+                    .service(favorites::get_favorites)
+                    .service(saved_searches::create_saved_search)
+                    .service(saved_searches::get_saved_searches)
+                    .service(saved_searches::delete_saved_search)
+                    .service(wishlist::add_to_wishlist)
+                    .service(wishlist::get_wishlist)
+                    .service(wishlist::remove_from_wishlist)
+                    .service(reports::report_offer)
+                    .service(bids::create_bid)
+                    .service(bids::get_bids)
+                    .service(bids::accept_bid)
+                    .service(bids::decline_bid)
+                    .service(bids::counter_bid)
+                    .service(auctions::start_auction)
+                    .service(auctions::get_auction)
+                    .service(auctions::place_auction_bid)
+                    .service(orders::buy_offer)
+                    .service(orders::get_orders)
+                    .service(orders::get_order)
+                    .service(orders::get_order_invoice)
+                    .service(orders::create_invoice_download_url)
+                    .service(orders::update_order_state)
+                    .service(orders::confirm_receipt)
+                    .service(cart::get_cart)
+                    .service(cart::add_cart_item)
+                    .service(cart::remove_cart_item)
+                    .service(cart::checkout_cart)
+                    .service(addresses::create_address)
+                    .service(addresses::get_addresses)
+                    .service(addresses::delete_address)
+                    .service(addresses::attach_shipping_address)
+                    .service(addresses::get_shipping_address)
+                    .service(conversations::start_conversation)
+                    .service(conversations::get_conversations)
+                    .service(conversations::get_messages)
+                    .service(conversations::send_message)
+                    .service(price_history::get_price_history)
+                    .service(notifications::get_notifications)
+                    .service(notifications::get_unread_count)
+                    .service(notifications::mark_notification_read)
+                    .service(notifications::mark_all_notifications_read)
+                    .service(notifications::poll_notifications)
+                    .service(admin::bulk_offer_action)
+                    .service(admin::bulk_user_action)
+                    .service(admin::bulk_dismiss_reports)
+                    .service(dashboard::get_dashboard)
+                    .service(dashboard::get_top_sellers)
+                    .service(admin::get_dead_letters)
+                    .service(admin::retry_dead_letters)
+                    .service(admin::discard_dead_letters)
+                    .service(admin::get_moderation_reasons)
+                    .service(admin::create_moderation_reason)
+                    .service(admin::update_moderation_reason)
+                    .service(admin::get_appeals)
+                    .service(admin::decide_appeal)
+                    .service(admin::get_legal_holds)
+                    .service(admin::place_legal_hold)
+                    .service(admin::lift_legal_hold)
+                    .service(admin::grant_moderator)
+                    .service(admin::revoke_moderator)
+                    .service(admin::impersonate_user)
+                    .service(admin::restore_user)
+                    .service(admin::get_runtime_config)
+                    .service(admin::reload_runtime_config)
+                    .service(admin::migrate_field_encryption)
+                    .service(admin::lookup_users_by_name)
+                    .service(moderation::get_reported_offers)
+                    .service(moderation::get_report)
+                    .service(moderation::resolve_report)
+                    .service(offer_questions::set_question_hidden)
+                    .service(serial_blacklist::get_blacklisted_serials)
+                    .service(serial_blacklist::add_blacklisted_serial)
+                    .service(serial_blacklist::remove_blacklisted_serial)
+                    .service(featured::feature_offer)
+                    .service(featured::unfeature_offer)
+                    .service(authenticity::request_authentication)
+                    .service(authenticity::get_authentication_requests)
+                    .service(authenticity::review_authentication_request)
+                    .service(seller_verification::apply_for_verification)
+                    .service(seller_verification::get_verification_requests)
+                    .service(seller_verification::review_verification_request)
+                    .service(seller_verification::revoke_verification)
+                    .service(listing_rules::get_listing_rules)
+                    .service(listing_rules::create_listing_rule)
+                    .service(listing_rules::delete_listing_rule)
+                    .service(fees::get_fee_rules)
+                    .service(fees::create_fee_rule)
+                    .service(fees::update_fee_rule)
+                    .service(fees::delete_fee_rule)
+                    .service(taxes::get_tax_rates)
+                    .service(taxes::set_tax_rate)
+                    .service(taxes::delete_tax_rate)
+                    .service(promo_codes::get_promo_codes)
+                    .service(promo_codes::create_promo_code)
+                    .service(promo_codes::deactivate_promo_code)
+                    .service(promo_codes::get_promo_redemptions)
+                    .service(config_bundle::export_config_bundle)
+                    .service(config_bundle::import_config_bundle)
+                    .service(legal_texts::get_legal_text)
+                    .service(legal_texts::get_legal_texts)
+                    .service(legal_texts::create_legal_text_version)
+                    .service(appeals::create_appeal)
+                    .service(appeals::get_my_moderation_actions),
+            )
+            // Serve static files from the "web" directory
+            // This order is important: specific paths before generic
+            .service(fs::Files::new(offer_images::IMAGES_PATH, &image_dir))
+            .service(fs::Files::new("/web", "./web").index_file("index.html"))
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
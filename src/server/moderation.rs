@@ -0,0 +1,419 @@
+//! src/server/moderation.rs
+//!
+//! This module defines the routes available to community moderators (and admins), such as the
+//! review queue of reported offers and the resolution of reports.
+
+use super::admin::{notify_sanction, require_reason};
+use crate::database::moderation::{ReportStatus, SanctionKind};
+use crate::database::{Database, Role, record_key};
+use crate::response::{ApiError, ApiResponse};
+use crate::scopes::{Moderation, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, post, web};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use validator::Validate;
+use validator_derive::Validate;
+
+/// The action a moderator takes to resolve a report.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ReportResolution {
+    /// Close the report without action.
+    Dismiss,
+    /// Hide the reported offer from the public listings.
+    RemoveOffer,
+    /// Ban the seller of the reported offer.
+    SuspendSeller,
+}
+
+impl ReportResolution {
+    /// Returns the name of the action recorded in the audit log.
+    fn audit_action(&self) -> &'static str {
+        match self {
+            ReportResolution::Dismiss => "dismiss_report",
+            ReportResolution::RemoveOffer => "remove_reported_offer",
+            ReportResolution::SuspendSeller => "suspend_reported_seller",
+        }
+    }
+}
+
+/// Struct representing the resolve report request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct ResolveReportRequest {
+    action: ReportResolution,
+    reason_code: Option<String>,
+    #[validate(length(max = 2000, message = "Note must be at most 2000 characters long"))]
+    note: Option<String>,
+}
+
+/// Ensures the authenticated user is a moderator or an admin.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// A `Result` containing the user's ID, or the `ApiError` to return if the user may not moderate.
+pub(super) async fn require_moderator(
+    db: &Database,
+    req: &HttpRequest,
+) -> Result<String, ApiError> {
+    let user_id = RequireScope::<Moderation>::check(req)?.user_id;
+
+    match db.get_user_by_id(user_id.clone()).await {
+        Ok(Some(user)) if matches!(user.role, Role::Moderator | Role::Admin) && !user.banned => {
+            Ok(user_id)
+        }
+        Ok(_) => {
+            tracing::warn!(
+                "User {} without moderator rights tried to access a moderator route",
+                user_id
+            );
+            Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "Moderator privileges required.",
+            ))
+        }
+        Err(e) => {
+            tracing::error!("Failed to load user for moderator check: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to verify moderator privileges.",
+            ))
+        }
+    }
+}
+
+/// Handles requests to list the open reports together with the reported offers.
+///
+/// This route is restricted to moderators and admins. Reported offers are included even if they
+/// are hidden from the public listings.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the open reports or an error.
+#[get("moderation/reports")]
+pub(super) async fn get_reported_offers(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<Vec<serde_json::Value>> {
+    if let Err(error) = require_moderator(&db, &req).await {
+        return error.into();
+    }
+
+    let reports = match db.get_open_reports().await {
+        Ok(reports) => reports,
+        Err(e) => {
+            tracing::error!("Failed to retrieve open reports: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve reports.",
+            );
+        }
+    };
+
+    let mut entries = Vec::with_capacity(reports.len());
+    for report in reports {
+        let offer = match db.get_offer_by_id(report.offer_id.clone()).await {
+            Ok(offer) => offer,
+            Err(e) => {
+                tracing::error!("Failed to retrieve reported offer: {:?}", e);
+                None
+            }
+        };
+        entries.push(json!({
+            "report": report,
+            "offer": offer
+        }));
+    }
+
+    ApiResponse::ok(entries)
+}
+
+/// Handles requests to inspect a report together with the reported offer, its seller, the
+/// seller's moderation history and all reports against the offer.
+///
+/// This route is restricted to moderators and admins.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the report ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the report and its context or an error.
+#[get("moderation/reports/{report_id}")]
+pub(super) async fn get_report(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<serde_json::Value> {
+    if let Err(error) = require_moderator(&db, &req).await {
+        return error.into();
+    }
+
+    let report = match db.get_report(path.into_inner()).await {
+        Ok(Some(report)) => report,
+        Ok(None) => return ApiResponse::error(StatusCode::NOT_FOUND, "Report not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve report: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve report.",
+            );
+        }
+    };
+
+    let offer = match db.get_offer_by_id(report.offer_id.clone()).await {
+        Ok(offer) => offer,
+        Err(e) => {
+            tracing::error!("Failed to retrieve reported offer: {:?}", e);
+            None
+        }
+    };
+    let offer_reports = match db.get_reports_for_offer(report.offer_id.clone()).await {
+        Ok(reports) => reports,
+        Err(e) => {
+            tracing::error!("Failed to retrieve reports of offer: {:?}", e);
+            Vec::new()
+        }
+    };
+
+    let mut seller = None;
+    let mut seller_actions = Vec::new();
+    if let Some(offer) = &offer {
+        let seller_id = record_key(&offer.seller_id);
+        match db.get_user_by_id(seller_id.clone()).await {
+            Ok(Some(user)) => {
+                seller = Some(json!({
+                    "id": seller_id,
+                    "username": user.username,
+                    "role": user.role,
+                    "banned": user.banned,
+                    "created_at": user.created_at
+                }));
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to retrieve seller of reported offer: {:?}", e),
+        }
+        match db.get_moderation_actions_for_user(seller_id).await {
+            Ok(actions) => seller_actions = actions,
+            Err(e) => tracing::error!("Failed to retrieve moderation actions of seller: {:?}", e),
+        }
+    }
+
+    ApiResponse::ok(json!({
+        "report": report,
+        "offer": offer,
+        "seller": seller,
+        "seller_moderation_actions": seller_actions,
+        "offer_reports": offer_reports
+    }))
+}
+
+/// Handles requests to resolve an open report by dismissing it, removing the reported offer or
+/// suspending its seller.
+///
+/// This route is restricted to moderators and admins. Removing the offer or suspending the seller
+/// requires an active moderation reason code, notifies the seller with the reason and closes all
+/// open reports against the offer. Moderators cannot suspend themselves, other moderators or
+/// admins. The resolution is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the report ID.
+/// * `body` - JSON payload containing the action, the reason code and an optional note.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the number of closed reports or an error.
+#[post("moderation/reports/{report_id}/resolve")]
+pub(super) async fn resolve_report(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ResolveReportRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let moderator_id = match require_moderator(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Resolve report request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let report_id = path.into_inner();
+    let report = match db.get_report(report_id.clone()).await {
+        Ok(Some(report)) if report.status == ReportStatus::Open => report,
+        Ok(Some(_)) => {
+            return ApiResponse::error(StatusCode::CONFLICT, "Report is already closed.");
+        }
+        Ok(None) => return ApiResponse::error(StatusCode::NOT_FOUND, "Report not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve report: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve report.",
+            );
+        }
+    };
+
+    let (target_id, closed) = match body.action {
+        ReportResolution::Dismiss => {
+            match db
+                .close_report(report_id.clone(), ReportStatus::Dismissed)
+                .await
+            {
+                Ok(true) => (report.offer_id.clone(), 1),
+                Ok(false) => {
+                    return ApiResponse::error(StatusCode::CONFLICT, "Report is already closed.");
+                }
+                Err(e) => {
+                    tracing::error!("Failed to dismiss report: {:?}", e);
+                    return ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to dismiss report.",
+                    );
+                }
+            }
+        }
+        ReportResolution::RemoveOffer | ReportResolution::SuspendSeller => {
+            let Some(code) = body.reason_code.as_deref() else {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    "A reason code is required to remove an offer or suspend a seller.",
+                );
+            };
+            let reason = match require_reason(&db, code).await {
+                Ok(reason) => reason,
+                Err(error) => return error.into(),
+            };
+            let offer = match db.get_offer_by_id(report.offer_id.clone()).await {
+                Ok(Some(offer)) => offer,
+                Ok(None) => {
+                    return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+                }
+                Err(e) => {
+                    tracing::error!("Failed to retrieve reported offer: {:?}", e);
+                    return ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to retrieve offer.",
+                    );
+                }
+            };
+            let seller_id = record_key(&offer.seller_id);
+
+            let target_id = if body.action == ReportResolution::RemoveOffer {
+                if let Err(e) = db.set_offer_hidden(report.offer_id.clone(), true).await {
+                    tracing::error!("Failed to hide reported offer: {:?}", e);
+                    return ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to remove offer.",
+                    );
+                }
+                notify_sanction(
+                    &db,
+                    &moderator_id,
+                    seller_id,
+                    SanctionKind::OfferHidden,
+                    &report.offer_id,
+                    &reason,
+                )
+                .await;
+                report.offer_id.clone()
+            } else {
+                if seller_id == moderator_id {
+                    return ApiResponse::error(
+                        StatusCode::BAD_REQUEST,
+                        "You cannot suspend yourself.",
+                    );
+                }
+                match db.get_user_by_id(seller_id.clone()).await {
+                    Ok(Some(seller)) if matches!(seller.role, Role::Moderator | Role::Admin) => {
+                        return ApiResponse::error(
+                            StatusCode::FORBIDDEN,
+                            "Moderators and admins cannot be suspended from the report queue.",
+                        );
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        return ApiResponse::error(StatusCode::NOT_FOUND, "Seller not found.");
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to retrieve seller of reported offer: {:?}", e);
+                        return ApiResponse::error(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to retrieve seller.",
+                        );
+                    }
+                }
+                if let Err(e) = db.set_user_banned(seller_id.clone(), true).await {
+                    tracing::error!("Failed to suspend seller: {:?}", e);
+                    return ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to suspend seller.",
+                    );
+                }
+                notify_sanction(
+                    &db,
+                    &moderator_id,
+                    seller_id.clone(),
+                    SanctionKind::UserBanned,
+                    &seller_id,
+                    &reason,
+                )
+                .await;
+                seller_id
+            };
+
+            match db
+                .close_reports_for_offer(report.offer_id.clone(), ReportStatus::Resolved)
+                .await
+            {
+                Ok(closed) => (target_id, closed),
+                Err(e) => {
+                    tracing::error!("Failed to resolve reports of offer: {:?}", e);
+                    return ApiResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to resolve reports.",
+                    );
+                }
+            }
+        }
+    };
+
+    let mut details = format!("Report {} ({})", report_id, report.reason);
+    if let Some(code) = &body.reason_code {
+        details.push_str(&format!(", reason: {}", code));
+    }
+    if let Some(note) = &body.note {
+        details.push_str(&format!(", note: {}", note));
+    }
+    if let Err(e) = db
+        .record_audit_entry(
+            moderator_id,
+            body.action.audit_action(),
+            vec![report_id, target_id],
+            details,
+        )
+        .await
+    {
+        tracing::error!("Failed to record audit entry: {:?}", e);
+    }
+
+    ApiResponse::ok(json!({ "closed_reports": closed })).with_message("Report resolved.")
+}
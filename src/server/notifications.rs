@@ -0,0 +1,227 @@
+//! src/server/notifications.rs
+//!
+//! This module defines the routes listing a user's notifications, marking them as read and
+//! counting the unread ones, and the long-poll route delivering notifications to clients behind
+//! proxies that break both WebSockets and server-sent events.
+
+use crate::database::Database;
+use crate::database::notifications::Notification;
+use crate::database::pagination::Pagination;
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileRead, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The number of seconds a poll waits if the client does not ask for a timeout.
+const DEFAULT_POLL_TIMEOUT_SECONDS: u64 = 30;
+
+/// The longest a poll may wait, so proxies don't cut the request off first.
+const MAX_POLL_TIMEOUT_SECONDS: u64 = 60;
+
+/// The query parameters of a notification poll.
+#[derive(Debug, Deserialize)]
+pub(super) struct PollQuery {
+    /// The number of seconds to wait for a notification (defaults to 30, at most 60).
+    timeout: Option<u64>,
+    /// Only return notifications created after this RFC 3339 timestamp, usually the
+    /// `created_at` of the last notification the client received.
+    since: Option<String>,
+}
+
+/// The query parameters of the notification list.
+#[derive(Debug, Deserialize)]
+pub(super) struct NotificationQuery {
+    /// Whether to only list unread notifications (defaults to `false`).
+    #[serde(default)]
+    unread: bool,
+}
+
+/// The number of a user's unread notifications.
+#[derive(Debug, Serialize)]
+pub(super) struct UnreadCount {
+    /// The number of unread notifications.
+    unread: u64,
+}
+
+/// Handles requests for the authenticated user's notifications.
+///
+/// Notifications are listed newest first and paginated like `GET /api/offers`. `?unread=true`
+/// skips notifications the user has read.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+/// * `query` - Query parameters selecting unread notifications.
+/// * `pagination` - Query parameters containing the requested page.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing a page of notifications and the pagination details, or an error.
+#[get("notifications")]
+pub(super) async fn get_notifications(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+    query: web::Query<NotificationQuery>,
+    pagination: web::Query<Pagination>,
+) -> ApiResponse<Vec<Notification>> {
+    match db
+        .get_notifications(&auth.user_id, query.unread, &pagination)
+        .await
+    {
+        Ok((notifications, page_info)) => ApiResponse::ok(notifications).with_pagination(page_info),
+        Err(e) => {
+            tracing::error!("Failed to retrieve notifications: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve notifications.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the number of the authenticated user's unread notifications, e.g. for
+/// the badge on the notification icon.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the unread count or an error.
+#[get("notifications/unread-count")]
+pub(super) async fn get_unread_count(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+) -> ApiResponse<UnreadCount> {
+    match db.count_unread_notifications(&auth.user_id).await {
+        Ok(unread) => ApiResponse::ok(UnreadCount { unread }),
+        Err(e) => {
+            tracing::error!("Failed to count unread notifications: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to count unread notifications.",
+            )
+        }
+    }
+}
+
+/// Handles requests to mark one of the authenticated user's notifications as read.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the notification ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated notification or an error.
+#[post("notifications/{notification_id}/read")]
+pub(super) async fn mark_notification_read(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Notification> {
+    match db
+        .mark_notification_read(&auth.user_id, &path.into_inner())
+        .await
+    {
+        Ok(Some(notification)) => ApiResponse::ok(notification),
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Notification not found."),
+        Err(e) => {
+            tracing::error!("Failed to mark notification as read: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to mark notification as read.",
+            )
+        }
+    }
+}
+
+/// Handles requests to mark all of the authenticated user's notifications as read.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the remaining unread count, which is zero, or an error.
+#[post("notifications/read-all")]
+pub(super) async fn mark_all_notifications_read(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+) -> ApiResponse<UnreadCount> {
+    match db.mark_all_notifications_read(&auth.user_id).await {
+        Ok(marked) => ApiResponse::ok(UnreadCount { unread: 0 })
+            .with_message(format!("Marked {} notifications as read.", marked)),
+        Err(e) => {
+            tracing::error!("Failed to mark notifications as read: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to mark notifications as read.",
+            )
+        }
+    }
+}
+
+/// Handles long-poll requests for the authenticated user's unread notifications.
+///
+/// Holds the request until a notification arrives or the timeout elapses. Unread notifications
+/// are returned immediately, so clients pass `?since=` to only wait for new ones.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+/// * `query` - Query parameters containing the timeout and the last received notification.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the notifications, empty if none arrived in time, or an error.
+#[get("notifications/poll")]
+pub(super) async fn poll_notifications(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+    query: web::Query<PollQuery>,
+) -> ApiResponse<Vec<Notification>> {
+    if query
+        .since
+        .as_deref()
+        .is_some_and(|since| DateTime::parse_from_rfc3339(since).is_err())
+    {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "since must be an RFC 3339 timestamp.",
+        );
+    }
+    let timeout = query
+        .timeout
+        .unwrap_or(DEFAULT_POLL_TIMEOUT_SECONDS)
+        .min(MAX_POLL_TIMEOUT_SECONDS);
+
+    match db
+        .wait_for_notifications(
+            &auth.user_id,
+            query.since.as_deref(),
+            Duration::from_secs(timeout),
+        )
+        .await
+    {
+        Ok(notifications) => ApiResponse::ok(notifications),
+        Err(e) => {
+            tracing::error!("Failed to poll notifications: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve notifications.",
+            )
+        }
+    }
+}
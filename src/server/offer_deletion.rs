@@ -0,0 +1,181 @@
+//! src/server/offer_deletion.rs
+//!
+//! This module defines the routes sellers use to find and restore the offers they deleted, the
+//! admin route purging deleted offers on demand, and the background job purging deleted offers
+//! whose restore window has passed.
+
+use super::admin::require_admin;
+use super::offer_images::remove_image_files;
+use crate::database::offer_deletion::OFFER_RESTORE_DAYS;
+use crate::database::{Database, Offer};
+use crate::errors::custom_errors::CustomError;
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersRead, OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, post, web};
+use std::time::{Duration, Instant};
+
+/// How often the purge job looks for deleted offers past their restore window.
+const PURGE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// The name of the purge job in the task metrics.
+const PURGE_TASK: &str = "offer_purge";
+
+/// Purges the deleted offers whose restore window has passed and removes their image files.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+///
+/// # Returns
+///
+/// A `Result` containing the number of purged offers or a `CustomError` if the purge fails.
+async fn purge_offers(db: &Database) -> Result<usize, CustomError> {
+    let purged = db.purge_deleted_offers().await?;
+    for offer in &purged {
+        remove_image_files(db, &offer.images).await;
+    }
+    Ok(purged.len())
+}
+
+/// Starts the background job that purges deleted offers whose restore window has passed.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+pub(super) fn spawn_purge_job(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            let outcome = match purge_offers(&db).await {
+                Ok(_) => TaskOutcome::Success,
+                Err(e) => {
+                    tracing::error!("Failed to purge deleted offers: {:?}", e);
+                    TaskOutcome::Failure
+                }
+            };
+            TaskMetrics::global().record_run(PURGE_TASK, outcome, started.elapsed());
+        }
+    });
+}
+
+/// Handles requests for the offers the authenticated seller deleted and can still restore.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the deleted offers, most recently deleted first, or an error.
+#[get("user/deleted-offers")]
+pub(super) async fn get_deleted_offers(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+) -> ApiResponse<Vec<Offer>> {
+    match db.get_deleted_offers(&auth.user_id).await {
+        Ok(offers) => ApiResponse::ok(offers),
+        Err(e) => {
+            tracing::error!("Failed to retrieve deleted offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve deleted offers.",
+            )
+        }
+    }
+}
+
+/// Handles requests to restore an offer the authenticated seller deleted.
+///
+/// Offers can be restored for `OFFER_RESTORE_DAYS` days after their deletion. Offers deleted by a
+/// moderator can only be restored through an appeal.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the restored offer or an error.
+#[post("offers/{offer_id}/restore")]
+pub(super) async fn restore_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    match db
+        .restore_offer(path.into_inner(), Some(auth.user_id))
+        .await
+    {
+        Ok(Some(offer)) => ApiResponse::ok(offer).with_message("Offer restored successfully."),
+        Ok(None) => ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            format!(
+                "No offer you deleted within the last {} days found.",
+                OFFER_RESTORE_DAYS
+            ),
+        ),
+        Err(e) => {
+            tracing::error!("Failed to restore offer: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to restore offer.",
+            )
+        }
+    }
+}
+
+/// Handles requests to purge the deleted offers whose restore window has passed right away,
+/// instead of waiting for the purge job.
+///
+/// This route is restricted to admins. The purge is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the number of purged offers or an error.
+#[post("admin/offers/purge")]
+pub(super) async fn purge_deleted_offers(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<usize> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    match purge_offers(&db).await {
+        Ok(purged) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "purge_deleted_offers",
+                    Vec::new(),
+                    format!("Purged {} deleted offers", purged),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(purged).with_message("Deleted offers purged successfully.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to purge deleted offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to purge deleted offers.",
+            )
+        }
+    }
+}
@@ -0,0 +1,1061 @@
+//! src/server/offer_images.rs
+//!
+//! This module defines the image upload routes of offers. Uploaded images are validated, stored
+//! in the upload directory and served as static files under `/images/offers`, together with the
+//! WebP thumbnails generated for them in the background. If `WATERMARK_IMAGES` is set, every
+//! offer gets its own thumbnails carrying its watermark instead of sharing them with other offers.
+//!
+//! Besides the multipart upload, images can be uploaded in two steps: the API issues a signed
+//! upload URL, the client streams the file there, and a finalize request validates the file and
+//! attaches it to the offer. Staged files are written chunk by chunk and never held in memory.
+//!
+//! Uploaded images that look like images of other sellers' offers are reported to the moderators.
+
+use crate::cpu_pool::CpuPool;
+use crate::database::dead_letters::DeadLetterJob;
+use crate::database::image_blobs::{ImageBlob, content_hash, content_hash_of_url};
+use crate::database::offer_images::{
+    ImageFormat, MAX_ALT_TEXT_LENGTH, MAX_IMAGE_BYTES, MAX_OFFER_IMAGES, OfferImage, THUMBNAIL_SIZE,
+};
+use crate::database::photo_matching::{parse_perceptual_hash, perceptual_hash};
+use crate::database::{Database, Offer, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::job_queue::{JobQueues, Queue};
+use crate::jwt::{
+    IMAGE_UPLOAD_VALIDITY_MINUTES, generate_image_upload_jwt, validate_image_upload_jwt,
+};
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::{ApiError, ApiResponse};
+use crate::scopes::{OffersWrite, RequireScope};
+use crate::upload_storage::{UploadStorage, staging_dir};
+use crate::watermark::{apply_watermark, watermark_enabled};
+use actix_multipart::Multipart;
+use actix_web::http::StatusCode;
+use actix_web::{post, put, web};
+use dotenvy::var;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+/// The path uploaded images are served from.
+pub(super) const IMAGES_PATH: &str = "/images/offers";
+
+/// Struct representing the upload URL request body
+#[derive(Debug, Deserialize, Serialize)]
+struct UploadUrlRequest {
+    content_type: String,
+}
+
+/// Struct representing the query parameters of a direct upload
+#[derive(Debug, Deserialize)]
+struct DirectUploadQuery {
+    token: String,
+}
+
+/// Struct representing the finalize upload request body
+#[derive(Debug, Deserialize, Serialize)]
+struct FinalizeUploadRequest {
+    upload_token: String,
+    #[serde(default)]
+    alt_text: String,
+}
+
+/// Struct representing the alternative text request body
+#[derive(Debug, Deserialize, Serialize)]
+struct AltTextRequest {
+    url: String,
+    #[serde(default)]
+    alt_text: String,
+}
+
+/// How often a thumbnail is retried if the `CpuPool` is too busy to render it.
+const THUMBNAIL_MAX_RETRIES: u32 = 3;
+
+/// How long to wait before the first retry of a thumbnail. Later retries wait longer.
+const THUMBNAIL_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Returns the directory uploaded images are stored in (`IMAGE_UPLOAD_DIR`, defaults to
+/// `./uploads/offers`).
+pub(super) fn upload_dir() -> PathBuf {
+    var("IMAGE_UPLOAD_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("./uploads/offers"))
+}
+
+/// Deletes the stored file served from a path.
+///
+/// Failures are logged, as a leftover file doesn't affect the offer.
+async fn remove_file(url: &str) {
+    let Some(file_name) = url.rsplit('/').next() else {
+        return;
+    };
+    if let Err(e) = tokio::fs::remove_file(upload_dir().join(file_name)).await {
+        tracing::warn!("Failed to delete image file {}: {}", file_name, e);
+    }
+}
+
+/// Releases the stored files of images, e.g. after their offer was deleted. Files and their
+/// shared thumbnails are only deleted once no other image uses them, watermarked thumbnails
+/// right away.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `images` - The images whose files are released.
+pub(super) async fn remove_image_files(db: &Database, images: &[OfferImage]) {
+    for image in images {
+        if let Some(hash) = content_hash_of_url(&image.url) {
+            let released = match db.release_image_blob(hash).await {
+                Ok(Some(0) | None) => true,
+                Ok(Some(_)) => false,
+                Err(e) => {
+                    // Keeping a file is safer than deleting one that is still used
+                    tracing::error!("Failed to release image file {}: {:?}", hash, e);
+                    false
+                }
+            };
+            if !released {
+                if let Some(thumbnail_url) = &image.thumbnail_url
+                    && is_watermarked_thumbnail(&image.url, thumbnail_url)
+                {
+                    remove_file(thumbnail_url).await;
+                }
+                continue;
+            }
+        }
+        remove_file(&image.url).await;
+        if let Some(thumbnail_url) = &image.thumbnail_url {
+            remove_file(thumbnail_url).await;
+        }
+    }
+}
+
+/// Normalizes the alternative text of an image: surrounding whitespace is removed and an empty
+/// text means the image has none.
+///
+/// # Arguments
+///
+/// * `alt_text` - The text entered by the seller.
+///
+/// # Returns
+///
+/// A `Result` containing the normalized text, or an `ApiError` if it is longer than
+/// `MAX_ALT_TEXT_LENGTH` characters.
+fn normalize_alt_text(alt_text: &str) -> Result<Option<String>, ApiError> {
+    let alt_text = alt_text.trim();
+    if alt_text.chars().count() > MAX_ALT_TEXT_LENGTH {
+        return Err(alt_text_too_long());
+    }
+    Ok((!alt_text.is_empty()).then(|| alt_text.to_string()))
+}
+
+/// The error returned for an alternative text longer than `MAX_ALT_TEXT_LENGTH` characters.
+fn alt_text_too_long() -> ApiError {
+    ApiError::new(
+        StatusCode::BAD_REQUEST,
+        format!(
+            "Alternative texts must be at most {} characters long.",
+            MAX_ALT_TEXT_LENGTH
+        ),
+    )
+}
+
+/// Stores an uploaded image, reusing the stored file and thumbnail if an image with the same
+/// content was uploaded before. Watermarked thumbnails belong to one offer and are never reused.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `format` - The format of the image.
+/// * `data` - The image data.
+/// * `alt_text` - The alternative text of the image (optional).
+///
+/// # Returns
+///
+/// A `Result` containing the image and its perceptual hash, if it could be computed, or the
+/// `ApiError` to return if it couldn't be stored.
+async fn store_image(
+    db: &Database,
+    format: ImageFormat,
+    data: &[u8],
+    alt_text: Option<String>,
+) -> Result<(OfferImage, Option<u64>), ApiError> {
+    let hash = content_hash(data);
+    let file_name = format!("{}.{}", hash, format.extension());
+    let path = upload_dir().join(&file_name);
+    if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        // Written under a temporary name first, so the file is never served half-written
+        let partial = upload_dir().join(format!("{}.part", Uuid::new_v4()));
+        let stored = match tokio::fs::write(&partial, data).await {
+            Ok(()) => tokio::fs::rename(&partial, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            tracing::error!("Failed to store uploaded image: {}", e);
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store images.",
+            ));
+        }
+    }
+
+    let blob = db.acquire_image_blob(&hash).await.map_err(|e| {
+        tracing::error!("Failed to reference image file {}: {:?}", hash, e);
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store images.")
+    })?;
+    let perceptual_hash = image_perceptual_hash(db, &hash, &blob, data).await;
+    let image = OfferImage {
+        url: format!("{}/{}", IMAGES_PATH, file_name),
+        content_type: format.content_type().to_string(),
+        size: data.len() as u64,
+        thumbnail_url: blob.thumbnail_url.filter(|_| !watermark_enabled()),
+        alt_text,
+    };
+    Ok((image, perceptual_hash))
+}
+
+/// Returns the perceptual hash of an uploaded image, computing it on the `CpuPool` and storing it
+/// with the file unless the file already has one.
+///
+/// Failures are logged and skip the image's reuse check, as they don't affect the upload.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `hash` - The content hash of the image's file.
+/// * `blob` - The stored file.
+/// * `data` - The image data.
+///
+/// # Returns
+///
+/// The perceptual hash, or `None` if it couldn't be computed.
+async fn image_perceptual_hash(
+    db: &Database,
+    hash: &str,
+    blob: &ImageBlob,
+    data: &[u8],
+) -> Option<u64> {
+    if let Some(stored) = blob
+        .perceptual_hash
+        .as_deref()
+        .and_then(parse_perceptual_hash)
+    {
+        return Some(stored);
+    }
+
+    let data = data.to_vec();
+    let computed = CpuPool::global()
+        .run(move || image::load_from_memory(&data).map(|image| perceptual_hash(&image)))
+        .await;
+    let perceptual_hash = match computed {
+        Ok(Ok(perceptual_hash)) => perceptual_hash,
+        Ok(Err(e)) => {
+            tracing::warn!(
+                "Failed to decode image {} for its perceptual hash: {}",
+                hash,
+                e
+            );
+            return None;
+        }
+        Err(e) => {
+            tracing::warn!("Skipped perceptual hash of image {}: {:?}", hash, e);
+            return None;
+        }
+    };
+    if let Err(e) = db
+        .set_image_blob_perceptual_hash(hash, perceptual_hash)
+        .await
+    {
+        tracing::error!("Failed to store perceptual hash of image {}: {:?}", hash, e);
+    }
+    Some(perceptual_hash)
+}
+
+/// Files a report for moderators if uploaded images look like images of other sellers' offers.
+///
+/// Failures are logged, as they don't affect the upload.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer` - The offer the images were uploaded for.
+/// * `perceptual_hashes` - The perceptual hashes of the uploaded images.
+async fn report_reused_images(db: &Database, offer: &Offer, perceptual_hashes: &[u64]) {
+    let mut urls = Vec::new();
+    for &perceptual_hash in perceptual_hashes {
+        match db.find_similar_image_blobs(perceptual_hash).await {
+            Ok(hashes) => urls.extend(hashes.iter().flat_map(|hash| {
+                ImageFormat::ALL
+                    .iter()
+                    .map(move |format| format!("{}/{}.{}", IMAGES_PATH, hash, format.extension()))
+            })),
+            Err(e) => {
+                tracing::error!("Failed to find similar images: {:?}", e);
+                return;
+            }
+        }
+    }
+    urls.sort();
+    urls.dedup();
+
+    let reported = match db.find_offers_showing(offer, urls).await {
+        Ok(matches) => db.report_photo_reuse(offer, matches).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = reported {
+        tracing::error!(
+            "Failed to check offer {} for reused images: {:?}",
+            record_key(&offer.id),
+            e
+        );
+    }
+}
+
+/// Downscales an image to fit in `THUMBNAIL_SIZE` pixels, keeping its aspect ratio, optionally
+/// watermarks it, and encodes it as WebP.
+///
+/// # Arguments
+///
+/// * `data` - The image data.
+/// * `watermark` - The ID of the offer to watermark the thumbnail with, if any.
+fn render_thumbnail(data: &[u8], watermark: Option<&str>) -> Result<Vec<u8>, image::ImageError> {
+    let thumbnail = image::load_from_memory(data)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let mut encoded = Cursor::new(Vec::new());
+    match watermark {
+        Some(offer_id) => {
+            let mut thumbnail = thumbnail.to_rgba8();
+            apply_watermark(&mut thumbnail, offer_id);
+            thumbnail.write_to(&mut encoded, image::ImageFormat::WebP)?;
+        }
+        None => thumbnail.write_to(&mut encoded, image::ImageFormat::WebP)?,
+    }
+    Ok(encoded.into_inner())
+}
+
+/// Returns the file name of an image's thumbnail.
+///
+/// # Arguments
+///
+/// * `url` - The path the image is served from.
+/// * `watermark` - The ID of the offer the thumbnail is watermarked with, if any.
+fn thumbnail_file_name(url: &str, watermark: Option<&str>) -> String {
+    let stem = url.rsplit('/').next().unwrap_or_default();
+    let stem = stem.split('.').next().unwrap_or_default();
+    match watermark {
+        Some(offer_id) => format!("{}_{}_thumb.webp", stem, offer_id),
+        None => format!("{}_thumb.webp", stem),
+    }
+}
+
+/// Returns whether a thumbnail is watermarked for one offer rather than shared by every image
+/// with the same file.
+fn is_watermarked_thumbnail(url: &str, thumbnail_url: &str) -> bool {
+    !thumbnail_url.ends_with(&format!("/{}", thumbnail_file_name(url, None)))
+}
+
+/// Generates the thumbnails of uploaded images on the `images` job queue and adds them to the
+/// offer.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `offer_id` - The ID of the offer the images belong to.
+/// * `uploads` - The path and data of every uploaded image.
+fn generate_thumbnails(db: web::Data<Database>, offer_id: String, uploads: Vec<(String, Vec<u8>)>) {
+    for (url, data) in uploads {
+        enqueue_thumbnail(db.clone(), offer_id.clone(), url, data);
+    }
+}
+
+/// Queues the generation of an image's thumbnail on the `images` job queue. If it fails, the job
+/// is dead-lettered, so an admin can retry it.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `offer_id` - The ID of the offer the image belongs to.
+/// * `url` - The path the image is served from.
+/// * `data` - The image data.
+pub(super) fn enqueue_thumbnail(
+    db: web::Data<Database>,
+    offer_id: String,
+    url: String,
+    data: Vec<u8>,
+) {
+    JobQueues::global().enqueue(Queue::Images, async move {
+        let Err(error) = add_thumbnail(&db, &offer_id, &url, data).await else {
+            return TaskOutcome::Success;
+        };
+        let job = DeadLetterJob::Thumbnail { offer_id, url };
+        if let Err(e) = db.record_dead_letter(&job, &error).await {
+            tracing::error!("Failed to dead-letter thumbnail job: {:?}", e);
+        }
+        TaskOutcome::Failure
+    });
+}
+
+/// Reads the stored file of an uploaded image, e.g. to retry its thumbnail.
+///
+/// # Arguments
+///
+/// * `url` - The path the image is served from.
+///
+/// # Returns
+///
+/// A `Result` containing the image data, or the error if the file can't be read.
+pub(super) async fn read_image_file(url: &str) -> std::io::Result<Vec<u8>> {
+    let file_name = url.rsplit('/').next().unwrap_or_default();
+    tokio::fs::read(upload_dir().join(file_name)).await
+}
+
+/// Generates the thumbnail of an uploaded image and adds it to the offer.
+///
+/// Decoding and resizing run on the `CpuPool`, and are retried with increasing delays while the
+/// pool is too busy. Until the thumbnail is generated, the image is shown without one. If
+/// watermarking is enabled, the thumbnail carries the offer's ID and isn't shared with other
+/// offers.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer_id` - The ID of the offer the image belongs to.
+/// * `url` - The path the image is served from.
+/// * `data` - The image data.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok` if the thumbnail was generated, or wasn't needed because the image was
+/// deleted, or containing why the thumbnail couldn't be generated.
+async fn add_thumbnail(
+    db: &Database,
+    offer_id: &str,
+    url: &str,
+    data: Vec<u8>,
+) -> Result<(), String> {
+    let data = Arc::new(data);
+    let watermark = watermark_enabled().then(|| offer_id.to_string());
+    let mut retries = 0;
+    let thumbnail = loop {
+        let data = data.clone();
+        let job_watermark = watermark.clone();
+        match CpuPool::global()
+            .run(move || render_thumbnail(&data, job_watermark.as_deref()))
+            .await
+        {
+            Ok(Ok(thumbnail)) => break thumbnail,
+            Ok(Err(e)) => {
+                tracing::warn!("Failed to generate thumbnail of {}: {}", url, e);
+                return Err(format!("Failed to decode image: {}", e));
+            }
+            Err(CustomError::Overloaded) if retries < THUMBNAIL_MAX_RETRIES => {
+                retries += 1;
+                TaskMetrics::global().record_retry(Queue::Images.as_str());
+                tokio::time::sleep(THUMBNAIL_RETRY_DELAY * retries).await;
+            }
+            Err(e) => {
+                tracing::warn!("Skipped thumbnail of {}: {:?}", url, e);
+                return Err(format!("Failed to render thumbnail: {}", e));
+            }
+        }
+    };
+
+    let file_name = thumbnail_file_name(url, watermark.as_deref());
+    if let Err(e) = tokio::fs::write(upload_dir().join(&file_name), thumbnail).await {
+        tracing::error!("Failed to store thumbnail of {}: {}", url, e);
+        return Err(format!("Failed to store thumbnail: {}", e));
+    }
+    let thumbnail_url = format!("{}/{}", IMAGES_PATH, file_name);
+    // A thumbnail of a shared file is deleted together with the file
+    let shared = match content_hash_of_url(url).filter(|_| watermark.is_none()) {
+        Some(hash) => db
+            .set_image_blob_thumbnail(hash, &thumbnail_url)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to record thumbnail of image file {}: {:?}", hash, e);
+                false
+            }),
+        None => false,
+    };
+    match db
+        .set_image_thumbnail(offer_id.to_string(), url.to_string(), thumbnail_url.clone())
+        .await
+    {
+        Ok(true) => Ok(()),
+        // The image or its offer was deleted in the meantime
+        Ok(false) => {
+            if !shared {
+                remove_file(&thumbnail_url).await;
+            }
+            Ok(())
+        }
+        Err(e) => {
+            tracing::error!("Failed to add thumbnail to offer {}: {:?}", offer_id, e);
+            if !shared {
+                remove_file(&thumbnail_url).await;
+            }
+            Err(format!("Failed to add thumbnail to offer: {}", e))
+        }
+    }
+}
+
+/// Reads the images of a multipart upload into memory, validating their type and size.
+///
+/// # Arguments
+///
+/// * `payload` - The multipart request body. A part named `alt_text` holds the alternative text
+///   of the image before it; every other part is treated as an image.
+/// * `max_images` - The number of images the offer can still take.
+///
+/// # Returns
+///
+/// A `Result` containing the format, data and alternative text of every image, or the error
+/// response.
+async fn read_images(
+    mut payload: Multipart,
+    max_images: usize,
+) -> Result<Vec<(ImageFormat, Vec<u8>, Option<String>)>, ApiError> {
+    let mut images: Vec<(ImageFormat, Vec<u8>, Option<String>)> = Vec::new();
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|e| {
+            tracing::warn!("Invalid multipart upload: {}", e);
+            ApiError::new(StatusCode::BAD_REQUEST, "Invalid upload.".to_string())
+        })?;
+        if field.name() == Some("alt_text") {
+            let Some((_, _, alt_text @ None)) = images.last_mut() else {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "Every alternative text must follow the image it describes.",
+                ));
+            };
+            let mut text = Vec::new();
+            while let Some(chunk) = field.next().await {
+                let chunk = chunk.map_err(|e| {
+                    tracing::warn!("Failed to read alternative text: {}", e);
+                    ApiError::new(StatusCode::BAD_REQUEST, "Invalid upload.".to_string())
+                })?;
+                // A UTF-8 character takes at most 4 bytes
+                if text.len() + chunk.len() > MAX_ALT_TEXT_LENGTH * 4 {
+                    return Err(alt_text_too_long());
+                }
+                text.extend_from_slice(&chunk);
+            }
+            let text = String::from_utf8(text).map_err(|_| {
+                ApiError::new(StatusCode::BAD_REQUEST, "Invalid upload.".to_string())
+            })?;
+            *alt_text = normalize_alt_text(&text)?;
+            continue;
+        }
+        if images.len() == max_images {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!("An offer can have at most {} images.", MAX_OFFER_IMAGES),
+            ));
+        }
+        let format = field
+            .content_type()
+            .and_then(|mime| ImageFormat::from_content_type(mime.essence_str()))
+            .ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Only JPEG, PNG and WebP images are allowed.".to_string(),
+                )
+            })?;
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|e| {
+                tracing::warn!("Failed to read uploaded image: {}", e);
+                ApiError::new(StatusCode::BAD_REQUEST, "Invalid upload.".to_string())
+            })?;
+            if data.len() + chunk.len() > MAX_IMAGE_BYTES {
+                return Err(ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!(
+                        "Images must be at most {} MiB.",
+                        MAX_IMAGE_BYTES / (1024 * 1024)
+                    ),
+                ));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        if !format.matches(&data) {
+            return Err(ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("The file is not a valid {} image.", format.content_type()),
+            ));
+        }
+        images.push((format, data, None));
+    }
+
+    if images.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "No images were uploaded.".to_string(),
+        ));
+    }
+    Ok(images)
+}
+
+/// Handles requests to upload images for an offer.
+///
+/// Only the seller can upload images. The request body is `multipart/form-data` with one part
+/// per image; each must be a JPEG, PNG or WebP image of at most `MAX_IMAGE_BYTES` bytes, and an
+/// offer can have at most `MAX_OFFER_IMAGES` images.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `payload` - The multipart request body.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated offer or an error.
+#[post("offers/{offer_id}/images")]
+pub(super) async fn upload_offer_images(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    payload: Multipart,
+) -> ApiResponse<Offer> {
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for image upload: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+
+    if !offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to upload images for this offer.",
+        );
+    }
+
+    let max_images = MAX_OFFER_IMAGES.saturating_sub(offer.images.len());
+    let uploads = match read_images(payload, max_images).await {
+        Ok(uploads) => uploads,
+        Err(error) => return error.into(),
+    };
+
+    let mut images = Vec::with_capacity(uploads.len());
+    let mut perceptual_hashes = Vec::with_capacity(uploads.len());
+    let mut thumbnail_jobs = Vec::with_capacity(uploads.len());
+    for (format, data, alt_text) in uploads {
+        let (image, perceptual_hash) = match store_image(&db, format, &data, alt_text).await {
+            Ok(stored) => stored,
+            Err(error) => {
+                remove_image_files(&db, &images).await;
+                return error.into();
+            }
+        };
+        if image.thumbnail_url.is_none() {
+            thumbnail_jobs.push((image.url.clone(), data));
+        }
+        images.push(image);
+        perceptual_hashes.extend(perceptual_hash);
+    }
+
+    match db.add_offer_images(offer_id.clone(), images.clone()).await {
+        Ok(Some(offer)) => {
+            report_reused_images(&db, &offer, &perceptual_hashes).await;
+            generate_thumbnails(db, record_key(&offer.id), thumbnail_jobs);
+            ApiResponse::created(offer).with_message(
+                "Images uploaded successfully. Thumbnails are generated in the background.",
+            )
+        }
+        Ok(None) => {
+            remove_image_files(&db, &images).await;
+            ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!("An offer can have at most {} images.", MAX_OFFER_IMAGES),
+            )
+        }
+        Err(e) => {
+            tracing::error!("Failed to add images to offer: {:?}", e);
+            remove_image_files(&db, &images).await;
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store images.")
+        }
+    }
+}
+
+/// Loads an offer and ensures the authenticated user is its seller.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the authenticated user.
+/// * `offer_id` - The ID of the offer.
+///
+/// # Returns
+///
+/// A `Result` containing the offer, or the `ApiError` to return.
+async fn require_own_offer(
+    db: &Database,
+    user_id: &str,
+    offer_id: &str,
+) -> Result<Offer, ApiError> {
+    match db.get_offer_by_id(offer_id.to_string()).await {
+        Ok(Some(offer)) if offer.is_seller(user_id) => Ok(offer),
+        Ok(Some(_)) => Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to change the images of this offer.",
+        )),
+        Ok(None) => Err(ApiError::new(StatusCode::NOT_FOUND, "Offer not found.")),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for image upload: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            ))
+        }
+    }
+}
+
+/// Handles requests for a signed URL to upload one image of an offer directly.
+///
+/// Only the seller can request upload URLs, and only while the offer can take more images. The
+/// client sends the image to `upload_url` with `method` and `headers` within
+/// `IMAGE_UPLOAD_VALIDITY_MINUTES`, then finalizes it with the `upload_token`. With S3 storage,
+/// the URL is presigned for the bucket, so the image doesn't pass through the server.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `storage` - The storage the image is uploaded to.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the content type of the image.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the upload URL and token, or an error.
+#[post("offers/{offer_id}/images/upload-url")]
+pub(super) async fn create_image_upload_url(
+    db: web::Data<Database>,
+    storage: web::Data<dyn UploadStorage>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<UploadUrlRequest>,
+) -> ApiResponse<serde_json::Value> {
+    let offer_id = path.into_inner();
+    let offer = match require_own_offer(&db, &auth.user_id, &offer_id).await {
+        Ok(offer) => offer,
+        Err(error) => return error.into(),
+    };
+    if offer.images.len() >= MAX_OFFER_IMAGES {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            format!("An offer can have at most {} images.", MAX_OFFER_IMAGES),
+        );
+    }
+    let Some(format) = ImageFormat::from_content_type(&body.content_type) else {
+        return ApiResponse::error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Only JPEG, PNG and WebP images are allowed.",
+        );
+    };
+
+    let file_name = format!("{}.{}", Uuid::new_v4(), format.extension());
+    let token = match generate_image_upload_jwt(
+        auth.user_id,
+        offer_id,
+        file_name.clone(),
+        format.content_type().to_string(),
+    ) {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!("Failed to generate image upload token: {}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create upload URL.",
+            );
+        }
+    };
+
+    let target = match storage.upload_target(
+        &file_name,
+        format.content_type(),
+        &token,
+        Duration::from_secs(IMAGE_UPLOAD_VALIDITY_MINUTES as u64 * 60),
+    ) {
+        Ok(target) => target,
+        Err(e) => {
+            tracing::error!("Failed to create upload target: {}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create upload URL.",
+            );
+        }
+    };
+
+    ApiResponse::created(json!({
+        "upload_url": target.url,
+        "upload_token": token,
+        "method": target.method,
+        "headers": target.headers,
+        "content_type": format.content_type(),
+        "max_bytes": MAX_IMAGE_BYTES,
+        "expires_in_seconds": IMAGE_UPLOAD_VALIDITY_MINUTES * 60
+    }))
+}
+
+/// Handles direct image uploads to a signed upload URL.
+///
+/// The route is authorized by the upload token in the URL instead of a session, and streams the
+/// request body to the staging directory. Uploading to the same URL again replaces the staged
+/// file. It only accepts uploads if they are staged locally, as S3 upload URLs point to the bucket.
+///
+/// # Arguments
+///
+/// * `storage` - The storage uploads are staged in.
+/// * `path` - Path containing the file name.
+/// * `query` - Query parameters containing the upload token.
+/// * `payload` - The raw image data.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating whether the image was staged.
+#[put("/uploads/offers/{file_name}")]
+pub(super) async fn receive_direct_upload(
+    storage: web::Data<dyn UploadStorage>,
+    path: web::Path<String>,
+    query: web::Query<DirectUploadQuery>,
+    mut payload: web::Payload,
+) -> ApiResponse<()> {
+    if !storage.receives_uploads() {
+        return ApiResponse::error(StatusCode::NOT_FOUND, "Not found.");
+    }
+    let claims = match validate_image_upload_jwt(&query.token) {
+        Ok(claims) if claims.file_name == *path => claims,
+        Ok(_) | Err(_) => {
+            return ApiResponse::error(StatusCode::FORBIDDEN, "Invalid or expired upload URL.");
+        }
+    };
+
+    let dir = staging_dir();
+    if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+        tracing::error!("Failed to create image staging directory: {}", e);
+        return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store image.");
+    }
+    let staged = dir.join(&claims.file_name);
+    let mut file = match tokio::fs::File::create(&staged).await {
+        Ok(file) => file,
+        Err(e) => {
+            tracing::error!("Failed to create staged upload: {}", e);
+            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store image.");
+        }
+    };
+
+    let mut size = 0;
+    let error = loop {
+        let chunk = match payload.next().await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(e)) => {
+                tracing::warn!("Failed to read direct upload: {}", e);
+                break ApiError::new(StatusCode::BAD_REQUEST, "Invalid upload.");
+            }
+            None => match file.flush().await {
+                Ok(()) => {
+                    return ApiResponse::message("Image uploaded. Finalize it to attach it.");
+                }
+                Err(e) => {
+                    tracing::error!("Failed to store staged upload: {}", e);
+                    break ApiError::new(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to store image.",
+                    );
+                }
+            },
+        };
+        size += chunk.len();
+        if size > MAX_IMAGE_BYTES {
+            break ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "Images must be at most {} MiB.",
+                    MAX_IMAGE_BYTES / (1024 * 1024)
+                ),
+            );
+        }
+        if let Err(e) = file.write_all(&chunk).await {
+            tracing::error!("Failed to store staged upload: {}", e);
+            break ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store image.");
+        }
+    };
+
+    drop(file);
+    if let Err(e) = tokio::fs::remove_file(&staged).await {
+        tracing::warn!("Failed to delete staged upload {}: {}", claims.file_name, e);
+    }
+    error.into()
+}
+
+/// Handles requests to attach a directly uploaded image to its offer.
+///
+/// Only the seller who requested the upload URL can finalize it, and each upload can be finalized
+/// once. The staged file is taken from the upload storage and validated like a multipart upload,
+/// then stored in the upload directory and its thumbnail is generated in the background.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `storage` - The storage the image was uploaded to.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the upload token and an optional alternative text.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated offer or an error.
+#[post("offers/{offer_id}/images/finalize")]
+pub(super) async fn finalize_image_upload(
+    db: web::Data<Database>,
+    storage: web::Data<dyn UploadStorage>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<FinalizeUploadRequest>,
+) -> ApiResponse<Offer> {
+    let offer_id = path.into_inner();
+    let alt_text = match normalize_alt_text(&body.alt_text) {
+        Ok(alt_text) => alt_text,
+        Err(error) => return error.into(),
+    };
+    let claims = match validate_image_upload_jwt(&body.upload_token) {
+        Ok(claims) if claims.sub == auth.user_id && claims.offer_id == offer_id => claims,
+        Ok(_) | Err(_) => {
+            return ApiResponse::error(StatusCode::FORBIDDEN, "Invalid or expired upload token.");
+        }
+    };
+    if let Err(error) = require_own_offer(&db, &auth.user_id, &offer_id).await {
+        return error.into();
+    }
+
+    let Some(format) = ImageFormat::from_content_type(&claims.content_type) else {
+        return ApiResponse::error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Only JPEG, PNG and WebP images are allowed.",
+        );
+    };
+
+    // Taking the staged file claims the upload, so it can't be finalized twice
+    let data = match storage.take(&claims.file_name, MAX_IMAGE_BYTES).await {
+        Ok(Some(data)) => data,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Upload not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to take staged upload: {}", e);
+            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store image.");
+        }
+    };
+    if data.len() > MAX_IMAGE_BYTES {
+        return ApiResponse::error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Images must be at most {} MiB.",
+                MAX_IMAGE_BYTES / (1024 * 1024)
+            ),
+        );
+    }
+    if !format.matches(&data) {
+        return ApiResponse::error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("The file is not a valid {} image.", format.content_type()),
+        );
+    }
+    let (image, perceptual_hash) = match store_image(&db, format, &data, alt_text).await {
+        Ok(stored) => stored,
+        Err(error) => return error.into(),
+    };
+
+    match db
+        .add_offer_images(offer_id.clone(), vec![image.clone()])
+        .await
+    {
+        Ok(Some(offer)) => {
+            if let Some(perceptual_hash) = perceptual_hash {
+                report_reused_images(&db, &offer, &[perceptual_hash]).await;
+            }
+            if image.thumbnail_url.is_some() {
+                return ApiResponse::created(offer).with_message("Image uploaded successfully.");
+            }
+            enqueue_thumbnail(db, record_key(&offer.id), image.url, data);
+            ApiResponse::created(offer).with_message(
+                "Image uploaded successfully. Its thumbnail is generated in the background.",
+            )
+        }
+        Ok(None) => {
+            remove_image_files(&db, &[image]).await;
+            ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!("An offer can have at most {} images.", MAX_OFFER_IMAGES),
+            )
+        }
+        Err(e) => {
+            tracing::error!("Failed to add image to offer: {:?}", e);
+            remove_image_files(&db, &[image]).await;
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store image.")
+        }
+    }
+}
+
+/// Handles requests to set or remove the alternative text of an offer image.
+///
+/// Only the seller can describe the images of an offer. An empty text removes the description.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the path of the image and its alternative text.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated offer or an error.
+#[put("offers/{offer_id}/images/alt-text")]
+pub(super) async fn update_image_alt_text(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<AltTextRequest>,
+) -> ApiResponse<Offer> {
+    let alt_text = match normalize_alt_text(&body.alt_text) {
+        Ok(alt_text) => alt_text,
+        Err(error) => return error.into(),
+    };
+    let offer_id = path.into_inner();
+    if let Err(error) = require_own_offer(&db, &auth.user_id, &offer_id).await {
+        return error.into();
+    }
+
+    let body = body.into_inner();
+    match db.set_image_alt_text(offer_id, body.url, alt_text).await {
+        Ok(Some(offer)) => ApiResponse::ok(offer).with_message("Image description updated."),
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Image not found."),
+        Err(e) => {
+            tracing::error!("Failed to update image alternative text: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update image description.",
+            )
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! src/server/offer_questions.rs
+//!
+//! This module defines the routes for public questions about offers: users ask, the seller
+//! answers, and anyone can read them on the offer page. Users can report questions to the
+//! moderators, who can hide them.
+
+use super::moderation::require_moderator;
+use super::notify;
+use crate::database::offer_questions::{MAX_UNANSWERED_QUESTIONS, OfferQuestion};
+use crate::database::{Database, Offer, record_key};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersWrite, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, post, put, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// The reason of reports filed against questions, as stored on the report.
+const QUESTION_REPORT_REASON: &str = "offer_question";
+
+/// Struct representing the ask question request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct AskQuestionRequest {
+    #[validate(length(
+        min = 3,
+        max = 1000,
+        message = "Question must be 3 to 1000 characters long"
+    ))]
+    question: String,
+}
+
+/// Struct representing the answer question request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct AnswerQuestionRequest {
+    #[validate(length(
+        min = 1,
+        max = 2000,
+        message = "Answer must be 1 to 2000 characters long"
+    ))]
+    answer: String,
+}
+
+/// Struct representing the report question request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct ReportQuestionRequest {
+    #[serde(default)]
+    #[validate(length(max = 2000, message = "Details must be at most 2000 characters long"))]
+    details: String,
+}
+
+/// Struct representing the hide question request body
+#[derive(Debug, Deserialize, Serialize)]
+struct HideQuestionRequest {
+    hidden: bool,
+}
+
+/// Retrieves a publicly listed offer, or the response to return if there is none.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer_id` - The ID of the offer.
+async fn listed_offer<T: Serialize>(
+    db: &Database,
+    offer_id: &str,
+) -> Result<Offer, ApiResponse<T>> {
+    match db.get_offer_by_id(offer_id.to_string()).await {
+        Ok(Some(offer)) if offer.is_listed() => Ok(offer),
+        Ok(_) => Err(ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            "Offer not found.",
+        )),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for questions: {:?}", e);
+            Err(ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            ))
+        }
+    }
+}
+
+/// Retrieves a question about an offer, or the response to return if there is none.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer_id` - The ID of the offer the question must be about.
+/// * `question_id` - The ID of the question.
+async fn offer_question<T: Serialize>(
+    db: &Database,
+    offer_id: &str,
+    question_id: &str,
+) -> Result<OfferQuestion, ApiResponse<T>> {
+    match db.get_question(question_id).await {
+        Ok(Some(question)) if question.offer_id == offer_id && !question.hidden => Ok(question),
+        Ok(_) => Err(ApiResponse::error(
+            StatusCode::NOT_FOUND,
+            "Question not found.",
+        )),
+        Err(e) => {
+            tracing::error!("Failed to retrieve question: {:?}", e);
+            Err(ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve question.",
+            ))
+        }
+    }
+}
+
+/// Handles requests for the public questions about an offer and their answers.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the questions, oldest first, or an error.
+#[get("offers/{offer_id}/questions")]
+pub(super) async fn get_offer_questions(
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> ApiResponse<Vec<OfferQuestion>> {
+    let offer_id = path.into_inner();
+    if let Err(response) = listed_offer(&db, &offer_id).await {
+        return response;
+    }
+
+    match db.get_offer_questions(&offer_id).await {
+        Ok(questions) => ApiResponse::ok(questions),
+        Err(e) => {
+            tracing::error!("Failed to retrieve questions: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve questions.",
+            )
+        }
+    }
+}
+
+/// Handles requests to ask a public question about an offer.
+///
+/// Sellers cannot ask about their own offers, and each user can have at most
+/// `MAX_UNANSWERED_QUESTIONS` unanswered questions per offer. The seller is notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the question.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created question or an error.
+#[post("offers/{offer_id}/questions")]
+pub(super) async fn ask_question(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<AskQuestionRequest>,
+) -> ApiResponse<OfferQuestion> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Ask question request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let question = body.question.trim();
+    if question.is_empty() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "Question must not be blank.");
+    }
+
+    let offer_id = path.into_inner();
+    let offer = match listed_offer(&db, &offer_id).await {
+        Ok(offer) => offer,
+        Err(response) => return response,
+    };
+    if offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "You cannot ask a question about your own offer.",
+        );
+    }
+
+    match db.create_question(&offer_id, &auth.user_id, question).await {
+        Ok(Some(created)) => {
+            let body = format!(
+                "A buyer asked about your offer \"{}\": {}",
+                offer.game_title, created.question
+            );
+            notify(
+                &db,
+                &record_key(&offer.seller_id),
+                "offer_question",
+                "New question about your offer",
+                body,
+            )
+            .await;
+            ApiResponse::created(created)
+                .with_message("Question posted. The seller will be notified.")
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "You already have {} unanswered questions about this offer. Wait for the seller to answer.",
+                MAX_UNANSWERED_QUESTIONS
+            ),
+        ),
+        Err(e) => {
+            tracing::error!("Failed to create question: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to post question.",
+            )
+        }
+    }
+}
+
+/// Handles requests by the seller to answer a question about their offer.
+///
+/// Answering again replaces the answer. The user who asked is notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID and the question ID.
+/// * `body` - JSON payload containing the answer.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the answered question or an error.
+#[put("offers/{offer_id}/questions/{question_id}/answer")]
+pub(crate) async fn answer_question(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<(String, String)>,
+    body: web::Json<AnswerQuestionRequest>,
+) -> ApiResponse<OfferQuestion> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Answer question request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let answer = body.answer.trim();
+    if answer.is_empty() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "Answer must not be blank.");
+    }
+
+    let (offer_id, question_id) = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to answer a question: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    if !offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "Only the seller can answer questions about this offer.",
+        );
+    }
+    if let Err(response) = offer_question(&db, &offer_id, &question_id).await {
+        return response;
+    }
+
+    match db.answer_question(&question_id, answer).await {
+        Ok(Some(answered)) => {
+            let body = format!(
+                "The seller answered your question about \"{}\": {}",
+                offer.game_title,
+                answered.answer.as_deref().unwrap_or_default()
+            );
+            notify(
+                &db,
+                &answered.asker_id,
+                "offer_question_answered",
+                "Your question was answered",
+                body,
+            )
+            .await;
+            ApiResponse::ok(answered).with_message("Answer posted.")
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Question not found."),
+        Err(e) => {
+            tracing::error!("Failed to answer question: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to post answer.")
+        }
+    }
+}
+
+/// Handles requests to report a question or its answer to the moderators.
+///
+/// The report is filed against the offer with the reason `offer_question` and names the question
+/// in its details, so it shows up in the moderators' review queue.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the offer ID and the question ID.
+/// * `body` - JSON payload containing optional details.
+///
+/// # Returns
+///
+/// An `ApiResponse` with a confirmation message or an error.
+#[post("offers/{offer_id}/questions/{question_id}/report")]
+pub(super) async fn report_question(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<(String, String)>,
+    body: web::Json<ReportQuestionRequest>,
+) -> ApiResponse<()> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Report question request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let (offer_id, question_id) = path.into_inner();
+    let question = match offer_question(&db, &offer_id, &question_id).await {
+        Ok(question) => question,
+        Err(response) => return response,
+    };
+    let details = format!(
+        "Question {}: \"{}\"{}",
+        question_id,
+        question.question,
+        match body.details.trim() {
+            "" => String::new(),
+            details => format!(" - {}", details),
+        }
+    );
+
+    match db
+        .create_report(
+            offer_id,
+            auth.user_id,
+            QUESTION_REPORT_REASON.to_string(),
+            details,
+        )
+        .await
+    {
+        Ok(_) => ApiResponse::message("Thank you, a moderator will review the question."),
+        Err(e) => {
+            tracing::error!("Failed to report question: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to report question.",
+            )
+        }
+    }
+}
+
+/// Handles requests by moderators to hide a question and its answer from the offer page, or to
+/// show it again.
+///
+/// This route is restricted to moderators and admins. The change is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the question ID.
+/// * `body` - JSON payload containing whether the question is hidden.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated question or an error.
+#[put("moderation/questions/{question_id}/hidden")]
+pub(super) async fn set_question_hidden(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<HideQuestionRequest>,
+) -> ApiResponse<OfferQuestion> {
+    let moderator_id = match require_moderator(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let question_id = path.into_inner();
+    match db.set_question_hidden(&question_id, body.hidden).await {
+        Ok(Some(question)) => {
+            let (action, message) = if body.hidden {
+                ("hide_question", "Question hidden.")
+            } else {
+                ("show_question", "Question shown again.")
+            };
+            if let Err(e) = db
+                .record_audit_entry(
+                    moderator_id,
+                    action,
+                    vec![question_id, question.offer_id.clone()],
+                    format!("Question: \"{}\"", question.question),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(question).with_message(message)
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Question not found."),
+        Err(e) => {
+            tracing::error!("Failed to change question visibility: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to update question.",
+            )
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! src/server/offer_status.rs
+//!
+//! This module defines the routes sellers use to move their offers through the lifecycle:
+//! publishing drafts, reserving, renewing, marking offers sold and withdrawing them. It also runs
+//! the background job that expires offers at the end of their listing period.
+
+use crate::config::ConfigHandle;
+use crate::database::offer_status::{OFFER_LIFETIME_DAYS, OFFER_RENEWAL_DAYS, OfferStatus};
+use crate::database::{Database, Offer, record_key};
+use crate::errors::custom_errors::CustomError;
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{post, put, web};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// How often the expiration job looks for expired offers.
+const EXPIRATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long the expiration job waits before retrying a failed run.
+const EXPIRATION_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How often a failed run of the expiration job is retried before waiting for the next interval.
+const EXPIRATION_MAX_RETRIES: u32 = 3;
+
+/// The name of the expiration job in the task metrics.
+const EXPIRATION_TASK: &str = "offer_expiration";
+
+/// Starts the background job that expires offers at the end of their listing period.
+///
+/// Sellers are notified about their expired offers unless `offer_expiration_notifications` is
+/// disabled in the runtime configuration. A failed run is retried a few times before the job
+/// waits for the next interval.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `config` - The runtime configuration.
+pub(super) fn spawn_expiration_job(db: Database, config: ConfigHandle) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRATION_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let Some(expired) = expire_with_retries(&db).await else {
+                continue;
+            };
+            if !config.current().offer_expiration_notifications {
+                continue;
+            }
+            for offer in expired {
+                let body = format!(
+                    "Your offer \"{}\" was listed for {} days and is no longer shown to buyers. You can list it again from your offers.",
+                    offer.game_title, OFFER_LIFETIME_DAYS
+                );
+                if let Err(e) = db
+                    .create_notification(
+                        record_key(&offer.seller_id),
+                        "offer_expired",
+                        "One of your offers expired".to_string(),
+                        body,
+                    )
+                    .await
+                {
+                    tracing::error!("Failed to notify seller about expired offer: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Runs the expiration of stale offers, retrying failed runs, and records every run in the task
+/// metrics.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+///
+/// # Returns
+///
+/// The offers that expired, or `None` if every attempt failed.
+async fn expire_with_retries(db: &Database) -> Option<Vec<Offer>> {
+    let metrics = TaskMetrics::global();
+    let mut retries = 0;
+    loop {
+        let started = Instant::now();
+        match db.expire_stale_offers().await {
+            Ok(expired) => {
+                metrics.record_run(EXPIRATION_TASK, TaskOutcome::Success, started.elapsed());
+                return Some(expired);
+            }
+            Err(e) => {
+                metrics.record_run(EXPIRATION_TASK, TaskOutcome::Failure, started.elapsed());
+                if retries == EXPIRATION_MAX_RETRIES {
+                    tracing::error!(
+                        "Failed to expire offers, giving up until the next run: {:?}",
+                        e
+                    );
+                    return None;
+                }
+                tracing::warn!("Failed to expire offers, retrying: {:?}", e);
+                retries += 1;
+                metrics.record_retry(EXPIRATION_TASK);
+                tokio::time::sleep(EXPIRATION_RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Struct representing the request body of a status change.
+#[derive(Debug, Deserialize)]
+pub(super) struct ChangeStatusRequest {
+    /// The new status. Sellers can't expire offers themselves.
+    status: OfferStatus,
+}
+
+/// Changes the status of one of the authenticated seller's offers.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the authenticated user.
+/// * `offer_id` - The ID of the offer.
+/// * `next` - The new status.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated offer or an error.
+async fn change_status(
+    db: &Database,
+    user_id: &str,
+    offer_id: String,
+    next: OfferStatus,
+) -> ApiResponse<Offer> {
+    let offer = match db.get_offer_by_id(offer_id).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for status change: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+
+    if !offer.is_seller(user_id) {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to change the status of this offer.",
+        );
+    }
+
+    match db.transition_offer_status(&offer, next).await {
+        Ok(Some(updated)) => {
+            if offer.status == OfferStatus::Draft && next == OfferStatus::Active {
+                super::saved_searches::alert_new_offer(db.clone(), updated.clone());
+            }
+            ApiResponse::ok(updated).with_message(format!("Offer is now {}.", next.as_str()))
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "The offer's status changed in the meantime. Please try again.",
+        ),
+        Err(e @ CustomError::InvalidStatusTransition(..)) => {
+            ApiResponse::error(StatusCode::CONFLICT, e.to_string())
+        }
+        Err(e) => {
+            tracing::error!("Failed to change offer status: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to change offer status.",
+            )
+        }
+    }
+}
+
+/// Handles requests to change the status of an offer.
+///
+/// Only the seller can change the status, and only along the offer lifecycle, e.g. to publish
+/// a draft (`active`), reserve an offer or list an expired offer again for another
+/// `OFFER_LIFETIME_DAYS` days. Publishing a draft alerts the users whose saved searches match it.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the new status.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated offer or an error.
+#[put("offers/{offer_id}/status")]
+pub(super) async fn update_offer_status(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<ChangeStatusRequest>,
+) -> ApiResponse<Offer> {
+    if body.status == OfferStatus::Expired {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "Offers expire automatically.");
+    }
+    change_status(&db, &auth.user_id, path.into_inner(), body.status).await
+}
+
+/// Handles requests to mark an offer as sold.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated offer or an error.
+#[post("offers/{offer_id}/sold")]
+pub(super) async fn mark_offer_sold(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    change_status(&db, &auth.user_id, path.into_inner(), OfferStatus::Sold).await
+}
+
+/// Handles requests to withdraw an offer.
+///
+/// Withdrawn offers are kept for the seller's records but no longer shown to anyone else.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated offer or an error.
+#[post("offers/{offer_id}/withdraw")]
+pub(super) async fn withdraw_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    change_status(&db, &auth.user_id, path.into_inner(), OfferStatus::Removed).await
+}
+
+/// Handles requests to renew an offer.
+///
+/// Renewing moves an active offer back to the top of the newest listings, as if it was just
+/// created, and lists it for another `OFFER_LIFETIME_DAYS` days. Each offer can be renewed once
+/// every `OFFER_RENEWAL_DAYS` days. Expired offers are listed again through the status route.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the renewed offer or an error.
+#[post("offers/{offer_id}/renew")]
+pub(super) async fn renew_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    let offer = match db.get_offer_by_id(path.into_inner()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to renew: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    if !offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to renew this offer.",
+        );
+    }
+    if offer.status != OfferStatus::Active {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Only active offers can be renewed. List expired offers again instead.",
+        );
+    }
+    let next_renewal = offer
+        .renewed_at
+        .as_deref()
+        .and_then(|renewed_at| DateTime::parse_from_rfc3339(renewed_at).ok())
+        .map(|renewed_at| renewed_at.with_timezone(&Utc) + ChronoDuration::days(OFFER_RENEWAL_DAYS))
+        .filter(|next_renewal| *next_renewal > Utc::now());
+    if let Some(next_renewal) = next_renewal {
+        return ApiResponse::error(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "Offers can be renewed once every {} days. This offer can be renewed again after {}.",
+                OFFER_RENEWAL_DAYS,
+                next_renewal.format("%Y-%m-%d %H:%M UTC")
+            ),
+        );
+    }
+
+    match db.renew_offer(&offer).await {
+        Ok(Some(renewed)) => ApiResponse::ok(renewed).with_message(format!(
+            "Offer renewed and listed for another {} days.",
+            OFFER_LIFETIME_DAYS
+        )),
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "The offer changed in the meantime. Please try again.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to renew offer: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to renew offer.")
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! src/server/offer_views.rs
+//!
+//! This module counts the views of offers and defines the route sellers use to see them.
+
+use crate::database::offer_views::OfferViews;
+use crate::database::{Database, Offer};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersRead, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpMessage, HttpRequest, get, web};
+
+/// Counts a view of an offer by the user making the request, or by their IP address if they
+/// are not logged in. Views by the seller are not counted.
+///
+/// Failures are logged and otherwise ignored, so counting never breaks viewing an offer.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `req` - HTTP request to access extensions and the peer address.
+/// * `offer` - The viewed offer.
+pub(super) async fn record_view(db: &Database, req: &HttpRequest, offer: &Offer) {
+    let user_id = req.extensions().get::<String>().cloned();
+    let viewer = match (user_id, req.peer_addr()) {
+        (Some(user_id), _) if offer.is_seller(&user_id) => return,
+        (Some(user_id), _) => format!("user:{}", user_id),
+        (None, Some(addr)) => format!("ip:{}", addr.ip()),
+        (None, None) => return,
+    };
+    if let Err(e) = db.record_offer_view(offer, &viewer).await {
+        tracing::warn!("Failed to record view of offer {}: {:?}", offer.id, e);
+    }
+}
+
+/// Handles requests for the view statistics of one of the authenticated user's offers.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the total and daily views of the offer or an error.
+#[get("offers/{offer_id}/views")]
+pub(super) async fn get_offer_views(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    path: web::Path<String>,
+) -> ApiResponse<OfferViews> {
+    let offer = match db.get_offer_by_id(path.into_inner()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer for view statistics: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+
+    if !offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to view the statistics of this offer.",
+        );
+    }
+
+    match db.get_offer_views(&offer).await {
+        Ok(views) => ApiResponse::ok(views),
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer views: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer views.",
+            )
+        }
+    }
+}
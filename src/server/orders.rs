@@ -0,0 +1,909 @@
+//! src/server/orders.rs
+//!
+//! This module defines the routes of the purchase flow: buyers buy offers, both parties follow
+//! their orders, and the order moves through payment and shipping to completion. Buying an offer
+//! reserves it, completing the order marks it sold, and cancelling the order lists it again. A
+//! background job releases escrowed payments the buyer didn't confirm in time.
+
+use super::notify;
+use super::promo_codes::{checkout_promo, redeem_promo};
+use crate::database::addresses::ShippingAddress;
+use crate::database::bids::BidStatus;
+use crate::database::offer_status::OfferStatus;
+use crate::database::orders::{ESCROW_RELEASE_DAYS, EscrowState, Order, OrderFilter, OrderState};
+use crate::database::promo_codes::PromoCode;
+use crate::database::taxes::{SellerType, normalize_country};
+use crate::database::{Database, Offer, record_key};
+use crate::email::EmailTemplate;
+use crate::errors::custom_errors::CustomError;
+use crate::invoice::{Invoice, InvoiceParty};
+use crate::jwt::{DOWNLOAD_VALIDITY_MINUTES, generate_download_jwt, validate_download_jwt};
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::{ApiError, ApiResponse};
+use crate::scopes::{OffersRead, OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE};
+use actix_web::{HttpRequest, HttpResponse, get, post, put, web};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+/// How often the escrow release job runs.
+const ESCROW_RELEASE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The name of the escrow release job in the task metrics.
+const ESCROW_RELEASE_TASK: &str = "escrow_release";
+
+/// Struct representing the request body of an order state change.
+#[derive(Debug, Deserialize)]
+pub(super) struct ChangeOrderStateRequest {
+    state: OrderState,
+}
+
+/// Query parameters of the routes placing orders.
+#[derive(Debug, Deserialize)]
+pub(super) struct CheckoutQuery {
+    /// The two-letter code of the country the buyer is taxed in. Defaults to the country of the
+    /// buyer's most recently added address.
+    country: Option<String>,
+    /// The promo code the buyer wants to redeem.
+    pub(super) promo_code: Option<String>,
+}
+
+/// Determines the country the VAT of a buyer's orders is computed for.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `buyer_id` - The ID of the buyer.
+/// * `query` - The query parameters of the request.
+///
+/// # Returns
+///
+/// A `Result` containing the normalized country code, `None` if the buyer gave none and has no
+/// address, or the `ApiError` to return.
+pub(super) async fn tax_country(
+    db: &Database,
+    buyer_id: &str,
+    query: &CheckoutQuery,
+) -> Result<Option<String>, ApiError> {
+    if let Some(country) = query.country.as_deref() {
+        return normalize_country(country).map(Some).ok_or_else(|| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Country must be a two-letter country code.",
+            )
+        });
+    }
+    match db.get_addresses(buyer_id).await {
+        Ok(addresses) => Ok(addresses
+            .last()
+            .and_then(|address| normalize_country(&address.address.country))),
+        Err(e) => {
+            tracing::error!("Failed to retrieve addresses of buyer: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to determine your country.",
+            ))
+        }
+    }
+}
+
+/// Determines the price a buyer pays for an offer, taking one of its copies if it was still
+/// available.
+///
+/// Active offers are bought at their asking price, one copy at a time. Reserved offers can only be
+/// bought by the buyer they are reserved for: at the asking price if the buyer reserved the offer,
+/// otherwise at the price agreed in an accepted bid or a won auction.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer` - The offer to buy.
+/// * `buyer_id` - The ID of the buyer.
+///
+/// # Returns
+///
+/// A `Result` containing the price and the offer as updated by taking a copy (`None` if the offer
+/// was reserved before), or the `ApiError` to return.
+async fn reserve_for_purchase(
+    db: &Database,
+    offer: &Offer,
+    buyer_id: &str,
+) -> Result<(f64, Option<Offer>), ApiError> {
+    let offer_id = record_key(&offer.id);
+    let internal = |e: CustomError| {
+        tracing::error!("Failed to prepare purchase of offer {}: {:?}", offer_id, e);
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to buy offer.")
+    };
+    let unavailable = || ApiError::new(StatusCode::CONFLICT, "The offer is no longer available.");
+    let auction = db.get_auction(offer_id.clone()).await.map_err(internal)?;
+
+    match offer.status {
+        OfferStatus::Active => {
+            if auction.is_some() {
+                return Err(ApiError::new(
+                    StatusCode::CONFLICT,
+                    "This offer is sold by auction. Bid in the auction instead.",
+                ));
+            }
+            match db.take_offer_copy(offer).await {
+                Ok(Some(taken)) => Ok((offer.price, Some(taken))),
+                Ok(None) => Err(unavailable()),
+                Err(e) => Err(internal(e)),
+            }
+        }
+        OfferStatus::Reserved => {
+            if let Some(reserved_by) = &offer.reserved_by {
+                return if reserved_by == buyer_id {
+                    Ok((offer.price, None))
+                } else {
+                    Err(unavailable())
+                };
+            }
+            if let Some(auction) = auction {
+                return match (auction.winner_id.as_deref(), auction.highest_bid) {
+                    (Some(winner_id), Some(highest_bid)) if winner_id == buyer_id => {
+                        Ok((highest_bid, None))
+                    }
+                    _ => Err(unavailable()),
+                };
+            }
+            let bids = db
+                .get_bids_for_offer(offer_id.clone(), Some(buyer_id.to_string()))
+                .await
+                .map_err(internal)?;
+            bids.iter()
+                .find(|bid| bid.status == BidStatus::Accepted)
+                .map(|bid| (bid.agreed_amount(), None))
+                .ok_or_else(unavailable)
+        }
+        _ => Err(unavailable()),
+    }
+}
+
+/// Places an order for an offer on behalf of a buyer, notifies the seller and emails the buyer an
+/// order confirmation.
+///
+/// A copy of the offer is taken through `reserve_for_purchase`, and put back again if the order
+/// can't be placed. The platform fee is computed from the fee schedule in effect now,
+/// and the VAT from the current rate of the buyer's country. A promo code is redeemed before the
+/// order is created and its use released again if the order can't be placed; fee and VAT are
+/// computed from the discounted price.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer` - The offer to buy.
+/// * `buyer_id` - The ID of the buyer.
+/// * `country` - The country the buyer is taxed in, or `None` to store the order without tax.
+/// * `promo` - The promo code the buyer entered, as checked by `checkout_promo`, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the created `Order`, or the `ApiError` to return.
+pub(super) async fn place_order(
+    db: &Database,
+    offer: &Offer,
+    buyer_id: &str,
+    country: Option<&str>,
+    promo: Option<&PromoCode>,
+) -> Result<Order, ApiError> {
+    let (list_price, taken) = reserve_for_purchase(db, offer, buyer_id).await?;
+    let result = match promo {
+        Some(promo) => redeem_promo(db, promo, offer, list_price).await.map(Some),
+        None => Ok(None),
+    };
+    let result = match result {
+        Ok(applied) => {
+            let price = applied.as_ref().map_or(list_price, |applied| {
+                ((list_price - applied.discount) * 100.0).round() / 100.0
+            });
+            let created = async {
+                let fee = db.quote_fee(offer, price).await?;
+                let tax = match country {
+                    Some(country) => Some(db.quote_tax(offer, price, country).await?),
+                    None => None,
+                };
+                db.create_order(
+                    offer,
+                    buyer_id.to_string(),
+                    price,
+                    &fee,
+                    tax.as_ref(),
+                    applied.as_ref(),
+                )
+                .await
+            }
+            .await;
+            if let Some(applied) = applied.filter(|_| !matches!(created, Ok(Some(_))))
+                && let Err(e) = db.release_promo_code(&applied.code).await
+            {
+                tracing::error!(
+                    "Failed to release use of promo code {}: {:?}",
+                    applied.code,
+                    e
+                );
+            }
+            match created {
+                Ok(Some(order)) => Ok(order),
+                Ok(None) => Err(ApiError::new(
+                    StatusCode::CONFLICT,
+                    "This offer has already been ordered.",
+                )),
+                Err(e) => {
+                    tracing::error!("Failed to create order: {:?}", e);
+                    Err(ApiError::new(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to buy offer.",
+                    ))
+                }
+            }
+        }
+        Err(error) => Err(error),
+    };
+    let placed = result.is_ok();
+    // Only put back a copy this call took
+    if taken.is_some()
+        && !placed
+        && let Err(e) = db.return_offer_copy(&record_key(&offer.id)).await
+    {
+        tracing::error!("Failed to put back copy of offer {}: {:?}", offer.id, e);
+    }
+
+    let order = result?;
+    if let Some(promo) = &order.promo
+        && let Err(e) = db.record_promo_redemption(&order, promo).await
+    {
+        tracing::error!("Failed to record promo code redemption: {:?}", e);
+    }
+    let body = format!(
+        "\"{}\" was bought for {:.2} ({:.2} platform fee). Confirm the payment once you have received it.",
+        order.game_title, order.price, order.platform_fee
+    );
+    notify(
+        db,
+        &order.seller_id,
+        "order_placed",
+        "Your offer was bought",
+        body,
+    )
+    .await;
+    let confirmation = EmailTemplate::OrderConfirmation {
+        order_id: record_key(&order.id),
+        game_title: order.game_title.clone(),
+        price: order.price,
+        currency: order.currency,
+        tax: order.tax.as_ref().map(|tax| tax.tax),
+    };
+    if let Err(e) = db.queue_email(&order.buyer_id, confirmation).await {
+        tracing::error!("Failed to send order confirmation: {:?}", e);
+    }
+    Ok(order)
+}
+
+/// Handles requests to buy an offer.
+///
+/// Active offers are bought at their asking price, and each order takes one of their copies; the
+/// offer is sold out once the last copy is ordered. An offer reserved through an accepted bid or
+/// a won auction can be bought by its buyer at the agreed price, once; an offer the buyer reserved
+/// through `reserve_offer` at its asking price. The platform fee is computed from the fee
+/// schedule in effect when the order is placed, and the VAT for the country given as `?country=`
+/// or, by default, the country of the buyer's most recently added address. A promo code given as
+/// `?promo_code=` is taken off the price. The seller is notified and the buyer gets an order
+/// confirmation email.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `query` - Query parameters containing the buyer's country and promo code.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created order or an error.
+#[post("offers/{offer_id}/buy")]
+pub(crate) async fn buy_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    query: web::Query<CheckoutQuery>,
+) -> ApiResponse<Order> {
+    let country = match tax_country(&db, &auth.user_id, &query).await {
+        Ok(country) => country,
+        Err(error) => return error.into(),
+    };
+    let promo = match checkout_promo(&db, query.promo_code.as_deref()).await {
+        Ok(promo) => promo,
+        Err(error) => return error.into(),
+    };
+    let offer = match db.get_offer_by_id(path.into_inner()).await {
+        Ok(Some(offer)) if offer.is_listed() => offer,
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to buy: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    if offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "You cannot buy your own offer.");
+    }
+
+    match place_order(
+        &db,
+        &offer,
+        &auth.user_id,
+        country.as_deref(),
+        promo.as_ref(),
+    )
+    .await
+    {
+        Ok(order) => ApiResponse::created(order).with_message("Order placed."),
+        Err(error) => error.into(),
+    }
+}
+
+/// Handles requests for the orders the authenticated user bought or sold.
+///
+/// `?role=buyer|seller` limits the list to one side, `?state=` to one order state and
+/// `?from=` / `?to=` (`YYYY-MM-DD`, inclusive) to the days the orders were placed on.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `filter` - Query parameters selecting the orders.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the orders, newest first, or an error.
+#[get("orders")]
+pub(super) async fn get_orders(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    filter: web::Query<OrderFilter>,
+) -> ApiResponse<Vec<Order>> {
+    if matches!((filter.from, filter.to), (Some(from), Some(to)) if from > to) {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "The start date must not be after the end date.",
+        );
+    }
+
+    match db.get_orders_for_user(&auth.user_id, &filter).await {
+        Ok(orders) => ApiResponse::ok(orders),
+        Err(e) => {
+            tracing::error!("Failed to retrieve orders: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve orders.",
+            )
+        }
+    }
+}
+
+/// Loads an order the authenticated user bought or sold.
+///
+/// Orders are private, so users who are neither the buyer nor the seller get a 404.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the authenticated user.
+/// * `order_id` - The ID of the order.
+///
+/// # Returns
+///
+/// A `Result` containing the order, or the `ApiError` to return.
+pub(super) async fn require_own_order(
+    db: &Database,
+    user_id: &str,
+    order_id: String,
+) -> Result<Order, ApiError> {
+    match db.get_order(order_id).await {
+        Ok(Some(order)) if order.involves(user_id) => Ok(order),
+        Ok(_) => Err(ApiError::new(StatusCode::NOT_FOUND, "Order not found.")),
+        Err(e) => {
+            tracing::error!("Failed to retrieve order: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve order.",
+            ))
+        }
+    }
+}
+
+/// Handles requests for a single order.
+///
+/// The order includes the offer as it was listed when the order was placed.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `path` - Path containing the order ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the order or an error.
+#[get("orders/{order_id}")]
+pub(super) async fn get_order(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    path: web::Path<String>,
+) -> ApiResponse<Order> {
+    match require_own_order(&db, &auth.user_id, path.into_inner()).await {
+        Ok(order) => ApiResponse::ok(order),
+        Err(error) => error.into(),
+    }
+}
+
+/// Returns the lines of a postal address as printed on an invoice.
+fn address_lines(address: &ShippingAddress) -> Vec<String> {
+    let mut lines = vec![address.street.clone()];
+    lines.extend(address.additional_line.clone());
+    lines.push(format!("{} {}", address.postal_code, address.city));
+    lines.push(address.country.clone());
+    lines
+}
+
+/// Determines the parties named on the invoice of an order.
+///
+/// The buyer is named with the shipping address attached to the order. The seller is named by
+/// username; business sellers, who must state their address, are named with their most recently
+/// added address. Private sellers' addresses stay private.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `order` - The invoiced order.
+///
+/// # Returns
+///
+/// A `Result` containing the seller and the buyer, or a `CustomError` if a lookup fails.
+async fn invoice_parties(
+    db: &Database,
+    order: &Order,
+) -> Result<(InvoiceParty, InvoiceParty), CustomError> {
+    let seller = db.get_user_by_id(order.seller_id.clone()).await?;
+    let seller_address = match &seller {
+        Some(user) if user.seller_type == SellerType::Business => db
+            .get_addresses(&order.seller_id)
+            .await?
+            .pop()
+            .map(|address| address.address),
+        _ => None,
+    };
+    let seller = InvoiceParty {
+        name: seller_address.as_ref().map_or_else(
+            || seller.map_or_else(|| order.seller_id.clone(), |user| user.username),
+            |address| address.recipient.clone(),
+        ),
+        address: seller_address.as_ref().map_or_else(Vec::new, address_lines),
+    };
+
+    let buyer = match db.get_shipping_address(order).await? {
+        Some(address) => InvoiceParty {
+            name: address.recipient.clone(),
+            address: address_lines(&address),
+        },
+        None => InvoiceParty {
+            name: db
+                .get_user_by_id(order.buyer_id.clone())
+                .await?
+                .map_or_else(|| order.buyer_id.clone(), |user| user.username),
+            address: Vec::new(),
+        },
+    };
+    Ok((seller, buyer))
+}
+
+/// The path signed invoice downloads are served from.
+pub(super) const INVOICE_DOWNLOADS_PATH: &str = "/downloads/invoices";
+
+/// Struct representing the query parameters of a signed download
+#[derive(Debug, Deserialize)]
+struct DownloadQuery {
+    token: String,
+}
+
+/// Issues the invoice of a completed order, if needed, and renders it as a PDF download.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `req` - HTTP request, used to render errors.
+/// * `order` - The order, already checked to involve the requesting user.
+///
+/// # Returns
+///
+/// An `HttpResponse` containing the PDF document, or an error.
+async fn invoice_response(db: &Database, req: &HttpRequest, order: Order) -> HttpResponse {
+    if order.state != OrderState::Completed {
+        return ApiError::new(
+            StatusCode::CONFLICT,
+            "Invoices are only available for completed orders.",
+        )
+        .into_http_response(req);
+    }
+
+    let invoice = async {
+        let Some(order) = db.issue_invoice_number(&order).await? else {
+            return Ok(None);
+        };
+        let (seller, buyer) = invoice_parties(db, &order).await?;
+        let sequence = order.invoice_number.unwrap_or_default();
+        Ok::<_, CustomError>(Some(Invoice::for_order(&order, sequence, seller, buyer)))
+    }
+    .await;
+    match invoice {
+        Ok(Some(invoice)) => HttpResponse::Ok()
+            .insert_header((CONTENT_TYPE, "application/pdf"))
+            .insert_header((
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.pdf\"", invoice.number),
+            ))
+            .insert_header((CACHE_CONTROL, "private, no-store"))
+            .body(invoice.to_pdf()),
+        Ok(None) => ApiError::new(
+            StatusCode::CONFLICT,
+            "Invoices are only available for completed orders.",
+        )
+        .into_http_response(req),
+        Err(e) => {
+            tracing::error!("Failed to issue invoice: {:?}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to issue invoice.",
+            )
+            .into_http_response(req)
+        }
+    }
+}
+
+/// Handles requests to download the invoice of a completed order as a PDF document.
+///
+/// Both the buyer and the seller can download the invoice. The order gets the next sequential
+/// invoice number when its invoice is first issued; later downloads show the same number and
+/// date.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `req` - HTTP request, used to render errors.
+/// * `path` - Path containing the order ID.
+///
+/// # Returns
+///
+/// An `HttpResponse` containing the PDF document, or an error.
+#[get("orders/{order_id}/invoice.pdf")]
+pub(super) async fn get_order_invoice(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match require_own_order(&db, &auth.user_id, path.into_inner()).await {
+        Ok(order) => invoice_response(&db, &req, order).await,
+        Err(error) => error.into_http_response(&req),
+    }
+}
+
+/// Handles requests for a signed URL to download the invoice of a completed order.
+///
+/// The URL works without a session for `DOWNLOAD_VALIDITY_MINUTES`, so the PDF can be fetched
+/// directly, e.g. by the browser's download manager.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+/// * `path` - Path containing the order ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the download URL, or an error.
+#[post("orders/{order_id}/invoice/download-url")]
+pub(super) async fn create_invoice_download_url(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+    path: web::Path<String>,
+) -> ApiResponse<serde_json::Value> {
+    let order = match require_own_order(&db, &auth.user_id, path.into_inner()).await {
+        Ok(order) => order,
+        Err(error) => return error.into(),
+    };
+    if order.state != OrderState::Completed {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Invoices are only available for completed orders.",
+        );
+    }
+
+    let download_path = format!("{}/{}", INVOICE_DOWNLOADS_PATH, record_key(&order.id));
+    let token = match generate_download_jwt(auth.user_id, download_path.clone()) {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!("Failed to generate invoice download token: {}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create download URL.",
+            );
+        }
+    };
+
+    ApiResponse::created(json!({
+        "download_url": format!("{}?token={}", download_path, token),
+        "expires_in_seconds": DOWNLOAD_VALIDITY_MINUTES * 60
+    }))
+}
+
+/// Handles invoice downloads from a signed URL.
+///
+/// The route is authorized by the download token in the URL instead of a session. The token is
+/// bound to the order, and the user it was issued to must still be its buyer or seller.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request, used to render errors.
+/// * `path` - Path containing the order ID.
+/// * `query` - Query parameters containing the download token.
+///
+/// # Returns
+///
+/// An `HttpResponse` containing the PDF document, or an error.
+#[get("/downloads/invoices/{order_id}")]
+pub(super) async fn download_invoice(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<DownloadQuery>,
+) -> HttpResponse {
+    let order_id = path.into_inner();
+    let claims = match validate_download_jwt(&query.token) {
+        Ok(claims) if claims.path == format!("{}/{}", INVOICE_DOWNLOADS_PATH, order_id) => claims,
+        Ok(_) | Err(_) => {
+            return ApiError::new(StatusCode::FORBIDDEN, "Invalid or expired download URL.")
+                .into_http_response(&req);
+        }
+    };
+    match require_own_order(&db, &claims.sub, order_id).await {
+        Ok(order) => invoice_response(&db, &req, order).await,
+        Err(error) => error.into_http_response(&req),
+    }
+}
+
+/// Moves the offer of an order along with it, see `Database::update_offer_for_order`.
+///
+/// Failures are logged, as the order was already updated.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `order` - The updated order.
+async fn update_offer_for_order(db: &Database, order: &Order) {
+    if let Err(e) = db.update_offer_for_order(order).await {
+        tracing::error!(
+            "Failed to change status of offer {} after order update: {:?}",
+            order.offer_id,
+            e
+        );
+    }
+}
+
+/// Changes the state of an order on behalf of its buyer or seller and notifies the other party.
+///
+/// The seller confirms the payment (`paid`) and the shipping (`shipped`), the buyer confirms
+/// receipt (`completed`), which releases the escrowed payment. The buyer can cancel pending
+/// orders, the seller can cancel orders until they are shipped.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `user_id` - The ID of the authenticated user.
+/// * `order_id` - The ID of the order.
+/// * `next` - The new state.
+///
+/// # Returns
+///
+/// A `Result` containing the updated order, or the `ApiError` to return.
+async fn change_order_state(
+    db: &Database,
+    user_id: &str,
+    order_id: String,
+    next: OrderState,
+) -> Result<Order, ApiError> {
+    let order = require_own_order(db, user_id, order_id).await?;
+    let is_seller = order.seller_id == user_id;
+    let allowed = match next {
+        OrderState::Paid | OrderState::Shipped => is_seller,
+        OrderState::Completed => !is_seller,
+        OrderState::Cancelled => is_seller || order.state == OrderState::Pending,
+        OrderState::Pending => false,
+    };
+    if !allowed {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("You cannot mark this order as {}.", next.as_str()),
+        ));
+    }
+
+    match db.transition_order_state(&order, next).await {
+        Ok(Some(updated)) => {
+            update_offer_for_order(db, &updated).await;
+            let other = if is_seller {
+                &updated.buyer_id
+            } else {
+                &updated.seller_id
+            };
+            let body = match (next, updated.escrow) {
+                (OrderState::Paid, _) => format!(
+                    "Your payment for \"{}\" is held in escrow until you confirm receipt.",
+                    updated.game_title
+                ),
+                (OrderState::Completed, _) => format!(
+                    "The buyer confirmed receipt of \"{}\". The payment of {:.2} was released to you.",
+                    updated.game_title,
+                    updated.price - updated.platform_fee
+                ),
+                (OrderState::Cancelled, Some(EscrowState::Refunded)) => format!(
+                    "Your order of \"{}\" was cancelled and your payment refunded.",
+                    updated.game_title
+                ),
+                _ => format!(
+                    "Your order of \"{}\" is now {}.",
+                    updated.game_title,
+                    next.as_str()
+                ),
+            };
+            notify(db, other, "order_updated", "An order was updated", body).await;
+            Ok(updated)
+        }
+        Ok(None) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "The order changed in the meantime. Please try again.",
+        )),
+        Err(CustomError::InvalidStatusTransition(current, next)) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("An order cannot change from {} to {}.", current, next),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to change order state: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to change order state.",
+            ))
+        }
+    }
+}
+
+/// Handles requests to change the state of an order.
+///
+/// See `change_order_state` for who may make which change. The other party is notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the order ID.
+/// * `body` - JSON payload containing the new state.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the updated order or an error.
+#[put("orders/{order_id}/state")]
+pub(super) async fn update_order_state(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+    body: web::Json<ChangeOrderStateRequest>,
+) -> ApiResponse<Order> {
+    let next = body.state;
+    match change_order_state(&db, &auth.user_id, path.into_inner(), next).await {
+        Ok(order) => {
+            ApiResponse::ok(order).with_message(format!("Order is now {}.", next.as_str()))
+        }
+        Err(error) => error.into(),
+    }
+}
+
+/// Handles requests of buyers to confirm they received a shipped order.
+///
+/// Completes the order, releases the escrowed payment to the seller and marks the offer sold.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the order ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the completed order or an error.
+#[post("orders/{order_id}/confirm-receipt")]
+pub(super) async fn confirm_receipt(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Order> {
+    match change_order_state(&db, &auth.user_id, path.into_inner(), OrderState::Completed).await {
+        Ok(order) => ApiResponse::ok(order)
+            .with_message("Receipt confirmed. The payment was released to the seller."),
+        Err(error) => error.into(),
+    }
+}
+
+/// Starts the background job that releases escrowed payments whose release date has passed.
+///
+/// The orders are completed as if the buyer had confirmed receipt, their offers are marked sold
+/// and both parties are notified.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+pub(super) fn spawn_escrow_release_job(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ESCROW_RELEASE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            let released = match db.release_due_escrows().await {
+                Ok(released) => {
+                    let metrics = TaskMetrics::global();
+                    metrics.record_run(
+                        ESCROW_RELEASE_TASK,
+                        TaskOutcome::Success,
+                        started.elapsed(),
+                    );
+                    released
+                }
+                Err(e) => {
+                    TaskMetrics::global().record_run(
+                        ESCROW_RELEASE_TASK,
+                        TaskOutcome::Failure,
+                        started.elapsed(),
+                    );
+                    tracing::error!("Failed to release escrowed payments: {:?}", e);
+                    continue;
+                }
+            };
+            for order in released {
+                update_offer_for_order(&db, &order).await;
+                let body = format!(
+                    "The payment of {:.2} for \"{}\" was released to you {} days after shipping.",
+                    order.price - order.platform_fee,
+                    order.game_title,
+                    ESCROW_RELEASE_DAYS
+                );
+                notify(
+                    &db,
+                    &order.seller_id,
+                    "escrow_released",
+                    "Payment released",
+                    body,
+                )
+                .await;
+                let body = format!(
+                    "Your order of \"{}\" was completed automatically {} days after shipping.",
+                    order.game_title, ESCROW_RELEASE_DAYS
+                );
+                notify(
+                    &db,
+                    &order.buyer_id,
+                    "order_updated",
+                    "An order was completed",
+                    body,
+                )
+                .await;
+            }
+        }
+    });
+}
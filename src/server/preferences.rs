@@ -0,0 +1,77 @@
+//! src/server/preferences.rs
+//!
+//! This module defines the routes users use to read and change their preferences.
+
+use crate::database::Database;
+use crate::database::preferences::{UserPreferences, validate_preferences};
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileRead, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{get, put, web};
+
+/// Handles requests to get the authenticated user's preferences.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the preferences or an error.
+#[get("/user/preferences")]
+pub(super) async fn get_preferences(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+) -> ApiResponse<UserPreferences> {
+    match db.get_user_preferences(auth.user_id).await {
+        Ok(Some(preferences)) => ApiResponse::ok(preferences),
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve preferences: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve preferences.",
+            )
+        }
+    }
+}
+
+/// Handles requests to replace the authenticated user's preferences.
+///
+/// Fields missing from the body are reset to their defaults.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the new preferences.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the saved preferences or an error.
+#[put("/user/preferences")]
+pub(super) async fn update_preferences(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<UserPreferences>,
+) -> ApiResponse<UserPreferences> {
+    let mut preferences = body.into_inner();
+    preferences.preferred_platforms.dedup();
+    if let Err(e) = validate_preferences(&preferences) {
+        tracing::warn!("Update preferences request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db.update_user_preferences(auth.user_id, &preferences).await {
+        Ok(true) => ApiResponse::ok(preferences).with_message("Preferences saved successfully."),
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            tracing::error!("Failed to save preferences: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to save preferences.",
+            )
+        }
+    }
+}
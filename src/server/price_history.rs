@@ -0,0 +1,52 @@
+//! src/server/price_history.rs
+//!
+//! This module defines the route exposing the price history of a game title.
+
+use crate::database::Database;
+use crate::database::price_history::{PriceHistory, PriceHistoryQuery};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{get, web};
+use validator::Validate;
+
+/// Handles requests for the price history of a game title.
+///
+/// Returns the lowest, average and median price of the title, overall and per day or week
+/// (`?interval=day|week`), over the last `?days=` days. Asking and sale prices are included
+/// unless `?sold_only=true`; `?platform=` limits the history to one platform.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `path` - Path containing the game title.
+/// * `query` - Query parameters containing the platform, period and kind of prices.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the price history or an error.
+#[get("games/{title}/price-history")]
+pub(super) async fn get_price_history(
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    query: web::Query<PriceHistoryQuery>,
+) -> ApiResponse<PriceHistory> {
+    let title = path.into_inner();
+    if title.trim().is_empty() {
+        return ApiResponse::error(StatusCode::BAD_REQUEST, "Game title is required.");
+    }
+    if let Err(e) = query.validate() {
+        tracing::warn!("Price history query validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db.get_price_history(&title, &query).await {
+        Ok(history) => ApiResponse::ok(history),
+        Err(e) => {
+            tracing::error!("Failed to retrieve price history: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve price history.",
+            )
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! src/server/price_index.rs
+//!
+//! This module publishes the weekly price index as a public JSON feed and runs the job storing a
+//! snapshot of every week once it has passed.
+
+use crate::database::Database;
+use crate::database::price_index::{PriceIndexQuery, PriceIndexSnapshot, last_completed_week};
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{get, web};
+use chrono::Utc;
+use std::time::{Duration, Instant};
+use validator::Validate;
+
+/// How often the snapshot job checks for a passed week without a snapshot.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The name of the snapshot job in the task metrics.
+const SNAPSHOT_TASK: &str = "price_index_snapshot";
+
+/// Starts the background job that stores the price index of every passed week.
+///
+/// Weeks that already have a snapshot are skipped, so a restarted server doesn't compute a week
+/// twice and a missed week is caught up on the next run.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+pub(super) fn spawn_snapshot_job(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            let week = last_completed_week(Utc::now().date_naive());
+            match db.create_price_index_snapshot(week).await {
+                Ok(snapshot) => {
+                    if let Some(snapshot) = snapshot {
+                        tracing::info!(
+                            "Stored the price index of the week starting {} with {} entries",
+                            week,
+                            snapshot.entries.len()
+                        );
+                    }
+                    let metrics = TaskMetrics::global();
+                    metrics.record_run(SNAPSHOT_TASK, TaskOutcome::Success, started.elapsed());
+                }
+                Err(e) => {
+                    TaskMetrics::global().record_run(
+                        SNAPSHOT_TASK,
+                        TaskOutcome::Failure,
+                        started.elapsed(),
+                    );
+                    tracing::error!("Failed to store the price index: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Handles requests for the price index feed.
+///
+/// This route requires no authentication. It returns the most recent weekly snapshots, newest
+/// first (`?weeks=`, default 12). Snapshots never change once stored, so clients may cache the
+/// feed for an hour.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `query` - Query parameters containing the number of weeks.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the snapshots or an error.
+#[get("/feeds/price-index.json")]
+pub(super) async fn get_price_index(
+    db: web::Data<Database>,
+    query: web::Query<PriceIndexQuery>,
+) -> ApiResponse<Vec<PriceIndexSnapshot>> {
+    if let Err(e) = query.validate() {
+        tracing::warn!("Price index query validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db.get_price_index_snapshots(&query).await {
+        Ok(snapshots) => {
+            ApiResponse::ok(snapshots).with_header("cache-control", "public, max-age=3600")
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve price index: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve price index.",
+            )
+        }
+    }
+}
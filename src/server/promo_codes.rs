@@ -0,0 +1,426 @@
+//! src/server/promo_codes.rs
+//!
+//! This module defines the admin routes managing promo codes and reporting their redemptions, and
+//! redeems the codes buyers enter at checkout.
+
+use super::admin::require_admin;
+use crate::database::preferences::Currency;
+use crate::database::promo_codes::{
+    AppliedPromo, DiscountKind, PromoCode, PromoCodeFields, PromoCodeProblem, PromoRedemption,
+    normalize_promo_code,
+};
+use crate::database::{Database, Offer};
+use crate::errors::custom_errors::CustomError;
+use crate::exchange_rates::{ConvertedPrice, ExchangeRateCache};
+use crate::response::{ApiError, ApiResponse};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, post, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the create promo code request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct PromoCodeRequest {
+    code: String,
+    kind: DiscountKind,
+    #[validate(range(
+        exclusive_min = 0.0,
+        max = 1000.0,
+        message = "Value must be greater than 0 and at most 1000"
+    ))]
+    value: f64,
+    #[serde(default)]
+    currency: Currency,
+    #[validate(range(min = 1, message = "The code must allow at least one use"))]
+    max_uses: Option<u64>,
+    expires_at: Option<String>,
+    #[validate(length(
+        min = 3,
+        max = 200,
+        message = "Description must be 3 to 200 characters long"
+    ))]
+    description: String,
+}
+
+/// A promo code with its redemptions.
+#[derive(Debug, Serialize)]
+pub(super) struct PromoCodeReport {
+    /// The promo code.
+    code: PromoCode,
+    /// The total discount granted, per currency.
+    total_discount: Vec<ConvertedPrice>,
+    /// The redemptions, newest first.
+    redemptions: Vec<PromoRedemption>,
+}
+
+/// Validates a promo code request and converts it to the values stored.
+///
+/// # Arguments
+///
+/// * `body` - The request body.
+///
+/// # Returns
+///
+/// A `Result` containing the `PromoCodeFields`, or the `ApiError` to return.
+fn promo_code_fields(body: PromoCodeRequest) -> Result<PromoCodeFields, ApiError> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Promo code request validation failed: {:?}", e);
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, e.to_string()));
+    }
+    let code = normalize_promo_code(&body.code).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Code must be 3 to 32 letters, digits and dashes.",
+        )
+    })?;
+    if body.kind == DiscountKind::Percent && body.value > 100.0 {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "A percentage must be at most 100.",
+        ));
+    }
+    let expires_at = match body.expires_at.as_deref() {
+        Some(expires_at) => {
+            let expires_at = DateTime::parse_from_rfc3339(expires_at)
+                .map(|parsed| parsed.with_timezone(&Utc))
+                .map_err(|_| {
+                    ApiError::new(
+                        StatusCode::BAD_REQUEST,
+                        "expires_at must be an RFC 3339 timestamp.",
+                    )
+                })?;
+            if expires_at <= Utc::now() {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "expires_at must be in the future.",
+                ));
+            }
+            Some(expires_at.to_rfc3339())
+        }
+        None => None,
+    };
+
+    Ok(PromoCodeFields {
+        code,
+        kind: body.kind,
+        value: body.value,
+        currency: body.currency,
+        max_uses: body.max_uses,
+        expires_at,
+        description: body.description.trim().to_string(),
+    })
+}
+
+/// Loads the promo code a buyer entered at checkout and checks that it can be redeemed.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `code` - The code the buyer entered, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the `PromoCode`, `None` if the buyer entered none, or the `ApiError` to
+/// return.
+pub(super) async fn checkout_promo(
+    db: &Database,
+    code: Option<&str>,
+) -> Result<Option<PromoCode>, ApiError> {
+    let Some(code) = code else {
+        return Ok(None);
+    };
+    let not_found = || ApiError::new(StatusCode::NOT_FOUND, "Promo code not found.");
+    let code = normalize_promo_code(code).ok_or_else(not_found)?;
+    let promo = match db.get_promo_code(&code).await {
+        Ok(Some(promo)) => promo,
+        Ok(None) => return Err(not_found()),
+        Err(e) => {
+            tracing::error!("Failed to retrieve promo code: {:?}", e);
+            return Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check promo code.",
+            ));
+        }
+    };
+
+    match promo.check_redeemable(Utc::now()) {
+        Ok(()) => Ok(Some(promo)),
+        Err(PromoCodeProblem::Inactive) => Err(not_found()),
+        Err(PromoCodeProblem::Expired) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "This promo code has expired.",
+        )),
+        Err(PromoCodeProblem::UsedUp) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "This promo code has been used up.",
+        )),
+    }
+}
+
+/// Redeems a promo code for an order of an offer.
+///
+/// The use is counted before the order is created. If the order can't be placed, the caller
+/// releases the use again with `Database::release_promo_code`.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `promo` - The promo code, as checked by `checkout_promo`.
+/// * `offer` - The ordered offer.
+/// * `list_price` - The price before the discount.
+///
+/// # Returns
+///
+/// A `Result` containing the `AppliedPromo`, or the `ApiError` to return.
+pub(super) async fn redeem_promo(
+    db: &Database,
+    promo: &PromoCode,
+    offer: &Offer,
+    list_price: f64,
+) -> Result<AppliedPromo, ApiError> {
+    let rates = if promo.kind == DiscountKind::Fixed && promo.currency != offer.currency {
+        ExchangeRateCache::global().rates().await
+    } else {
+        None
+    };
+    let discount = promo
+        .discount_for(list_price, offer.currency, rates.as_ref())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The promo code can't be converted into the offer's currency right now.",
+            )
+        })?;
+
+    match db.redeem_promo_code(&promo.code).await {
+        Ok(Some(_)) => Ok(AppliedPromo {
+            code: promo.code.clone(),
+            list_price,
+            discount,
+        }),
+        Ok(None) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "This promo code can no longer be redeemed.",
+        )),
+        Err(e) => {
+            tracing::error!("Failed to redeem promo code: {:?}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to redeem promo code.",
+            ))
+        }
+    }
+}
+
+/// Handles requests to list the promo codes.
+///
+/// This route is restricted to admins. Deactivated, expired and used-up codes are included.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the promo codes, newest first, or an error.
+#[get("admin/promo-codes")]
+pub(super) async fn get_promo_codes(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<Vec<PromoCode>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_promo_codes().await {
+        Ok(codes) => ApiResponse::ok(codes),
+        Err(e) => {
+            tracing::error!("Failed to retrieve promo codes: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve promo codes.",
+            )
+        }
+    }
+}
+
+/// Handles requests to create a promo code.
+///
+/// This route is restricted to admins. Codes are case-insensitive and stored in upper case. The
+/// new code is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the code, its discount and its limits.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created promo code or an error.
+#[post("admin/promo-codes")]
+pub(super) async fn create_promo_code(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<PromoCodeRequest>,
+) -> ApiResponse<PromoCode> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    let fields = match promo_code_fields(body.into_inner()) {
+        Ok(fields) => fields,
+        Err(error) => return error.into(),
+    };
+
+    match db.create_promo_code(&fields, admin_id.clone()).await {
+        Ok(Some(promo)) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "create_promo_code",
+                    vec![promo.code.clone()],
+                    format!("Created promo code {}: {}", promo.code, promo.description),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::created(promo).with_message("Promo code created successfully.")
+        }
+        Ok(None) => ApiResponse::error(StatusCode::CONFLICT, "This promo code already exists."),
+        Err(e) => {
+            tracing::error!("Failed to create promo code: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create promo code.",
+            )
+        }
+    }
+}
+
+/// Handles requests to deactivate a promo code.
+///
+/// This route is restricted to admins. The code and its redemptions are kept for reporting. The
+/// deactivation is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the code.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the deactivated promo code or an error.
+#[post("admin/promo-codes/{code}/deactivate")]
+pub(super) async fn deactivate_promo_code(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<PromoCode> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    let Some(code) = normalize_promo_code(&path) else {
+        return ApiResponse::error(StatusCode::NOT_FOUND, "Promo code not found.");
+    };
+
+    match db.deactivate_promo_code(&code).await {
+        Ok(Some(promo)) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "deactivate_promo_code",
+                    vec![code.clone()],
+                    format!("Deactivated promo code {}", code),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(promo).with_message("Promo code deactivated successfully.")
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Promo code not found."),
+        Err(e) => {
+            tracing::error!("Failed to deactivate promo code: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to deactivate promo code.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the redemptions of a promo code.
+///
+/// This route is restricted to admins. The report sums the granted discounts per currency.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the code.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the promo code and its redemptions, or an error.
+#[get("admin/promo-codes/{code}/redemptions")]
+pub(super) async fn get_promo_redemptions(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<PromoCodeReport> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+    let Some(code) = normalize_promo_code(&path) else {
+        return ApiResponse::error(StatusCode::NOT_FOUND, "Promo code not found.");
+    };
+
+    let report = async {
+        let Some(promo) = db.get_promo_code(&code).await? else {
+            return Ok(None);
+        };
+        let redemptions = db.get_promo_redemptions(&code).await?;
+        Ok::<_, CustomError>(Some((promo, redemptions)))
+    }
+    .await;
+    match report {
+        Ok(Some((promo, redemptions))) => {
+            let mut total_discount: Vec<ConvertedPrice> = Vec::new();
+            for redemption in &redemptions {
+                match total_discount
+                    .iter_mut()
+                    .find(|total| total.currency == redemption.currency)
+                {
+                    Some(total) => total.amount += redemption.discount,
+                    None => total_discount.push(ConvertedPrice {
+                        amount: redemption.discount,
+                        currency: redemption.currency,
+                    }),
+                }
+            }
+            for total in &mut total_discount {
+                total.amount = (total.amount * 100.0).round() / 100.0;
+            }
+            ApiResponse::ok(PromoCodeReport {
+                code: promo,
+                total_discount,
+                redemptions,
+            })
+        }
+        Ok(None) => ApiResponse::error(StatusCode::NOT_FOUND, "Promo code not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve promo code redemptions: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve promo code redemptions.",
+            )
+        }
+    }
+}
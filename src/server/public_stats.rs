@@ -0,0 +1,142 @@
+//! src/server/public_stats.rs
+//!
+//! This module defines the public statistics endpoint community sites embed and the marketplace
+//! statistics endpoint. The statistics are computed by a background job and served from memory,
+//! so the endpoints never query the database. Like every route, they are subject to the rate
+//! limit.
+
+use crate::database::Database;
+use crate::database::public_stats::{MarketplaceStats, PublicStats};
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{get, web};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// How often the statistics are recomputed.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// The name of the refresh job in the task metrics.
+const REFRESH_TASK: &str = "public_stats_refresh";
+
+/// The most recently computed public and marketplace statistics.
+#[derive(Debug, Default)]
+pub struct PublicStatsCache {
+    stats: RwLock<Option<(PublicStats, MarketplaceStats)>>,
+}
+
+impl PublicStatsCache {
+    /// Returns the cached statistics, or `None` until they were computed once.
+    fn get(&self) -> Option<(PublicStats, MarketplaceStats)> {
+        self.stats
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Replaces the cached statistics.
+    fn set(&self, stats: PublicStats, marketplace: MarketplaceStats) {
+        *self
+            .stats
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some((stats, marketplace));
+    }
+}
+
+/// Starts the background job that recomputes the public and marketplace statistics.
+///
+/// The first run happens right away. If a run fails, the previous statistics are kept.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `cache` - The cache the statistics are served from.
+pub(super) fn spawn_refresh_job(db: Database, cache: web::Data<PublicStatsCache>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            let computed = match db.compute_public_stats().await {
+                Ok(stats) => db
+                    .compute_marketplace_stats()
+                    .await
+                    .map(|marketplace| (stats, marketplace)),
+                Err(e) => Err(e),
+            };
+            match computed {
+                Ok((stats, marketplace)) => {
+                    cache.set(stats, marketplace);
+                    let metrics = TaskMetrics::global();
+                    metrics.record_run(REFRESH_TASK, TaskOutcome::Success, started.elapsed());
+                }
+                Err(e) => {
+                    TaskMetrics::global().record_run(
+                        REFRESH_TASK,
+                        TaskOutcome::Failure,
+                        started.elapsed(),
+                    );
+                    tracing::error!("Failed to compute public statistics: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Handles requests for the public marketplace statistics.
+///
+/// This route requires no authentication. The statistics may be up to ten minutes old and can be
+/// cached by clients for five minutes.
+///
+/// # Arguments
+///
+/// * `cache` - Web data containing the cached statistics.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the statistics, or `503 Service Unavailable` until they were
+/// computed for the first time.
+#[get("/stats/public")]
+pub(super) async fn get_public_stats(
+    cache: web::Data<PublicStatsCache>,
+) -> ApiResponse<PublicStats> {
+    match cache.get() {
+        Some((stats, _)) => {
+            ApiResponse::ok(stats).with_header("cache-control", "public, max-age=300")
+        }
+        None => ApiResponse::error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Statistics are not available yet. Please try again later.",
+        ),
+    }
+}
+
+/// Handles requests for the marketplace statistics: the number of active offers, and the number
+/// of offers and their average price per platform.
+///
+/// This route requires no authentication. Average prices are in euros. The statistics may be up
+/// to ten minutes old and can be cached by clients for five minutes.
+///
+/// # Arguments
+///
+/// * `cache` - Web data containing the cached statistics.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the statistics, or `503 Service Unavailable` until they were
+/// computed for the first time.
+#[get("/stats")]
+pub(super) async fn get_marketplace_stats(
+    cache: web::Data<PublicStatsCache>,
+) -> ApiResponse<MarketplaceStats> {
+    match cache.get() {
+        Some((_, marketplace)) => {
+            ApiResponse::ok(marketplace).with_header("cache-control", "public, max-age=300")
+        }
+        None => ApiResponse::error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Statistics are not available yet. Please try again later.",
+        ),
+    }
+}
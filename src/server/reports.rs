@@ -0,0 +1,97 @@
+//! src/server/reports.rs
+//!
+//! This module defines the route users use to report offers for abuse, such as scams and
+//! prohibited content. The reports end up in the moderators' review queue.
+
+use crate::database::Database;
+use crate::database::moderation::{Report, ReportReason};
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{post, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the report request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct ReportRequest {
+    reason: ReportReason,
+    #[serde(default)]
+    #[validate(length(max = 2000, message = "Details must be at most 2000 characters long"))]
+    details: String,
+}
+
+/// Handles requests to report an offer for abuse.
+///
+/// Only publicly visible offers of other sellers can be reported, and each user can have one open
+/// report per offer. Reports with the reason `other` must explain the problem in the details.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the offer ID.
+/// * `body` - JSON payload containing the reason and optional details.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created report or an error.
+#[post("offers/{offer_id}/report")]
+pub(super) async fn report_offer(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<String>,
+    body: web::Json<ReportRequest>,
+) -> ApiResponse<Report> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Report request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let body = body.into_inner();
+    let details = body.details.trim().to_string();
+    if body.reason == ReportReason::Other && details.is_empty() {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "Details are required when the reason is 'other'.",
+        );
+    }
+
+    let offer_id = path.into_inner();
+    match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) if offer.is_listed() => {
+            if offer.is_seller(&auth.user_id) {
+                return ApiResponse::error(
+                    StatusCode::BAD_REQUEST,
+                    "You cannot report your own offer.",
+                );
+            }
+        }
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to report: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    }
+
+    match db
+        .create_user_report(offer_id, auth.user_id, body.reason, details)
+        .await
+    {
+        Ok(Some(report)) => ApiResponse::created(report)
+            .with_message("Thank you, a moderator will review the offer."),
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "You have already reported this offer.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to create report: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to report offer.")
+        }
+    }
+}
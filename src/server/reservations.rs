@@ -0,0 +1,271 @@
+//! src/server/reservations.rs
+//!
+//! This module defines the routes buyers use to reserve an offer while they arrange the payment,
+//! and to release their reservation. It also runs the background job that releases reservations
+//! which ran out before the buyer ordered the offer.
+
+use super::notify;
+use crate::config::ConfigHandle;
+use crate::database::offer_status::OfferStatus;
+use crate::database::{Database, Offer, record_key};
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::ApiResponse;
+use crate::scopes::{OffersWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{delete, post, web};
+use std::time::{Duration, Instant};
+
+/// How often the expiry job looks for reservations that ran out.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The name of the expiry job in the task metrics.
+const EXPIRY_TASK: &str = "reservation_expiry";
+
+/// Starts the background job that releases reservations which ran out without an order and
+/// notifies the buyer and the seller.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+pub(super) fn spawn_expiry_job(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            let released = match db.release_expired_reservations().await {
+                Ok(released) => {
+                    let metrics = TaskMetrics::global();
+                    metrics.record_run(EXPIRY_TASK, TaskOutcome::Success, started.elapsed());
+                    released
+                }
+                Err(e) => {
+                    TaskMetrics::global().record_run(
+                        EXPIRY_TASK,
+                        TaskOutcome::Failure,
+                        started.elapsed(),
+                    );
+                    tracing::error!("Failed to release expired reservations: {:?}", e);
+                    continue;
+                }
+            };
+            for offer in released {
+                if let Some(buyer_id) = &offer.reserved_by {
+                    let body = format!(
+                        "Your reservation of \"{}\" ran out before you ordered it, so it is available to other buyers again.",
+                        offer.game_title
+                    );
+                    notify(
+                        &db,
+                        buyer_id,
+                        "reservation_expired",
+                        "Your reservation ran out",
+                        body,
+                    )
+                    .await;
+                }
+                let body = format!(
+                    "The reservation of your offer \"{}\" ran out without an order, so it is listed again.",
+                    offer.game_title
+                );
+                notify(
+                    &db,
+                    &record_key(&offer.seller_id),
+                    "reservation_expired",
+                    "A reservation of your offer ran out",
+                    body,
+                )
+                .await;
+            }
+        }
+    });
+}
+
+/// Handles requests to reserve an offer while arranging the payment.
+///
+/// Only active offers of a single copy by other sellers can be reserved. The reservation lasts
+/// for the configured `reservation_hours`, during which only the buyer can buy the offer, at its
+/// asking price. It is released automatically unless the buyer orders the offer in time. The
+/// seller is notified.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `config` - Web data containing the runtime configuration.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the reserved offer or an error.
+#[post("offers/{offer_id}/reservation")]
+pub(super) async fn reserve_offer(
+    db: web::Data<Database>,
+    config: web::Data<ConfigHandle>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) if offer.is_listed() => offer,
+        Ok(_) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to reserve: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    if offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(
+            StatusCode::BAD_REQUEST,
+            "You cannot reserve your own offer.",
+        );
+    }
+    if offer.status != OfferStatus::Active {
+        return ApiResponse::error(StatusCode::CONFLICT, "The offer is no longer available.");
+    }
+    if offer.quantity > 1 {
+        return ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Offers of several copies can be bought right away and don't need a reservation.",
+        );
+    }
+    match db.get_auction(offer_id).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return ApiResponse::error(
+                StatusCode::CONFLICT,
+                "This offer is sold by auction. Bid in the auction instead.",
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to check for an auction of the offer: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to reserve offer.",
+            );
+        }
+    }
+
+    let hours = config.current().reservation_hours;
+    match db.reserve_offer(&offer, &auth.user_id, hours).await {
+        Ok(Some(reserved)) => {
+            let body = format!(
+                "A buyer reserved your offer \"{}\" for {} hours while arranging the payment.",
+                reserved.game_title, hours
+            );
+            notify(
+                &db,
+                &record_key(&reserved.seller_id),
+                "offer_reserved",
+                "Your offer was reserved",
+                body,
+            )
+            .await;
+            ApiResponse::ok(reserved).with_message(format!(
+                "Offer reserved for you for {} hours. Buy it before the reservation runs out.",
+                hours
+            ))
+        }
+        Ok(None) => ApiResponse::error(StatusCode::CONFLICT, "The offer is no longer available."),
+        Err(e) => {
+            tracing::error!("Failed to reserve offer: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to reserve offer.",
+            )
+        }
+    }
+}
+
+/// Handles requests to release a buyer's reservation of an offer early.
+///
+/// The reserving buyer and the seller can release the reservation, which lists the offer again.
+/// Once the buyer has ordered the offer, the reservation can only end with the order.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:write` scope.
+/// * `path` - Path containing the offer ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the listed offer or an error.
+#[delete("offers/{offer_id}/reservation")]
+pub(super) async fn release_reservation(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<Offer> {
+    let offer_id = path.into_inner();
+    let offer = match db.get_offer_by_id(offer_id.clone()).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return ApiResponse::error(StatusCode::NOT_FOUND, "Offer not found.");
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve offer to release: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve offer.",
+            );
+        }
+    };
+    let is_buyer = offer.reserved_by.as_deref() == Some(auth.user_id.as_str());
+    if !is_buyer && !offer.is_seller(&auth.user_id) {
+        return ApiResponse::error(StatusCode::NOT_FOUND, "Reservation not found.");
+    }
+
+    match db.release_reservation(&offer_id).await {
+        Ok(Some(released)) => {
+            // The other party is told about the release
+            let notified = if is_buyer {
+                Some((
+                    record_key(&offer.seller_id),
+                    format!(
+                        "The buyer released their reservation of your offer \"{}\", so it is listed again.",
+                        offer.game_title
+                    ),
+                ))
+            } else {
+                offer.reserved_by.clone().map(|buyer_id| {
+                    (
+                        buyer_id,
+                        format!(
+                            "The seller released your reservation of \"{}\".",
+                            offer.game_title
+                        ),
+                    )
+                })
+            };
+            if let Some((user_id, body)) = notified {
+                notify(
+                    &db,
+                    &user_id,
+                    "reservation_released",
+                    "A reservation was released",
+                    body,
+                )
+                .await;
+            }
+            ApiResponse::ok(released).with_message("Reservation released.")
+        }
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "The offer has no reservation that can be released. Ordered reservations end with their order.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to release reservation: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to release reservation.",
+            )
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! src/server/saved_searches.rs
+//!
+//! This module defines the routes managing users' saved searches and sends the alerts when a new
+//! offer matches one of them or a user's wishlist.
+
+use crate::database::catalog::MATURE_AGE;
+use crate::database::saved_searches::{MAX_SAVED_SEARCHES, SavedSearch, SavedSearchCriteria};
+use crate::database::{Database, Offer};
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileRead, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web};
+use validator::Validate;
+
+/// Notifies the users whose saved searches or wishlists match a newly listed offer.
+///
+/// Runs in the background, so the seller's request doesn't wait for it. Users matched by both are
+/// notified once, about the wishlist match. Mature-rated offers are only announced to adults.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+/// * `offer` - The listed offer.
+pub(super) fn alert_new_offer(db: Database, offer: Offer) {
+    if offer.hidden {
+        return;
+    }
+    tokio::spawn(async move {
+        let wishlist_users = match db.get_wishlist_matches(&offer).await {
+            Ok(users) => users,
+            Err(e) => {
+                tracing::error!("Failed to match wishlists: {:?}", e);
+                Vec::new()
+            }
+        };
+        let search_users = match db.get_saved_search_matches(&offer).await {
+            Ok(users) => users,
+            Err(e) => {
+                tracing::error!("Failed to match saved searches: {:?}", e);
+                Vec::new()
+            }
+        };
+        let alerts = wishlist_users.iter().map(|user_id| (user_id, true)).chain(
+            search_users
+                .iter()
+                .filter(|user_id| !wishlist_users.contains(user_id))
+                .map(|user_id| (user_id, false)),
+        );
+        let mature = offer.age_rating.is_some_and(|rating| rating.is_mature());
+        for (user_id, wishlist) in alerts {
+            if mature {
+                match db.get_user_age(user_id.clone()).await {
+                    Ok(Some(age)) if age >= MATURE_AGE => {}
+                    Ok(_) => continue,
+                    Err(e) => {
+                        tracing::error!("Failed to determine age of user {}: {:?}", user_id, e);
+                        continue;
+                    }
+                }
+            }
+            let (kind, title, reason) = if wishlist {
+                (
+                    "wishlist_match",
+                    "A game on your wishlist was listed",
+                    "a game on your wishlist",
+                )
+            } else {
+                (
+                    "saved_search_match",
+                    "New offer for your saved search",
+                    "one of your saved searches",
+                )
+            };
+            let body = format!(
+                "\"{}\" for {} was just listed for {:.2}, matching {}.",
+                offer.game_title,
+                offer.platform.label(),
+                offer.price,
+                reason
+            );
+            if let Err(e) = db
+                .create_notification(user_id.clone(), kind, title.to_string(), body)
+                .await
+            {
+                tracing::error!("Failed to notify user about new matching offer: {:?}", e);
+            }
+        }
+    });
+}
+
+/// Handles requests to save a search for the authenticated user.
+///
+/// The user is notified whenever a new offer matches the search. Give at least one of `title`
+/// (contained in the game title), `platform` and `max_price`.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the search criteria.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the saved search or an error.
+#[post("saved-searches")]
+pub(super) async fn create_saved_search(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<SavedSearchCriteria>,
+) -> ApiResponse<SavedSearch> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Saved search validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    match db.get_saved_searches(&auth.user_id).await {
+        Ok(searches) if searches.len() >= MAX_SAVED_SEARCHES => {
+            return ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!("You can save at most {} searches.", MAX_SAVED_SEARCHES),
+            );
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Failed to retrieve saved searches: {:?}", e);
+            return ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to save search.");
+        }
+    }
+
+    match db.create_saved_search(&auth.user_id, &body).await {
+        Ok(search) => ApiResponse::created(search)
+            .with_message("Search saved. You will be notified about new matching offers."),
+        Err(e) => {
+            tracing::error!("Failed to save search: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to save search.")
+        }
+    }
+}
+
+/// Handles requests for the authenticated user's saved searches.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the saved searches, oldest first, or an error.
+#[get("saved-searches")]
+pub(super) async fn get_saved_searches(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+) -> ApiResponse<Vec<SavedSearch>> {
+    match db.get_saved_searches(&auth.user_id).await {
+        Ok(searches) => ApiResponse::ok(searches),
+        Err(e) => {
+            tracing::error!("Failed to retrieve saved searches: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve saved searches.",
+            )
+        }
+    }
+}
+
+/// Handles requests to delete one of the authenticated user's saved searches.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the saved search ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the deletion.
+#[delete("saved-searches/{search_id}")]
+pub(super) async fn delete_saved_search(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    match db
+        .delete_saved_search(&auth.user_id, &path.into_inner())
+        .await
+    {
+        Ok(true) => ApiResponse::message("Saved search deleted."),
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "Saved search not found."),
+        Err(e) => {
+            tracing::error!("Failed to delete saved search: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete saved search.",
+            )
+        }
+    }
+}
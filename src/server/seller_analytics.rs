@@ -0,0 +1,41 @@
+//! src/server/seller_analytics.rs
+//!
+//! This module defines the route sellers use to see the analytics of their offers and the revenue
+//! of their sales.
+
+use crate::database::Database;
+use crate::database::seller_analytics::SellerAnalytics;
+use crate::response::ApiResponse;
+use crate::scopes::{OffersRead, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{get, web};
+
+/// Handles requests for the analytics of the authenticated user's offers.
+///
+/// Returns the views, favorites, messages, orders, sales and conversion rate of every offer that
+/// isn't deleted, and the revenue of the completed sales per month and currency.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `offers:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the analytics or an error.
+#[get("my-offers/analytics")]
+pub(super) async fn get_seller_analytics(
+    db: web::Data<Database>,
+    auth: RequireScope<OffersRead>,
+) -> ApiResponse<SellerAnalytics> {
+    match db.get_seller_analytics(&auth.user_id).await {
+        Ok(analytics) => ApiResponse::ok(analytics),
+        Err(e) => {
+            tracing::error!("Failed to retrieve seller analytics: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve analytics.",
+            )
+        }
+    }
+}
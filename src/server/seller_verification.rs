@@ -0,0 +1,313 @@
+//! src/server/seller_verification.rs
+//!
+//! This module defines the routes of the verified seller badge: sellers apply for the badge, and
+//! admins review the applications of sellers who don't meet the criteria yet, or revoke the badge.
+
+use super::admin::require_admin;
+use crate::database::Database;
+use crate::database::seller_verification::{
+    VERIFIED_MIN_COMPLETED_SALES, VerificationRequest, VerificationState,
+};
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, delete, get, post, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the seller verification application body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct ApplyForVerificationRequest {
+    #[validate(length(max = 2000, message = "Message must be at most 2000 characters long"))]
+    message: Option<String>,
+}
+
+/// Struct representing the query parameters of the verification queue
+#[derive(Debug, Deserialize)]
+struct VerificationQueueQuery {
+    state: Option<VerificationState>,
+}
+
+/// Struct representing the verification review request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct VerificationReviewRequest {
+    approve: bool,
+    #[validate(length(max = 2000, message = "Note must be at most 2000 characters long"))]
+    note: Option<String>,
+}
+
+/// Handles requests to apply for the verified seller badge.
+///
+/// Sellers with at least `VERIFIED_MIN_COMPLETED_SALES` completed sales are verified right away;
+/// all other applications wait for an admin to review them.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing an optional message to the admins.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the recorded application or an error.
+#[post("user/verification")]
+pub(super) async fn apply_for_verification(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<ApplyForVerificationRequest>,
+) -> ApiResponse<VerificationRequest> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Verification application validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db.get_user_by_id(auth.user_id.clone()).await {
+        Ok(Some(user)) if user.verified => {
+            return ApiResponse::error(StatusCode::CONFLICT, "You are already verified.");
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return ApiResponse::error(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            tracing::error!("Failed to retrieve user for verification: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to apply for verification.",
+            );
+        }
+    }
+
+    match db
+        .request_seller_verification(auth.user_id, body.into_inner().message)
+        .await
+    {
+        Ok(Some(request)) if request.state == VerificationState::Approved => {
+            ApiResponse::created(request).with_message("You are now a verified seller.")
+        }
+        Ok(Some(request)) => ApiResponse::created(request).with_message(format!(
+            "Application submitted for review. Sellers with {} completed sales are verified without a review.",
+            VERIFIED_MIN_COMPLETED_SALES
+        )),
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "Your application is already waiting for review.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to apply for verification: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to apply for verification.",
+            )
+        }
+    }
+}
+
+/// Handles requests to list seller verification applications, oldest first.
+///
+/// This route is restricted to admins. Pass `state=pending` to only list applications awaiting
+/// review.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `query` - Query parameters containing the state to filter by (optional).
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of applications or an error.
+#[get("admin/seller-verifications")]
+pub(super) async fn get_verification_requests(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    query: web::Query<VerificationQueueQuery>,
+) -> ApiResponse<Vec<VerificationRequest>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_verification_requests(query.state).await {
+        Ok(requests) => ApiResponse::ok(requests),
+        Err(e) => {
+            tracing::error!("Failed to retrieve verification requests: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve verification requests.",
+            )
+        }
+    }
+}
+
+/// Handles requests to review a pending seller verification application.
+///
+/// This route is restricted to admins. The seller is notified of the outcome and the review is
+/// recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the application ID.
+/// * `body` - JSON payload containing the decision and an optional note.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the reviewed application or an error.
+#[post("admin/seller-verifications/{id}/review")]
+pub(super) async fn review_verification_request(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<VerificationReviewRequest>,
+) -> ApiResponse<VerificationRequest> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Verification review request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let request_id = path.into_inner();
+    let body = body.into_inner();
+    let request = match db
+        .review_verification_request(
+            request_id.clone(),
+            body.approve,
+            admin_id.clone(),
+            body.note,
+        )
+        .await
+    {
+        Ok(Some(request)) => request,
+        Ok(None) => {
+            return ApiResponse::error(
+                StatusCode::NOT_FOUND,
+                "Pending verification request not found.",
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to review verification request: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to review verification request.",
+            );
+        }
+    };
+
+    let (title, mut body_text) = if body.approve {
+        (
+            "You are now a verified seller",
+            "Your offers now carry the verified seller badge.".to_string(),
+        )
+    } else {
+        (
+            "Your verification was declined",
+            "Your application for the verified seller badge was declined.".to_string(),
+        )
+    };
+    if let Some(note) = &request.review_note {
+        body_text.push_str(&format!("\n\nNote from the reviewer: {}", note));
+    }
+    if let Err(e) = db
+        .create_notification(
+            request.seller_id.clone(),
+            "verification_reviewed",
+            title.to_string(),
+            body_text,
+        )
+        .await
+    {
+        tracing::error!("Failed to notify seller about verification review: {:?}", e);
+    }
+
+    if let Err(e) = db
+        .record_audit_entry(
+            admin_id,
+            if body.approve {
+                "approve_seller_verification"
+            } else {
+                "reject_seller_verification"
+            },
+            vec![request_id, request.seller_id.clone()],
+            format!(
+                "Seller verification with {} completed sales",
+                request.completed_sales
+            ),
+        )
+        .await
+    {
+        tracing::error!("Failed to record audit entry: {:?}", e);
+    }
+
+    ApiResponse::ok(request).with_message("Verification request reviewed successfully.")
+}
+
+/// Handles requests to revoke the verified seller badge of a user.
+///
+/// This route is restricted to admins. The badge is removed from the user's offers, the user is
+/// notified and the revocation is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the user ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the revocation.
+#[delete("admin/users/{id}/verification")]
+pub(super) async fn revoke_verification(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let user_id = path.into_inner();
+    match db.set_seller_verified(user_id.clone(), false).await {
+        Ok(true) => {
+            if let Err(e) = db
+                .create_notification(
+                    user_id.clone(),
+                    "verification_revoked",
+                    "Your verified seller badge was removed".to_string(),
+                    "An admin removed the verified seller badge from your account and offers."
+                        .to_string(),
+                )
+                .await
+            {
+                tracing::error!(
+                    "Failed to notify seller about verification revocation: {:?}",
+                    e
+                );
+            }
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "revoke_seller_verification",
+                    vec![user_id],
+                    "Revoked verified seller badge".to_string(),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::message("Verified seller badge revoked successfully.")
+        }
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "User not found."),
+        Err(e) => {
+            tracing::error!("Failed to revoke verification: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to revoke verification.",
+            )
+        }
+    }
+}
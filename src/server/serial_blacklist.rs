@@ -0,0 +1,176 @@
+//! src/server/serial_blacklist.rs
+//!
+//! This module defines the admin routes managing the blacklist of serial numbers reported as
+//! stolen. Console listings with a blacklisted serial are held for moderation when created.
+
+use super::admin::require_admin;
+use crate::database::serial_blacklist::{BlacklistedSerial, normalize_serial};
+use crate::database::{Database, record_key};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, delete, get, post, web};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+use validator_derive::Validate;
+
+/// Struct representing the add blacklisted serial request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct AddBlacklistedSerialRequest {
+    #[validate(custom(function = "validate_serial"))]
+    serial: String,
+    #[validate(length(min = 3, max = 500, message = "Note must be 3 to 500 characters long"))]
+    note: String,
+}
+
+/// Ensures a serial number contains letters or digits and is not overly long.
+fn validate_serial(serial: &str) -> Result<(), ValidationError> {
+    if normalize_serial(serial).is_empty() || serial.len() > 64 {
+        Err(ValidationError::new("serial")
+            .with_message("Serial number must be 1 to 64 characters long".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Handles requests to list the blacklisted serial numbers.
+///
+/// This route is restricted to admins.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the list of blacklisted serials or an error.
+#[get("admin/serial-blacklist")]
+pub(super) async fn get_blacklisted_serials(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<Vec<BlacklistedSerial>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_blacklisted_serials().await {
+        Ok(serials) => ApiResponse::ok(serials),
+        Err(e) => {
+            tracing::error!("Failed to retrieve blacklisted serials: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve blacklisted serials.",
+            )
+        }
+    }
+}
+
+/// Handles requests to add a serial number to the blacklist.
+///
+/// This route is restricted to admins. The addition is recorded in the audit log. Listings that
+/// were created before the serial was blacklisted are not rechecked.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `body` - JSON payload containing the serial number and a note on where the report came from.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the created blacklist entry or an error.
+#[post("admin/serial-blacklist")]
+pub(super) async fn add_blacklisted_serial(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    body: web::Json<AddBlacklistedSerialRequest>,
+) -> ApiResponse<BlacklistedSerial> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    if let Err(e) = body.validate() {
+        tracing::warn!("Add blacklisted serial request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let body = body.into_inner();
+    match db
+        .add_blacklisted_serial(body.serial, body.note, admin_id.clone())
+        .await
+    {
+        Ok(entry) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "add_blacklisted_serial",
+                    vec![record_key(&entry.id)],
+                    format!("Blacklisted serial {}: {}", entry.serial, entry.note),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::created(entry).with_message("Serial number added to the blacklist.")
+        }
+        Err(e) => {
+            tracing::warn!("Failed to add blacklisted serial: {:?}", e);
+            ApiResponse::error(
+                StatusCode::CONFLICT,
+                "Failed to add serial number. It may already be blacklisted.",
+            )
+        }
+    }
+}
+
+/// Handles requests to remove a serial number from the blacklist.
+///
+/// This route is restricted to admins. The removal is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the serial number.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the removal.
+#[delete("admin/serial-blacklist/{serial}")]
+pub(super) async fn remove_blacklisted_serial(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+
+    let serial = normalize_serial(&path.into_inner());
+    match db.remove_blacklisted_serial(serial.clone()).await {
+        Ok(true) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "remove_blacklisted_serial",
+                    vec![serial.clone()],
+                    format!("Removed serial {} from the blacklist", serial),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::message("Serial number removed from the blacklist.")
+        }
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "Serial number is not blacklisted."),
+        Err(e) => {
+            tracing::error!("Failed to remove blacklisted serial: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to remove serial number.",
+            )
+        }
+    }
+}
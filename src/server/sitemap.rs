@@ -0,0 +1,128 @@
+//! src/server/sitemap.rs
+//!
+//! This module defines the public sitemap routes search engines crawl: a sitemap index at
+//! `/sitemap.xml`, listing paginated sitemaps of the offers and of the sellers.
+
+use crate::database::Database;
+use crate::sitemap::{SITEMAP_PAGE_SIZE, SitemapEntry, SitemapKind, render_index, render_urlset};
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use dotenvy::var;
+
+/// Returns the public URL the sitemap entries start with (`PUBLIC_BASE_URL`, defaults to the
+/// scheme and host of the request).
+fn base_url(req: &HttpRequest) -> String {
+    match var("PUBLIC_BASE_URL") {
+        Ok(url) if !url.trim().is_empty() => url.trim().trim_end_matches('/').to_string(),
+        _ => {
+            let info = req.connection_info();
+            format!("{}://{}", info.scheme(), info.host())
+        }
+    }
+}
+
+/// Wraps a rendered sitemap in a response.
+fn xml_response(xml: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(xml)
+}
+
+/// Returns the plain-text response for sitemaps that don't exist.
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound()
+        .content_type("text/plain; charset=utf-8")
+        .body("Sitemap not found.")
+}
+
+/// Serves the sitemap index, listing one sitemap per `SITEMAP_PAGE_SIZE` offers or sellers.
+///
+/// This route requires no authentication.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - The HTTP request, used for the host when `PUBLIC_BASE_URL` isn't set.
+///
+/// # Returns
+///
+/// An `HttpResponse` containing the sitemap index or a plain-text error.
+#[get("/sitemap.xml")]
+pub(super) async fn get_sitemap_index(db: web::Data<Database>, req: HttpRequest) -> HttpResponse {
+    let (offers, sellers) = match db.count_sitemap_records().await {
+        Ok(counts) => counts,
+        Err(e) => {
+            tracing::error!("Failed to count sitemap records: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .content_type("text/plain; charset=utf-8")
+                .body("Failed to generate sitemap.");
+        }
+    };
+
+    let base_url = base_url(&req);
+    let sitemaps: Vec<SitemapEntry> = SitemapKind::ALL
+        .into_iter()
+        .zip([offers, sellers])
+        .flat_map(|(kind, count)| {
+            let pages = count.div_ceil(u64::from(SITEMAP_PAGE_SIZE)) as u32;
+            let base_url = base_url.clone();
+            (1..=pages).map(move |page| SitemapEntry {
+                loc: format!("{}{}", base_url, kind.sitemap_path(page)),
+                lastmod: None,
+            })
+        })
+        .collect();
+    xml_response(render_index(&sitemaps))
+}
+
+/// Serves one sitemap of offer pages or seller profiles.
+///
+/// This route requires no authentication. Only offers anonymous visitors can see are listed, so
+/// offers rated for adults and their sellers are left out.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - The HTTP request, used for the host when `PUBLIC_BASE_URL` isn't set.
+/// * `path` - Path containing the kind of pages (`offers` or `sellers`) and the 1-based page.
+///
+/// # Returns
+///
+/// An `HttpResponse` containing the sitemap or a plain-text error.
+#[get("/sitemaps/{kind}/{page}.xml")]
+pub(super) async fn get_sitemap(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<(String, u32)>,
+) -> HttpResponse {
+    let (kind, page) = path.into_inner();
+    let Some(kind) = SitemapKind::from_name(&kind) else {
+        return not_found();
+    };
+    if page == 0 {
+        return not_found();
+    }
+
+    let records = match db.get_sitemap_records(kind, page).await {
+        Ok(records) => records,
+        Err(e) => {
+            tracing::error!("Failed to retrieve sitemap records: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .content_type("text/plain; charset=utf-8")
+                .body("Failed to generate sitemap.");
+        }
+    };
+    // Pages past the end don't exist; the first one is served empty while nothing is listed
+    if records.is_empty() && page > 1 {
+        return not_found();
+    }
+
+    let base_url = base_url(&req);
+    let entries: Vec<SitemapEntry> = records
+        .into_iter()
+        .map(|record| SitemapEntry {
+            loc: format!("{}{}", base_url, kind.page_path(&record.id)),
+            lastmod: Some(record.last_modified),
+        })
+        .collect();
+    xml_response(render_urlset(&entries))
+}
@@ -0,0 +1,183 @@
+//! src/server/taxes.rs
+//!
+//! This module defines the admin routes managing the VAT rates of countries. Orders keep the tax
+//! they were placed with when a rate changes.
+
+use super::admin::require_admin;
+use crate::database::Database;
+use crate::database::taxes::{TaxRate, normalize_country};
+use crate::response::{ApiError, ApiResponse};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, delete, get, put, web};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use validator_derive::Validate;
+
+/// Struct representing the set tax rate request body
+#[derive(Debug, Deserialize, Serialize, Validate)]
+struct TaxRateRequest {
+    #[validate(range(
+        min = 0.0,
+        max = 50.0,
+        message = "Rate must be between 0 and 50 percent"
+    ))]
+    rate_percent: f64,
+}
+
+/// Normalizes the country code of a request path.
+fn path_country(country: &str) -> Result<String, ApiError> {
+    normalize_country(country).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Country must be a two-letter country code.",
+        )
+    })
+}
+
+/// Handles requests to list the VAT rates.
+///
+/// This route is restricted to admins.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the rates, ordered by country code, or an error.
+#[get("admin/tax-rates")]
+pub(super) async fn get_tax_rates(
+    db: web::Data<Database>,
+    req: HttpRequest,
+) -> ApiResponse<Vec<TaxRate>> {
+    if let Err(error) = require_admin(&db, &req).await {
+        return error.into();
+    }
+
+    match db.get_tax_rates().await {
+        Ok(rates) => ApiResponse::ok(rates),
+        Err(e) => {
+            tracing::error!("Failed to retrieve tax rates: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve tax rates.",
+            )
+        }
+    }
+}
+
+/// Handles requests to set the VAT rate of a country.
+///
+/// This route is restricted to admins. The rate applies to orders placed from now on by buyers in
+/// the country from business sellers. The change is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the two-letter country code.
+/// * `body` - JSON payload containing the rate in percent.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the stored rate or an error.
+#[put("admin/tax-rates/{country}")]
+pub(super) async fn set_tax_rate(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<TaxRateRequest>,
+) -> ApiResponse<TaxRate> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    let country = match path_country(&path) {
+        Ok(country) => country,
+        Err(error) => return error.into(),
+    };
+    if let Err(e) = body.validate() {
+        tracing::warn!("Tax rate request validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    match db
+        .set_tax_rate(&country, body.rate_percent, admin_id.clone())
+        .await
+    {
+        Ok(rate) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "set_tax_rate",
+                    vec![country.clone()],
+                    format!("Set VAT rate of {} to {}%", country, rate.rate_percent),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::ok(rate).with_message("Tax rate set successfully.")
+        }
+        Err(e) => {
+            tracing::error!("Failed to set tax rate: {:?}", e);
+            ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to set tax rate.")
+        }
+    }
+}
+
+/// Handles requests to delete the VAT rate of a country.
+///
+/// This route is restricted to admins. Orders from the country are free of VAT afterwards. The
+/// deletion is recorded in the audit log.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `path` - Path containing the two-letter country code.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the deletion.
+#[delete("admin/tax-rates/{country}")]
+pub(super) async fn delete_tax_rate(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    let admin_id = match require_admin(&db, &req).await {
+        Ok(id) => id,
+        Err(error) => return error.into(),
+    };
+    let country = match path_country(&path) {
+        Ok(country) => country,
+        Err(error) => return error.into(),
+    };
+
+    match db.delete_tax_rate(&country).await {
+        Ok(true) => {
+            if let Err(e) = db
+                .record_audit_entry(
+                    admin_id,
+                    "delete_tax_rate",
+                    vec![country.clone()],
+                    format!("Deleted VAT rate of {}", country),
+                )
+                .await
+            {
+                tracing::error!("Failed to record audit entry: {:?}", e);
+            }
+            ApiResponse::message("Tax rate deleted successfully.")
+        }
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "Tax rate not found."),
+        Err(e) => {
+            tracing::error!("Failed to delete tax rate: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to delete tax rate.",
+            )
+        }
+    }
+}
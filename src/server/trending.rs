@@ -0,0 +1,90 @@
+//! src/server/trending.rs
+//!
+//! This module defines the trending offers route the homepage lists, and runs the job
+//! recomputing the trending scores from the recent views and favorites.
+
+use super::{convert_prices, viewer_currency, viewer_is_adult};
+use crate::database::pagination::Pagination;
+use crate::database::{Database, Offer};
+use crate::metrics::{TaskMetrics, TaskOutcome};
+use crate::response::ApiResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, get, web};
+use std::time::{Duration, Instant};
+
+/// How often the trending scores are recomputed.
+const TRENDING_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// The name of the trending job in the task metrics.
+const TRENDING_TASK: &str = "trending_refresh";
+
+/// Starts the background job that recomputes the trending scores of the offers.
+///
+/// The first run happens right away. If a run fails, the previous scores are kept.
+///
+/// # Arguments
+///
+/// * `db` - The database connection.
+pub(super) fn spawn_trending_job(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TRENDING_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let started = Instant::now();
+            match db.refresh_trending_scores().await {
+                Ok(scored) => {
+                    tracing::info!("Refreshed the trending scores of {} offers", scored);
+                    let metrics = TaskMetrics::global();
+                    metrics.record_run(TRENDING_TASK, TaskOutcome::Success, started.elapsed());
+                }
+                Err(e) => {
+                    TaskMetrics::global().record_run(
+                        TRENDING_TASK,
+                        TaskOutcome::Failure,
+                        started.elapsed(),
+                    );
+                    tracing::error!("Failed to refresh trending scores: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Handles requests for the offers that are popular right now.
+///
+/// Offers are ranked by their views and favorites of the last days, recent activity weighing
+/// more, see `src/database/trending.rs`. The ranking is up to 15 minutes old. Offers without
+/// recent activity are left out. Mature-rated offers are only included for logged-in adults, and
+/// prices are also shown in the viewer's preferred currency. Paginated like `GET /api/offers`.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `req` - HTTP request to access extensions.
+/// * `pagination` - Query parameters containing the requested page.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing a page of offers, most popular first, and the pagination details,
+/// or an error.
+#[get("offers/trending")]
+pub(super) async fn get_trending_offers(
+    db: web::Data<Database>,
+    req: HttpRequest,
+    pagination: web::Query<Pagination>,
+) -> ApiResponse<Vec<Offer>> {
+    let include_mature = viewer_is_adult(&db, &req).await;
+    match db.query_trending_offers(include_mature, &pagination).await {
+        Ok((mut offers, page_info)) => {
+            convert_prices(&mut offers, viewer_currency(&db, &req).await).await;
+            ApiResponse::ok(offers).with_pagination(page_info)
+        }
+        Err(e) => {
+            tracing::error!("Failed to retrieve trending offers: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve trending offers.",
+            )
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! src/server/wishlist.rs
+//!
+//! This module defines the routes managing users' wishlists. The alerts about newly listed offers
+//! matching a wishlist are sent with the saved search alerts, see `alert_new_offer`.
+
+use crate::database::Database;
+use crate::database::wishlist::{MAX_WISHLIST_ITEMS, WishlistEntry, WishlistItem};
+use crate::response::ApiResponse;
+use crate::scopes::{ProfileRead, ProfileWrite, RequireScope};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web};
+use validator::Validate;
+
+/// Handles requests to add a game to the authenticated user's wishlist.
+///
+/// The user is notified whenever an offer for the game is listed, on the given `platform` (any
+/// if omitted) and at most at `max_price` (any price if omitted).
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `body` - JSON payload containing the game.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the wishlist item or an error.
+#[post("wishlist")]
+pub(super) async fn add_to_wishlist(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    body: web::Json<WishlistEntry>,
+) -> ApiResponse<WishlistItem> {
+    if let Err(e) = body.validate() {
+        tracing::warn!("Wishlist item validation failed: {:?}", e);
+        return ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    match db.get_wishlist(&auth.user_id).await {
+        Ok(items) if items.len() >= MAX_WISHLIST_ITEMS => {
+            return ApiResponse::error(
+                StatusCode::CONFLICT,
+                format!(
+                    "Your wishlist can hold at most {} games.",
+                    MAX_WISHLIST_ITEMS
+                ),
+            );
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Failed to retrieve wishlist: {:?}", e);
+            return ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to add game to wishlist.",
+            );
+        }
+    }
+
+    match db.create_wishlist_item(&auth.user_id, &body).await {
+        Ok(Some(item)) => ApiResponse::created(item)
+            .with_message("Game added to your wishlist. You will be notified about new offers."),
+        Ok(None) => ApiResponse::error(
+            StatusCode::CONFLICT,
+            "This game is already on your wishlist.",
+        ),
+        Err(e) => {
+            tracing::error!("Failed to add game to wishlist: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to add game to wishlist.",
+            )
+        }
+    }
+}
+
+/// Handles requests for the authenticated user's wishlist.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:read` scope.
+///
+/// # Returns
+///
+/// An `ApiResponse` containing the wishlist items, oldest first, or an error.
+#[get("wishlist")]
+pub(super) async fn get_wishlist(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileRead>,
+) -> ApiResponse<Vec<WishlistItem>> {
+    match db.get_wishlist(&auth.user_id).await {
+        Ok(items) => ApiResponse::ok(items),
+        Err(e) => {
+            tracing::error!("Failed to retrieve wishlist: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to retrieve wishlist.",
+            )
+        }
+    }
+}
+
+/// Handles requests to remove a game from the authenticated user's wishlist.
+///
+/// # Arguments
+///
+/// * `db` - Web data containing the database connection.
+/// * `auth` - The authenticated user. The token must carry the `profile:write` scope.
+/// * `path` - Path containing the wishlist item ID.
+///
+/// # Returns
+///
+/// An `ApiResponse` indicating the success or failure of the removal.
+#[delete("wishlist/{item_id}")]
+pub(super) async fn remove_from_wishlist(
+    db: web::Data<Database>,
+    auth: RequireScope<ProfileWrite>,
+    path: web::Path<String>,
+) -> ApiResponse<()> {
+    match db
+        .delete_wishlist_item(&auth.user_id, &path.into_inner())
+        .await
+    {
+        Ok(true) => ApiResponse::message("Game removed from your wishlist."),
+        Ok(false) => ApiResponse::error(StatusCode::NOT_FOUND, "Wishlist item not found."),
+        Err(e) => {
+            tracing::error!("Failed to remove game from wishlist: {:?}", e);
+            ApiResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to remove game from wishlist.",
+            )
+        }
+    }
+}
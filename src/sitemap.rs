@@ -0,0 +1,129 @@
+//! src/sitemap.rs
+//!
+//! This module renders the sitemaps search engines crawl the marketplace with, following the
+//! sitemaps.org protocol. The pages of offers and sellers are split into sitemaps of at most
+//! `SITEMAP_PAGE_SIZE` URLs each, which are listed in a sitemap index.
+
+/// The largest number of URLs in one sitemap. The protocol allows 50,000; smaller sitemaps keep
+/// the responses small.
+pub const SITEMAP_PAGE_SIZE: u32 = 10_000;
+
+/// The kinds of pages listed in the sitemaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitemapKind {
+    /// The pages of active offers.
+    Offers,
+    /// The profile pages of sellers with active offers.
+    Sellers,
+}
+
+impl SitemapKind {
+    /// All kinds, in the order they are listed in the sitemap index.
+    pub const ALL: [SitemapKind; 2] = [SitemapKind::Offers, SitemapKind::Sellers];
+
+    /// Returns the name of the kind used in the sitemap URLs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SitemapKind::Offers => "offers",
+            SitemapKind::Sellers => "sellers",
+        }
+    }
+
+    /// Parses the name of a kind used in the sitemap URLs.
+    pub fn from_name(name: &str) -> Option<SitemapKind> {
+        SitemapKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == name)
+    }
+
+    /// Returns the path of the page of an offer or seller.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The slug or ID of the offer, or the ID of the seller.
+    pub fn page_path(&self, id: &str) -> String {
+        match self {
+            SitemapKind::Offers => format!("/web/browse.html?offer={}", id),
+            SitemapKind::Sellers => format!("/web/browse.html?seller={}", id),
+        }
+    }
+
+    /// Returns the path of one of the sitemaps of this kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - The 1-based number of the sitemap.
+    pub fn sitemap_path(&self, page: u32) -> String {
+        format!("/sitemaps/{}/{}.xml", self.as_str(), page)
+    }
+}
+
+/// A page listed in a sitemap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitemapEntry {
+    /// The absolute URL of the page.
+    pub loc: String,
+    /// The timestamp when the page last changed, if known.
+    pub lastmod: Option<String>,
+}
+
+/// Escapes the characters that are special in XML text.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Renders one `<loc>` with its optional `<lastmod>`.
+fn render_location(entry: &SitemapEntry) -> String {
+    let mut location = format!("<loc>{}</loc>", xml_escape(&entry.loc));
+    if let Some(lastmod) = &entry.lastmod {
+        location.push_str(&format!("<lastmod>{}</lastmod>", xml_escape(lastmod)));
+    }
+    location
+}
+
+/// Renders a sitemap listing pages.
+///
+/// # Arguments
+///
+/// * `entries` - The pages.
+///
+/// # Returns
+///
+/// The XML document.
+pub fn render_urlset(entries: &[SitemapEntry]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for entry in entries {
+        xml.push_str(&format!("  <url>{}</url>\n", render_location(entry)));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// Renders a sitemap index listing sitemaps.
+///
+/// # Arguments
+///
+/// * `sitemaps` - The sitemaps.
+///
+/// # Returns
+///
+/// The XML document.
+pub fn render_index(sitemaps: &[SitemapEntry]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for sitemap in sitemaps {
+        xml.push_str(&format!(
+            "  <sitemap>{}</sitemap>\n",
+            render_location(sitemap)
+        ));
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
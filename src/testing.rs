@@ -0,0 +1,463 @@
+//! src/testing.rs
+//!
+//! This module provides fluent builders creating valid users, offers and orders for tests. The
+//! records are inserted through the regular `Database` methods, so they pass the same checks as
+//! records created through the API. Related records a builder needs but wasn't given, such as the
+//! seller of an offer, are created with default values. `backdate` moves timestamps into the past,
+//! so tests can pass deadlines without waiting.
+//!
+//! Available in the crate's own tests and, for other crates, with the `testing` feature.
+
+use crate::database::conditions::Condition;
+use crate::database::offer_status::OfferStatus;
+use crate::database::orders::{Order, OrderState};
+use crate::database::platforms::Platform;
+use crate::database::{
+    Database, Offer, Role, User,
+    catalog::{OfferAttributes, OfferMetadata},
+    record_key,
+};
+use crate::errors::custom_errors::CustomError;
+
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use surrealdb::sql::Value;
+use uuid::Uuid;
+
+/// Opens an empty database in a new temporary directory.
+///
+/// The database name, namespaces and encryption key are read from the environment, like for the
+/// server.
+///
+/// # Returns
+///
+/// A `Result` containing the database connection or a `CustomError` if it can't be opened.
+pub async fn test_database() -> Result<Database, CustomError> {
+    let path = std::env::temp_dir().join(format!("gameshop-test-{}", Uuid::new_v4()));
+    Database::connect(path.to_string_lossy().into_owned()).await
+}
+
+/// Builds a registered user.
+#[derive(Debug, Clone)]
+pub struct UserBuilder {
+    firstname: String,
+    lastname: String,
+    username: String,
+    email: String,
+    password: String,
+    date_of_birth: NaiveDate,
+    role: Role,
+}
+
+impl Default for UserBuilder {
+    fn default() -> Self {
+        let unique = Uuid::new_v4().simple().to_string();
+        UserBuilder {
+            firstname: "Test".to_string(),
+            lastname: "User".to_string(),
+            username: format!("user_{}", &unique[..12]),
+            email: format!("{unique}@example.com"),
+            password: "correct horse battery staple".to_string(),
+            date_of_birth: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap_or_default(),
+            role: Role::User,
+        }
+    }
+}
+
+impl UserBuilder {
+    /// Starts building an adult user with a unique email address and username.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the first name.
+    pub fn firstname(mut self, firstname: impl Into<String>) -> Self {
+        self.firstname = firstname.into();
+        self
+    }
+
+    /// Sets the last name.
+    pub fn lastname(mut self, lastname: impl Into<String>) -> Self {
+        self.lastname = lastname.into();
+        self
+    }
+
+    /// Sets the username.
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = username.into();
+        self
+    }
+
+    /// Sets the email address.
+    pub fn email(mut self, email: impl Into<String>) -> Self {
+        self.email = email.into();
+        self
+    }
+
+    /// Sets the password.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = password.into();
+        self
+    }
+
+    /// Sets the date of birth.
+    pub fn date_of_birth(mut self, date_of_birth: NaiveDate) -> Self {
+        self.date_of_birth = date_of_birth;
+        self
+    }
+
+    /// Sets the role.
+    pub fn role(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Registers the user and assigns the role.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The database to insert the user into.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `User` or a `CustomError` if registration fails.
+    pub async fn create(self, db: &Database) -> Result<User, CustomError> {
+        db.register(
+            self.firstname,
+            self.lastname,
+            self.username,
+            self.password.clone(),
+            self.email.clone(),
+            self.date_of_birth,
+        )
+        .await?;
+        let user = db
+            .verify_credentials(self.email.clone(), self.password.clone())
+            .await?;
+        if self.role == Role::User {
+            return Ok(user);
+        }
+        db.change_user_role(record_key(&user.id), Role::User, self.role)
+            .await?;
+        db.verify_credentials(self.email, self.password).await
+    }
+}
+
+/// Builds a game offer.
+#[derive(Debug, Clone)]
+pub struct OfferBuilder {
+    game_title: String,
+    platform: Platform,
+    condition: Condition,
+    price: f64,
+    description: String,
+    seller_id: Option<String>,
+    metadata: OfferMetadata,
+    status: OfferStatus,
+}
+
+impl Default for OfferBuilder {
+    fn default() -> Self {
+        OfferBuilder {
+            game_title: "The Legend of Zelda: Breath of the Wild".to_string(),
+            platform: Platform::Switch,
+            condition: Condition::Good,
+            price: 40.0,
+            description: "Complete in box, no scratches.".to_string(),
+            seller_id: None,
+            metadata: OfferMetadata::default(),
+            status: OfferStatus::Active,
+        }
+    }
+}
+
+impl OfferBuilder {
+    /// Starts building an active game offer by a new seller.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the game title.
+    pub fn game_title(mut self, game_title: impl Into<String>) -> Self {
+        self.game_title = game_title.into();
+        self
+    }
+
+    /// Sets the platform.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Sets the condition.
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.condition = condition;
+        self
+    }
+
+    /// Sets the price.
+    pub fn price(mut self, price: f64) -> Self {
+        self.price = price;
+        self
+    }
+
+    /// Sets the description.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the seller. Without a seller, a new user is registered as the seller.
+    pub fn seller(mut self, seller_id: impl Into<String>) -> Self {
+        self.seller_id = Some(seller_id.into());
+        self
+    }
+
+    /// Sets the category and category-specific attributes.
+    pub fn attributes(mut self, attributes: OfferAttributes) -> Self {
+        self.metadata.attributes = Some(attributes);
+        self
+    }
+
+    /// Sets the catalog metadata, replacing previously set attributes.
+    pub fn metadata(mut self, metadata: OfferMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets the number of copies for sale. Defaults to one.
+    pub fn quantity(mut self, quantity: u32) -> Self {
+        self.metadata.quantity = Some(quantity);
+        self
+    }
+
+    /// Creates the offer as a draft instead of publishing it.
+    pub fn draft(mut self) -> Self {
+        self.status = OfferStatus::Draft;
+        self
+    }
+
+    /// Creates the offer, registering its seller first if none was set.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The database to insert the offer into.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Offer` or a `CustomError` if creation fails.
+    pub async fn create(self, db: &Database) -> Result<Offer, CustomError> {
+        let seller_id = match self.seller_id {
+            Some(seller_id) => seller_id,
+            None => record_key(&UserBuilder::new().create(db).await?.id),
+        };
+        db.create_offer(
+            self.game_title,
+            self.platform,
+            self.condition,
+            self.price,
+            self.description,
+            seller_id,
+            self.metadata,
+            self.status,
+        )
+        .await
+    }
+}
+
+/// Builds an order and moves it to the requested state.
+#[derive(Debug, Clone)]
+pub struct OrderBuilder {
+    offer: Option<Offer>,
+    buyer_id: Option<String>,
+    price: Option<f64>,
+    state: OrderState,
+}
+
+impl Default for OrderBuilder {
+    fn default() -> Self {
+        OrderBuilder {
+            offer: None,
+            buyer_id: None,
+            price: None,
+            state: OrderState::Pending,
+        }
+    }
+}
+
+impl OrderBuilder {
+    /// Starts building a pending order of a new offer by a new buyer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the ordered offer. It must be active. Without an offer, a new one is created.
+    pub fn offer(mut self, offer: Offer) -> Self {
+        self.offer = Some(offer);
+        self
+    }
+
+    /// Sets the buyer. Without a buyer, a new user is registered as the buyer.
+    pub fn buyer(mut self, buyer_id: impl Into<String>) -> Self {
+        self.buyer_id = Some(buyer_id.into());
+        self
+    }
+
+    /// Sets the price. Defaults to the asking price of the offer.
+    pub fn price(mut self, price: f64) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    /// Sets the state the order is moved to after it is placed.
+    pub fn state(mut self, state: OrderState) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Takes a copy of the offer, places the order with the fee schedule in effect and walks it
+    /// through the valid transitions to the requested state, like the order routes do: paying
+    /// holds the payment in escrow, shipping starts the release period, completing releases the
+    /// payment and cancelling refunds it. The offer follows the order, so completing the last
+    /// order of a sold-out offer marks it sold and cancelling puts the copy back.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The database to insert the order into.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Order` in the requested state or a `CustomError` if a step fails.
+    pub async fn create(self, db: &Database) -> Result<Order, CustomError> {
+        let offer = match self.offer {
+            Some(offer) => offer,
+            None => OfferBuilder::new().create(db).await?,
+        };
+        let buyer_id = match self.buyer_id {
+            Some(buyer_id) => buyer_id,
+            None => record_key(&UserBuilder::new().create(db).await?.id),
+        };
+        let price = self.price.unwrap_or(offer.price);
+        let not_applied =
+            |step: &str| CustomError::DatabaseError(format!("Failed to {step} for the test order"));
+
+        db.take_offer_copy(&offer)
+            .await?
+            .ok_or_else(|| not_applied("take a copy of the offer"))?;
+        let fee = db.quote_fee(&offer, price).await?;
+        let mut order = db
+            .create_order(&offer, buyer_id, price, &fee, None, None)
+            .await?
+            .ok_or_else(|| not_applied("place the order"))?;
+
+        let path: &[OrderState] = match self.state {
+            OrderState::Pending => &[],
+            OrderState::Paid => &[OrderState::Paid],
+            OrderState::Shipped => &[OrderState::Paid, OrderState::Shipped],
+            OrderState::Completed => {
+                &[OrderState::Paid, OrderState::Shipped, OrderState::Completed]
+            }
+            OrderState::Cancelled => &[OrderState::Cancelled],
+        };
+        for &next in path {
+            order = db
+                .transition_order_state(&order, next)
+                .await?
+                .ok_or_else(|| not_applied("change the order state"))?;
+        }
+        db.update_offer_for_order(&order).await?;
+        Ok(order)
+    }
+}
+
+/// The namespace holding a table, see `backdate` and `stored_value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    /// The user namespace (`USER_DATABASE_NAMESPACE`).
+    Users,
+    /// The offer namespace (`OFFER_DB_NAMESPACE`).
+    Offers,
+}
+
+/// Selects a namespace for a raw query.
+async fn use_namespace(db: &Database, namespace: Namespace) -> Result<(), CustomError> {
+    let variable = match namespace {
+        Namespace::Users => "USER_DATABASE_NAMESPACE",
+        Namespace::Offers => "OFFER_DB_NAMESPACE",
+    };
+    let namespace = std::env::var(variable)
+        .map_err(|e| CustomError::DatabaseError(format!("{variable} not set: {e}")))?;
+    db.db.use_ns(namespace).await?;
+    Ok(())
+}
+
+/// Moves a timestamp of a record the given number of days into the past.
+///
+/// # Arguments
+///
+/// * `db` - The database holding the record.
+/// * `namespace` - The namespace of the record's table.
+/// * `table` - The table of the record.
+/// * `key` - The key of the record.
+/// * `field` - The datetime field to move. It must be set.
+/// * `days` - The number of days to subtract.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CustomError` if the record wasn't updated.
+pub async fn backdate(
+    db: &Database,
+    namespace: Namespace,
+    table: &str,
+    key: &str,
+    field: &str,
+    days: u32,
+) -> Result<(), CustomError> {
+    use_namespace(db, namespace).await?;
+    let sql = format!(
+        "UPDATE type::thing($table, $key) SET {field} = {field} - {days}d WHERE {field} != NONE RETURN AFTER;"
+    );
+    let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+    vars.insert("table".into(), Value::from(table));
+    vars.insert("key".into(), Value::from(key));
+
+    let mut response: surrealdb::Response = db.db.query(sql).bind(vars).await?;
+    let updated: Vec<Value> = response.take(0)?;
+    if updated.is_empty() {
+        return Err(CustomError::DatabaseError(format!(
+            "Failed to backdate {field} of {table}:{key} for the test"
+        )));
+    }
+    Ok(())
+}
+
+/// Reads a field of a record as it is stored, e.g. to check that it is encrypted.
+///
+/// # Arguments
+///
+/// * `db` - The database holding the record.
+/// * `namespace` - The namespace of the record's table.
+/// * `table` - The table of the record.
+/// * `key` - The key of the record.
+/// * `field` - The field to read.
+///
+/// # Returns
+///
+/// A `Result` containing the stored value, or `None` if the record or the field doesn't exist.
+pub async fn stored_value(
+    db: &Database,
+    namespace: Namespace,
+    table: &str,
+    key: &str,
+    field: &str,
+) -> Result<Option<Value>, CustomError> {
+    use_namespace(db, namespace).await?;
+    let sql = format!("SELECT VALUE {field} FROM type::thing($table, $key);");
+    let mut vars: BTreeMap<String, Value> = BTreeMap::new();
+    vars.insert("table".into(), Value::from(table));
+    vars.insert("key".into(), Value::from(key));
+
+    let mut response: surrealdb::Response = db.db.query(sql).bind(vars).await?;
+    let value: Option<Value> = response.take(0)?;
+    Ok(value.filter(|value| !value.is_none_or_null()))
+}
@@ -0,0 +1,442 @@
+//! src/upload_storage.rs
+//!
+//! This module stores direct image uploads until they are finalized. The `UploadStorage` trait is
+//! implemented by the `S3UploadStorage`, which presigns URLs so clients upload straight to an
+//! S3-compatible bucket without sending the file through the server, and by the
+//! `LocalUploadStorage`, which stages uploads on disk through the server's own upload route and is
+//! meant for development.
+//!
+//! The storage is selected with `UPLOAD_STORAGE` (`local` or `s3`, defaults to `local`).
+
+use crate::errors::custom_errors::CustomError;
+
+use chrono::{DateTime, Utc};
+use dotenvy::var;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The path the `LocalUploadStorage` receives uploads on.
+pub const LOCAL_UPLOADS_PATH: &str = "/uploads/offers";
+
+/// How long a request of the server to the bucket may take.
+const S3_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the presigned URLs the server uses itself are valid.
+const S3_SERVER_URL_VALIDITY: Duration = Duration::from_secs(60);
+
+/// How long taken S3 uploads are remembered, longer than any upload URL is valid.
+const S3_TAKEN_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Where and how a client uploads a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UploadTarget {
+    /// The URL to upload the file to.
+    pub url: String,
+    /// The HTTP method to upload the file with.
+    pub method: &'static str,
+    /// The headers the client must send with the file.
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Stores direct uploads until they are finalized.
+pub trait UploadStorage: Send + Sync {
+    /// Issues the target a client uploads a file to.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name the file is stored under. It must be URL-safe.
+    /// * `content_type` - The content type the client must declare.
+    /// * `token` - The upload token, for storages that authorize uploads with it.
+    /// * `expires_in` - How long the target accepts the upload.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the target, or a `StorageError` if it can't be issued.
+    fn upload_target(
+        &self,
+        key: &str,
+        content_type: &str,
+        token: &str,
+        expires_in: Duration,
+    ) -> Result<UploadTarget, CustomError>;
+
+    /// Removes an uploaded file and returns its content, so every upload can be taken only once.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name the file is stored under.
+    /// * `max_bytes` - The size files are expected to stay within. At most one more byte is
+    ///   read, so callers can reject larger files.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the content, `None` if no file was uploaded under the key or it was
+    /// already taken, or a `StorageError` if the storage can't be read.
+    fn take<'a>(
+        &'a self,
+        key: &'a str,
+        max_bytes: usize,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>, CustomError>>;
+
+    /// Returns whether uploads are sent to the server's own upload route.
+    fn receives_uploads(&self) -> bool;
+}
+
+/// Returns the directory the `LocalUploadStorage` stages uploads in (`IMAGE_STAGING_DIR`,
+/// defaults to `./uploads/staged`). It must not be served.
+pub fn staging_dir() -> PathBuf {
+    var("IMAGE_STAGING_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("./uploads/staged"))
+}
+
+/// Stages uploads in `staging_dir`. Clients upload to `LOCAL_UPLOADS_PATH`, authorized by the
+/// upload token, so the files pass through the server.
+#[derive(Debug, Default)]
+pub struct LocalUploadStorage;
+
+impl UploadStorage for LocalUploadStorage {
+    fn upload_target(
+        &self,
+        key: &str,
+        content_type: &str,
+        token: &str,
+        _expires_in: Duration,
+    ) -> Result<UploadTarget, CustomError> {
+        Ok(UploadTarget {
+            url: format!("{}/{}?token={}", LOCAL_UPLOADS_PATH, key, token),
+            method: "PUT",
+            headers: BTreeMap::from([("Content-Type".to_string(), content_type.to_string())]),
+        })
+    }
+
+    fn take<'a>(
+        &'a self,
+        key: &'a str,
+        _max_bytes: usize,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>, CustomError>> {
+        Box::pin(async move {
+            let staged = staging_dir().join(key);
+            let data = match tokio::fs::read(&staged).await {
+                Ok(data) => data,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(CustomError::StorageError(e.to_string())),
+            };
+            // Only the request that deletes the file takes it
+            match tokio::fs::remove_file(&staged).await {
+                Ok(()) => Ok(Some(data)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(CustomError::StorageError(e.to_string())),
+            }
+        })
+    }
+
+    fn receives_uploads(&self) -> bool {
+        true
+    }
+}
+
+/// Percent-encodes a value as required by AWS Signature Version 4.
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Computes the HMAC-SHA256 of a string.
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Presigns S3 requests with AWS Signature Version 4, authorized by the query string.
+#[derive(Debug, Clone)]
+pub struct S3Signer {
+    /// The ID of the access key.
+    pub access_key_id: String,
+    /// The secret of the access key.
+    pub secret_access_key: String,
+    /// The region of the bucket, e.g. `eu-central-1`.
+    pub region: String,
+}
+
+impl S3Signer {
+    /// Presigns a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The HTTP method of the request.
+    /// * `url` - The URL of the object, without a query string.
+    /// * `content_type` - The content type the request must declare (optional).
+    /// * `expires_in` - How long the URL is valid, at most seven days.
+    /// * `now` - The time the URL is signed at.
+    ///
+    /// # Returns
+    ///
+    /// The presigned URL.
+    pub fn presign(
+        &self,
+        method: &str,
+        url: &Url,
+        content_type: Option<&str>,
+        expires_in: Duration,
+        now: DateTime<Utc>,
+    ) -> String {
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let (canonical_headers, signed_headers) = match content_type {
+            Some(content_type) => (
+                format!("content-type:{}\nhost:{}\n", content_type, host),
+                "content-type;host",
+            ),
+            None => (format!("host:{}\n", host), "host"),
+        };
+        let query = format!(
+            "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential={}&X-Amz-Date={}&X-Amz-Expires={}&X-Amz-SignedHeaders={}",
+            uri_encode(&format!("{}/{}", self.access_key_id, scope)),
+            timestamp,
+            expires_in.as_secs(),
+            uri_encode(signed_headers)
+        );
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\nUNSIGNED-PAYLOAD",
+            method,
+            url.path(),
+            query,
+            canonical_headers,
+            signed_headers
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            timestamp,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())
+        );
+
+        let date_key = hmac_sha256(format!("AWS4{}", self.secret_access_key).as_bytes(), &date);
+        let region_key = hmac_sha256(&date_key, &self.region);
+        let service_key = hmac_sha256(&region_key, "s3");
+        let signing_key = hmac_sha256(&service_key, "aws4_request");
+        let signature: String = hmac_sha256(&signing_key, &string_to_sign)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        let mut presigned = url.clone();
+        presigned.set_query(Some(&format!("{}&X-Amz-Signature={}", query, signature)));
+        presigned.to_string()
+    }
+}
+
+/// Stages uploads in an S3-compatible bucket. Clients upload to presigned URLs, so the files never
+/// pass through the server until they are finalized.
+pub struct S3UploadStorage {
+    client: reqwest::Client,
+    signer: S3Signer,
+    /// The URL of the bucket, ending with a slash.
+    bucket_url: Url,
+    /// The uploads taken by this server and when, so each is taken only once.
+    taken: Mutex<HashMap<String, Instant>>,
+}
+
+impl S3UploadStorage {
+    /// Creates a storage from the environment.
+    ///
+    /// Reads `S3_ENDPOINT` (e.g. `https://s3.eu-central-1.amazonaws.com`), `S3_REGION`,
+    /// `S3_BUCKET`, `S3_ACCESS_KEY_ID` and `S3_SECRET_ACCESS_KEY`. Objects are addressed
+    /// path-style and stored under the `staged/` prefix.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the storage, or an `EnvironmentVariableError` if a variable is
+    /// missing or invalid.
+    pub fn from_env() -> Result<Self, CustomError> {
+        let endpoint = var("S3_ENDPOINT")?;
+        let bucket = var("S3_BUCKET")?;
+        let bucket_url = Url::parse(&format!(
+            "{}/{}/staged/",
+            endpoint.trim().trim_end_matches('/'),
+            bucket.trim()
+        ))
+        .map_err(|e| CustomError::EnvironmentVariableError(format!("S3_ENDPOINT: {}", e)))?;
+        let signer = S3Signer {
+            access_key_id: var("S3_ACCESS_KEY_ID")?,
+            secret_access_key: var("S3_SECRET_ACCESS_KEY")?,
+            region: var("S3_REGION")?,
+        };
+        let client = reqwest::Client::builder()
+            .timeout(S3_REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| CustomError::StorageError(e.to_string()))?;
+        Ok(S3UploadStorage {
+            client,
+            signer,
+            bucket_url,
+            taken: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the URL of the object stored under a key.
+    fn object_url(&self, key: &str) -> Result<Url, CustomError> {
+        self.bucket_url
+            .join(key)
+            .map_err(|e| CustomError::StorageError(e.to_string()))
+    }
+
+    /// Marks an upload as taken, forgetting uploads taken long ago.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the upload wasn't taken before.
+    fn claim(&self, key: &str) -> bool {
+        let mut taken = self
+            .taken
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        taken.retain(|_, taken_at| taken_at.elapsed() < S3_TAKEN_RETENTION);
+        taken.insert(key.to_string(), Instant::now()).is_none()
+    }
+
+    /// Releases the claim on an upload that couldn't be taken, so it can be finalized again.
+    fn release(&self, key: &str) {
+        self.taken
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(key);
+    }
+
+    /// Downloads an uploaded object.
+    async fn download(&self, url: &Url, max_bytes: usize) -> Result<Option<Vec<u8>>, CustomError> {
+        let presigned = self
+            .signer
+            .presign("GET", url, None, S3_SERVER_URL_VALIDITY, Utc::now());
+        let mut response = self
+            .client
+            .get(presigned)
+            .send()
+            .await
+            .map_err(|e| CustomError::StorageError(e.without_url().to_string()))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        response = response
+            .error_for_status()
+            .map_err(|e| CustomError::StorageError(e.without_url().to_string()))?;
+
+        let mut data = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| CustomError::StorageError(e.without_url().to_string()))?
+        {
+            data.extend_from_slice(&chunk);
+            if data.len() > max_bytes {
+                data.truncate(max_bytes + 1);
+                break;
+            }
+        }
+        Ok(Some(data))
+    }
+}
+
+impl UploadStorage for S3UploadStorage {
+    fn upload_target(
+        &self,
+        key: &str,
+        content_type: &str,
+        _token: &str,
+        expires_in: Duration,
+    ) -> Result<UploadTarget, CustomError> {
+        let url = self.object_url(key)?;
+        Ok(UploadTarget {
+            url: self
+                .signer
+                .presign("PUT", &url, Some(content_type), expires_in, Utc::now()),
+            method: "PUT",
+            headers: BTreeMap::from([("Content-Type".to_string(), content_type.to_string())]),
+        })
+    }
+
+    fn take<'a>(
+        &'a self,
+        key: &'a str,
+        max_bytes: usize,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>, CustomError>> {
+        Box::pin(async move {
+            if !self.claim(key) {
+                return Ok(None);
+            }
+            let url = self.object_url(key)?;
+            let data = match self.download(&url, max_bytes).await {
+                Ok(Some(data)) => data,
+                other => {
+                    self.release(key);
+                    return other;
+                }
+            };
+
+            // A leftover object is removed by the bucket's lifecycle rule
+            let presigned =
+                self.signer
+                    .presign("DELETE", &url, None, S3_SERVER_URL_VALIDITY, Utc::now());
+            if let Err(e) = self
+                .client
+                .delete(presigned)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+            {
+                tracing::warn!(
+                    "Failed to delete staged upload {}: {}",
+                    key,
+                    e.without_url()
+                );
+            }
+            Ok(Some(data))
+        })
+    }
+
+    fn receives_uploads(&self) -> bool {
+        false
+    }
+}
+
+/// Creates the upload storage configured in the environment.
+///
+/// Uses S3 if `UPLOAD_STORAGE` is `s3`. Otherwise, or if the S3 configuration is invalid, the
+/// error is logged and uploads are staged locally.
+pub fn upload_storage_from_env() -> Arc<dyn UploadStorage> {
+    if !var("UPLOAD_STORAGE").is_ok_and(|storage| storage.trim() == "s3") {
+        tracing::warn!("UPLOAD_STORAGE is not s3, direct uploads are sent through the server");
+        return Arc::new(LocalUploadStorage);
+    }
+    match S3UploadStorage::from_env() {
+        Ok(storage) => Arc::new(storage),
+        Err(e) => {
+            tracing::error!(
+                "Invalid S3 configuration, direct uploads are sent through the server: {}",
+                e
+            );
+            Arc::new(LocalUploadStorage)
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! src/watermark.rs
+//!
+//! This module overlays the watermark of listing photos: the shop name and the offer's ID, set in
+//! a small bitmap font in the bottom-left corner of the thumbnails. It deters scammers from
+//! reusing the photos of real offers on other platforms. Watermarking is off unless
+//! `WATERMARK_IMAGES` is `true`. Uploaded originals are never changed, as their files are shared
+//! by every offer showing the same image.
+
+use dotenvy::var;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::ascii::FONT_5X8;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use image::{Rgba, RgbaImage};
+use std::convert::Infallible;
+
+/// The shop name shown above the offer's ID.
+pub const WATERMARK_SHOP_NAME: &str = "gameshop";
+
+/// How opaque the text is, from 0 (invisible) to 255.
+const TEXT_OPACITY: u16 = 140;
+
+/// The distance of the text from the left and bottom edge, in pixels.
+const MARGIN: i32 = 4;
+
+/// Returns whether thumbnails are watermarked (`WATERMARK_IMAGES`, defaults to `false`).
+///
+/// The variable is read on every call, so a configuration reload applies to the next thumbnail.
+pub fn watermark_enabled() -> bool {
+    var("WATERMARK_IMAGES").is_ok_and(|enabled| enabled.trim() == "true")
+}
+
+/// An image the text is blended onto.
+struct Canvas<'a>(&'a mut RgbaImage);
+
+impl OriginDimensions for Canvas<'_> {
+    fn size(&self) -> Size {
+        Size::new(self.0.width(), self.0.height())
+    }
+}
+
+impl DrawTarget for Canvas<'_> {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y)) else {
+                continue;
+            };
+            // Text running past the edge is cut off
+            let Some(pixel) = self.0.get_pixel_mut_checked(x, y) else {
+                continue;
+            };
+            let Rgba([red, green, blue, alpha]) = *pixel;
+            let blend = |under: u8, over: u8| {
+                ((u16::from(under) * (255 - TEXT_OPACITY) + u16::from(over) * TEXT_OPACITY) / 255)
+                    as u8
+            };
+            *pixel = Rgba([
+                blend(red, color.r()),
+                blend(green, color.g()),
+                blend(blue, color.b()),
+                alpha.max(TEXT_OPACITY as u8),
+            ]);
+        }
+        Ok(())
+    }
+}
+
+/// Overlays the watermark of an offer on an image.
+///
+/// # Arguments
+///
+/// * `image` - The image, usually a thumbnail.
+/// * `offer_id` - The ID of the offer the image belongs to.
+pub fn apply_watermark(image: &mut RgbaImage, offer_id: &str) {
+    let line_height = FONT_5X8.character_size.height as i32;
+    let bottom = image.height() as i32 - MARGIN;
+    let shadow = MonoTextStyle::new(&FONT_5X8, Rgb888::BLACK);
+    let text = MonoTextStyle::new(&FONT_5X8, Rgb888::WHITE);
+    let mut canvas = Canvas(image);
+    for (line, content) in [offer_id, WATERMARK_SHOP_NAME].into_iter().enumerate() {
+        let origin = Point::new(MARGIN, bottom - line_height * (line as i32 + 1));
+        // The shadow keeps the text readable on light photos
+        let Ok(_) = Text::with_baseline(content, origin + Point::new(1, 1), shadow, Baseline::Top)
+            .draw(&mut canvas);
+        let Ok(_) = Text::with_baseline(content, origin, text, Baseline::Top).draw(&mut canvas);
+    }
+}
@@ -3,6 +3,15 @@
 document.addEventListener('DOMContentLoaded', async () => {
     const gameListingsContainer = document.getElementById('game-listings');
     const loadingIndicator = document.getElementById('loading-indicator');
+    const categoryFilter = document.getElementById('filter-category');
+    const genreFilter = document.getElementById('filter-genre');
+    const regionFilter = document.getElementById('filter-region');
+    const languageFilter = document.getElementById('filter-language');
+    const authenticatedFilter = document.getElementById('filter-authenticated');
+    const searchInput = document.getElementById('search-query');
+    const platformFilter = document.getElementById('filter-platform');
+    const minPriceFilter = document.getElementById('filter-min-price');
+    const maxPriceFilter = document.getElementById('filter-max-price');
 
     // Function to show a message box (reusing the pattern from sell.js)
     function showMessageBox(title, message, isSuccess = true) {
@@ -60,27 +69,61 @@ document.addEventListener('DOMContentLoaded', async () => {
         }
     }
 
-    async function fetchAndDisplayOffers() {
-        loadingIndicator.classList.remove('hidden');
-        gameListingsContainer.innerHTML = ''; // Clear previous listings
+    // Links from the sitemaps open a single offer (?offer=) or a seller's offers (?seller=)
+    const pageParams = new URLSearchParams(window.location.search);
+    const linkedOfferId = pageParams.get('offer');
+    const linkedSellerId = pageParams.get('seller');
+
+    // The next page to load, or null once the last page has been loaded
+    let nextPage = 1;
+    let isLoading = false;
 
-        const token = localStorage.getItem('jwt');
+    async function fetchAndDisplayOffers(reset = true) {
+        if (reset) {
+            nextPage = 1;
+            gameListingsContainer.innerHTML = ''; // Clear previous listings
+        }
+        if (isLoading || nextPage === null) return;
+        isLoading = true;
+        loadingIndicator.classList.remove('hidden');
 
         try {
-            const response = await fetch('/api/offers', {
+            const params = new URLSearchParams();
+            // Full-text search is ordered by relevance and ignores the filters
+            const searchQuery = searchInput.value.trim();
+            if (searchQuery) params.set('q', searchQuery);
+            if (categoryFilter.value) params.set('category', categoryFilter.value);
+            if (genreFilter.value) params.set('genre', genreFilter.value);
+            if (regionFilter.value) params.set('region', regionFilter.value);
+            if (languageFilter.value) params.set('language', languageFilter.value);
+            if (authenticatedFilter.value) params.set('authenticated', authenticatedFilter.value);
+            if (platformFilter.value.trim()) params.set('platform', platformFilter.value.trim());
+            if (minPriceFilter.value) params.set('min_price', minPriceFilter.value);
+            if (maxPriceFilter.value) params.set('max_price', maxPriceFilter.value);
+            if (linkedSellerId) params.set('seller', linkedSellerId);
+            params.set('page', nextPage);
+
+            const endpoint = searchQuery ? '/api/offers/search' : '/api/offers';
+            const url = linkedOfferId
+                ? `/api/offers/${encodeURIComponent(linkedOfferId)}`
+                : `${endpoint}?${params.toString()}`;
+            const response = await fetch(url, {
                 method: 'GET',
                 headers: {
-                    'Content-Type': 'application/json',
-                    // Include Authorization header if the route is protected
-                    ...(token && { 'Authorization': `Bearer ${token}` })
-                }
+                    'Content-Type': 'application/json'
+                },
+                // Send the auth cookie in case the route is protected
+                credentials: 'same-origin'
             });
 
             const result = await response.json();
 
             if (response.ok) {
-                if (result.offers && result.offers.length > 0) {
-                    result.offers.forEach(offer => {
+                nextPage = result.pagination ? result.pagination.next_page : null;
+                // A single offer is shown as a one-card page
+                if (linkedOfferId && result.data) result.data = [result.data];
+                if (result.data && result.data.length > 0) {
+                    result.data.forEach(offer => {
                         const offerCard = document.createElement('div');
                         offerCard.className = 'bg-white p-6 rounded-xl shadow-lg hover:shadow-xl transition-shadow duration-300 ease-in-out flex flex-col';
 
@@ -90,11 +133,17 @@ document.addEventListener('DOMContentLoaded', async () => {
                             : 'N/A';
                         const formattedPrice = typeof offer.price === 'number' ? `$${offer.price.toFixed(2)}` : 'N/A';
                         const formattedCreatedAt = offer.created_at ? formatDateTime(offer.created_at) : 'N/A';
+                        // Prefer the small thumbnail of an uploaded image over the full-size file
+                        const uploadedImage = offer.images && offer.images.length > 0 ? offer.images[0] : null;
+                        const imageUrl = uploadedImage
+                            ? (uploadedImage.thumbnail_url || uploadedImage.url)
+                            : (offer.photos && offer.photos.length > 0 ? offer.photos[0].url : 'https://placehold.co/400x250/FFB400/23272F?text=Game+Image');
 
                         offerCard.innerHTML = `
-                            <img src="https://placehold.co/400x250/FFB400/23272F?text=Game+Image" alt="${offer.game_title}" class="w-full h-48 object-cover object-center rounded-t-xl" onerror="this.onerror=null;this.src='https://placehold.co/400x250/FFB400/23272F?text=Image+Error';">
+                            <img src="${imageUrl}" alt="" class="w-full h-48 object-cover object-center rounded-t-xl" onerror="this.onerror=null;this.src='https://placehold.co/400x250/FFB400/23272F?text=Image+Error';">
                             <div class="p-6 flex flex-col flex-grow">
                                 <h3 class="text-2xl font-bold text-gray-900 mb-2 truncate">${offer.game_title}</h3>
+                                ${offer.authenticated ? '<span class="self-start bg-green-100 text-green-800 text-xs font-semibold px-3 py-1 rounded-full mb-2">&#10003; Authenticated</span>' : ''}
                                 <div class="flex flex-col text-left mb-2">
                                     <p class="text-gray-700 font-medium flex items-baseline"><span class="flex-shrink-0 w-20">Platform:</span> <span class="font-normal flex-grow">${offer.platform}</span></p>
                                     <p class="text-gray-700 font-medium flex items-baseline"><span class="flex-shrink-0 w-20">Condition:</span> <span class="font-normal flex-grow">${offer.condition}</span></p>
@@ -110,30 +159,66 @@ document.addEventListener('DOMContentLoaded', async () => {
                                 </button>
                             </div>
                         `;
+                        // Set as a property, so the seller's text can't break out of the attribute
+                        offerCard.querySelector('img').alt = (uploadedImage && uploadedImage.alt_text) || offer.game_title;
                         gameListingsContainer.appendChild(offerCard);
                     });
-                } else {
+                } else if (gameListingsContainer.children.length === 0) {
                     gameListingsContainer.innerHTML = `
                         <p class="col-span-full text-center text-gray-600 text-xl py-8">No games listed yet. Be the first to sell one!</p>
                     `;
                 }
             } else {
-                showMessageBox('Error', result.message || 'Failed to load games. Please try again.', false);
+                showMessageBox('Error', (result.error && result.error.message) || 'Failed to load games. Please try again.', false);
             }
         } catch (error) {
             console.error('Error fetching offers:', error);
             showMessageBox('Network Error', 'Could not connect to the server. Please check your internet connection and try again.', false);
         } finally {
+            isLoading = false;
             loadingIndicator.classList.add('hidden');
         }
     }
 
+    // Load the next page when the user scrolls near the bottom
+    function handleScroll() {
+        if (window.innerHeight + window.scrollY >= document.body.offsetHeight - 300) {
+            fetchAndDisplayOffers(false);
+        }
+    }
+
     // Initial fetch of games when the page loads
     fetchAndDisplayOffers();
 
-    // The infinite scrolling logic will need to be adapted if your backend
-    // supports paginated results. For now, this fetches all offers at once.
-    // If you implement backend pagination, you'd modify fetchAndDisplayOffers
-    // to take page/limit parameters and call it on scroll.
-    // window.addEventListener('scroll', handleScroll); // Uncomment and implement if backend pagination is added.
+    // Refetch whenever a filter changes
+    categoryFilter.addEventListener('change', () => fetchAndDisplayOffers());
+    genreFilter.addEventListener('change', () => fetchAndDisplayOffers());
+
+    // Fill the genre filter from the curated taxonomy
+    fetch('/api/categories')
+        .then(response => response.json())
+        .then(result => {
+            ((result.data && result.data.genres) || []).forEach(genre => {
+                const option = document.createElement('option');
+                option.value = genre.id;
+                option.textContent = genre.label;
+                genreFilter.appendChild(option);
+            });
+        })
+        .catch(error => console.error('Error loading genres:', error));
+    regionFilter.addEventListener('change', () => fetchAndDisplayOffers());
+    languageFilter.addEventListener('change', () => fetchAndDisplayOffers());
+    authenticatedFilter.addEventListener('change', () => fetchAndDisplayOffers());
+    platformFilter.addEventListener('change', () => fetchAndDisplayOffers());
+    minPriceFilter.addEventListener('change', () => fetchAndDisplayOffers());
+    maxPriceFilter.addEventListener('change', () => fetchAndDisplayOffers());
+
+    // Search once the user stops typing
+    let searchTimeout;
+    searchInput.addEventListener('input', () => {
+        clearTimeout(searchTimeout);
+        searchTimeout = setTimeout(() => fetchAndDisplayOffers(), 300);
+    });
+
+    window.addEventListener('scroll', handleScroll);
 });
@@ -15,6 +15,7 @@ document.addEventListener('DOMContentLoaded', function () {
         headers: {
           'Content-Type': 'application/json'
         },
+        credentials: 'same-origin',
         body: JSON.stringify({ email, password })
       });
 
@@ -22,7 +23,7 @@ document.addEventListener('DOMContentLoaded', function () {
         let errorMsg = 'Login failed';
         try {
           const error = await response.json();
-          errorMsg = error.message || errorMsg;
+          errorMsg = (error.error && error.error.message) || errorMsg;
         } catch { }
         messageDiv.textContent = errorMsg;
         messageDiv.classList.remove('hidden'); // Ensure message is visible
@@ -31,15 +32,7 @@ document.addEventListener('DOMContentLoaded', function () {
       }
 
       const data = await response.json();
-      const jwt = data.token || data.jwt; // adjust according to your API's response
-      const username = data.username || data.username;
-
-      if (!jwt) {
-        messageDiv.textContent = 'Login failed: No token received.';
-        messageDiv.classList.remove('hidden');
-        messageDiv.classList.add('text-red-600');
-        return;
-      }
+      const username = data.data && data.data.username;
 
       if (!username) {
         messageDiv.textContent = 'Login failed: No username.';
@@ -48,9 +41,7 @@ document.addEventListener('DOMContentLoaded', function () {
         return;
       }
 
-      // Store JWT in localStorage
-      localStorage.setItem('jwt', jwt);
-
+      // The JWT is kept in the HttpOnly auth cookie, only the username is stored for the UI
       localStorage.setItem('username', username);
 
       messageDiv.textContent = 'Login successful!';
@@ -20,9 +20,10 @@ document.addEventListener('DOMContentLoaded', function () {
     });
 
     // 2) Auth‐specific links
-    const jwt = localStorage.getItem('jwt');
+    // The JWT lives in the HttpOnly auth cookie, the stored username marks a logged in user
+    const username = localStorage.getItem('username');
 
-    if (jwt) {
+    if (username) {
         // Profile icon link
         const profileLink = document.createElement('a');
         profileLink.href = '/web/profile.html';
@@ -43,8 +44,13 @@ document.addEventListener('DOMContentLoaded', function () {
         // Tailwind classes for logout link
         logoutLink.className = 'text-white hover:text-yellow-500 transition duration-200 ease-in-out px-3 py-2 rounded-md';
         logoutLink.textContent = 'Logout';
-        logoutLink.addEventListener('click', function (e) {
+        logoutLink.addEventListener('click', async function (e) {
             e.preventDefault();
+            // Clear the HttpOnly auth cookie as well, it can't be removed from JavaScript
+            try {
+                await fetch('/auth/logout', { method: 'POST', credentials: 'same-origin' });
+            } catch { }
+            // Tokens stored by older versions of the frontend
             localStorage.removeItem('jwt');
             localStorage.removeItem('username');
             window.location.reload();
@@ -1,12 +1,16 @@
 document.addEventListener('DOMContentLoaded', function () {
-    const jwt = localStorage.getItem('jwt');
     const username = localStorage.getItem('username');
-    if (!jwt || !username) {
+    if (!username) {
         window.location.href = '/web/login.html';
         return;
     }
     document.getElementById('profile-username').textContent = username;
-    document.getElementById('logout-btn').addEventListener('click', () => {
+    document.getElementById('logout-btn').addEventListener('click', async () => {
+        // Clear the HttpOnly auth cookie, it can't be removed from JavaScript
+        try {
+            await fetch('/auth/logout', { method: 'POST', credentials: 'same-origin' });
+        } catch { }
+        // Tokens stored by older versions of the frontend
         localStorage.removeItem('jwt');
         localStorage.removeItem('username');
         window.location.href = '/web/index.html';
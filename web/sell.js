@@ -7,6 +7,12 @@ document.addEventListener('DOMContentLoaded', () => {
     const conditionSelect = document.getElementById('condition');
     const priceInput = document.getElementById('price');
     const descriptionTextarea = document.getElementById('description');
+    const regionSelect = document.getElementById('region');
+    const boxLanguageSelect = document.getElementById('box_language');
+    const manualLanguageSelect = document.getElementById('manual_language');
+    const ageRatingSelect = document.getElementById('age_rating');
+    const imagesInput = document.getElementById('images');
+    const imageAltTextInput = document.getElementById('imageAltText');
 
     // Message box elements
     const messageBox = document.createElement('div');
@@ -76,11 +82,9 @@ document.addEventListener('DOMContentLoaded', () => {
             return;
         }
 
-        const token = localStorage.getItem('jwt');
-
-        if (!token) {
+        if (!localStorage.getItem('username')) {
             showMessageBox('Authentication Error', 'You must be logged in to list a game.', false);
-            // Redirect to login page if no token
+            // Redirect to login page if not logged in
             setTimeout(() => {
                 window.location.href = '/web/login.html';
             }, 2000);
@@ -93,26 +97,55 @@ document.addEventListener('DOMContentLoaded', () => {
             condition: condition,
             price: price,
             description: description,
+            // Optional collector metadata; omitted fields are left unset
+            ...(regionSelect.value && { region: regionSelect.value }),
+            ...(boxLanguageSelect.value && { box_language: boxLanguageSelect.value }),
+            ...(manualLanguageSelect.value && { manual_language: manualLanguageSelect.value }),
+            ...(ageRatingSelect.value && { age_rating: ageRatingSelect.value }),
         };
 
         try {
             const response = await fetch('/api/offers', {
                 method: 'POST',
                 headers: {
-                    'Content-Type': 'application/json',
-                    'Authorization': `Bearer ${token}`
+                    'Content-Type': 'application/json'
                 },
+                credentials: 'same-origin',
                 body: JSON.stringify(offerData)
             });
 
             const result = await response.json();
 
+            if (response.ok && imagesInput.files.length > 0) {
+                const offerId = result.data.id.id.String;
+                const formData = new FormData();
+                const altText = imageAltTextInput.value.trim();
+                for (const file of imagesInput.files) {
+                    formData.append('images', file);
+                    // The description applies to the photo right before it
+                    if (altText) {
+                        formData.append('alt_text', altText);
+                    }
+                }
+                const uploadResponse = await fetch(`/api/offers/${offerId}/images`, {
+                    method: 'POST',
+                    credentials: 'same-origin',
+                    body: formData
+                });
+                if (!uploadResponse.ok) {
+                    const uploadResult = await uploadResponse.json();
+                    showMessageBox('Photos Not Uploaded', `Your game was listed, but the photos could not be uploaded: ${uploadResult.error ? uploadResult.error.message : 'Unknown error.'}`, false);
+                    form.reset();
+                    return;
+                }
+            }
+
             if (response.ok) {
                 showMessageBox('Success!', result.message || 'Game listed successfully!', true);
                 // Clear the form after successful submission
                 form.reset();
             } else {
-                showMessageBox('Error', result.message || 'Failed to list game. Please try again.', false);
+                showMessageBox('Error', (result.error && result.error.message) || 'Failed to list game. Please try again.', false);
             }
         } catch (error) {
             console.error('Error listing game:', error);
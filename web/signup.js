@@ -11,13 +11,15 @@ document.addEventListener('DOMContentLoaded', function () {
     const username = form.username.value;
     const email = form.email.value;
     const password = form.password.value;
+    const date_of_birth = form.date_of_birth.value;
     // Add other fields as needed (e.g., username, but do not expect it back)
 
     try {
       const response = await fetch('/auth/register', {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ firstname, lastname, username, email, password })
+        credentials: 'same-origin',
+        body: JSON.stringify({ firstname, lastname, username, email, password, date_of_birth })
       });
 
 
@@ -25,7 +27,7 @@ document.addEventListener('DOMContentLoaded', function () {
         let errorMsg = 'Signup failed';
         try {
           const error = await response.json();
-          errorMsg = error.message || errorMsg;
+          errorMsg = (error.error && error.error.message) || errorMsg;
         } catch { }
         messageDiv.textContent = errorMsg;
         messageDiv.classList.remove('hidden'); // Ensure message is visible
@@ -33,19 +35,7 @@ document.addEventListener('DOMContentLoaded', function () {
         return;
       }
 
-      const data = await response.json();
-      const jwt = data.token || data.jwt;
-
-      if (!jwt) {
-        messageDiv.textContent = 'Signup failed: No token received.';
-        messageDiv.classList.remove('hidden');
-        messageDiv.classList.add('text-red-600');
-        return;
-      }
-
-      // Store JWT in localStorage
-      localStorage.setItem('jwt', jwt);
-
+      // The JWT is kept in the HttpOnly auth cookie, only the username is stored for the UI
       localStorage.setItem('username', username);
 
       messageDiv.textContent = 'Signup successful!';